@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS menu_item_schedules;
+
+ALTER TABLE canteens
+    DROP COLUMN last_stock_reset_on;
+
+ALTER TABLE menu_items
+    DROP COLUMN default_stock;
@@ -0,0 +1,18 @@
+ALTER TABLE menu_items
+    ADD COLUMN default_stock INTEGER CHECK (default_stock IS NULL OR default_stock >= -1);
+
+ALTER TABLE canteens
+    ADD COLUMN last_stock_reset_on DATE;
+
+-- weekday follows ISO order starting at 0 = Monday.
+-- A window whose end_time is before start_time runs past midnight into the next day.
+CREATE TABLE menu_item_schedules (
+    schedule_id SERIAL PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    CHECK (start_time <> end_time)
+);
+
+CREATE INDEX idx_menu_item_schedules_item_id ON menu_item_schedules(item_id);
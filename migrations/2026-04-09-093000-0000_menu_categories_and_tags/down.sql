@@ -0,0 +1,10 @@
+DROP INDEX IF EXISTS idx_menu_items_tags;
+DROP INDEX IF EXISTS idx_menu_items_category_id;
+
+ALTER TABLE menu_items
+    DROP COLUMN tags,
+    DROP COLUMN is_pinned,
+    DROP COLUMN display_order,
+    DROP COLUMN category_id;
+
+DROP TABLE IF EXISTS menu_categories;
@@ -0,0 +1,18 @@
+CREATE TABLE menu_categories (
+    category_id SERIAL PRIMARY KEY,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    display_order INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (canteen_id, name)
+);
+
+CREATE INDEX idx_menu_categories_canteen_order ON menu_categories(canteen_id, display_order);
+
+ALTER TABLE menu_items
+    ADD COLUMN category_id INTEGER REFERENCES menu_categories(category_id) ON DELETE SET NULL,
+    ADD COLUMN display_order INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN is_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_menu_items_category_id ON menu_items(category_id);
+CREATE INDEX idx_menu_items_tags ON menu_items USING GIN (tags);
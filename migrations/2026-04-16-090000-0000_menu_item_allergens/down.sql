@@ -0,0 +1,6 @@
+ALTER TABLE users
+    DROP COLUMN IF EXISTS allergen_exclusions;
+
+ALTER TABLE menu_items
+    DROP COLUMN IF EXISTS diet_labels,
+    DROP COLUMN IF EXISTS allergens;
@@ -0,0 +1,6 @@
+ALTER TABLE menu_items
+    ADD COLUMN allergens TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN diet_labels TEXT[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE users
+    ADD COLUMN allergen_exclusions TEXT[] NOT NULL DEFAULT '{}';
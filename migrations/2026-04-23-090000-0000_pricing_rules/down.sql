@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS pricing_rule_windows;
+DROP TABLE IF EXISTS pricing_rules;
+DROP TYPE IF EXISTS pricing_rule_kind;
@@ -0,0 +1,31 @@
+CREATE TYPE pricing_rule_kind AS ENUM ('percent_off', 'flat_off', 'combo');
+
+-- percent_off and flat_off discount every unit of the listed items (every item on the
+-- menu when item_ids is empty). combo sells one unit of each listed item for combo_price;
+-- an item listed twice needs two units.
+CREATE TABLE pricing_rules (
+    rule_id SERIAL PRIMARY KEY,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    kind pricing_rule_kind NOT NULL,
+    percent_off INTEGER CHECK (percent_off BETWEEN 1 AND 100),
+    amount_off INTEGER CHECK (amount_off > 0),
+    combo_price INTEGER CHECK (combo_price >= 0),
+    item_ids INTEGER[] NOT NULL DEFAULT '{}',
+    is_active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE INDEX idx_pricing_rules_canteen_id ON pricing_rules(canteen_id);
+
+-- Happy-hour windows, same semantics as menu_item_schedules.
+-- A rule without windows applies at all times.
+CREATE TABLE pricing_rule_windows (
+    window_id SERIAL PRIMARY KEY,
+    rule_id INTEGER NOT NULL REFERENCES pricing_rules(rule_id) ON DELETE CASCADE,
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    CHECK (start_time <> end_time)
+);
+
+CREATE INDEX idx_pricing_rule_windows_rule_id ON pricing_rule_windows(rule_id);
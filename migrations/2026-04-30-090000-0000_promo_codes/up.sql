@@ -0,0 +1,38 @@
+-- canteen_id NULL marks a platform-wide code, valid at every canteen.
+-- Exactly one of percent_off and amount_off is set; max_discount caps percent codes.
+-- NULL usage limits and validity bounds mean unlimited.
+CREATE TABLE promo_codes (
+    promo_id SERIAL PRIMARY KEY,
+    code VARCHAR NOT NULL UNIQUE,
+    canteen_id INTEGER REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    description VARCHAR,
+    percent_off INTEGER CHECK (percent_off BETWEEN 1 AND 100),
+    amount_off INTEGER CHECK (amount_off > 0),
+    max_discount INTEGER CHECK (max_discount > 0),
+    min_order_total INTEGER NOT NULL DEFAULT 0 CHECK (min_order_total >= 0),
+    valid_from TIMESTAMPTZ,
+    valid_until TIMESTAMPTZ,
+    max_uses_total INTEGER CHECK (max_uses_total > 0),
+    max_uses_per_user INTEGER CHECK (max_uses_per_user > 0),
+    first_order_only BOOLEAN NOT NULL DEFAULT FALSE,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((percent_off IS NULL) <> (amount_off IS NULL))
+);
+
+CREATE INDEX idx_promo_codes_canteen_id ON promo_codes(canteen_id);
+
+-- A redemption is tied to its hold while the hold is pending, so releasing or expiring
+-- the hold deletes it and gives the usage back. Confirming the hold moves it to the order.
+CREATE TABLE promo_redemptions (
+    redemption_id SERIAL PRIMARY KEY,
+    promo_id INTEGER NOT NULL REFERENCES promo_codes(promo_id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    hold_id INTEGER UNIQUE REFERENCES held_orders(hold_id) ON DELETE CASCADE,
+    order_id INTEGER,
+    discount INTEGER NOT NULL,
+    redeemed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (hold_id IS NOT NULL OR order_id IS NOT NULL)
+);
+
+CREATE INDEX idx_promo_redemptions_promo_user ON promo_redemptions(promo_id, user_id);
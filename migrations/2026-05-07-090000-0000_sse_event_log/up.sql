@@ -0,0 +1,11 @@
+-- Durable copy of the SSE replay buffer, so clients can resume with Last-Event-ID across
+-- restarts. Each stream (kind + id) keeps its own sequence and only its most recent events.
+CREATE TABLE sse_events (
+    stream_kind VARCHAR NOT NULL,
+    stream_id INTEGER NOT NULL,
+    seq BIGINT NOT NULL,
+    event_name VARCHAR NOT NULL,
+    data TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (stream_kind, stream_id, seq)
+);
@@ -0,0 +1,11 @@
+-- Push notification tokens of users' devices. A token belongs to whichever user registered
+-- it last, since devices can change hands between sign-ins.
+CREATE TABLE device_tokens (
+    token VARCHAR PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    platform VARCHAR NOT NULL CHECK (platform IN ('android', 'ios', 'web')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX device_tokens_user_id_idx ON device_tokens (user_id);
@@ -0,0 +1,20 @@
+-- Emails waiting to be sent. Rows are written in the same transaction as the change they
+-- report on and sent afterwards, so a mail server outage never holds up or rolls back orders.
+CREATE TABLE email_outbox (
+    email_id SERIAL PRIMARY KEY,
+    -- Keeps a retried confirmation from queueing the same email twice, e.g. receipt:<order_id>.
+    dedupe_key VARCHAR NOT NULL UNIQUE,
+    user_id INTEGER REFERENCES users(user_id) ON DELETE CASCADE,
+    recipient VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    body_text TEXT NOT NULL,
+    body_html TEXT NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX email_outbox_due_idx ON email_outbox (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,15 @@
+-- Side effects of state changes (SSE events, pushes, webhooks), written in the same transaction
+-- as the change and delivered at least once by the outbox dispatcher. Rows are deleted once
+-- delivered; rows that can never be delivered stay behind with failed_at set.
+CREATE TABLE outbox (
+    outbox_id BIGSERIAL PRIMARY KEY,
+    topic VARCHAR NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    failed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX outbox_due_idx ON outbox (next_attempt_at, outbox_id) WHERE failed_at IS NULL;
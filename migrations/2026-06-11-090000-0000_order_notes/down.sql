@@ -0,0 +1,2 @@
+ALTER TABLE active_orders DROP COLUMN IF EXISTS note;
+ALTER TABLE held_orders DROP COLUMN IF EXISTS note;
@@ -0,0 +1,3 @@
+-- A note from the customer for the kitchen, carried from the hold to the order.
+ALTER TABLE held_orders ADD COLUMN note VARCHAR(200);
+ALTER TABLE active_orders ADD COLUMN note VARCHAR(200);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS menu_items_search_document_idx;
@@ -0,0 +1,9 @@
+-- Full-text search over item names and descriptions. The expression has to match
+-- `MENU_SEARCH_DOCUMENT` in src/db/common/search.rs for the index to be used.
+CREATE INDEX menu_items_search_document_idx
+ON menu_items
+USING GIN ((
+    setweight(to_tsvector('english', name), 'A')
+    || setweight(to_tsvector('english', coalesce(description, '')), 'B')
+));
+
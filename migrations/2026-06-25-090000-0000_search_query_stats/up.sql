@@ -0,0 +1,17 @@
+-- Anonymised log of menu searches: how often each normalised query was searched per day and
+-- how often it found nothing, without anything that identifies who searched. Feeds search
+-- suggestions and the search insights shown to canteen admins.
+CREATE TABLE search_query_stats (
+    stat_id BIGSERIAL PRIMARY KEY,
+    searched_on DATE NOT NULL,
+    query VARCHAR(100) NOT NULL,
+    -- The canteen the search was limited to, if any.
+    canteen_id INTEGER REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    search_count INTEGER NOT NULL DEFAULT 0,
+    no_result_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE UNIQUE INDEX search_query_stats_key_idx
+ON search_query_stats (searched_on, query, COALESCE(canteen_id, 0));
+
+CREATE INDEX search_query_stats_query_idx ON search_query_stats (query text_pattern_ops);
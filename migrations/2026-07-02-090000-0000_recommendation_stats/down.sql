@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS item_hourly_sales;
+DROP TABLE IF EXISTS item_pair_stats;
+DROP TABLE IF EXISTS user_item_stats;
@@ -0,0 +1,58 @@
+-- Running totals kept up to date as orders are delivered, for recommendations.
+
+-- How often each user has had each item delivered: their favourites.
+CREATE TABLE user_item_stats (
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    order_count INTEGER NOT NULL DEFAULT 0,
+    quantity INTEGER NOT NULL DEFAULT 0,
+    last_ordered_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id, item_id)
+);
+
+-- How many delivered orders had both items, stored once in each direction.
+CREATE TABLE item_pair_stats (
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    paired_item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    order_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (item_id, paired_item_id)
+);
+
+-- How much of each item was delivered per day and hour of ordering (in the canteens' time
+-- zone), for what is popular at a canteen right now.
+CREATE TABLE item_hourly_sales (
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    sold_on DATE NOT NULL,
+    hour SMALLINT NOT NULL CHECK (hour BETWEEN 0 AND 23),
+    quantity INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (item_id, sold_on, hour)
+);
+
+CREATE INDEX item_hourly_sales_sold_on_idx ON item_hourly_sales (sold_on);
+
+-- Seed the per-user and pair totals from the delivered orders so far. Hourly sales start
+-- from now, as the time zone they are counted in is configured outside the database.
+WITH delivered AS (
+    SELECT p.order_id, p.user_id, p.ordered_at, i.item_id
+    FROM past_orders p
+    CROSS JOIN LATERAL unnest(p.items) AS i(item_id)
+    JOIN menu_items m ON m.item_id = i.item_id
+    WHERE p.order_status
+)
+INSERT INTO user_item_stats (user_id, item_id, order_count, quantity, last_ordered_at)
+SELECT user_id, item_id, COUNT(DISTINCT order_id), COUNT(*), MAX(ordered_at)
+FROM delivered
+GROUP BY user_id, item_id;
+
+WITH delivered AS (
+    SELECT DISTINCT p.order_id, i.item_id
+    FROM past_orders p
+    CROSS JOIN LATERAL unnest(p.items) AS i(item_id)
+    JOIN menu_items m ON m.item_id = i.item_id
+    WHERE p.order_status
+)
+INSERT INTO item_pair_stats (item_id, paired_item_id, order_count)
+SELECT a.item_id, b.item_id, COUNT(*)
+FROM delivered a
+JOIN delivered b ON a.order_id = b.order_id AND a.item_id <> b.item_id
+GROUP BY a.item_id, b.item_id;
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS saved_cart_items;
+DROP TABLE IF EXISTS saved_carts;
+DROP TABLE IF EXISTS favourite_canteens;
+DROP TABLE IF EXISTS favourite_items;
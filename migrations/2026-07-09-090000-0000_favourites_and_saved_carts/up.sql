@@ -0,0 +1,40 @@
+-- Menu items and canteens users have marked as favourites.
+CREATE TABLE favourite_items (
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, item_id)
+);
+
+CREATE INDEX favourite_items_item_id_idx ON favourite_items (item_id);
+
+CREATE TABLE favourite_canteens (
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, canteen_id)
+);
+
+CREATE INDEX favourite_canteens_canteen_id_idx ON favourite_canteens (canteen_id);
+
+-- Named carts a user can hold again in one go, e.g. "Tuesday lunch". Like an order, a cart
+-- holds items of one canteen.
+CREATE TABLE saved_carts (
+    cart_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX saved_carts_user_id_name_idx ON saved_carts (user_id, LOWER(name));
+
+CREATE TABLE saved_cart_items (
+    cart_id INTEGER NOT NULL REFERENCES saved_carts(cart_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    quantity SMALLINT NOT NULL CHECK (quantity > 0),
+    PRIMARY KEY (cart_id, item_id)
+);
+
+CREATE INDEX saved_cart_items_item_id_idx ON saved_cart_items (item_id);
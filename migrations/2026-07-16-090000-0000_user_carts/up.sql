@@ -0,0 +1,15 @@
+-- Each user's cart at each canteen, kept on the server so it can be validated against the
+-- menu on every read and held in one go.
+CREATE TABLE cart_items (
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    quantity SMALLINT NOT NULL CHECK (quantity > 0),
+    -- The item's price when it was last added or changed, to tell the user it has changed.
+    added_price INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, canteen_id, item_id)
+);
+
+CREATE INDEX cart_items_item_id_idx ON cart_items (item_id);
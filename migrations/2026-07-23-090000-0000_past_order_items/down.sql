@@ -0,0 +1 @@
+DROP TABLE IF EXISTS past_order_items;
@@ -0,0 +1,10 @@
+-- What each item of a past order cost, so a reorder can be compared line by line. Orders
+-- finished before this table existed have no rows.
+CREATE TABLE past_order_items (
+    order_id INTEGER NOT NULL REFERENCES past_orders(order_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL,
+    quantity SMALLINT NOT NULL CHECK (quantity > 0),
+    -- Unit price paid, after pricing rules.
+    price INTEGER NOT NULL,
+    PRIMARY KEY (order_id, item_id)
+);
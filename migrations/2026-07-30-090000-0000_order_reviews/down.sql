@@ -0,0 +1,4 @@
+ALTER TABLE canteens DROP COLUMN IF EXISTS rating_count, DROP COLUMN IF EXISTS average_rating;
+ALTER TABLE menu_items DROP COLUMN IF EXISTS rating_count, DROP COLUMN IF EXISTS average_rating;
+DROP TABLE IF EXISTS item_ratings;
+DROP TABLE IF EXISTS order_reviews;
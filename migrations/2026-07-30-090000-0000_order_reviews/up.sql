@@ -0,0 +1,34 @@
+-- One review per delivered order, with optional ratings of the items in it. Hidden reviews
+-- stay visible to the canteen's admins but do not count towards average ratings.
+CREATE TABLE order_reviews (
+    review_id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL UNIQUE REFERENCES past_orders(order_id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    comment TEXT,
+    is_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    reply TEXT,
+    replied_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX order_reviews_canteen_idx ON order_reviews (canteen_id, created_at DESC);
+
+CREATE TABLE item_ratings (
+    review_id INTEGER NOT NULL REFERENCES order_reviews(review_id) ON DELETE CASCADE,
+    item_id INTEGER NOT NULL REFERENCES menu_items(item_id) ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    PRIMARY KEY (review_id, item_id)
+);
+
+CREATE INDEX item_ratings_item_idx ON item_ratings (item_id);
+
+-- Kept up to date as reviews are written or moderated, so menus need no aggregate per read.
+ALTER TABLE menu_items
+    ADD COLUMN average_rating DOUBLE PRECISION,
+    ADD COLUMN rating_count INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE canteens
+    ADD COLUMN average_rating DOUBLE PRECISION,
+    ADD COLUMN rating_count INTEGER NOT NULL DEFAULT 0;
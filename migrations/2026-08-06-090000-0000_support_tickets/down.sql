@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS refunds;
+DROP TABLE IF EXISTS support_messages;
+DROP TABLE IF EXISTS support_tickets;
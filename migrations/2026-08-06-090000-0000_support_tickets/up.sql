@@ -0,0 +1,54 @@
+-- Complaints about an order, raised by its user and answered by the canteen. Orders keep
+-- their id when they are delivered or cancelled, so `order_id` is the active or past order.
+CREATE TABLE support_tickets (
+    ticket_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    order_id INTEGER NOT NULL,
+    category VARCHAR NOT NULL
+        CHECK (category IN ('wrong_item', 'missing_item', 'quality', 'late', 'other')),
+    status VARCHAR NOT NULL DEFAULT 'open'
+        CHECK (status IN ('open', 'in_progress', 'resolved', 'closed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX support_tickets_canteen_idx ON support_tickets (canteen_id, updated_at DESC);
+CREATE INDEX support_tickets_user_idx ON support_tickets (user_id, updated_at DESC);
+-- One ticket in progress per order.
+CREATE UNIQUE INDEX support_tickets_open_order_idx ON support_tickets (order_id)
+    WHERE status IN ('open', 'in_progress');
+
+CREATE TABLE support_messages (
+    message_id SERIAL PRIMARY KEY,
+    ticket_id INTEGER NOT NULL REFERENCES support_tickets(ticket_id) ON DELETE CASCADE,
+    author VARCHAR NOT NULL CHECK (author IN ('user', 'canteen')),
+    body TEXT NOT NULL,
+    -- S3 keys under `support/{ticket_id}/`.
+    attachment_keys TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX support_messages_ticket_idx ON support_messages (ticket_id, created_at);
+
+-- Money sent back through PhonePe against a completed payment.
+CREATE TABLE refunds (
+    refund_id SERIAL PRIMARY KEY,
+    payment_id INTEGER NOT NULL REFERENCES payment_orders(payment_id),
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    ticket_id INTEGER REFERENCES support_tickets(ticket_id) ON DELETE SET NULL,
+    merchant_refund_id VARCHAR NOT NULL UNIQUE,
+    -- In paisa, like payment_orders.amount.
+    amount INTEGER NOT NULL CHECK (amount > 0),
+    -- PENDING until PhonePe settles it as COMPLETED or FAILED.
+    state VARCHAR NOT NULL DEFAULT 'PENDING',
+    -- Set once PhonePe has accepted the refund.
+    phonepe_refund_id VARCHAR,
+    failure_reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX refunds_payment_idx ON refunds (payment_id);
+CREATE INDEX refunds_canteen_idx ON refunds (canteen_id, created_at);
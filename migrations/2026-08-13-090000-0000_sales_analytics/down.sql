@@ -0,0 +1,9 @@
+DROP TABLE hold_history;
+
+DROP INDEX idx_payment_orders_canteen_created_at;
+ALTER TABLE payment_orders DROP COLUMN canteen_id;
+
+DROP INDEX idx_past_orders_canteen_ordered_at;
+ALTER TABLE past_orders
+    DROP COLUMN deliver_at,
+    DROP COLUMN canteen_id;
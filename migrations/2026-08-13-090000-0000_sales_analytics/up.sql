@@ -0,0 +1,45 @@
+-- Where and for which slot each past order was placed, so sales can be reported per canteen.
+-- Orders finished before these columns existed get the canteen of their items where it can
+-- still be found, and no slot.
+ALTER TABLE past_orders
+    ADD COLUMN canteen_id INTEGER REFERENCES canteens(canteen_id) ON DELETE SET NULL,
+    ADD COLUMN deliver_at time_band;
+
+UPDATE past_orders p
+SET canteen_id = (
+    SELECT m.canteen_id
+    FROM menu_items m
+    WHERE m.item_id = ANY(p.items)
+    LIMIT 1
+);
+
+CREATE INDEX idx_past_orders_canteen_ordered_at ON past_orders (canteen_id, ordered_at);
+
+-- The canteen a payment was for; payments for holds that are long gone are attributed through
+-- the order they became, if any.
+ALTER TABLE payment_orders
+    ADD COLUMN canteen_id INTEGER REFERENCES canteens(canteen_id) ON DELETE SET NULL;
+
+UPDATE payment_orders po
+SET canteen_id = COALESCE(
+    (SELECT h.canteen_id FROM held_orders h WHERE h.hold_id = po.hold_id),
+    (SELECT a.canteen_id FROM active_orders a WHERE a.order_id = po.app_order_id),
+    (SELECT p.canteen_id FROM past_orders p WHERE p.order_id = po.app_order_id)
+);
+
+CREATE INDEX idx_payment_orders_canteen_created_at ON payment_orders (canteen_id, created_at);
+
+-- How each hold ended, recorded when it is removed from held_orders.
+CREATE TABLE hold_history (
+    hold_id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    total_price INTEGER NOT NULL,
+    held_at TIMESTAMPTZ NOT NULL,
+    outcome VARCHAR NOT NULL CHECK (outcome IN ('confirmed', 'released', 'expired')),
+    -- The order a confirmed hold became.
+    order_id INTEGER,
+    ended_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_hold_history_canteen_held_at ON hold_history (canteen_id, held_at);
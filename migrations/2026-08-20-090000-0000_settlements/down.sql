@@ -0,0 +1,3 @@
+DROP TRIGGER trigger_settlements_frozen ON settlements;
+DROP FUNCTION settlements_frozen();
+DROP TABLE settlements;
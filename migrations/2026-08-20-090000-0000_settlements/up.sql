@@ -0,0 +1,45 @@
+-- What each canteen is owed for a business day out of the money PhonePe collected for it.
+-- A statement is frozen once generated; only its payout can be recorded afterwards.
+CREATE TABLE settlements (
+    settlement_id SERIAL PRIMARY KEY,
+    canteen_id INTEGER NOT NULL REFERENCES canteens(canteen_id) ON DELETE CASCADE,
+    business_date DATE NOT NULL,
+    -- Amounts are in paisa, like payment_orders.amount.
+    payment_count INTEGER NOT NULL,
+    gross BIGINT NOT NULL,
+    refund_count INTEGER NOT NULL,
+    refunds BIGINT NOT NULL,
+    -- The platform's cut of gross less refunds, in basis points, as it was when generated.
+    commission_bps INTEGER NOT NULL,
+    commission BIGINT NOT NULL,
+    -- gross - refunds - commission; negative when refunds outweigh the day's takings.
+    net_payable BIGINT NOT NULL,
+    generated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    paid_at TIMESTAMPTZ,
+    payout_reference VARCHAR,
+    UNIQUE (canteen_id, business_date),
+    CHECK ((paid_at IS NULL) = (payout_reference IS NULL))
+);
+
+CREATE INDEX idx_settlements_unpaid ON settlements (business_date) WHERE paid_at IS NULL;
+
+CREATE OR REPLACE FUNCTION settlements_frozen()
+    RETURNS TRIGGER AS $$
+BEGIN
+    IF OLD.paid_at IS NOT NULL
+        OR (NEW.canteen_id, NEW.business_date, NEW.payment_count, NEW.gross, NEW.refund_count,
+            NEW.refunds, NEW.commission_bps, NEW.commission, NEW.net_payable, NEW.generated_at)
+        IS DISTINCT FROM
+           (OLD.canteen_id, OLD.business_date, OLD.payment_count, OLD.gross, OLD.refund_count,
+            OLD.refunds, OLD.commission_bps, OLD.commission, OLD.net_payable, OLD.generated_at)
+    THEN
+        RAISE EXCEPTION 'settlement % is frozen', OLD.settlement_id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_settlements_frozen
+    BEFORE UPDATE ON settlements
+    FOR EACH ROW
+EXECUTE FUNCTION settlements_frozen();
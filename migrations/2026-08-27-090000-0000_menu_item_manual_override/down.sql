@@ -0,0 +1,2 @@
+ALTER TABLE menu_items
+    DROP COLUMN manually_disabled;
@@ -0,0 +1,10 @@
+-- Set while an admin has switched the item off by hand. Neither the weekly schedule nor the
+-- daily stock reset switches such an item back on; switching it on by hand clears it.
+ALTER TABLE menu_items
+    ADD COLUMN manually_disabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Unscheduled items that are off while in stock can only have been switched off by hand.
+UPDATE menu_items SET manually_disabled = TRUE
+WHERE NOT is_available
+  AND stock <> 0
+  AND item_id NOT IN (SELECT item_id FROM menu_item_schedules);
@@ -0,0 +1,13 @@
+-- Last sequence number of every SSE stream (kind + id). With the Postgres broker backend the
+-- database numbers each event before notifying, so every instance sends the same ids.
+CREATE TABLE sse_stream_seqs (
+    stream_kind VARCHAR NOT NULL,
+    stream_id INTEGER NOT NULL,
+    last_seq BIGINT NOT NULL,
+    PRIMARY KEY (stream_kind, stream_id)
+);
+
+INSERT INTO sse_stream_seqs (stream_kind, stream_id, last_seq)
+SELECT stream_kind, stream_id, MAX(seq)
+FROM sse_events
+GROUP BY stream_kind, stream_id;
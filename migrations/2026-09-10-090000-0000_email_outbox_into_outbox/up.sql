@@ -0,0 +1,20 @@
+-- Emails are sent through the generic outbox. Move the ones still waiting there and drop the
+-- email outbox.
+INSERT INTO outbox (topic, payload, attempts, next_attempt_at, last_error, created_at)
+SELECT 'email',
+       json_build_object(
+           'topic', 'email',
+           'user_id', user_id,
+           'email', json_build_object(
+               'recipient', recipient,
+               'subject', subject,
+               'body_text', body_text,
+               'body_html', body_html
+           )
+       )::text,
+       attempts, next_attempt_at, last_error, created_at
+FROM email_outbox
+WHERE status = 'pending' AND user_id IS NOT NULL
+ORDER BY email_id;
+
+DROP TABLE email_outbox;
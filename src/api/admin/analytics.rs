@@ -0,0 +1,76 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{AnalyticsOperations, RepositoryError};
+use crate::enums::admin::{AnalyticsGranularity, SalesAnalyticsQuery, SalesAnalyticsResponse};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, Responder};
+use log::{debug, error};
+
+#[utoipa::path(
+    tag = "Analytics",
+    params(SalesAnalyticsQuery),
+    responses(
+        (status = 200, description = "Sales of the admin's canteen over the range", body = SalesAnalyticsResponse),
+        (status = 400, description = "Unknown granularity, or an empty or too long range", body = SalesAnalyticsResponse),
+        (status = 500, description = "Failed to build the report", body = SalesAnalyticsResponse)
+    ),
+    summary = "Report the sales of the canteen",
+    description = "Revenue, orders and cancellations per day, week or month, with the item mix, how busy each delivery slot was, how many holds became orders and how many online payments failed. Days are business days in the canteen time zone."
+)]
+#[get("/sales")]
+pub(super) async fn get_sales_analytics(
+    analytics_ops: web::Data<AnalyticsOperations>,
+    query: web::Query<SalesAnalyticsQuery>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let SalesAnalyticsQuery {
+        from,
+        to,
+        granularity,
+    } = query.into_inner();
+    let granularity = match granularity.as_deref() {
+        None => AnalyticsGranularity::Day,
+        Some(raw) => match AnalyticsGranularity::parse(raw) {
+            Some(granularity) => granularity,
+            None => {
+                return Ok(HttpResponse::BadRequest().json(SalesAnalyticsResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(format!("unknown granularity {raw}")),
+                }))
+            }
+        },
+    };
+    let result =
+        web::block(move || analytics_ops.sales_report(admin.canteen_id, from, to, granularity))
+            .await?;
+    match result {
+        Ok(report) => {
+            debug!(
+                "get_sales_analytics: {} orders from {} to {} for canteen {}",
+                report.totals.orders, report.from, report.to, admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(SalesAnalyticsResponse {
+                status: "ok".to_string(),
+                data: Some(report),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => {
+                    error!(
+                        "get_sales_analytics: failed to build the report for canteen {}: {}",
+                        admin.canteen_id, other
+                    );
+                    (StatusCode::INTERNAL_SERVER_ERROR, other.to_string())
+                }
+            };
+            Ok(HttpResponse::build(status).json(SalesAnalyticsResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
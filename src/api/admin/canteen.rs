@@ -2,10 +2,11 @@ use crate::auth::admin_jwt::issue_admin_jwt;
 use crate::auth::extractors::PrincipalExtractor;
 use crate::auth::principal::Principal;
 use crate::auth::AdminJwtConfig;
-use crate::db::CanteenOperations;
+use crate::db::{CanteenOperations, MenuOperations, RepositoryError};
 use crate::enums::admin::{
-    AllCanteenResponse, AllItemsResponse, CanteenStatusResponse, GeneralMenuResponse, LoginRequest,
-    LoginResponse, NewCanteenResponse, UploadCanteenPicPresignedResponse,
+    AllCanteenResponse, AllItemsResponse, AllMenuCategoriesResponse, CanteenStatusResponse,
+    GeneralMenuResponse, LoginRequest, LoginResponse, MenuFilterQuery, NewCanteenResponse,
+    UploadCanteenPicPresignedResponse,
 };
 use crate::models::admin::{NewCanteen, NewCanteenInsert};
 use crate::services::canteen_hours::{compute_close_at, parse_tz_offset_from_env};
@@ -207,26 +208,34 @@ pub(super) async fn get_all_canteens(
 
 #[utoipa::path(
     tag = "Canteen",
+    params(
+        MenuFilterQuery,
+    ),
     responses(
         (status = 200, description = "Successfully retrieved the menu of canteen", body = AllItemsResponse),
+        (status = 400, description = "Invalid tag filter", body = AllItemsResponse),
         (status = 500, description = "Failed to retrieve menu of canteen due to server error", body = AllItemsResponse)
     ),
-    summary = "Retrieve the menu of a canteen"
+    summary = "Retrieve the menu of a canteen",
+    description = "Pinned items come first, followed by items grouped by category display order. Optionally filter by category_id and/or tag. For users, items containing allergens excluded on their profile are listed in allergen_warnings, or dropped with hide_allergens=true."
 )]
 #[get("/{id}/items")]
 pub(super) async fn get_canteen_menu(
     menu_ops: web::Data<CanteenOperations>,
     path: web::Path<(i32,)>,
     principal: PrincipalExtractor,
+    filter: web::Query<MenuFilterQuery>,
 ) -> actix_web::Result<impl Responder> {
     let requested_canteen_id = path.into_inner().0;
 
     // Admins are restricted to their own canteen; users can query by path id
-    let search_canteen_id = match principal.0 {
-        Principal::Admin { canteen_id } => canteen_id,
-        Principal::User { .. } => requested_canteen_id,
+    let (search_canteen_id, viewer_id) = match principal.0 {
+        Principal::Admin { canteen_id } => (canteen_id, None),
+        Principal::User { user_id, .. } => (requested_canteen_id, Some(user_id)),
     };
-    let result = menu_ops.get_canteen_items(search_canteen_id).await;
+    let result = menu_ops
+        .get_canteen_items(search_canteen_id, &filter.into_inner(), viewer_id)
+        .await;
     match result {
         Ok(x) => {
             debug!(
@@ -245,6 +254,13 @@ pub(super) async fn get_canteen_menu(
                 "get_canteen_menu: failed to retrieve canteen items of {}: {}",
                 search_canteen_id, e
             );
+            if let RepositoryError::ValidationError(message) = e {
+                return Ok(HttpResponse::BadRequest().json(AllItemsResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(message),
+                }));
+            }
             Ok(
                 HttpResponse::InternalServerError().json(AllCanteenResponse {
                     status: "error".to_string(),
@@ -256,6 +272,57 @@ pub(super) async fn get_canteen_menu(
     }
 }
 
+#[utoipa::path(
+    tag = "Canteen",
+    responses(
+        (status = 200, description = "Successfully retrieved the menu categories of the canteen", body = AllMenuCategoriesResponse),
+        (status = 500, description = "Failed to retrieve menu categories due to server error", body = AllMenuCategoriesResponse)
+    ),
+    summary = "Retrieve the menu categories of a canteen in display order"
+)]
+#[get("/{id}/categories")]
+pub(super) async fn get_canteen_categories(
+    menu_ops: web::Data<MenuOperations>,
+    path: web::Path<(i32,)>,
+    principal: PrincipalExtractor,
+) -> actix_web::Result<impl Responder> {
+    let requested_canteen_id = path.into_inner().0;
+
+    // Admins are restricted to their own canteen; users can query by path id
+    let search_canteen_id = match principal.0 {
+        Principal::Admin { canteen_id } => canteen_id,
+        Principal::User { .. } => requested_canteen_id,
+    };
+    let result = web::block(move || menu_ops.list_categories(search_canteen_id)).await?;
+    match result {
+        Ok(x) => {
+            debug!(
+                "get_canteen_categories: successfully fetched {} categories of canteen {}",
+                x.len(),
+                search_canteen_id
+            );
+            Ok(HttpResponse::Ok().json(AllMenuCategoriesResponse {
+                status: "ok".to_string(),
+                data: x,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_canteen_categories: failed to retrieve categories of {}: {}",
+                search_canteen_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(AllMenuCategoriesResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
 #[utoipa::path(
     tag = "Canteen",
     request_body = LoginRequest,
@@ -0,0 +1,345 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{MenuOperations, RepositoryError};
+use crate::enums::admin::{
+    AllMenuCategoriesResponse, CreateMenuCategoryRequest, GeneralMenuResponse,
+    MenuCategoryResponse, MenuTagsResponse, RenameMenuTagRequest, UpdateMenuCategoryRequest,
+};
+use crate::models::admin::NewMenuCategory;
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn category_error_status(e: RepositoryError) -> (StatusCode, String) {
+    match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::FORBIDDEN, "category not found".to_string()),
+        other => (StatusCode::CONFLICT, other.to_string()),
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    request_body = CreateMenuCategoryRequest,
+    responses(
+        (status = 200, description = "Category successfully created", body = MenuCategoryResponse),
+        (status = 400, description = "Invalid or duplicate category name", body = MenuCategoryResponse),
+        (status = 409, description = "Failed to create category", body = MenuCategoryResponse)
+    ),
+    summary = "Create a menu category for the canteen"
+)]
+#[post("/categories")]
+pub(super) async fn create_menu_category(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    req_data: web::Json<CreateMenuCategoryRequest>,
+) -> actix_web::Result<impl Responder> {
+    let req_data = req_data.into_inner();
+    let new_category = NewMenuCategory {
+        canteen_id: admin.canteen_id,
+        name: req_data.name,
+        display_order: req_data.display_order,
+    };
+    let result = web::block(move || menu_ops.create_category(new_category)).await?;
+    match result {
+        Ok(category) => {
+            debug!(
+                "create_menu_category: created category '{}' with id {}",
+                category.name, category.category_id
+            );
+            Ok(HttpResponse::Ok().json(MenuCategoryResponse {
+                status: "ok".to_string(),
+                data: Some(category),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "create_menu_category: failed to create category for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            let (status, message) = category_error_status(e);
+            Ok(HttpResponse::build(status).json(MenuCategoryResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    responses(
+        (status = 200, description = "Successfully retrieved the categories of the canteen", body = AllMenuCategoriesResponse),
+        (status = 500, description = "Failed to retrieve categories", body = AllMenuCategoriesResponse)
+    ),
+    summary = "List the menu categories of the canteen in display order"
+)]
+#[get("/categories")]
+pub(super) async fn get_menu_categories(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || menu_ops.list_categories(admin.canteen_id)).await?;
+    match result {
+        Ok(categories) => {
+            debug!(
+                "get_menu_categories: fetched {} categories of canteen {}",
+                categories.len(),
+                admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(AllMenuCategoriesResponse {
+                status: "ok".to_string(),
+                data: categories,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_menu_categories: failed to fetch categories of canteen {}: {}",
+                admin.canteen_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(AllMenuCategoriesResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    request_body = UpdateMenuCategoryRequest,
+    responses(
+        (status = 200, description = "Category updated successfully", body = MenuCategoryResponse),
+        (status = 400, description = "Invalid or duplicate category name", body = MenuCategoryResponse),
+        (status = 403, description = "Category not found", body = MenuCategoryResponse),
+        (status = 409, description = "Failed to update category", body = MenuCategoryResponse)
+    ),
+    summary = "Rename or reorder a menu category"
+)]
+#[put("/categories")]
+pub(super) async fn update_menu_category(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    req_data: web::Json<UpdateMenuCategoryRequest>,
+) -> actix_web::Result<impl Responder> {
+    let req_data = req_data.into_inner();
+    let category_id = req_data.category_id;
+    let result = web::block(move || {
+        menu_ops.update_category(category_id, admin.canteen_id, req_data.update)
+    })
+    .await?;
+    match result {
+        Ok(category) => {
+            debug!(
+                "update_menu_category: updated category {} to '{}' (order {})",
+                category.category_id, category.name, category.display_order
+            );
+            Ok(HttpResponse::Ok().json(MenuCategoryResponse {
+                status: "ok".to_string(),
+                data: Some(category),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "update_menu_category: failed to update category {}: {}",
+                category_id, e
+            );
+            let (status, message) = category_error_status(e);
+            Ok(HttpResponse::build(status).json(MenuCategoryResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(
+        ("id", description = "The unique identifier of the category to delete"),
+    ),
+    responses(
+        (status = 200, description = "Category deleted; its items are left uncategorised", body = GeneralMenuResponse),
+        (status = 403, description = "Category not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to delete category", body = GeneralMenuResponse)
+    ),
+    summary = "Delete a menu category"
+)]
+#[delete("/categories/{id}")]
+pub(super) async fn delete_menu_category(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let category_id = path.into_inner().0;
+    let result =
+        web::block(move || menu_ops.delete_category(category_id, admin.canteen_id)).await?;
+    match result {
+        Ok(category) => {
+            debug!("delete_menu_category: deleted category '{}'", category.name);
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "delete_menu_category: failed to delete category {}: {}",
+                category_id, e
+            );
+            let (status, message) = category_error_status(e);
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    responses(
+        (status = 200, description = "Successfully retrieved the tags used on the menu", body = MenuTagsResponse),
+        (status = 500, description = "Failed to retrieve tags", body = MenuTagsResponse)
+    ),
+    summary = "List the tags used on the canteen's menu with item counts"
+)]
+#[get("/tags")]
+pub(super) async fn get_menu_tags(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || menu_ops.list_tags(admin.canteen_id)).await?;
+    match result {
+        Ok(tags) => {
+            debug!(
+                "get_menu_tags: fetched {} tags of canteen {}",
+                tags.len(),
+                admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(MenuTagsResponse {
+                status: "ok".to_string(),
+                data: tags,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_menu_tags: failed to fetch tags of canteen {}: {}",
+                admin.canteen_id, e
+            );
+            Ok(HttpResponse::InternalServerError().json(MenuTagsResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(
+        ("tag", description = "The tag to rename"),
+    ),
+    request_body = RenameMenuTagRequest,
+    responses(
+        (status = 200, description = "Tag renamed on every item", body = GeneralMenuResponse),
+        (status = 400, description = "Invalid tag", body = GeneralMenuResponse),
+        (status = 403, description = "Tag not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to rename tag", body = GeneralMenuResponse)
+    ),
+    summary = "Rename a tag on every item of the canteen"
+)]
+#[put("/tags/{tag}")]
+pub(super) async fn rename_menu_tag(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(String,)>,
+    req_data: web::Json<RenameMenuTagRequest>,
+) -> actix_web::Result<impl Responder> {
+    let tag = path.into_inner().0;
+    let tag_cl = tag.clone();
+    let new_tag = req_data.into_inner().new_tag;
+    let result =
+        web::block(move || menu_ops.rename_tag(admin.canteen_id, &tag_cl, &new_tag)).await?;
+    match result {
+        Ok(count) => {
+            debug!("rename_menu_tag: renamed tag '{}' on {} items", tag, count);
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("rename_menu_tag: failed to rename tag '{}': {}", tag, e);
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::FORBIDDEN, "tag not found".to_string())
+                }
+                other => (StatusCode::CONFLICT, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(
+        ("tag", description = "The tag to remove"),
+    ),
+    responses(
+        (status = 200, description = "Tag removed from every item", body = GeneralMenuResponse),
+        (status = 403, description = "Tag not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to remove tag", body = GeneralMenuResponse)
+    ),
+    summary = "Remove a tag from every item of the canteen"
+)]
+#[delete("/tags/{tag}")]
+pub(super) async fn delete_menu_tag(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(String,)>,
+) -> actix_web::Result<impl Responder> {
+    let tag = path.into_inner().0;
+    let tag_cl = tag.clone();
+    let result = web::block(move || menu_ops.delete_tag(admin.canteen_id, &tag_cl)).await?;
+    match result {
+        Ok(count) => {
+            debug!(
+                "delete_menu_tag: removed tag '{}' from {} items",
+                tag, count
+            );
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("delete_menu_tag: failed to remove tag '{}': {}", tag, e);
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::FORBIDDEN, "tag not found".to_string())
+                }
+                other => (StatusCode::CONFLICT, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::auth::extractors::PrincipalExtractor;
 use crate::auth::AdminPrincipal;
-use crate::sse::SseBroker;
-use actix_web::{get, web, Responder};
+use crate::sse::{last_event_id, SseBroker};
+use actix_web::{get, web, HttpRequest, Responder};
 use actix_web_lab::sse;
 use actix_web_lab::sse::Sse;
 use std::time::Duration;
@@ -10,7 +10,7 @@ use uuid::Uuid;
 #[utoipa::path(
     tag = "Canteen",
     responses(
-        (status = 200, description = "Successfully connect to SSE stream", content_type = "text/event-stream"),
+        (status = 200, description = "Successfully connect to SSE stream. Events carry per-stream sequence ids; reconnect with `Last-Event-ID` to replay missed events. A `status: resync` event means some could not be replayed and state should be refetched.", content_type = "text/event-stream"),
         (status = 401, description = "Auth token missing"),
         (status = 500, description = "Failed to connect to SSE stream"),
     ),
@@ -20,16 +20,16 @@ use uuid::Uuid;
 pub async fn canteen_aggregated_order_events(
     admin: AdminPrincipal,
     broker: web::Data<SseBroker>,
+    req: HttpRequest,
 ) -> impl Responder {
     let canteen_id = admin.canteen_id;
     let conn_id = Uuid::new_v4();
 
-    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(64);
-    broker.register_canteen_connection(canteen_id, conn_id, tx.clone());
-
+    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(broker.channel_capacity());
     let _ = tx
         .send(sse::Data::new("connected").event("status").into())
         .await;
+    broker.register_canteen_connection(canteen_id, conn_id, tx.clone(), last_event_id(&req));
 
     let cleanup_broker = broker.clone();
     actix_web::rt::spawn(async move {
@@ -45,7 +45,7 @@ pub async fn canteen_aggregated_order_events(
 #[utoipa::path(
     tag = "Menu",
     responses(
-        (status = 200, description = "Successfully connect to SSE stream", content_type = "text/event-stream"),
+        (status = 200, description = "Successfully connect to SSE stream. Events carry per-stream sequence ids; reconnect with `Last-Event-ID` to replay missed events. A `status: resync` event means some could not be replayed and state should be refetched.", content_type = "text/event-stream"),
         (status = 401, description = "Auth token missing"),
         (status = 500, description = "Failed to connect to SSE stream"),
     ),
@@ -56,16 +56,21 @@ pub async fn inventory_update_events(
     path: web::Path<(i32,)>,
     _principal: PrincipalExtractor,
     broker: web::Data<SseBroker>,
+    req: HttpRequest,
 ) -> impl Responder {
     let canteen_id_to_sub = path.into_inner().0;
     let conn_id = Uuid::new_v4();
 
-    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(64);
-    broker.register_canteen_subscription(canteen_id_to_sub, conn_id, tx.clone());
-
+    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(broker.channel_capacity());
     let _ = tx
         .send(sse::Data::new("connected").event("status").into())
         .await;
+    broker.register_canteen_subscription(
+        canteen_id_to_sub,
+        conn_id,
+        tx.clone(),
+        last_event_id(&req),
+    );
 
     let cleanup_broker = broker.clone();
     actix_web::rt::spawn(async move {
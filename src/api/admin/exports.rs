@@ -0,0 +1,125 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{ExportOperations, ReportRange, RepositoryError};
+use crate::enums::admin::{AccountingExport, AccountingExportErrorResponse, AccountingExportQuery};
+use crate::services::accounting_export::csv_stream;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, Responder};
+use log::debug;
+
+/// Stream `export` for the admin's canteen as a CSV download named after it and the range.
+fn csv_download(
+    export_ops: &ExportOperations,
+    admin: &AdminPrincipal,
+    query: AccountingExportQuery,
+    export: AccountingExport,
+) -> HttpResponse {
+    let range = match ReportRange::resolve(query.from, query.to) {
+        Ok(range) => range,
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            return HttpResponse::build(status).json(AccountingExportErrorResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            });
+        }
+    };
+    let filename = format!("{}-{}-{}.csv", export.file_stem(), range.from, range.to);
+    debug!(
+        "csv_download: streaming {} for canteen {}",
+        filename, admin.canteen_id
+    );
+    HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .streaming(csv_stream(
+            export_ops.clone(),
+            export,
+            admin.canteen_id,
+            range,
+        ))
+}
+
+#[utoipa::path(
+    tag = "Exports",
+    params(AccountingExportQuery),
+    responses(
+        (status = 200, description = "Finished orders as CSV, one row each", content(
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Empty or too long range", body = AccountingExportErrorResponse)
+    ),
+    summary = "Download the canteen's orders as CSV",
+    description = "Orders delivered or cancelled that were placed in the range, oldest first. Columns: `order_id`, `ordered_at` (UTC, RFC 3339), `business_date` (in the canteen time zone), `user_id`, `status` (`delivered` or `cancelled`), `slot` (delivery slot or `Instant`), `total` (rupees), `merchant_order_id` and `payment_state` (empty unless paid online). Columns are only ever added at the end."
+)]
+#[get("/orders")]
+pub(super) async fn export_orders(
+    export_ops: web::Data<ExportOperations>,
+    query: web::Query<AccountingExportQuery>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    Ok(csv_download(
+        &export_ops,
+        &admin,
+        query.into_inner(),
+        AccountingExport::Orders,
+    ))
+}
+
+#[utoipa::path(
+    tag = "Exports",
+    params(AccountingExportQuery),
+    responses(
+        (status = 200, description = "Items of finished orders as CSV, one row each", content(
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Empty or too long range", body = AccountingExportErrorResponse)
+    ),
+    summary = "Download the line items of the canteen's orders as CSV",
+    description = "Items of the orders in the orders export. Columns: `order_id`, `ordered_at`, `business_date`, `status`, `item_id`, `item_name` (empty once the item is removed), `quantity`, `unit_price` and `line_total` (rupees, as charged). Orders finished before 2026-07-23 have no line items. Columns are only ever added at the end."
+)]
+#[get("/order-items")]
+pub(super) async fn export_order_items(
+    export_ops: web::Data<ExportOperations>,
+    query: web::Query<AccountingExportQuery>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    Ok(csv_download(
+        &export_ops,
+        &admin,
+        query.into_inner(),
+        AccountingExport::OrderItems,
+    ))
+}
+
+#[utoipa::path(
+    tag = "Exports",
+    params(AccountingExportQuery),
+    responses(
+        (status = 200, description = "Online payments as CSV, one row each", content(
+            (String = "text/csv")
+        )),
+        (status = 400, description = "Empty or too long range", body = AccountingExportErrorResponse)
+    ),
+    summary = "Download the canteen's PhonePe payments as CSV",
+    description = "Payments started in the range, for matching against PhonePe settlement reports by `merchant_order_id`. Columns: `payment_id`, `created_at`, `business_date`, `merchant_order_id`, `phonepe_order_id`, `hold_id`, `order_id` (empty unless the payment confirmed an order), `user_id`, `amount_paisa`, `payment_state`, `refunded_paisa` (completed refunds) and `updated_at`. Columns are only ever added at the end."
+)]
+#[get("/payments")]
+pub(super) async fn export_payments(
+    export_ops: web::Data<ExportOperations>,
+    query: web::Query<AccountingExportQuery>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    Ok(csv_download(
+        &export_ops,
+        &admin,
+        query.into_inner(),
+        AccountingExport::Payments,
+    ))
+}
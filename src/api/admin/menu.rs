@@ -2,9 +2,11 @@ use crate::auth::{AdminPrincipal, PrincipalExtractor};
 use crate::db::{MenuOperations, RepositoryError};
 use crate::enums::admin::{
     AllItemsResponse, CreateMenuItemRequest, CreateMenuItemResponse, GeneralMenuResponse,
-    ItemResponse, MenuItemWithPic, UpdateItemRequest, UploadMenuItemPicPresignedResponse,
+    ItemResponse, MenuItemScheduleRequest, MenuItemScheduleResponse, MenuItemWithPic,
+    UpdateItemRequest, UploadMenuItemPicPresignedResponse,
 };
 use crate::models::admin::NewMenuItem;
+use crate::services::outbox::OutboxDispatcher;
 use crate::sse::{InventoryUpdateItems, SseBroker, SseEvent};
 use actix_web::http::StatusCode;
 use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
@@ -34,6 +36,13 @@ pub(super) async fn create_menu_item(
         stock: req_data.stock,
         is_available: req_data.is_available,
         description: req_data.description,
+        default_stock: req_data.default_stock,
+        category_id: req_data.category_id,
+        display_order: req_data.display_order,
+        is_pinned: req_data.is_pinned,
+        tags: req_data.tags,
+        allergens: req_data.allergens,
+        diet_labels: req_data.diet_labels,
     };
     let req_data = match new_item.sanitize_and_validate() {
         Ok(data) => data,
@@ -247,6 +256,7 @@ pub(super) async fn update_menu_item(
     menu_ops: web::Data<MenuOperations>,
     admin: AdminPrincipal,
     broker: web::Data<SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     req_data: web::Json<UpdateItemRequest>,
 ) -> actix_web::Result<impl Responder> {
     let req_data = req_data.into_inner();
@@ -270,6 +280,7 @@ pub(super) async fn update_menu_item(
                 "update_menu_item: successfully updated menu item '{}' with changes: {:?}",
                 x.name, update_data
             );
+            outbox.wake();
             broker.publish_canteen_subscription_event(
                 x.canteen_id,
                 &SseEvent::InventoryUpdate {
@@ -384,3 +395,117 @@ pub(super) async fn get_menu_item(
         }
     }
 }
+
+#[utoipa::path(
+    tag = "Menu",
+    params(
+        ("item_id", description = "The unique identifier of the menu item"),
+    ),
+    responses(
+        (status = 200, description = "Successfully fetched the availability schedule", body = MenuItemScheduleResponse),
+        (status = 403, description = "Item not found", body = MenuItemScheduleResponse),
+        (status = 409, description = "Failed to fetch the schedule", body = MenuItemScheduleResponse)
+    ),
+    summary = "Retrieve the weekly availability schedule of a menu item"
+)]
+#[get("/schedule/{item_id}")]
+pub(super) async fn get_menu_item_schedule(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let item_id = path.into_inner().0;
+    let result =
+        web::block(move || menu_ops.get_menu_item_schedule(item_id, admin.canteen_id)).await?;
+    match result {
+        Ok(windows) => {
+            debug!(
+                "get_menu_item_schedule: fetched {} windows for menu item {}",
+                windows.len(),
+                item_id
+            );
+            Ok(HttpResponse::Ok().json(MenuItemScheduleResponse {
+                status: "ok".to_string(),
+                data: windows,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_menu_item_schedule: failed to fetch schedule for menu item {}: {}",
+                item_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::FORBIDDEN, "item not found".to_string())
+                }
+                other => (StatusCode::CONFLICT, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(MenuItemScheduleResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(
+        ("item_id", description = "The unique identifier of the menu item"),
+    ),
+    request_body = MenuItemScheduleRequest,
+    responses(
+        (status = 200, description = "Availability schedule replaced", body = MenuItemScheduleResponse),
+        (status = 400, description = "Invalid schedule window", body = MenuItemScheduleResponse),
+        (status = 403, description = "Item not found", body = MenuItemScheduleResponse),
+        (status = 409, description = "Failed to update the schedule", body = MenuItemScheduleResponse)
+    ),
+    summary = "Replace the weekly availability schedule of a menu item. The background scheduler toggles is_available within a minute; an empty list removes the schedule."
+)]
+#[put("/schedule/{item_id}")]
+pub(super) async fn set_menu_item_schedule(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<MenuItemScheduleRequest>,
+) -> actix_web::Result<impl Responder> {
+    let item_id = path.into_inner().0;
+    let windows = req_data.into_inner().windows;
+    let result =
+        web::block(move || menu_ops.set_menu_item_schedule(item_id, admin.canteen_id, windows))
+            .await?;
+    match result {
+        Ok(windows) => {
+            debug!(
+                "set_menu_item_schedule: stored {} windows for menu item {}",
+                windows.len(),
+                item_id
+            );
+            Ok(HttpResponse::Ok().json(MenuItemScheduleResponse {
+                status: "ok".to_string(),
+                data: windows,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "set_menu_item_schedule: failed to store schedule for menu item {}: {}",
+                item_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::FORBIDDEN, "item not found".to_string())
+                }
+                other => (StatusCode::CONFLICT, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(MenuItemScheduleResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(message),
+            }))
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{MenuOperations, RepositoryError};
+use crate::enums::admin::{
+    MenuExportQuery, MenuImportQuery, MenuImportReport, MenuImportResponse, MenuTransferFormat,
+};
+use crate::services::menu_transfer::{parse_menu_csv, parse_menu_json, write_menu_csv};
+use crate::services::outbox::OutboxDispatcher;
+use crate::sse::{SseBroker, SseEvent};
+use actix_web::http::header::{
+    ContentDisposition, DispositionParam, DispositionType, CONTENT_TYPE,
+};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use log::{debug, error};
+
+/// Import format picked from the request's Content-Type.
+fn import_format(req: &HttpRequest) -> Option<MenuTransferFormat> {
+    let content_type = req.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = content_type.split(';').next()?.trim().to_lowercase();
+    match mime.as_str() {
+        "application/json" => Some(MenuTransferFormat::Json),
+        "text/csv" => Some(MenuTransferFormat::Csv),
+        _ => None,
+    }
+}
+
+fn import_error(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(MenuImportResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(MenuImportQuery),
+    request_body(
+        description = "Menu rows as a JSON array or a CSV file with a header row. List columns in CSV are separated by `|`.",
+        content(
+            (Vec<crate::enums::admin::MenuTransferRow> = "application/json"),
+            (String = "text/csv")
+        )
+    ),
+    responses(
+        (status = 200, description = "Import validated, and applied unless `dry_run` was set", body = MenuImportResponse),
+        (status = 400, description = "The file could not be parsed or some rows are invalid; nothing was written", body = MenuImportResponse),
+        (status = 415, description = "Content-Type is neither application/json nor text/csv", body = MenuImportResponse),
+        (status = 409, description = "Failed to apply the import", body = MenuImportResponse)
+    ),
+    summary = "Bulk import the canteen menu from CSV or JSON"
+)]
+#[post("")]
+pub(super) async fn import_menu(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    broker: web::Data<SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
+    query: web::Query<MenuImportQuery>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> actix_web::Result<impl Responder> {
+    let rows = match import_format(&req) {
+        Some(MenuTransferFormat::Json) => parse_menu_json(&body),
+        Some(MenuTransferFormat::Csv) => parse_menu_csv(&body),
+        None => {
+            return Ok(import_error(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "expected Content-Type application/json or text/csv".to_string(),
+            ));
+        }
+    };
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(message) => {
+            debug!(
+                "import_menu: rejected unparsable file for canteen {}: {}",
+                admin.canteen_id, message
+            );
+            return Ok(import_error(StatusCode::BAD_REQUEST, message));
+        }
+    };
+
+    let dry_run = query.dry_run;
+    let result = web::block(move || menu_ops.import_menu(admin.canteen_id, rows, dry_run)).await?;
+    match result {
+        Ok((report, inventory_updates)) => {
+            debug!(
+                "import_menu: canteen {} import (dry_run: {}) created {}, updated {}, unchanged {}, errors {}",
+                admin.canteen_id,
+                report.dry_run,
+                report.created.len(),
+                report.updated.len(),
+                report.unchanged,
+                report.errors.len()
+            );
+            if !inventory_updates.is_empty() {
+                outbox.wake();
+                broker.publish_canteen_subscription_event(
+                    admin.canteen_id,
+                    &SseEvent::InventoryUpdate {
+                        items: inventory_updates,
+                    },
+                );
+            }
+            if report.errors.is_empty() {
+                return Ok(HttpResponse::Ok().json(MenuImportResponse {
+                    status: "ok".to_string(),
+                    data: Some(report),
+                    error: None,
+                }));
+            }
+            Ok(HttpResponse::BadRequest().json(MenuImportResponse {
+                status: "error".to_string(),
+                error: Some("some rows are invalid; nothing was imported".to_string()),
+                data: Some(report),
+            }))
+        }
+        Err(e) => {
+            error!(
+                "import_menu: failed to import menu for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => (StatusCode::CONFLICT, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(MenuImportResponse {
+                status: "error".to_string(),
+                data: Some(MenuImportReport {
+                    dry_run,
+                    ..Default::default()
+                }),
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Menu",
+    params(MenuExportQuery),
+    responses(
+        (status = 200, description = "The canteen menu as a downloadable file in the import format", content(
+            (Vec<crate::enums::admin::MenuTransferRow> = "application/json"),
+            (String = "text/csv")
+        )),
+        (status = 500, description = "Failed to export the menu", body = MenuImportResponse)
+    ),
+    summary = "Download the canteen menu as CSV or JSON"
+)]
+#[get("/export")]
+pub(super) async fn export_menu(
+    menu_ops: web::Data<MenuOperations>,
+    admin: AdminPrincipal,
+    query: web::Query<MenuExportQuery>,
+) -> actix_web::Result<impl Responder> {
+    let format = query.format;
+    let result = web::block(move || menu_ops.export_menu(admin.canteen_id)).await?;
+    let rows = match result {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!(
+                "export_menu: failed to export menu for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            return Ok(import_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to export menu".to_string(),
+            ));
+        }
+    };
+
+    let (body, content_type, filename) = match format {
+        MenuTransferFormat::Json => (
+            serde_json::to_vec(&rows).map_err(|e| e.to_string()),
+            "application/json",
+            "menu.json",
+        ),
+        MenuTransferFormat::Csv => (write_menu_csv(&rows), "text/csv", "menu.csv"),
+    };
+    match body {
+        Ok(body) => {
+            debug!(
+                "export_menu: exported {} items for canteen {} as {:?}",
+                rows.len(),
+                admin.canteen_id,
+                format
+            );
+            Ok(HttpResponse::Ok()
+                .content_type(content_type)
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(filename.to_string())],
+                })
+                .body(body))
+        }
+        Err(message) => {
+            error!(
+                "export_menu: failed to encode menu for canteen {}: {}",
+                admin.canteen_id, message
+            );
+            Ok(import_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to export menu".to_string(),
+            ))
+        }
+    }
+}
@@ -1,44 +1,82 @@
 use crate::api::ContentTypeHeader;
-use crate::db::{AssetOperations, CanteenOperations, MenuOperations};
+use crate::db::{
+    AnalyticsOperations, AssetOperations, CanteenOperations, ExportOperations, MenuOperations,
+    PricingOperations, PromoOperations, ReviewOperations, SettlementOperations, WebhookOperations,
+};
 use crate::services::canteen_scheduler::CanteenSchedulerNotifier;
+use crate::services::outbox::OutboxDispatcher;
 use crate::sse::SseBroker;
 use actix_web::middleware::NormalizePath;
 use actix_web::web;
+use analytics::*;
 use asset_management::*;
 use canteen::*;
+use categories::*;
 use events::*;
+use exports::*;
 use menu::*;
+use menu_transfer::*;
+use pricing::*;
+use promo::*;
+use reviews::*;
+use settlements::*;
 use utoipa_actix_web::{scope, service_config::ServiceConfig};
+use webhooks::*;
 
+mod analytics;
 mod asset_management;
 mod canteen;
+mod categories;
 mod events;
+mod exports;
 mod menu;
+mod menu_transfer;
+mod pricing;
+mod promo;
+mod reviews;
+mod settlements;
+mod webhooks;
 
+#[allow(clippy::too_many_arguments)]
 pub fn config(
     cfg: &mut ServiceConfig,
     menu_ops: &MenuOperations,
     canteen_ops: &CanteenOperations,
+    pricing_ops: &PricingOperations,
+    promo_ops: &PromoOperations,
+    webhook_ops: &WebhookOperations,
+    review_ops: &ReviewOperations,
+    analytics_ops: &AnalyticsOperations,
+    export_ops: &ExportOperations,
+    settlement_ops: &SettlementOperations,
     asset_ops: &AssetOperations,
     scheduler: &CanteenSchedulerNotifier,
     sse_broker: &SseBroker,
+    outbox_dispatcher: &OutboxDispatcher,
 ) {
     cfg.service(
         scope::scope("/menu")
             .wrap(NormalizePath::trim())
             .app_data(web::Data::new(menu_ops.clone()))
             .app_data(web::Data::new(sse_broker.clone()))
+            .app_data(web::Data::new(outbox_dispatcher.clone()))
             .service(
                 scope::scope("/events")
                     .app_data(web::Data::new(sse_broker.clone()))
                     .wrap(NormalizePath::trim())
                     .service(inventory_update_events),
             )
+            // Accepts both JSON and CSV bodies, so it sits outside the JSON-only scope.
+            .service(scope::scope("/import").service(import_menu))
             .service(
                 scope::scope("")
                     .guard(ContentTypeHeader)
                     .service(create_menu_item)
-                    .service(update_menu_item),
+                    .service(update_menu_item)
+                    .service(set_menu_item_schedule)
+                    .service(create_menu_category)
+                    .service(update_menu_category)
+                    .service(rename_menu_tag),
             )
             .service(
                 scope::scope("")
@@ -46,7 +84,13 @@ pub fn config(
                     .service(get_menu_item)
                     .service(remove_menu_item)
                     .service(upload_menu_item_pic)
-                    .service(set_menu_pic_link),
+                    .service(set_menu_pic_link)
+                    .service(get_menu_item_schedule)
+                    .service(get_menu_categories)
+                    .service(delete_menu_category)
+                    .service(get_menu_tags)
+                    .service(export_menu)
+                    .service(delete_menu_tag),
             ),
     )
     .service(
@@ -59,12 +103,16 @@ pub fn config(
                     .service(canteen_aggregated_order_events),
             )
             .app_data(web::Data::new(canteen_ops.clone()))
+            .app_data(web::Data::new(menu_ops.clone()))
             .app_data(web::Data::new(scheduler.clone()))
+            .app_data(web::Data::new(review_ops.clone()))
             .service(
                 scope::scope("")
                     .guard(ContentTypeHeader)
                     .service(create_canteen)
-                    .service(login_canteen),
+                    .service(login_canteen)
+                    .service(moderate_review)
+                    .service(reply_to_review),
             )
             .service(
                 scope::scope("")
@@ -73,9 +121,85 @@ pub fn config(
                     .service(open_canteen)
                     .service(close_canteen)
                     .service(get_all_canteens)
-                    .service(get_canteen_menu),
+                    .service(get_canteen_menu)
+                    .service(get_canteen_categories)
+                    .service(get_canteen_reviews),
+            ),
+    )
+    .service(
+        scope::scope("/pricing")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(pricing_ops.clone()))
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .service(create_pricing_rule)
+                    .service(update_pricing_rule),
+            )
+            .service(
+                scope::scope("")
+                    .service(get_pricing_rules)
+                    .service(delete_pricing_rule),
+            ),
+    )
+    .service(
+        scope::scope("/promo")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(promo_ops.clone()))
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .service(create_promo_code)
+                    .service(update_promo_code),
+            )
+            .service(
+                scope::scope("")
+                    .service(get_promo_codes)
+                    .service(delete_promo_code),
+            ),
+    )
+    .service(
+        scope::scope("/webhooks")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(webhook_ops.clone()))
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .service(create_webhook)
+                    .service(update_webhook),
+            )
+            .service(
+                scope::scope("")
+                    .service(get_webhooks)
+                    .service(delete_webhook)
+                    .service(get_webhook_deliveries),
             ),
     )
+    .service(
+        scope::scope("/analytics")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(analytics_ops.clone()))
+            .service(get_sales_analytics),
+    )
+    .service(
+        scope::scope("/exports")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(export_ops.clone()))
+            .service(export_orders)
+            .service(export_order_items)
+            .service(export_payments),
+    )
+    .service(
+        scope::scope("/settlements")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(settlement_ops.clone()))
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .service(record_settlement_payout),
+            )
+            .service(scope::scope("").service(get_settlements)),
+    )
     .service(
         scope::scope("/assets")
             .wrap(NormalizePath::trim())
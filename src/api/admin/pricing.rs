@@ -0,0 +1,215 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{PricingOperations, RepositoryError};
+use crate::enums::admin::{
+    AllPricingRulesResponse, GeneralMenuResponse, PricingRuleRequest, PricingRuleResponse,
+};
+use crate::models::admin::{NewPricingRule, ScheduleWindow};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn rule_error_status(e: RepositoryError) -> (StatusCode, String) {
+    match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::FORBIDDEN, "rule not found".to_string()),
+        other => (StatusCode::CONFLICT, other.to_string()),
+    }
+}
+
+fn split_request(
+    canteen_id: i32,
+    req_data: PricingRuleRequest,
+) -> (NewPricingRule, Vec<ScheduleWindow>) {
+    (
+        NewPricingRule {
+            canteen_id,
+            name: req_data.name,
+            kind: req_data.kind,
+            percent_off: req_data.percent_off,
+            amount_off: req_data.amount_off,
+            combo_price: req_data.combo_price,
+            item_ids: req_data.item_ids,
+            is_active: req_data.is_active,
+        },
+        req_data.windows,
+    )
+}
+
+#[utoipa::path(
+    tag = "Pricing",
+    request_body = PricingRuleRequest,
+    responses(
+        (status = 200, description = "Pricing rule created", body = PricingRuleResponse),
+        (status = 400, description = "Invalid rule", body = PricingRuleResponse),
+        (status = 409, description = "Failed to create rule", body = PricingRuleResponse)
+    ),
+    summary = "Create a discount, happy-hour or combo pricing rule"
+)]
+#[post("/rules")]
+pub(super) async fn create_pricing_rule(
+    pricing_ops: web::Data<PricingOperations>,
+    admin: AdminPrincipal,
+    req_data: web::Json<PricingRuleRequest>,
+) -> actix_web::Result<impl Responder> {
+    let (new_rule, windows) = split_request(admin.canteen_id, req_data.into_inner());
+    let result = web::block(move || pricing_ops.create_rule(new_rule, windows)).await?;
+    match result {
+        Ok(rule) => {
+            debug!(
+                "create_pricing_rule: created rule {} '{}' for canteen {}",
+                rule.rule_id, rule.name, admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(PricingRuleResponse {
+                status: "ok".to_string(),
+                data: Some(rule),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "create_pricing_rule: failed to create rule for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            let (status, message) = rule_error_status(e);
+            Ok(HttpResponse::build(status).json(PricingRuleResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Pricing",
+    responses(
+        (status = 200, description = "Successfully retrieved the pricing rules of the canteen", body = AllPricingRulesResponse),
+        (status = 500, description = "Failed to retrieve pricing rules", body = AllPricingRulesResponse)
+    ),
+    summary = "List the pricing rules of the canteen"
+)]
+#[get("/rules")]
+pub(super) async fn get_pricing_rules(
+    pricing_ops: web::Data<PricingOperations>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || pricing_ops.list_rules(admin.canteen_id)).await?;
+    match result {
+        Ok(rules) => {
+            debug!(
+                "get_pricing_rules: fetched {} rules of canteen {}",
+                rules.len(),
+                admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(AllPricingRulesResponse {
+                status: "ok".to_string(),
+                data: rules,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_pricing_rules: failed to fetch rules of canteen {}: {}",
+                admin.canteen_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(AllPricingRulesResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Pricing",
+    params(
+        ("id", description = "The unique identifier of the rule to replace"),
+    ),
+    request_body = PricingRuleRequest,
+    responses(
+        (status = 200, description = "Pricing rule replaced", body = PricingRuleResponse),
+        (status = 400, description = "Invalid rule", body = PricingRuleResponse),
+        (status = 403, description = "Rule not found", body = PricingRuleResponse),
+        (status = 409, description = "Failed to update rule", body = PricingRuleResponse)
+    ),
+    summary = "Replace a pricing rule"
+)]
+#[put("/rules/{id}")]
+pub(super) async fn update_pricing_rule(
+    pricing_ops: web::Data<PricingOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<PricingRuleRequest>,
+) -> actix_web::Result<impl Responder> {
+    let rule_id = path.into_inner().0;
+    let (changed_rule, windows) = split_request(admin.canteen_id, req_data.into_inner());
+    let result =
+        web::block(move || pricing_ops.update_rule(rule_id, changed_rule, windows)).await?;
+    match result {
+        Ok(rule) => {
+            debug!("update_pricing_rule: replaced rule {}", rule.rule_id);
+            Ok(HttpResponse::Ok().json(PricingRuleResponse {
+                status: "ok".to_string(),
+                data: Some(rule),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "update_pricing_rule: failed to update rule {}: {}",
+                rule_id, e
+            );
+            let (status, message) = rule_error_status(e);
+            Ok(HttpResponse::build(status).json(PricingRuleResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Pricing",
+    params(
+        ("id", description = "The unique identifier of the rule to delete"),
+    ),
+    responses(
+        (status = 200, description = "Pricing rule deleted", body = GeneralMenuResponse),
+        (status = 403, description = "Rule not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to delete rule", body = GeneralMenuResponse)
+    ),
+    summary = "Delete a pricing rule"
+)]
+#[delete("/rules/{id}")]
+pub(super) async fn delete_pricing_rule(
+    pricing_ops: web::Data<PricingOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let rule_id = path.into_inner().0;
+    let result = web::block(move || pricing_ops.delete_rule(rule_id, admin.canteen_id)).await?;
+    match result {
+        Ok(rule) => {
+            debug!("delete_pricing_rule: deleted rule '{}'", rule.name);
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "delete_pricing_rule: failed to delete rule {}: {}",
+                rule_id, e
+            );
+            let (status, message) = rule_error_status(e);
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::auth::{AdminPrincipal, OperatorPrincipal};
+use crate::db::{PromoOperations, PromoScope, RepositoryError};
+use crate::enums::admin::{
+    AllPromoCodesResponse, GeneralMenuResponse, PromoCodeRequest, PromoCodeResponse,
+};
+use crate::models::admin::NewPromoCode;
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn code_error_status(e: RepositoryError) -> (StatusCode, String) {
+    match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::FORBIDDEN, "promo code not found".to_string()),
+        other => (StatusCode::CONFLICT, other.to_string()),
+    }
+}
+
+fn code_error_response(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(PromoCodeResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+/// Canteen admins manage the codes of their canteen; platform operators manage platform-wide
+/// codes.
+fn promo_scope(
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+) -> Option<PromoScope> {
+    admin
+        .map(|admin| PromoScope::Canteen(admin.canteen_id))
+        .or(operator.map(|_| PromoScope::Platform))
+}
+
+fn forbidden_response() -> HttpResponse {
+    code_error_response(
+        StatusCode::FORBIDDEN,
+        "only platform operators may manage platform-wide codes".to_string(),
+    )
+}
+
+/// The code to store, or `None` if a canteen admin asked for a platform-wide code. Codes
+/// of platform operators are always platform-wide.
+fn new_code_from_request(scope: PromoScope, req_data: PromoCodeRequest) -> Option<NewPromoCode> {
+    if req_data.platform_wide && scope != PromoScope::Platform {
+        return None;
+    }
+    Some(NewPromoCode {
+        code: req_data.code,
+        canteen_id: scope.canteen_id(),
+        description: req_data.description,
+        percent_off: req_data.percent_off,
+        amount_off: req_data.amount_off,
+        max_discount: req_data.max_discount,
+        min_order_total: req_data.min_order_total,
+        valid_from: req_data.valid_from,
+        valid_until: req_data.valid_until,
+        max_uses_total: req_data.max_uses_total,
+        max_uses_per_user: req_data.max_uses_per_user,
+        first_order_only: req_data.first_order_only,
+        is_active: req_data.is_active,
+    })
+}
+
+#[utoipa::path(
+    tag = "Promo",
+    request_body = PromoCodeRequest,
+    responses(
+        (status = 200, description = "Promo code created", body = PromoCodeResponse),
+        (status = 400, description = "Invalid or duplicate code", body = PromoCodeResponse),
+        (status = 403, description = "Only platform operators may create platform-wide codes", body = PromoCodeResponse),
+        (status = 409, description = "Failed to create code", body = PromoCodeResponse)
+    ),
+    summary = "Create a promo code for the canteen, or a platform-wide one",
+    description = "Codes created by a platform operator are platform-wide."
+)]
+#[post("/codes")]
+pub(super) async fn create_promo_code(
+    promo_ops: web::Data<PromoOperations>,
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+    req_data: web::Json<PromoCodeRequest>,
+) -> actix_web::Result<impl Responder> {
+    let Some(scope) = promo_scope(admin, operator) else {
+        return Ok(forbidden_response());
+    };
+    let Some(new_code) = new_code_from_request(scope, req_data.into_inner()) else {
+        return Ok(forbidden_response());
+    };
+    let result = web::block(move || promo_ops.create_code(new_code)).await?;
+    match result {
+        Ok(details) => {
+            debug!(
+                "create_promo_code: created code {} '{}' in {:?}",
+                details.promo.promo_id, details.promo.code, scope
+            );
+            Ok(HttpResponse::Ok().json(PromoCodeResponse {
+                status: "ok".to_string(),
+                data: Some(details),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "create_promo_code: failed to create code in {:?}: {}",
+                scope, e
+            );
+            let (status, message) = code_error_status(e);
+            Ok(code_error_response(status, message))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Promo",
+    responses(
+        (status = 200, description = "Successfully retrieved the promo codes", body = AllPromoCodesResponse),
+        (status = 403, description = "Neither a canteen admin nor a platform operator", body = AllPromoCodesResponse),
+        (status = 500, description = "Failed to retrieve promo codes", body = AllPromoCodesResponse)
+    ),
+    summary = "List the promo codes of the canteen, or the platform-wide codes for platform operators"
+)]
+#[get("/codes")]
+pub(super) async fn get_promo_codes(
+    promo_ops: web::Data<PromoOperations>,
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+) -> actix_web::Result<impl Responder> {
+    let Some(scope) = promo_scope(admin, operator) else {
+        return Ok(HttpResponse::Forbidden().json(AllPromoCodesResponse {
+            status: "error".to_string(),
+            data: Vec::new(),
+            error: Some("only canteen admins and platform operators have promo codes".to_string()),
+        }));
+    };
+    let result = web::block(move || promo_ops.list_codes(scope)).await?;
+    match result {
+        Ok(codes) => {
+            debug!(
+                "get_promo_codes: fetched {} codes of {:?}",
+                codes.len(),
+                scope
+            );
+            Ok(HttpResponse::Ok().json(AllPromoCodesResponse {
+                status: "ok".to_string(),
+                data: codes,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_promo_codes: failed to fetch codes of {:?}: {}",
+                scope, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(AllPromoCodesResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Promo",
+    params(
+        ("id", description = "The unique identifier of the promo code to replace"),
+    ),
+    request_body = PromoCodeRequest,
+    responses(
+        (status = 200, description = "Promo code replaced", body = PromoCodeResponse),
+        (status = 400, description = "Invalid or duplicate code", body = PromoCodeResponse),
+        (status = 403, description = "Promo code not found", body = PromoCodeResponse),
+        (status = 409, description = "Failed to update code", body = PromoCodeResponse)
+    ),
+    summary = "Replace a promo code"
+)]
+#[put("/codes/{id}")]
+pub(super) async fn update_promo_code(
+    promo_ops: web::Data<PromoOperations>,
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<PromoCodeRequest>,
+) -> actix_web::Result<impl Responder> {
+    let promo_id = path.into_inner().0;
+    let Some(scope) = promo_scope(admin, operator) else {
+        return Ok(forbidden_response());
+    };
+    let Some(changed_code) = new_code_from_request(scope, req_data.into_inner()) else {
+        return Ok(forbidden_response());
+    };
+    let result = web::block(move || promo_ops.update_code(promo_id, scope, changed_code)).await?;
+    match result {
+        Ok(details) => {
+            debug!("update_promo_code: replaced code {}", promo_id);
+            Ok(HttpResponse::Ok().json(PromoCodeResponse {
+                status: "ok".to_string(),
+                data: Some(details),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "update_promo_code: failed to update code {}: {}",
+                promo_id, e
+            );
+            let (status, message) = code_error_status(e);
+            Ok(code_error_response(status, message))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Promo",
+    params(
+        ("id", description = "The unique identifier of the promo code to delete"),
+    ),
+    responses(
+        (status = 200, description = "Promo code deleted", body = GeneralMenuResponse),
+        (status = 403, description = "Promo code not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to delete code", body = GeneralMenuResponse)
+    ),
+    summary = "Delete a promo code"
+)]
+#[delete("/codes/{id}")]
+pub(super) async fn delete_promo_code(
+    promo_ops: web::Data<PromoOperations>,
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let promo_id = path.into_inner().0;
+    let Some(scope) = promo_scope(admin, operator) else {
+        return Ok(HttpResponse::Forbidden().json(GeneralMenuResponse {
+            status: "error".to_string(),
+            error: Some("only platform operators may manage platform-wide codes".to_string()),
+        }));
+    };
+    let result = web::block(move || promo_ops.delete_code(promo_id, scope)).await?;
+    match result {
+        Ok(promo) => {
+            debug!("delete_promo_code: deleted code '{}'", promo.code);
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "delete_promo_code: failed to delete code {}: {}",
+                promo_id, e
+            );
+            let (status, message) = code_error_status(e);
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::auth::extractors::PrincipalExtractor;
+use crate::auth::principal::Principal;
+use crate::auth::AdminPrincipal;
+use crate::db::{RepositoryError, ReviewOperations};
+use crate::enums::common::{
+    ReviewModerationRequest, ReviewReplyRequest, ReviewResponse, ReviewsResponse,
+};
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn review_error(e: RepositoryError) -> HttpResponse {
+    let (status, message) = match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::NOT_FOUND, "review not found".to_string()),
+        other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+    };
+    HttpResponse::build(status).json(ReviewResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "Canteen",
+    responses(
+        (status = 200, description = "Reviews of the canteen, newest first", body = ReviewsResponse),
+        (status = 500, description = "Failed to load reviews", body = ReviewsResponse)
+    ),
+    summary = "List the reviews of a canteen",
+    description = "Admins get the reviews of their own canteen, including hidden ones; users get the visible reviews of the canteen in the path."
+)]
+#[get("/{id}/reviews")]
+pub(super) async fn get_canteen_reviews(
+    review_ops: web::Data<ReviewOperations>,
+    path: web::Path<(i32,)>,
+    principal: PrincipalExtractor,
+) -> actix_web::Result<impl Responder> {
+    let requested_canteen_id = path.into_inner().0;
+
+    // Admins are restricted to their own canteen; users can query by path id
+    let (review_canteen_id, include_hidden) = match principal.0 {
+        Principal::Admin { canteen_id } => (canteen_id, true),
+        Principal::User { .. } => (requested_canteen_id, false),
+    };
+    let result =
+        web::block(move || review_ops.list_reviews(review_canteen_id, include_hidden)).await?;
+    match result {
+        Ok(reviews) => {
+            debug!(
+                "get_canteen_reviews: fetched {} reviews of canteen {}",
+                reviews.len(),
+                review_canteen_id
+            );
+            Ok(HttpResponse::Ok().json(ReviewsResponse {
+                status: "ok".to_string(),
+                data: reviews,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_canteen_reviews: failed to retrieve reviews of {}: {}",
+                review_canteen_id, e
+            );
+            Ok(HttpResponse::InternalServerError().json(ReviewsResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Canteen",
+    params(
+        ("review_id", description = "A review of the admin's canteen"),
+    ),
+    request_body = ReviewModerationRequest,
+    responses(
+        (status = 200, description = "Review hidden or shown; average ratings are recalculated", body = ReviewResponse),
+        (status = 404, description = "No such review of the canteen", body = ReviewResponse),
+        (status = 500, description = "Failed to moderate the review", body = ReviewResponse)
+    ),
+    summary = "Hide or show a review",
+    description = "Hidden reviews are only listed for the canteen's admins and do not count towards average ratings."
+)]
+#[put("/reviews/{review_id}/moderation")]
+pub(super) async fn moderate_review(
+    review_ops: web::Data<ReviewOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<ReviewModerationRequest>,
+) -> actix_web::Result<impl Responder> {
+    let review_id = path.into_inner().0;
+    let hidden = req_data.into_inner().is_hidden;
+    let result =
+        web::block(move || review_ops.set_review_hidden(admin.canteen_id, review_id, hidden))
+            .await?;
+    match result {
+        Ok(review) => Ok(HttpResponse::Ok().json(ReviewResponse {
+            status: "ok".to_string(),
+            data: Some(review),
+            error: None,
+        })),
+        Err(e) => Ok(review_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "Canteen",
+    params(
+        ("review_id", description = "A review of the admin's canteen"),
+    ),
+    request_body = ReviewReplyRequest,
+    responses(
+        (status = 200, description = "Reply saved, replacing any earlier one", body = ReviewResponse),
+        (status = 400, description = "Reply too long", body = ReviewResponse),
+        (status = 404, description = "No such review of the canteen", body = ReviewResponse),
+        (status = 500, description = "Failed to save the reply", body = ReviewResponse)
+    ),
+    summary = "Reply to a review",
+    description = "A null or blank reply removes the canteen's reply."
+)]
+#[put("/reviews/{review_id}/reply")]
+pub(super) async fn reply_to_review(
+    review_ops: web::Data<ReviewOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<ReviewReplyRequest>,
+) -> actix_web::Result<impl Responder> {
+    let review_id = path.into_inner().0;
+    let reply = req_data.into_inner().reply;
+    let result =
+        web::block(move || review_ops.reply_to_review(admin.canteen_id, review_id, reply)).await?;
+    match result {
+        Ok(review) => Ok(HttpResponse::Ok().json(ReviewResponse {
+            status: "ok".to_string(),
+            data: Some(review),
+            error: None,
+        })),
+        Err(e) => Ok(review_error(e)),
+    }
+}
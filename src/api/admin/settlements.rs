@@ -0,0 +1,179 @@
+use crate::auth::{AdminPrincipal, OperatorPrincipal};
+use crate::db::{ReportRange, RepositoryError, SettlementOperations};
+use crate::enums::admin::{
+    PayoutRequest, SettlementLedgerResponse, SettlementQuery, SettlementResponse,
+};
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+/// The canteen whose ledger is shown: canteen admins see their own, platform operators the
+/// one they ask for.
+fn ledger_canteen_id(
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+    canteen_id: Option<i32>,
+) -> Result<i32, (StatusCode, String)> {
+    match (admin, operator) {
+        (Some(admin), _) => match canteen_id {
+            Some(canteen_id) if canteen_id != admin.canteen_id => Err((
+                StatusCode::FORBIDDEN,
+                "only platform operators can see other canteens".to_string(),
+            )),
+            _ => Ok(admin.canteen_id),
+        },
+        (None, Some(_)) => canteen_id.ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "canteen_id is required for platform operators".to_string(),
+            )
+        }),
+        (None, None) => Err((
+            StatusCode::FORBIDDEN,
+            "only canteen admins and platform operators have settlements".to_string(),
+        )),
+    }
+}
+
+#[utoipa::path(
+    tag = "Settlements",
+    params(SettlementQuery),
+    responses(
+        (status = 200, description = "Statements of the canteen over the range, the latest first", body = SettlementLedgerResponse),
+        (status = 400, description = "Empty or too long range, or no canteen given by a platform operator", body = SettlementLedgerResponse),
+        (status = 403, description = "Another canteen's statements, for a canteen admin", body = SettlementLedgerResponse),
+        (status = 500, description = "Failed to load the statements", body = SettlementLedgerResponse)
+    ),
+    summary = "List the canteen's daily settlement statements",
+    description = "Each statement covers one business day: online payments and refunds PhonePe completed that day, the platform commission on the difference and the net payable to the canteen, in paisa. Statements are generated once the day is over and never change afterwards; `paid_at` and `payout_reference` are set when the payout is recorded."
+)]
+#[get("")]
+pub(super) async fn get_settlements(
+    settlement_ops: web::Data<SettlementOperations>,
+    query: web::Query<SettlementQuery>,
+    admin: Option<AdminPrincipal>,
+    operator: Option<OperatorPrincipal>,
+) -> actix_web::Result<impl Responder> {
+    let SettlementQuery {
+        from,
+        to,
+        canteen_id,
+    } = query.into_inner();
+    let ledger_canteen_id = match ledger_canteen_id(admin, operator, canteen_id) {
+        Ok(ledger_canteen_id) => ledger_canteen_id,
+        Err((status, message)) => {
+            return Ok(HttpResponse::build(status).json(SettlementLedgerResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }));
+        }
+    };
+    let result = web::block(move || {
+        let range = ReportRange::resolve(from, to)?;
+        settlement_ops.ledger(ledger_canteen_id, &range)
+    })
+    .await?;
+    match result {
+        Ok(ledger) => {
+            debug!(
+                "get_settlements: {} statements of canteen {} from {} to {}",
+                ledger.statements.len(),
+                ledger_canteen_id,
+                ledger.from,
+                ledger.to
+            );
+            Ok(HttpResponse::Ok().json(SettlementLedgerResponse {
+                status: "ok".to_string(),
+                data: Some(ledger),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => {
+                    error!(
+                        "get_settlements: failed to load statements of canteen {}: {}",
+                        ledger_canteen_id, other
+                    );
+                    (StatusCode::INTERNAL_SERVER_ERROR, other.to_string())
+                }
+            };
+            Ok(HttpResponse::build(status).json(SettlementLedgerResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Settlements",
+    params(
+        ("settlement_id", description = "Statement to mark as paid out"),
+    ),
+    request_body = PayoutRequest,
+    responses(
+        (status = 200, description = "Payout recorded", body = SettlementResponse),
+        (status = 400, description = "Invalid reference, or the statement is already paid out", body = SettlementResponse),
+        (status = 403, description = "Not a platform operator", body = SettlementResponse),
+        (status = 404, description = "No such statement", body = SettlementResponse),
+        (status = 500, description = "Failed to record the payout", body = SettlementResponse)
+    ),
+    summary = "Record the payout of a settlement statement",
+    description = "For platform operators (`PLATFORM_OPERATOR_USER_IDS`) once the net payable has been transferred to the canteen. A statement is paid out once."
+)]
+#[put("/{settlement_id}/payout")]
+pub(super) async fn record_settlement_payout(
+    settlement_ops: web::Data<SettlementOperations>,
+    operator: Option<OperatorPrincipal>,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<PayoutRequest>,
+) -> actix_web::Result<impl Responder> {
+    let Some(operator) = operator else {
+        return Ok(HttpResponse::Forbidden().json(SettlementResponse {
+            status: "error".to_string(),
+            data: None,
+            error: Some("only platform operators can record payouts".to_string()),
+        }));
+    };
+    let settlement_id = path.into_inner().0;
+    let reference = req_data.into_inner().reference;
+    let result =
+        web::block(move || settlement_ops.record_payout(settlement_id, &reference)).await?;
+    match result {
+        Ok(settlement) => {
+            debug!(
+                "record_settlement_payout: operator {} paid out settlement {}",
+                operator.user_id, settlement_id
+            );
+            Ok(HttpResponse::Ok().json(SettlementResponse {
+                status: "ok".to_string(),
+                data: Some(settlement),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::NOT_FOUND, "settlement not found".to_string())
+                }
+                other => {
+                    error!(
+                        "record_settlement_payout: failed to record the payout of settlement {}: {}",
+                        settlement_id, other
+                    );
+                    (StatusCode::INTERNAL_SERVER_ERROR, other.to_string())
+                }
+            };
+            Ok(HttpResponse::build(status).json(SettlementResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
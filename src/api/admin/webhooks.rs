@@ -0,0 +1,276 @@
+use crate::auth::AdminPrincipal;
+use crate::db::{RepositoryError, WebhookOperations};
+use crate::enums::admin::{
+    AllWebhooksResponse, GeneralMenuResponse, WebhookDeliveriesQuery, WebhookDeliveriesResponse,
+    WebhookRequest, WebhookResponse,
+};
+use crate::models::admin::{
+    NewCanteenWebhook, UpdateCanteenWebhook, WEBHOOK_DELIVERIES_DEFAULT_LIMIT,
+};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn webhook_error_status(e: RepositoryError) -> (StatusCode, String) {
+    match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::FORBIDDEN, "webhook not found".to_string()),
+        other => (StatusCode::CONFLICT, other.to_string()),
+    }
+}
+
+fn webhook_error_response(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(WebhookResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "Webhooks",
+    request_body = WebhookRequest,
+    responses(
+        (status = 200, description = "Webhook registered", body = WebhookResponse),
+        (status = 400, description = "Invalid URL, secret or events", body = WebhookResponse),
+        (status = 409, description = "Failed to register webhook", body = WebhookResponse)
+    ),
+    summary = "Register a webhook for the canteen's order and stock events"
+)]
+#[post("")]
+pub(super) async fn create_webhook(
+    webhook_ops: web::Data<WebhookOperations>,
+    admin: AdminPrincipal,
+    req_data: web::Json<WebhookRequest>,
+) -> actix_web::Result<impl Responder> {
+    let req_data = req_data.into_inner();
+    let Some(secret) = req_data.secret else {
+        return Ok(webhook_error_response(
+            StatusCode::BAD_REQUEST,
+            "secret is required".to_string(),
+        ));
+    };
+    let new_webhook = NewCanteenWebhook {
+        canteen_id: admin.canteen_id,
+        url: req_data.url,
+        secret,
+        events: req_data.events,
+        is_active: req_data.is_active,
+    };
+    let result = web::block(move || webhook_ops.create_webhook(new_webhook)).await?;
+    match result {
+        Ok(webhook) => {
+            debug!(
+                "create_webhook: registered webhook {} for canteen {}",
+                webhook.webhook_id, admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(WebhookResponse {
+                status: "ok".to_string(),
+                data: Some(webhook),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "create_webhook: failed to register webhook for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            let (status, message) = webhook_error_status(e);
+            Ok(webhook_error_response(status, message))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Webhooks",
+    responses(
+        (status = 200, description = "Successfully retrieved the webhooks", body = AllWebhooksResponse),
+        (status = 500, description = "Failed to retrieve webhooks", body = AllWebhooksResponse)
+    ),
+    summary = "List the canteen's webhooks"
+)]
+#[get("")]
+pub(super) async fn get_webhooks(
+    webhook_ops: web::Data<WebhookOperations>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let result = web::block(move || webhook_ops.list_webhooks(admin.canteen_id)).await?;
+    match result {
+        Ok(webhooks) => {
+            debug!(
+                "get_webhooks: fetched {} webhooks for canteen {}",
+                webhooks.len(),
+                admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(AllWebhooksResponse {
+                status: "ok".to_string(),
+                data: webhooks,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_webhooks: failed to fetch webhooks for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(AllWebhooksResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Webhooks",
+    params(
+        ("id", description = "The unique identifier of the webhook to replace"),
+    ),
+    request_body = WebhookRequest,
+    responses(
+        (status = 200, description = "Webhook replaced", body = WebhookResponse),
+        (status = 400, description = "Invalid URL, secret or events", body = WebhookResponse),
+        (status = 403, description = "Webhook not found", body = WebhookResponse),
+        (status = 409, description = "Failed to update webhook", body = WebhookResponse)
+    ),
+    summary = "Replace a webhook, keeping its secret unless a new one is given"
+)]
+#[put("/{id}")]
+pub(super) async fn update_webhook(
+    webhook_ops: web::Data<WebhookOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<WebhookRequest>,
+) -> actix_web::Result<impl Responder> {
+    let webhook_id = path.into_inner().0;
+    let req_data = req_data.into_inner();
+    let changes = UpdateCanteenWebhook {
+        url: req_data.url,
+        secret: req_data.secret,
+        events: req_data.events,
+        is_active: req_data.is_active,
+    };
+    let result =
+        web::block(move || webhook_ops.update_webhook(webhook_id, admin.canteen_id, changes))
+            .await?;
+    match result {
+        Ok(webhook) => {
+            debug!("update_webhook: replaced webhook {}", webhook_id);
+            Ok(HttpResponse::Ok().json(WebhookResponse {
+                status: "ok".to_string(),
+                data: Some(webhook),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "update_webhook: failed to update webhook {}: {}",
+                webhook_id, e
+            );
+            let (status, message) = webhook_error_status(e);
+            Ok(webhook_error_response(status, message))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Webhooks",
+    params(
+        ("id", description = "The unique identifier of the webhook to delete"),
+    ),
+    responses(
+        (status = 200, description = "Webhook deleted", body = GeneralMenuResponse),
+        (status = 403, description = "Webhook not found", body = GeneralMenuResponse),
+        (status = 409, description = "Failed to delete webhook", body = GeneralMenuResponse)
+    ),
+    summary = "Delete a webhook along with its delivery log"
+)]
+#[delete("/{id}")]
+pub(super) async fn delete_webhook(
+    webhook_ops: web::Data<WebhookOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let webhook_id = path.into_inner().0;
+    let result =
+        web::block(move || webhook_ops.delete_webhook(webhook_id, admin.canteen_id)).await?;
+    match result {
+        Ok(webhook) => {
+            debug!("delete_webhook: deleted webhook {}", webhook.webhook_id);
+            Ok(HttpResponse::Ok().json(GeneralMenuResponse {
+                status: "ok".to_string(),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "delete_webhook: failed to delete webhook {}: {}",
+                webhook_id, e
+            );
+            let (status, message) = webhook_error_status(e);
+            Ok(HttpResponse::build(status).json(GeneralMenuResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Webhooks",
+    params(
+        ("id", description = "The unique identifier of the webhook"),
+        WebhookDeliveriesQuery
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved the deliveries", body = WebhookDeliveriesResponse),
+        (status = 400, description = "Unknown status", body = WebhookDeliveriesResponse),
+        (status = 403, description = "Webhook not found", body = WebhookDeliveriesResponse),
+        (status = 409, description = "Failed to retrieve deliveries", body = WebhookDeliveriesResponse)
+    ),
+    summary = "Inspect a webhook's most recent deliveries, newest first"
+)]
+#[get("/{id}/deliveries")]
+pub(super) async fn get_webhook_deliveries(
+    webhook_ops: web::Data<WebhookOperations>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    query: web::Query<WebhookDeliveriesQuery>,
+) -> actix_web::Result<impl Responder> {
+    let webhook_id = path.into_inner().0;
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(WEBHOOK_DELIVERIES_DEFAULT_LIMIT);
+    let result = web::block(move || {
+        webhook_ops.list_deliveries(webhook_id, admin.canteen_id, query.status, limit)
+    })
+    .await?;
+    match result {
+        Ok(deliveries) => {
+            debug!(
+                "get_webhook_deliveries: fetched {} deliveries of webhook {}",
+                deliveries.len(),
+                webhook_id
+            );
+            Ok(HttpResponse::Ok().json(WebhookDeliveriesResponse {
+                status: "ok".to_string(),
+                data: deliveries,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_webhook_deliveries: failed to fetch deliveries of webhook {}: {}",
+                webhook_id, e
+            );
+            let (status, message) = webhook_error_status(e);
+            Ok(HttpResponse::build(status).json(WebhookDeliveriesResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(message),
+            }))
+        }
+    }
+}
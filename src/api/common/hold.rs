@@ -1,67 +1,127 @@
 use crate::auth::{AdminPrincipal, UserPrincipal};
-use crate::db::HoldOperations;
-use crate::enums::common::{ConfirmHoldResponse, HoldOrderResponse, OrderRequest, OrderResponse};
-use crate::sse::{CanteenAggregatedOrderUpdateItem, SseEvent};
+use crate::db::{FavouriteOperations, HoldOperations, RepositoryError};
+use crate::enums::common::{
+    ConfirmHoldResponse, HoldOptions, HoldOrderResponse, OrderRequest, OrderResponse,
+    ReorderResponse, UnavailableItem,
+};
+use crate::models::common::sanitize_order_note;
+use crate::services::outbox::OutboxDispatcher;
+use actix_web::http::StatusCode;
 use actix_web::{delete, post, web, HttpResponse, Responder};
 use log::{debug, error};
 
+/// Whether `deliver_at` is absent or one of the delivery time bands.
+fn is_valid_time_band(deliver_at: Option<&str>) -> bool {
+    matches!(
+        deliver_at,
+        None | Some("11:00am - 12:00pm") | Some("12:00pm - 01:00pm")
+    )
+}
+
+fn hold_error_response(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(HoldOrderResponse {
+        status: "error".to_string(),
+        hold_id: None,
+        expires_at: None,
+        total_price: None,
+        applied_rules: Vec::new(),
+        applied_promo: None,
+        error: Some(message),
+    })
+}
+
 #[utoipa::path(
     tag = "Orders",
     request_body = OrderRequest,
     responses(
         (status = 200, description = "Order held successfully, stock reserved", body = HoldOrderResponse),
+        (status = 400, description = "Invalid time band or note, or not exactly one of item_ids and saved_cart_id", body = HoldOrderResponse),
+        (status = 404, description = "No such saved cart of the user", body = HoldOrderResponse),
         (status = 409, description = "Failed to hold order due to stock/validation issues", body = HoldOrderResponse)
     ),
-    summary = "Hold (reserve) an order for payment"
+    summary = "Hold (reserve) an order for payment",
+    description = "Holds the given item_ids, or the items of one of the user's saved carts given as saved_cart_id."
 )]
 #[post("")]
 pub(super) async fn hold_order(
     hold_ops: web::Data<HoldOperations>,
-    broker: web::Data<crate::sse::SseBroker>,
+    favourite_ops: web::Data<FavouriteOperations>,
+    outbox: web::Data<OutboxDispatcher>,
     user: UserPrincipal,
     req_data: web::Json<OrderRequest>,
 ) -> actix_web::Result<impl Responder> {
     let OrderRequest {
         deliver_at,
         item_ids,
+        saved_cart_id,
+        promo_code,
+        note,
     } = req_data.into_inner();
 
-    if deliver_at.is_some()
-        && (deliver_at != Some(String::from("11:00am - 12:00pm"))
-            && deliver_at != Some(String::from("12:00pm - 01:00pm")))
-    {
-        return Ok(HttpResponse::BadRequest().json(HoldOrderResponse {
-            status: "error".to_string(),
-            hold_id: None,
-            expires_at: None,
-            error: Some(format!(
-                "Invalid time band: {}",
-                deliver_at.unwrap_or_default()
-            )),
-        }));
+    if !is_valid_time_band(deliver_at.as_deref()) {
+        return Ok(hold_error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid time band: {}", deliver_at.unwrap_or_default()),
+        ));
     }
 
+    let note = match sanitize_order_note(note) {
+        Ok(note) => note,
+        Err(message) => return Ok(hold_error_response(StatusCode::BAD_REQUEST, message)),
+    };
+
     let uid = user.user_id();
+    let item_ids = match (item_ids, saved_cart_id) {
+        (Some(item_ids), None) => item_ids,
+        (None, Some(cart_id)) => {
+            match web::block(move || favourite_ops.saved_cart_item_ids(uid, cart_id)).await? {
+                Ok(item_ids) => item_ids,
+                Err(RepositoryError::NotFound(_)) => {
+                    return Ok(hold_error_response(
+                        StatusCode::NOT_FOUND,
+                        format!("Saved cart {cart_id} not found"),
+                    ));
+                }
+                Err(e) => {
+                    error!(
+                        "hold_order: failed to load saved cart {} of user {}: {}",
+                        cart_id, uid, e
+                    );
+                    return Ok(hold_error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        e.to_string(),
+                    ));
+                }
+            }
+        }
+        _ => {
+            return Ok(hold_error_response(
+                StatusCode::BAD_REQUEST,
+                "Give either item_ids or saved_cart_id".to_string(),
+            ));
+        }
+    };
     let deliver_at_cl = deliver_at.clone();
     let item_ids_cl = item_ids.clone();
-    let result = web::block(move || hold_ops.hold_order(uid, item_ids_cl, deliver_at_cl)).await?;
+    let result = web::block(move || {
+        hold_ops.hold_order_with_promo(uid, item_ids_cl, deliver_at_cl, promo_code, note)
+    })
+    .await?;
 
     match result {
-        Ok((hold_id, expires_at, (canteen_id, inventory_updates))) => {
+        Ok((hold_id, expires_at, (_, _, (total_price, applied_rules, applied_promo)))) => {
             debug!(
                 "hold_order: created hold {} for user {} with items {:?}",
                 hold_id, uid, item_ids
             );
-            broker.publish_canteen_subscription_event(
-                canteen_id,
-                &SseEvent::InventoryUpdate {
-                    items: inventory_updates,
-                },
-            );
+            outbox.wake();
             Ok(HttpResponse::Ok().json(HoldOrderResponse {
                 status: "ok".to_string(),
                 hold_id: Some(hold_id),
                 expires_at: Some(expires_at),
+                total_price: Some(total_price),
+                applied_rules,
+                applied_promo,
                 error: None,
             }))
         }
@@ -70,12 +130,7 @@ pub(super) async fn hold_order(
                 "hold_order: failed to hold order for user {} with items {:?}: {}",
                 uid, item_ids, e
             );
-            Ok(HttpResponse::Conflict().json(HoldOrderResponse {
-                status: "error".to_string(),
-                hold_id: None,
-                expires_at: None,
-                error: Some(e.to_string()),
-            }))
+            Ok(hold_error_response(StatusCode::CONFLICT, e.to_string()))
         }
     }
 }
@@ -94,7 +149,7 @@ pub(super) async fn hold_order(
 #[post("/{id}/confirm")]
 pub(super) async fn confirm_hold(
     hold_ops: web::Data<HoldOperations>,
-    broker: web::Data<crate::sse::SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     admin: AdminPrincipal,
     path: web::Path<(i32,)>,
 ) -> actix_web::Result<impl Responder> {
@@ -103,19 +158,12 @@ pub(super) async fn confirm_hold(
     let result = web::block(move || hold_ops.confirm_held_order_internal(hold_id)).await?;
 
     match result {
-        Ok((order_id, user_id, canteen_id, (time_band, aggregated_updates))) => {
+        Ok((order_id, user_id, canteen_id, _)) => {
             debug!(
                 "confirm_hold: admin canteen {} confirmed hold {} as order {} for user {}",
                 canteen_id, hold_id, order_id, user_id
             );
-            publish_confirmed_order_events(
-                &broker,
-                order_id,
-                user_id,
-                canteen_id,
-                time_band,
-                aggregated_updates,
-            );
+            outbox.wake();
 
             Ok(HttpResponse::Ok().json(ConfirmHoldResponse {
                 status: "ok".to_string(),
@@ -151,7 +199,7 @@ pub(super) async fn confirm_hold(
 #[delete("/{id}")]
 pub(super) async fn cancel_hold(
     hold_ops: web::Data<HoldOperations>,
-    broker: web::Data<crate::sse::SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     user: UserPrincipal,
     path: web::Path<(i32,)>,
 ) -> actix_web::Result<impl Responder> {
@@ -160,9 +208,9 @@ pub(super) async fn cancel_hold(
     let result = web::block(move || hold_ops.release_held_order(hold_id, uid)).await?;
 
     match result {
-        Ok((canteen_id, inventory_updates)) => {
+        Ok(_) => {
             debug!("cancel_hold: hold {} cancelled for user {}", hold_id, uid);
-            publish_cancel_hold_inventory_event(&broker, canteen_id, inventory_updates);
+            outbox.wake();
             Ok(HttpResponse::Ok().json(OrderResponse {
                 status: "ok".to_string(),
                 error: None,
@@ -181,49 +229,188 @@ pub(super) async fn cancel_hold(
     }
 }
 
-pub(super) fn publish_confirmed_order_events(
-    broker: &crate::sse::SseBroker,
-    order_id: i32,
-    user_id: i32,
-    canteen_id: i32,
-    time_band: String,
-    aggregated_updates: Vec<(i32, i32)>,
-) {
-    let aggregated_items = aggregated_updates
-        .into_iter()
-        .map(|(item_id, num_ordered)| CanteenAggregatedOrderUpdateItem {
-            item_id,
-            num_ordered,
-        })
-        .collect::<Vec<CanteenAggregatedOrderUpdateItem>>();
-    broker.publish_canteen_event(
-        canteen_id,
-        &SseEvent::CanteenAggregatedOrderUpdate {
-            time_band,
-            items: aggregated_items,
-        },
-    );
-    broker.publish_user_event(
-        user_id,
-        &SseEvent::UserOrderUpdate {
-            order_id,
-            status: "placed".to_string(),
-        },
-    );
+#[utoipa::path(
+    tag = "Orders",
+    params(
+        ("past_order_id", description = "ID of the user's past order to order again"),
+    ),
+    request_body(content = Option<HoldOptions>, description = "Optional delivery time band, promo code and note for the new hold"),
+    responses(
+        (status = 200, description = "Order held again; items that cannot be ordered now are listed and left out", body = ReorderResponse),
+        (status = 400, description = "Invalid time band or note", body = ReorderResponse),
+        (status = 404, description = "The user has no such past order", body = ReorderResponse),
+        (status = 409, description = "None of the items can be ordered now, or the hold failed", body = ReorderResponse)
+    ),
+    summary = "Order a past order again"
+)]
+#[post("/{past_order_id}")]
+pub(super) async fn reorder_past_order(
+    hold_ops: web::Data<HoldOperations>,
+    outbox: web::Data<OutboxDispatcher>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: Option<web::Json<HoldOptions>>,
+) -> actix_web::Result<impl Responder> {
+    let past_order_id = path.into_inner().0;
+    let HoldOptions {
+        deliver_at,
+        promo_code,
+        note,
+    } = req_data.map(web::Json::into_inner).unwrap_or_default();
+    let error_response = |error: String, unavailable_items: Vec<UnavailableItem>| ReorderResponse {
+        status: "error".to_string(),
+        hold_id: None,
+        expires_at: None,
+        total_price: None,
+        original_price: None,
+        price_difference: None,
+        applied_rules: Vec::new(),
+        applied_promo: None,
+        reduced_items: Vec::new(),
+        unavailable_items,
+        error: Some(error),
+    };
+
+    if !is_valid_time_band(deliver_at.as_deref()) {
+        return Ok(HttpResponse::BadRequest().json(error_response(
+            format!("Invalid time band: {}", deliver_at.unwrap_or_default()),
+            Vec::new(),
+        )));
+    }
+    let note = match sanitize_order_note(note) {
+        Ok(note) => note,
+        Err(message) => {
+            return Ok(HttpResponse::BadRequest().json(error_response(message, Vec::new())));
+        }
+    };
+
+    let uid = user.user_id();
+    let result = web::block(move || {
+        hold_ops.reorder_past_order(uid, past_order_id, deliver_at, promo_code, note)
+    })
+    .await?;
+
+    match result {
+        Ok((
+            Some((hold_id, expires_at, (_, _, (total_price, applied_rules, applied_promo)))),
+            original_price,
+            reduced_items,
+            unavailable_items,
+        )) => {
+            debug!(
+                "reorder_past_order: created hold {} for user {} from past order {}, {} items reduced, {} left out",
+                hold_id,
+                uid,
+                past_order_id,
+                reduced_items.len(),
+                unavailable_items.len()
+            );
+            outbox.wake();
+            Ok(HttpResponse::Ok().json(ReorderResponse {
+                status: "ok".to_string(),
+                hold_id: Some(hold_id),
+                expires_at: Some(expires_at),
+                total_price: Some(total_price),
+                original_price,
+                price_difference: original_price.map(|original| total_price - original),
+                applied_rules,
+                applied_promo,
+                reduced_items,
+                unavailable_items,
+                error: None,
+            }))
+        }
+        Ok((None, _, _, unavailable_items)) => {
+            debug!(
+                "reorder_past_order: nothing of past order {} can be ordered now",
+                past_order_id
+            );
+            Ok(HttpResponse::Conflict().json(error_response(
+                format!("None of the items of order {past_order_id} can be ordered now"),
+                unavailable_items,
+            )))
+        }
+        Err(RepositoryError::NotFound(_)) => Ok(HttpResponse::NotFound().json(error_response(
+            format!("Past order {past_order_id} not found"),
+            Vec::new(),
+        ))),
+        Err(e) => {
+            error!(
+                "reorder_past_order: failed to reorder past order {} for user {}: {}",
+                past_order_id, uid, e
+            );
+            Ok(HttpResponse::Conflict().json(error_response(e.to_string(), Vec::new())))
+        }
+    }
 }
 
-pub(super) fn publish_cancel_hold_inventory_event(
-    broker: &crate::sse::SseBroker,
-    canteen_id: i32,
-    inventory_updates: Vec<crate::sse::InventoryUpdateItems>,
-) {
-    if inventory_updates.is_empty() {
-        return;
+#[utoipa::path(
+    tag = "Orders",
+    params(
+        ("canteen_id", description = "Canteen of the user's cart to hold"),
+    ),
+    request_body(content = Option<HoldOptions>, description = "Optional delivery time band, promo code and note for the hold"),
+    responses(
+        (status = 200, description = "Cart held and emptied, stock reserved", body = HoldOrderResponse),
+        (status = 400, description = "Invalid time band or note", body = HoldOrderResponse),
+        (status = 409, description = "Cart is empty or cannot be held as it is; the cart is left unchanged", body = HoldOrderResponse)
+    ),
+    summary = "Hold (reserve) the user's cart at a canteen for payment"
+)]
+#[post("/cart/{canteen_id}")]
+pub(super) async fn hold_cart(
+    hold_ops: web::Data<HoldOperations>,
+    outbox: web::Data<OutboxDispatcher>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: Option<web::Json<HoldOptions>>,
+) -> actix_web::Result<impl Responder> {
+    let canteen_id = path.into_inner().0;
+    let HoldOptions {
+        deliver_at,
+        promo_code,
+        note,
+    } = req_data.map(web::Json::into_inner).unwrap_or_default();
+
+    if !is_valid_time_band(deliver_at.as_deref()) {
+        return Ok(hold_error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid time band: {}", deliver_at.unwrap_or_default()),
+        ));
+    }
+    let note = match sanitize_order_note(note) {
+        Ok(note) => note,
+        Err(message) => return Ok(hold_error_response(StatusCode::BAD_REQUEST, message)),
+    };
+
+    let uid = user.user_id();
+    let result =
+        web::block(move || hold_ops.hold_cart(uid, canteen_id, deliver_at, promo_code, note))
+            .await?;
+
+    match result {
+        Ok((hold_id, expires_at, (_, _, (total_price, applied_rules, applied_promo)))) => {
+            debug!(
+                "hold_cart: created hold {} for user {} from their cart at canteen {}",
+                hold_id, uid, canteen_id
+            );
+            outbox.wake();
+            Ok(HttpResponse::Ok().json(HoldOrderResponse {
+                status: "ok".to_string(),
+                hold_id: Some(hold_id),
+                expires_at: Some(expires_at),
+                total_price: Some(total_price),
+                applied_rules,
+                applied_promo,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "hold_cart: failed to hold the cart of user {} at canteen {}: {}",
+                uid, canteen_id, e
+            );
+            Ok(hold_error_response(StatusCode::CONFLICT, e.to_string()))
+        }
     }
-    broker.publish_canteen_subscription_event(
-        canteen_id,
-        &SseEvent::InventoryUpdate {
-            items: inventory_updates,
-        },
-    );
 }
@@ -1,6 +1,10 @@
 use crate::api::common::qr::QrConfig;
 use crate::api::ContentTypeHeader;
-use crate::db::{HoldOperations, OrderOperations, PaymentOperations, SearchOperations};
+use crate::db::{
+    FavouriteOperations, HoldOperations, OrderOperations, PaymentOperations, SearchOperations,
+    SupportOperations,
+};
+use crate::services::outbox::OutboxDispatcher;
 use crate::services::phonepe::PhonePeClient;
 use crate::sse::SseBroker;
 use actix_web::middleware::NormalizePath;
@@ -8,7 +12,10 @@ use actix_web::web;
 use hold::*;
 use orders::*;
 use qr::*;
+use realtime::*;
 use search::*;
+use support::*;
+use tickets::*;
 use utoipa_actix_web::scope;
 use utoipa_actix_web::service_config::ServiceConfig;
 
@@ -16,17 +23,23 @@ mod hold;
 mod orders;
 mod payments;
 pub mod qr;
+mod realtime;
 mod search;
+mod support;
+mod tickets;
 
 #[allow(clippy::too_many_arguments)]
 pub(super) fn config(
     cfg: &mut ServiceConfig,
     order_ops: &OrderOperations,
     hold_ops: &HoldOperations,
+    favourite_ops: &FavouriteOperations,
     payment_ops: &PaymentOperations,
     search_ops: &SearchOperations,
+    support_ops: &SupportOperations,
     sse_broker: &SseBroker,
     phonepe_client: &PhonePeClient,
+    outbox_dispatcher: &OutboxDispatcher,
     qr_cfg: QrConfig,
 ) {
     cfg.service(
@@ -34,10 +47,12 @@ pub(super) fn config(
             .wrap(NormalizePath::trim())
             .app_data(web::Data::new(order_ops.clone()))
             .app_data(web::Data::new(hold_ops.clone()))
-            .app_data(web::Data::new(sse_broker.clone()))
+            .app_data(web::Data::new(favourite_ops.clone()))
+            .app_data(web::Data::new(outbox_dispatcher.clone()))
             .app_data(web::Data::new(qr_cfg))
             .service(
                 scope::scope("/hold")
+                    .service(hold_cart)
                     .service(
                         scope::scope("")
                             .guard(ContentTypeHeader)
@@ -46,7 +61,9 @@ pub(super) fn config(
                     .service(confirm_hold)
                     .service(cancel_hold),
             )
+            .service(scope::scope("/reorder").service(reorder_past_order))
             .service(generate_order_qr)
+            .service(get_order_ticket)
             .service(
                 scope::scope("")
                     .guard(ContentTypeHeader)
@@ -62,7 +79,7 @@ pub(super) fn config(
             .wrap(NormalizePath::trim())
             .app_data(web::Data::new(hold_ops.clone()))
             .app_data(web::Data::new(payment_ops.clone()))
-            .app_data(web::Data::new(sse_broker.clone()))
+            .app_data(web::Data::new(outbox_dispatcher.clone()))
             .app_data(web::Data::new(phonepe_client.clone()))
             .service(payments::webhook_payment)
             .service(
@@ -73,6 +90,14 @@ pub(super) fn config(
                     .service(payments::verify_payment),
             ),
     )
+    .service(
+        scope::scope("/realtime")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(order_ops.clone()))
+            .app_data(web::Data::new(sse_broker.clone()))
+            .app_data(web::Data::new(outbox_dispatcher.clone()))
+            .service(realtime_socket),
+    )
     // Search Routes
     .service(
         scope::scope("/search")
@@ -80,8 +105,28 @@ pub(super) fn config(
             .app_data(web::Data::new(search_ops.clone()))
             .service(
                 scope::scope("")
+                    .service(search_menu)
+                    // Registered ahead of `/{query}`, which would otherwise take these paths.
+                    .service(get_search_suggestions)
+                    .service(get_search_insights)
                     .service(get_search_query_results)
                     .service(search_query_by_canteen),
             ),
+    )
+    .service(
+        scope::scope("/support")
+            .wrap(NormalizePath::trim())
+            .app_data(web::Data::new(support_ops.clone()))
+            .app_data(web::Data::new(outbox_dispatcher.clone()))
+            .service(list_support_tickets)
+            .service(get_support_ticket)
+            .service(upload_support_attachment)
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .service(create_support_ticket)
+                    .service(add_support_message)
+                    .service(update_support_ticket_status),
+            ),
     );
 }
@@ -1,11 +1,11 @@
 use crate::auth::extractors::PrincipalExtractor;
 use crate::auth::{AdminPrincipal, Principal};
-use crate::db::OrderOperations;
+use crate::db::{OrderOperations, RepositoryError};
 use crate::enums::common::{
     OrderItemsResponse, OrderResponse, OrdersItemsResponse, TimedActiveItemCount,
     TimedActiveItemCountResponse,
 };
-use crate::sse::{SseBroker, SseEvent};
+use crate::services::outbox::OutboxDispatcher;
 use actix_web::{get, put, web, HttpResponse, Responder};
 use log::{debug, error};
 use serde::Deserialize;
@@ -248,12 +248,12 @@ pub(super) async fn get_orders_by_user(
 #[put("/{id}/{action}")]
 pub(super) async fn order_actions(
     order_ops: web::Data<OrderOperations>,
-    broker: web::Data<SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     admin: AdminPrincipal,
     path: web::Path<(i32, String)>,
 ) -> actix_web::Result<impl Responder> {
     let (order_id, status) = path.into_inner();
-    if !(status == "delivered" || status == "cancelled") {
+    if !ORDER_ACTIONS.contains(&status.as_str()) {
         error!(
             "order_actions: failed to parse order with order_id {:?}: Invalid status: {:?}",
             order_id, status
@@ -265,25 +265,14 @@ pub(super) async fn order_actions(
             )),
         }));
     }
-    let status_cl = status.clone();
-    let status_for_db = status_cl.clone();
-    let canteen_id = admin.canteen_id;
     let result =
-        web::block(move || order_ops.order_actions(&order_id, &status_for_db, canteen_id)).await?;
+        perform_order_action(order_ops, &outbox, admin.canteen_id, order_id, &status).await?;
     match result {
-        Ok(user_id) => {
+        Ok(()) => {
             debug!(
                 "order_actions: successfully changed order with order_id {:?} to status {:?}",
                 order_id, status
             );
-            broker.publish_user_event(
-                user_id,
-                &SseEvent::UserOrderUpdate {
-                    //-> send the user a sse event as update for order
-                    order_id,
-                    status: status_cl,
-                },
-            );
             Ok(HttpResponse::Ok().json(OrderResponse {
                 status: "ok".to_string(),
                 error: None,
@@ -301,3 +290,24 @@ pub(super) async fn order_actions(
         }
     }
 }
+
+/// Statuses an admin can move an active order to.
+pub(crate) const ORDER_ACTIONS: [&str; 2] = ["delivered", "cancelled"];
+
+/// Move one of the canteen's orders to `status`, one of [`ORDER_ACTIONS`], and tell its user.
+/// Shared by the REST and realtime APIs.
+pub(crate) async fn perform_order_action(
+    order_ops: web::Data<OrderOperations>,
+    outbox: &OutboxDispatcher,
+    canteen_id: i32,
+    order_id: i32,
+    status: &str,
+) -> actix_web::Result<Result<(), RepositoryError>> {
+    let status_for_db = status.to_string();
+    let result =
+        web::block(move || order_ops.order_actions(&order_id, &status_for_db, canteen_id)).await?;
+    Ok(result.map(|_| {
+        //-> the user's update was queued with the order change
+        outbox.wake();
+    }))
+}
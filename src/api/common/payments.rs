@@ -1,12 +1,11 @@
-use super::hold::{publish_cancel_hold_inventory_event, publish_confirmed_order_events};
 use crate::auth::UserPrincipal;
 use crate::db::{HoldOperations, PaymentOperations, RepositoryError};
 use crate::enums::common::{
     InitiatePaymentRequest, InitiatePaymentResponse, VerifyPaymentRequest, VerifyPaymentResponse,
 };
-use crate::models::common::NewPaymentOrder;
+use crate::models::common::{NewPaymentOrder, REFUND_STATE_COMPLETED, REFUND_STATE_FAILED};
+use crate::services::outbox::OutboxDispatcher;
 use crate::services::phonepe::PhonePeClient;
-use crate::sse::{SseBroker, SseEvent};
 use actix_web::http::header::AUTHORIZATION;
 use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use chrono::{Duration, Utc};
@@ -286,6 +285,7 @@ async fn initiate_payment_with_api(
         payment_state: PAYMENT_STATE_CREATED.to_string(),
         phonepe_expires_at: Some(Utc::now() + Duration::seconds(expire_after)),
         app_order_id: None,
+        canteen_id: Some(hold_snapshot.canteen_id),
     };
     let stored_mapping = match payment_ops.create_mapping(mapping) {
         Ok(stored_mapping) => stored_mapping,
@@ -401,7 +401,7 @@ impl CreateApi {
 pub(super) async fn verify_payment(
     payment_ops: web::Data<PaymentOperations>,
     hold_ops: web::Data<HoldOperations>,
-    broker: web::Data<SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     phonepe_client: web::Data<PhonePeClient>,
     user: UserPrincipal,
     path: web::Path<(i32,)>,
@@ -463,17 +463,7 @@ pub(super) async fn verify_payment(
 
             let result = web::block(move || hold_ops.confirm_held_order(hold_id, uid)).await?;
             let order_id = match result {
-                Ok((order_id, user_id, canteen_id, (time_band, aggregated_updates))) => {
-                    publish_confirmed_order_events(
-                        &broker,
-                        order_id,
-                        user_id,
-                        canteen_id,
-                        time_band,
-                        aggregated_updates,
-                    );
-                    order_id
-                }
+                Ok((order_id, _, _, _)) => order_id,
                 Err(e) => {
                     error!(
                         "verify_payment: failed to confirm hold {} for user {} after completed payment: {}",
@@ -493,13 +483,7 @@ pub(super) async fn verify_payment(
                 PAYMENT_STATE_COMPLETED,
                 Some(order_id),
             );
-            publish_payment_update_event(
-                &broker,
-                uid,
-                hold_id,
-                &merchant_order_id,
-                PAYMENT_STATE_COMPLETED,
-            );
+            outbox.wake();
 
             Ok(HttpResponse::Ok().json(VerifyPaymentResponse {
                 status: "ok".to_string(),
@@ -511,13 +495,7 @@ pub(super) async fn verify_payment(
         PAYMENT_STATE_PENDING => {
             let _ =
                 payment_ops.update_mapping_state(&merchant_order_id, PAYMENT_STATE_PENDING, None);
-            publish_payment_update_event(
-                &broker,
-                uid,
-                hold_id,
-                &merchant_order_id,
-                PAYMENT_STATE_PENDING,
-            );
+            outbox.wake();
 
             Ok(HttpResponse::Conflict().json(VerifyPaymentResponse {
                 status: "error".to_string(),
@@ -527,33 +505,24 @@ pub(super) async fn verify_payment(
             }))
         }
         PAYMENT_STATE_FAILED => {
+            // The `payment_update` event is queued with the state change, so a payment that
+            // already completed here is left as it is and no FAILED update is sent for it.
             if mapping.payment_state != PAYMENT_STATE_COMPLETED {
                 let cancel_result =
                     web::block(move || hold_ops.release_held_order(hold_id, uid)).await?;
-                match cancel_result {
-                    Ok((canteen_id, inventory_updates)) => {
-                        publish_cancel_hold_inventory_event(&broker, canteen_id, inventory_updates);
-                    }
-                    Err(e) => {
-                        warn!(
-                            "verify_payment: failed to release hold {} for user {} after failed payment: {}",
-                            hold_id, uid, e
-                        );
-                    }
+                if let Err(e) = cancel_result {
+                    warn!(
+                        "verify_payment: failed to release hold {} for user {} after failed payment: {}",
+                        hold_id, uid, e
+                    );
                 }
                 let _ = payment_ops.update_mapping_state(
                     &merchant_order_id,
                     PAYMENT_STATE_FAILED,
                     None,
                 );
+                outbox.wake();
             }
-            publish_payment_update_event(
-                &broker,
-                uid,
-                hold_id,
-                &merchant_order_id,
-                PAYMENT_STATE_FAILED,
-            );
 
             Ok(HttpResponse::Conflict().json(VerifyPaymentResponse {
                 status: "error".to_string(),
@@ -582,14 +551,15 @@ pub(super) async fn verify_payment(
         (status = 400, description = "Webhook payload parsing failed", body = serde_json::Value),
         (status = 417, description = "Webhook authorization failed", body = serde_json::Value)
     ),
-    summary = "PhonePe payment webhook callback"
+    summary = "PhonePe payment webhook callback",
+    description = "Settles payments on `checkout.order.*` events and refunds on `pg.refund.*` events."
 )]
 #[post("/webhook")]
 pub(super) async fn webhook_payment(
     req: HttpRequest,
     payment_ops: web::Data<PaymentOperations>,
     hold_ops: web::Data<HoldOperations>,
-    broker: web::Data<SseBroker>,
+    outbox: web::Data<OutboxDispatcher>,
     phonepe_client: web::Data<PhonePeClient>,
     raw_body: web::Bytes,
 ) -> actix_web::Result<impl Responder> {
@@ -628,6 +598,10 @@ pub(super) async fn webhook_payment(
         .unwrap_or_default()
         .to_lowercase();
 
+    if event.starts_with("pg.refund") {
+        return settle_refund(payment_ops, body, &event).await;
+    }
+
     if !event.starts_with("checkout.order") {
         debug!(
             "webhook_payment: ignoring non-checkout event '{}', acknowledging with no-op",
@@ -699,17 +673,7 @@ pub(super) async fn webhook_payment(
         let confirm_result =
             web::block(move || hold_ops.confirm_held_order(hold_id, user_id)).await?;
         let confirmed_order_id = match confirm_result {
-            Ok((order_id, user_id, canteen_id, (time_band, aggregated_updates))) => {
-                publish_confirmed_order_events(
-                    &broker,
-                    order_id,
-                    user_id,
-                    canteen_id,
-                    time_band,
-                    aggregated_updates,
-                );
-                Some(order_id)
-            }
+            Ok((order_id, _, _, _)) => Some(order_id),
             Err(e) => {
                 warn!(
                     "webhook_payment: confirmation race for merchant_order_id {} hold {}: {}",
@@ -724,42 +688,87 @@ pub(super) async fn webhook_payment(
             PAYMENT_STATE_COMPLETED,
             confirmed_order_id,
         );
-        publish_payment_update_event(
-            &broker,
-            mapping.user_id,
-            mapping.hold_id,
-            &merchant_order_id,
-            PAYMENT_STATE_COMPLETED,
-        );
     } else {
         let hold_id = mapping.hold_id;
         let user_id = mapping.user_id;
         let release_result =
             web::block(move || hold_ops.release_held_order(hold_id, user_id)).await?;
-        match release_result {
-            Ok((canteen_id, inventory_updates)) => {
-                publish_cancel_hold_inventory_event(&broker, canteen_id, inventory_updates);
-            }
-            Err(e) => {
-                warn!(
-                    "webhook_payment: failed/redundant release for merchant_order_id {} hold {}: {}",
-                    merchant_order_id, mapping.hold_id, e
-                );
-            }
+        if let Err(e) = release_result {
+            warn!(
+                "webhook_payment: failed/redundant release for merchant_order_id {} hold {}: {}",
+                merchant_order_id, mapping.hold_id, e
+            );
         }
         let _ = payment_ops.update_mapping_state(&merchant_order_id, PAYMENT_STATE_FAILED, None);
-        publish_payment_update_event(
-            &broker,
-            mapping.user_id,
-            mapping.hold_id,
-            &merchant_order_id,
-            PAYMENT_STATE_FAILED,
-        );
     }
+    outbox.wake();
 
     Ok(HttpResponse::Ok().json(body))
 }
 
+/// Record the outcome PhonePe reports for a refund.
+async fn settle_refund(
+    payment_ops: web::Data<PaymentOperations>,
+    body: serde_json::Value,
+    event: &str,
+) -> actix_web::Result<HttpResponse> {
+    let new_state = match extract_webhook_state(&body).as_deref() {
+        Some("COMPLETED") => REFUND_STATE_COMPLETED,
+        Some("FAILED") => REFUND_STATE_FAILED,
+        other => {
+            debug!(
+                "webhook_payment: ignoring refund state {:?} for event '{}', acknowledging with no-op",
+                other, event
+            );
+            return Ok(HttpResponse::Ok().json(body));
+        }
+    };
+    let Some(merchant_refund_id) = body
+        .pointer("/payload/merchantRefundId")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+    else {
+        warn!(
+            "webhook_payment: missing merchantRefundId for event '{}', acknowledging with no-op",
+            event
+        );
+        return Ok(HttpResponse::Ok().json(body));
+    };
+    let reason = (new_state == REFUND_STATE_FAILED).then(|| {
+        body.pointer("/payload/detailedErrorCode")
+            .or_else(|| body.pointer("/payload/errorCode"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("refund failed at PhonePe")
+            .to_string()
+    });
+
+    let refund_id = merchant_refund_id.clone();
+    let updated = web::block(move || {
+        payment_ops.update_refund_state(&refund_id, new_state, reason.as_deref())
+    })
+    .await?;
+    match updated {
+        Ok(true) => debug!(
+            "webhook_payment: refund {} is now {}",
+            merchant_refund_id, new_state
+        ),
+        Ok(false) => debug!(
+            "webhook_payment: refund {} unknown or already settled",
+            merchant_refund_id
+        ),
+        Err(e) => {
+            error!(
+                "webhook_payment: failed to settle refund {}: {}",
+                merchant_refund_id, e
+            );
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "message": "Internal server error."
+            })));
+        }
+    }
+    Ok(HttpResponse::Ok().json(body))
+}
+
 fn conflict_initiate_response(e: RepositoryError) -> HttpResponse {
     match e {
         RepositoryError::NotFound(_) | RepositoryError::ValidationError(_) => {
@@ -790,23 +799,6 @@ fn conflict_initiate_response(e: RepositoryError) -> HttpResponse {
     }
 }
 
-fn publish_payment_update_event(
-    broker: &SseBroker,
-    user_id: i32,
-    hold_id: i32,
-    merchant_order_id: &str,
-    state: &str,
-) {
-    broker.publish_user_event(
-        user_id,
-        &SseEvent::PaymentUpdate {
-            hold_id,
-            merchant_order_id: merchant_order_id.to_string(),
-            payment_state: state.to_string(),
-        },
-    );
-}
-
 fn extract_webhook_merchant_order_id(value: &serde_json::Value) -> Option<String> {
     value
         .pointer("/payload/merchantOrderId")
@@ -0,0 +1,343 @@
+use super::orders::{perform_order_action, ORDER_ACTIONS};
+use crate::auth::extractors::PrincipalExtractor;
+use crate::auth::Principal;
+use crate::db::OrderOperations;
+use crate::enums::common::{RealtimeChannel, RealtimeCommand, RealtimeMessage, RealtimeRequest};
+use crate::services::outbox::OutboxDispatcher;
+use crate::sse::{ChannelEvent, SseBroker};
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{CloseCode, CloseReason, Codec, Frame, Message};
+use actix_web::body::BodyStream;
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use log::{debug, error, warn};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// A client that has not answered a ping in this long is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Frames queued for the client before the socket is considered stuck.
+const OUTBOUND_BACKLOG: usize = 64;
+/// Streams one socket may be subscribed to at a time.
+const MAX_SUBSCRIPTIONS: usize = 16;
+
+#[utoipa::path(
+    tag = "Realtime",
+    request_body(
+        content = RealtimeRequest,
+        description = "JSON text frames sent by the client after the upgrade: `subscribe`, `unsubscribe`, `ack` and `order_action`, each optionally with a `ref` echoed on its `ok` or `error` reply."
+    ),
+    responses(
+        (status = 101, description = "Upgraded to a WebSocket. The server sends `connected`, then JSON text frames: `event` for each event of a subscribed stream (`seq` is its SSE event id), `status` with `resync` when a replay is incomplete, and `ok`/`error` replies to commands. Clients must answer pings.", body = RealtimeMessage),
+        (status = 400, description = "Not a WebSocket upgrade request"),
+        (status = 401, description = "Auth token missing"),
+    ),
+    summary = "Open a realtime WebSocket multiplexing order and inventory streams",
+)]
+#[get("")]
+pub(super) async fn realtime_socket(
+    principal: PrincipalExtractor,
+    req: HttpRequest,
+    payload: web::Payload,
+    broker: web::Data<SseBroker>,
+    order_ops: web::Data<OrderOperations>,
+    outbox: web::Data<OutboxDispatcher>,
+) -> actix_web::Result<HttpResponse> {
+    let mut response = actix_http::ws::handshake(req.head())?;
+
+    let (out_tx, out_rx) = mpsc::channel::<Message>(OUTBOUND_BACKLOG);
+    // Every subscription shares this channel, so leave room for each one's replay.
+    let (event_tx, event_rx) = mpsc::channel::<ChannelEvent>(broker.channel_capacity() * 4);
+    let session = RealtimeSession {
+        principal: principal.0,
+        broker: broker.into_inner(),
+        order_ops,
+        outbox: outbox.into_inner(),
+        out_tx,
+        event_tx,
+        subscriptions: HashMap::new(),
+        acked: HashMap::new(),
+    };
+    actix_web::rt::spawn(session.run(payload, event_rx));
+
+    let frames = futures::stream::unfold(
+        (out_rx, Codec::new(), false),
+        |(mut out_rx, mut codec, closed)| async move {
+            if closed {
+                return None;
+            }
+            let message = out_rx.recv().await?;
+            let closing = matches!(message, Message::Close(_));
+            let mut buf = BytesMut::new();
+            let frame = codec
+                .encode(message, &mut buf)
+                .map(|_| buf.freeze())
+                .map_err(actix_web::error::ErrorInternalServerError);
+            Some((frame, (out_rx, codec, closing)))
+        },
+    );
+    Ok(HttpResponse::from(response.body(BodyStream::new(frames))).map_into_boxed_body())
+}
+
+type StreamKey = (RealtimeChannel, i32);
+
+/// One WebSocket: its subscriptions are ordinary broker connections that share the socket's
+/// event channel.
+struct RealtimeSession {
+    principal: Principal,
+    broker: std::sync::Arc<SseBroker>,
+    order_ops: web::Data<OrderOperations>,
+    outbox: std::sync::Arc<OutboxDispatcher>,
+    out_tx: mpsc::Sender<Message>,
+    event_tx: mpsc::Sender<ChannelEvent>,
+    subscriptions: HashMap<StreamKey, Uuid>,
+    acked: HashMap<StreamKey, u64>,
+}
+
+impl RealtimeSession {
+    async fn run(mut self, mut payload: web::Payload, mut event_rx: mpsc::Receiver<ChannelEvent>) {
+        let mut codec = Codec::new();
+        let mut buf = BytesMut::new();
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut last_heard = Instant::now();
+
+        let mut open = self.send(RealtimeMessage::Connected).await;
+        while open {
+            tokio::select! {
+                chunk = payload.next() => {
+                    let Some(Ok(chunk)) = chunk else {
+                        break;
+                    };
+                    last_heard = Instant::now();
+                    buf.extend_from_slice(&chunk);
+                    loop {
+                        match codec.decode(&mut buf) {
+                            Ok(Some(frame)) => {
+                                open = self.handle_frame(frame).await;
+                                if !open {
+                                    break;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(e) => {
+                                debug!("realtime_socket: protocol error: {}", e);
+                                self.close(CloseCode::Protocol).await;
+                                open = false;
+                                break;
+                            }
+                        }
+                    }
+                }
+                Some(event) = event_rx.recv() => {
+                    open = self.forward(event).await;
+                }
+                _ = heartbeat.tick() => {
+                    if last_heard.elapsed() > CLIENT_TIMEOUT {
+                        debug!("realtime_socket: client timed out");
+                        self.close(CloseCode::Away).await;
+                        break;
+                    }
+                    open = self.out_tx.send(Message::Ping(Bytes::new())).await.is_ok();
+                }
+            }
+        }
+
+        for ((channel, id), conn_id) in self.subscriptions.drain() {
+            unregister(&self.broker, channel, id, conn_id);
+        }
+    }
+
+    /// `false` once the socket should close.
+    async fn handle_frame(&mut self, frame: Frame) -> bool {
+        match frame {
+            Frame::Text(text) => {
+                let reply = match serde_json::from_slice::<RealtimeRequest>(&text) {
+                    Ok(request) => {
+                        let request_ref = request.request_ref;
+                        match self.handle_command(request.command).await {
+                            Ok(()) => RealtimeMessage::Ok { request_ref },
+                            Err(error) => RealtimeMessage::Error { request_ref, error },
+                        }
+                    }
+                    Err(e) => RealtimeMessage::Error {
+                        request_ref: None,
+                        error: format!("invalid command: {e}"),
+                    },
+                };
+                self.send(reply).await
+            }
+            Frame::Ping(bytes) => self.out_tx.send(Message::Pong(bytes)).await.is_ok(),
+            Frame::Pong(_) => true,
+            Frame::Close(reason) => {
+                let _ = self.out_tx.send(Message::Close(reason)).await;
+                false
+            }
+            Frame::Binary(_) | Frame::Continuation(_) => {
+                self.close(CloseCode::Unsupported).await;
+                false
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, command: RealtimeCommand) -> Result<(), String> {
+        match command {
+            RealtimeCommand::Subscribe {
+                channel,
+                id,
+                last_event_id,
+            } => {
+                let key = (channel, self.stream_id(channel, id)?);
+                if let Some(conn_id) = self.subscriptions.remove(&key) {
+                    // Subscribing again restarts the stream, replaying from the new position.
+                    unregister(&self.broker, key.0, key.1, conn_id);
+                } else if self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return Err(format!(
+                        "a socket can subscribe to at most {MAX_SUBSCRIPTIONS} streams"
+                    ));
+                }
+                let last_event_id = last_event_id.or_else(|| self.acked.get(&key).copied());
+                let conn_id = Uuid::new_v4();
+                let tx = self.event_tx.clone();
+                match key.0 {
+                    RealtimeChannel::UserOrders => {
+                        self.broker
+                            .register_user_connection(key.1, conn_id, tx, last_event_id)
+                    }
+                    RealtimeChannel::CanteenOrders => {
+                        self.broker
+                            .register_canteen_connection(key.1, conn_id, tx, last_event_id)
+                    }
+                    RealtimeChannel::CanteenInventory => {
+                        self.broker
+                            .register_canteen_subscription(key.1, conn_id, tx, last_event_id)
+                    }
+                }
+                self.subscriptions.insert(key, conn_id);
+                Ok(())
+            }
+            RealtimeCommand::Unsubscribe { channel, id } => {
+                let key = (channel, self.stream_id(channel, id)?);
+                let conn_id = self
+                    .subscriptions
+                    .remove(&key)
+                    .ok_or_else(|| "not subscribed to this stream".to_string())?;
+                unregister(&self.broker, key.0, key.1, conn_id);
+                Ok(())
+            }
+            RealtimeCommand::Ack { channel, id, seq } => {
+                let key = (channel, self.stream_id(channel, id)?);
+                if !self.subscriptions.contains_key(&key) {
+                    return Err("not subscribed to this stream".to_string());
+                }
+                let acked = self.acked.entry(key).or_default();
+                *acked = (*acked).max(seq);
+                Ok(())
+            }
+            RealtimeCommand::OrderAction { order_id, action } => {
+                let Principal::Admin { canteen_id } = self.principal else {
+                    return Err("order actions are only available to canteen admins".to_string());
+                };
+                if !ORDER_ACTIONS.contains(&action.as_str()) {
+                    return Err(format!(
+                        "action cannot be {action}, must be either \"delivered\" or \"cancelled\"."
+                    ));
+                }
+                match perform_order_action(
+                    self.order_ops.clone(),
+                    &self.outbox,
+                    canteen_id,
+                    order_id,
+                    &action,
+                )
+                .await
+                {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(e)) => {
+                        error!(
+                            "realtime_socket: failed to change order with order_id {:?} to status {:?}: {}",
+                            order_id, action, e
+                        );
+                        Err(e.to_string())
+                    }
+                    Err(e) => {
+                        error!("realtime_socket: order action {} failed: {}", order_id, e);
+                        Err("failed to perform order action".to_string())
+                    }
+                }
+            }
+        }
+    }
+
+    /// The stream a principal means by `id` on `channel`, if it may subscribe to it.
+    fn stream_id(&self, channel: RealtimeChannel, id: Option<i32>) -> Result<i32, String> {
+        let own_id = match (channel, &self.principal) {
+            (RealtimeChannel::UserOrders, Principal::User { user_id, .. }) => *user_id,
+            (RealtimeChannel::UserOrders, _) => {
+                return Err("user_orders is only available to users".to_string())
+            }
+            (RealtimeChannel::CanteenOrders, Principal::Admin { canteen_id }) => *canteen_id,
+            (RealtimeChannel::CanteenOrders, _) => {
+                return Err("canteen_orders is only available to canteen admins".to_string())
+            }
+            (RealtimeChannel::CanteenInventory, _) => {
+                return id.ok_or_else(|| "canteen_inventory needs a canteen id".to_string())
+            }
+        };
+        match id {
+            Some(id) if id != own_id => {
+                Err("cannot subscribe to another account's stream".to_string())
+            }
+            _ => Ok(own_id),
+        }
+    }
+
+    async fn forward(&self, event: ChannelEvent) -> bool {
+        let Some(channel) = RealtimeChannel::from_stream(event.channel) else {
+            warn!(
+                "realtime_socket: event of unknown stream '{}'",
+                event.channel
+            );
+            return true;
+        };
+        let message = match event.seq {
+            Some(seq) => RealtimeMessage::Event {
+                channel,
+                id: event.stream_id,
+                seq,
+                event: event.event,
+                data: serde_json::from_str(&event.data)
+                    .unwrap_or(serde_json::Value::String(event.data)),
+            },
+            None => RealtimeMessage::Status {
+                channel,
+                id: event.stream_id,
+                status: event.data,
+            },
+        };
+        self.send(message).await
+    }
+
+    async fn send(&self, message: RealtimeMessage) -> bool {
+        let text = serde_json::to_string(&message).expect("realtime messages serialize");
+        self.out_tx.send(Message::Text(text.into())).await.is_ok()
+    }
+
+    async fn close(&self, code: CloseCode) {
+        let _ = self
+            .out_tx
+            .send(Message::Close(Some(CloseReason::from(code))))
+            .await;
+    }
+}
+
+fn unregister(broker: &SseBroker, channel: RealtimeChannel, id: i32, conn_id: Uuid) {
+    match channel {
+        RealtimeChannel::UserOrders => broker.unregister_user_connection(id, conn_id),
+        RealtimeChannel::CanteenOrders => broker.unregister_canteen_connection(id, conn_id),
+        RealtimeChannel::CanteenInventory => broker.unregister_canteen_subscription(id, conn_id),
+    }
+}
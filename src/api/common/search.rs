@@ -1,12 +1,164 @@
-use crate::db::SearchOperations;
-use crate::enums::admin::AllItemsResponse;
+use crate::auth::extractors::PrincipalExtractor;
+use crate::auth::{AdminPrincipal, Principal};
+use crate::db::{SearchOperations, SEARCH_INSIGHTS_DEFAULT_DAYS, SEARCH_INSIGHTS_DEFAULT_LIMIT};
+use crate::enums::admin::{
+    AllItemsResponse, MenuFilterQuery, MenuSearchQuery, MenuSearchResponse, SearchInsightsQuery,
+    SearchInsightsResponse, SearchSuggestQuery, SearchSuggestionsResponse,
+};
 use actix_web::{get, web, HttpResponse, Responder};
 use log::{debug, error};
 
+/// Users get their saved allergen exclusions applied to the results; admins do not.
+fn viewer_id(principal: PrincipalExtractor) -> Option<i32> {
+    match principal.0 {
+        Principal::User { user_id, .. } => Some(user_id),
+        Principal::Admin { .. } => None,
+    }
+}
+
+#[utoipa::path(
+    tag = "Search",
+    params(MenuSearchQuery),
+    responses(
+        (status = 200, description = "One page of matching menu items with the total and facet counts", body = MenuSearchResponse),
+        (status = 400, description = "Invalid filters or paging", body = MenuSearchResponse)
+    ),
+    summary = "Search menu item names and descriptions with filters, paging and facets"
+)]
+#[get("")]
+pub(super) async fn search_menu(
+    search_ops: web::Data<SearchOperations>,
+    query: web::Query<MenuSearchQuery>,
+    principal: PrincipalExtractor,
+) -> actix_web::Result<impl Responder> {
+    let viewer_id = viewer_id(principal);
+    let query = query.into_inner();
+    let result = search_ops.search_menu(&query, viewer_id).await;
+    match result {
+        Ok(results) => {
+            debug!(
+                "search_menu: {} items matched query {:?}, returning page {}",
+                results.total, query.q, results.page
+            );
+            Ok(HttpResponse::Ok().json(MenuSearchResponse {
+                status: "ok".to_string(),
+                data: Some(results),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("search_menu: search failed for query {:?}: {}", query.q, e);
+            Ok(HttpResponse::BadRequest().json(MenuSearchResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Search",
+    params(SearchSuggestQuery),
+    responses(
+        (status = 200, description = "Item names, canteens and popular searches matching the typed text", body = SearchSuggestionsResponse),
+        (status = 400, description = "Missing or overly long text", body = SearchSuggestionsResponse)
+    ),
+    summary = "Suggest searches as the user types"
+)]
+#[get("/suggest")]
+pub(super) async fn get_search_suggestions(
+    search_ops: web::Data<SearchOperations>,
+    query: web::Query<SearchSuggestQuery>,
+    _principal: PrincipalExtractor,
+) -> actix_web::Result<impl Responder> {
+    let query = query.into_inner();
+    let typed = query.q.clone();
+    let result = web::block(move || search_ops.suggest(&query)).await?;
+    match result {
+        Ok(suggestions) => {
+            debug!(
+                "get_search_suggestions: {} items, {} canteens and {} queries for '{}'",
+                suggestions.items.len(),
+                suggestions.canteens.len(),
+                suggestions.queries.len(),
+                typed
+            );
+            Ok(HttpResponse::Ok().json(SearchSuggestionsResponse {
+                status: "ok".to_string(),
+                data: Some(suggestions),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_search_suggestions: failed to suggest for '{}': {}",
+                typed, e
+            );
+            Ok(HttpResponse::BadRequest().json(SearchSuggestionsResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(e.to_string()),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Search",
+    params(SearchInsightsQuery),
+    responses(
+        (status = 200, description = "Popular and no-result searches", body = SearchInsightsResponse),
+        (status = 500, description = "Failed to load search insights", body = SearchInsightsResponse)
+    ),
+    summary = "Show the canteen what people search for and what they do not find"
+)]
+#[get("/insights")]
+pub(super) async fn get_search_insights(
+    search_ops: web::Data<SearchOperations>,
+    query: web::Query<SearchInsightsQuery>,
+    admin: AdminPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let query = query.into_inner();
+    let days = query.days.unwrap_or(SEARCH_INSIGHTS_DEFAULT_DAYS);
+    let limit = query.limit.unwrap_or(SEARCH_INSIGHTS_DEFAULT_LIMIT);
+    let result =
+        web::block(move || search_ops.search_insights(admin.canteen_id, days, limit)).await?;
+    match result {
+        Ok(insights) => {
+            debug!(
+                "get_search_insights: {} popular and {} no-result queries for canteen {}",
+                insights.popular.len(),
+                insights.no_results.len(),
+                admin.canteen_id
+            );
+            Ok(HttpResponse::Ok().json(SearchInsightsResponse {
+                status: "ok".to_string(),
+                data: Some(insights),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_search_insights: failed to load insights for canteen {}: {}",
+                admin.canteen_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(SearchInsightsResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
 #[utoipa::path(
     tag = "Search",
     params(
-        ("query", description = "The search query used to perform a fuzzy match on menu item names."),
+        ("query", description = "The search query matched against menu item names and descriptions."),
+        MenuFilterQuery,
     ),
     responses(
         (status = 200, description = "Successfully retrieved fuzzy search results for menu items", body = AllItemsResponse)
@@ -17,10 +169,15 @@ use log::{debug, error};
 pub(super) async fn get_search_query_results(
     search_ops: web::Data<SearchOperations>,
     path: web::Path<(String,)>,
+    filter: web::Query<MenuFilterQuery>,
+    principal: PrincipalExtractor,
 ) -> actix_web::Result<impl Responder> {
+    let viewer_id = viewer_id(principal);
     let search_query = path.into_inner().0;
     let search_query_cl = search_query.clone();
-    let result = search_ops.search_menu_items(&search_query_cl).await;
+    let result = search_ops
+        .search_menu_items(&search_query_cl, &filter.into_inner(), viewer_id)
+        .await;
     match result {
         Ok(x) => {
             debug!(
@@ -50,7 +207,8 @@ pub(super) async fn get_search_query_results(
 #[utoipa::path(
     tag = "Search",
     params(
-        ("query", description = "The search query used to perform a fuzzy match on menu item names."),
+        ("query", description = "The search query matched against menu item names and descriptions."),
+        MenuFilterQuery,
     ),
     responses(
         (status = 200, description = "Successfully retrieved fuzzy search results for menu items", body = AllItemsResponse)
@@ -61,11 +219,19 @@ pub(super) async fn get_search_query_results(
 pub(super) async fn search_query_by_canteen(
     search_ops: web::Data<SearchOperations>,
     path: web::Path<(i32, String)>,
+    filter: web::Query<MenuFilterQuery>,
+    principal: PrincipalExtractor,
 ) -> actix_web::Result<impl Responder> {
+    let viewer_id = viewer_id(principal);
     let (canteen_id, search_query) = path.into_inner();
     let search_query_cl = search_query.clone();
     let result = search_ops
-        .search_menu_items_by_canteen(&canteen_id, &search_query_cl)
+        .search_menu_items_by_canteen(
+            &canteen_id,
+            &search_query_cl,
+            &filter.into_inner(),
+            viewer_id,
+        )
         .await;
     match result {
         Ok(x) => {
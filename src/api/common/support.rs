@@ -0,0 +1,311 @@
+use crate::auth::extractors::PrincipalExtractor;
+use crate::auth::principal::Principal;
+use crate::auth::{AdminPrincipal, UserPrincipal};
+use crate::db::{RepositoryError, SupportOperations, SupportParty};
+use crate::enums::common::{
+    CreateSupportTicketRequest, SupportAttachmentUploadResponse, SupportMessageRequest,
+    SupportTicketQuery, SupportTicketResponse, SupportTicketStatus, SupportTicketStatusRequest,
+    SupportTicketsResponse,
+};
+use crate::services::outbox::OutboxDispatcher;
+use actix_web::http::StatusCode;
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use log::{debug, error};
+
+fn party_of(principal: Principal) -> SupportParty {
+    match principal {
+        Principal::Admin { canteen_id } => SupportParty::Canteen(canteen_id),
+        Principal::User { user_id, .. } => SupportParty::User(user_id),
+    }
+}
+
+fn support_error(e: RepositoryError) -> HttpResponse {
+    let (status, message) = match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+        other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+    };
+    HttpResponse::build(status).json(SupportTicketResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "Support",
+    request_body = CreateSupportTicketRequest,
+    responses(
+        (status = 200, description = "Ticket opened with the message as its first", body = SupportTicketResponse),
+        (status = 400, description = "Invalid message, or the order already has an open ticket", body = SupportTicketResponse),
+        (status = 404, description = "No such order of the user", body = SupportTicketResponse),
+        (status = 500, description = "Failed to open the ticket", body = SupportTicketResponse)
+    ),
+    summary = "Raise a complaint about an order",
+    description = "Opens a ticket with the canteen the order was placed at. An order can have one open ticket at a time."
+)]
+#[post("/tickets")]
+pub(super) async fn create_support_ticket(
+    support_ops: web::Data<SupportOperations>,
+    user: UserPrincipal,
+    req_data: web::Json<CreateSupportTicketRequest>,
+) -> actix_web::Result<impl Responder> {
+    let user_id = user.user_id();
+    let CreateSupportTicketRequest {
+        order_id,
+        category,
+        message,
+    } = req_data.into_inner();
+    let result =
+        web::block(move || support_ops.create_ticket(user_id, order_id, category, message)).await?;
+    match result {
+        Ok(ticket) => {
+            debug!(
+                "create_support_ticket: user {} opened ticket {} on order {}",
+                user_id, ticket.ticket.ticket_id, order_id
+            );
+            Ok(HttpResponse::Ok().json(SupportTicketResponse {
+                status: "ok".to_string(),
+                data: Some(ticket),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(support_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "Support",
+    params(SupportTicketQuery),
+    responses(
+        (status = 200, description = "Tickets, most recently updated first", body = SupportTicketsResponse),
+        (status = 400, description = "Unknown status", body = SupportTicketsResponse),
+        (status = 500, description = "Failed to load tickets", body = SupportTicketsResponse)
+    ),
+    summary = "List support tickets",
+    description = "Users get the tickets they raised; admins get the tickets of their canteen."
+)]
+#[get("/tickets")]
+pub(super) async fn list_support_tickets(
+    support_ops: web::Data<SupportOperations>,
+    principal: PrincipalExtractor,
+    query: web::Query<SupportTicketQuery>,
+) -> actix_web::Result<impl Responder> {
+    let party = party_of(principal.0);
+    let status = match query.into_inner().status {
+        Some(raw) => match SupportTicketStatus::parse(&raw) {
+            Some(status) => Some(status),
+            None => {
+                return Ok(HttpResponse::BadRequest().json(SupportTicketsResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(format!("unknown ticket status {raw}")),
+                }))
+            }
+        },
+        None => None,
+    };
+    let result = web::block(move || support_ops.list_tickets(party, status)).await?;
+    match result {
+        Ok(tickets) => Ok(HttpResponse::Ok().json(SupportTicketsResponse {
+            status: "ok".to_string(),
+            data: tickets,
+            error: None,
+        })),
+        Err(e) => {
+            error!(
+                "list_support_tickets: failed to retrieve tickets of {:?}: {}",
+                party, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(SupportTicketsResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Support",
+    params(
+        ("ticket_id", description = "A ticket of the user, or of the admin's canteen"),
+    ),
+    responses(
+        (status = 200, description = "The ticket with its thread and refunds", body = SupportTicketResponse),
+        (status = 404, description = "No such ticket", body = SupportTicketResponse),
+        (status = 500, description = "Failed to load the ticket", body = SupportTicketResponse)
+    ),
+    summary = "Get a support ticket",
+    description = "Attachments come with download links valid for 12 hours."
+)]
+#[get("/tickets/{ticket_id}")]
+pub(super) async fn get_support_ticket(
+    support_ops: web::Data<SupportOperations>,
+    principal: PrincipalExtractor,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let party = party_of(principal.0);
+    let ticket_id = path.into_inner().0;
+    let ops = support_ops.clone();
+    let result = web::block(move || ops.get_ticket(party, ticket_id)).await?;
+    match result {
+        Ok(mut ticket) => {
+            support_ops.link_attachments(&mut ticket).await;
+            Ok(HttpResponse::Ok().json(SupportTicketResponse {
+                status: "ok".to_string(),
+                data: Some(ticket),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(support_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "Support",
+    params(
+        ("ticket_id", description = "A ticket of the user, or of the admin's canteen"),
+    ),
+    request_body = SupportMessageRequest,
+    responses(
+        (status = 200, description = "Message added to the thread", body = SupportTicketResponse),
+        (status = 400, description = "Invalid message or attachments, or the ticket is closed", body = SupportTicketResponse),
+        (status = 404, description = "No such ticket", body = SupportTicketResponse),
+        (status = 500, description = "Failed to add the message", body = SupportTicketResponse)
+    ),
+    summary = "Reply on a support ticket",
+    description = "A reply from the canteen takes an open ticket in progress; a message from the user reopens a resolved ticket. Attachments are keys from the upload endpoint of files already uploaded."
+)]
+#[post("/tickets/{ticket_id}/messages")]
+pub(super) async fn add_support_message(
+    support_ops: web::Data<SupportOperations>,
+    principal: PrincipalExtractor,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<SupportMessageRequest>,
+) -> actix_web::Result<impl Responder> {
+    let party = party_of(principal.0);
+    let ticket_id = path.into_inner().0;
+    let SupportMessageRequest {
+        message,
+        attachments,
+    } = req_data.into_inner();
+    match support_ops
+        .add_message(party, ticket_id, message, attachments)
+        .await
+    {
+        Ok(mut ticket) => {
+            support_ops.link_attachments(&mut ticket).await;
+            Ok(HttpResponse::Ok().json(SupportTicketResponse {
+                status: "ok".to_string(),
+                data: Some(ticket),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(support_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "Support",
+    params(
+        ("ticket_id", description = "A ticket of the user, or of the admin's canteen"),
+    ),
+    responses(
+        (status = 200, description = "Presigned URL to upload the attachment to", body = SupportAttachmentUploadResponse),
+        (status = 400, description = "The ticket is closed", body = SupportAttachmentUploadResponse),
+        (status = 404, description = "No such ticket", body = SupportAttachmentUploadResponse),
+        (status = 500, description = "Failed to sign the upload", body = SupportAttachmentUploadResponse)
+    ),
+    summary = "Upload an attachment for a support ticket",
+    description = "`PUT` the file to `upload_url` within 5 minutes, then send `attachment_key` with the message it belongs to."
+)]
+#[post("/tickets/{ticket_id}/attachments")]
+pub(super) async fn upload_support_attachment(
+    support_ops: web::Data<SupportOperations>,
+    principal: PrincipalExtractor,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let party = party_of(principal.0);
+    let ticket_id = path.into_inner().0;
+    match support_ops.attachment_upload_url(party, ticket_id).await {
+        Ok((upload_url, attachment_key)) => {
+            Ok(HttpResponse::Ok().json(SupportAttachmentUploadResponse {
+                status: "ok".to_string(),
+                upload_url: Some(upload_url),
+                attachment_key: Some(attachment_key),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+                other => {
+                    error!(
+                        "upload_support_attachment: failed to sign an upload for ticket {}: {}",
+                        ticket_id, other
+                    );
+                    (StatusCode::INTERNAL_SERVER_ERROR, other.to_string())
+                }
+            };
+            Ok(
+                HttpResponse::build(status).json(SupportAttachmentUploadResponse {
+                    status: "error".to_string(),
+                    upload_url: None,
+                    attachment_key: None,
+                    error: Some(message),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "Support",
+    params(
+        ("ticket_id", description = "A ticket of the admin's canteen"),
+    ),
+    request_body = SupportTicketStatusRequest,
+    responses(
+        (status = 200, description = "Ticket moved; any refund is on its way to PhonePe", body = SupportTicketResponse),
+        (status = 400, description = "The ticket is closed, or the refund is not possible", body = SupportTicketResponse),
+        (status = 404, description = "No such ticket of the canteen", body = SupportTicketResponse),
+        (status = 500, description = "Failed to update the ticket", body = SupportTicketResponse)
+    ),
+    summary = "Move a support ticket to another status",
+    description = "Resolving a ticket can refund part or all of the order's online payment; refunds of one payment add up to at most what was paid. Closed tickets cannot be changed."
+)]
+#[put("/tickets/{ticket_id}/status")]
+pub(super) async fn update_support_ticket_status(
+    support_ops: web::Data<SupportOperations>,
+    outbox: web::Data<OutboxDispatcher>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<SupportTicketStatusRequest>,
+) -> actix_web::Result<impl Responder> {
+    let ticket_id = path.into_inner().0;
+    let SupportTicketStatusRequest {
+        status,
+        refund_amount,
+    } = req_data.into_inner();
+    let result = web::block(move || {
+        support_ops.update_status(admin.canteen_id, ticket_id, status, refund_amount)
+    })
+    .await?;
+    match result {
+        Ok(ticket) => {
+            if refund_amount.is_some() {
+                outbox.wake();
+            }
+            Ok(HttpResponse::Ok().json(SupportTicketResponse {
+                status: "ok".to_string(),
+                data: Some(ticket),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(support_error(e)),
+    }
+}
@@ -0,0 +1,90 @@
+use crate::api::common::qr::QrConfig;
+use crate::auth::qr_token;
+use crate::auth::AdminPrincipal;
+use crate::db::{OrderOperations, RepositoryError};
+use crate::enums::common::{OrderResponse, TicketQuery};
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use crate::services::tickets::TicketFormat;
+use actix_web::{get, web, HttpResponse, Responder};
+use log::{debug, error};
+
+#[utoipa::path(
+    tag = "Orders",
+    params(
+        ("id", description = "The active order to print"),
+        TicketQuery
+    ),
+    responses(
+        (status = 200, description = "The kitchen ticket: ESC/POS bytes, or text/plain or text/html for those formats", content_type = "application/octet-stream"),
+        (status = 400, description = "Unknown format", body = OrderResponse),
+        (status = 404, description = "No such active order at this canteen", body = OrderResponse),
+        (status = 500, description = "Failed to render the ticket", body = OrderResponse)
+    ),
+    summary = "Render a kitchen ticket for an active order, with a QR code to scan at handover"
+)]
+#[get("/{id}/ticket")]
+pub(super) async fn get_order_ticket(
+    order_ops: web::Data<OrderOperations>,
+    qr_cfg: web::Data<QrConfig>,
+    admin: AdminPrincipal,
+    path: web::Path<(i32,)>,
+    query: web::Query<TicketQuery>,
+) -> actix_web::Result<impl Responder> {
+    let order_id = path.into_inner().0;
+    let raw_format = query.into_inner().format;
+    let Some(format) = TicketFormat::parse(raw_format.as_deref().unwrap_or("escpos")) else {
+        return Ok(HttpResponse::BadRequest().json(OrderResponse {
+            status: "error".to_string(),
+            error: Some(format!(
+                "format cannot be {}, must be one of escpos, text or html.",
+                raw_format.unwrap_or_default()
+            )),
+        }));
+    };
+
+    let result =
+        web::block(move || order_ops.get_kitchen_ticket(order_id, admin.canteen_id)).await?;
+    let (user_id, ticket) = match result {
+        Ok(found) => found,
+        Err(RepositoryError::NotFound(_)) => {
+            return Ok(HttpResponse::NotFound().json(OrderResponse {
+                status: "error".to_string(),
+                error: Some("Order not found".to_string()),
+            }));
+        }
+        Err(e) => {
+            error!(
+                "get_order_ticket: error fetching order {} for canteen {}: {}",
+                order_id, admin.canteen_id, e
+            );
+            return Ok(HttpResponse::InternalServerError().json(OrderResponse {
+                status: "error".to_string(),
+                error: Some(e.to_string()),
+            }));
+        }
+    };
+
+    // The same token the customer's QR carries, so either can be scanned at handover.
+    let token = qr_token::generate_qr_token(order_id, user_id, &qr_cfg.secret);
+    match ticket.render(format, &token, parse_tz_offset_from_env()) {
+        Ok(rendered) => {
+            debug!(
+                "get_order_ticket: rendered {:?} ticket for order {}",
+                format, order_id
+            );
+            Ok(HttpResponse::Ok()
+                .content_type(format.content_type())
+                .body(rendered))
+        }
+        Err(e) => {
+            error!(
+                "get_order_ticket: failed to render ticket for order {}: {}",
+                order_id, e
+            );
+            Ok(HttpResponse::InternalServerError().json(OrderResponse {
+                status: "error".to_string(),
+                error: Some(e),
+            }))
+        }
+    }
+}
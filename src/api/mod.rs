@@ -64,21 +64,43 @@ pub fn configure(cfg: &mut ServiceConfig, state: &AppState, qr_cfg: QrConfig) {
                 cfg,
                 &state.menu_ops,
                 &state.canteen_ops,
+                &state.pricing_ops,
+                &state.promo_ops,
+                &state.webhook_ops,
+                &state.review_ops,
+                &state.analytics_ops,
+                &state.export_ops,
+                &state.settlement_ops,
                 &state.asset_ops,
                 &state.canteen_scheduler,
                 &state.sse_broker,
+                &state.outbox_dispatcher,
+            )
+        })
+        .configure(|cfg| {
+            users::config(
+                cfg,
+                &state.user_ops,
+                &state.device_ops,
+                &state.favourite_ops,
+                &state.cart_ops,
+                &state.recommendation_ops,
+                &state.review_ops,
+                &state.sse_broker,
             )
         })
-        .configure(|cfg| users::config(cfg, &state.user_ops, &state.sse_broker))
         .configure(|cfg| {
             common::config(
                 cfg,
                 &state.order_ops,
                 &state.hold_ops,
+                &state.favourite_ops,
                 &state.payment_ops,
                 &state.search_ops,
+                &state.support_ops,
                 &state.sse_broker,
                 &state.phonepe_client,
+                &state.outbox_dispatcher,
                 qr_cfg,
             )
         });
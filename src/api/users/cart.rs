@@ -0,0 +1,169 @@
+use crate::auth::UserPrincipal;
+use crate::db::{CartOperations, RepositoryError};
+use crate::enums::users::{Cart, CartItemRequest, CartQuantityRequest, CartResponse};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+
+/// Respond with the cart, or with why it could not be changed.
+fn cart_response(result: Result<Cart, RepositoryError>) -> HttpResponse {
+    match result {
+        Ok(cart) => HttpResponse::Ok().json(CartResponse {
+            status: "ok".to_string(),
+            data: Some(cart),
+            error: None,
+        }),
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                RepositoryError::NotFound(_) => (
+                    StatusCode::NOT_FOUND,
+                    "item not found on the menu or in the cart".to_string(),
+                ),
+                e @ RepositoryError::NotAvailable(..) => (StatusCode::CONFLICT, e.to_string()),
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            HttpResponse::build(status).json(CartResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the cart"),
+    ),
+    responses(
+        (status = 200, description = "The cart, with each line checked against the current menu prices and stock", body = CartResponse),
+        (status = 500, description = "Failed to load the cart", body = CartResponse)
+    ),
+    summary = "Get the signed-in user's cart at a canteen"
+)]
+#[get("/cart/{canteen_id}")]
+pub(super) async fn get_cart(
+    cart_ops: web::Data<CartOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let canteen_id = path.into_inner().0;
+    let result = web::block(move || cart_ops.get_cart(owner_id, canteen_id)).await?;
+    Ok(cart_response(result))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the cart"),
+    ),
+    request_body = CartItemRequest,
+    responses(
+        (status = 200, description = "Item added; the updated cart", body = CartResponse),
+        (status = 400, description = "Invalid quantity, item of another canteen or cart full", body = CartResponse),
+        (status = 404, description = "No such menu item", body = CartResponse),
+        (status = 409, description = "Item not available in that quantity", body = CartResponse),
+        (status = 500, description = "Failed to add the item", body = CartResponse)
+    ),
+    summary = "Add an item to the user's cart"
+)]
+#[post("/cart/{canteen_id}/items")]
+pub(super) async fn add_cart_item(
+    cart_ops: web::Data<CartOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<CartItemRequest>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let canteen_id = path.into_inner().0;
+    let CartItemRequest { item_id, quantity } = req_data.into_inner();
+    let result =
+        web::block(move || cart_ops.add_item(owner_id, canteen_id, item_id, quantity)).await?;
+    Ok(cart_response(result))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the cart"),
+        ("item_id", description = "The item in the cart"),
+    ),
+    request_body = CartQuantityRequest,
+    responses(
+        (status = 200, description = "Quantity changed; the updated cart", body = CartResponse),
+        (status = 400, description = "Invalid quantity", body = CartResponse),
+        (status = 404, description = "Item not in the cart", body = CartResponse),
+        (status = 409, description = "Item not available in that quantity", body = CartResponse),
+        (status = 500, description = "Failed to change the quantity", body = CartResponse)
+    ),
+    summary = "Change the quantity of an item in the user's cart"
+)]
+#[put("/cart/{canteen_id}/items/{item_id}")]
+pub(super) async fn update_cart_item(
+    cart_ops: web::Data<CartOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32, i32)>,
+    req_data: web::Json<CartQuantityRequest>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let (canteen_id, item_id) = path.into_inner();
+    let quantity = req_data.into_inner().quantity;
+    let result =
+        web::block(move || cart_ops.set_quantity(owner_id, canteen_id, item_id, quantity)).await?;
+    Ok(cart_response(result))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the cart"),
+        ("item_id", description = "The item to remove"),
+    ),
+    responses(
+        (status = 200, description = "Item removed; the updated cart", body = CartResponse),
+        (status = 404, description = "Item not in the cart", body = CartResponse),
+        (status = 500, description = "Failed to remove the item", body = CartResponse)
+    ),
+    summary = "Remove an item from the user's cart"
+)]
+#[delete("/cart/{canteen_id}/items/{item_id}")]
+pub(super) async fn remove_cart_item(
+    cart_ops: web::Data<CartOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32, i32)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let (canteen_id, item_id) = path.into_inner();
+    let result = web::block(move || cart_ops.remove_item(owner_id, canteen_id, item_id)).await?;
+    Ok(cart_response(result))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the cart"),
+    ),
+    responses(
+        (status = 200, description = "Cart emptied", body = CartResponse),
+        (status = 500, description = "Failed to empty the cart", body = CartResponse)
+    ),
+    summary = "Empty the user's cart at a canteen"
+)]
+#[delete("/cart/{canteen_id}")]
+pub(super) async fn clear_cart(
+    cart_ops: web::Data<CartOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let canteen_id = path.into_inner().0;
+    let result = web::block(move || {
+        cart_ops
+            .clear_cart(owner_id, canteen_id)
+            .and_then(|()| cart_ops.get_cart(owner_id, canteen_id))
+    })
+    .await?;
+    Ok(cart_response(result))
+}
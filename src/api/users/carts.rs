@@ -0,0 +1,159 @@
+use crate::auth::UserPrincipal;
+use crate::db::{FavouriteOperations, RepositoryError};
+use crate::enums::users::{SavedCartRequest, SavedCartResponse, SavedCartsResponse};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+
+fn saved_cart_error(e: RepositoryError) -> HttpResponse {
+    let (status, message) = match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::NOT_FOUND, "cart not found".to_string()),
+        other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+    };
+    HttpResponse::build(status).json(SavedCartResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "User",
+    responses(
+        (status = 200, description = "The user's saved carts by name, at current prices", body = SavedCartsResponse),
+        (status = 500, description = "Failed to load saved carts", body = SavedCartsResponse)
+    ),
+    summary = "List the signed-in user's saved carts"
+)]
+#[get("/carts")]
+pub(super) async fn get_saved_carts(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let result = web::block(move || favourite_ops.get_saved_carts(owner_id)).await?;
+    match result {
+        Ok(carts) => Ok(HttpResponse::Ok().json(SavedCartsResponse {
+            status: "ok".to_string(),
+            data: carts,
+            error: None,
+        })),
+        Err(e) => {
+            error!(
+                "get_saved_carts: error loading saved carts of user_id {}: {}",
+                owner_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(SavedCartsResponse {
+                    status: "error".to_string(),
+                    data: Vec::new(),
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    request_body = SavedCartRequest,
+    responses(
+        (status = 200, description = "Cart saved", body = SavedCartResponse),
+        (status = 400, description = "Invalid name or items, duplicate name or too many carts", body = SavedCartResponse),
+        (status = 500, description = "Failed to save the cart", body = SavedCartResponse)
+    ),
+    summary = "Save a named cart to hold again later"
+)]
+#[post("/carts")]
+pub(super) async fn create_saved_cart(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    req_data: web::Json<SavedCartRequest>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let SavedCartRequest { name, item_ids } = req_data.into_inner();
+    let result =
+        web::block(move || favourite_ops.create_saved_cart(owner_id, &name, &item_ids)).await?;
+    match result {
+        Ok(cart) => {
+            debug!(
+                "create_saved_cart: saved cart {} for user_id {}",
+                cart.cart_id, owner_id
+            );
+            Ok(HttpResponse::Ok().json(SavedCartResponse {
+                status: "ok".to_string(),
+                data: Some(cart),
+                error: None,
+            }))
+        }
+        Err(e) => Ok(saved_cart_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("cart_id", description = "The saved cart to replace"),
+    ),
+    request_body = SavedCartRequest,
+    responses(
+        (status = 200, description = "Cart renamed and its items replaced", body = SavedCartResponse),
+        (status = 400, description = "Invalid name or items, or duplicate name", body = SavedCartResponse),
+        (status = 404, description = "No such cart of the user", body = SavedCartResponse),
+        (status = 500, description = "Failed to update the cart", body = SavedCartResponse)
+    ),
+    summary = "Update a saved cart"
+)]
+#[put("/carts/{cart_id}")]
+pub(super) async fn update_saved_cart(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<SavedCartRequest>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let cart_id = path.into_inner().0;
+    let SavedCartRequest { name, item_ids } = req_data.into_inner();
+    let result =
+        web::block(move || favourite_ops.update_saved_cart(owner_id, cart_id, &name, &item_ids))
+            .await?;
+    match result {
+        Ok(cart) => Ok(HttpResponse::Ok().json(SavedCartResponse {
+            status: "ok".to_string(),
+            data: Some(cart),
+            error: None,
+        })),
+        Err(e) => Ok(saved_cart_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("cart_id", description = "The saved cart to delete"),
+    ),
+    responses(
+        (status = 200, description = "Cart deleted", body = SavedCartResponse),
+        (status = 404, description = "No such cart of the user", body = SavedCartResponse),
+        (status = 500, description = "Failed to delete the cart", body = SavedCartResponse)
+    ),
+    summary = "Delete a saved cart"
+)]
+#[delete("/carts/{cart_id}")]
+pub(super) async fn delete_saved_cart(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let cart_id = path.into_inner().0;
+    let result = web::block(move || favourite_ops.delete_saved_cart(owner_id, cart_id)).await?;
+    match result {
+        Ok(()) => Ok(HttpResponse::Ok().json(SavedCartResponse {
+            status: "ok".to_string(),
+            data: None,
+            error: None,
+        })),
+        Err(e) => Ok(saved_cart_error(e)),
+    }
+}
@@ -0,0 +1,102 @@
+use crate::auth::UserPrincipal;
+use crate::db::{DeviceOperations, RepositoryError};
+use crate::enums::users::{DeviceTokenResponse, RegisterDeviceRequest};
+use actix_web::http::StatusCode;
+use actix_web::{delete, post, web, HttpResponse, Responder};
+
+#[utoipa::path(
+    tag = "User",
+    request_body = RegisterDeviceRequest,
+    responses(
+        (status = 200, description = "Device registered for push notifications", body = DeviceTokenResponse),
+        (status = 400, description = "Invalid token or platform", body = DeviceTokenResponse),
+        (status = 500, description = "Failed to register the device due to server error", body = DeviceTokenResponse),
+    ),
+    summary = "Register a device of the signed-in user for push notifications",
+)]
+#[post("/devices")]
+pub(super) async fn register_device(
+    device_ops: web::Data<DeviceOperations>,
+    user: UserPrincipal,
+    req_data: web::Json<RegisterDeviceRequest>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let RegisterDeviceRequest { token, platform } = req_data.into_inner();
+    let result =
+        web::block(move || device_ops.register_device(owner_id, &token, &platform)).await?;
+    match result {
+        Ok(device) => {
+            debug!(
+                "register_device: registered {} device for user_id {}",
+                device.platform, owner_id
+            );
+            Ok(HttpResponse::Ok().json(DeviceTokenResponse {
+                status: "ok".to_string(),
+                data: Some(device),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "register_device: error registering device for user_id {}: {}",
+                owner_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(DeviceTokenResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("token", description = "The FCM registration token to stop pushing to"),
+    ),
+    responses(
+        (status = 200, description = "Device unregistered", body = DeviceTokenResponse),
+        (status = 404, description = "Device not registered to the user", body = DeviceTokenResponse),
+        (status = 500, description = "Failed to unregister the device due to server error", body = DeviceTokenResponse),
+    ),
+    summary = "Stop push notifications to a device of the signed-in user",
+)]
+#[delete("/devices/{token}")]
+pub(super) async fn unregister_device(
+    device_ops: web::Data<DeviceOperations>,
+    user: UserPrincipal,
+    path: web::Path<(String,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let token = path.into_inner().0;
+    let result = web::block(move || device_ops.unregister_device(owner_id, &token)).await?;
+    match result {
+        Ok(()) => Ok(HttpResponse::Ok().json(DeviceTokenResponse {
+            status: "ok".to_string(),
+            data: None,
+            error: None,
+        })),
+        Err(e) => {
+            error!(
+                "unregister_device: error unregistering device for user_id {}: {}",
+                owner_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::NotFound(_) => {
+                    (StatusCode::NOT_FOUND, "device not registered".to_string())
+                }
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(DeviceTokenResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
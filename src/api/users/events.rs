@@ -1,6 +1,6 @@
 use crate::auth::UserPrincipal;
-use crate::sse::SseBroker;
-use actix_web::{get, web, Responder};
+use crate::sse::{last_event_id, SseBroker};
+use actix_web::{get, web, HttpRequest, Responder};
 use actix_web_lab::sse;
 use actix_web_lab::sse::Sse;
 use std::time::Duration;
@@ -9,7 +9,7 @@ use uuid::Uuid;
 #[utoipa::path(
     tag = "User",
     responses(
-        (status = 200, description = "Successfully connect to SSE stream", content_type = "text/event-stream"),
+        (status = 200, description = "Successfully connect to SSE stream. Events carry per-stream sequence ids; reconnect with `Last-Event-ID` to replay missed events. A `status: resync` event means some could not be replayed and state should be refetched.", content_type = "text/event-stream"),
         (status = 401, description = "Auth token missing"),
         (status = 500, description = "Failed to connect to SSE stream"),
     ),
@@ -19,16 +19,16 @@ use uuid::Uuid;
 pub async fn user_order_events(
     user: UserPrincipal,
     broker: web::Data<SseBroker>,
+    req: HttpRequest,
 ) -> impl Responder {
     let user_id = user.user_id();
     let conn_id = Uuid::new_v4();
 
-    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(64);
-    broker.register_user_connection(user_id, conn_id, tx.clone());
-
+    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(broker.channel_capacity());
     let _ = tx
         .send(sse::Data::new("connected").event("status").into())
         .await;
+    broker.register_user_connection(user_id, conn_id, tx.clone(), last_event_id(&req));
 
     let cleanup_broker = broker.clone();
     actix_web::rt::spawn(async move {
@@ -40,3 +40,42 @@ pub async fn user_order_events(
         .with_retry_duration(Duration::from_secs(3))
         .with_keep_alive(Duration::from_secs(10))
 }
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "Canteen of the user's cart"),
+    ),
+    responses(
+        (status = 200, description = "Successfully connect to SSE stream. Sends the canteen's `inventory_update` events; refetch the cart when one names an item in it. Reconnect with `Last-Event-ID` to replay missed events.", content_type = "text/event-stream"),
+        (status = 401, description = "Auth token missing"),
+        (status = 500, description = "Failed to connect to SSE stream"),
+    ),
+    summary = "Connect to SSE stream for inventory updates affecting the user's cart",
+)]
+#[get("/cart/{canteen_id}")]
+pub async fn user_cart_events(
+    path: web::Path<(i32,)>,
+    _user: UserPrincipal,
+    broker: web::Data<SseBroker>,
+    req: HttpRequest,
+) -> impl Responder {
+    let cart_canteen_id = path.into_inner().0;
+    let conn_id = Uuid::new_v4();
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<sse::Event>(broker.channel_capacity());
+    let _ = tx
+        .send(sse::Data::new("connected").event("status").into())
+        .await;
+    broker.register_canteen_subscription(cart_canteen_id, conn_id, tx.clone(), last_event_id(&req));
+
+    let cleanup_broker = broker.clone();
+    actix_web::rt::spawn(async move {
+        let _ = tx.closed().await;
+        cleanup_broker.unregister_canteen_subscription(cart_canteen_id, conn_id);
+    });
+
+    Sse::from_infallible_receiver(rx)
+        .with_retry_duration(Duration::from_secs(3))
+        .with_keep_alive(Duration::from_secs(10))
+}
@@ -0,0 +1,168 @@
+use crate::auth::UserPrincipal;
+use crate::db::{FavouriteOperations, RepositoryError};
+use crate::enums::users::{FavouriteResponse, FavouritesResponse};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+
+/// Respond to adding or removing a favourite; `NotFound` means the item or canteen (or the
+/// favourite, when removing) does not exist.
+fn favourite_response(result: Result<(), RepositoryError>, not_found: &str) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().json(FavouriteResponse {
+            status: "ok".to_string(),
+            error: None,
+        }),
+        Err(e) => {
+            let (status, message) = match e {
+                RepositoryError::NotFound(_) => (StatusCode::NOT_FOUND, not_found.to_string()),
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            HttpResponse::build(status).json(FavouriteResponse {
+                status: "error".to_string(),
+                error: Some(message),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    responses(
+        (status = 200, description = "The user's favourite items and canteens, latest first", body = FavouritesResponse),
+        (status = 500, description = "Failed to load favourites", body = FavouritesResponse)
+    ),
+    summary = "List the signed-in user's favourite items and canteens"
+)]
+#[get("/favourites")]
+pub(super) async fn get_favourites(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    match favourite_ops.get_favourites(owner_id).await {
+        Ok(favourites) => {
+            debug!(
+                "get_favourites: {} items and {} canteens for user_id {}",
+                favourites.items.len(),
+                favourites.canteens.len(),
+                owner_id
+            );
+            Ok(HttpResponse::Ok().json(FavouritesResponse {
+                status: "ok".to_string(),
+                data: Some(favourites),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_favourites: error loading favourites of user_id {}: {}",
+                owner_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(FavouritesResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("item_id", description = "The menu item to favourite"),
+    ),
+    responses(
+        (status = 200, description = "Item favourited; favouriting it again is a no-op", body = FavouriteResponse),
+        (status = 404, description = "No such menu item", body = FavouriteResponse),
+        (status = 500, description = "Failed to favourite the item", body = FavouriteResponse)
+    ),
+    summary = "Favourite a menu item"
+)]
+#[put("/favourites/items/{item_id}")]
+pub(super) async fn add_favourite_item(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let item_id = path.into_inner().0;
+    let result = web::block(move || favourite_ops.add_favourite_item(owner_id, item_id)).await?;
+    Ok(favourite_response(result, "menu item not found"))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("item_id", description = "The menu item to stop favouriting"),
+    ),
+    responses(
+        (status = 200, description = "Item removed from favourites", body = FavouriteResponse),
+        (status = 404, description = "Item is not a favourite of the user", body = FavouriteResponse),
+        (status = 500, description = "Failed to remove the favourite", body = FavouriteResponse)
+    ),
+    summary = "Remove a menu item from favourites"
+)]
+#[delete("/favourites/items/{item_id}")]
+pub(super) async fn remove_favourite_item(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let item_id = path.into_inner().0;
+    let result = web::block(move || favourite_ops.remove_favourite_item(owner_id, item_id)).await?;
+    Ok(favourite_response(result, "item is not a favourite"))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "The canteen to favourite"),
+    ),
+    responses(
+        (status = 200, description = "Canteen favourited; favouriting it again is a no-op", body = FavouriteResponse),
+        (status = 404, description = "No such canteen", body = FavouriteResponse),
+        (status = 500, description = "Failed to favourite the canteen", body = FavouriteResponse)
+    ),
+    summary = "Favourite a canteen"
+)]
+#[put("/favourites/canteens/{canteen_id}")]
+pub(super) async fn add_favourite_canteen(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let canteen_id = path.into_inner().0;
+    let result =
+        web::block(move || favourite_ops.add_favourite_canteen(owner_id, canteen_id)).await?;
+    Ok(favourite_response(result, "canteen not found"))
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "The canteen to stop favouriting"),
+    ),
+    responses(
+        (status = 200, description = "Canteen removed from favourites", body = FavouriteResponse),
+        (status = 404, description = "Canteen is not a favourite of the user", body = FavouriteResponse),
+        (status = 500, description = "Failed to remove the favourite", body = FavouriteResponse)
+    ),
+    summary = "Remove a canteen from favourites"
+)]
+#[delete("/favourites/canteens/{canteen_id}")]
+pub(super) async fn remove_favourite_canteen(
+    favourite_ops: web::Data<FavouriteOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let owner_id = user.user_id();
+    let canteen_id = path.into_inner().0;
+    let result =
+        web::block(move || favourite_ops.remove_favourite_canteen(owner_id, canteen_id)).await?;
+    Ok(favourite_response(result, "canteen is not a favourite"))
+}
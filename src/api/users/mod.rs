@@ -1,28 +1,102 @@
+mod cart;
+mod carts;
+mod devices;
 mod events;
+mod favourites;
 mod orders;
+mod preferences;
+mod recommendations;
+mod reviews;
 
-use crate::api::users::events::user_order_events;
-use crate::db::UserOperations;
+use crate::api::users::events::{user_cart_events, user_order_events};
+use crate::api::ContentTypeHeader;
+use crate::db::{
+    CartOperations, DeviceOperations, FavouriteOperations, RecommendationOperations,
+    ReviewOperations, UserOperations,
+};
 use crate::sse::SseBroker;
 use actix_web::middleware::NormalizePath;
 use actix_web::web;
+use cart::{add_cart_item, clear_cart, get_cart, remove_cart_item, update_cart_item};
+use carts::{create_saved_cart, delete_saved_cart, get_saved_carts, update_saved_cart};
+use devices::{register_device, unregister_device};
+use favourites::{
+    add_favourite_canteen, add_favourite_item, get_favourites, remove_favourite_canteen,
+    remove_favourite_item,
+};
 use orders::get_past_orders_of_user;
+use preferences::{get_user_preferences, set_user_preferences};
+use recommendations::{get_popular_now, get_recommendations, get_usual_order};
+use reviews::{create_review, get_review};
 use utoipa_actix_web::{scope, service_config::ServiceConfig};
 
-pub fn config(cfg: &mut ServiceConfig, user_ops: &UserOperations, sse_broker: &SseBroker) {
+#[allow(clippy::too_many_arguments)]
+pub fn config(
+    cfg: &mut ServiceConfig,
+    user_ops: &UserOperations,
+    device_ops: &DeviceOperations,
+    favourite_ops: &FavouriteOperations,
+    cart_ops: &CartOperations,
+    recommendation_ops: &RecommendationOperations,
+    review_ops: &ReviewOperations,
+    sse_broker: &SseBroker,
+) {
     cfg.service(
         scope::scope("/users")
             .service(
                 scope::scope("/events")
                     .app_data(web::Data::new(sse_broker.clone()))
                     .wrap(NormalizePath::trim())
-                    .service(user_order_events),
+                    .service(user_order_events)
+                    .service(user_cart_events),
+            )
+            .service(
+                scope::scope("/recommendations")
+                    .app_data(web::Data::new(recommendation_ops.clone()))
+                    .wrap(NormalizePath::trim())
+                    .service(get_usual_order)
+                    .service(get_popular_now)
+                    .service(get_recommendations),
+            )
+            .service(
+                scope::scope("")
+                    .guard(ContentTypeHeader)
+                    .app_data(web::Data::new(user_ops.clone()))
+                    .app_data(web::Data::new(device_ops.clone()))
+                    .app_data(web::Data::new(favourite_ops.clone()))
+                    .app_data(web::Data::new(cart_ops.clone()))
+                    .app_data(web::Data::new(review_ops.clone()))
+                    .wrap(NormalizePath::trim())
+                    .service(set_user_preferences)
+                    .service(register_device)
+                    .service(create_saved_cart)
+                    .service(update_saved_cart)
+                    .service(add_cart_item)
+                    .service(update_cart_item)
+                    .service(create_review),
             )
             .service(
                 scope::scope("")
                     .app_data(web::Data::new(user_ops.clone()))
+                    .app_data(web::Data::new(device_ops.clone()))
+                    .app_data(web::Data::new(favourite_ops.clone()))
+                    .app_data(web::Data::new(cart_ops.clone()))
+                    .app_data(web::Data::new(review_ops.clone()))
                     .wrap(NormalizePath::trim())
-                    .service(get_past_orders_of_user),
+                    .service(get_past_orders_of_user)
+                    .service(get_review)
+                    .service(unregister_device)
+                    .service(get_user_preferences)
+                    .service(get_favourites)
+                    .service(add_favourite_item)
+                    .service(remove_favourite_item)
+                    .service(add_favourite_canteen)
+                    .service(remove_favourite_canteen)
+                    .service(get_saved_carts)
+                    .service(delete_saved_cart)
+                    .service(get_cart)
+                    .service(remove_cart_item)
+                    .service(clear_cart),
             ),
     );
 }
@@ -0,0 +1,97 @@
+use crate::auth::UserPrincipal;
+use crate::db::{RepositoryError, UserOperations};
+use crate::enums::users::{UserPreferences, UserPreferencesResponse};
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web, HttpResponse, Responder};
+
+#[utoipa::path(
+    tag = "User",
+    responses(
+        (status = 200, description = "Successfully retrieved the user's preferences", body = UserPreferencesResponse),
+        (status = 500, description = "Failed to retrieve preferences due to server error", body = UserPreferencesResponse),
+    ),
+    summary = "Get the dietary preferences of the signed-in user",
+)]
+#[get("/preferences")]
+pub(super) async fn get_user_preferences(
+    user_ops: web::Data<UserOperations>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let viewer_id = user.user_id();
+    let result = web::block(move || user_ops.get_allergen_exclusions(viewer_id)).await?;
+    match result {
+        Ok(allergen_exclusions) => Ok(HttpResponse::Ok().json(UserPreferencesResponse {
+            status: "ok".to_string(),
+            data: Some(UserPreferences {
+                allergen_exclusions,
+            }),
+            error: None,
+        })),
+        Err(e) => {
+            error!(
+                "get_user_preferences: error retrieving preferences for user_id {}: {}",
+                viewer_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(UserPreferencesResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    request_body = UserPreferences,
+    responses(
+        (status = 200, description = "Preferences saved", body = UserPreferencesResponse),
+        (status = 400, description = "Unknown allergen", body = UserPreferencesResponse),
+        (status = 500, description = "Failed to save preferences due to server error", body = UserPreferencesResponse),
+    ),
+    summary = "Save the dietary preferences of the signed-in user",
+)]
+#[put("/preferences")]
+pub(super) async fn set_user_preferences(
+    user_ops: web::Data<UserOperations>,
+    user: UserPrincipal,
+    req_data: web::Json<UserPreferences>,
+) -> actix_web::Result<impl Responder> {
+    let viewer_id = user.user_id();
+    let exclusions = req_data.into_inner().allergen_exclusions;
+    let result =
+        web::block(move || user_ops.set_allergen_exclusions(viewer_id, exclusions)).await?;
+    match result {
+        Ok(allergen_exclusions) => {
+            debug!(
+                "set_user_preferences: saved {} allergen exclusions for user_id {}",
+                allergen_exclusions.len(),
+                viewer_id
+            );
+            Ok(HttpResponse::Ok().json(UserPreferencesResponse {
+                status: "ok".to_string(),
+                data: Some(UserPreferences {
+                    allergen_exclusions,
+                }),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "set_user_preferences: error saving preferences for user_id {}: {}",
+                viewer_id, e
+            );
+            let (status, message) = match e {
+                RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+                other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+            };
+            Ok(HttpResponse::build(status).json(UserPreferencesResponse {
+                status: "error".to_string(),
+                data: None,
+                error: Some(message),
+            }))
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::auth::UserPrincipal;
+use crate::db::RecommendationOperations;
+use crate::enums::admin::AllItemsResponse;
+use crate::enums::users::{RecommendationsQuery, RecommendationsResponse, UsualOrderResponse};
+use actix_web::{get, web, HttpResponse, Responder};
+
+#[utoipa::path(
+    tag = "User",
+    params(RecommendationsQuery),
+    responses(
+        (status = 200, description = "The user's favourites, items often ordered with them and what is popular now", body = RecommendationsResponse),
+        (status = 500, description = "Failed to load recommendations", body = RecommendationsResponse)
+    ),
+    summary = "Recommend items from the user's order history"
+)]
+#[get("")]
+pub(super) async fn get_recommendations(
+    recommendation_ops: web::Data<RecommendationOperations>,
+    query: web::Query<RecommendationsQuery>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let for_user_id = user.user_id();
+    let result = recommendation_ops
+        .recommendations(for_user_id, query.canteen_id)
+        .await;
+    match result {
+        Ok(recommendations) => {
+            debug!(
+                "get_recommendations: {} favourites, {} also ordered and {} popular items for user {}",
+                recommendations.most_ordered.len(),
+                recommendations.also_ordered.len(),
+                recommendations.popular_now.len(),
+                for_user_id
+            );
+            Ok(HttpResponse::Ok().json(RecommendationsResponse {
+                status: "ok".to_string(),
+                data: Some(recommendations),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_recommendations: failed to recommend items for user {}: {}",
+                for_user_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(RecommendationsResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(RecommendationsQuery),
+    responses(
+        (status = 200, description = "The user's usual order as a ready-to-submit hold request", body = UsualOrderResponse),
+        (status = 404, description = "The user has no delivered orders", body = UsualOrderResponse),
+        (status = 500, description = "Failed to load the usual order", body = UsualOrderResponse)
+    ),
+    summary = "Reorder my usual: the order the user places most often"
+)]
+#[get("/usual")]
+pub(super) async fn get_usual_order(
+    recommendation_ops: web::Data<RecommendationOperations>,
+    query: web::Query<RecommendationsQuery>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let for_user_id = user.user_id();
+    let in_canteen_id = query.canteen_id;
+    let result =
+        web::block(move || recommendation_ops.usual_order(for_user_id, in_canteen_id)).await?;
+    match result {
+        Ok(Some(usual)) => {
+            debug!(
+                "get_usual_order: usual order of user {} has {} lines, {} items unavailable",
+                for_user_id,
+                usual.lines.len(),
+                usual.unavailable_item_ids.len()
+            );
+            Ok(HttpResponse::Ok().json(UsualOrderResponse {
+                status: "ok".to_string(),
+                data: Some(usual),
+                error: None,
+            }))
+        }
+        Ok(None) => Ok(HttpResponse::NotFound().json(UsualOrderResponse {
+            status: "error".to_string(),
+            data: None,
+            error: Some("no delivered orders yet".to_string()),
+        })),
+        Err(e) => {
+            error!(
+                "get_usual_order: failed to load the usual order of user {}: {}",
+                for_user_id, e
+            );
+            Ok(
+                HttpResponse::InternalServerError().json(UsualOrderResponse {
+                    status: "error".to_string(),
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("canteen_id", description = "The canteen to list popular items of"),
+    ),
+    responses(
+        (status = 200, description = "The canteen's best sellers around this time of day", body = AllItemsResponse),
+        (status = 500, description = "Failed to load popular items", body = AllItemsResponse)
+    ),
+    summary = "What is popular at a canteen right now"
+)]
+#[get("/popular/{canteen_id}")]
+pub(super) async fn get_popular_now(
+    recommendation_ops: web::Data<RecommendationOperations>,
+    path: web::Path<(i32,)>,
+    user: UserPrincipal,
+) -> actix_web::Result<impl Responder> {
+    let at_canteen_id = path.into_inner().0;
+    let result = recommendation_ops
+        .popular_now(at_canteen_id, Some(user.user_id()))
+        .await;
+    match result {
+        Ok(items) => {
+            debug!(
+                "get_popular_now: {} popular items at canteen {}",
+                items.len(),
+                at_canteen_id
+            );
+            Ok(HttpResponse::Ok().json(AllItemsResponse {
+                status: "ok".to_string(),
+                data: items,
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!(
+                "get_popular_now: failed to load popular items of canteen {}: {}",
+                at_canteen_id, e
+            );
+            Ok(HttpResponse::InternalServerError().json(AllItemsResponse {
+                status: "error".to_string(),
+                data: Vec::new(),
+                error: Some(e.to_string()),
+            }))
+        }
+    }
+}
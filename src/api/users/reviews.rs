@@ -0,0 +1,91 @@
+use crate::auth::UserPrincipal;
+use crate::db::{RepositoryError, ReviewOperations};
+use crate::enums::common::{ReviewRequest, ReviewResponse};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpResponse, Responder};
+
+fn review_error(e: RepositoryError) -> HttpResponse {
+    let (status, message) = match e {
+        RepositoryError::ValidationError(message) => (StatusCode::BAD_REQUEST, message),
+        RepositoryError::NotFound(_) => (StatusCode::NOT_FOUND, "review not found".to_string()),
+        other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+    };
+    HttpResponse::build(status).json(ReviewResponse {
+        status: "error".to_string(),
+        data: None,
+        error: Some(message),
+    })
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("order_id", description = "A delivered order of the user"),
+    ),
+    request_body = ReviewRequest,
+    responses(
+        (status = 200, description = "Review saved; average ratings of the canteen and rated items are updated", body = ReviewResponse),
+        (status = 400, description = "Invalid rating or comment, an item not in the order, an order not delivered or past the review window, or an order already reviewed", body = ReviewResponse),
+        (status = 404, description = "No such past order of the user", body = ReviewResponse),
+        (status = 500, description = "Failed to save the review", body = ReviewResponse)
+    ),
+    summary = "Review a delivered order",
+    description = "Rates the order 1 to 5 with an optional comment, and optionally rates items of the order. Each order can be reviewed once, within 7 days of being placed."
+)]
+#[post("/orders/{order_id}/review")]
+pub(super) async fn create_review(
+    review_ops: web::Data<ReviewOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+    req_data: web::Json<ReviewRequest>,
+) -> actix_web::Result<impl Responder> {
+    let reviewer_id = user.user_id();
+    let order_id = path.into_inner().0;
+    let result =
+        web::block(move || review_ops.create_review(reviewer_id, order_id, req_data.into_inner()))
+            .await?;
+    match result {
+        Ok(review) => Ok(HttpResponse::Ok().json(ReviewResponse {
+            status: "ok".to_string(),
+            data: Some(review),
+            error: None,
+        })),
+        Err(RepositoryError::NotFound(_)) => Ok(HttpResponse::NotFound().json(ReviewResponse {
+            status: "error".to_string(),
+            data: None,
+            error: Some("order not found".to_string()),
+        })),
+        Err(e) => Ok(review_error(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "User",
+    params(
+        ("order_id", description = "A past order of the user"),
+    ),
+    responses(
+        (status = 200, description = "The user's review of the order, with the canteen's reply", body = ReviewResponse),
+        (status = 404, description = "The order has not been reviewed", body = ReviewResponse),
+        (status = 500, description = "Failed to load the review", body = ReviewResponse)
+    ),
+    summary = "Get the signed-in user's review of an order"
+)]
+#[get("/orders/{order_id}/review")]
+pub(super) async fn get_review(
+    review_ops: web::Data<ReviewOperations>,
+    user: UserPrincipal,
+    path: web::Path<(i32,)>,
+) -> actix_web::Result<impl Responder> {
+    let reviewer_id = user.user_id();
+    let order_id = path.into_inner().0;
+    let result = web::block(move || review_ops.get_review(reviewer_id, order_id)).await?;
+    match result {
+        Ok(review) => Ok(HttpResponse::Ok().json(ReviewResponse {
+            status: "ok".to_string(),
+            data: Some(review),
+            error: None,
+        })),
+        Err(e) => Ok(review_error(e)),
+    }
+}
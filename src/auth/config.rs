@@ -83,3 +83,30 @@ impl AdminJwtConfig {
 }
 
 // Note: Swagger BasicAuth is provided via utoipa-swagger-ui configuration in main.rs
+
+/// Platform operators: signed-in users who manage what belongs to no single canteen, such as
+/// platform-wide promo codes. Listed by user id in the comma-separated
+/// `PLATFORM_OPERATOR_USER_IDS`; canteen admin accounts are never operators.
+#[derive(Clone, Debug, Default)]
+pub struct PlatformOperatorConfig {
+    pub user_ids: Vec<i32>,
+}
+
+impl PlatformOperatorConfig {
+    pub fn from_env() -> Self {
+        Self::parse(&var("PLATFORM_OPERATOR_USER_IDS").unwrap_or_default())
+    }
+
+    /// Ids that do not parse are skipped.
+    pub fn parse(raw: &str) -> Self {
+        let user_ids = raw
+            .split(',')
+            .filter_map(|id| id.trim().parse::<i32>().ok())
+            .collect();
+        Self { user_ids }
+    }
+
+    pub fn is_operator(&self, user_id: i32) -> bool {
+        self.user_ids.contains(&user_id)
+    }
+}
@@ -1,6 +1,7 @@
+use crate::auth::config::PlatformOperatorConfig;
 use crate::auth::principal::Principal;
 use actix_web::dev::Payload;
-use actix_web::{error::ErrorUnauthorized, Error, FromRequest, HttpMessage, HttpRequest};
+use actix_web::{error::ErrorUnauthorized, web, Error, FromRequest, HttpMessage, HttpRequest};
 use futures::future::{ready, Ready};
 
 #[allow(dead_code)]
@@ -77,3 +78,30 @@ impl FromRequest for AdminPrincipal {
         ready(Err(ErrorUnauthorized("missing principal")))
     }
 }
+
+/// A signed-in user listed in [`PlatformOperatorConfig`].
+pub struct OperatorPrincipal {
+    pub user_id: i32,
+}
+
+impl FromRequest for OperatorPrincipal {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(p) = req.extensions().get::<Principal>().cloned() else {
+            return ready(Err(ErrorUnauthorized("missing principal")));
+        };
+        let operators = req.app_data::<web::Data<PlatformOperatorConfig>>();
+        match p {
+            Principal::User { user_id, .. }
+                if operators.is_some_and(|operators| operators.is_operator(user_id)) =>
+            {
+                ready(Ok(OperatorPrincipal { user_id }))
+            }
+            _ => ready(Err(actix_web::error::ErrorForbidden(
+                "platform operators only",
+            ))),
+        }
+    }
+}
@@ -7,8 +7,8 @@ pub mod middleware;
 pub mod principal;
 pub mod qr_token;
 
-pub use config::{AdminJwtConfig, FirebaseAuthConfig};
-pub use extractors::{AdminPrincipal, PrincipalExtractor, UserPrincipal};
+pub use config::{AdminJwtConfig, FirebaseAuthConfig, PlatformOperatorConfig};
+pub use extractors::{AdminPrincipal, OperatorPrincipal, PrincipalExtractor, UserPrincipal};
 pub use jwks::JwksCache;
 pub use middleware::AuthLayer;
 pub use principal::Principal;
@@ -74,7 +74,7 @@ pub fn verify_qr_token(token: &str, secret: &str, max_age_secs: u64) -> Result<(
 }
 
 /// HMAC-SHA256 sign a payload string, returning the hex-encoded signature.
-fn sign_payload(payload: &str, secret: &str) -> String {
+pub fn sign_payload(payload: &str, secret: &str) -> String {
     let mut mac =
         HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
     mac.update(payload.as_bytes());
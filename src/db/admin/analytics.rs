@@ -0,0 +1,329 @@
+use crate::db::common::payments::{PAYMENT_STATE_COMPLETED, PAYMENT_STATE_FAILED};
+use crate::db::schema::{held_orders, hold_history, past_orders, payment_orders};
+use crate::db::{DbConnection, RepositoryError};
+use crate::enums::admin::{
+    AnalyticsGranularity, HoldConversion, ItemSales, PaymentStats, SalesAnalytics, SalesPeriod,
+    SalesTotals, SlotUtilisation,
+};
+use crate::models::common::{
+    TimeBandEnum, HOLD_OUTCOME_CONFIRMED, HOLD_OUTCOME_EXPIRED, HOLD_OUTCOME_RELEASED,
+};
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{BigInt, Date, Int4, Nullable, Text, Timestamptz, Varchar};
+use log::error;
+
+/// Longest range one report can cover, in days.
+pub const ANALYTICS_MAX_DAYS: i64 = 366;
+/// Days covered when the range is left open.
+pub const ANALYTICS_DEFAULT_DAYS: i64 = 30;
+
+/// A range of business days in the canteen time zone, and the instants it spans.
+#[derive(Debug, Clone)]
+pub struct ReportRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// When `from` starts.
+    pub starts: DateTime<Utc>,
+    /// When the day after `to` starts.
+    pub ends: DateTime<Utc>,
+    pub tz: FixedOffset,
+}
+
+impl ReportRange {
+    /// The business days `from` to `to`, both included. Open ends default to the last
+    /// [`ANALYTICS_DEFAULT_DAYS`] days.
+    pub fn resolve(
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Self, RepositoryError> {
+        let tz = parse_tz_offset_from_env();
+        let to = to.unwrap_or_else(|| Utc::now().with_timezone(&tz).date_naive());
+        let from = from.unwrap_or(to - Duration::days(ANALYTICS_DEFAULT_DAYS - 1));
+        if from > to {
+            return Err(RepositoryError::ValidationError(
+                "from must not be after to".to_string(),
+            ));
+        }
+        if (to - from).num_days() >= ANALYTICS_MAX_DAYS {
+            return Err(RepositoryError::ValidationError(format!(
+                "a report covers at most {ANALYTICS_MAX_DAYS} days"
+            )));
+        }
+        Ok(Self {
+            from,
+            to,
+            starts: day_start(from, tz),
+            ends: day_start(to + Duration::days(1), tz),
+            tz,
+        })
+    }
+}
+
+#[derive(QueryableByName)]
+struct PeriodRow {
+    #[diesel(sql_type = Date)]
+    period_start: NaiveDate,
+    #[diesel(sql_type = BigInt)]
+    orders: i64,
+    #[diesel(sql_type = BigInt)]
+    delivered: i64,
+    #[diesel(sql_type = BigInt)]
+    revenue: i64,
+}
+
+#[derive(QueryableByName)]
+struct ItemRow {
+    #[diesel(sql_type = Int4)]
+    item_id: i32,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    name: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    quantity: i64,
+    #[diesel(sql_type = BigInt)]
+    revenue: i64,
+}
+
+#[derive(Clone)]
+pub struct AnalyticsOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl AnalyticsOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// Sales of the canteen over the business days `from` to `to`, both included, in the
+    /// canteen time zone. Open ends default to the last [`ANALYTICS_DEFAULT_DAYS`] days.
+    pub fn sales_report(
+        &self,
+        for_canteen_id: i32,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        granularity: AnalyticsGranularity,
+    ) -> Result<SalesAnalytics, RepositoryError> {
+        let ReportRange {
+            from,
+            to,
+            starts,
+            ends,
+            tz,
+        } = ReportRange::resolve(from, to)?;
+
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "sales_report: failed to acquire DB connection for canteen {}: {}",
+                for_canteen_id, e
+            );
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let periods = diesel::sql_query(
+                    "SELECT date_trunc($4, ordered_at AT TIME ZONE 'UTC' + $5 * INTERVAL '1 second')::DATE AS period_start, \
+                            COUNT(*) AS orders, \
+                            COUNT(*) FILTER (WHERE order_status) AS delivered, \
+                            COALESCE(SUM(price) FILTER (WHERE order_status), 0)::BIGINT AS revenue \
+                     FROM past_orders \
+                     WHERE canteen_id = $1 AND ordered_at >= $2 AND ordered_at < $3 \
+                     GROUP BY 1 ORDER BY 1",
+                )
+                .bind::<Int4, _>(for_canteen_id)
+                .bind::<Timestamptz, _>(starts)
+                .bind::<Timestamptz, _>(ends)
+                .bind::<Text, _>(granularity.as_str())
+                .bind::<Int4, _>(tz.local_minus_utc())
+                .load::<PeriodRow>(conn)?;
+
+                let items = diesel::sql_query(
+                    "SELECT i.item_id, m.name, \
+                            SUM(i.quantity)::BIGINT AS quantity, \
+                            SUM(i.quantity * i.price)::BIGINT AS revenue \
+                     FROM past_order_items i \
+                     JOIN past_orders p ON p.order_id = i.order_id \
+                     LEFT JOIN menu_items m ON m.item_id = i.item_id \
+                     WHERE p.canteen_id = $1 AND p.order_status \
+                       AND p.ordered_at >= $2 AND p.ordered_at < $3 \
+                     GROUP BY i.item_id, m.name \
+                     ORDER BY revenue DESC, quantity DESC, i.item_id",
+                )
+                .bind::<Int4, _>(for_canteen_id)
+                .bind::<Timestamptz, _>(starts)
+                .bind::<Timestamptz, _>(ends)
+                .load::<ItemRow>(conn)?;
+
+                let slots = past_orders::table
+                    .filter(past_orders::canteen_id.eq(for_canteen_id))
+                    .filter(past_orders::ordered_at.ge(starts))
+                    .filter(past_orders::ordered_at.lt(ends))
+                    .group_by(past_orders::deliver_at)
+                    .select((past_orders::deliver_at, count_star()))
+                    .load::<(Option<TimeBandEnum>, i64)>(conn)?;
+
+                let hold_outcomes = hold_history::table
+                    .filter(hold_history::canteen_id.eq(for_canteen_id))
+                    .filter(hold_history::held_at.ge(starts))
+                    .filter(hold_history::held_at.lt(ends))
+                    .group_by(hold_history::outcome)
+                    .select((hold_history::outcome, count_star()))
+                    .load::<(String, i64)>(conn)?;
+                let pending_holds = held_orders::table
+                    .filter(held_orders::canteen_id.eq(for_canteen_id))
+                    .filter(held_orders::held_at.ge(starts))
+                    .filter(held_orders::held_at.lt(ends))
+                    .count()
+                    .get_result::<i64>(conn)?;
+
+                let payment_states = payment_orders::table
+                    .filter(payment_orders::canteen_id.eq(for_canteen_id))
+                    .filter(payment_orders::created_at.ge(starts))
+                    .filter(payment_orders::created_at.lt(ends))
+                    .group_by(payment_orders::payment_state)
+                    .select((payment_orders::payment_state, count_star()))
+                    .load::<(String, i64)>(conn)?;
+
+                Ok(SalesAnalytics {
+                    from,
+                    to,
+                    granularity: granularity.as_str().to_string(),
+                    totals: sales_totals(&periods),
+                    periods: periods
+                        .iter()
+                        .map(|row| SalesPeriod {
+                            period_start: row.period_start,
+                            totals: sales_totals(std::slice::from_ref(row)),
+                        })
+                        .collect(),
+                    items: item_mix(items),
+                    slots: slot_utilisation(slots),
+                    holds: hold_conversion(&hold_outcomes, pending_holds),
+                    payments: payment_stats(&payment_states),
+                })
+            })
+            .map_err(|e: diesel::result::Error| {
+                error!(
+                    "sales_report: error building the report for canteen {}: {}",
+                    for_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+}
+
+/// When the business day `day` starts in the canteen time zone.
+pub(crate) fn day_start(day: NaiveDate, tz: FixedOffset) -> DateTime<Utc> {
+    (day.and_hms_opt(0, 0, 0).expect("midnight") - Duration::seconds(tz.local_minus_utc() as i64))
+        .and_utc()
+}
+
+/// `part` of `whole` as a fraction rounded to four places; 0 when there is no whole.
+fn ratio(part: i64, whole: i64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    (part as f64 / whole as f64 * 10_000.0).round() / 10_000.0
+}
+
+fn sales_totals(periods: &[PeriodRow]) -> SalesTotals {
+    let orders = periods.iter().map(|row| row.orders).sum::<i64>();
+    let delivered = periods.iter().map(|row| row.delivered).sum::<i64>();
+    let revenue = periods.iter().map(|row| row.revenue).sum::<i64>();
+    SalesTotals {
+        orders,
+        delivered,
+        cancelled: orders - delivered,
+        cancellation_rate: ratio(orders - delivered, orders),
+        revenue,
+        average_basket: if delivered == 0 {
+            0.0
+        } else {
+            (revenue as f64 / delivered as f64 * 100.0).round() / 100.0
+        },
+    }
+}
+
+fn item_mix(rows: Vec<ItemRow>) -> Vec<ItemSales> {
+    let total = rows.iter().map(|row| row.revenue).sum::<i64>();
+    rows.into_iter()
+        .map(|row| ItemSales {
+            item_id: row.item_id,
+            name: row.name,
+            quantity: row.quantity,
+            revenue: row.revenue,
+            revenue_share: ratio(row.revenue, total),
+        })
+        .collect()
+}
+
+fn slot_utilisation(rows: Vec<(Option<TimeBandEnum>, i64)>) -> Vec<SlotUtilisation> {
+    let total = rows.iter().map(|(_, orders)| orders).sum::<i64>();
+    let mut slots = rows
+        .into_iter()
+        .map(|(band, orders)| {
+            let rank = match band {
+                None => 0,
+                Some(TimeBandEnum::ElevenAM) => 1,
+                Some(TimeBandEnum::TwevlvePM) => 2,
+            };
+            let slot = band
+                .as_ref()
+                .map(|band| band.human_readable().to_string())
+                .unwrap_or_else(|| "Instant".to_string());
+            (
+                rank,
+                SlotUtilisation {
+                    slot,
+                    orders,
+                    share: ratio(orders, total),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    slots.sort_by_key(|(rank, _)| *rank);
+    slots.into_iter().map(|(_, slot)| slot).collect()
+}
+
+fn hold_conversion(outcomes: &[(String, i64)], pending: i64) -> HoldConversion {
+    let count_of = |wanted: &str| {
+        outcomes
+            .iter()
+            .filter(|(outcome, _)| outcome == wanted)
+            .map(|(_, count)| count)
+            .sum::<i64>()
+    };
+    let confirmed = count_of(HOLD_OUTCOME_CONFIRMED);
+    let released = count_of(HOLD_OUTCOME_RELEASED);
+    let expired = count_of(HOLD_OUTCOME_EXPIRED);
+    HoldConversion {
+        holds: confirmed + released + expired + pending,
+        confirmed,
+        released,
+        expired,
+        pending,
+        conversion_rate: ratio(confirmed, confirmed + released + expired),
+    }
+}
+
+fn payment_stats(states: &[(String, i64)]) -> PaymentStats {
+    let count_of = |wanted: &str| {
+        states
+            .iter()
+            .filter(|(state, _)| state == wanted)
+            .map(|(_, count)| count)
+            .sum::<i64>()
+    };
+    let payments = states.iter().map(|(_, count)| count).sum::<i64>();
+    let completed = count_of(PAYMENT_STATE_COMPLETED);
+    let failed = count_of(PAYMENT_STATE_FAILED);
+    PaymentStats {
+        payments,
+        completed,
+        failed,
+        pending: payments - completed - failed,
+        failure_rate: ratio(failed, completed + failed),
+    }
+}
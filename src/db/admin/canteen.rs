@@ -1,18 +1,19 @@
 use crate::db::errors::RepositoryError;
 use crate::db::schema::canteens::dsl::*;
-use crate::db::{AssetOperations, DbConnection};
-use crate::enums::admin::{CanteenDetailsWithPic, MenuItemWithPic};
+use crate::db::{AssetOperations, DbConnection, FavouriteOperations, UserOperations};
+use crate::enums::admin::{CanteenDetailsWithPic, MenuFilterQuery, MenuItemWithPic};
 use crate::models::admin::{
     Canteen, CanteenDetails, CanteenLoginSuccess, MenuItem, NewCanteenInsert,
 };
-use chrono::{DateTime, NaiveTime, Utc};
-use diesel::dsl::{case_when, sql};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use diesel::dsl::{case_when, not, sql};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::result::Error;
 use diesel::sql_types::{Bool, Text};
 use futures::future::join_all;
 use log::error;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 pub struct CanteenOperations {
@@ -29,6 +30,13 @@ pub struct CanteenHoursState {
     pub last_opened_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Queryable)]
+pub struct CanteenStockResetState {
+    pub canteen_id: i32,
+    pub opening_time: Option<NaiveTime>,
+    pub last_stock_reset_on: Option<NaiveDate>,
+}
+
 impl CanteenOperations {
     pub async fn new(
         pool: Pool<ConnectionManager<PgConnection>>,
@@ -121,6 +129,24 @@ impl CanteenOperations {
             })
     }
 
+    pub fn list_stock_reset_states(&self) -> Result<Vec<CanteenStockResetState>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "list_stock_reset_states: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        canteens
+            .select((canteen_id, opening_time, last_stock_reset_on))
+            .load::<CanteenStockResetState>(conn.connection())
+            .map_err(|e| {
+                error!("list_stock_reset_states: error fetching canteens: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
     pub fn set_canteen_open(
         &self,
         canteen_id_val: i32,
@@ -353,19 +379,59 @@ impl CanteenOperations {
         Ok(results)
     }
 
+    /// Menu of a canteen: pinned items first, then by category and item display order.
+    /// With a `viewer_id`, items are flagged (or hidden) using the user's allergen exclusions
+    /// and the user's favourites are marked.
     pub async fn get_canteen_items(
         &self,
         search_canteen_id: i32,
+        filter: &MenuFilterQuery,
+        viewer_id: Option<i32>,
     ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
+        let filter_tag = filter
+            .normalized_tag()
+            .map_err(RepositoryError::ValidationError)?;
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!("get_canteen_items: failed to acquire DB connection: {}", e);
             e
         })?;
 
+        let exclusions = match viewer_id {
+            Some(viewer_id) => {
+                UserOperations::load_allergen_exclusions(conn.connection(), viewer_id)?
+            }
+            None => Vec::new(),
+        };
+        let favourite_ids = match viewer_id {
+            Some(viewer_id) => {
+                FavouriteOperations::load_favourite_item_ids(conn.connection(), viewer_id)?
+            }
+            None => HashSet::new(),
+        };
+
+        use crate::db::schema::menu_categories;
         use crate::db::schema::menu_items::dsl::*;
-        let items = menu_items
+        let mut query = menu_items
+            .left_join(menu_categories::table)
             .filter(canteen_id.eq(search_canteen_id))
-            .order(item_id.asc())
+            .select(MenuItem::as_select())
+            .into_boxed();
+        if let Some(filter_category_id) = filter.category_id {
+            query = query.filter(category_id.eq(filter_category_id));
+        }
+        if let Some(filter_tag) = filter_tag {
+            query = query.filter(tags.contains(vec![filter_tag]));
+        }
+        if filter.hide_allergens.unwrap_or(false) && !exclusions.is_empty() {
+            query = query.filter(not(allergens.overlaps_with(exclusions.clone())));
+        }
+        let items = query
+            .order((
+                is_pinned.desc(),
+                menu_categories::display_order.asc().nulls_last(),
+                display_order.asc(),
+                item_id.asc(),
+            ))
             .load::<MenuItem>(conn.connection())
             .map_err(|e| {
                 error!(
@@ -377,6 +443,8 @@ impl CanteenOperations {
 
         let futures = items.iter().map(async |item| {
             let mut item_with_pic: MenuItemWithPic = item.into();
+            item_with_pic.flag_allergens(&exclusions);
+            item_with_pic.mark_favourite(&favourite_ids);
             item_with_pic
                 .populate_pic_link_from(&self.asset_ops, item)
                 .await;
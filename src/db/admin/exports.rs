@@ -0,0 +1,271 @@
+use crate::db::schema::sql_types::TimeBand;
+use crate::db::{DbConnection, ReportRange, RepositoryError};
+use crate::enums::admin::{OrderExportRow, OrderItemExportRow, PaymentExportRow};
+use crate::models::common::{TimeBandEnum, REFUND_STATE_COMPLETED};
+use chrono::{DateTime, NaiveDate, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{BigInt, Bool, Date, Int2, Int4, Nullable, Timestamptz, Varchar};
+use log::error;
+
+#[derive(QueryableByName)]
+struct OrderRecord {
+    #[diesel(sql_type = Int4)]
+    order_id: i32,
+    #[diesel(sql_type = Timestamptz)]
+    ordered_at: DateTime<Utc>,
+    #[diesel(sql_type = Date)]
+    business_date: NaiveDate,
+    #[diesel(sql_type = Int4)]
+    user_id: i32,
+    #[diesel(sql_type = Bool)]
+    order_status: bool,
+    #[diesel(sql_type = Nullable<TimeBand>)]
+    deliver_at: Option<TimeBandEnum>,
+    #[diesel(sql_type = Int4)]
+    price: i32,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    merchant_order_id: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    payment_state: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct OrderItemRecord {
+    #[diesel(sql_type = Int4)]
+    order_id: i32,
+    #[diesel(sql_type = Timestamptz)]
+    ordered_at: DateTime<Utc>,
+    #[diesel(sql_type = Date)]
+    business_date: NaiveDate,
+    #[diesel(sql_type = Bool)]
+    order_status: bool,
+    #[diesel(sql_type = Int4)]
+    item_id: i32,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    item_name: Option<String>,
+    #[diesel(sql_type = Int2)]
+    quantity: i16,
+    #[diesel(sql_type = Int4)]
+    price: i32,
+}
+
+#[derive(QueryableByName)]
+struct PaymentRecord {
+    #[diesel(sql_type = Int4)]
+    payment_id: i32,
+    #[diesel(sql_type = Timestamptz)]
+    created_at: DateTime<Utc>,
+    #[diesel(sql_type = Date)]
+    business_date: NaiveDate,
+    #[diesel(sql_type = Varchar)]
+    merchant_order_id: String,
+    #[diesel(sql_type = Varchar)]
+    phonepe_order_id: String,
+    #[diesel(sql_type = Int4)]
+    hold_id: i32,
+    #[diesel(sql_type = Nullable<Int4>)]
+    app_order_id: Option<i32>,
+    #[diesel(sql_type = Int4)]
+    user_id: i32,
+    #[diesel(sql_type = Int4)]
+    amount: i32,
+    #[diesel(sql_type = Varchar)]
+    payment_state: String,
+    #[diesel(sql_type = BigInt)]
+    refunded: i64,
+    #[diesel(sql_type = Timestamptz)]
+    updated_at: DateTime<Utc>,
+}
+
+fn order_status_label(delivered: bool) -> String {
+    if delivered { "delivered" } else { "cancelled" }.to_string()
+}
+
+/// Reads the rows of the accounting exports a page at a time, in key order, so that an
+/// export of any size can be streamed.
+#[derive(Clone)]
+pub struct ExportOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl ExportOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    fn connection(&self, canteen_id: i32) -> Result<DbConnection<'_>, RepositoryError> {
+        DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "export: failed to acquire DB connection for canteen {}: {}",
+                canteen_id, e
+            );
+            e
+        })
+    }
+
+    /// Finished orders of the canteen placed in the range, after order `after_order_id`.
+    pub fn order_rows(
+        &self,
+        for_canteen_id: i32,
+        range: &ReportRange,
+        after_order_id: i32,
+        limit: i64,
+    ) -> Result<Vec<OrderExportRow>, RepositoryError> {
+        let mut conn = self.connection(for_canteen_id)?;
+        let records = diesel::sql_query(
+            "SELECT p.order_id, p.ordered_at, \
+                    (p.ordered_at AT TIME ZONE 'UTC' + $4 * INTERVAL '1 second')::DATE AS business_date, \
+                    p.user_id, p.order_status, p.deliver_at, p.price, \
+                    po.merchant_order_id, po.payment_state \
+             FROM past_orders p \
+             LEFT JOIN payment_orders po ON po.app_order_id = p.order_id \
+             WHERE p.canteen_id = $1 AND p.ordered_at >= $2 AND p.ordered_at < $3 \
+               AND p.order_id > $5 \
+             ORDER BY p.order_id \
+             LIMIT $6",
+        )
+        .bind::<Int4, _>(for_canteen_id)
+        .bind::<Timestamptz, _>(range.starts)
+        .bind::<Timestamptz, _>(range.ends)
+        .bind::<Int4, _>(range.tz.local_minus_utc())
+        .bind::<Int4, _>(after_order_id)
+        .bind::<BigInt, _>(limit)
+        .load::<OrderRecord>(conn.connection())
+        .map_err(|e| {
+            error!(
+                "order_rows: error reading orders of canteen {}: {}",
+                for_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        Ok(records
+            .into_iter()
+            .map(|record| OrderExportRow {
+                order_id: record.order_id,
+                ordered_at: record.ordered_at,
+                business_date: record.business_date,
+                user_id: record.user_id,
+                status: order_status_label(record.order_status),
+                slot: record
+                    .deliver_at
+                    .as_ref()
+                    .map(|band| band.human_readable().to_string())
+                    .unwrap_or_else(|| "Instant".to_string()),
+                total: record.price,
+                merchant_order_id: record.merchant_order_id,
+                payment_state: record.payment_state,
+            })
+            .collect())
+    }
+
+    /// Items of the finished orders of the canteen placed in the range, after the item
+    /// `after` = (order_id, item_id).
+    pub fn order_item_rows(
+        &self,
+        for_canteen_id: i32,
+        range: &ReportRange,
+        after: (i32, i32),
+        limit: i64,
+    ) -> Result<Vec<OrderItemExportRow>, RepositoryError> {
+        let mut conn = self.connection(for_canteen_id)?;
+        let records = diesel::sql_query(
+            "SELECT i.order_id, p.ordered_at, \
+                    (p.ordered_at AT TIME ZONE 'UTC' + $4 * INTERVAL '1 second')::DATE AS business_date, \
+                    p.order_status, i.item_id, m.name AS item_name, i.quantity, i.price \
+             FROM past_order_items i \
+             JOIN past_orders p ON p.order_id = i.order_id \
+             LEFT JOIN menu_items m ON m.item_id = i.item_id \
+             WHERE p.canteen_id = $1 AND p.ordered_at >= $2 AND p.ordered_at < $3 \
+               AND (i.order_id, i.item_id) > ($5, $6) \
+             ORDER BY i.order_id, i.item_id \
+             LIMIT $7",
+        )
+        .bind::<Int4, _>(for_canteen_id)
+        .bind::<Timestamptz, _>(range.starts)
+        .bind::<Timestamptz, _>(range.ends)
+        .bind::<Int4, _>(range.tz.local_minus_utc())
+        .bind::<Int4, _>(after.0)
+        .bind::<Int4, _>(after.1)
+        .bind::<BigInt, _>(limit)
+        .load::<OrderItemRecord>(conn.connection())
+        .map_err(|e| {
+            error!(
+                "order_item_rows: error reading order items of canteen {}: {}",
+                for_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        Ok(records
+            .into_iter()
+            .map(|record| OrderItemExportRow {
+                order_id: record.order_id,
+                ordered_at: record.ordered_at,
+                business_date: record.business_date,
+                status: order_status_label(record.order_status),
+                item_id: record.item_id,
+                item_name: record.item_name,
+                quantity: record.quantity,
+                unit_price: record.price,
+                line_total: record.price * record.quantity as i32,
+            })
+            .collect())
+    }
+
+    /// Online payments for the canteen started in the range, after payment `after_payment_id`.
+    pub fn payment_rows(
+        &self,
+        for_canteen_id: i32,
+        range: &ReportRange,
+        after_payment_id: i32,
+        limit: i64,
+    ) -> Result<Vec<PaymentExportRow>, RepositoryError> {
+        let mut conn = self.connection(for_canteen_id)?;
+        let records = diesel::sql_query(
+            "SELECT po.payment_id, po.created_at, \
+                    (po.created_at AT TIME ZONE 'UTC' + $4 * INTERVAL '1 second')::DATE AS business_date, \
+                    po.merchant_order_id, po.phonepe_order_id, po.hold_id, po.app_order_id, \
+                    po.user_id, po.amount, po.payment_state, \
+                    COALESCE((SELECT SUM(r.amount) FROM refunds r \
+                              WHERE r.payment_id = po.payment_id AND r.state = $7), 0)::BIGINT AS refunded, \
+                    po.updated_at \
+             FROM payment_orders po \
+             WHERE po.canteen_id = $1 AND po.created_at >= $2 AND po.created_at < $3 \
+               AND po.payment_id > $5 \
+             ORDER BY po.payment_id \
+             LIMIT $6",
+        )
+        .bind::<Int4, _>(for_canteen_id)
+        .bind::<Timestamptz, _>(range.starts)
+        .bind::<Timestamptz, _>(range.ends)
+        .bind::<Int4, _>(range.tz.local_minus_utc())
+        .bind::<Int4, _>(after_payment_id)
+        .bind::<BigInt, _>(limit)
+        .bind::<Varchar, _>(REFUND_STATE_COMPLETED)
+        .load::<PaymentRecord>(conn.connection())
+        .map_err(|e| {
+            error!(
+                "payment_rows: error reading payments of canteen {}: {}",
+                for_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        Ok(records
+            .into_iter()
+            .map(|record| PaymentExportRow {
+                payment_id: record.payment_id,
+                created_at: record.created_at,
+                business_date: record.business_date,
+                merchant_order_id: record.merchant_order_id,
+                phonepe_order_id: record.phonepe_order_id,
+                hold_id: record.hold_id,
+                order_id: record.app_order_id,
+                user_id: record.user_id,
+                amount_paisa: record.amount,
+                payment_state: record.payment_state,
+                refunded_paisa: record.refunded,
+                updated_at: record.updated_at,
+            })
+            .collect())
+    }
+}
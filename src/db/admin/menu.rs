@@ -1,15 +1,36 @@
 use crate::db::errors::RepositoryError;
 use crate::db::schema::menu_items::dsl::*;
-use crate::db::{AssetOperations, DbConnection};
-use crate::enums::admin::MenuItemWithPic;
-use crate::models::admin::{MenuItem, NewMenuItem, UpdateMenuItem};
+use crate::db::{AssetOperations, DbConnection, OutboxOperations};
+use crate::enums::admin::{
+    MenuImportChange, MenuImportReport, MenuImportRowError, MenuItemWithPic, MenuTagCount,
+    MenuTransferRow,
+};
+use crate::models::admin::{
+    sanitize_category_name, sanitize_tag, validate_schedule_windows, MenuCategory, MenuItem,
+    MenuItemSchedule, NewMenuCategory, NewMenuItem, NewMenuItemSchedule, ScheduleWindow,
+    UpdateMenuCategory, UpdateMenuItem,
+};
+use crate::sse::InventoryUpdateItems;
+use chrono::NaiveDate;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::result::Error;
 use futures::future::join_all;
 use log::error;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+/// A menu item whose availability is driven by its weekly schedule.
+#[derive(Debug)]
+pub struct ScheduledItemState {
+    pub item_id: i32,
+    pub canteen_id: i32,
+    pub stock: i32,
+    pub is_available: bool,
+    pub manually_disabled: bool,
+    pub windows: Vec<ScheduleWindow>,
+}
+
 pub struct MenuOperations {
     pool: Pool<ConnectionManager<PgConnection>>,
     asset_ops: AssetOperations,
@@ -32,6 +53,10 @@ impl MenuOperations {
             e
         })?;
 
+        if let Some(target_category) = menu_item.category_id {
+            Self::ensure_category_owned(conn.connection(), target_category, menu_item.canteen_id)?;
+        }
+
         diesel::insert_into(menu_items)
             .values(&menu_item)
             .get_result(conn.connection())
@@ -237,25 +262,979 @@ impl MenuOperations {
             e
         })?;
 
-        diesel::update(
-            menu_items
+        if let Some(Some(target_category)) = changed_menu_item.category_id {
+            Self::ensure_category_owned(conn.connection(), target_category, owner_canteen_id)?;
+        }
+
+        conn.connection().transaction(|conn| {
+            let to_update = menu_items
                 .filter(item_id.eq(itemid))
-                .filter(canteen_id.eq(owner_canteen_id)),
+                .filter(canteen_id.eq(owner_canteen_id));
+            let map_err = |e: Error| {
+                error!(
+                    "update_menu_item: error updating menu item with id {} (canteen {}): {}",
+                    itemid, owner_canteen_id, e
+                );
+                match e {
+                    Error::NotFound => RepositoryError::NotFound(format!("menu_items: {itemid}")),
+                    other => RepositoryError::DatabaseError(other),
+                }
+            };
+
+            let previous_stock = to_update
+                .select(stock)
+                .for_update()
+                .first::<i32>(conn)
+                .map_err(map_err)?;
+            // Switching an item off by hand overrides the schedule and the stock reset, unless
+            // it is out of stock: then it is sold out and comes back with the next reset.
+            let new_stock = changed_menu_item.stock.unwrap_or(previous_stock);
+            let manual_override = changed_menu_item
+                .is_available
+                .map(|available| manually_disabled.eq(!available && new_stock != 0));
+            let updated = diesel::update(to_update)
+                .set((&changed_menu_item, manual_override))
+                .get_result::<MenuItem>(conn)
+                .map_err(map_err)?;
+            if previous_stock != 0 && updated.stock == 0 {
+                OutboxOperations::enqueue_stock_outs(
+                    conn,
+                    owner_canteen_id,
+                    &[(updated.item_id, updated.name.clone())],
+                )?;
+            }
+            Ok(updated)
+        })
+    }
+
+    pub fn get_menu_item_schedule(
+        &self,
+        itemid: i32,
+        owner_canteen_id: i32,
+    ) -> Result<Vec<ScheduleWindow>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_menu_item_schedule: failed to acquire DB connection for id {}: {}",
+                itemid, e
+            );
+            e
+        })?;
+
+        Self::ensure_item_owned(conn.connection(), itemid, owner_canteen_id)?;
+        Self::load_schedule_windows(conn.connection(), itemid)
+    }
+
+    /// Replace the weekly availability schedule of a menu item. An empty list removes the
+    /// schedule and leaves `is_available` under manual control again.
+    pub fn set_menu_item_schedule(
+        &self,
+        itemid: i32,
+        owner_canteen_id: i32,
+        windows: Vec<ScheduleWindow>,
+    ) -> Result<Vec<ScheduleWindow>, RepositoryError> {
+        validate_schedule_windows(&windows).map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "set_menu_item_schedule: failed to acquire DB connection for id {}: {}",
+                itemid, e
+            );
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            Self::ensure_item_owned(conn, itemid, owner_canteen_id)?;
+
+            use crate::db::schema::menu_item_schedules;
+            diesel::delete(
+                menu_item_schedules::table.filter(menu_item_schedules::item_id.eq(itemid)),
+            )
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+            let new_windows = windows
+                .iter()
+                .map(|window| NewMenuItemSchedule {
+                    item_id: itemid,
+                    weekday: window.weekday,
+                    start_time: window.start_time,
+                    end_time: window.end_time,
+                })
+                .collect::<Vec<NewMenuItemSchedule>>();
+            diesel::insert_into(menu_item_schedules::table)
+                .values(&new_windows)
+                .execute(conn)
+                .map_err(|e| {
+                    error!(
+                        "set_menu_item_schedule: error inserting schedule for item {}: {}",
+                        itemid, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+
+            Self::load_schedule_windows(conn, itemid)
+        })
+    }
+
+    /// Load every menu item that has at least one schedule window, with its windows.
+    pub fn list_scheduled_items(&self) -> Result<Vec<ScheduledItemState>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "list_scheduled_items: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        use crate::db::schema::menu_item_schedules;
+        let rows = menu_item_schedules::table
+            .inner_join(menu_items)
+            .select((
+                MenuItemSchedule::as_select(),
+                canteen_id,
+                stock,
+                is_available,
+                manually_disabled,
+            ))
+            .load::<(MenuItemSchedule, i32, i32, bool, bool)>(conn.connection())
+            .map_err(|e| {
+                error!("list_scheduled_items: error fetching schedules: {}", e);
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let mut grouped: HashMap<i32, ScheduledItemState> = HashMap::new();
+        for (schedule, item_canteen_id, item_stock, item_is_available, item_manually_disabled) in
+            rows
+        {
+            grouped
+                .entry(schedule.item_id)
+                .or_insert_with(|| ScheduledItemState {
+                    item_id: schedule.item_id,
+                    canteen_id: item_canteen_id,
+                    stock: item_stock,
+                    is_available: item_is_available,
+                    manually_disabled: item_manually_disabled,
+                    windows: Vec::new(),
+                })
+                .windows
+                .push((&schedule).into());
+        }
+
+        Ok(grouped.into_values().collect())
+    }
+
+    /// Apply the schedule outcome to a menu item. Inside a window the item is available
+    /// unless it is out of stock or switched off by hand; outside every window it is
+    /// unavailable.
+    /// Returns `None` when the item already had the expected availability.
+    pub fn apply_scheduled_availability(
+        &self,
+        itemid: i32,
+        within_schedule: bool,
+    ) -> Result<Option<(i32, InventoryUpdateItems)>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "apply_scheduled_availability: failed to acquire DB connection for id {}: {}",
+                itemid, e
+            );
+            e
+        })?;
+
+        let target = menu_items.filter(item_id.eq(itemid));
+        let result = if within_schedule {
+            let expected = stock.ne(0).and(manually_disabled.eq(false));
+            diesel::update(target.filter(is_available.ne(expected)))
+                .set(is_available.eq(expected))
+                .returning((canteen_id, item_id, stock, is_available, price))
+                .get_result::<(i32, i32, i32, bool, i32)>(conn.connection())
+                .optional()
+        } else {
+            diesel::update(target.filter(is_available.eq(true)))
+                .set(is_available.eq(false))
+                .returning((canteen_id, item_id, stock, is_available, price))
+                .get_result::<(i32, i32, i32, bool, i32)>(conn.connection())
+                .optional()
+        }
+        .map_err(|e| {
+            error!(
+                "apply_scheduled_availability: error updating availability for item {}: {}",
+                itemid, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+
+        Ok(result.map(
+            |(item_canteen_id, updated_id, updated_stock, updated_available, updated_price)| {
+                (
+                    item_canteen_id,
+                    InventoryUpdateItems {
+                        item_id: updated_id,
+                        stock: updated_stock,
+                        is_available: updated_available,
+                        price: updated_price,
+                    },
+                )
+            },
+        ))
+    }
+
+    /// Reset stock to `default_stock` for every item of the canteen that has one, and mark
+    /// the canteen as reset for `reset_on`. Running it twice for the same day is a no-op.
+    /// Items switched off by hand stay off; only items that had sold out are switched back on.
+    pub fn reset_daily_stock(
+        &self,
+        reset_canteen_id: i32,
+        reset_on: NaiveDate,
+    ) -> Result<Vec<InventoryUpdateItems>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "reset_daily_stock: failed to acquire DB connection for canteen {}: {}",
+                reset_canteen_id, e
+            );
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            {
+                use crate::db::schema::canteens;
+                let claimed = diesel::update(
+                    canteens::table
+                        .filter(canteens::canteen_id.eq(reset_canteen_id))
+                        .filter(
+                            canteens::last_stock_reset_on
+                                .is_null()
+                                .or(canteens::last_stock_reset_on.lt(reset_on)),
+                        ),
+                )
+                .set(canteens::last_stock_reset_on.eq(reset_on))
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+                if claimed == 0 {
+                    return Ok(Vec::new());
+                }
+            }
+
+            let sold_out = menu_items
+                .filter(canteen_id.eq(reset_canteen_id))
+                .filter(default_stock.eq(0))
+                .filter(stock.ne(0))
+                .select((item_id, name))
+                .load::<(i32, String)>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+
+            let rows = diesel::update(
+                menu_items
+                    .filter(canteen_id.eq(reset_canteen_id))
+                    .filter(default_stock.is_not_null()),
+            )
+            .set((
+                stock.eq(default_stock.assume_not_null()),
+                // SET sees the row as it was, so `stock` here is the stock before the reset.
+                is_available.eq(is_available
+                    .or(stock.eq(0))
+                    .and(manually_disabled.eq(false))
+                    .and(default_stock.assume_not_null().ne(0))),
+            ))
+            .returning((item_id, stock, is_available, price))
+            .get_results::<(i32, i32, bool, i32)>(conn)
+            .map_err(|e| {
+                error!(
+                    "reset_daily_stock: error resetting stock for canteen {}: {}",
+                    reset_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+            OutboxOperations::enqueue_stock_outs(conn, reset_canteen_id, &sold_out)?;
+
+            Ok(rows
+                .into_iter()
+                .map(
+                    |(updated_id, updated_stock, updated_available, updated_price)| {
+                        InventoryUpdateItems {
+                            item_id: updated_id,
+                            stock: updated_stock,
+                            is_available: updated_available,
+                            price: updated_price,
+                        }
+                    },
+                )
+                .collect())
+        })
+    }
+
+    pub fn create_category(
+        &self,
+        new_category: NewMenuCategory,
+    ) -> Result<MenuCategory, RepositoryError> {
+        let new_category = new_category
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_category: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        use crate::db::schema::menu_categories;
+        diesel::insert_into(menu_categories::table)
+            .values(&new_category)
+            .returning(MenuCategory::as_returning())
+            .get_result(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "create_category: error inserting category '{}' for canteen {}: {}",
+                    new_category.name, new_category.canteen_id, e
+                );
+                Self::map_category_conflict(e, &new_category.name)
+            })
+    }
+
+    /// Categories of a canteen in the order they should be displayed.
+    pub fn list_categories(
+        &self,
+        owner_canteen_id: i32,
+    ) -> Result<Vec<MenuCategory>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_categories: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        use crate::db::schema::menu_categories;
+        menu_categories::table
+            .filter(menu_categories::canteen_id.eq(owner_canteen_id))
+            .order((
+                menu_categories::display_order.asc(),
+                menu_categories::category_id.asc(),
+            ))
+            .select(MenuCategory::as_select())
+            .load(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "list_categories: error fetching categories for canteen {}: {}",
+                    owner_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    pub fn update_category(
+        &self,
+        target_category_id: i32,
+        owner_canteen_id: i32,
+        changed_category: UpdateMenuCategory,
+    ) -> Result<MenuCategory, RepositoryError> {
+        let changed_category = changed_category
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        if changed_category.name.is_none() && changed_category.display_order.is_none() {
+            return Err(RepositoryError::ValidationError(
+                "nothing to update".to_string(),
+            ));
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "update_category: failed to acquire DB connection for id {}: {}",
+                target_category_id, e
+            );
+            e
+        })?;
+
+        use crate::db::schema::menu_categories;
+        diesel::update(
+            menu_categories::table
+                .filter(menu_categories::category_id.eq(target_category_id))
+                .filter(menu_categories::canteen_id.eq(owner_canteen_id)),
         )
-        .set(&changed_menu_item)
+        .set(&changed_category)
+        .returning(MenuCategory::as_returning())
         .get_result(conn.connection())
         .map_err(|e| {
             error!(
-                "update_menu_item: error updating menu item with id {} (canteen {}): {}",
-                itemid, owner_canteen_id, e
+                "update_category: error updating category {} (canteen {}): {}",
+                target_category_id, owner_canteen_id, e
             );
             match e {
-                Error::NotFound => RepositoryError::NotFound(format!("menu_items: {itemid}")),
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("menu_categories: {target_category_id}"))
+                }
+                other => Self::map_category_conflict(
+                    other,
+                    changed_category.name.as_deref().unwrap_or_default(),
+                ),
+            }
+        })
+    }
+
+    /// Delete a category. Its items stay on the menu without a category.
+    pub fn delete_category(
+        &self,
+        target_category_id: i32,
+        owner_canteen_id: i32,
+    ) -> Result<MenuCategory, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "delete_category: failed to acquire DB connection for id {}: {}",
+                target_category_id, e
+            );
+            e
+        })?;
+
+        use crate::db::schema::menu_categories;
+        diesel::delete(
+            menu_categories::table
+                .filter(menu_categories::category_id.eq(target_category_id))
+                .filter(menu_categories::canteen_id.eq(owner_canteen_id)),
+        )
+        .returning(MenuCategory::as_returning())
+        .get_result(conn.connection())
+        .map_err(|e| {
+            error!(
+                "delete_category: error deleting category {} (canteen {}): {}",
+                target_category_id, owner_canteen_id, e
+            );
+            match e {
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("menu_categories: {target_category_id}"))
+                }
                 other => RepositoryError::DatabaseError(other),
             }
         })
     }
 
+    /// Every tag used on the canteen's menu with the number of items carrying it,
+    /// most used first.
+    pub fn list_tags(&self, owner_canteen_id: i32) -> Result<Vec<MenuTagCount>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_tags: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        let item_tags = menu_items
+            .filter(canteen_id.eq(owner_canteen_id))
+            .select(tags)
+            .load::<Vec<String>>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "list_tags: error fetching tags for canteen {}: {}",
+                    owner_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for tag in item_tags.into_iter().flatten() {
+            *counts.entry(tag).or_default() += 1;
+        }
+        let mut result = counts
+            .into_iter()
+            .map(|(tag, item_count)| MenuTagCount { tag, item_count })
+            .collect::<Vec<MenuTagCount>>();
+        result.sort_by(|a, b| b.item_count.cmp(&a.item_count).then(a.tag.cmp(&b.tag)));
+        Ok(result)
+    }
+
+    /// Rename a tag on every item of the canteen. Returns the number of items changed.
+    pub fn rename_tag(
+        &self,
+        owner_canteen_id: i32,
+        old_tag: &str,
+        new_tag: &str,
+    ) -> Result<usize, RepositoryError> {
+        let old_tag = sanitize_tag(old_tag).map_err(RepositoryError::ValidationError)?;
+        let new_tag = sanitize_tag(new_tag).map_err(RepositoryError::ValidationError)?;
+        self.rewrite_tag(owner_canteen_id, &old_tag, |item_tags| {
+            item_tags.retain(|tag| *tag != old_tag);
+            if !item_tags.contains(&new_tag) {
+                item_tags.push(new_tag.clone());
+            }
+        })
+    }
+
+    /// Remove a tag from every item of the canteen. Returns the number of items changed.
+    pub fn delete_tag(
+        &self,
+        owner_canteen_id: i32,
+        target_tag: &str,
+    ) -> Result<usize, RepositoryError> {
+        let target_tag = sanitize_tag(target_tag).map_err(RepositoryError::ValidationError)?;
+        self.rewrite_tag(owner_canteen_id, &target_tag, |item_tags| {
+            item_tags.retain(|tag| *tag != target_tag);
+        })
+    }
+
+    fn rewrite_tag(
+        &self,
+        owner_canteen_id: i32,
+        target_tag: &str,
+        rewrite: impl Fn(&mut Vec<String>),
+    ) -> Result<usize, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("rewrite_tag: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            let tagged = menu_items
+                .filter(canteen_id.eq(owner_canteen_id))
+                .filter(tags.contains(vec![target_tag.to_string()]))
+                .select((item_id, tags))
+                .for_update()
+                .load::<(i32, Vec<String>)>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            if tagged.is_empty() {
+                return Err(RepositoryError::NotFound(format!("tag: {target_tag}")));
+            }
+
+            for (tagged_id, mut item_tags) in tagged.iter().cloned() {
+                rewrite(&mut item_tags);
+                diesel::update(menu_items.filter(item_id.eq(tagged_id)))
+                    .set(tags.eq(item_tags))
+                    .execute(conn)
+                    .map_err(|e| {
+                        error!(
+                            "rewrite_tag: error updating tags of item {}: {}",
+                            tagged_id, e
+                        );
+                        RepositoryError::DatabaseError(e)
+                    })?;
+            }
+            Ok(tagged.len())
+        })
+    }
+
+    /// Apply an imported menu to the canteen in a single transaction.
+    ///
+    /// Rows are matched to existing items by `item_id`, or by name when the id is missing;
+    /// items that are not in the file are left untouched. Every row goes through the same
+    /// validation as [`NewMenuItem`], and nothing is written when `dry_run` is set or any row
+    /// fails. Returns the report plus inventory updates for items that were written.
+    pub fn import_menu(
+        &self,
+        owner_canteen_id: i32,
+        rows: Vec<MenuTransferRow>,
+        dry_run: bool,
+    ) -> Result<(MenuImportReport, Vec<InventoryUpdateItems>), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("import_menu: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            use crate::db::schema::menu_categories;
+            let existing_items = menu_items
+                .filter(canteen_id.eq(owner_canteen_id))
+                .select(MenuItem::as_select())
+                .for_update()
+                .load::<MenuItem>(conn)
+                .map_err(|e| {
+                    error!(
+                        "import_menu: error fetching menu of canteen {}: {}",
+                        owner_canteen_id, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+            let existing_categories = menu_categories::table
+                .filter(menu_categories::canteen_id.eq(owner_canteen_id))
+                .select(MenuCategory::as_select())
+                .load::<MenuCategory>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+
+            let items_by_id: HashMap<i32, &MenuItem> = existing_items
+                .iter()
+                .map(|item| (item.item_id, item))
+                .collect();
+            let mut items_by_name: HashMap<String, Vec<i32>> = HashMap::new();
+            for item in &existing_items {
+                items_by_name
+                    .entry(item.name.to_lowercase())
+                    .or_default()
+                    .push(item.item_id);
+            }
+            let mut category_ids: HashMap<String, i32> = existing_categories
+                .iter()
+                .map(|category| (category.name.to_lowercase(), category.category_id))
+                .collect();
+
+            let mut report = MenuImportReport {
+                dry_run,
+                ..Default::default()
+            };
+            let mut new_category_names: Vec<String> = Vec::new();
+            let mut seen_items: HashSet<i32> = HashSet::new();
+            let mut seen_new_names: HashSet<String> = HashSet::new();
+            let mut inserts: Vec<(usize, NewMenuItem, Option<String>)> = Vec::new();
+            let mut updates: Vec<(i32, NewMenuItem, Option<String>)> = Vec::new();
+
+            for (index, row) in rows.into_iter().enumerate() {
+                let row_no = index + 1;
+                let mut fail = |error: String| {
+                    report
+                        .errors
+                        .push(MenuImportRowError { row: row_no, error });
+                };
+
+                let category_key = match row.category.as_deref().map(sanitize_category_name) {
+                    None => None,
+                    Some(Ok(category_name)) => {
+                        let key = category_name.to_lowercase();
+                        if !category_ids.contains_key(&key)
+                            && !new_category_names
+                                .iter()
+                                .any(|planned| planned.to_lowercase() == key)
+                        {
+                            new_category_names.push(category_name);
+                        }
+                        Some(key)
+                    }
+                    Some(Err(e)) => {
+                        fail(e);
+                        continue;
+                    }
+                };
+
+                let candidate = NewMenuItem {
+                    canteen_id: owner_canteen_id,
+                    name: row.name,
+                    is_veg: row.is_veg,
+                    price: row.price,
+                    stock: row.stock,
+                    is_available: row.is_available,
+                    description: row.description,
+                    default_stock: row.default_stock,
+                    category_id: None,
+                    display_order: row.display_order,
+                    is_pinned: row.is_pinned,
+                    tags: row.tags,
+                    allergens: row.allergens,
+                    diet_labels: row.diet_labels,
+                };
+                let candidate = match candidate.sanitize_and_validate() {
+                    Ok(candidate) => candidate,
+                    Err(e) => {
+                        fail(e);
+                        continue;
+                    }
+                };
+
+                let name_key = candidate.name.to_lowercase();
+                let target = match row.item_id {
+                    Some(target_id) if items_by_id.contains_key(&target_id) => Some(target_id),
+                    Some(target_id) => {
+                        fail(format!("item {target_id} does not belong to this canteen"));
+                        continue;
+                    }
+                    None => match items_by_name.get(&name_key).map(Vec::as_slice) {
+                        None | Some([]) => None,
+                        Some([target_id]) => Some(*target_id),
+                        Some(_) => {
+                            fail(format!(
+                                "several items are named '{}'; set item_id to pick one",
+                                candidate.name
+                            ));
+                            continue;
+                        }
+                    },
+                };
+
+                match target {
+                    Some(target_id) => {
+                        if !seen_items.insert(target_id) {
+                            fail(format!("item {target_id} appears more than once"));
+                            continue;
+                        }
+                        let current = items_by_id[&target_id];
+                        let current_category_key = existing_categories
+                            .iter()
+                            .find(|category| Some(category.category_id) == current.category_id)
+                            .map(|category| category.name.to_lowercase());
+                        let changed_fields = Self::changed_fields(
+                            current,
+                            &candidate,
+                            &current_category_key,
+                            &category_key,
+                        );
+                        if changed_fields.is_empty() {
+                            report.unchanged += 1;
+                            continue;
+                        }
+                        report.updated.push(MenuImportChange {
+                            row: row_no,
+                            item_id: Some(target_id),
+                            name: candidate.name.clone(),
+                            changed_fields,
+                        });
+                        updates.push((target_id, candidate, category_key));
+                    }
+                    None => {
+                        if !seen_new_names.insert(name_key) {
+                            fail(format!("item '{}' appears more than once", candidate.name));
+                            continue;
+                        }
+                        report.created.push(MenuImportChange {
+                            row: row_no,
+                            item_id: None,
+                            name: candidate.name.clone(),
+                            changed_fields: Vec::new(),
+                        });
+                        inserts.push((report.created.len() - 1, candidate, category_key));
+                    }
+                }
+            }
+
+            report.created_categories = new_category_names.clone();
+            if dry_run || !report.errors.is_empty() {
+                return Ok((report, Vec::new()));
+            }
+
+            for category_name in new_category_names {
+                let created = diesel::insert_into(menu_categories::table)
+                    .values(&NewMenuCategory {
+                        canteen_id: owner_canteen_id,
+                        name: category_name.clone(),
+                        display_order: 0,
+                    })
+                    .returning(MenuCategory::as_returning())
+                    .get_result(conn)
+                    .map_err(|e| {
+                        error!(
+                            "import_menu: error creating category '{}' for canteen {}: {}",
+                            category_name, owner_canteen_id, e
+                        );
+                        Self::map_category_conflict(e, &category_name)
+                    })?;
+                category_ids.insert(category_name.to_lowercase(), created.category_id);
+            }
+            let resolve_category =
+                |key: &Option<String>| key.as_ref().and_then(|key| category_ids.get(key).copied());
+
+            let mut inventory_updates = Vec::new();
+            for (created_index, mut candidate, category_key) in inserts {
+                candidate.category_id = resolve_category(&category_key);
+                let inserted = diesel::insert_into(menu_items)
+                    .values(&candidate)
+                    .returning(MenuItem::as_returning())
+                    .get_result(conn)
+                    .map_err(|e| {
+                        error!(
+                            "import_menu: error inserting item '{}' for canteen {}: {}",
+                            candidate.name, owner_canteen_id, e
+                        );
+                        RepositoryError::DatabaseError(e)
+                    })?;
+                report.created[created_index].item_id = Some(inserted.item_id);
+                inventory_updates.push(InventoryUpdateItems {
+                    item_id: inserted.item_id,
+                    stock: inserted.stock,
+                    is_available: inserted.is_available,
+                    price: inserted.price,
+                });
+            }
+
+            let mut sold_out = Vec::new();
+            for (target_id, candidate, category_key) in updates {
+                let current = items_by_id[&target_id];
+                if current.stock != 0 && candidate.stock == 0 {
+                    sold_out.push((target_id, candidate.name.clone()));
+                }
+                // Switching an item off in the import is a manual override, like doing it
+                // by hand; items the schedule had switched off are left to the schedule.
+                let still_or_newly_off = !candidate.is_available
+                    && candidate.stock != 0
+                    && (current.manually_disabled || current.is_available);
+                diesel::update(menu_items.filter(item_id.eq(target_id)))
+                    .set((
+                        name.eq(&candidate.name),
+                        is_veg.eq(candidate.is_veg),
+                        price.eq(candidate.price),
+                        stock.eq(candidate.stock),
+                        is_available.eq(candidate.is_available),
+                        manually_disabled.eq(still_or_newly_off),
+                        description.eq(&candidate.description),
+                        default_stock.eq(candidate.default_stock),
+                        category_id.eq(resolve_category(&category_key)),
+                        display_order.eq(candidate.display_order),
+                        is_pinned.eq(candidate.is_pinned),
+                        tags.eq(&candidate.tags),
+                        allergens.eq(&candidate.allergens),
+                        diet_labels.eq(&candidate.diet_labels),
+                    ))
+                    .execute(conn)
+                    .map_err(|e| {
+                        error!(
+                            "import_menu: error updating item {} for canteen {}: {}",
+                            target_id, owner_canteen_id, e
+                        );
+                        RepositoryError::DatabaseError(e)
+                    })?;
+                inventory_updates.push(InventoryUpdateItems {
+                    item_id: target_id,
+                    stock: candidate.stock,
+                    is_available: candidate.is_available,
+                    price: candidate.price,
+                });
+            }
+
+            OutboxOperations::enqueue_stock_outs(conn, owner_canteen_id, &sold_out)?;
+
+            report.applied = true;
+            Ok((report, inventory_updates))
+        })
+    }
+
+    /// Names of the fields an import row would change on an existing item.
+    fn changed_fields(
+        current: &MenuItem,
+        candidate: &NewMenuItem,
+        current_category: &Option<String>,
+        candidate_category: &Option<String>,
+    ) -> Vec<String> {
+        let checks = [
+            ("name", current.name != candidate.name),
+            ("is_veg", current.is_veg != candidate.is_veg),
+            ("price", current.price != candidate.price),
+            ("stock", current.stock != candidate.stock),
+            (
+                "is_available",
+                current.is_available != candidate.is_available,
+            ),
+            ("description", current.description != candidate.description),
+            (
+                "default_stock",
+                current.default_stock != candidate.default_stock,
+            ),
+            ("category", current_category != candidate_category),
+            (
+                "display_order",
+                current.display_order != candidate.display_order,
+            ),
+            ("is_pinned", current.is_pinned != candidate.is_pinned),
+            ("tags", current.tags != candidate.tags),
+            ("allergens", current.allergens != candidate.allergens),
+            ("diet_labels", current.diet_labels != candidate.diet_labels),
+        ];
+        checks
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(field, _)| field.to_string())
+            .collect()
+    }
+
+    /// The canteen's menu in import format, ordered by item id.
+    pub fn export_menu(
+        &self,
+        owner_canteen_id: i32,
+    ) -> Result<Vec<MenuTransferRow>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("export_menu: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        use crate::db::schema::menu_categories;
+        let rows = menu_items
+            .left_join(menu_categories::table)
+            .filter(canteen_id.eq(owner_canteen_id))
+            .order(item_id.asc())
+            .select((MenuItem::as_select(), menu_categories::name.nullable()))
+            .load::<(MenuItem, Option<String>)>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "export_menu: error fetching menu of canteen {}: {}",
+                    owner_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(item, category)| MenuTransferRow {
+                item_id: Some(item.item_id),
+                name: item.name,
+                is_veg: item.is_veg,
+                price: item.price,
+                stock: item.stock,
+                is_available: item.is_available,
+                description: item.description,
+                default_stock: item.default_stock,
+                category,
+                display_order: item.display_order,
+                is_pinned: item.is_pinned,
+                tags: item.tags,
+                allergens: item.allergens,
+                diet_labels: item.diet_labels,
+            })
+            .collect())
+    }
+
+    fn ensure_category_owned(
+        conn: &mut PgConnection,
+        target_category_id: i32,
+        owner_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        use crate::db::schema::menu_categories;
+        menu_categories::table
+            .filter(menu_categories::category_id.eq(target_category_id))
+            .filter(menu_categories::canteen_id.eq(owner_canteen_id))
+            .select(menu_categories::category_id)
+            .first::<i32>(conn)
+            .map(|_| ())
+            .map_err(|e| match e {
+                Error::NotFound => RepositoryError::ValidationError(format!(
+                    "category {target_category_id} does not belong to this canteen"
+                )),
+                other => RepositoryError::DatabaseError(other),
+            })
+    }
+
+    fn map_category_conflict(e: Error, category_name: &str) -> RepositoryError {
+        match e {
+            Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => {
+                RepositoryError::ValidationError(format!(
+                    "a category named '{category_name}' already exists"
+                ))
+            }
+            other => RepositoryError::DatabaseError(other),
+        }
+    }
+
+    fn ensure_item_owned(
+        conn: &mut PgConnection,
+        itemid: i32,
+        owner_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        menu_items
+            .filter(item_id.eq(itemid))
+            .filter(canteen_id.eq(owner_canteen_id))
+            .select(item_id)
+            .first::<i32>(conn)
+            .map(|_| ())
+            .map_err(|e| match e {
+                Error::NotFound => RepositoryError::NotFound(format!("menu_items: {itemid}")),
+                other => RepositoryError::DatabaseError(other),
+            })
+    }
+
+    fn load_schedule_windows(
+        conn: &mut PgConnection,
+        itemid: i32,
+    ) -> Result<Vec<ScheduleWindow>, RepositoryError> {
+        use crate::db::schema::menu_item_schedules;
+        let schedules = menu_item_schedules::table
+            .filter(menu_item_schedules::item_id.eq(itemid))
+            .order((
+                menu_item_schedules::weekday.asc(),
+                menu_item_schedules::start_time.asc(),
+            ))
+            .select(MenuItemSchedule::as_select())
+            .load::<MenuItemSchedule>(conn)
+            .map_err(|e| {
+                error!(
+                    "load_schedule_windows: error fetching schedule for item {}: {}",
+                    itemid, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        Ok(schedules.iter().map(ScheduleWindow::from).collect())
+    }
+
     pub async fn get_all_menu_items(&self) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!("get_all_menu_items: failed to acquire DB connection: {}", e);
@@ -1,3 +1,9 @@
+pub(crate) mod analytics;
 pub(crate) mod asset_management;
 pub(crate) mod canteen;
+pub(crate) mod exports;
 pub(crate) mod menu;
+pub(crate) mod pricing;
+pub(crate) mod promo;
+pub(crate) mod settlements;
+pub(crate) mod webhooks;
@@ -0,0 +1,253 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{menu_items, pricing_rule_windows, pricing_rules};
+use crate::db::DbConnection;
+use crate::enums::admin::PricingRuleDetails;
+use crate::models::admin::{
+    validate_schedule_windows, NewPricingRule, NewPricingRuleWindow, PricingRule,
+    PricingRuleWindow, ScheduleWindow,
+};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::Error;
+use log::error;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone)]
+pub struct PricingOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl PricingOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    pub fn create_rule(
+        &self,
+        new_rule: NewPricingRule,
+        windows: Vec<ScheduleWindow>,
+    ) -> Result<PricingRuleDetails, RepositoryError> {
+        let new_rule = new_rule
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        validate_schedule_windows(&windows).map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_rule: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            Self::ensure_items_owned(conn, &new_rule.item_ids, new_rule.canteen_id)?;
+            let rule = diesel::insert_into(pricing_rules::table)
+                .values(&new_rule)
+                .returning(PricingRule::as_returning())
+                .get_result(conn)
+                .map_err(|e| {
+                    error!(
+                        "create_rule: error inserting rule '{}' for canteen {}: {}",
+                        new_rule.name, new_rule.canteen_id, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+            Self::replace_windows(conn, rule.rule_id, &windows)?;
+            Ok(PricingRuleDetails::new(rule, windows))
+        })
+    }
+
+    /// Every rule of the canteen, active or not, ordered by id.
+    pub fn list_rules(
+        &self,
+        owner_canteen_id: i32,
+    ) -> Result<Vec<PricingRuleDetails>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_rules: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        Self::load_rules(conn.connection(), owner_canteen_id, false)
+    }
+
+    /// Replace a rule and its windows wholesale.
+    pub fn update_rule(
+        &self,
+        target_rule_id: i32,
+        changed_rule: NewPricingRule,
+        windows: Vec<ScheduleWindow>,
+    ) -> Result<PricingRuleDetails, RepositoryError> {
+        let changed_rule = changed_rule
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        validate_schedule_windows(&windows).map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "update_rule: failed to acquire DB connection for id {}: {}",
+                target_rule_id, e
+            );
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            Self::ensure_items_owned(conn, &changed_rule.item_ids, changed_rule.canteen_id)?;
+            let rule = diesel::update(
+                pricing_rules::table
+                    .filter(pricing_rules::rule_id.eq(target_rule_id))
+                    .filter(pricing_rules::canteen_id.eq(changed_rule.canteen_id)),
+            )
+            .set(&changed_rule)
+            .returning(PricingRule::as_returning())
+            .get_result(conn)
+            .map_err(|e| {
+                error!(
+                    "update_rule: error updating rule {} (canteen {}): {}",
+                    target_rule_id, changed_rule.canteen_id, e
+                );
+                match e {
+                    Error::NotFound => {
+                        RepositoryError::NotFound(format!("pricing_rules: {target_rule_id}"))
+                    }
+                    other => RepositoryError::DatabaseError(other),
+                }
+            })?;
+            Self::replace_windows(conn, rule.rule_id, &windows)?;
+            Ok(PricingRuleDetails::new(rule, windows))
+        })
+    }
+
+    pub fn delete_rule(
+        &self,
+        target_rule_id: i32,
+        owner_canteen_id: i32,
+    ) -> Result<PricingRule, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "delete_rule: failed to acquire DB connection for id {}: {}",
+                target_rule_id, e
+            );
+            e
+        })?;
+
+        diesel::delete(
+            pricing_rules::table
+                .filter(pricing_rules::rule_id.eq(target_rule_id))
+                .filter(pricing_rules::canteen_id.eq(owner_canteen_id)),
+        )
+        .returning(PricingRule::as_returning())
+        .get_result(conn.connection())
+        .map_err(|e| {
+            error!(
+                "delete_rule: error deleting rule {} (canteen {}): {}",
+                target_rule_id, owner_canteen_id, e
+            );
+            match e {
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("pricing_rules: {target_rule_id}"))
+                }
+                other => RepositoryError::DatabaseError(other),
+            }
+        })
+    }
+
+    /// Rules of a canteen with their windows. Used inside the hold transaction, so it takes
+    /// a connection rather than the pool.
+    pub(crate) fn load_rules(
+        conn: &mut PgConnection,
+        owner_canteen_id: i32,
+        only_active: bool,
+    ) -> Result<Vec<PricingRuleDetails>, RepositoryError> {
+        let mut query = pricing_rules::table
+            .filter(pricing_rules::canteen_id.eq(owner_canteen_id))
+            .into_boxed();
+        if only_active {
+            query = query.filter(pricing_rules::is_active.eq(true));
+        }
+        let rules = query
+            .order(pricing_rules::rule_id.asc())
+            .select(PricingRule::as_select())
+            .load::<PricingRule>(conn)
+            .map_err(|e| {
+                error!(
+                    "load_rules: error fetching pricing rules for canteen {}: {}",
+                    owner_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let rule_ids = rules.iter().map(|rule| rule.rule_id).collect::<Vec<i32>>();
+        let windows = pricing_rule_windows::table
+            .filter(pricing_rule_windows::rule_id.eq_any(&rule_ids))
+            .order((
+                pricing_rule_windows::weekday.asc(),
+                pricing_rule_windows::start_time.asc(),
+            ))
+            .select(PricingRuleWindow::as_select())
+            .load::<PricingRuleWindow>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        let mut windows_by_rule: HashMap<i32, Vec<ScheduleWindow>> = HashMap::new();
+        for window in &windows {
+            windows_by_rule
+                .entry(window.rule_id)
+                .or_default()
+                .push(ScheduleWindow::from(window));
+        }
+
+        Ok(rules
+            .into_iter()
+            .map(|rule| {
+                let rule_windows = windows_by_rule.remove(&rule.rule_id).unwrap_or_default();
+                PricingRuleDetails::new(rule, rule_windows)
+            })
+            .collect())
+    }
+
+    fn replace_windows(
+        conn: &mut PgConnection,
+        target_rule_id: i32,
+        windows: &[ScheduleWindow],
+    ) -> Result<(), RepositoryError> {
+        diesel::delete(
+            pricing_rule_windows::table.filter(pricing_rule_windows::rule_id.eq(target_rule_id)),
+        )
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        let new_windows = windows
+            .iter()
+            .map(|window| NewPricingRuleWindow {
+                rule_id: target_rule_id,
+                weekday: window.weekday,
+                start_time: window.start_time,
+                end_time: window.end_time,
+            })
+            .collect::<Vec<NewPricingRuleWindow>>();
+        diesel::insert_into(pricing_rule_windows::table)
+            .values(&new_windows)
+            .execute(conn)
+            .map_err(|e| {
+                error!(
+                    "replace_windows: error inserting windows for rule {}: {}",
+                    target_rule_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+        Ok(())
+    }
+
+    fn ensure_items_owned(
+        conn: &mut PgConnection,
+        rule_item_ids: &[i32],
+        owner_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let wanted = rule_item_ids.iter().copied().collect::<HashSet<i32>>();
+        let owned = menu_items::table
+            .filter(menu_items::item_id.eq_any(&wanted))
+            .filter(menu_items::canteen_id.eq(owner_canteen_id))
+            .select(menu_items::item_id)
+            .load::<i32>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        match wanted.iter().find(|item| !owned.contains(item)) {
+            Some(foreign) => Err(RepositoryError::ValidationError(format!(
+                "item {foreign} does not belong to this canteen"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{active_orders, past_orders, promo_codes, promo_redemptions};
+use crate::db::DbConnection;
+use crate::enums::admin::PromoCodeDetails;
+use crate::enums::common::AppliedPromoCode;
+use crate::models::admin::{sanitize_promo_code, NewPromoCode, PromoCode};
+use chrono::{DateTime, Utc};
+use diesel::dsl::{count_star, exists};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::{DatabaseErrorKind, Error};
+use log::error;
+use std::collections::HashMap;
+
+/// Whose promo codes a caller manages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromoScope {
+    /// A canteen admin, managing the canteen's own codes.
+    Canteen(i32),
+    /// A platform operator, managing platform-wide codes.
+    Platform,
+}
+
+impl PromoScope {
+    /// The `canteen_id` of codes in this scope.
+    pub fn canteen_id(&self) -> Option<i32> {
+        match self {
+            PromoScope::Canteen(canteen_id) => Some(*canteen_id),
+            PromoScope::Platform => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PromoOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl PromoOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// `new_code.canteen_id` is `None` for a platform-wide code; the caller sets it from
+    /// its [`PromoScope`].
+    pub fn create_code(&self, new_code: NewPromoCode) -> Result<PromoCodeDetails, RepositoryError> {
+        let new_code = new_code
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_code: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        diesel::insert_into(promo_codes::table)
+            .values(&new_code)
+            .returning(PromoCode::as_returning())
+            .get_result(conn.connection())
+            .map(|promo| PromoCodeDetails {
+                promo,
+                times_redeemed: 0,
+            })
+            .map_err(|e| {
+                error!(
+                    "create_code: error inserting promo code '{}': {}",
+                    new_code.code, e
+                );
+                Self::map_code_conflict(e, &new_code.code)
+            })
+    }
+
+    /// Codes in the scope, ordered by id.
+    pub fn list_codes(&self, scope: PromoScope) -> Result<Vec<PromoCodeDetails>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_codes: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let conn = conn.connection();
+
+        let mut query = promo_codes::table.into_boxed();
+        query = match scope {
+            PromoScope::Canteen(owner_canteen_id) => {
+                query.filter(promo_codes::canteen_id.eq(owner_canteen_id))
+            }
+            PromoScope::Platform => query.filter(promo_codes::canteen_id.is_null()),
+        };
+        let codes = query
+            .order(promo_codes::promo_id.asc())
+            .select(PromoCode::as_select())
+            .load::<PromoCode>(conn)
+            .map_err(|e| {
+                error!(
+                    "list_codes: error fetching promo codes of {:?}: {}",
+                    scope, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let promo_ids = codes.iter().map(|c| c.promo_id).collect::<Vec<i32>>();
+        let counts = promo_redemptions::table
+            .filter(promo_redemptions::promo_id.eq_any(&promo_ids))
+            .group_by(promo_redemptions::promo_id)
+            .select((promo_redemptions::promo_id, count_star()))
+            .load::<(i32, i64)>(conn)
+            .map_err(RepositoryError::DatabaseError)?
+            .into_iter()
+            .collect::<HashMap<i32, i64>>();
+
+        Ok(codes
+            .into_iter()
+            .map(|promo| PromoCodeDetails {
+                times_redeemed: counts.get(&promo.promo_id).copied().unwrap_or(0),
+                promo,
+            })
+            .collect())
+    }
+
+    /// Replace a code wholesale. The code stays in `scope`: `changed_code.canteen_id` must be
+    /// the scope's.
+    pub fn update_code(
+        &self,
+        target_promo_id: i32,
+        scope: PromoScope,
+        changed_code: NewPromoCode,
+    ) -> Result<PromoCodeDetails, RepositoryError> {
+        let changed_code = changed_code
+            .sanitize_and_validate()
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "update_code: failed to acquire DB connection for id {}: {}",
+                target_promo_id, e
+            );
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            Self::find_managed_code(conn, target_promo_id, scope)?;
+            let promo = diesel::update(promo_codes::table.find(target_promo_id))
+                .set(&changed_code)
+                .returning(PromoCode::as_returning())
+                .get_result(conn)
+                .map_err(|e| {
+                    error!(
+                        "update_code: error updating promo code {}: {}",
+                        target_promo_id, e
+                    );
+                    Self::map_code_conflict(e, &changed_code.code)
+                })?;
+            let times_redeemed = promo_redemptions::table
+                .filter(promo_redemptions::promo_id.eq(target_promo_id))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            Ok(PromoCodeDetails {
+                promo,
+                times_redeemed,
+            })
+        })
+    }
+
+    /// Deleting a code also deletes its redemptions; holds already priced with it keep
+    /// their discounted total.
+    pub fn delete_code(
+        &self,
+        target_promo_id: i32,
+        scope: PromoScope,
+    ) -> Result<PromoCode, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "delete_code: failed to acquire DB connection for id {}: {}",
+                target_promo_id, e
+            );
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            Self::find_managed_code(conn, target_promo_id, scope)?;
+            diesel::delete(promo_codes::table.find(target_promo_id))
+                .returning(PromoCode::as_returning())
+                .get_result(conn)
+                .map_err(|e| {
+                    error!(
+                        "delete_code: error deleting promo code {}: {}",
+                        target_promo_id, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })
+        })
+    }
+
+    /// Check that `raw_code` can be redeemed by the user on an order of `order_total` at the
+    /// canteen, and work out its discount. Locks the code row so concurrent holds cannot
+    /// overshoot its usage limits; must run inside the hold transaction, followed by
+    /// [`Self::record_redemption`].
+    pub(crate) fn apply_code(
+        conn: &mut PgConnection,
+        raw_code: &str,
+        redeeming_user_id: i32,
+        order_canteen_id: i32,
+        order_total: i32,
+        now: DateTime<Utc>,
+    ) -> Result<(i32, AppliedPromoCode), RepositoryError> {
+        let invalid = |reason: &str| {
+            RepositoryError::ValidationError(format!("promo code {raw_code} {reason}"))
+        };
+        let code = sanitize_promo_code(raw_code).map_err(|_| invalid("is not valid"))?;
+        let promo = promo_codes::table
+            .filter(promo_codes::code.eq(&code))
+            .for_update()
+            .select(PromoCode::as_select())
+            .first::<PromoCode>(conn)
+            .map_err(|e| match e {
+                Error::NotFound => invalid("is not valid"),
+                other => RepositoryError::DatabaseError(other),
+            })?;
+
+        if !promo.is_active {
+            return Err(invalid("is not active"));
+        }
+        if promo
+            .canteen_id
+            .is_some_and(|canteen| canteen != order_canteen_id)
+        {
+            return Err(invalid("is not valid at this canteen"));
+        }
+        if promo.valid_from.is_some_and(|from| now < from) {
+            return Err(invalid("is not valid yet"));
+        }
+        if promo.valid_until.is_some_and(|until| now >= until) {
+            return Err(invalid("has expired"));
+        }
+        if order_total < promo.min_order_total {
+            return Err(invalid(&format!(
+                "requires a minimum order of {}",
+                promo.min_order_total
+            )));
+        }
+
+        if let Some(max_uses) = promo.max_uses_total {
+            let used = promo_redemptions::table
+                .filter(promo_redemptions::promo_id.eq(promo.promo_id))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            if used >= max_uses as i64 {
+                return Err(invalid("has reached its usage limit"));
+            }
+        }
+        if let Some(max_uses) = promo.max_uses_per_user {
+            let used = promo_redemptions::table
+                .filter(promo_redemptions::promo_id.eq(promo.promo_id))
+                .filter(promo_redemptions::user_id.eq(redeeming_user_id))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            if used >= max_uses as i64 {
+                return Err(invalid("has already been used the maximum number of times"));
+            }
+        }
+        if promo.first_order_only {
+            let has_ordered = diesel::select(
+                exists(past_orders::table.filter(past_orders::user_id.eq(redeeming_user_id))).or(
+                    exists(
+                        active_orders::table.filter(active_orders::user_id.eq(redeeming_user_id)),
+                    ),
+                ),
+            )
+            .get_result::<bool>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+            if has_ordered {
+                return Err(invalid("is only valid on a first order"));
+            }
+        }
+
+        Ok((
+            promo.promo_id,
+            AppliedPromoCode {
+                code: promo.code.clone(),
+                discount: promo.discount_for(order_total),
+            },
+        ))
+    }
+
+    pub(crate) fn record_redemption(
+        conn: &mut PgConnection,
+        redeemed_promo_id: i32,
+        redeeming_user_id: i32,
+        redeemed_hold_id: i32,
+        redeemed_discount: i32,
+    ) -> Result<(), RepositoryError> {
+        diesel::insert_into(promo_redemptions::table)
+            .values((
+                promo_redemptions::promo_id.eq(redeemed_promo_id),
+                promo_redemptions::user_id.eq(redeeming_user_id),
+                promo_redemptions::hold_id.eq(redeemed_hold_id),
+                promo_redemptions::discount.eq(redeemed_discount),
+            ))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "record_redemption: error recording promo {} on hold {}: {}",
+                    redeemed_promo_id, redeemed_hold_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Move the redemption of a hold onto the order it was confirmed as, so deleting the
+    /// hold keeps the usage.
+    pub(crate) fn attach_redemption_to_order(
+        conn: &mut PgConnection,
+        confirmed_hold_id: i32,
+        new_order_id: i32,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(
+            promo_redemptions::table.filter(promo_redemptions::hold_id.eq(confirmed_hold_id)),
+        )
+        .set((
+            promo_redemptions::hold_id.eq(None::<i32>),
+            promo_redemptions::order_id.eq(new_order_id),
+        ))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    /// The code, if it is in `scope`.
+    fn find_managed_code(
+        conn: &mut PgConnection,
+        target_promo_id: i32,
+        scope: PromoScope,
+    ) -> Result<PromoCode, RepositoryError> {
+        let promo = promo_codes::table
+            .find(target_promo_id)
+            .for_update()
+            .select(PromoCode::as_select())
+            .first::<PromoCode>(conn)
+            .map_err(|e| match e {
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("promo_codes: {target_promo_id}"))
+                }
+                other => RepositoryError::DatabaseError(other),
+            })?;
+        if promo.canteen_id != scope.canteen_id() {
+            return Err(RepositoryError::NotFound(format!(
+                "promo_codes: {target_promo_id}"
+            )));
+        }
+        Ok(promo)
+    }
+
+    fn map_code_conflict(e: Error, promo_code: &str) -> RepositoryError {
+        match e {
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                RepositoryError::ValidationError(format!("promo code {promo_code} already exists"))
+            }
+            other => RepositoryError::DatabaseError(other),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use super::analytics::day_start;
+use crate::db::common::payments::PAYMENT_STATE_COMPLETED;
+use crate::db::schema::{canteens, payment_orders, refunds, settlements};
+use crate::db::{DbConnection, ReportRange, RepositoryError};
+use crate::enums::admin::{SettlementLedger, SettlementTotals};
+use crate::models::admin::{NewSettlement, Settlement};
+use crate::models::common::REFUND_STATE_COMPLETED;
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use crate::services::settlement::SettlementConfig;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use diesel::dsl::{count_star, sum};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::Error;
+use log::{debug, error};
+use std::collections::HashMap;
+
+/// Longest payout reference accepted, in characters.
+pub const PAYOUT_REFERENCE_MAX_LEN: usize = 100;
+
+#[derive(Clone)]
+pub struct SettlementOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl SettlementOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// Generate the missing statements of the last `cfg.lookback_days` business days that
+    /// ended at least `cfg.grace_secs` before `now`. Payments and refunds count on the day
+    /// PhonePe completed them, so one completed after its day's statement is paid out with
+    /// the next. Canteens with neither on a day
+    /// get no statement for it. Returns how many statements were generated.
+    pub fn generate_statements(
+        &self,
+        cfg: &SettlementConfig,
+        now: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "generate_statements: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+        let tz = parse_tz_offset_from_env();
+        let last_day = (now - Duration::seconds(cfg.grace_secs))
+            .with_timezone(&tz)
+            .date_naive()
+            - Duration::days(1);
+        let mut generated = 0;
+        for days_back in (0..cfg.lookback_days).rev() {
+            let day = last_day - Duration::days(days_back);
+            generated += conn
+                .connection()
+                .transaction(|conn| Self::generate_day(conn, cfg, day, tz))
+                .map_err(|e| {
+                    error!(
+                        "generate_statements: error generating statements for {}: {}",
+                        day, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+        }
+        Ok(generated)
+    }
+
+    fn generate_day(
+        conn: &mut PgConnection,
+        cfg: &SettlementConfig,
+        day: NaiveDate,
+        tz: FixedOffset,
+    ) -> Result<usize, Error> {
+        let (starts, ends) = (day_start(day, tz), day_start(day + Duration::days(1), tz));
+        let settled: Vec<i32> = settlements::table
+            .filter(settlements::business_date.eq(day))
+            .select(settlements::canteen_id)
+            .load(conn)?;
+        let payments: Vec<(Option<i32>, i64, Option<i64>)> = payment_orders::table
+            .filter(payment_orders::payment_state.eq(PAYMENT_STATE_COMPLETED))
+            .filter(payment_orders::canteen_id.is_not_null())
+            .filter(payment_orders::updated_at.ge(starts))
+            .filter(payment_orders::updated_at.lt(ends))
+            .group_by(payment_orders::canteen_id)
+            .select((
+                payment_orders::canteen_id,
+                count_star(),
+                sum(payment_orders::amount),
+            ))
+            .load(conn)?;
+        let completed_refunds: Vec<(i32, i64, Option<i64>)> = refunds::table
+            .filter(refunds::state.eq(REFUND_STATE_COMPLETED))
+            .filter(refunds::updated_at.ge(starts))
+            .filter(refunds::updated_at.lt(ends))
+            .group_by(refunds::canteen_id)
+            .select((refunds::canteen_id, count_star(), sum(refunds::amount)))
+            .load(conn)?;
+
+        // (payment_count, gross, refund_count, refunds) per canteen
+        let mut days_takings: HashMap<i32, (i64, i64, i64, i64)> = HashMap::new();
+        for (canteen_id, count, amount) in payments {
+            if let Some(canteen_id) = canteen_id {
+                let takings = days_takings.entry(canteen_id).or_default();
+                takings.0 = count;
+                takings.1 = amount.unwrap_or(0);
+            }
+        }
+        for (canteen_id, count, amount) in completed_refunds {
+            let takings = days_takings.entry(canteen_id).or_default();
+            takings.2 = count;
+            takings.3 = amount.unwrap_or(0);
+        }
+        // Canteens deleted since lose their takings along with their statements.
+        let existing: Vec<i32> = canteens::table
+            .filter(canteens::canteen_id.eq_any(days_takings.keys().copied().collect::<Vec<_>>()))
+            .select(canteens::canteen_id)
+            .load(conn)?;
+
+        let statements = days_takings
+            .into_iter()
+            .filter(|(canteen_id, _)| {
+                existing.contains(canteen_id) && !settled.contains(canteen_id)
+            })
+            .map(
+                |(canteen_id, (payment_count, gross, refund_count, refunded))| {
+                    let commission = cfg.commission_on(gross - refunded);
+                    NewSettlement {
+                        canteen_id,
+                        business_date: day,
+                        payment_count: payment_count as i32,
+                        gross,
+                        refund_count: refund_count as i32,
+                        refunds: refunded,
+                        commission_bps: cfg.commission_bps,
+                        commission,
+                        net_payable: gross - refunded - commission,
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        if statements.is_empty() {
+            return Ok(0);
+        }
+        let generated = diesel::insert_into(settlements::table)
+            .values(&statements)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        debug!(
+            "generate_statements: generated {} statements for {}",
+            generated, day
+        );
+        Ok(generated)
+    }
+
+    /// Statements of the canteen over the business days of `range`, the latest first, with
+    /// their totals.
+    pub fn ledger(
+        &self,
+        for_canteen_id: i32,
+        range: &ReportRange,
+    ) -> Result<SettlementLedger, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "ledger: failed to acquire DB connection for canteen {}: {}",
+                for_canteen_id, e
+            );
+            e
+        })?;
+        let statements = settlements::table
+            .filter(settlements::canteen_id.eq(for_canteen_id))
+            .filter(settlements::business_date.ge(range.from))
+            .filter(settlements::business_date.le(range.to))
+            .order(settlements::business_date.desc())
+            .select(Settlement::as_select())
+            .load::<Settlement>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "ledger: error loading statements of canteen {}: {}",
+                    for_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+        let totals = SettlementTotals {
+            gross: statements.iter().map(|s| s.gross).sum(),
+            refunds: statements.iter().map(|s| s.refunds).sum(),
+            commission: statements.iter().map(|s| s.commission).sum(),
+            net_payable: statements.iter().map(|s| s.net_payable).sum(),
+            outstanding: statements
+                .iter()
+                .filter(|s| s.paid_at.is_none())
+                .map(|s| s.net_payable)
+                .sum(),
+        };
+        Ok(SettlementLedger {
+            from: range.from,
+            to: range.to,
+            statements,
+            totals,
+        })
+    }
+
+    /// Record that the statement has been paid out, with the bank or UPI reference of the
+    /// transfer. A statement is paid out once.
+    pub fn record_payout(
+        &self,
+        search_settlement_id: i32,
+        reference: &str,
+    ) -> Result<Settlement, RepositoryError> {
+        let reference = reference.trim();
+        if reference.is_empty() || reference.chars().count() > PAYOUT_REFERENCE_MAX_LEN {
+            return Err(RepositoryError::ValidationError(format!(
+                "reference must be 1 to {PAYOUT_REFERENCE_MAX_LEN} characters"
+            )));
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "record_payout: failed to acquire DB connection for settlement {}: {}",
+                search_settlement_id, e
+            );
+            e
+        })?;
+        conn.connection().transaction(|conn| {
+            let paid_at = settlements::table
+                .find(search_settlement_id)
+                .select(settlements::paid_at)
+                .for_update()
+                .first::<Option<DateTime<Utc>>>(conn)
+                .map_err(|e| match e {
+                    Error::NotFound => {
+                        RepositoryError::NotFound(format!("settlement {search_settlement_id}"))
+                    }
+                    other => RepositoryError::DatabaseError(other),
+                })?;
+            if paid_at.is_some() {
+                return Err(RepositoryError::ValidationError(format!(
+                    "settlement {search_settlement_id} has already been paid out"
+                )));
+            }
+            let settlement = diesel::update(settlements::table.find(search_settlement_id))
+                .set((
+                    settlements::paid_at.eq(Some(Utc::now())),
+                    settlements::payout_reference.eq(Some(reference)),
+                ))
+                .returning(Settlement::as_returning())
+                .get_result(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            debug!(
+                "record_payout: settlement {} of canteen {} paid out as {}",
+                settlement.settlement_id, settlement.canteen_id, reference
+            );
+            Ok(settlement)
+        })
+    }
+}
@@ -0,0 +1,384 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{canteen_webhooks, webhook_deliveries};
+use crate::db::DbConnection;
+use crate::models::admin::{
+    CanteenWebhook, NewCanteenWebhook, UpdateCanteenWebhook, WebhookDelivery,
+    WEBHOOK_DELIVERIES_MAX_LIMIT, WEBHOOK_DELIVERY_DELIVERED, WEBHOOK_DELIVERY_FAILED,
+    WEBHOOK_DELIVERY_PENDING, WEBHOOK_DELIVERY_STATUSES,
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::Error;
+use log::error;
+
+/// A claimed delivery with the URL and secret of its webhook.
+pub type DueWebhookDelivery = (WebhookDelivery, String, String);
+
+#[derive(Clone)]
+pub struct WebhookOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    /// Accept URLs pointing into the local network, see
+    /// [`WebhookConfig`](crate::services::webhooks::WebhookConfig).
+    allow_private_targets: bool,
+}
+
+impl WebhookOperations {
+    pub async fn new(
+        pool: Pool<ConnectionManager<PgConnection>>,
+        allow_private_targets: bool,
+    ) -> Self {
+        Self {
+            pool,
+            allow_private_targets,
+        }
+    }
+
+    pub fn create_webhook(
+        &self,
+        new_webhook: NewCanteenWebhook,
+    ) -> Result<CanteenWebhook, RepositoryError> {
+        let new_webhook = new_webhook
+            .sanitize_and_validate(self.allow_private_targets)
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_webhook: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        diesel::insert_into(canteen_webhooks::table)
+            .values(&new_webhook)
+            .returning(CanteenWebhook::as_returning())
+            .get_result(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "create_webhook: error inserting webhook for canteen {}: {}",
+                    new_webhook.canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// The canteen's webhooks, ordered by id.
+    pub fn list_webhooks(
+        &self,
+        owner_canteen_id: i32,
+    ) -> Result<Vec<CanteenWebhook>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_webhooks: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        canteen_webhooks::table
+            .filter(canteen_webhooks::canteen_id.eq(owner_canteen_id))
+            .order(canteen_webhooks::webhook_id.asc())
+            .select(CanteenWebhook::as_select())
+            .load(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "list_webhooks: error fetching webhooks for canteen {}: {}",
+                    owner_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    pub fn update_webhook(
+        &self,
+        target_webhook_id: i32,
+        owner_canteen_id: i32,
+        changes: UpdateCanteenWebhook,
+    ) -> Result<CanteenWebhook, RepositoryError> {
+        let changes = changes
+            .sanitize_and_validate(self.allow_private_targets)
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "update_webhook: failed to acquire DB connection for id {}: {}",
+                target_webhook_id, e
+            );
+            e
+        })?;
+
+        diesel::update(
+            canteen_webhooks::table
+                .filter(canteen_webhooks::webhook_id.eq(target_webhook_id))
+                .filter(canteen_webhooks::canteen_id.eq(owner_canteen_id)),
+        )
+        .set((&changes, canteen_webhooks::updated_at.eq(Utc::now())))
+        .returning(CanteenWebhook::as_returning())
+        .get_result(conn.connection())
+        .map_err(|e| Self::map_webhook_error("update_webhook", target_webhook_id, e))
+    }
+
+    /// Deleting a webhook drops its pending deliveries and its log.
+    pub fn delete_webhook(
+        &self,
+        target_webhook_id: i32,
+        owner_canteen_id: i32,
+    ) -> Result<CanteenWebhook, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "delete_webhook: failed to acquire DB connection for id {}: {}",
+                target_webhook_id, e
+            );
+            e
+        })?;
+
+        diesel::delete(
+            canteen_webhooks::table
+                .filter(canteen_webhooks::webhook_id.eq(target_webhook_id))
+                .filter(canteen_webhooks::canteen_id.eq(owner_canteen_id)),
+        )
+        .returning(CanteenWebhook::as_returning())
+        .get_result(conn.connection())
+        .map_err(|e| Self::map_webhook_error("delete_webhook", target_webhook_id, e))
+    }
+
+    /// The most recent deliveries of one of the canteen's webhooks, newest first.
+    pub fn list_deliveries(
+        &self,
+        target_webhook_id: i32,
+        owner_canteen_id: i32,
+        status_filter: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, RepositoryError> {
+        if let Some(wanted) = status_filter.as_deref() {
+            if !WEBHOOK_DELIVERY_STATUSES.contains(&wanted) {
+                return Err(RepositoryError::ValidationError(format!(
+                    "status must be one of {}",
+                    WEBHOOK_DELIVERY_STATUSES.join(", ")
+                )));
+            }
+        }
+        let limit = limit.clamp(1, WEBHOOK_DELIVERIES_MAX_LIMIT);
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_deliveries: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let conn = conn.connection();
+
+        canteen_webhooks::table
+            .filter(canteen_webhooks::webhook_id.eq(target_webhook_id))
+            .filter(canteen_webhooks::canteen_id.eq(owner_canteen_id))
+            .select(canteen_webhooks::webhook_id)
+            .first::<i32>(conn)
+            .map_err(|e| Self::map_webhook_error("list_deliveries", target_webhook_id, e))?;
+
+        let mut query = webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(target_webhook_id))
+            .into_boxed();
+        if let Some(wanted) = status_filter {
+            query = query.filter(webhook_deliveries::status.eq(wanted));
+        }
+        query
+            .order(webhook_deliveries::delivery_id.desc())
+            .limit(limit)
+            .select(WebhookDelivery::as_select())
+            .load(conn)
+            .map_err(|e| {
+                error!(
+                    "list_deliveries: error fetching deliveries of webhook {}: {}",
+                    target_webhook_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Queue an event for every active webhook of the canteen subscribed to it. Recording the
+    /// same event again is a no-op. Returns how many deliveries were queued.
+    pub fn record_event(
+        &self,
+        event_canteen_id: i32,
+        new_event_id: &str,
+        new_event_type: &str,
+        payload: &str,
+    ) -> Result<usize, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("record_event: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let conn = conn.connection();
+
+        let subscribed = canteen_webhooks::table
+            .filter(canteen_webhooks::canteen_id.eq(event_canteen_id))
+            .filter(canteen_webhooks::is_active.eq(true))
+            .filter(canteen_webhooks::events.contains(vec![new_event_type]))
+            .select(canteen_webhooks::webhook_id)
+            .load::<i32>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        if subscribed.is_empty() {
+            return Ok(0);
+        }
+
+        let rows = subscribed
+            .into_iter()
+            .map(|subscribed_webhook_id| {
+                (
+                    webhook_deliveries::webhook_id.eq(subscribed_webhook_id),
+                    webhook_deliveries::event_id.eq(new_event_id),
+                    webhook_deliveries::event_type.eq(new_event_type),
+                    webhook_deliveries::payload.eq(payload),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(webhook_deliveries::table)
+            .values(&rows)
+            .on_conflict((webhook_deliveries::webhook_id, webhook_deliveries::event_id))
+            .do_nothing()
+            .execute(conn)
+            .map_err(|e| {
+                error!(
+                    "record_event: error queueing event {} for canteen {}: {}",
+                    new_event_id, event_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Claim up to `limit` due deliveries of active webhooks, oldest first, pushing their next
+    /// attempt `lease_secs` out so other instances leave them alone while they are being sent.
+    pub fn claim_due_deliveries(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+    ) -> Result<Vec<DueWebhookDelivery>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "claim_due_deliveries: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        conn.connection()
+            .transaction(|conn| {
+                let active_webhooks = canteen_webhooks::table
+                    .filter(canteen_webhooks::is_active.eq(true))
+                    .select(canteen_webhooks::webhook_id);
+                let due_ids = webhook_deliveries::table
+                    .filter(webhook_deliveries::status.eq(WEBHOOK_DELIVERY_PENDING))
+                    .filter(webhook_deliveries::next_attempt_at.le(diesel::dsl::now))
+                    .filter(webhook_deliveries::webhook_id.eq_any(active_webhooks))
+                    .order(webhook_deliveries::delivery_id.asc())
+                    .limit(limit)
+                    .select(webhook_deliveries::delivery_id)
+                    .for_update()
+                    .skip_locked()
+                    .load::<i64>(conn)?;
+                if due_ids.is_empty() {
+                    return Ok(Vec::new());
+                }
+                diesel::update(
+                    webhook_deliveries::table
+                        .filter(webhook_deliveries::delivery_id.eq_any(&due_ids)),
+                )
+                .set(
+                    webhook_deliveries::next_attempt_at
+                        .eq(Utc::now() + Duration::seconds(lease_secs)),
+                )
+                .execute(conn)?;
+                webhook_deliveries::table
+                    .inner_join(canteen_webhooks::table)
+                    .filter(webhook_deliveries::delivery_id.eq_any(&due_ids))
+                    .order(webhook_deliveries::delivery_id.asc())
+                    .select((
+                        WebhookDelivery::as_select(),
+                        canteen_webhooks::url,
+                        canteen_webhooks::secret,
+                    ))
+                    .load::<DueWebhookDelivery>(conn)
+            })
+            .map_err(|e| {
+                error!("claim_due_deliveries: error claiming deliveries: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    pub fn mark_delivery_delivered(
+        &self,
+        target_delivery_id: i64,
+        status_code: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "mark_delivery_delivered: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        diesel::update(webhook_deliveries::table.find(target_delivery_id))
+            .set((
+                webhook_deliveries::status.eq(WEBHOOK_DELIVERY_DELIVERED),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_status_code.eq(Some(status_code)),
+                webhook_deliveries::last_error.eq(None::<String>),
+                webhook_deliveries::delivered_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "mark_delivery_delivered: error updating delivery {}: {}",
+                    target_delivery_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Record a failed attempt: retry at `retry_at`, or give up on the delivery when `None`.
+    pub fn record_delivery_failure(
+        &self,
+        target_delivery_id: i64,
+        status_code: Option<i32>,
+        failure: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "record_delivery_failure: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        let status = if retry_at.is_some() {
+            WEBHOOK_DELIVERY_PENDING
+        } else {
+            WEBHOOK_DELIVERY_FAILED
+        };
+        diesel::update(webhook_deliveries::table.find(target_delivery_id))
+            .set((
+                webhook_deliveries::status.eq(status),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_status_code.eq(status_code),
+                webhook_deliveries::last_error.eq(failure),
+                webhook_deliveries::next_attempt_at.eq(retry_at.unwrap_or_else(Utc::now)),
+            ))
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "record_delivery_failure: error updating delivery {}: {}",
+                    target_delivery_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    fn map_webhook_error(context: &str, target_webhook_id: i32, e: Error) -> RepositoryError {
+        match e {
+            Error::NotFound => {
+                RepositoryError::NotFound(format!("canteen_webhooks: {target_webhook_id}"))
+            }
+            other => {
+                error!(
+                    "{}: error on webhook {}: {}",
+                    context, target_webhook_id, other
+                );
+                RepositoryError::DatabaseError(other)
+            }
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use crate::db::{DbConnection, RepositoryError};
+use crate::db::{
+    DbConnection, OutboxOperations, PricingOperations, PromoOperations, RepositoryError,
+};
+use crate::enums::common::{AppliedPricingRule, AppliedPromoCode, ReducedItem, UnavailableItem};
 use crate::models::admin::MenuItemCheck;
-use crate::models::common::{NewHeldOrder, TimeBandEnum};
-use crate::sse::InventoryUpdateItems;
+use crate::models::common::{
+    NewHeldOrder, TimeBandEnum, HOLD_OUTCOME_CONFIRMED, HOLD_OUTCOME_EXPIRED, HOLD_OUTCOME_RELEASED,
+};
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use crate::services::outbox::OutboxMessage;
+use crate::services::pricing::{price_order, PricingLine};
+use crate::services::webhooks::{WebhookEvent, WebhookOrderItem};
+use crate::sse::{CanteenAggregatedOrderUpdateItem, InventoryUpdateItems, SseEvent};
 use chrono::{Duration, Utc};
 use diesel::dsl::sum;
 use diesel::prelude::*;
@@ -12,12 +21,25 @@ use log::{debug, error, info, warn};
 use std::cmp::max;
 use std::collections::HashMap;
 
-/// (hold_id, expires_at_epoch, (canteen_id, inventory_updates))
-type HoldOrderResult = (i32, i64, (i32, Vec<InventoryUpdateItems>));
+/// (hold_id, expires_at_epoch, (canteen_id, inventory_updates, (total_price, applied_rules, applied_promo)))
+type HoldOrderResult = (
+    i32,
+    i64,
+    (
+        i32,
+        Vec<InventoryUpdateItems>,
+        (i32, Vec<AppliedPricingRule>, Option<AppliedPromoCode>),
+    ),
+);
+/// (hold, original_price, reduced_items, unavailable_items)
+type ReorderResult = (
+    Option<HoldOrderResult>,
+    Option<i32>,
+    Vec<ReducedItem>,
+    Vec<UnavailableItem>,
+);
 /// (order_id, user_id, canteen_id, (time_band, [(item_id, num_ordered)]))
 type ConfirmOrderResult = (i32, i32, i32, (String, Vec<(i32, i32)>));
-/// (expired_count, [(canteen_id, inventory_updates)])
-type CleanupResult = (usize, Vec<(i32, Vec<InventoryUpdateItems>)>);
 
 #[derive(Insertable, Debug)]
 #[diesel(table_name = crate::db::schema::held_order_items)]
@@ -53,6 +75,7 @@ struct HeldOrderWithItems {
     total_price: i32,
     deliver_at: Option<TimeBandEnum>,
     expires_at: chrono::DateTime<chrono::Utc>,
+    note: Option<String>,
     item_id: i32,
     quantity: i16,
     price: i32,
@@ -72,19 +95,54 @@ impl HoldOperations {
         }
     }
 
-    /// Hold (reserve) an order: validate items, decrement stock, insert into held tables.
-    /// Returns (hold_id, expires_at_epoch, (canteen_id, inventory_updates)).
+    /// Hold (reserve) an order without a promo code. See [`Self::hold_order_with_promo`].
     pub fn hold_order(
         &self,
         userid: i32,
         itemids: Vec<i32>,
         order_deliver_at: Option<String>,
+    ) -> Result<HoldOrderResult, RepositoryError> {
+        self.hold_order_with_promo(userid, itemids, order_deliver_at, None, None)
+    }
+
+    /// Hold (reserve) an order: validate items, price it with the canteen's active pricing
+    /// rules, redeem the promo code if any, decrement stock, insert into held tables. The
+    /// discounted unit prices are snapshotted in held_order_items; the promo discount only
+    /// lowers the hold total. The customer's note for the kitchen is kept with the hold.
+    /// Returns (hold_id, expires_at_epoch, (canteen_id, inventory_updates, (total_price, applied_rules, applied_promo))).
+    pub fn hold_order_with_promo(
+        &self,
+        userid: i32,
+        itemids: Vec<i32>,
+        order_deliver_at: Option<String>,
+        promo_code: Option<String>,
+        order_note: Option<String>,
     ) -> Result<HoldOrderResult, RepositoryError> {
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!("hold_order: failed to acquire DB connection: {}", e);
             e
         })?;
+        self.hold_items(
+            conn.connection(),
+            userid,
+            itemids,
+            order_deliver_at,
+            promo_code,
+            order_note,
+        )
+    }
 
+    /// The body of [`Self::hold_order_with_promo`] on a given connection, so that a caller
+    /// can make the hold part of its own transaction.
+    fn hold_items(
+        &self,
+        conn: &mut PgConnection,
+        userid: i32,
+        itemids: Vec<i32>,
+        order_deliver_at: Option<String>,
+        promo_code: Option<String>,
+        order_note: Option<String>,
+    ) -> Result<HoldOrderResult, RepositoryError> {
         if itemids.is_empty() {
             return Err(RepositoryError::ValidationError(format!(
                 "Order is empty for user: {:?}",
@@ -101,7 +159,7 @@ impl HoldOperations {
 
         let expires_at = Utc::now() + Duration::seconds(self.hold_ttl_secs);
 
-        conn.connection().transaction(|conn| {
+        conn.transaction(|conn| {
             // Validate items and lock rows to prevent concurrent oversells.
             let items_in_order: Vec<MenuItemCheck>;
             let canteen_id_in_order: i32;
@@ -185,10 +243,35 @@ impl HoldOperations {
             }
 
             // Order total price calc
-            let order_total_price = items_in_order
+            let pricing_rules = PricingOperations::load_rules(conn, canteen_id_in_order, true)?;
+            let pricing_lines = items_in_order
                 .iter()
-                .map(|e| e.price * *ordered_qty.get(&e.item_id).unwrap_or(&1) as i32)
-                .sum::<i32>();
+                .map(|e| PricingLine {
+                    item_id: e.item_id,
+                    unit_price: e.price,
+                    quantity: *ordered_qty.get(&e.item_id).unwrap_or(&1) as i32,
+                })
+                .collect::<Vec<PricingLine>>();
+            let now_local = Utc::now()
+                .with_timezone(&parse_tz_offset_from_env())
+                .naive_local();
+            let priced = price_order(&pricing_lines, &pricing_rules, now_local);
+            let promo = match promo_code.as_deref() {
+                Some(code) => Some(PromoOperations::apply_code(
+                    conn,
+                    code,
+                    userid,
+                    canteen_id_in_order,
+                    priced.total_price,
+                    Utc::now(),
+                )?),
+                None => None,
+            };
+            let order_total_price = priced.total_price
+                - promo
+                    .as_ref()
+                    .map(|(_, applied)| applied.discount)
+                    .unwrap_or(0);
 
             let order_deliver_time_enum: Option<TimeBandEnum> =
                 TimeBandEnum::get_enum_from_str(order_deliver_at.as_deref());
@@ -201,6 +284,7 @@ impl HoldOperations {
                 total_price: order_total_price,
                 deliver_at: order_deliver_time_enum,
                 expires_at,
+                note: order_note,
             };
             {
                 use crate::db::schema::held_orders::dsl::*;
@@ -211,6 +295,16 @@ impl HoldOperations {
                     .map_err(RepositoryError::DatabaseError)?;
             }
 
+            if let Some((promo_id, applied)) = &promo {
+                PromoOperations::record_redemption(
+                    conn,
+                    *promo_id,
+                    userid,
+                    new_hold_id,
+                    applied.discount,
+                )?;
+            }
+
             // Insert held order items
             {
                 let mut new_items: Vec<HeldOrderItemInsert> = Vec::new();
@@ -219,7 +313,8 @@ impl HoldOperations {
                         hold_id: new_hold_id,
                         item_id: *item,
                         quantity: *qty as i16,
-                        price: *item_prices
+                        price: *priced
+                            .unit_prices
                             .get(item)
                             .expect("hold_order: missing price for item"),
                     });
@@ -234,6 +329,7 @@ impl HoldOperations {
 
             // Decrement stock
             let mut inventory_updates = Vec::new();
+            let mut stock_outs = Vec::new();
             {
                 let mut updated_stock: HashMap<i32, i64> = HashMap::new();
                 for item in &items_in_order {
@@ -269,9 +365,30 @@ impl HoldOperations {
                         is_available: is_available_val,
                         price: *item_prices.get(&item_id_val).unwrap(),
                     });
+                    if new_stock == 0 {
+                        stock_outs.push((
+                            item_id_val,
+                            items_in_order
+                                .iter()
+                                .find(|item| item.item_id == item_id_val)
+                                .map(|item| item.name.clone())
+                                .unwrap_or_default(),
+                        ));
+                    }
                 }
             }
 
+            OutboxOperations::enqueue(
+                conn,
+                &[OutboxMessage::CanteenSubscriptionEvent {
+                    canteen_id: canteen_id_in_order,
+                    event: SseEvent::InventoryUpdate {
+                        items: inventory_updates.clone(),
+                    },
+                }],
+            )?;
+            OutboxOperations::enqueue_stock_outs(conn, canteen_id_in_order, &stock_outs)?;
+
             debug!(
                 "hold_order: created hold {} for user {} with items {:?}, expires at {}",
                 new_hold_id, userid, itemids, expires_at
@@ -280,11 +397,219 @@ impl HoldOperations {
             Ok((
                 new_hold_id,
                 expires_at.timestamp(),
-                (canteen_id_in_order, inventory_updates),
+                (
+                    canteen_id_in_order,
+                    inventory_updates,
+                    (
+                        order_total_price,
+                        priced.applied_rules,
+                        promo.map(|(_, applied)| applied),
+                    ),
+                ),
             ))
         })
     }
 
+    /// Hold a past order of the user again. Items no longer on the menu are left out; the
+    /// rest go through [`Self::hold_order_with_promo`]. When it rejects an item that is
+    /// still in stock but not in the ordered quantity, the quantity is capped at the stock
+    /// left; other rejected items are dropped. Then it tries again with what is left.
+    /// Returns (hold, original_price, reduced_items, unavailable_items), with no hold when
+    /// nothing is left. `original_price` is what the held items cost in the past order, at
+    /// the unit prices paid then; `None` for orders finished before those were recorded.
+    pub fn reorder_past_order(
+        &self,
+        userid: i32,
+        past_order_id: i32,
+        order_deliver_at: Option<String>,
+        promo_code: Option<String>,
+        order_note: Option<String>,
+    ) -> Result<ReorderResult, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("reorder_past_order: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        let past_items = {
+            use crate::db::schema::past_orders::dsl::*;
+            past_orders
+                .filter(order_id.eq(past_order_id))
+                .filter(user_id.eq(userid))
+                .select(items)
+                .first::<Vec<Option<i32>>>(conn.connection())
+                .map_err(|e| match e {
+                    Error::NotFound => {
+                        RepositoryError::NotFound(format!("past_orders: {past_order_id}"))
+                    }
+                    other => RepositoryError::DatabaseError(other),
+                })?
+        };
+        let mut itemids = past_items.into_iter().flatten().collect::<Vec<i32>>();
+        let past_prices: HashMap<i32, i32> = {
+            use crate::db::schema::past_order_items::dsl::*;
+            past_order_items
+                .filter(order_id.eq(past_order_id))
+                .select((item_id, price))
+                .load::<(i32, i32)>(conn.connection())
+                .map_err(RepositoryError::DatabaseError)?
+                .into_iter()
+                .collect()
+        };
+
+        let on_menu = {
+            use crate::db::schema::menu_items::dsl::*;
+            menu_items
+                .filter(item_id.eq_any(&itemids))
+                .select(item_id)
+                .load::<i32>(conn.connection())
+                .map_err(RepositoryError::DatabaseError)?
+        };
+        let mut unavailable_items: Vec<UnavailableItem> = Vec::new();
+        for id in &itemids {
+            if !on_menu.contains(id) && unavailable_items.iter().all(|item| item.item_id != *id) {
+                unavailable_items.push(UnavailableItem {
+                    item_id: *id,
+                    name: None,
+                    reason: "No longer on the menu".to_string(),
+                });
+            }
+        }
+        itemids.retain(|id| on_menu.contains(id));
+        drop(conn);
+
+        let mut reduced_items: Vec<ReducedItem> = Vec::new();
+        while !itemids.is_empty() {
+            match self.hold_order_with_promo(
+                userid,
+                itemids.clone(),
+                order_deliver_at.clone(),
+                promo_code.clone(),
+                order_note.clone(),
+            ) {
+                Ok(hold) => {
+                    let original_price = itemids
+                        .iter()
+                        .map(|id| past_prices.get(id))
+                        .sum::<Option<i32>>();
+                    return Ok((Some(hold), original_price, reduced_items, unavailable_items));
+                }
+                Err(RepositoryError::NotAvailable(id, name, reason)) => {
+                    let wanted = itemids.iter().filter(|item| **item == id).count() as i32;
+                    let (left, available) = self.item_stock(id)?;
+                    if available && left > 0 && left < wanted {
+                        debug!(
+                            "reorder_past_order: holding {} of item {} instead of {} in the reorder of {}",
+                            left, id, wanted, past_order_id
+                        );
+                        let mut kept = 0;
+                        itemids.retain(|item| {
+                            if *item != id {
+                                return true;
+                            }
+                            kept += 1;
+                            kept <= left
+                        });
+                        match reduced_items.iter_mut().find(|item| item.item_id == id) {
+                            Some(reduced) => reduced.held_quantity = left,
+                            None => reduced_items.push(ReducedItem {
+                                item_id: id,
+                                name,
+                                ordered_quantity: wanted,
+                                held_quantity: left,
+                            }),
+                        }
+                        continue;
+                    }
+                    debug!(
+                        "reorder_past_order: leaving item {} out of the reorder of {}: {}",
+                        id, past_order_id, reason
+                    );
+                    itemids.retain(|item| *item != id);
+                    reduced_items.retain(|item| item.item_id != id);
+                    unavailable_items.push(UnavailableItem {
+                        item_id: id,
+                        name: Some(name),
+                        reason,
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((None, None, reduced_items, unavailable_items))
+    }
+
+    /// (stock, is_available) of a menu item.
+    fn item_stock(&self, stock_item_id: i32) -> Result<(i32, bool), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("item_stock: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        use crate::db::schema::menu_items::dsl::*;
+        menu_items
+            .find(stock_item_id)
+            .select((stock, is_available))
+            .first::<(i32, bool)>(conn.connection())
+            .map_err(|e| match e {
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("menu_items: {stock_item_id}"))
+                }
+                other => RepositoryError::DatabaseError(other),
+            })
+    }
+
+    /// Hold the user's cart at the canteen and empty it, in one transaction: when the hold
+    /// fails, the cart is left as it was.
+    pub fn hold_cart(
+        &self,
+        userid: i32,
+        cart_canteen_id: i32,
+        order_deliver_at: Option<String>,
+        promo_code: Option<String>,
+        order_note: Option<String>,
+    ) -> Result<HoldOrderResult, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("hold_cart: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            use crate::db::schema::cart_items::dsl::*;
+            let lines = cart_items
+                .filter(user_id.eq(userid))
+                .filter(canteen_id.eq(cart_canteen_id))
+                .select((item_id, quantity))
+                .for_update()
+                .load::<(i32, i16)>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+            if lines.is_empty() {
+                return Err(RepositoryError::ValidationError(format!(
+                    "Cart is empty for user: {userid}"
+                )));
+            }
+            let itemids = lines
+                .iter()
+                .flat_map(|(id, qty)| std::iter::repeat_n(*id, *qty as usize))
+                .collect::<Vec<i32>>();
+
+            let hold = self.hold_items(
+                conn,
+                userid,
+                itemids,
+                order_deliver_at,
+                promo_code,
+                order_note,
+            )?;
+            diesel::delete(
+                cart_items
+                    .filter(user_id.eq(userid))
+                    .filter(canteen_id.eq(cart_canteen_id)),
+            )
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+            Ok(hold)
+        })
+    }
+
     /// Confirm a held order: move to active_orders, delete from held tables.
     /// Returns (order_id, user_id, canteen_id, (time_band, [(item_id, num_ordered)])).
     pub fn confirm_held_order(
@@ -336,6 +661,7 @@ impl HoldOperations {
                         held_orders::total_price,
                         held_orders::deliver_at,
                         held_orders::expires_at,
+                        held_orders::note,
                         held_order_items::item_id,
                         held_order_items::quantity,
                         held_order_items::price,
@@ -372,7 +698,9 @@ impl HoldOperations {
             if Utc::now() > first.expires_at {
                 use crate::db::schema::held_orders::dsl::*;
                 // Hold has expired — clean it up but allow commit.
-                let _ = Self::restore_stock_for_hold(conn, search_hold_id)?;
+                let restored_inventory = Self::restore_stock_for_hold(conn, search_hold_id)?;
+                Self::enqueue_inventory_restored(conn, &restored_inventory)?;
+                Self::record_hold_outcome(conn, search_hold_id, HOLD_OUTCOME_EXPIRED, None)?;
                 diesel::delete(held_orders.filter(hold_id.eq(search_hold_id)))
                     .execute(conn)
                     .map_err(RepositoryError::DatabaseError)?;
@@ -389,6 +717,7 @@ impl HoldOperations {
                         canteen_id.eq(first.canteen_id),
                         total_price.eq(first.total_price),
                         deliver_at.eq(&first.deliver_at),
+                        note.eq(&first.note),
                     ))
                     .returning(order_id)
                     .get_result::<i32>(conn)
@@ -463,7 +792,70 @@ impl HoldOperations {
                     .collect::<Vec<(i32, i32)>>()
             };
 
-            // Delete held order (cascade deletes items)
+            let item_names: HashMap<i32, String> = {
+                use crate::db::schema::menu_items;
+                menu_items::table
+                    .filter(menu_items::item_id.eq_any(held_data.iter().map(|row| row.item_id)))
+                    .select((menu_items::item_id, menu_items::name))
+                    .load::<(i32, String)>(conn)
+                    .map_err(RepositoryError::DatabaseError)?
+                    .into_iter()
+                    .collect()
+            };
+
+            PromoOperations::attach_redemption_to_order(conn, search_hold_id, new_order_id)?;
+            OutboxOperations::enqueue_order_receipt(conn, search_hold_id, new_order_id);
+            OutboxOperations::enqueue(
+                conn,
+                &[
+                    OutboxMessage::CanteenEvent {
+                        canteen_id: first.canteen_id,
+                        event: SseEvent::CanteenAggregatedOrderUpdate {
+                            time_band: deliver_time_string.clone(),
+                            items: aggregated_updates
+                                .iter()
+                                .map(|&(item_id, num_ordered)| CanteenAggregatedOrderUpdateItem {
+                                    item_id,
+                                    num_ordered,
+                                })
+                                .collect(),
+                        },
+                    },
+                    OutboxMessage::UserEvent {
+                        user_id: first.user_id,
+                        event: SseEvent::UserOrderUpdate {
+                            order_id: new_order_id,
+                            status: "placed".to_string(),
+                        },
+                    },
+                    OutboxMessage::CanteenWebhook {
+                        canteen_id: first.canteen_id,
+                        event: WebhookEvent::OrderConfirmed {
+                            order_id: new_order_id,
+                            user_id: first.user_id,
+                            total_price: first.total_price,
+                            deliver_at: deliver_time_string.clone(),
+                            items: held_data
+                                .iter()
+                                .map(|row| WebhookOrderItem {
+                                    item_id: row.item_id,
+                                    name: item_names.get(&row.item_id).cloned().unwrap_or_default(),
+                                    quantity: row.quantity as i32,
+                                    price: row.price,
+                                })
+                                .collect(),
+                        },
+                    },
+                ],
+            )?;
+
+            Self::record_hold_outcome(
+                conn,
+                search_hold_id,
+                HOLD_OUTCOME_CONFIRMED,
+                Some(new_order_id),
+            )?;
+            // Delete held order (cascade deletes items and pending promo redemptions)
             {
                 use crate::db::schema::held_orders::dsl::*;
                 diesel::delete(held_orders.filter(hold_id.eq(search_hold_id)))
@@ -530,7 +922,9 @@ impl HoldOperations {
 
             // Restore stock
             let restored_inventory = Self::restore_stock_for_hold(conn, search_hold_id)?;
+            Self::enqueue_inventory_restored(conn, &restored_inventory)?;
 
+            Self::record_hold_outcome(conn, search_hold_id, HOLD_OUTCOME_RELEASED, None)?;
             // Delete held order (cascade deletes items)
             {
                 use crate::db::schema::held_orders::dsl::*;
@@ -549,8 +943,9 @@ impl HoldOperations {
     }
 
     /// Clean up all expired holds: restore stock and delete.
-    /// Returns the number of expired holds cleaned up and stock updates for SSE.
-    pub fn cleanup_expired_holds(&self) -> Result<CleanupResult, RepositoryError> {
+    /// Returns the number of expired holds cleaned up; the stock given back is queued for the
+    /// canteens' subscribers.
+    pub fn cleanup_expired_holds(&self) -> Result<usize, RepositoryError> {
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!(
                 "cleanup_expired_holds: failed to acquire DB connection: {}",
@@ -571,21 +966,21 @@ impl HoldOperations {
         }
 
         if expired_hold_ids.is_empty() {
-            return Ok((0, Vec::new()));
+            return Ok(0);
         }
 
         let count = expired_hold_ids.len();
-        let mut restored_inventory_updates: Vec<(i32, Vec<InventoryUpdateItems>)> = Vec::new();
         info!(
             "cleanup_expired_holds: found {} expired holds to clean up",
             count
         );
 
         for expired_id in &expired_hold_ids {
-            let restored_inventory = conn
-                .connection()
-                .transaction::<(i32, Vec<InventoryUpdateItems>), RepositoryError, _>(|conn| {
+            conn.connection()
+                .transaction::<(), RepositoryError, _>(|conn| {
                     let restored_inventory = Self::restore_stock_for_hold(conn, *expired_id)?;
+                    Self::enqueue_inventory_restored(conn, &restored_inventory)?;
+                    Self::record_hold_outcome(conn, *expired_id, HOLD_OUTCOME_EXPIRED, None)?;
 
                     use crate::db::schema::held_orders::dsl::*;
                     diesel::delete(held_orders.filter(hold_id.eq(expired_id)))
@@ -596,16 +991,53 @@ impl HoldOperations {
                         "cleanup_expired_holds: cleaned up expired hold {}",
                         expired_id
                     );
-                    Ok(restored_inventory)
+                    Ok(())
                 })?;
-
-            if !restored_inventory.1.is_empty() {
-                restored_inventory_updates.push(restored_inventory);
-            }
         }
 
         warn!("cleanup_expired_holds: released {} expired holds", count);
-        Ok((count, restored_inventory_updates))
+        Ok(count)
+    }
+
+    /// Tell the canteen's subscribers about stock given back by a released hold, if any.
+    fn enqueue_inventory_restored(
+        conn: &mut PgConnection,
+        (canteen_id, inventory_updates): &(i32, Vec<InventoryUpdateItems>),
+    ) -> Result<(), RepositoryError> {
+        if inventory_updates.is_empty() {
+            return Ok(());
+        }
+        OutboxOperations::enqueue(
+            conn,
+            &[OutboxMessage::CanteenSubscriptionEvent {
+                canteen_id: *canteen_id,
+                event: SseEvent::InventoryUpdate {
+                    items: inventory_updates.clone(),
+                },
+            }],
+        )
+    }
+
+    /// Keep what became of a hold for sales analytics, before the hold itself is deleted.
+    /// Must be called within a transaction.
+    fn record_hold_outcome(
+        conn: &mut PgConnection,
+        ended_hold_id: i32,
+        outcome: &str,
+        order_id: Option<i32>,
+    ) -> Result<(), RepositoryError> {
+        diesel::sql_query(
+            "INSERT INTO hold_history (hold_id, user_id, canteen_id, total_price, held_at, outcome, order_id) \
+             SELECT hold_id, user_id, canteen_id, total_price, held_at, $2, $3 \
+             FROM held_orders WHERE hold_id = $1 \
+             ON CONFLICT (hold_id) DO NOTHING",
+        )
+        .bind::<diesel::sql_types::Int4, _>(ended_hold_id)
+        .bind::<diesel::sql_types::Varchar, _>(outcome)
+        .bind::<diesel::sql_types::Nullable<diesel::sql_types::Int4>, _>(order_id)
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
     }
 
     /// Restore stock for all items in a held order. Must be called within a transaction.
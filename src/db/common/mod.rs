@@ -1,4 +1,9 @@
 pub(crate) mod hold;
 pub(crate) mod orders;
+pub(crate) mod outbox;
 pub(crate) mod payments;
+pub(crate) mod recommendations;
+pub(crate) mod reviews;
 pub(crate) mod search;
+pub(crate) mod sse_events;
+pub(crate) mod support;
@@ -1,9 +1,19 @@
-use crate::db::{AssetOperations, DbConnection, RepositoryError};
+use crate::db::{
+    AssetOperations, DbConnection, OutboxOperations, RecommendationOperations, RepositoryError,
+};
 use crate::enums::common::{
     ActiveItemCount, ItemContainer, OrderItemContainer, OrderItemsWithPic, TimedActiveItemCount,
 };
 use crate::models::common::TimeBandEnum;
-use crate::models::{admin::MenuItemCheck, common::OrderItems, user::NewPastOrder};
+use crate::models::{
+    admin::MenuItemCheck,
+    common::OrderItems,
+    user::{NewPastOrder, NewPastOrderItem},
+};
+use crate::services::outbox::OutboxMessage;
+use crate::services::tickets::{KitchenTicket, TicketLine};
+use crate::services::webhooks::WebhookEvent;
+use crate::sse::SseEvent;
 use chrono::{DateTime, Utc};
 use diesel::dsl::sum;
 use diesel::prelude::*;
@@ -15,6 +25,17 @@ use log::{debug, error};
 use std::cmp::max;
 use std::collections::HashMap;
 
+/// (user_id, canteen_name, ordered_at, deliver_at, note, user_name, display_name)
+type KitchenTicketRow = (
+    i32,
+    String,
+    DateTime<Utc>,
+    Option<TimeBandEnum>,
+    Option<String>,
+    String,
+    Option<String>,
+);
+
 #[derive(Insertable, Debug)]
 #[diesel(table_name = crate::db::schema::active_order_items)]
 struct OrderItem {
@@ -39,6 +60,8 @@ struct OrderDeliverItems {
     price: i32,
     quantity: i16,
     ordered_at: DateTime<Utc>,
+    item_price: i32,
+    deliver_at: Option<TimeBandEnum>,
 }
 
 #[derive(Debug)]
@@ -333,6 +356,8 @@ impl OrderOperations {
                 pic_link: item.pic_link,
                 pic_etag: item.pic_etag,
                 description: item.description,
+                allergens: item.allergens,
+                diet_labels: item.diet_labels,
             });
         }
         debug!("Grouped order items: {:?}", &grouped);
@@ -418,6 +443,8 @@ impl OrderOperations {
                 menu_items::pic_etag,
                 menu_items::pic_key,
                 menu_items::description,
+                menu_items::allergens,
+                menu_items::diet_labels,
             ))
             .order_by(active_orders::ordered_at.desc())
             .load::<OrderItems>(conn.connection())
@@ -507,6 +534,8 @@ impl OrderOperations {
                 menu_items::pic_etag,
                 menu_items::pic_key,
                 menu_items::description,
+                menu_items::allergens,
+                menu_items::diet_labels,
             ))
             .order_by(active_orders::ordered_at.desc())
             .load::<OrderItems>(conn.connection())
@@ -688,6 +717,8 @@ impl OrderOperations {
                 menu_items::pic_etag,
                 menu_items::pic_key,
                 menu_items::description,
+                menu_items::allergens,
+                menu_items::diet_labels,
             ))
             .order(menu_items::item_id.asc())
             .load::<OrderItems>(conn.connection())
@@ -730,6 +761,94 @@ impl OrderOperations {
             .map(|data| (canteen_id_in_order, data)))
     }
 
+    /// The kitchen ticket of one of the canteen's active orders, with the ordering user's id.
+    pub fn get_kitchen_ticket(
+        &self,
+        search_order_id: i32,
+        owner_canteen_id: i32,
+    ) -> Result<(i32, KitchenTicket), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_kitchen_ticket: failed to acquire DB connection for order_id {}: {}",
+                search_order_id, e
+            );
+            e
+        })?;
+        let conn = conn.connection();
+        use crate::db::schema::*;
+
+        let (
+            owner_id,
+            canteen_name,
+            order_ordered_at,
+            order_deliver_at,
+            order_note,
+            name,
+            display_name,
+        ) = active_orders::table
+            .inner_join(canteens::table)
+            .inner_join(users::table)
+            .filter(active_orders::order_id.eq(search_order_id))
+            .filter(active_orders::canteen_id.eq(owner_canteen_id))
+            .select((
+                active_orders::user_id,
+                canteens::canteen_name,
+                active_orders::ordered_at,
+                active_orders::deliver_at,
+                active_orders::note,
+                users::name,
+                users::display_name,
+            ))
+            .first::<KitchenTicketRow>(conn)
+            .map_err(|e| match e {
+                Error::NotFound => {
+                    RepositoryError::NotFound(format!("active_orders: {search_order_id}"))
+                }
+                other => {
+                    error!(
+                        "get_kitchen_ticket: error fetching order {}: {}",
+                        search_order_id, other
+                    );
+                    RepositoryError::DatabaseError(other)
+                }
+            })?;
+
+        let lines = active_order_items::table
+            .inner_join(menu_items::table)
+            .filter(active_order_items::order_id.eq(search_order_id))
+            .select((menu_items::name, active_order_items::quantity))
+            .order(menu_items::name.asc())
+            .load::<(String, i16)>(conn)
+            .map_err(|e| {
+                error!(
+                    "get_kitchen_ticket: error fetching items of order {}: {}",
+                    search_order_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?
+            .into_iter()
+            .map(|(name, quantity)| TicketLine { name, quantity })
+            .collect();
+
+        Ok((
+            owner_id,
+            KitchenTicket {
+                order_id: search_order_id,
+                canteen_name,
+                customer_name: display_name
+                    .filter(|n| !n.trim().is_empty())
+                    .unwrap_or(name),
+                slot: order_deliver_at
+                    .as_ref()
+                    .map(|band| band.human_readable().to_string())
+                    .unwrap_or_else(|| "Instant".to_string()),
+                ordered_at: order_ordered_at,
+                lines,
+                note: order_note,
+            },
+        ))
+    }
+
     pub fn order_actions(
         &self,
         search_order_id: &i32,
@@ -754,7 +873,9 @@ impl OrderOperations {
                         active_order_items::item_id,
                         active_orders::total_price,
                         active_order_items::quantity,
-                        active_orders::ordered_at
+                        active_orders::ordered_at,
+                        active_order_items::price,
+                        active_orders::deliver_at
                     ))
                     .filter(active_orders::order_id.eq(search_order_id))
                     .filter(active_orders::canteen_id.eq(owner_canteen_id))
@@ -786,11 +907,41 @@ impl OrderOperations {
                         items: items_in_order,
                         price: first_item.price,
                         order_status: deliver_status == "delivered",
-                        ordered_at: first_item.ordered_at
+                        ordered_at: first_item.ordered_at,
+                        canteen_id: Some(owner_canteen_id),
+                        deliver_at: first_item.deliver_at.clone(),
                     })
                     .execute(conn)
                     .map_err(RepositoryError::DatabaseError)?;
             }
+            {
+                use crate::db::schema::past_order_items;
+                let past_items = order_items
+                    .iter()
+                    .map(|item| NewPastOrderItem {
+                        order_id: *search_order_id,
+                        item_id: item.item_id,
+                        quantity: item.quantity,
+                        price: item.item_price,
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(past_order_items::table)
+                    .values(&past_items)
+                    .execute(conn)
+                    .map_err(RepositoryError::DatabaseError)?;
+            }
+            if deliver_status == "delivered" {
+                let delivered_items = order_items
+                    .iter()
+                    .map(|item| (item.item_id, item.quantity))
+                    .collect::<Vec<_>>();
+                RecommendationOperations::record_delivered_order(
+                    conn,
+                    first_item.user_id,
+                    &delivered_items,
+                    first_item.ordered_at,
+                )?;
+            }
 
             {
                 use crate::db::schema::*;
@@ -806,6 +957,33 @@ impl OrderOperations {
                         }
                     })?;
             }
+            let webhook_event = if deliver_status == "delivered" {
+                WebhookEvent::OrderDelivered {
+                    order_id: *search_order_id,
+                    user_id: first_item.user_id,
+                }
+            } else {
+                WebhookEvent::OrderCancelled {
+                    order_id: *search_order_id,
+                    user_id: first_item.user_id,
+                }
+            };
+            OutboxOperations::enqueue(
+                conn,
+                &[
+                    OutboxMessage::UserEvent {
+                        user_id: first_item.user_id,
+                        event: SseEvent::UserOrderUpdate {
+                            order_id: *search_order_id,
+                            status: deliver_status.to_string(),
+                        },
+                    },
+                    OutboxMessage::CanteenWebhook {
+                        canteen_id: owner_canteen_id,
+                        event: webhook_event,
+                    },
+                ],
+            )?;
             Ok(first_item.user_id)
         })
     }
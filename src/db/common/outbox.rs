@@ -0,0 +1,322 @@
+use crate::db::schema::{
+    active_order_items, active_orders, canteens, device_tokens, menu_items, outbox, payment_orders,
+    users,
+};
+use crate::db::{DbConnection, RepositoryError};
+use crate::models::common::{OutboxEntry, TimeBandEnum};
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use crate::services::email::Email;
+use crate::services::outbox::OutboxMessage;
+use crate::services::push::PushMessage;
+use crate::services::receipts::{OrderReceipt, ReceiptLine};
+use crate::services::webhooks::WebhookEvent;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
+use log::{debug, error, warn};
+
+#[derive(Clone)]
+pub struct OutboxOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl OutboxOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// Write side effects of a state change. Must be called within the transaction making the
+    /// change, so they are recorded if and only if it commits. User events that warrant a push
+    /// are followed by a push for each of the user's devices.
+    pub(crate) fn enqueue(
+        conn: &mut PgConnection,
+        messages: &[OutboxMessage],
+    ) -> Result<(), RepositoryError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let mut expanded = Vec::with_capacity(messages.len());
+        for message in messages {
+            expanded.push(message.clone());
+            let OutboxMessage::UserEvent { user_id, event } = message else {
+                continue;
+            };
+            let Some(push) = PushMessage::for_event(event) else {
+                continue;
+            };
+            let tokens = device_tokens::table
+                .filter(device_tokens::user_id.eq(user_id))
+                .select(device_tokens::token)
+                .load::<String>(conn)
+                .map_err(|e| {
+                    error!(
+                        "enqueue: error fetching devices of user_id {}: {}",
+                        user_id, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+            expanded.extend(tokens.into_iter().map(|token| OutboxMessage::DevicePush {
+                user_id: *user_id,
+                token,
+                message: push.clone(),
+            }));
+        }
+        let rows = expanded
+            .iter()
+            .map(|message| {
+                let payload = serde_json::to_string(message).map_err(|e| {
+                    RepositoryError::InternalError(format!(
+                        "failed to encode outbox message: {}",
+                        e
+                    ))
+                })?;
+                Ok((
+                    outbox::topic.eq(message.topic()),
+                    outbox::payload.eq(payload),
+                ))
+            })
+            .collect::<Result<Vec<_>, RepositoryError>>()?;
+        diesel::insert_into(outbox::table)
+            .values(&rows)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|e| {
+                error!("enqueue: error writing outbox entries: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Queue `item.stock_out` for the canteen's webhooks, one per `(item_id, name)`. Called by
+    /// every change that can take an item's stock to zero, within its transaction.
+    pub(crate) fn enqueue_stock_outs(
+        conn: &mut PgConnection,
+        stock_canteen_id: i32,
+        sold_out: &[(i32, String)],
+    ) -> Result<(), RepositoryError> {
+        let messages = sold_out
+            .iter()
+            .map(
+                |(sold_out_id, sold_out_name)| OutboxMessage::CanteenWebhook {
+                    canteen_id: stock_canteen_id,
+                    event: WebhookEvent::StockOut {
+                        item_id: *sold_out_id,
+                        name: sold_out_name.clone(),
+                    },
+                },
+            )
+            .collect::<Vec<_>>();
+        Self::enqueue(conn, &messages)
+    }
+
+    /// Queue the receipt of a just-confirmed order, inside the confirming transaction. Users
+    /// without a verified email get none. Runs in a savepoint: failing to queue the receipt is
+    /// logged and never rolls back the order.
+    pub(crate) fn enqueue_order_receipt(
+        conn: &mut PgConnection,
+        confirmed_hold_id: i32,
+        new_order_id: i32,
+    ) {
+        let queued = conn
+            .transaction(|conn| Self::insert_order_receipt(conn, confirmed_hold_id, new_order_id));
+        if let Err(e) = queued {
+            warn!(
+                "enqueue_order_receipt: failed to queue receipt for order {}: {}",
+                new_order_id, e
+            );
+        }
+    }
+
+    fn insert_order_receipt(
+        conn: &mut PgConnection,
+        confirmed_hold_id: i32,
+        new_order_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let (owner_id, order_canteen_id, order_total, order_ordered_at, order_deliver_at) =
+            active_orders::table
+                .find(new_order_id)
+                .select((
+                    active_orders::user_id,
+                    active_orders::canteen_id,
+                    active_orders::total_price,
+                    active_orders::ordered_at,
+                    active_orders::deliver_at,
+                ))
+                .first::<(i32, i32, i32, DateTime<Utc>, Option<TimeBandEnum>)>(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+
+        let (recipient, verified, name, display_name) = users::table
+            .find(owner_id)
+            .select((
+                users::email,
+                users::email_verified,
+                users::name,
+                users::display_name,
+            ))
+            .first::<(String, bool, String, Option<String>)>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        let recipient = recipient.trim().to_string();
+        if !verified || recipient.is_empty() {
+            debug!(
+                "enqueue_order_receipt: user_id {} has no verified email, skipping receipt",
+                owner_id
+            );
+            return Ok(());
+        }
+
+        let (canteen_name, canteen_location) = canteens::table
+            .find(order_canteen_id)
+            .select((canteens::canteen_name, canteens::location))
+            .first::<(String, String)>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let lines = active_order_items::table
+            .inner_join(menu_items::table)
+            .filter(active_order_items::order_id.eq(new_order_id))
+            .select((
+                menu_items::name,
+                active_order_items::quantity,
+                active_order_items::price,
+            ))
+            .order(menu_items::name.asc())
+            .load::<(String, i16, i32)>(conn)
+            .map_err(RepositoryError::DatabaseError)?
+            .into_iter()
+            .map(|(name, quantity, unit_price)| ReceiptLine {
+                name,
+                quantity,
+                unit_price,
+            })
+            .collect::<Vec<ReceiptLine>>();
+
+        let payment_reference = payment_orders::table
+            .filter(payment_orders::hold_id.eq(confirmed_hold_id))
+            .order(payment_orders::created_at.desc())
+            .select(payment_orders::merchant_order_id)
+            .first::<String>(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let receipt = OrderReceipt {
+            order_id: new_order_id,
+            customer_name: display_name
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or(name),
+            canteen_name,
+            canteen_location,
+            slot: order_deliver_at
+                .as_ref()
+                .map(|band| band.human_readable().to_string())
+                .unwrap_or_else(|| "Instant".to_string()),
+            ordered_at: order_ordered_at,
+            lines,
+            total_price: order_total,
+            payment_reference,
+        };
+        let rendered = receipt.render(parse_tz_offset_from_env());
+
+        Self::enqueue(
+            conn,
+            &[OutboxMessage::Email {
+                user_id: owner_id,
+                email: Email {
+                    recipient,
+                    subject: rendered.subject,
+                    body_text: rendered.text,
+                    body_html: rendered.html,
+                },
+            }],
+        )
+    }
+
+    /// Claim up to `limit` due entries, oldest first, pushing their next attempt `lease_secs`
+    /// out so other instances leave them alone while they are being delivered.
+    pub fn claim_due(
+        &self,
+        limit: i64,
+        lease_secs: i64,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("claim_due: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection()
+            .transaction(|conn| {
+                let due_ids = outbox::table
+                    .filter(outbox::failed_at.is_null())
+                    .filter(outbox::next_attempt_at.le(diesel::dsl::now))
+                    .order(outbox::outbox_id.asc())
+                    .limit(limit)
+                    .select(outbox::outbox_id)
+                    .for_update()
+                    .skip_locked()
+                    .load::<i64>(conn)?;
+                if due_ids.is_empty() {
+                    return Ok(Vec::new());
+                }
+                diesel::update(outbox::table.filter(outbox::outbox_id.eq_any(due_ids)))
+                    .set(outbox::next_attempt_at.eq(Utc::now() + Duration::seconds(lease_secs)))
+                    .returning(OutboxEntry::as_returning())
+                    .get_results::<OutboxEntry>(conn)
+                    .map(|mut entries| {
+                        entries.sort_by_key(|entry| entry.outbox_id);
+                        entries
+                    })
+            })
+            .map_err(|e| {
+                error!("claim_due: error claiming outbox entries: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Delivered entries are done with and removed.
+    pub fn mark_delivered(&self, delivered_ids: &[i64]) -> Result<(), RepositoryError> {
+        if delivered_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("mark_delivered: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        diesel::delete(outbox::table.filter(outbox::outbox_id.eq_any(delivered_ids)))
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!("mark_delivered: error removing outbox entries: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Record a failed delivery: retry at `retry_at`, or give up on the entry when `None`.
+    pub fn record_failure(
+        &self,
+        target_outbox_id: i64,
+        failure: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("record_failure: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        let now = Utc::now();
+        diesel::update(outbox::table.find(target_outbox_id))
+            .set((
+                outbox::attempts.eq(outbox::attempts + 1),
+                outbox::last_error.eq(failure),
+                outbox::next_attempt_at.eq(retry_at.unwrap_or(now)),
+                outbox::failed_at.eq(if retry_at.is_some() { None } else { Some(now) }),
+            ))
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "record_failure: error updating outbox entry {}: {}",
+                    target_outbox_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+}
@@ -1,5 +1,10 @@
-use crate::db::{DbConnection, RepositoryError};
-use crate::models::common::{NewPaymentOrder, PaymentOrder};
+use crate::db::{DbConnection, OutboxOperations, RepositoryError};
+use crate::models::common::{
+    NewPaymentOrder, PaymentOrder, REFUND_STATE_COMPLETED, REFUND_STATE_FAILED,
+    REFUND_STATE_PENDING,
+};
+use crate::services::outbox::OutboxMessage;
+use crate::sse::SseEvent;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
@@ -14,11 +19,21 @@ pub const PAYMENT_STATE_FAILED: &str = "FAILED";
 pub struct HoldPaymentSnapshot {
     pub hold_id: i32,
     pub user_id: i32,
+    pub canteen_id: i32,
     pub total_price: i32,
     pub expires_at: DateTime<Utc>,
     pub remaining_secs: i64,
 }
 
+/// What PhonePe needs to send a refund.
+#[derive(Debug, Clone)]
+pub struct RefundRequest {
+    pub merchant_refund_id: String,
+    pub original_merchant_order_id: String,
+    /// In paisa.
+    pub amount: i32,
+}
+
 #[derive(Clone)]
 pub struct PaymentOperations {
     pool: Pool<ConnectionManager<PgConnection>>,
@@ -47,10 +62,10 @@ impl PaymentOperations {
         })?;
 
         use crate::db::schema::held_orders::dsl::*;
-        let (hold_user_id, hold_total_price, hold_expires_at) = held_orders
+        let (hold_user_id, hold_canteen_id, hold_total_price, hold_expires_at) = held_orders
             .filter(hold_id.eq(search_hold_id))
-            .select((user_id, total_price, expires_at))
-            .first::<(i32, i32, DateTime<Utc>)>(conn.connection())
+            .select((user_id, canteen_id, total_price, expires_at))
+            .first::<(i32, i32, i32, DateTime<Utc>)>(conn.connection())
             .map_err(|e| match e {
                 Error::NotFound => {
                     RepositoryError::NotFound(format!("Hold {} not found", search_hold_id))
@@ -74,6 +89,7 @@ impl PaymentOperations {
         Ok(HoldPaymentSnapshot {
             hold_id: search_hold_id,
             user_id: hold_user_id,
+            canteen_id: hold_canteen_id,
             total_price: hold_total_price,
             expires_at: hold_expires_at,
             remaining_secs: (hold_expires_at - now).num_seconds().max(0),
@@ -207,6 +223,7 @@ impl PaymentOperations {
             })
     }
 
+    /// Record the payment state and tell the user about it in the same transaction.
     pub fn update_mapping_state(
         &self,
         search_merchant_order_id: &str,
@@ -224,26 +241,180 @@ impl PaymentOperations {
         use crate::db::schema::payment_orders::dsl::*;
         let now = Utc::now();
 
-        let updated = if let Some(order_id_val) = order_id_to_set {
-            diesel::update(payment_orders.filter(merchant_order_id.eq(search_merchant_order_id)))
+        conn.connection().transaction(|conn| {
+            let updated = if let Some(order_id_val) = order_id_to_set {
+                diesel::update(
+                    payment_orders.filter(merchant_order_id.eq(search_merchant_order_id)),
+                )
                 .set((
                     payment_state.eq(new_state),
                     app_order_id.eq(Some(order_id_val)),
                     updated_at.eq(now),
                 ))
-                .get_result::<PaymentOrder>(conn.connection())
-        } else {
-            diesel::update(payment_orders.filter(merchant_order_id.eq(search_merchant_order_id)))
+                .get_result::<PaymentOrder>(conn)
+            } else {
+                diesel::update(
+                    payment_orders.filter(merchant_order_id.eq(search_merchant_order_id)),
+                )
                 .set((payment_state.eq(new_state), updated_at.eq(now)))
-                .get_result::<PaymentOrder>(conn.connection())
-        };
+                .get_result::<PaymentOrder>(conn)
+            }
+            .map_err(|e| match e {
+                Error::NotFound => RepositoryError::NotFound(format!(
+                    "Payment mapping not found for merchant_order_id {}",
+                    search_merchant_order_id
+                )),
+                other => RepositoryError::DatabaseError(other),
+            })?;
 
-        updated.map_err(|e| match e {
-            Error::NotFound => RepositoryError::NotFound(format!(
-                "Payment mapping not found for merchant_order_id {}",
-                search_merchant_order_id
-            )),
-            other => RepositoryError::DatabaseError(other),
+            OutboxOperations::enqueue(
+                conn,
+                &[OutboxMessage::UserEvent {
+                    user_id: updated.user_id,
+                    event: SseEvent::PaymentUpdate {
+                        hold_id: updated.hold_id,
+                        merchant_order_id: updated.merchant_order_id.clone(),
+                        payment_state: updated.payment_state.clone(),
+                    },
+                }],
+            )?;
+            Ok(updated)
         })
     }
+
+    /// The refund to send, or `None` once it has been sent or settled.
+    pub fn refund_request(
+        &self,
+        search_refund_id: i32,
+    ) -> Result<Option<RefundRequest>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("refund_request: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        use crate::db::schema::{payment_orders, refunds};
+        refunds::table
+            .inner_join(payment_orders::table)
+            .filter(refunds::refund_id.eq(search_refund_id))
+            .filter(refunds::state.eq(REFUND_STATE_PENDING))
+            .filter(refunds::phonepe_refund_id.is_null())
+            .select((
+                refunds::merchant_refund_id,
+                payment_orders::merchant_order_id,
+                refunds::amount,
+            ))
+            .first::<(String, String, i32)>(conn.connection())
+            .optional()
+            .map(|found| {
+                found.map(|(merchant_refund_id, original_merchant_order_id, amount)| {
+                    RefundRequest {
+                        merchant_refund_id,
+                        original_merchant_order_id,
+                        amount,
+                    }
+                })
+            })
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    /// Record that PhonePe accepted a refund, with the state it reported.
+    pub fn record_refund_submitted(
+        &self,
+        search_refund_id: i32,
+        new_phonepe_refund_id: &str,
+        new_state: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "record_refund_submitted: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        use crate::db::schema::refunds::dsl::*;
+        let settled = match new_state {
+            REFUND_STATE_COMPLETED | REFUND_STATE_FAILED => new_state,
+            _ => REFUND_STATE_PENDING,
+        };
+        diesel::update(
+            refunds
+                .filter(refund_id.eq(search_refund_id))
+                .filter(state.eq(REFUND_STATE_PENDING)),
+        )
+        .set((
+            phonepe_refund_id.eq(Some(new_phonepe_refund_id)),
+            state.eq(settled),
+            updated_at.eq(Utc::now()),
+        ))
+        .execute(conn.connection())
+        .map_err(RepositoryError::DatabaseError)?;
+        debug!(
+            "record_refund_submitted: refund {} is {} at PhonePe",
+            search_refund_id, settled
+        );
+        Ok(())
+    }
+
+    /// Record that a refund could not be sent.
+    pub fn record_refund_failure(
+        &self,
+        search_refund_id: i32,
+        reason: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "record_refund_failure: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        use crate::db::schema::refunds::dsl::*;
+        diesel::update(
+            refunds
+                .filter(refund_id.eq(search_refund_id))
+                .filter(state.eq(REFUND_STATE_PENDING)),
+        )
+        .set((
+            state.eq(REFUND_STATE_FAILED),
+            failure_reason.eq(Some(reason)),
+            updated_at.eq(Utc::now()),
+        ))
+        .execute(conn.connection())
+        .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    /// Settle a pending refund from a PhonePe callback. Returns whether a refund changed;
+    /// refunds already settled keep their state.
+    pub fn update_refund_state(
+        &self,
+        search_merchant_refund_id: &str,
+        new_state: &str,
+        reason: Option<&str>,
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "update_refund_state: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+
+        use crate::db::schema::refunds::dsl::*;
+        let updated = diesel::update(
+            refunds
+                .filter(merchant_refund_id.eq(search_merchant_refund_id))
+                .filter(state.eq(REFUND_STATE_PENDING)),
+        )
+        .set((
+            state.eq(new_state),
+            failure_reason.eq(reason),
+            updated_at.eq(Utc::now()),
+        ))
+        .execute(conn.connection())
+        .map_err(RepositoryError::DatabaseError)?;
+        Ok(updated > 0)
+    }
 }
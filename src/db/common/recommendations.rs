@@ -0,0 +1,452 @@
+use crate::db::schema::{
+    item_hourly_sales, item_pair_stats, menu_items, past_orders, user_item_stats,
+};
+use crate::db::{
+    AssetOperations, DbConnection, FavouriteOperations, RepositoryError, UserOperations,
+};
+use crate::enums::admin::MenuItemWithPic;
+use crate::enums::common::OrderRequest;
+use crate::enums::users::{Recommendations, UsualOrder, UsualOrderLine};
+use crate::models::admin::MenuItem;
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use diesel::dsl::{sql, sum};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{BigInt, SmallInt};
+use diesel::upsert::excluded;
+use diesel::PgConnection;
+use futures::future::join_all;
+use log::error;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Items in each list of recommendations.
+const RECOMMENDATION_LIMIT: i64 = 10;
+/// How many of a user's most ordered items "also ordered" suggestions are drawn from.
+const PAIR_SEED_ITEMS: i64 = 5;
+/// How many days of sales "popular now" looks back over.
+const POPULAR_WINDOW_DAYS: i64 = 28;
+/// How many of a user's latest delivered orders their usual order is picked from.
+const USUAL_ORDER_HISTORY: i64 = 30;
+
+#[derive(Clone)]
+pub struct RecommendationOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    asset_ops: AssetOperations,
+}
+
+impl RecommendationOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self {
+            pool,
+            asset_ops: AssetOperations::new().await.unwrap(),
+        }
+    }
+
+    /// Add a delivered order to the running totals recommendations are made from. Runs in
+    /// the transaction that delivers the order.
+    pub(crate) fn record_delivered_order(
+        conn: &mut PgConnection,
+        order_user_id: i32,
+        items: &[(i32, i16)],
+        order_ordered_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let mut quantities: BTreeMap<i32, i32> = BTreeMap::new();
+        for (item, count) in items {
+            *quantities.entry(*item).or_default() += i32::from(*count);
+        }
+        if quantities.is_empty() {
+            return Ok(());
+        }
+        let local_ordered_at = order_ordered_at.with_timezone(&parse_tz_offset_from_env());
+
+        {
+            use crate::db::schema::user_item_stats::dsl::*;
+            diesel::insert_into(user_item_stats)
+                .values(
+                    quantities
+                        .iter()
+                        .map(|(item, count)| {
+                            (
+                                user_id.eq(order_user_id),
+                                item_id.eq(*item),
+                                order_count.eq(1),
+                                quantity.eq(*count),
+                                last_ordered_at.eq(order_ordered_at),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .on_conflict((user_id, item_id))
+                .do_update()
+                .set((
+                    order_count.eq(order_count + 1),
+                    quantity.eq(quantity + excluded(quantity)),
+                    last_ordered_at.eq(excluded(last_ordered_at)),
+                ))
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+        }
+
+        let pairs = quantities
+            .keys()
+            .flat_map(|a| {
+                quantities
+                    .keys()
+                    .filter(move |b| *b != a)
+                    .map(move |b| (*a, *b))
+            })
+            .collect::<Vec<_>>();
+        if !pairs.is_empty() {
+            use crate::db::schema::item_pair_stats::dsl::*;
+            diesel::insert_into(item_pair_stats)
+                .values(
+                    pairs
+                        .iter()
+                        .map(|(item, paired)| {
+                            (
+                                item_id.eq(*item),
+                                paired_item_id.eq(*paired),
+                                order_count.eq(1),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .on_conflict((item_id, paired_item_id))
+                .do_update()
+                .set(order_count.eq(order_count + 1))
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+        }
+
+        {
+            use crate::db::schema::item_hourly_sales::dsl::*;
+            diesel::insert_into(item_hourly_sales)
+                .values(
+                    quantities
+                        .iter()
+                        .map(|(item, count)| {
+                            (
+                                item_id.eq(*item),
+                                sold_on.eq(local_ordered_at.date_naive()),
+                                hour.eq(local_ordered_at.hour() as i16),
+                                quantity.eq(*count),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .on_conflict((item_id, sold_on, hour))
+                .do_update()
+                .set(quantity.eq(quantity + excluded(quantity)))
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+        }
+        Ok(())
+    }
+
+    /// The user's most ordered items, items often ordered together with those, and, for a
+    /// canteen, what is popular there at this time of day. Only items that can be ordered
+    /// now are recommended.
+    pub async fn recommendations(
+        &self,
+        for_user_id: i32,
+        in_canteen_id: Option<i32>,
+    ) -> Result<Recommendations, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "recommendations: failed to acquire DB connection for user {}: {}",
+                for_user_id, e
+            );
+            e
+        })?;
+        let exclusions = UserOperations::load_allergen_exclusions(conn.connection(), for_user_id)?;
+        let favourite_ids =
+            FavouriteOperations::load_favourite_item_ids(conn.connection(), for_user_id)?;
+        let (most_ordered, also_ordered, popular_now) = conn
+            .connection()
+            .transaction(|conn| {
+                let mut ordered_query = user_item_stats::table
+                    .inner_join(menu_items::table)
+                    .filter(user_item_stats::user_id.eq(for_user_id))
+                    .select(user_item_stats::item_id)
+                    .order_by((
+                        user_item_stats::order_count.desc(),
+                        user_item_stats::last_ordered_at.desc(),
+                    ))
+                    .into_boxed();
+                if let Some(canteen) = in_canteen_id {
+                    ordered_query = ordered_query.filter(menu_items::canteen_id.eq(canteen));
+                }
+                let ordered_ids = ordered_query.load::<i32>(conn)?;
+                let most_ordered = load_orderable(conn, &ordered_ids, RECOMMENDATION_LIMIT)?;
+
+                let seeds = &ordered_ids[..ordered_ids.len().min(PAIR_SEED_ITEMS as usize)];
+                let mut paired_query = item_pair_stats::table
+                    .inner_join(
+                        menu_items::table
+                            .on(menu_items::item_id.eq(item_pair_stats::paired_item_id)),
+                    )
+                    .filter(item_pair_stats::item_id.eq_any(seeds))
+                    .filter(item_pair_stats::paired_item_id.ne_all(&ordered_ids))
+                    .group_by(item_pair_stats::paired_item_id)
+                    .select(item_pair_stats::paired_item_id)
+                    .order_by((
+                        sum(item_pair_stats::order_count).desc(),
+                        item_pair_stats::paired_item_id.asc(),
+                    ))
+                    .into_boxed();
+                if let Some(canteen) = in_canteen_id {
+                    paired_query = paired_query.filter(menu_items::canteen_id.eq(canteen));
+                }
+                let paired_ids = if seeds.is_empty() {
+                    Vec::new()
+                } else {
+                    paired_query.load::<i32>(conn)?
+                };
+                let also_ordered = load_orderable(conn, &paired_ids, RECOMMENDATION_LIMIT)?;
+
+                let popular_now = match in_canteen_id {
+                    Some(canteen) => popular_now_at(conn, canteen)?,
+                    None => Vec::new(),
+                };
+                Ok((most_ordered, also_ordered, popular_now))
+            })
+            .map_err(|e| {
+                error!(
+                    "recommendations: error loading recommendations for user {}: {}",
+                    for_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        Ok(Recommendations {
+            most_ordered: self
+                .with_pics(&most_ordered, &exclusions, &favourite_ids)
+                .await,
+            also_ordered: self
+                .with_pics(&also_ordered, &exclusions, &favourite_ids)
+                .await,
+            popular_now: self
+                .with_pics(&popular_now, &exclusions, &favourite_ids)
+                .await,
+        })
+    }
+
+    /// What sells most at the canteen around this hour of the day, flagged against the
+    /// viewer's allergen exclusions and favourites when a `viewer_id` is given.
+    pub async fn popular_now(
+        &self,
+        at_canteen_id: i32,
+        viewer_id: Option<i32>,
+    ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "popular_now: failed to acquire DB connection for canteen {}: {}",
+                at_canteen_id, e
+            );
+            e
+        })?;
+        let exclusions = match viewer_id {
+            Some(viewer_id) => {
+                UserOperations::load_allergen_exclusions(conn.connection(), viewer_id)?
+            }
+            None => Vec::new(),
+        };
+        let favourite_ids = match viewer_id {
+            Some(viewer_id) => {
+                FavouriteOperations::load_favourite_item_ids(conn.connection(), viewer_id)?
+            }
+            None => HashSet::new(),
+        };
+        let items = popular_now_at(conn.connection(), at_canteen_id).map_err(|e| {
+            error!(
+                "popular_now: error loading popular items of canteen {}: {}",
+                at_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        Ok(self.with_pics(&items, &exclusions, &favourite_ids).await)
+    }
+
+    /// The order the user has had delivered most often among their latest orders (the most
+    /// recent one on a tie), optionally only among orders from one canteen, as a hold request
+    /// of the items that can be ordered now. `None` without a delivered order.
+    pub fn usual_order(
+        &self,
+        for_user_id: i32,
+        in_canteen_id: Option<i32>,
+    ) -> Result<Option<UsualOrder>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "usual_order: failed to acquire DB connection for user {}: {}",
+                for_user_id, e
+            );
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let orders = past_orders::table
+                    .filter(past_orders::user_id.eq(for_user_id))
+                    .filter(past_orders::order_status.eq(true))
+                    .order_by(past_orders::ordered_at.desc())
+                    .limit(USUAL_ORDER_HISTORY)
+                    .select(past_orders::items)
+                    .load::<Vec<Option<i32>>>(conn)?
+                    .into_iter()
+                    .map(|items| {
+                        let mut items = items.into_iter().flatten().collect::<Vec<_>>();
+                        items.sort_unstable();
+                        items
+                    })
+                    .filter(|items| !items.is_empty())
+                    .collect::<Vec<_>>();
+
+                let all_ids = orders.iter().flatten().copied().collect::<HashSet<_>>();
+                let menu = menu_items::table
+                    .filter(menu_items::item_id.eq_any(all_ids))
+                    .select(MenuItem::as_select())
+                    .load::<MenuItem>(conn)?
+                    .into_iter()
+                    .map(|item| (item.item_id, item))
+                    .collect::<HashMap<_, _>>();
+
+                // Orders are counted by their exact items; the first seen is the latest.
+                let mut counts: HashMap<&Vec<i32>, (i64, usize)> = HashMap::new();
+                for (position, items) in orders.iter().enumerate() {
+                    if let Some(canteen) = in_canteen_id {
+                        let in_canteen = items
+                            .iter()
+                            .all(|id| menu.get(id).is_some_and(|item| item.canteen_id == canteen));
+                        if !in_canteen {
+                            continue;
+                        }
+                    }
+                    counts.entry(items).or_insert((0, position)).0 += 1;
+                }
+                let Some((items, (times_ordered, _))) = counts
+                    .into_iter()
+                    .max_by_key(|(_, (count, position))| (*count, std::cmp::Reverse(*position)))
+                else {
+                    return Ok(None);
+                };
+
+                let mut quantities: BTreeMap<i32, i16> = BTreeMap::new();
+                for id in items {
+                    *quantities.entry(*id).or_default() += 1;
+                }
+                let mut lines = Vec::new();
+                let mut unavailable_item_ids = Vec::new();
+                for (id, quantity) in quantities {
+                    match menu.get(&id) {
+                        Some(item)
+                            if item.is_available
+                                && (item.stock == -1 || item.stock >= i32::from(quantity)) =>
+                        {
+                            lines.push(UsualOrderLine {
+                                item_id: id,
+                                name: item.name.clone(),
+                                quantity,
+                                price: item.price,
+                            })
+                        }
+                        _ => unavailable_item_ids.push(id),
+                    }
+                }
+                let item_ids = lines
+                    .iter()
+                    .flat_map(|line| std::iter::repeat_n(line.item_id, line.quantity as usize))
+                    .collect();
+                Ok(Some(UsualOrder {
+                    hold_request: OrderRequest {
+                        deliver_at: None,
+                        item_ids: Some(item_ids),
+                        saved_cart_id: None,
+                        promo_code: None,
+                        note: None,
+                    },
+                    total_price: lines
+                        .iter()
+                        .map(|line| line.price * i32::from(line.quantity))
+                        .sum(),
+                    lines,
+                    unavailable_item_ids,
+                    times_ordered,
+                }))
+            })
+            .map_err(|e| {
+                error!(
+                    "usual_order: error loading the usual order of user {}: {}",
+                    for_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    async fn with_pics(
+        &self,
+        items: &[MenuItem],
+        exclusions: &[String],
+        favourite_ids: &HashSet<i32>,
+    ) -> Vec<MenuItemWithPic> {
+        let futures = items.iter().map(async |item| {
+            let mut item_with_pic: MenuItemWithPic = item.into();
+            item_with_pic.flag_allergens(exclusions);
+            item_with_pic.mark_favourite(favourite_ids);
+            item_with_pic
+                .populate_pic_link_from(&self.asset_ops, item)
+                .await;
+            item_with_pic
+        });
+        join_all(futures).await
+    }
+}
+
+/// The first `limit` of the items that can be ordered now, in the order of `ids`.
+fn load_orderable(
+    conn: &mut PgConnection,
+    ids: &[i32],
+    limit: i64,
+) -> Result<Vec<MenuItem>, diesel::result::Error> {
+    let mut items = menu_items::table
+        .filter(menu_items::item_id.eq_any(ids))
+        .filter(menu_items::is_available.eq(true))
+        .select(MenuItem::as_select())
+        .load::<MenuItem>(conn)?
+        .into_iter()
+        .map(|item| (item.item_id, item))
+        .collect::<HashMap<_, _>>();
+    Ok(ids
+        .iter()
+        .filter_map(|id| items.remove(id))
+        .take(limit as usize)
+        .collect())
+}
+
+/// The canteen's available items by how much of them sold within an hour either side of now
+/// over the last few weeks, then by sales at any hour.
+fn popular_now_at(
+    conn: &mut PgConnection,
+    at_canteen_id: i32,
+) -> Result<Vec<MenuItem>, diesel::result::Error> {
+    let now = Utc::now().with_timezone(&parse_tz_offset_from_env());
+    let current_hour = now.hour() as i16;
+    let since = now.date_naive() - Duration::days(POPULAR_WINDOW_DAYS - 1);
+    let ids = item_hourly_sales::table
+        .inner_join(menu_items::table)
+        .filter(menu_items::canteen_id.eq(at_canteen_id))
+        .filter(menu_items::is_available.eq(true))
+        .filter(item_hourly_sales::sold_on.ge(since))
+        .group_by(item_hourly_sales::item_id)
+        .select(item_hourly_sales::item_id)
+        .order_by((
+            sql::<BigInt>("SUM(CASE WHEN (item_hourly_sales.hour - ")
+                .bind::<SmallInt, _>(current_hour)
+                .sql(" + 24) % 24 IN (0, 1, 23) THEN item_hourly_sales.quantity ELSE 0 END)")
+                .desc(),
+            sum(item_hourly_sales::quantity).desc(),
+            item_hourly_sales::item_id.asc(),
+        ))
+        .limit(RECOMMENDATION_LIMIT)
+        .load::<i32>(conn)?;
+    load_orderable(conn, &ids, RECOMMENDATION_LIMIT)
+}
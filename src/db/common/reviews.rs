@@ -0,0 +1,397 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{item_ratings, menu_items, order_reviews, past_orders};
+use crate::db::DbConnection;
+use crate::enums::common::{ItemRating, Review, ReviewRequest};
+use crate::models::common::{
+    sanitize_review_text, NewItemRating, NewOrderReview, OrderReview, REVIEW_COMMENT_MAX_LEN,
+    REVIEW_REPLY_MAX_LEN,
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::sql_types::{Array, Integer};
+use log::error;
+use std::collections::{HashMap, HashSet};
+
+/// Days after it was placed that a delivered order can still be reviewed.
+pub const REVIEW_WINDOW_DAYS: i64 = 7;
+
+#[derive(Clone)]
+pub struct ReviewOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl ReviewOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// Review a delivered order of the user: a rating for the order, an optional comment and
+    /// optional ratings of the items in it. Each order can be reviewed once, within
+    /// [`REVIEW_WINDOW_DAYS`] of being placed.
+    pub fn create_review(
+        &self,
+        reviewer_id: i32,
+        review_order_id: i32,
+        request: ReviewRequest,
+    ) -> Result<Review, RepositoryError> {
+        let ReviewRequest {
+            rating,
+            comment,
+            items,
+        } = request;
+        check_rating(rating)?;
+        let comment = sanitize_review_text(comment, REVIEW_COMMENT_MAX_LEN, "comment")
+            .map_err(RepositoryError::ValidationError)?;
+        let mut rated_ids = HashSet::new();
+        for item in &items {
+            check_rating(item.rating)?;
+            if !rated_ids.insert(item.item_id) {
+                return Err(RepositoryError::ValidationError(format!(
+                    "item {} is rated more than once",
+                    item.item_id
+                )));
+            }
+        }
+
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "create_review: failed to acquire DB connection for user_id {}: {}",
+                reviewer_id, e
+            );
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let (ordered_items, delivered, placed_at) = past_orders::table
+                    .filter(past_orders::order_id.eq(review_order_id))
+                    .filter(past_orders::user_id.eq(reviewer_id))
+                    .select((
+                        past_orders::items,
+                        past_orders::order_status,
+                        past_orders::ordered_at,
+                    ))
+                    .for_update()
+                    .first::<(Vec<Option<i32>>, bool, DateTime<Utc>)>(conn)
+                    .map_err(|e| match e {
+                        Error::NotFound => {
+                            RepositoryError::NotFound(format!("past_orders: {review_order_id}"))
+                        }
+                        other => RepositoryError::DatabaseError(other),
+                    })?;
+                if !delivered {
+                    return Err(RepositoryError::ValidationError(
+                        "only delivered orders can be reviewed".to_string(),
+                    ));
+                }
+                if placed_at + Duration::days(REVIEW_WINDOW_DAYS) < Utc::now() {
+                    return Err(RepositoryError::ValidationError(format!(
+                        "orders can only be reviewed within {REVIEW_WINDOW_DAYS} days"
+                    )));
+                }
+                let ordered_ids = ordered_items.into_iter().flatten().collect::<HashSet<_>>();
+                if let Some(stray) = rated_ids.iter().find(|id| !ordered_ids.contains(id)) {
+                    return Err(RepositoryError::ValidationError(format!(
+                        "item {stray} is not part of order {review_order_id}"
+                    )));
+                }
+                let review_canteen_id = menu_items::table
+                    .filter(menu_items::item_id.eq_any(&ordered_ids))
+                    .select(menu_items::canteen_id)
+                    .first::<i32>(conn)
+                    .optional()?
+                    .ok_or_else(|| {
+                        RepositoryError::ValidationError(format!(
+                            "the items of order {review_order_id} are no longer on the menu"
+                        ))
+                    })?;
+
+                let new_review_id = diesel::insert_into(order_reviews::table)
+                    .values(&NewOrderReview {
+                        order_id: review_order_id,
+                        user_id: reviewer_id,
+                        canteen_id: review_canteen_id,
+                        rating,
+                        comment,
+                    })
+                    .returning(order_reviews::review_id)
+                    .get_result::<i32>(conn)
+                    .map_err(|e| match e {
+                        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                            RepositoryError::ValidationError(format!(
+                                "order {review_order_id} has already been reviewed"
+                            ))
+                        }
+                        other => RepositoryError::DatabaseError(other),
+                    })?;
+                let new_ratings = items
+                    .iter()
+                    .map(|item| NewItemRating {
+                        review_id: new_review_id,
+                        item_id: item.item_id,
+                        rating: item.rating,
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(item_ratings::table)
+                    .values(&new_ratings)
+                    .execute(conn)?;
+
+                refresh_ratings(conn, review_canteen_id, new_review_id)?;
+                Ok(load_review(conn, new_review_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "create_review: error reviewing order {} of user_id {}: {}",
+                    review_order_id, reviewer_id, e
+                )
+            })
+    }
+
+    /// The user's review of one of their orders.
+    pub fn get_review(
+        &self,
+        reviewer_id: i32,
+        review_order_id: i32,
+    ) -> Result<Review, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_review: failed to acquire DB connection for user_id {}: {}",
+                reviewer_id, e
+            );
+            e
+        })?;
+        let conn = conn.connection();
+        let review = order_reviews::table
+            .filter(order_reviews::order_id.eq(review_order_id))
+            .filter(order_reviews::user_id.eq(reviewer_id))
+            .select(OrderReview::as_select())
+            .first::<OrderReview>(conn)
+            .optional()
+            .and_then(|review| with_item_ratings(conn, review.into_iter().collect()))
+            .map_err(|e| {
+                error!(
+                    "get_review: error loading the review of order {}: {}",
+                    review_order_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+        review
+            .into_iter()
+            .next()
+            .ok_or_else(|| RepositoryError::NotFound(format!("order_reviews: {review_order_id}")))
+    }
+
+    /// Reviews of the canteen, newest first. Hidden reviews are only listed for its admins.
+    pub fn list_reviews(
+        &self,
+        review_canteen_id: i32,
+        include_hidden: bool,
+    ) -> Result<Vec<Review>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "list_reviews: failed to acquire DB connection for canteen {}: {}",
+                review_canteen_id, e
+            );
+            e
+        })?;
+        let conn = conn.connection();
+        let mut query = order_reviews::table
+            .filter(order_reviews::canteen_id.eq(review_canteen_id))
+            .into_boxed();
+        if !include_hidden {
+            query = query.filter(order_reviews::is_hidden.eq(false));
+        }
+        query
+            .order_by((
+                order_reviews::created_at.desc(),
+                order_reviews::review_id.desc(),
+            ))
+            .select(OrderReview::as_select())
+            .load::<OrderReview>(conn)
+            .and_then(|reviews| with_item_ratings(conn, reviews))
+            .map_err(|e| {
+                error!(
+                    "list_reviews: error loading reviews of canteen {}: {}",
+                    review_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Hide a review of the canteen from everyone but its admins, or show it again. Hidden
+    /// reviews do not count towards average ratings.
+    pub fn set_review_hidden(
+        &self,
+        owner_canteen_id: i32,
+        target_review_id: i32,
+        hidden: bool,
+    ) -> Result<Review, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("set_review_hidden: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let updated = diesel::update(
+                    order_reviews::table
+                        .filter(order_reviews::review_id.eq(target_review_id))
+                        .filter(order_reviews::canteen_id.eq(owner_canteen_id)),
+                )
+                .set(order_reviews::is_hidden.eq(hidden))
+                .execute(conn)?;
+                if updated == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "order_reviews: {target_review_id}"
+                    )));
+                }
+                refresh_ratings(conn, owner_canteen_id, target_review_id)?;
+                Ok(load_review(conn, target_review_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "set_review_hidden: error moderating review {} of canteen {}: {}",
+                    target_review_id, owner_canteen_id, e
+                )
+            })
+    }
+
+    /// Reply to a review of the canteen, replacing any earlier reply; an empty reply removes it.
+    pub fn reply_to_review(
+        &self,
+        owner_canteen_id: i32,
+        target_review_id: i32,
+        reply_text: Option<String>,
+    ) -> Result<Review, RepositoryError> {
+        let reply_text = sanitize_review_text(reply_text, REVIEW_REPLY_MAX_LEN, "reply")
+            .map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("reply_to_review: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let updated = diesel::update(
+                    order_reviews::table
+                        .filter(order_reviews::review_id.eq(target_review_id))
+                        .filter(order_reviews::canteen_id.eq(owner_canteen_id)),
+                )
+                .set((
+                    order_reviews::replied_at.eq(reply_text.as_ref().map(|_| Utc::now())),
+                    order_reviews::reply.eq(&reply_text),
+                ))
+                .execute(conn)?;
+                if updated == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "order_reviews: {target_review_id}"
+                    )));
+                }
+                Ok(load_review(conn, target_review_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "reply_to_review: error replying to review {} of canteen {}: {}",
+                    target_review_id, owner_canteen_id, e
+                )
+            })
+    }
+}
+
+fn check_rating(rating: i16) -> Result<(), RepositoryError> {
+    if !(1..=5).contains(&rating) {
+        return Err(RepositoryError::ValidationError(
+            "ratings must be 1 to 5".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Recompute the average ratings of the canteen and of the items rated in the review, from
+/// the reviews that are not hidden.
+fn refresh_ratings(
+    conn: &mut PgConnection,
+    review_canteen_id: i32,
+    changed_review_id: i32,
+) -> Result<(), Error> {
+    diesel::sql_query(
+        "UPDATE canteens SET (average_rating, rating_count) = ( \
+             SELECT ROUND(AVG(rating), 2)::FLOAT8, COUNT(*)::INT4 FROM order_reviews \
+             WHERE canteen_id = $1 AND NOT is_hidden) \
+         WHERE canteen_id = $1",
+    )
+    .bind::<Integer, _>(review_canteen_id)
+    .execute(conn)?;
+    let rated_ids = item_ratings::table
+        .filter(item_ratings::review_id.eq(changed_review_id))
+        .select(item_ratings::item_id)
+        .load::<i32>(conn)?;
+    if rated_ids.is_empty() {
+        return Ok(());
+    }
+    diesel::sql_query(
+        "UPDATE menu_items SET (average_rating, rating_count) = ( \
+             SELECT ROUND(AVG(item_ratings.rating), 2)::FLOAT8, COUNT(*)::INT4 \
+             FROM item_ratings JOIN order_reviews USING (review_id) \
+             WHERE item_ratings.item_id = menu_items.item_id AND NOT order_reviews.is_hidden) \
+         WHERE item_id = ANY($1)",
+    )
+    .bind::<Array<Integer>, _>(rated_ids)
+    .execute(conn)?;
+    Ok(())
+}
+
+/// The review with its item ratings.
+fn load_review(conn: &mut PgConnection, target_review_id: i32) -> Result<Review, Error> {
+    let review = order_reviews::table
+        .find(target_review_id)
+        .select(OrderReview::as_select())
+        .first::<OrderReview>(conn)?;
+    Ok(with_item_ratings(conn, vec![review])?.remove(0))
+}
+
+/// Attach the item ratings, with item names, to loaded reviews.
+fn with_item_ratings(
+    conn: &mut PgConnection,
+    reviews: Vec<OrderReview>,
+) -> Result<Vec<Review>, Error> {
+    let review_ids = reviews.iter().map(|r| r.review_id).collect::<Vec<_>>();
+    let mut ratings_by_review: HashMap<i32, Vec<ItemRating>> = HashMap::new();
+    for (rated_review_id, rated_item_id, item_name, item_rating) in item_ratings::table
+        .inner_join(menu_items::table)
+        .filter(item_ratings::review_id.eq_any(&review_ids))
+        .order_by((item_ratings::review_id, item_ratings::item_id))
+        .select((
+            item_ratings::review_id,
+            item_ratings::item_id,
+            menu_items::name,
+            item_ratings::rating,
+        ))
+        .load::<(i32, i32, String, i16)>(conn)?
+    {
+        ratings_by_review
+            .entry(rated_review_id)
+            .or_default()
+            .push(ItemRating {
+                item_id: rated_item_id,
+                name: item_name,
+                rating: item_rating,
+            });
+    }
+    Ok(reviews
+        .into_iter()
+        .map(|review| Review {
+            items: ratings_by_review
+                .remove(&review.review_id)
+                .unwrap_or_default(),
+            review_id: review.review_id,
+            order_id: review.order_id,
+            canteen_id: review.canteen_id,
+            rating: review.rating,
+            comment: review.comment,
+            is_hidden: review.is_hidden,
+            reply: review.reply,
+            replied_at: review.replied_at,
+            created_at: review.created_at,
+        })
+        .collect())
+}
@@ -1,13 +1,326 @@
-use crate::db::{AssetOperations, DbConnection, RepositoryError};
-use crate::enums::admin::MenuItemWithPic;
-use crate::models::admin::MenuItem;
-use diesel::dsl::sql;
+use crate::db::schema::{canteens, menu_items};
+use crate::db::{
+    AssetOperations, DbConnection, FavouriteOperations, RepositoryError, UserOperations,
+};
+use crate::enums::admin::{
+    CanteenSearchFacet, CanteenSuggestion, MenuFilterQuery, MenuItemWithPic, MenuSearchFacets,
+    MenuSearchQuery, MenuSearchResults, SearchInsights, SearchQueryStat, SearchSuggestQuery,
+    SearchSuggestions,
+};
+use crate::models::admin::{sanitize_tag, MenuItem};
+use crate::services::canteen_hours::parse_tz_offset_from_env;
+use chrono::{NaiveDate, Utc};
+use diesel::dsl::{count_star, max, min, not, sql, sum, Filter, InnerJoin, InnerJoinQuerySource};
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
-use diesel::sql_types::{Bool, Text};
+use diesel::sql_types::{Bool, Date, Float, Integer, Nullable, Text};
 use diesel::PgConnection;
 use futures::future::join_all;
-use log::{debug, error};
+use log::{debug, error, warn};
+use std::collections::HashSet;
+
+const MENU_SEARCH_DEFAULT_PER_PAGE: i64 = 20;
+const MENU_SEARCH_MAX_PER_PAGE: i64 = 50;
+const MENU_SEARCH_MAX_QUERY_CHARS: usize = 100;
+/// How many items the path-style search routes return.
+const LEGACY_SEARCH_LIMIT: i64 = 10;
+const SUGGEST_DEFAULT_LIMIT: i64 = 8;
+const SUGGEST_MAX_LIMIT: i64 = 20;
+/// A logged search is only suggested once it has been made this many times, so nobody's
+/// one-off searches are shown to others.
+const SUGGEST_MIN_SEARCHES: i64 = 3;
+/// How many days of logged searches popular suggestions are drawn from.
+const SUGGEST_WINDOW_DAYS: i64 = 30;
+pub const SEARCH_INSIGHTS_DEFAULT_DAYS: i64 = 30;
+const INSIGHTS_MAX_DAYS: i64 = 365;
+pub const SEARCH_INSIGHTS_DEFAULT_LIMIT: i64 = 20;
+const INSIGHTS_MAX_LIMIT: i64 = 100;
+
+/// The weighted document items are matched against: names rank above descriptions. It has to
+/// stay in step with the expression of `menu_items_search_document_idx`.
+const MENU_SEARCH_DOCUMENT: &str = "(setweight(to_tsvector('english', menu_items.name), 'A') \
+     || setweight(to_tsvector('english', coalesce(menu_items.description, '')), 'B'))";
+
+type MenuSearchSource = InnerJoin<menu_items::table, canteens::table>;
+type MenuSearchCondition = Box<
+    dyn BoxableExpression<
+        InnerJoinQuerySource<menu_items::table, canteens::table>,
+        Pg,
+        SqlType = Bool,
+    >,
+>;
+
+/// A validated [`MenuSearchQuery`].
+#[derive(Clone, Debug)]
+struct SearchCriteria {
+    text: Option<String>,
+    canteen_id: Option<i32>,
+    category_id: Option<i32>,
+    tag: Option<String>,
+    veg: Option<bool>,
+    min_price: Option<i32>,
+    max_price: Option<i32>,
+    available_only: bool,
+    open_only: bool,
+    /// Allergens whose items are left out.
+    hidden_allergens: Vec<String>,
+}
+
+impl SearchCriteria {
+    fn new(params: &MenuSearchQuery, exclusions: &[String]) -> Result<Self, RepositoryError> {
+        let text = params
+            .q
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(str::to_string);
+        if text
+            .as_ref()
+            .is_some_and(|text| text.chars().count() > MENU_SEARCH_MAX_QUERY_CHARS)
+        {
+            return Err(RepositoryError::ValidationError(format!(
+                "q must be at most {} characters",
+                MENU_SEARCH_MAX_QUERY_CHARS
+            )));
+        }
+        if let (Some(min_price), Some(max_price)) = (params.min_price, params.max_price) {
+            if min_price > max_price {
+                return Err(RepositoryError::ValidationError(
+                    "min_price must not be greater than max_price".to_string(),
+                ));
+            }
+        }
+        let tag = params
+            .tag
+            .as_deref()
+            .map(sanitize_tag)
+            .transpose()
+            .map_err(RepositoryError::ValidationError)?;
+        Ok(Self {
+            text,
+            canteen_id: params.canteen_id,
+            category_id: params.category_id,
+            tag,
+            veg: params.veg,
+            min_price: params.min_price,
+            max_price: params.max_price,
+            available_only: params.available_only.unwrap_or(false),
+            open_only: params.open_only.unwrap_or(false),
+            hidden_allergens: if params.hide_allergens.unwrap_or(false) {
+                exclusions.to_vec()
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    /// Whether an item matches the text and filters.
+    fn condition(&self) -> MenuSearchCondition {
+        let mut condition: MenuSearchCondition = Box::new(sql::<Bool>("TRUE"));
+        if let Some(text) = &self.text {
+            // Words anywhere in the name or description, or a name close enough to the
+            // query to forgive typos.
+            condition = Box::new(
+                condition.and(
+                    sql::<Bool>(&format!(
+                        "({} @@ websearch_to_tsquery('english', ",
+                        MENU_SEARCH_DOCUMENT
+                    ))
+                    .bind::<Text, _>(text.clone())
+                    .sql(") OR menu_items.name % ")
+                    .bind::<Text, _>(text.clone())
+                    .sql(")"),
+                ),
+            );
+        }
+        if let Some(canteen_id) = self.canteen_id {
+            condition = Box::new(condition.and(menu_items::canteen_id.eq(canteen_id)));
+        }
+        if let Some(category_id) = self.category_id {
+            condition =
+                Box::new(condition.and(menu_items::category_id.eq(category_id).assume_not_null()));
+        }
+        if let Some(tag) = &self.tag {
+            condition = Box::new(condition.and(menu_items::tags.contains(vec![tag.clone()])));
+        }
+        if let Some(veg) = self.veg {
+            condition = Box::new(condition.and(menu_items::is_veg.eq(veg)));
+        }
+        if let Some(min_price) = self.min_price {
+            condition = Box::new(condition.and(menu_items::price.ge(min_price)));
+        }
+        if let Some(max_price) = self.max_price {
+            condition = Box::new(condition.and(menu_items::price.le(max_price)));
+        }
+        if self.available_only {
+            condition = Box::new(condition.and(menu_items::is_available.eq(true)));
+        }
+        if self.open_only {
+            condition = Box::new(condition.and(canteens::is_open.eq(true)));
+        }
+        if !self.hidden_allergens.is_empty() {
+            condition = Box::new(condition.and(not(
+                menu_items::allergens.overlaps_with(self.hidden_allergens.clone()),
+            )));
+        }
+        condition
+    }
+
+    /// Every item matching the text and filters, unordered.
+    fn matching_items(&self) -> Filter<MenuSearchSource, MenuSearchCondition> {
+        menu_items::table
+            .inner_join(canteens::table)
+            .filter(self.condition())
+    }
+
+    /// One page of matching items, best matches first; alphabetical without a query text.
+    fn load_page(
+        &self,
+        conn: &mut PgConnection,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<MenuItem>, diesel::result::Error> {
+        let mut query = self
+            .matching_items()
+            .select(MenuItem::as_select())
+            .into_boxed();
+        query = match &self.text {
+            Some(text) => query.order_by(
+                sql::<Float>(&format!(
+                    "ts_rank({}, websearch_to_tsquery('english', ",
+                    MENU_SEARCH_DOCUMENT
+                ))
+                .bind::<Text, _>(text.clone())
+                .sql(")) + similarity(menu_items.name, ")
+                .bind::<Text, _>(text.clone())
+                .sql(")")
+                .desc(),
+            ),
+            None => query.order_by(menu_items::name.asc()),
+        };
+        query
+            .then_order_by(menu_items::item_id.asc())
+            .offset(offset)
+            .limit(limit)
+            .load(conn)
+    }
+
+    /// Facet counts over all matching items. Each facet ignores its own filter, so picking
+    /// "veg" still shows how many non-veg items there are.
+    fn facets(&self, conn: &mut PgConnection) -> Result<MenuSearchFacets, diesel::result::Error> {
+        let veg_counts = Self {
+            veg: None,
+            ..self.clone()
+        }
+        .matching_items()
+        .group_by(menu_items::is_veg)
+        .select((menu_items::is_veg, count_star()))
+        .load::<(bool, i64)>(conn)?;
+        let count_of = |veg: bool| {
+            veg_counts
+                .iter()
+                .find(|(is_veg, _)| *is_veg == veg)
+                .map_or(0, |(_, count)| *count)
+        };
+
+        let available = Self {
+            available_only: false,
+            ..self.clone()
+        }
+        .matching_items()
+        .filter(menu_items::is_available.eq(true))
+        .count()
+        .get_result::<i64>(conn)?;
+
+        let canteens = Self {
+            canteen_id: None,
+            ..self.clone()
+        }
+        .matching_items()
+        .group_by((canteens::canteen_id, canteens::canteen_name))
+        .select((canteens::canteen_id, canteens::canteen_name, count_star()))
+        .order_by((count_star().desc(), canteens::canteen_name.asc()))
+        .load::<(i32, String, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(canteen_id, canteen_name, item_count)| CanteenSearchFacet {
+                canteen_id,
+                canteen_name,
+                item_count,
+            },
+        )
+        .collect();
+
+        let (min_price, max_price) = Self {
+            min_price: None,
+            max_price: None,
+            ..self.clone()
+        }
+        .matching_items()
+        .select((min(menu_items::price), max(menu_items::price)))
+        .get_result::<(Option<i32>, Option<i32>)>(conn)?;
+
+        Ok(MenuSearchFacets {
+            veg: count_of(true),
+            non_veg: count_of(false),
+            available,
+            canteens,
+            min_price,
+            max_price,
+        })
+    }
+}
+
+/// How a search is logged and matched against logged searches: lowercased, with runs of
+/// whitespace collapsed.
+fn normalize_search_query(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// A LIKE pattern matching text that starts with `text`.
+fn like_prefix(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}%", escaped)
+}
+
+/// The day searches are counted under, in the canteens' time zone.
+fn search_log_today() -> NaiveDate {
+    Utc::now()
+        .with_timezone(&parse_tz_offset_from_env())
+        .date_naive()
+}
+
+/// Counts a search in the query log. Only the normalised text, the canteen searched and
+/// whether anything was found are kept, per day. Failing to log never fails the search.
+fn record_search(conn: &mut PgConnection, text: &str, in_canteen_id: Option<i32>, found: i64) {
+    let normalized = normalize_search_query(text);
+    let result = diesel::sql_query(
+        "INSERT INTO search_query_stats \
+             (searched_on, query, canteen_id, search_count, no_result_count) \
+         VALUES ($1, $2, $3, 1, $4) \
+         ON CONFLICT (searched_on, query, COALESCE(canteen_id, 0)) DO UPDATE SET \
+             search_count = search_query_stats.search_count + 1, \
+             no_result_count = search_query_stats.no_result_count + EXCLUDED.no_result_count",
+    )
+    .bind::<Date, _>(search_log_today())
+    .bind::<Text, _>(&normalized)
+    .bind::<Nullable<Integer>, _>(in_canteen_id)
+    .bind::<Integer, _>(i32::from(found == 0))
+    .execute(conn);
+    if let Err(e) = result {
+        warn!(
+            "record_search: failed to log search '{}': {}",
+            normalized, e
+        );
+    }
+}
 
 #[derive(Clone)]
 pub struct SearchOperations {
@@ -23,110 +336,347 @@ impl SearchOperations {
         }
     }
 
-    /// Performs a fuzzy search on the menu_items table using the pg_trgm extension.
-    /// Returns up to 10 menu items ordered by descending similarity.
-    pub async fn search_menu_items(
+    /// Full-text search over item names and descriptions, combined with pg_trgm matching on
+    /// names, narrowed by the query's filters. Returns one page of items, best matches first,
+    /// flagged against the viewer's allergen exclusions and favourites when a `viewer_id` is
+    /// given, along with the total and facet counts of everything that matched.
+    pub async fn search_menu(
         &self,
-        search_query: &str,
-    ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
+        params: &MenuSearchQuery,
+        viewer_id: Option<i32>,
+    ) -> Result<MenuSearchResults, RepositoryError> {
+        let page = params.page.unwrap_or(1);
+        if page < 1 {
+            return Err(RepositoryError::ValidationError(
+                "page must be at least 1".to_string(),
+            ));
+        }
+        let per_page = params
+            .per_page
+            .unwrap_or(MENU_SEARCH_DEFAULT_PER_PAGE)
+            .clamp(1, MENU_SEARCH_MAX_PER_PAGE);
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!(
-                "search_menu_items: failed to acquire DB connection for query '{}': {}",
-                search_query, e
+                "search_menu: failed to acquire DB connection for query {:?}: {}",
+                params.q, e
             );
             e
         })?;
-        debug!(
-            "search_menu_items: executing fuzzy search for query '{}'",
-            search_query
-        );
-        use crate::db::schema::menu_items::dsl::*;
-        // SELECT * FROM menu_items
-        //            WHERE name % $1
-        //            ORDER BY similarity(name, $1) DESC
-        //            LIMIT 500;
-        let items = menu_items
-            .filter(sql::<Bool>("name % ").bind::<Text, _>(search_query))
-            .order_by(
-                sql::<Text>("similarity (name, ")
-                    .bind::<Text, _>(search_query)
-                    .sql(")")
-                    .desc(),
-            )
-            .limit(10)
-            .load::<MenuItem>(conn.connection())
+        let exclusions = match viewer_id {
+            Some(viewer_id) => {
+                UserOperations::load_allergen_exclusions(conn.connection(), viewer_id)?
+            }
+            None => Vec::new(),
+        };
+        let favourite_ids = match viewer_id {
+            Some(viewer_id) => {
+                FavouriteOperations::load_favourite_item_ids(conn.connection(), viewer_id)?
+            }
+            None => HashSet::new(),
+        };
+        let criteria = SearchCriteria::new(params, &exclusions)?;
+        debug!("search_menu: searching with {:?}, page {}", criteria, page);
+
+        let (items, total, facets) = conn
+            .connection()
+            .transaction(|conn| {
+                let items = criteria.load_page(conn, (page - 1) * per_page, per_page)?;
+                let total = criteria.matching_items().count().get_result::<i64>(conn)?;
+                let facets = criteria.facets(conn)?;
+                Ok((items, total, facets))
+            })
             .map_err(|e| {
                 error!(
-                    "search_menu_items: error performing search for query '{}': {}",
-                    search_query, e
+                    "search_menu: error performing search for query {:?}: {}",
+                    params.q, e
                 );
                 RepositoryError::DatabaseError(e)
             })?;
+        // Later pages are the same search again.
+        if let (Some(text), 1) = (&criteria.text, page) {
+            record_search(conn.connection(), text, criteria.canteen_id, total);
+        }
 
-        let futures = items.iter().map(async |item| {
-            let mut item_with_pic: MenuItemWithPic = item.into();
-            item_with_pic
-                .populate_pic_link_from(&self.asset_ops, item)
-                .await;
-            item_with_pic
-        });
+        Ok(MenuSearchResults {
+            items: self.with_pics(&items, &exclusions, &favourite_ids).await,
+            total,
+            page,
+            per_page,
+            facets,
+        })
+    }
 
-        let results = join_all(futures).await;
-        Ok(results)
+    /// The best matches for `search_query` across all canteens, up to 10, flagged against the
+    /// viewer's allergen exclusions and favourites when a `viewer_id` is given.
+    pub async fn search_menu_items(
+        &self,
+        search_query: &str,
+        filter: &MenuFilterQuery,
+        viewer_id: Option<i32>,
+    ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
+        self.search_top_items(None, search_query, filter, viewer_id)
+            .await
     }
 
-    /// Performs a fuzzy search on the menu_items table using the pg_trgm extension.
-    /// Returns up to 10 menu items ordered by descending similarity.
+    /// The best matches for `search_query` in one canteen, up to 10, flagged against the
+    /// viewer's allergen exclusions and favourites when a `viewer_id` is given.
     pub async fn search_menu_items_by_canteen(
         &self,
         from_canteen_id: &i32,
         search_query: &str,
+        filter: &MenuFilterQuery,
+        viewer_id: Option<i32>,
+    ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
+        self.search_top_items(Some(*from_canteen_id), search_query, filter, viewer_id)
+            .await
+    }
+
+    async fn search_top_items(
+        &self,
+        from_canteen_id: Option<i32>,
+        search_query: &str,
+        filter: &MenuFilterQuery,
+        viewer_id: Option<i32>,
     ) -> Result<Vec<MenuItemWithPic>, RepositoryError> {
         let mut conn = DbConnection::new(&self.pool).map_err(|e| {
             error!(
-                "search_menu_items_by_canteen: failed to acquire DB connection for query '{}': {}",
+                "search_top_items: failed to acquire DB connection for query '{}': {}",
                 search_query, e
             );
             e
         })?;
         debug!(
-            "search_menu_items_by_canteen: executing fuzzy search for query '{}' for canteen ID: '{}'",
+            "search_top_items: searching for '{}' in canteen {:?}",
             search_query, from_canteen_id
         );
-        use crate::db::schema::menu_items::dsl::*;
-        // SELECT * FROM menu_items
-        //            WHERE name % $1
-        //            AND canteen_id = $2
-        //            ORDER BY similarity(name, $1) DESC
-        //            LIMIT 500;
-        let items = menu_items
-            .filter(canteen_id.eq(from_canteen_id))
-            .filter(sql::<Bool>("name % ").bind::<Text, _>(search_query))
-            .order_by(
-                sql::<Text>("similarity (name, ")
-                    .bind::<Text, _>(search_query)
-                    .sql(")")
-                    .desc(),
-            )
-            .limit(10)
-            .load::<MenuItem>(conn.connection())
+        let exclusions = match viewer_id {
+            Some(viewer_id) => {
+                UserOperations::load_allergen_exclusions(conn.connection(), viewer_id)?
+            }
+            None => Vec::new(),
+        };
+        let favourite_ids = match viewer_id {
+            Some(viewer_id) => {
+                FavouriteOperations::load_favourite_item_ids(conn.connection(), viewer_id)?
+            }
+            None => HashSet::new(),
+        };
+        let criteria = SearchCriteria::new(
+            &MenuSearchQuery {
+                q: Some(search_query.to_string()),
+                canteen_id: from_canteen_id,
+                category_id: filter.category_id,
+                tag: filter.tag.clone(),
+                hide_allergens: filter.hide_allergens,
+                ..Default::default()
+            },
+            &exclusions,
+        )?;
+        let items = criteria
+            .load_page(conn.connection(), 0, LEGACY_SEARCH_LIMIT)
             .map_err(|e| {
                 error!(
-                    "search_menu_items_by_canteen: error performing search for query '{}' for canteen '{}': {}",
+                    "search_top_items: error performing search for query '{}' in canteen {:?}: {}",
                     search_query, from_canteen_id, e
                 );
                 RepositoryError::DatabaseError(e)
             })?;
+        if let Some(text) = &criteria.text {
+            record_search(conn.connection(), text, from_canteen_id, items.len() as i64);
+        }
+        Ok(self.with_pics(&items, &exclusions, &favourite_ids).await)
+    }
 
+    /// Item names, canteens and popular searches for what has been typed so far, for
+    /// type-ahead. Popular searches only include queries enough people have made that found
+    /// something.
+    pub fn suggest(
+        &self,
+        params: &SearchSuggestQuery,
+    ) -> Result<SearchSuggestions, RepositoryError> {
+        let typed = normalize_search_query(&params.q);
+        if typed.is_empty() {
+            return Err(RepositoryError::ValidationError(
+                "q is required".to_string(),
+            ));
+        }
+        if typed.chars().count() > MENU_SEARCH_MAX_QUERY_CHARS {
+            return Err(RepositoryError::ValidationError(format!(
+                "q must be at most {} characters",
+                MENU_SEARCH_MAX_QUERY_CHARS
+            )));
+        }
+        let limit = params
+            .limit
+            .unwrap_or(SUGGEST_DEFAULT_LIMIT)
+            .clamp(1, SUGGEST_MAX_LIMIT);
+        let starts_with = like_prefix(&typed);
+        let word_starts_with = format!("% {}", starts_with);
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "suggest: failed to acquire DB connection for '{}': {}",
+                typed, e
+            );
+            e
+        })?;
+
+        conn.connection()
+            .transaction(|conn| {
+                // Names starting with the text come before names with a later word starting
+                // with it; the same name in several canteens is suggested once.
+                let mut item_query = menu_items::table
+                    .filter(
+                        menu_items::name
+                            .ilike(starts_with.clone())
+                            .or(menu_items::name.ilike(word_starts_with.clone())),
+                    )
+                    .select(menu_items::name)
+                    .order_by((
+                        menu_items::name.ilike(starts_with.clone()).desc(),
+                        menu_items::name.asc(),
+                    ))
+                    .limit(limit * 4)
+                    .into_boxed();
+                if let Some(canteen_id) = params.canteen_id {
+                    item_query = item_query.filter(menu_items::canteen_id.eq(canteen_id));
+                }
+                let mut seen = HashSet::new();
+                let mut items = item_query.load::<String>(conn)?;
+                items.retain(|name| seen.insert(name.to_lowercase()));
+                items.truncate(limit as usize);
+
+                let canteens = if params.canteen_id.is_some() {
+                    Vec::new()
+                } else {
+                    canteens::table
+                        .filter(
+                            canteens::canteen_name
+                                .ilike(starts_with.clone())
+                                .or(canteens::canteen_name.ilike(word_starts_with.clone())),
+                        )
+                        .select((canteens::canteen_id, canteens::canteen_name))
+                        .order_by((
+                            canteens::canteen_name.ilike(starts_with.clone()).desc(),
+                            canteens::canteen_name.asc(),
+                        ))
+                        .limit(limit)
+                        .load::<(i32, String)>(conn)?
+                        .into_iter()
+                        .map(|(canteen_id, canteen_name)| CanteenSuggestion {
+                            canteen_id,
+                            canteen_name,
+                        })
+                        .collect()
+                };
+
+                let queries = {
+                    use crate::db::schema::search_query_stats::dsl::*;
+                    let since =
+                        search_log_today() - chrono::Duration::days(SUGGEST_WINDOW_DAYS - 1);
+                    search_query_stats
+                        .filter(query.like(starts_with.clone()))
+                        .filter(searched_on.ge(since))
+                        .group_by(query)
+                        .having(
+                            sum(search_count)
+                                .ge(SUGGEST_MIN_SEARCHES)
+                                .and(sum(no_result_count).lt(sum(search_count))),
+                        )
+                        .select(query)
+                        .order_by((sum(search_count).desc(), query.asc()))
+                        .limit(limit)
+                        .load::<String>(conn)?
+                };
+
+                Ok(SearchSuggestions {
+                    items,
+                    canteens,
+                    queries,
+                })
+            })
+            .map_err(|e| {
+                error!("suggest: error loading suggestions for '{}': {}", typed, e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// The most searched queries and the queries that found nothing, among searches of the
+    /// canteen's menu and searches across all canteens over the last `days` days.
+    pub fn search_insights(
+        &self,
+        for_canteen_id: i32,
+        days: i64,
+        limit: i64,
+    ) -> Result<SearchInsights, RepositoryError> {
+        let days = days.clamp(1, INSIGHTS_MAX_DAYS);
+        let limit = limit.clamp(1, INSIGHTS_MAX_LIMIT);
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "search_insights: failed to acquire DB connection for canteen {}: {}",
+                for_canteen_id, e
+            );
+            e
+        })?;
+        use crate::db::schema::search_query_stats::dsl::*;
+        let since = search_log_today() - chrono::Duration::days(days - 1);
+        let stats_of = |rows: Vec<(String, Option<i64>, Option<i64>)>| {
+            rows.into_iter()
+                .map(|(text, searches, no_results)| SearchQueryStat {
+                    query: text,
+                    search_count: searches.unwrap_or(0),
+                    no_result_count: no_results.unwrap_or(0),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        conn.connection()
+            .transaction(|conn| {
+                let popular = search_query_stats
+                    .filter(canteen_id.eq(for_canteen_id).or(canteen_id.is_null()))
+                    .filter(searched_on.ge(since))
+                    .group_by(query)
+                    .select((query, sum(search_count), sum(no_result_count)))
+                    .order_by((sum(search_count).desc(), query.asc()))
+                    .limit(limit)
+                    .load::<(String, Option<i64>, Option<i64>)>(conn)?;
+                let no_results = search_query_stats
+                    .filter(canteen_id.eq(for_canteen_id).or(canteen_id.is_null()))
+                    .filter(searched_on.ge(since))
+                    .group_by(query)
+                    .having(sum(no_result_count).gt(0))
+                    .select((query, sum(search_count), sum(no_result_count)))
+                    .order_by((sum(no_result_count).desc(), query.asc()))
+                    .limit(limit)
+                    .load::<(String, Option<i64>, Option<i64>)>(conn)?;
+                Ok(SearchInsights {
+                    popular: stats_of(popular),
+                    no_results: stats_of(no_results),
+                })
+            })
+            .map_err(|e| {
+                error!(
+                    "search_insights: error loading insights for canteen {}: {}",
+                    for_canteen_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    async fn with_pics(
+        &self,
+        items: &[MenuItem],
+        exclusions: &[String],
+        favourite_ids: &HashSet<i32>,
+    ) -> Vec<MenuItemWithPic> {
         let futures = items.iter().map(async |item| {
             let mut item_with_pic: MenuItemWithPic = item.into();
+            item_with_pic.flag_allergens(exclusions);
+            item_with_pic.mark_favourite(favourite_ids);
             item_with_pic
                 .populate_pic_link_from(&self.asset_ops, item)
                 .await;
             item_with_pic
         });
-
-        let results = join_all(futures).await;
-        Ok(results)
+        join_all(futures).await
     }
 }
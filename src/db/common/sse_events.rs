@@ -0,0 +1,194 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{sse_events, sse_stream_seqs};
+use crate::db::DbConnection;
+use crate::models::common::StoredSseEvent;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::Text;
+use log::error;
+
+#[derive(Clone)]
+pub struct SseEventLogOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl SseEventLogOperations {
+    pub fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// The `per_stream` most recent events of every stream, oldest first within a stream.
+    pub fn load_recent(&self, per_stream: usize) -> Result<Vec<StoredSseEvent>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("load_recent: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        // The writer prunes each stream as it appends, so the table stays small.
+        let mut events = sse_events::table
+            .order((
+                sse_events::stream_kind.asc(),
+                sse_events::stream_id.asc(),
+                sse_events::seq.desc(),
+            ))
+            .select(StoredSseEvent::as_select())
+            .load::<StoredSseEvent>(conn.connection())
+            .map_err(|e| {
+                error!("load_recent: error fetching SSE events: {}", e);
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let mut kept = 0;
+        let mut previous: Option<(String, i32)> = None;
+        events.retain(|event| {
+            let stream = (event.stream_kind.clone(), event.stream_id);
+            if previous.as_ref() != Some(&stream) {
+                previous = Some(stream);
+                kept = 0;
+            }
+            kept += 1;
+            kept <= per_stream
+        });
+        events.reverse();
+        Ok(events)
+    }
+
+    /// Append an event and drop anything older than the last `keep` events of its stream.
+    pub fn append(&self, event: &StoredSseEvent, keep: usize) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("append: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection()
+            .transaction(|conn| store_event(conn, event, keep))
+    }
+}
+
+fn store_event(
+    conn: &mut PgConnection,
+    event: &StoredSseEvent,
+    keep: usize,
+) -> Result<(), RepositoryError> {
+    diesel::insert_into(sse_events::table)
+        .values(event)
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map_err(|e| {
+            error!(
+                "store_event: error storing SSE event {} of {} {}: {}",
+                event.seq, event.stream_kind, event.stream_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+    diesel::delete(
+        sse_events::table
+            .filter(sse_events::stream_kind.eq(&event.stream_kind))
+            .filter(sse_events::stream_id.eq(event.stream_id))
+            .filter(sse_events::seq.le(event.seq - keep as i64)),
+    )
+    .execute(conn)
+    .map_err(RepositoryError::DatabaseError)?;
+    Ok(())
+}
+
+/// Postgres `NOTIFY`/`LISTEN` used to fan SSE events out across instances.
+#[derive(Clone)]
+pub struct SseNotifyOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    database_url: String,
+}
+
+impl SseNotifyOperations {
+    pub fn new(pool: Pool<ConnectionManager<PgConnection>>, database_url: &str) -> Self {
+        Self {
+            pool,
+            database_url: database_url.to_string(),
+        }
+    }
+
+    /// Number `event` on its stream (its own `seq` is ignored), store it in the event log
+    /// when `keep` is set, and notify `channel` with the payload built for that number, all
+    /// in one transaction. The stream's counter stays locked until the commit, so listeners
+    /// hear every stream's events in sequence order.
+    pub fn notify_event(
+        &self,
+        channel: &str,
+        mut event: StoredSseEvent,
+        keep: Option<usize>,
+        payload: impl FnOnce(i64) -> String,
+    ) -> Result<i64, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("notify_event: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        conn.connection().transaction(|conn| {
+            event.seq = diesel::insert_into(sse_stream_seqs::table)
+                .values((
+                    sse_stream_seqs::stream_kind.eq(&event.stream_kind),
+                    sse_stream_seqs::stream_id.eq(event.stream_id),
+                    sse_stream_seqs::last_seq.eq(1),
+                ))
+                .on_conflict((sse_stream_seqs::stream_kind, sse_stream_seqs::stream_id))
+                .do_update()
+                .set(sse_stream_seqs::last_seq.eq(sse_stream_seqs::last_seq + 1))
+                .returning(sse_stream_seqs::last_seq)
+                .get_result(conn)
+                .map_err(|e| {
+                    error!(
+                        "notify_event: error numbering SSE event of {} {}: {}",
+                        event.stream_kind, event.stream_id, e
+                    );
+                    RepositoryError::DatabaseError(e)
+                })?;
+            if let Some(keep) = keep {
+                store_event(conn, &event, keep)?;
+            }
+            diesel::sql_query("SELECT pg_notify($1, $2)")
+                .bind::<Text, _>(channel)
+                .bind::<Text, _>(payload(event.seq))
+                .execute(conn)
+                .map_err(|e| {
+                    error!("notify_event: error notifying channel {}: {}", channel, e);
+                    RepositoryError::DatabaseError(e)
+                })?;
+            Ok(event.seq)
+        })
+    }
+
+    /// A session of its own subscribed to `channel`, never taken from or returned to the
+    /// pool. Pass it to [`Self::unlisten`] before dropping it.
+    pub fn listen(&self, channel: &str) -> Result<PgConnection, RepositoryError> {
+        let mut conn = PgConnection::establish(&self.database_url).map_err(|e| {
+            error!("listen: failed to connect to the database: {}", e);
+            RepositoryError::InternalError(e.to_string())
+        })?;
+
+        diesel::sql_query(format!("LISTEN \"{channel}\""))
+            .execute(&mut conn)
+            .map_err(|e| {
+                error!("listen: error listening on channel {}: {}", channel, e);
+                RepositoryError::DatabaseError(e)
+            })?;
+        Ok(conn)
+    }
+
+    pub fn unlisten(conn: &mut PgConnection) -> Result<(), RepositoryError> {
+        diesel::sql_query("UNLISTEN *")
+            .execute(conn)
+            .map(|_| ())
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    /// Payloads received on a listening connection since the last call, in commit order.
+    pub fn received(conn: &mut PgConnection) -> Result<Vec<String>, RepositoryError> {
+        conn.notifications_iter()
+            .map(|notification| notification.map(|n| n.payload))
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| {
+                error!("received: error reading notifications: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+}
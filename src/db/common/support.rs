@@ -0,0 +1,549 @@
+use crate::db::common::payments::PAYMENT_STATE_COMPLETED;
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{
+    active_orders, menu_items, past_orders, payment_orders, refunds, support_messages,
+    support_tickets,
+};
+use crate::db::{AssetOperations, DbConnection, OutboxOperations, S3Error};
+use crate::enums::common::{
+    Refund, SupportAttachment, SupportMessage, SupportTicket, SupportTicketCategory,
+    SupportTicketDetail, SupportTicketStatus,
+};
+use crate::models::common::{
+    NewRefund, NewSupportMessage, NewSupportTicket, StoredRefund, StoredSupportMessage,
+    StoredSupportTicket, REFUND_STATE_FAILED,
+};
+use crate::services::outbox::OutboxMessage;
+use chrono::Utc;
+use diesel::dsl::sum;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::{DatabaseErrorKind, Error};
+use log::{error, warn};
+use uuid::Uuid;
+
+/// Most characters in one message of a ticket.
+pub const SUPPORT_MESSAGE_MAX_LEN: usize = 2000;
+/// Most attachments on one message.
+pub const SUPPORT_MESSAGE_MAX_ATTACHMENTS: usize = 5;
+
+/// Who is acting on a ticket: the user who raised it, or the staff of its canteen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupportParty {
+    User(i32),
+    Canteen(i32),
+}
+
+impl SupportParty {
+    fn author(&self) -> &'static str {
+        match self {
+            SupportParty::User(_) => "user",
+            SupportParty::Canteen(_) => "canteen",
+        }
+    }
+
+    fn can_see(&self, ticket: &StoredSupportTicket) -> bool {
+        match *self {
+            SupportParty::User(id) => ticket.user_id == id,
+            SupportParty::Canteen(id) => ticket.canteen_id == id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SupportOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    asset_ops: AssetOperations,
+}
+
+impl SupportOperations {
+    pub async fn new(
+        pool: Pool<ConnectionManager<PgConnection>>,
+        asset_ops: AssetOperations,
+    ) -> Self {
+        Self { pool, asset_ops }
+    }
+
+    /// Open a ticket about one of the user's active or past orders, with its first message.
+    pub fn create_ticket(
+        &self,
+        owner_user_id: i32,
+        about_order_id: i32,
+        ticket_category: SupportTicketCategory,
+        message: String,
+    ) -> Result<SupportTicketDetail, RepositoryError> {
+        let message = sanitize_message(message)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_ticket: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let order_canteen_id = order_canteen(conn, owner_user_id, about_order_id)?;
+                let ticket = diesel::insert_into(support_tickets::table)
+                    .values(&NewSupportTicket {
+                        user_id: owner_user_id,
+                        canteen_id: order_canteen_id,
+                        order_id: about_order_id,
+                        category: ticket_category.as_str().to_string(),
+                    })
+                    .returning(StoredSupportTicket::as_returning())
+                    .get_result::<StoredSupportTicket>(conn)
+                    .map_err(|e| open_ticket_conflict(e, about_order_id))?;
+                diesel::insert_into(support_messages::table)
+                    .values(&NewSupportMessage {
+                        ticket_id: ticket.ticket_id,
+                        author: SupportParty::User(owner_user_id).author().to_string(),
+                        body: message,
+                        attachment_keys: Vec::new(),
+                    })
+                    .execute(conn)?;
+                Ok(load_detail(conn, ticket)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "create_ticket: error opening a ticket on order {} for user_id {}: {}",
+                    about_order_id, owner_user_id, e
+                )
+            })
+    }
+
+    /// The party's tickets, most recently updated first.
+    pub fn list_tickets(
+        &self,
+        party: SupportParty,
+        with_status: Option<SupportTicketStatus>,
+    ) -> Result<Vec<SupportTicket>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("list_tickets: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let mut query = support_tickets::table.into_boxed();
+        query = match party {
+            SupportParty::User(id) => query.filter(support_tickets::user_id.eq(id)),
+            SupportParty::Canteen(id) => query.filter(support_tickets::canteen_id.eq(id)),
+        };
+        if let Some(wanted) = with_status {
+            query = query.filter(support_tickets::status.eq(wanted.as_str()));
+        }
+        let tickets = query
+            .order_by((
+                support_tickets::updated_at.desc(),
+                support_tickets::ticket_id.desc(),
+            ))
+            .select(StoredSupportTicket::as_select())
+            .load::<StoredSupportTicket>(conn.connection())
+            .map_err(|e| {
+                error!("list_tickets: error loading tickets of {:?}: {}", party, e);
+                RepositoryError::DatabaseError(e)
+            })?;
+        Ok(tickets.into_iter().map(ticket_view).collect())
+    }
+
+    /// A ticket with its messages and refunds. Attachment links are left for
+    /// [`link_attachments`](Self::link_attachments).
+    pub fn get_ticket(
+        &self,
+        party: SupportParty,
+        target_ticket_id: i32,
+    ) -> Result<SupportTicketDetail, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("get_ticket: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let conn = conn.connection();
+        let ticket = visible_ticket(conn, party, target_ticket_id, false)?;
+        load_detail(conn, ticket).map_err(|e| {
+            error!(
+                "get_ticket: error loading ticket {}: {}",
+                target_ticket_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })
+    }
+
+    /// A presigned URL to upload one attachment to, and the key to send with the message.
+    pub async fn attachment_upload_url(
+        &self,
+        party: SupportParty,
+        target_ticket_id: i32,
+    ) -> Result<(String, String), RepositoryError> {
+        {
+            let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+                error!(
+                    "attachment_upload_url: failed to acquire DB connection: {}",
+                    e
+                );
+                e
+            })?;
+            let ticket = visible_ticket(conn.connection(), party, target_ticket_id, false)?;
+            ensure_not_closed(&ticket)?;
+        }
+        let key = format!("support/{}/{}", target_ticket_id, Uuid::now_v7());
+        let url = self.asset_ops.get_upload_presign_url(&key).await?;
+        Ok((url, key))
+    }
+
+    /// Add a message to the thread. A reply from the canteen takes an open ticket in
+    /// progress; a message from the user reopens a resolved one.
+    pub async fn add_message(
+        &self,
+        party: SupportParty,
+        target_ticket_id: i32,
+        message: String,
+        attachment_keys: Vec<String>,
+    ) -> Result<SupportTicketDetail, RepositoryError> {
+        let message = sanitize_message(message)?;
+        if attachment_keys.len() > SUPPORT_MESSAGE_MAX_ATTACHMENTS {
+            return Err(RepositoryError::ValidationError(format!(
+                "a message can have at most {SUPPORT_MESSAGE_MAX_ATTACHMENTS} attachments"
+            )));
+        }
+        let prefix = format!("support/{target_ticket_id}/");
+        for key in &attachment_keys {
+            if !key.starts_with(&prefix) || key.len() == prefix.len() {
+                return Err(RepositoryError::ValidationError(format!(
+                    "attachment {key} does not belong to ticket {target_ticket_id}"
+                )));
+            }
+            match self.asset_ops.get_object_etag(key).await {
+                Ok(_) => {}
+                Err(S3Error::NotFound(_)) => {
+                    return Err(RepositoryError::ValidationError(format!(
+                        "attachment {key} has not been uploaded"
+                    )))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("add_message: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let ticket = visible_ticket(conn, party, target_ticket_id, true)?;
+                ensure_not_closed(&ticket)?;
+                diesel::insert_into(support_messages::table)
+                    .values(&NewSupportMessage {
+                        ticket_id: ticket.ticket_id,
+                        author: party.author().to_string(),
+                        body: message,
+                        attachment_keys,
+                    })
+                    .execute(conn)?;
+                let current = SupportTicketStatus::parse(&ticket.status);
+                let next = match (party, current) {
+                    (SupportParty::Canteen(_), Some(SupportTicketStatus::Open)) => {
+                        SupportTicketStatus::InProgress
+                    }
+                    (SupportParty::User(_), Some(SupportTicketStatus::Resolved)) => {
+                        SupportTicketStatus::Open
+                    }
+                    _ => current.unwrap_or(SupportTicketStatus::Open),
+                };
+                let ticket = set_status(conn, &ticket, next)?;
+                Ok(load_detail(conn, ticket)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "add_message: error adding a message to ticket {}: {}",
+                    target_ticket_id, e
+                )
+            })
+    }
+
+    /// Move a ticket of the canteen to another status. Resolving it can refund part or all
+    /// of the order's online payment, `refund_amount` in paisa; the refund is sent to
+    /// PhonePe through the outbox.
+    pub fn update_status(
+        &self,
+        owner_canteen_id: i32,
+        target_ticket_id: i32,
+        new_status: SupportTicketStatus,
+        refund_amount: Option<i32>,
+    ) -> Result<SupportTicketDetail, RepositoryError> {
+        if let Some(amount) = refund_amount {
+            if new_status != SupportTicketStatus::Resolved {
+                return Err(RepositoryError::ValidationError(
+                    "refunds can only be issued when resolving a ticket".to_string(),
+                ));
+            }
+            if amount <= 0 {
+                return Err(RepositoryError::ValidationError(
+                    "refund_amount must be positive".to_string(),
+                ));
+            }
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("update_status: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let ticket = visible_ticket(
+                    conn,
+                    SupportParty::Canteen(owner_canteen_id),
+                    target_ticket_id,
+                    true,
+                )?;
+                ensure_not_closed(&ticket)?;
+                if let Some(amount) = refund_amount {
+                    let refund_id = issue_refund(conn, &ticket, amount)?;
+                    OutboxOperations::enqueue(conn, &[OutboxMessage::Refund { refund_id }])?;
+                }
+                let ticket = set_status(conn, &ticket, new_status)?;
+                Ok(load_detail(conn, ticket)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "update_status: error moving ticket {} of canteen {} to {}: {}",
+                    target_ticket_id,
+                    owner_canteen_id,
+                    new_status.as_str(),
+                    e
+                )
+            })
+    }
+
+    /// Fill in download links of the ticket's attachments. Attachments that cannot be linked
+    /// keep no URL.
+    pub async fn link_attachments(&self, detail: &mut SupportTicketDetail) {
+        for attachment in detail
+            .messages
+            .iter_mut()
+            .flat_map(|message| message.attachments.iter_mut())
+        {
+            match self.asset_ops.get_object_presign(&attachment.key).await {
+                Ok(url) => attachment.url = Some(url),
+                Err(e) => warn!(
+                    "link_attachments: cannot link attachment {}: {}",
+                    attachment.key, e
+                ),
+            }
+        }
+    }
+}
+
+fn sanitize_message(message: String) -> Result<String, RepositoryError> {
+    let message = message.trim().to_string();
+    if message.is_empty() {
+        return Err(RepositoryError::ValidationError(
+            "message must not be empty".to_string(),
+        ));
+    }
+    if message.chars().count() > SUPPORT_MESSAGE_MAX_LEN {
+        return Err(RepositoryError::ValidationError(format!(
+            "message must be at most {SUPPORT_MESSAGE_MAX_LEN} characters"
+        )));
+    }
+    Ok(message)
+}
+
+fn open_ticket_conflict(e: Error, about_order_id: i32) -> RepositoryError {
+    match e {
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            RepositoryError::ValidationError(format!(
+                "order {about_order_id} already has an open ticket"
+            ))
+        }
+        other => RepositoryError::DatabaseError(other),
+    }
+}
+
+fn ensure_not_closed(ticket: &StoredSupportTicket) -> Result<(), RepositoryError> {
+    if ticket.status == SupportTicketStatus::Closed.as_str() {
+        return Err(RepositoryError::ValidationError(
+            "ticket is closed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The canteen of the user's active or past order.
+fn order_canteen(
+    conn: &mut PgConnection,
+    owner_user_id: i32,
+    about_order_id: i32,
+) -> Result<i32, RepositoryError> {
+    if let Some(active_canteen_id) = active_orders::table
+        .filter(active_orders::order_id.eq(about_order_id))
+        .filter(active_orders::user_id.eq(owner_user_id))
+        .select(active_orders::canteen_id)
+        .first::<i32>(conn)
+        .optional()?
+    {
+        return Ok(active_canteen_id);
+    }
+    let ordered_items = past_orders::table
+        .filter(past_orders::order_id.eq(about_order_id))
+        .filter(past_orders::user_id.eq(owner_user_id))
+        .select(past_orders::items)
+        .first::<Vec<Option<i32>>>(conn)
+        .optional()?
+        .ok_or_else(|| RepositoryError::NotFound(format!("orders: {about_order_id}")))?;
+    menu_items::table
+        .filter(menu_items::item_id.eq_any(ordered_items.into_iter().flatten()))
+        .select(menu_items::canteen_id)
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            RepositoryError::ValidationError(format!(
+                "the items of order {about_order_id} are no longer on the menu"
+            ))
+        })
+}
+
+/// The ticket, if the party may see it; locked for update when `lock` is set.
+fn visible_ticket(
+    conn: &mut PgConnection,
+    party: SupportParty,
+    target_ticket_id: i32,
+    lock: bool,
+) -> Result<StoredSupportTicket, RepositoryError> {
+    let query = support_tickets::table
+        .find(target_ticket_id)
+        .select(StoredSupportTicket::as_select());
+    let ticket = if lock {
+        query.for_update().first::<StoredSupportTicket>(conn)
+    } else {
+        query.first::<StoredSupportTicket>(conn)
+    }
+    .optional()?
+    .filter(|ticket| party.can_see(ticket))
+    .ok_or_else(|| RepositoryError::NotFound(format!("support_tickets: {target_ticket_id}")))?;
+    Ok(ticket)
+}
+
+fn set_status(
+    conn: &mut PgConnection,
+    ticket: &StoredSupportTicket,
+    new_status: SupportTicketStatus,
+) -> Result<StoredSupportTicket, RepositoryError> {
+    let now = Utc::now();
+    let resolved_at = match new_status {
+        SupportTicketStatus::Resolved => Some(now),
+        SupportTicketStatus::Closed => ticket.resolved_at,
+        SupportTicketStatus::Open | SupportTicketStatus::InProgress => None,
+    };
+    diesel::update(support_tickets::table.find(ticket.ticket_id))
+        .set((
+            support_tickets::status.eq(new_status.as_str()),
+            support_tickets::updated_at.eq(now),
+            support_tickets::resolved_at.eq(resolved_at),
+        ))
+        .returning(StoredSupportTicket::as_returning())
+        .get_result::<StoredSupportTicket>(conn)
+        .map_err(|e| open_ticket_conflict(e, ticket.order_id))
+}
+
+/// Record a refund of `amount` paisa against the order's completed payment. Refunds that
+/// have not failed count towards what the payment still allows.
+fn issue_refund(
+    conn: &mut PgConnection,
+    ticket: &StoredSupportTicket,
+    amount: i32,
+) -> Result<i32, RepositoryError> {
+    let (paid_payment_id, paid_amount) = payment_orders::table
+        .filter(payment_orders::app_order_id.eq(ticket.order_id))
+        .filter(payment_orders::user_id.eq(ticket.user_id))
+        .filter(payment_orders::payment_state.eq(PAYMENT_STATE_COMPLETED))
+        .select((payment_orders::payment_id, payment_orders::amount))
+        .for_update()
+        .first::<(i32, i32)>(conn)
+        .optional()?
+        .ok_or_else(|| {
+            RepositoryError::ValidationError(format!(
+                "order {} has no completed online payment to refund",
+                ticket.order_id
+            ))
+        })?;
+    let refunded = refunds::table
+        .filter(refunds::payment_id.eq(paid_payment_id))
+        .filter(refunds::state.ne(REFUND_STATE_FAILED))
+        .select(sum(refunds::amount))
+        .first::<Option<i64>>(conn)?
+        .unwrap_or(0);
+    let refundable = i64::from(paid_amount) - refunded;
+    if i64::from(amount) > refundable {
+        return Err(RepositoryError::ValidationError(format!(
+            "at most {refundable} paisa of the payment can still be refunded"
+        )));
+    }
+    let new_refund_id = diesel::insert_into(refunds::table)
+        .values(&NewRefund {
+            payment_id: paid_payment_id,
+            canteen_id: ticket.canteen_id,
+            ticket_id: Some(ticket.ticket_id),
+            merchant_refund_id: format!(
+                "RFD_{}_{}",
+                ticket.ticket_id,
+                Utc::now().timestamp_millis()
+            ),
+            amount,
+        })
+        .returning(refunds::refund_id)
+        .get_result::<i32>(conn)?;
+    Ok(new_refund_id)
+}
+
+fn ticket_view(ticket: StoredSupportTicket) -> SupportTicket {
+    SupportTicket {
+        ticket_id: ticket.ticket_id,
+        user_id: ticket.user_id,
+        canteen_id: ticket.canteen_id,
+        order_id: ticket.order_id,
+        // Both are constrained to these values by the table.
+        category: SupportTicketCategory::parse(&ticket.category)
+            .unwrap_or(SupportTicketCategory::Other),
+        status: SupportTicketStatus::parse(&ticket.status).unwrap_or(SupportTicketStatus::Open),
+        created_at: ticket.created_at,
+        updated_at: ticket.updated_at,
+        resolved_at: ticket.resolved_at,
+    }
+}
+
+fn load_detail(
+    conn: &mut PgConnection,
+    ticket: StoredSupportTicket,
+) -> Result<SupportTicketDetail, Error> {
+    let messages = support_messages::table
+        .filter(support_messages::ticket_id.eq(ticket.ticket_id))
+        .order_by((support_messages::created_at, support_messages::message_id))
+        .select(StoredSupportMessage::as_select())
+        .load::<StoredSupportMessage>(conn)?;
+    let ticket_refunds = refunds::table
+        .filter(refunds::ticket_id.eq(ticket.ticket_id))
+        .order_by(refunds::refund_id)
+        .select(StoredRefund::as_select())
+        .load::<StoredRefund>(conn)?;
+    Ok(SupportTicketDetail {
+        ticket: ticket_view(ticket),
+        messages: messages
+            .into_iter()
+            .map(|message| SupportMessage {
+                message_id: message.message_id,
+                author: message.author,
+                body: message.body,
+                attachments: message
+                    .attachment_keys
+                    .into_iter()
+                    .map(|key| SupportAttachment { key, url: None })
+                    .collect(),
+                created_at: message.created_at,
+            })
+            .collect(),
+        refunds: ticket_refunds
+            .into_iter()
+            .map(|refund| Refund {
+                refund_id: refund.refund_id,
+                amount: refund.amount,
+                state: refund.state,
+                failure_reason: refund.failure_reason,
+                created_at: refund.created_at,
+                updated_at: refund.updated_at,
+            })
+            .collect(),
+    })
+}
@@ -7,16 +7,34 @@ mod errors;
 pub mod schema;
 mod users;
 
+pub use admin::analytics::{AnalyticsOperations, ReportRange};
 pub use admin::asset_management::AssetOperations;
 pub use admin::canteen::CanteenHoursState;
 pub use admin::canteen::CanteenOperations;
+pub use admin::canteen::CanteenStockResetState;
+pub use admin::exports::ExportOperations;
 pub use admin::menu::MenuOperations;
+pub use admin::menu::ScheduledItemState;
+pub use admin::pricing::PricingOperations;
+pub use admin::promo::{PromoOperations, PromoScope};
+pub use admin::settlements::{SettlementOperations, PAYOUT_REFERENCE_MAX_LEN};
+pub use admin::webhooks::{DueWebhookDelivery, WebhookOperations};
 pub use common::hold::HoldOperations;
 pub use common::orders::{OrderOperations, QrGenerationLookup, QrScanLookup};
+pub use common::outbox::OutboxOperations;
 pub use common::payments::PaymentOperations;
-pub use common::search::SearchOperations;
+pub use common::recommendations::RecommendationOperations;
+pub use common::reviews::{ReviewOperations, REVIEW_WINDOW_DAYS};
+pub use common::search::{
+    SearchOperations, SEARCH_INSIGHTS_DEFAULT_DAYS, SEARCH_INSIGHTS_DEFAULT_LIMIT,
+};
+pub use common::sse_events::{SseEventLogOperations, SseNotifyOperations};
+pub use common::support::{SupportOperations, SupportParty};
 pub use errors::RepositoryError;
 pub use errors::S3Error;
+pub use users::cart::CartOperations;
+pub use users::devices::DeviceOperations;
+pub use users::favourites::FavouriteOperations;
 pub use users::user::UserOperations;
 
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
@@ -1,6 +1,10 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "pricing_rule_kind"))]
+    pub struct PricingRuleKind;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "time_band"))]
     pub struct TimeBand;
@@ -26,6 +30,20 @@ diesel::table! {
         ordered_at -> Timestamptz,
         canteen_id -> Int4,
         deliver_at -> Nullable<TimeBand>,
+        note -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    canteen_webhooks (webhook_id) {
+        webhook_id -> Int4,
+        canteen_id -> Int4,
+        url -> Varchar,
+        secret -> Varchar,
+        events -> Array<Text>,
+        is_active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -42,6 +60,47 @@ diesel::table! {
         is_open -> Bool,
         last_opened_at -> Nullable<Timestamptz>,
         pic_key -> Nullable<Varchar>,
+        last_stock_reset_on -> Nullable<Date>,
+        average_rating -> Nullable<Float8>,
+        rating_count -> Int4,
+    }
+}
+
+diesel::table! {
+    cart_items (user_id, canteen_id, item_id) {
+        user_id -> Int4,
+        canteen_id -> Int4,
+        item_id -> Int4,
+        quantity -> Int2,
+        added_price -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    device_tokens (token) {
+        token -> Varchar,
+        user_id -> Int4,
+        platform -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    favourite_canteens (user_id, canteen_id) {
+        user_id -> Int4,
+        canteen_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    favourite_items (user_id, item_id) {
+        user_id -> Int4,
+        item_id -> Int4,
+        created_at -> Timestamptz,
     }
 }
 
@@ -66,6 +125,64 @@ diesel::table! {
         deliver_at -> Nullable<TimeBand>,
         held_at -> Timestamptz,
         expires_at -> Timestamptz,
+        note -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    hold_history (hold_id) {
+        hold_id -> Int4,
+        user_id -> Int4,
+        canteen_id -> Int4,
+        total_price -> Int4,
+        held_at -> Timestamptz,
+        outcome -> Varchar,
+        order_id -> Nullable<Int4>,
+        ended_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    item_hourly_sales (item_id, sold_on, hour) {
+        item_id -> Int4,
+        sold_on -> Date,
+        hour -> Int2,
+        quantity -> Int4,
+    }
+}
+
+diesel::table! {
+    item_pair_stats (item_id, paired_item_id) {
+        item_id -> Int4,
+        paired_item_id -> Int4,
+        order_count -> Int4,
+    }
+}
+
+diesel::table! {
+    item_ratings (review_id, item_id) {
+        review_id -> Int4,
+        item_id -> Int4,
+        rating -> Int2,
+    }
+}
+
+diesel::table! {
+    menu_categories (category_id) {
+        category_id -> Int4,
+        canteen_id -> Int4,
+        name -> Varchar,
+        display_order -> Int4,
+    }
+}
+
+diesel::table! {
+    menu_item_schedules (schedule_id) {
+        schedule_id -> Int4,
+        item_id -> Int4,
+        weekday -> Int2,
+        start_time -> Time,
+        end_time -> Time,
     }
 }
 
@@ -81,10 +198,60 @@ diesel::table! {
         description -> Nullable<Varchar>,
         pic_etag -> Nullable<Varchar>,
         pic_key -> Nullable<Varchar>,
+        default_stock -> Nullable<Int4>,
+        category_id -> Nullable<Int4>,
+        display_order -> Int4,
+        is_pinned -> Bool,
+        tags -> Array<Text>,
+        allergens -> Array<Text>,
+        diet_labels -> Array<Text>,
+        average_rating -> Nullable<Float8>,
+        rating_count -> Int4,
+        manually_disabled -> Bool,
+    }
+}
+
+diesel::table! {
+    order_reviews (review_id) {
+        review_id -> Int4,
+        order_id -> Int4,
+        user_id -> Int4,
+        canteen_id -> Int4,
+        rating -> Int2,
+        comment -> Nullable<Text>,
+        is_hidden -> Bool,
+        reply -> Nullable<Text>,
+        replied_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    outbox (outbox_id) {
+        outbox_id -> Int8,
+        topic -> Varchar,
+        payload -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        failed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    past_order_items (order_id, item_id) {
+        order_id -> Int4,
+        item_id -> Int4,
+        quantity -> Int2,
+        price -> Int4,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TimeBand;
+
     past_orders (order_id) {
         order_id -> Int4,
         user_id -> Int4,
@@ -92,6 +259,8 @@ diesel::table! {
         price -> Int4,
         order_status -> Bool,
         ordered_at -> Timestamptz,
+        canteen_id -> Nullable<Int4>,
+        deliver_at -> Nullable<TimeBand>,
     }
 }
 
@@ -109,6 +278,184 @@ diesel::table! {
         app_order_id -> Nullable<Int4>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        canteen_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    pricing_rule_windows (window_id) {
+        window_id -> Int4,
+        rule_id -> Int4,
+        weekday -> Int2,
+        start_time -> Time,
+        end_time -> Time,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PricingRuleKind;
+
+    pricing_rules (rule_id) {
+        rule_id -> Int4,
+        canteen_id -> Int4,
+        name -> Varchar,
+        kind -> PricingRuleKind,
+        percent_off -> Nullable<Int4>,
+        amount_off -> Nullable<Int4>,
+        combo_price -> Nullable<Int4>,
+        item_ids -> Array<Int4>,
+        is_active -> Bool,
+    }
+}
+
+diesel::table! {
+    promo_codes (promo_id) {
+        promo_id -> Int4,
+        code -> Varchar,
+        canteen_id -> Nullable<Int4>,
+        description -> Nullable<Varchar>,
+        percent_off -> Nullable<Int4>,
+        amount_off -> Nullable<Int4>,
+        max_discount -> Nullable<Int4>,
+        min_order_total -> Int4,
+        valid_from -> Nullable<Timestamptz>,
+        valid_until -> Nullable<Timestamptz>,
+        max_uses_total -> Nullable<Int4>,
+        max_uses_per_user -> Nullable<Int4>,
+        first_order_only -> Bool,
+        is_active -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    promo_redemptions (redemption_id) {
+        redemption_id -> Int4,
+        promo_id -> Int4,
+        user_id -> Int4,
+        hold_id -> Nullable<Int4>,
+        order_id -> Nullable<Int4>,
+        discount -> Int4,
+        redeemed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    refunds (refund_id) {
+        refund_id -> Int4,
+        payment_id -> Int4,
+        canteen_id -> Int4,
+        ticket_id -> Nullable<Int4>,
+        merchant_refund_id -> Varchar,
+        amount -> Int4,
+        state -> Varchar,
+        phonepe_refund_id -> Nullable<Varchar>,
+        failure_reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    saved_cart_items (cart_id, item_id) {
+        cart_id -> Int4,
+        item_id -> Int4,
+        quantity -> Int2,
+    }
+}
+
+diesel::table! {
+    saved_carts (cart_id) {
+        cart_id -> Int4,
+        user_id -> Int4,
+        canteen_id -> Int4,
+        name -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    search_query_stats (stat_id) {
+        stat_id -> Int8,
+        searched_on -> Date,
+        query -> Varchar,
+        canteen_id -> Nullable<Int4>,
+        search_count -> Int4,
+        no_result_count -> Int4,
+    }
+}
+
+diesel::table! {
+    settlements (settlement_id) {
+        settlement_id -> Int4,
+        canteen_id -> Int4,
+        business_date -> Date,
+        payment_count -> Int4,
+        gross -> Int8,
+        refund_count -> Int4,
+        refunds -> Int8,
+        commission_bps -> Int4,
+        commission -> Int8,
+        net_payable -> Int8,
+        generated_at -> Timestamptz,
+        paid_at -> Nullable<Timestamptz>,
+        payout_reference -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    sse_events (stream_kind, stream_id, seq) {
+        stream_kind -> Varchar,
+        stream_id -> Int4,
+        seq -> Int8,
+        event_name -> Varchar,
+        data -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    sse_stream_seqs (stream_kind, stream_id) {
+        stream_kind -> Varchar,
+        stream_id -> Int4,
+        last_seq -> Int8,
+    }
+}
+
+diesel::table! {
+    support_messages (message_id) {
+        message_id -> Int4,
+        ticket_id -> Int4,
+        author -> Varchar,
+        body -> Text,
+        attachment_keys -> Array<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    support_tickets (ticket_id) {
+        ticket_id -> Int4,
+        user_id -> Int4,
+        canteen_id -> Int4,
+        order_id -> Int4,
+        category -> Varchar,
+        status -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    user_item_stats (user_id, item_id) {
+        user_id -> Int4,
+        item_id -> Int4,
+        order_count -> Int4,
+        quantity -> Int4,
+        last_ordered_at -> Timestamptz,
     }
 }
 
@@ -124,6 +471,24 @@ diesel::table! {
         email_verified -> Bool,
         display_name -> Nullable<Text>,
         photo_url -> Nullable<Text>,
+        allergen_exclusions -> Array<Text>,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (delivery_id) {
+        delivery_id -> Int8,
+        webhook_id -> Int4,
+        event_id -> Varchar,
+        event_type -> Varchar,
+        payload -> Text,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_status_code -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -131,22 +496,95 @@ diesel::joinable!(active_order_items -> active_orders (order_id));
 diesel::joinable!(active_order_items -> menu_items (item_id));
 diesel::joinable!(active_orders -> canteens (canteen_id));
 diesel::joinable!(active_orders -> users (user_id));
+diesel::joinable!(canteen_webhooks -> canteens (canteen_id));
+diesel::joinable!(cart_items -> canteens (canteen_id));
+diesel::joinable!(cart_items -> menu_items (item_id));
+diesel::joinable!(cart_items -> users (user_id));
+diesel::joinable!(device_tokens -> users (user_id));
+diesel::joinable!(favourite_canteens -> canteens (canteen_id));
+diesel::joinable!(favourite_canteens -> users (user_id));
+diesel::joinable!(favourite_items -> menu_items (item_id));
+diesel::joinable!(favourite_items -> users (user_id));
 diesel::joinable!(held_order_items -> held_orders (hold_id));
 diesel::joinable!(held_order_items -> menu_items (item_id));
 diesel::joinable!(held_orders -> canteens (canteen_id));
 diesel::joinable!(held_orders -> users (user_id));
+diesel::joinable!(hold_history -> canteens (canteen_id));
+diesel::joinable!(hold_history -> users (user_id));
+diesel::joinable!(item_hourly_sales -> menu_items (item_id));
+diesel::joinable!(item_ratings -> menu_items (item_id));
+diesel::joinable!(item_ratings -> order_reviews (review_id));
+diesel::joinable!(menu_categories -> canteens (canteen_id));
+diesel::joinable!(menu_item_schedules -> menu_items (item_id));
 diesel::joinable!(menu_items -> canteens (canteen_id));
+diesel::joinable!(menu_items -> menu_categories (category_id));
+diesel::joinable!(order_reviews -> canteens (canteen_id));
+diesel::joinable!(order_reviews -> past_orders (order_id));
+diesel::joinable!(order_reviews -> users (user_id));
+diesel::joinable!(past_order_items -> past_orders (order_id));
+diesel::joinable!(past_orders -> canteens (canteen_id));
 diesel::joinable!(past_orders -> users (user_id));
+diesel::joinable!(payment_orders -> canteens (canteen_id));
 diesel::joinable!(payment_orders -> users (user_id));
+diesel::joinable!(pricing_rule_windows -> pricing_rules (rule_id));
+diesel::joinable!(pricing_rules -> canteens (canteen_id));
+diesel::joinable!(promo_codes -> canteens (canteen_id));
+diesel::joinable!(promo_redemptions -> held_orders (hold_id));
+diesel::joinable!(promo_redemptions -> promo_codes (promo_id));
+diesel::joinable!(promo_redemptions -> users (user_id));
+diesel::joinable!(refunds -> canteens (canteen_id));
+diesel::joinable!(refunds -> payment_orders (payment_id));
+diesel::joinable!(refunds -> support_tickets (ticket_id));
+diesel::joinable!(saved_cart_items -> menu_items (item_id));
+diesel::joinable!(saved_cart_items -> saved_carts (cart_id));
+diesel::joinable!(saved_carts -> canteens (canteen_id));
+diesel::joinable!(saved_carts -> users (user_id));
+diesel::joinable!(search_query_stats -> canteens (canteen_id));
+diesel::joinable!(settlements -> canteens (canteen_id));
+diesel::joinable!(support_messages -> support_tickets (ticket_id));
+diesel::joinable!(support_tickets -> canteens (canteen_id));
+diesel::joinable!(support_tickets -> users (user_id));
+diesel::joinable!(user_item_stats -> menu_items (item_id));
+diesel::joinable!(user_item_stats -> users (user_id));
+diesel::joinable!(webhook_deliveries -> canteen_webhooks (webhook_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     active_order_items,
     active_orders,
+    canteen_webhooks,
     canteens,
+    cart_items,
+    device_tokens,
+    favourite_canteens,
+    favourite_items,
     held_order_items,
     held_orders,
+    hold_history,
+    item_hourly_sales,
+    item_pair_stats,
+    item_ratings,
+    menu_categories,
+    menu_item_schedules,
     menu_items,
+    order_reviews,
+    outbox,
+    past_order_items,
     past_orders,
     payment_orders,
+    pricing_rule_windows,
+    pricing_rules,
+    promo_codes,
+    promo_redemptions,
+    refunds,
+    saved_cart_items,
+    saved_carts,
+    search_query_stats,
+    settlements,
+    sse_events,
+    sse_stream_seqs,
+    support_messages,
+    support_tickets,
+    user_item_stats,
     users,
+    webhook_deliveries,
 );
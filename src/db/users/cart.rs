@@ -0,0 +1,337 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{cart_items, menu_items};
+use crate::db::DbConnection;
+use crate::enums::users::{Cart, CartLine, CartLineStatus};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::Error;
+use diesel::upsert::excluded;
+use log::error;
+
+/// Most units of one item a cart line can hold.
+const CART_LINE_MAX_QUANTITY: i16 = 20;
+/// Most distinct items a cart can hold.
+const CART_MAX_LINES: i64 = 50;
+
+#[derive(Queryable, Debug)]
+struct CartRow {
+    item_id: i32,
+    name: String,
+    quantity: i16,
+    added_price: i32,
+    updated_at: DateTime<Utc>,
+    price: i32,
+    stock: i32,
+    is_available: bool,
+}
+
+#[derive(Clone)]
+pub struct CartOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl CartOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// The user's cart at the canteen, each line checked against the current menu.
+    pub fn get_cart(
+        &self,
+        owner_user_id: i32,
+        cart_canteen_id: i32,
+    ) -> Result<Cart, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_cart: failed to acquire DB connection for user_id {}: {}",
+                owner_user_id, e
+            );
+            e
+        })?;
+        load_cart(conn.connection(), owner_user_id, cart_canteen_id).map_err(|e| {
+            error!(
+                "get_cart: error loading cart of user_id {} at canteen {}: {}",
+                owner_user_id, cart_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })
+    }
+
+    /// Add `quantity` units of the item to the cart. Only items that can be ordered in the
+    /// resulting quantity are added.
+    pub fn add_item(
+        &self,
+        owner_user_id: i32,
+        cart_canteen_id: i32,
+        target_item_id: i32,
+        quantity: i16,
+    ) -> Result<Cart, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("add_item: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let in_cart = cart_items::table
+                    .find((owner_user_id, cart_canteen_id, target_item_id))
+                    .select(cart_items::quantity)
+                    .for_update()
+                    .first::<i16>(conn)
+                    .optional()?;
+                if in_cart.is_none() {
+                    let lines = cart_items::table
+                        .filter(cart_items::user_id.eq(owner_user_id))
+                        .filter(cart_items::canteen_id.eq(cart_canteen_id))
+                        .count()
+                        .get_result::<i64>(conn)?;
+                    if lines >= CART_MAX_LINES {
+                        return Err(RepositoryError::ValidationError(format!(
+                            "a cart can hold at most {CART_MAX_LINES} different items"
+                        )));
+                    }
+                }
+                let new_quantity = in_cart.unwrap_or(0).saturating_add(quantity);
+                let price = orderable_price(conn, cart_canteen_id, target_item_id, new_quantity)?;
+                diesel::insert_into(cart_items::table)
+                    .values((
+                        cart_items::user_id.eq(owner_user_id),
+                        cart_items::canteen_id.eq(cart_canteen_id),
+                        cart_items::item_id.eq(target_item_id),
+                        cart_items::quantity.eq(new_quantity),
+                        cart_items::added_price.eq(price),
+                    ))
+                    .on_conflict((
+                        cart_items::user_id,
+                        cart_items::canteen_id,
+                        cart_items::item_id,
+                    ))
+                    .do_update()
+                    .set((
+                        cart_items::quantity.eq(excluded(cart_items::quantity)),
+                        cart_items::added_price.eq(excluded(cart_items::added_price)),
+                        cart_items::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .execute(conn)?;
+                Ok(load_cart(conn, owner_user_id, cart_canteen_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "add_item: error adding item {} to the cart of user_id {} at canteen {}: {}",
+                    target_item_id, owner_user_id, cart_canteen_id, e
+                )
+            })
+    }
+
+    /// Change how many units of an item are in the cart; zero removes the item.
+    pub fn set_quantity(
+        &self,
+        owner_user_id: i32,
+        cart_canteen_id: i32,
+        target_item_id: i32,
+        quantity: i16,
+    ) -> Result<Cart, RepositoryError> {
+        if quantity == 0 {
+            return self.remove_item(owner_user_id, cart_canteen_id, target_item_id);
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("set_quantity: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let price = orderable_price(conn, cart_canteen_id, target_item_id, quantity)?;
+                let updated = diesel::update(
+                    cart_items::table.find((owner_user_id, cart_canteen_id, target_item_id)),
+                )
+                .set((
+                    cart_items::quantity.eq(quantity),
+                    cart_items::added_price.eq(price),
+                    cart_items::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+                if updated == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "cart_items: {target_item_id}"
+                    )));
+                }
+                Ok(load_cart(conn, owner_user_id, cart_canteen_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "set_quantity: error updating item {} in the cart of user_id {} at canteen {}: {}",
+                    target_item_id, owner_user_id, cart_canteen_id, e
+                )
+            })
+    }
+
+    pub fn remove_item(
+        &self,
+        owner_user_id: i32,
+        cart_canteen_id: i32,
+        target_item_id: i32,
+    ) -> Result<Cart, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("remove_item: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let removed = diesel::delete(
+                    cart_items::table.find((owner_user_id, cart_canteen_id, target_item_id)),
+                )
+                .execute(conn)?;
+                if removed == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "cart_items: {target_item_id}"
+                    )));
+                }
+                Ok(load_cart(conn, owner_user_id, cart_canteen_id)?)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "remove_item: error removing item {} from the cart of user_id {} at canteen {}: {}",
+                    target_item_id, owner_user_id, cart_canteen_id, e
+                )
+            })
+    }
+
+    pub fn clear_cart(
+        &self,
+        owner_user_id: i32,
+        cart_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("clear_cart: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        diesel::delete(
+            cart_items::table
+                .filter(cart_items::user_id.eq(owner_user_id))
+                .filter(cart_items::canteen_id.eq(cart_canteen_id)),
+        )
+        .execute(conn.connection())
+        .map(|_| ())
+        .map_err(|e| {
+            error!(
+                "clear_cart: error clearing the cart of user_id {} at canteen {}: {}",
+                owner_user_id, cart_canteen_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })
+    }
+}
+
+/// The item's current price, if it is on the canteen's menu and `quantity` of it can be
+/// ordered now.
+fn orderable_price(
+    conn: &mut PgConnection,
+    cart_canteen_id: i32,
+    target_item_id: i32,
+    quantity: i16,
+) -> Result<i32, RepositoryError> {
+    if !(1..=CART_LINE_MAX_QUANTITY).contains(&quantity) {
+        return Err(RepositoryError::ValidationError(format!(
+            "quantity must be 1 to {CART_LINE_MAX_QUANTITY}"
+        )));
+    }
+    let (item_canteen_id, item_name, price, stock, is_available) = menu_items::table
+        .find(target_item_id)
+        .select((
+            menu_items::canteen_id,
+            menu_items::name,
+            menu_items::price,
+            menu_items::stock,
+            menu_items::is_available,
+        ))
+        .first::<(i32, String, i32, i32, bool)>(conn)
+        .map_err(|e| match e {
+            Error::NotFound => RepositoryError::NotFound(format!("menu_items: {target_item_id}")),
+            other => RepositoryError::DatabaseError(other),
+        })?;
+    if item_canteen_id != cart_canteen_id {
+        return Err(RepositoryError::ValidationError(format!(
+            "item {target_item_id} is not on the menu of canteen {cart_canteen_id}"
+        )));
+    }
+    if !is_available {
+        return Err(RepositoryError::NotAvailable(
+            target_item_id,
+            item_name,
+            "Not available".to_string(),
+        ));
+    }
+    if stock != -1 && stock < i32::from(quantity) {
+        return Err(RepositoryError::NotAvailable(
+            target_item_id,
+            item_name,
+            "Out of stock".to_string(),
+        ));
+    }
+    Ok(price)
+}
+
+/// The cart with every line checked against the current menu.
+pub(crate) fn load_cart(
+    conn: &mut PgConnection,
+    owner_user_id: i32,
+    cart_canteen_id: i32,
+) -> Result<Cart, Error> {
+    let rows = cart_items::table
+        .inner_join(menu_items::table)
+        .filter(cart_items::user_id.eq(owner_user_id))
+        .filter(cart_items::canteen_id.eq(cart_canteen_id))
+        .order_by((cart_items::created_at.asc(), cart_items::item_id.asc()))
+        .select((
+            cart_items::item_id,
+            menu_items::name,
+            cart_items::quantity,
+            cart_items::added_price,
+            cart_items::updated_at,
+            menu_items::price,
+            menu_items::stock,
+            menu_items::is_available,
+        ))
+        .load::<CartRow>(conn)?;
+
+    let updated_at = rows.iter().map(|row| row.updated_at).max();
+    let lines = rows
+        .into_iter()
+        .map(|row| {
+            let status = if !row.is_available {
+                CartLineStatus::Unavailable
+            } else if row.stock != -1 && row.stock < i32::from(row.quantity) {
+                CartLineStatus::InsufficientStock
+            } else if row.price != row.added_price {
+                CartLineStatus::PriceChanged
+            } else {
+                CartLineStatus::Ok
+            };
+            CartLine {
+                item_id: row.item_id,
+                name: row.name,
+                quantity: row.quantity,
+                unit_price: row.price,
+                added_price: row.added_price,
+                available_stock: (row.stock != -1).then_some(row.stock),
+                status,
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(Cart {
+        canteen_id: cart_canteen_id,
+        total_price: lines
+            .iter()
+            .map(|line| line.unit_price * i32::from(line.quantity))
+            .sum(),
+        is_orderable: !lines.is_empty()
+            && lines.iter().all(|line| {
+                matches!(
+                    line.status,
+                    CartLineStatus::Ok | CartLineStatus::PriceChanged
+                )
+            }),
+        lines,
+        updated_at,
+    })
+}
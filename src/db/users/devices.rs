@@ -0,0 +1,139 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::device_tokens;
+use crate::db::DbConnection;
+use crate::models::user::{DeviceToken, DEVICE_PLATFORMS};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::upsert::excluded;
+use log::error;
+
+/// FCM registration tokens are well under this; anything longer is not a token.
+const DEVICE_TOKEN_MAX_LEN: usize = 4096;
+
+#[derive(Clone)]
+pub struct DeviceOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl DeviceOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self { pool }
+    }
+
+    /// Register the device for pushes to `owner_user_id`, taking the token over from any
+    /// user who registered it before.
+    pub fn register_device(
+        &self,
+        owner_user_id: i32,
+        raw_token: &str,
+        raw_platform: &str,
+    ) -> Result<DeviceToken, RepositoryError> {
+        let token = raw_token.trim();
+        if token.is_empty() || token.len() > DEVICE_TOKEN_MAX_LEN {
+            return Err(RepositoryError::ValidationError(
+                "device token is not valid".to_string(),
+            ));
+        }
+        let platform = raw_platform.trim().to_lowercase();
+        if !DEVICE_PLATFORMS.contains(&platform.as_str()) {
+            return Err(RepositoryError::ValidationError(format!(
+                "platform must be one of {}",
+                DEVICE_PLATFORMS.join(", ")
+            )));
+        }
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("register_device: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        diesel::insert_into(device_tokens::table)
+            .values((
+                device_tokens::token.eq(token),
+                device_tokens::user_id.eq(owner_user_id),
+                device_tokens::platform.eq(&platform),
+            ))
+            .on_conflict(device_tokens::token)
+            .do_update()
+            .set((
+                device_tokens::user_id.eq(excluded(device_tokens::user_id)),
+                device_tokens::platform.eq(excluded(device_tokens::platform)),
+                device_tokens::updated_at.eq(diesel::dsl::now),
+            ))
+            .returning(DeviceToken::as_returning())
+            .get_result(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "register_device: error registering device for user_id {}: {}",
+                    owner_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    pub fn unregister_device(
+        &self,
+        owner_user_id: i32,
+        target_token: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("unregister_device: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        let deleted = diesel::delete(
+            device_tokens::table
+                .filter(device_tokens::token.eq(target_token))
+                .filter(device_tokens::user_id.eq(owner_user_id)),
+        )
+        .execute(conn.connection())
+        .map_err(|e| {
+            error!(
+                "unregister_device: error removing device of user_id {}: {}",
+                owner_user_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        if deleted == 0 {
+            return Err(RepositoryError::NotFound(
+                "device_tokens: token not registered".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn tokens_for_user(&self, target_user_id: i32) -> Result<Vec<String>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("tokens_for_user: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        device_tokens::table
+            .filter(device_tokens::user_id.eq(target_user_id))
+            .order(device_tokens::updated_at.desc())
+            .select(device_tokens::token)
+            .load::<String>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "tokens_for_user: error fetching devices of user_id {}: {}",
+                    target_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// Forget a token the push service reported as no longer registered.
+    pub fn remove_token(&self, stale_token: &str) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("remove_token: failed to acquire DB connection: {}", e);
+            e
+        })?;
+
+        diesel::delete(device_tokens::table.find(stale_token))
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!("remove_token: error removing stale device token: {}", e);
+                RepositoryError::DatabaseError(e)
+            })
+    }
+}
@@ -0,0 +1,604 @@
+use crate::db::errors::RepositoryError;
+use crate::db::schema::{
+    canteens, favourite_canteens, favourite_items, menu_items, saved_cart_items, saved_carts,
+};
+use crate::db::{AssetOperations, DbConnection};
+use crate::enums::admin::{CanteenDetailsWithPic, MenuItemWithPic};
+use crate::enums::users::{Favourites, SavedCart, SavedCartLine};
+use crate::models::admin::{CanteenDetails, MenuItem};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::result::{DatabaseErrorKind, Error};
+use futures::future::join_all;
+use log::error;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Saved carts a user can keep.
+const SAVED_CARTS_MAX: i64 = 20;
+/// Items, counting each unit, a saved cart can hold.
+const SAVED_CART_MAX_ITEMS: usize = 50;
+/// Characters in a saved cart's name.
+const SAVED_CART_NAME_MAX_CHARS: usize = 50;
+
+#[derive(Clone)]
+pub struct FavouriteOperations {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    asset_ops: AssetOperations,
+}
+
+impl FavouriteOperations {
+    pub async fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        Self {
+            pool,
+            asset_ops: AssetOperations::new().await.unwrap(),
+        }
+    }
+
+    /// IDs of the menu items the viewer has favourited, to mark them in menu listings.
+    pub(crate) fn load_favourite_item_ids(
+        conn: &mut PgConnection,
+        viewer_id: i32,
+    ) -> Result<HashSet<i32>, RepositoryError> {
+        favourite_items::table
+            .filter(favourite_items::user_id.eq(viewer_id))
+            .select(favourite_items::item_id)
+            .load::<i32>(conn)
+            .map(|ids| ids.into_iter().collect())
+            .map_err(|e| {
+                error!(
+                    "load_favourite_item_ids: error loading favourites of user_id {}: {}",
+                    viewer_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
+    /// The user's favourite items and canteens, latest favourite first.
+    pub async fn get_favourites(&self, owner_user_id: i32) -> Result<Favourites, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_favourites: failed to acquire DB connection for user_id {}: {}",
+                owner_user_id, e
+            );
+            e
+        })?;
+        let (items, canteen_details) = conn
+            .connection()
+            .transaction(|conn| {
+                let items = favourite_items::table
+                    .inner_join(menu_items::table)
+                    .filter(favourite_items::user_id.eq(owner_user_id))
+                    .order_by((
+                        favourite_items::created_at.desc(),
+                        favourite_items::item_id.asc(),
+                    ))
+                    .select(MenuItem::as_select())
+                    .load::<MenuItem>(conn)?;
+                let canteen_details = favourite_canteens::table
+                    .inner_join(canteens::table)
+                    .filter(favourite_canteens::user_id.eq(owner_user_id))
+                    .order_by((
+                        favourite_canteens::created_at.desc(),
+                        favourite_canteens::canteen_id.asc(),
+                    ))
+                    .select(CanteenDetails::as_select())
+                    .load::<CanteenDetails>(conn)?;
+                Ok((items, canteen_details))
+            })
+            .map_err(|e| {
+                error!(
+                    "get_favourites: error loading favourites of user_id {}: {}",
+                    owner_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+
+        let item_futures = items.iter().map(async |item| {
+            let mut item_with_pic: MenuItemWithPic = item.into();
+            item_with_pic.is_favourite = true;
+            item_with_pic
+                .populate_pic_link_from(&self.asset_ops, item)
+                .await;
+            item_with_pic
+        });
+        let canteen_futures = canteen_details.iter().map(async |canteen| {
+            let mut canteen_with_pic: CanteenDetailsWithPic = canteen.into();
+            canteen_with_pic
+                .populate_pic_link_from(&self.asset_ops, canteen)
+                .await;
+            canteen_with_pic
+        });
+        Ok(Favourites {
+            items: join_all(item_futures).await,
+            canteens: join_all(canteen_futures).await,
+        })
+    }
+
+    /// Favourite a menu item. Favouriting it again is a no-op.
+    pub fn add_favourite_item(
+        &self,
+        owner_user_id: i32,
+        target_item_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("add_favourite_item: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        diesel::insert_into(favourite_items::table)
+            .values((
+                favourite_items::user_id.eq(owner_user_id),
+                favourite_items::item_id.eq(target_item_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "add_favourite_item: error favouriting item {} for user_id {}: {}",
+                    target_item_id, owner_user_id, e
+                );
+                missing_target(e, format!("menu_items: {target_item_id}"))
+            })
+    }
+
+    pub fn remove_favourite_item(
+        &self,
+        owner_user_id: i32,
+        target_item_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "remove_favourite_item: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+        let removed = diesel::delete(
+            favourite_items::table
+                .filter(favourite_items::user_id.eq(owner_user_id))
+                .filter(favourite_items::item_id.eq(target_item_id)),
+        )
+        .execute(conn.connection())
+        .map_err(|e| {
+            error!(
+                "remove_favourite_item: error removing item {} for user_id {}: {}",
+                target_item_id, owner_user_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        if removed == 0 {
+            return Err(RepositoryError::NotFound(format!(
+                "favourite_items: {target_item_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Favourite a canteen. Favouriting it again is a no-op.
+    pub fn add_favourite_canteen(
+        &self,
+        owner_user_id: i32,
+        target_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "add_favourite_canteen: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+        diesel::insert_into(favourite_canteens::table)
+            .values((
+                favourite_canteens::user_id.eq(owner_user_id),
+                favourite_canteens::canteen_id.eq(target_canteen_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn.connection())
+            .map(|_| ())
+            .map_err(|e| {
+                error!(
+                    "add_favourite_canteen: error favouriting canteen {} for user_id {}: {}",
+                    target_canteen_id, owner_user_id, e
+                );
+                missing_target(e, format!("canteens: {target_canteen_id}"))
+            })
+    }
+
+    pub fn remove_favourite_canteen(
+        &self,
+        owner_user_id: i32,
+        target_canteen_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "remove_favourite_canteen: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+        let removed = diesel::delete(
+            favourite_canteens::table
+                .filter(favourite_canteens::user_id.eq(owner_user_id))
+                .filter(favourite_canteens::canteen_id.eq(target_canteen_id)),
+        )
+        .execute(conn.connection())
+        .map_err(|e| {
+            error!(
+                "remove_favourite_canteen: error removing canteen {} for user_id {}: {}",
+                target_canteen_id, owner_user_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        if removed == 0 {
+            return Err(RepositoryError::NotFound(format!(
+                "favourite_canteens: {target_canteen_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The user's saved carts by name, priced with current menu prices.
+    pub fn get_saved_carts(&self, owner_user_id: i32) -> Result<Vec<SavedCart>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_saved_carts: failed to acquire DB connection for user_id {}: {}",
+                owner_user_id, e
+            );
+            e
+        })?;
+        load_saved_carts(conn.connection(), owner_user_id, None).map_err(|e| {
+            error!(
+                "get_saved_carts: error loading saved carts of user_id {}: {}",
+                owner_user_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })
+    }
+
+    /// Save a named cart of `item_ids`, repeated once per unit like in an order. Names are
+    /// unique per user, ignoring case.
+    pub fn create_saved_cart(
+        &self,
+        owner_user_id: i32,
+        raw_name: &str,
+        item_ids: &[i32],
+    ) -> Result<SavedCart, RepositoryError> {
+        let cart_name = sanitize_cart_name(raw_name)?;
+        let quantities = cart_quantities(item_ids)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("create_saved_cart: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let saved = saved_carts::table
+                    .filter(saved_carts::user_id.eq(owner_user_id))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if saved >= SAVED_CARTS_MAX {
+                    return Err(RepositoryError::ValidationError(format!(
+                        "at most {SAVED_CARTS_MAX} carts can be saved"
+                    )));
+                }
+                let cart_canteen_id = cart_canteen(conn, &quantities)?;
+                let new_cart_id = diesel::insert_into(saved_carts::table)
+                    .values((
+                        saved_carts::user_id.eq(owner_user_id),
+                        saved_carts::canteen_id.eq(cart_canteen_id),
+                        saved_carts::name.eq(&cart_name),
+                    ))
+                    .returning(saved_carts::cart_id)
+                    .get_result::<i32>(conn)
+                    .map_err(|e| duplicate_name(e, &cart_name))?;
+                insert_cart_items(conn, new_cart_id, &quantities)?;
+                single_cart(conn, owner_user_id, new_cart_id)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "create_saved_cart: error saving cart '{}' for user_id {}: {}",
+                    cart_name, owner_user_id, e
+                )
+            })
+    }
+
+    /// Rename the cart and replace its items.
+    pub fn update_saved_cart(
+        &self,
+        owner_user_id: i32,
+        target_cart_id: i32,
+        raw_name: &str,
+        item_ids: &[i32],
+    ) -> Result<SavedCart, RepositoryError> {
+        let cart_name = sanitize_cart_name(raw_name)?;
+        let quantities = cart_quantities(item_ids)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("update_saved_cart: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        conn.connection()
+            .transaction(|conn| {
+                let cart_canteen_id = cart_canteen(conn, &quantities)?;
+                let updated = diesel::update(
+                    saved_carts::table
+                        .filter(saved_carts::cart_id.eq(target_cart_id))
+                        .filter(saved_carts::user_id.eq(owner_user_id)),
+                )
+                .set((
+                    saved_carts::name.eq(&cart_name),
+                    saved_carts::canteen_id.eq(cart_canteen_id),
+                    saved_carts::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)
+                .map_err(|e| duplicate_name(e, &cart_name))?;
+                if updated == 0 {
+                    return Err(RepositoryError::NotFound(format!(
+                        "saved_carts: {target_cart_id}"
+                    )));
+                }
+                diesel::delete(
+                    saved_cart_items::table.filter(saved_cart_items::cart_id.eq(target_cart_id)),
+                )
+                .execute(conn)?;
+                insert_cart_items(conn, target_cart_id, &quantities)?;
+                single_cart(conn, owner_user_id, target_cart_id)
+            })
+            .inspect_err(|e| {
+                error!(
+                    "update_saved_cart: error updating cart {} of user_id {}: {}",
+                    target_cart_id, owner_user_id, e
+                )
+            })
+    }
+
+    pub fn delete_saved_cart(
+        &self,
+        owner_user_id: i32,
+        target_cart_id: i32,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!("delete_saved_cart: failed to acquire DB connection: {}", e);
+            e
+        })?;
+        let removed = diesel::delete(
+            saved_carts::table
+                .filter(saved_carts::cart_id.eq(target_cart_id))
+                .filter(saved_carts::user_id.eq(owner_user_id)),
+        )
+        .execute(conn.connection())
+        .map_err(|e| {
+            error!(
+                "delete_saved_cart: error deleting cart {} of user_id {}: {}",
+                target_cart_id, owner_user_id, e
+            );
+            RepositoryError::DatabaseError(e)
+        })?;
+        if removed == 0 {
+            return Err(RepositoryError::NotFound(format!(
+                "saved_carts: {target_cart_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The cart's items, repeated once per unit, as `item_ids` for holding the order.
+    pub fn saved_cart_item_ids(
+        &self,
+        owner_user_id: i32,
+        target_cart_id: i32,
+    ) -> Result<Vec<i32>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "saved_cart_item_ids: failed to acquire DB connection: {}",
+                e
+            );
+            e
+        })?;
+        let lines = saved_cart_items::table
+            .inner_join(saved_carts::table)
+            .filter(saved_carts::cart_id.eq(target_cart_id))
+            .filter(saved_carts::user_id.eq(owner_user_id))
+            .order_by(saved_cart_items::item_id)
+            .select((saved_cart_items::item_id, saved_cart_items::quantity))
+            .load::<(i32, i16)>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "saved_cart_item_ids: error loading cart {} of user_id {}: {}",
+                    target_cart_id, owner_user_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })?;
+        if lines.is_empty() {
+            return Err(RepositoryError::NotFound(format!(
+                "saved_carts: {target_cart_id}"
+            )));
+        }
+        Ok(lines
+            .into_iter()
+            .flat_map(|(id, quantity)| std::iter::repeat_n(id, quantity as usize))
+            .collect())
+    }
+}
+
+/// A foreign key violation means the favourited item or canteen does not exist.
+fn missing_target(e: Error, target: String) -> RepositoryError {
+    match e {
+        Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            RepositoryError::NotFound(target)
+        }
+        other => RepositoryError::DatabaseError(other),
+    }
+}
+
+fn duplicate_name(e: Error, cart_name: &str) -> RepositoryError {
+    match e {
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            RepositoryError::ValidationError(format!("a cart named '{cart_name}' already exists"))
+        }
+        other => RepositoryError::DatabaseError(other),
+    }
+}
+
+fn sanitize_cart_name(raw_name: &str) -> Result<String, RepositoryError> {
+    let cart_name = raw_name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if cart_name.is_empty() || cart_name.chars().count() > SAVED_CART_NAME_MAX_CHARS {
+        return Err(RepositoryError::ValidationError(format!(
+            "cart name must be 1 to {SAVED_CART_NAME_MAX_CHARS} characters"
+        )));
+    }
+    Ok(cart_name)
+}
+
+fn cart_quantities(item_ids: &[i32]) -> Result<BTreeMap<i32, i16>, RepositoryError> {
+    if item_ids.is_empty() || item_ids.len() > SAVED_CART_MAX_ITEMS {
+        return Err(RepositoryError::ValidationError(format!(
+            "a cart must have 1 to {SAVED_CART_MAX_ITEMS} items"
+        )));
+    }
+    let mut quantities: BTreeMap<i32, i16> = BTreeMap::new();
+    for id in item_ids {
+        *quantities.entry(*id).or_default() += 1;
+    }
+    Ok(quantities)
+}
+
+/// The one canteen all of the cart's items are from.
+fn cart_canteen(
+    conn: &mut PgConnection,
+    quantities: &BTreeMap<i32, i16>,
+) -> Result<i32, RepositoryError> {
+    let item_canteens = menu_items::table
+        .filter(menu_items::item_id.eq_any(quantities.keys()))
+        .select((menu_items::item_id, menu_items::canteen_id))
+        .load::<(i32, i32)>(conn)?;
+    if item_canteens.len() != quantities.len() {
+        return Err(RepositoryError::ValidationError(format!(
+            "cart contains missing menu items: {:?}",
+            quantities.keys().collect::<Vec<_>>()
+        )));
+    }
+    let cart_canteen_id = item_canteens[0].1;
+    if item_canteens
+        .iter()
+        .any(|(_, item_canteen)| *item_canteen != cart_canteen_id)
+    {
+        return Err(RepositoryError::ValidationError(
+            "cart contains items from multiple canteens".to_string(),
+        ));
+    }
+    Ok(cart_canteen_id)
+}
+
+fn insert_cart_items(
+    conn: &mut PgConnection,
+    target_cart_id: i32,
+    quantities: &BTreeMap<i32, i16>,
+) -> Result<(), RepositoryError> {
+    diesel::insert_into(saved_cart_items::table)
+        .values(
+            quantities
+                .iter()
+                .map(|(id, quantity)| {
+                    (
+                        saved_cart_items::cart_id.eq(target_cart_id),
+                        saved_cart_items::item_id.eq(*id),
+                        saved_cart_items::quantity.eq(*quantity),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)?;
+    Ok(())
+}
+
+fn single_cart(
+    conn: &mut PgConnection,
+    owner_user_id: i32,
+    target_cart_id: i32,
+) -> Result<SavedCart, RepositoryError> {
+    load_saved_carts(conn, owner_user_id, Some(target_cart_id))?
+        .pop()
+        .ok_or_else(|| RepositoryError::NotFound(format!("saved_carts: {target_cart_id}")))
+}
+
+type CartRow = (i32, i32, String, DateTime<Utc>, DateTime<Utc>);
+
+/// The user's saved carts, or only `only_cart_id`, with their lines at current prices.
+fn load_saved_carts(
+    conn: &mut PgConnection,
+    owner_user_id: i32,
+    only_cart_id: Option<i32>,
+) -> Result<Vec<SavedCart>, Error> {
+    let mut carts_query = saved_carts::table
+        .filter(saved_carts::user_id.eq(owner_user_id))
+        .order_by((saved_carts::name.asc(), saved_carts::cart_id.asc()))
+        .select((
+            saved_carts::cart_id,
+            saved_carts::canteen_id,
+            saved_carts::name,
+            saved_carts::created_at,
+            saved_carts::updated_at,
+        ))
+        .into_boxed();
+    if let Some(only_cart_id) = only_cart_id {
+        carts_query = carts_query.filter(saved_carts::cart_id.eq(only_cart_id));
+    }
+    let carts = carts_query.load::<CartRow>(conn)?;
+
+    let mut lines_by_cart: HashMap<i32, Vec<SavedCartLine>> = HashMap::new();
+    for (line_cart_id, line) in saved_cart_items::table
+        .inner_join(menu_items::table)
+        .filter(saved_cart_items::cart_id.eq_any(carts.iter().map(|cart| cart.0)))
+        .order_by((
+            saved_cart_items::cart_id,
+            menu_items::name,
+            menu_items::item_id,
+        ))
+        .select((
+            saved_cart_items::cart_id,
+            menu_items::item_id,
+            menu_items::name,
+            saved_cart_items::quantity,
+            menu_items::price,
+            menu_items::is_available,
+            menu_items::stock,
+        ))
+        .load::<(i32, i32, String, i16, i32, bool, i32)>(conn)?
+        .into_iter()
+        .map(
+            |(line_cart_id, id, item_name, quantity, price, is_available, stock)| {
+                (
+                    line_cart_id,
+                    SavedCartLine {
+                        item_id: id,
+                        name: item_name,
+                        quantity,
+                        price,
+                        is_available: is_available && (stock == -1 || stock >= i32::from(quantity)),
+                    },
+                )
+            },
+        )
+    {
+        lines_by_cart.entry(line_cart_id).or_default().push(line);
+    }
+
+    Ok(carts
+        .into_iter()
+        .map(|(id, cart_canteen_id, cart_name, created, updated)| {
+            let lines = lines_by_cart.remove(&id).unwrap_or_default();
+            SavedCart {
+                cart_id: id,
+                canteen_id: cart_canteen_id,
+                name: cart_name,
+                total_price: lines
+                    .iter()
+                    .map(|line| line.price * i32::from(line.quantity))
+                    .sum(),
+                lines,
+                created_at: created,
+                updated_at: updated,
+            }
+        })
+        .collect())
+}
@@ -1 +1,4 @@
+pub(crate) mod cart;
+pub(crate) mod devices;
+pub(crate) mod favourites;
 pub(crate) mod user;
@@ -2,6 +2,7 @@ use crate::db::errors::RepositoryError;
 use crate::db::{AssetOperations, DbConnection};
 use crate::enums::common::ItemContainer;
 use crate::enums::users::{PastOrderItemContainer, PastOrderItemWithPic};
+use crate::models::admin::sanitize_allergens;
 use crate::models::user::{NewUser, PastOrder, PastOrderItem, User};
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
@@ -18,6 +19,8 @@ type MenuItemInfo = (
     Option<String>,
     Option<String>,
     Option<String>,
+    Vec<String>,
+    Vec<String>,
 );
 
 #[derive(Debug)]
@@ -105,6 +108,73 @@ impl UserOperations {
             })
     }
 
+    pub fn get_allergen_exclusions(&self, viewer_id: i32) -> Result<Vec<String>, RepositoryError> {
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "get_allergen_exclusions: failed to acquire DB connection for user_id {}: {}",
+                viewer_id, e
+            );
+            e
+        })?;
+
+        Self::load_allergen_exclusions(conn.connection(), viewer_id)
+    }
+
+    /// Replace the allergens the user wants to avoid. Returns the normalized list.
+    pub fn set_allergen_exclusions(
+        &self,
+        viewer_id: i32,
+        exclusions: Vec<String>,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let exclusions =
+            sanitize_allergens(&exclusions).map_err(RepositoryError::ValidationError)?;
+        let mut conn = DbConnection::new(&self.pool).map_err(|e| {
+            error!(
+                "set_allergen_exclusions: failed to acquire DB connection for user_id {}: {}",
+                viewer_id, e
+            );
+            e
+        })?;
+
+        use crate::db::schema::users::dsl::*;
+        diesel::update(users.filter(user_id.eq(viewer_id)))
+            .set(allergen_exclusions.eq(exclusions))
+            .returning(allergen_exclusions)
+            .get_result::<Vec<String>>(conn.connection())
+            .map_err(|e| {
+                error!(
+                    "set_allergen_exclusions: error updating user_id {}: {}",
+                    viewer_id, e
+                );
+                match e {
+                    Error::NotFound => RepositoryError::NotFound(format!("users: {viewer_id}")),
+                    other => RepositoryError::DatabaseError(other),
+                }
+            })
+    }
+
+    /// Allergen exclusions saved on a user's profile, used to filter and flag menu items.
+    /// Unknown users have none.
+    pub(crate) fn load_allergen_exclusions(
+        conn: &mut PgConnection,
+        viewer_id: i32,
+    ) -> Result<Vec<String>, RepositoryError> {
+        use crate::db::schema::users::dsl::*;
+        users
+            .filter(user_id.eq(viewer_id))
+            .select(allergen_exclusions)
+            .first::<Vec<String>>(conn)
+            .optional()
+            .map(Option::unwrap_or_default)
+            .map_err(|e| {
+                error!(
+                    "load_allergen_exclusions: error fetching exclusions for user_id {}: {}",
+                    viewer_id, e
+                );
+                RepositoryError::DatabaseError(e)
+            })
+    }
+
     fn group_order_items(items: Vec<PastOrderItemWithPic>) -> Vec<PastOrderItemContainer> {
         debug!("Ungrouped order items: {:?}", &items);
         let mut grouped: HashMap<i32, GroupedPastOrder> = HashMap::new();
@@ -129,6 +199,8 @@ impl UserOperations {
                 pic_link: item.pic_link,
                 pic_etag: item.pic_etag,
                 description: item.description,
+                allergens: item.allergens,
+                diet_labels: item.diet_labels,
             });
         }
         debug!("Grouped order items: {:?}", &grouped);
@@ -210,6 +282,8 @@ impl UserOperations {
                     pic_key,
                     pic_etag,
                     description,
+                    allergens,
+                    diet_labels,
                 ))
                 .filter(item_id.eq_any(all_items))
                 .load::<(
@@ -220,6 +294,8 @@ impl UserOperations {
                     Option<String>,
                     Option<String>,
                     Option<String>,
+                    Vec<String>,
+                    Vec<String>,
                 )>(conn.connection())
                 .map_err(|e| {
                     error!(
@@ -243,6 +319,8 @@ impl UserOperations {
                         item.4.clone(),
                         item.5.clone(),
                         item.6.clone(),
+                        item.7.clone(),
+                        item.8.clone(),
                     )
                 });
             })
@@ -272,6 +350,8 @@ impl UserOperations {
                     pic_key: menu_items_in_orders.get(&item_unwrap).unwrap().3.clone(),
                     pic_etag: menu_items_in_orders.get(&item_unwrap).unwrap().4.clone(),
                     description: menu_items_in_orders.get(&item_unwrap).unwrap().5.clone(),
+                    allergens: menu_items_in_orders.get(&item_unwrap).unwrap().6.clone(),
+                    diet_labels: menu_items_in_orders.get(&item_unwrap).unwrap().7.clone(),
                 });
                 items_qty.remove(&item_unwrap);
             }
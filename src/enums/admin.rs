@@ -1,7 +1,12 @@
-use crate::models::admin::{CanteenLoginSuccess, UpdateMenuItem};
-use chrono::NaiveTime;
+use crate::models::admin::{
+    sanitize_tag, CanteenLoginSuccess, CanteenWebhook, MenuCategory, PricingRule,
+    PricingRuleKindEnum, PromoCode, ScheduleWindow, Settlement, UpdateMenuCategory, UpdateMenuItem,
+    WebhookDelivery,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::collections::HashSet;
+use utoipa::{IntoParams, ToSchema};
 use with_pic_macro::{with_pic, WithPic};
 
 #[derive(Serialize, ToSchema)]
@@ -37,6 +42,39 @@ pub struct MenuItemWithPic {
     pub description: Option<String>,
     pub pic_link: Option<String>,
     pub pic_etag: Option<String>,
+    pub default_stock: Option<i32>,
+    pub category_id: Option<i32>,
+    pub display_order: i32,
+    pub is_pinned: bool,
+    pub tags: Vec<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
+    /// Average rating out of 5 from visible reviews, rounded to two places; null until rated.
+    pub average_rating: Option<f64>,
+    pub rating_count: i32,
+    /// Allergens of this item the requesting user has excluded on their profile.
+    #[with_pic(default)]
+    pub allergen_warnings: Vec<String>,
+    /// Whether the requesting user has favourited this item.
+    #[with_pic(default)]
+    pub is_favourite: bool,
+}
+
+impl MenuItemWithPic {
+    /// Fill `allergen_warnings` from the viewer's allergen exclusions.
+    pub fn flag_allergens(&mut self, exclusions: &[String]) {
+        self.allergen_warnings = self
+            .allergens
+            .iter()
+            .filter(|allergen| exclusions.contains(allergen))
+            .cloned()
+            .collect();
+    }
+
+    /// Set `is_favourite` from the viewer's favourite item IDs.
+    pub fn mark_favourite(&mut self, favourite_ids: &HashSet<i32>) {
+        self.is_favourite = favourite_ids.contains(&self.item_id);
+    }
 }
 
 impl Default for MenuItemWithPic {
@@ -52,6 +90,17 @@ impl Default for MenuItemWithPic {
             description: None,
             pic_link: None,
             pic_etag: None,
+            default_stock: None,
+            category_id: None,
+            display_order: 0,
+            is_pinned: false,
+            tags: Vec::new(),
+            allergens: Vec::new(),
+            diet_labels: Vec::new(),
+            average_rating: None,
+            rating_count: 0,
+            allergen_warnings: Vec::new(),
+            is_favourite: false,
         }
     }
 }
@@ -69,6 +118,9 @@ pub struct CanteenDetailsWithPic {
     pub is_open: bool,
     pub pic_link: Option<String>,
     pub pic_etag: Option<String>,
+    /// Average rating out of 5 from visible reviews, rounded to two places; null until rated.
+    pub average_rating: Option<f64>,
+    pub rating_count: i32,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -100,6 +152,799 @@ pub struct CreateMenuItemRequest {
     pub stock: i32,
     pub is_available: bool,
     pub description: Option<String>,
+    /// Stock restored every day at the canteen's opening time. `None` disables the reset.
+    pub default_stock: Option<i32>,
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub display_order: i32,
+    /// Pinned items (e.g. bestsellers) are listed before everything else.
+    #[serde(default)]
+    pub is_pinned: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Allergens present in the item, e.g. "nuts", "dairy", "gluten".
+    #[serde(default)]
+    pub allergens: Vec<String>,
+    /// Diet labels such as "vegan" or "jain".
+    #[serde(default)]
+    pub diet_labels: Vec<String>,
+}
+
+/// Optional filters accepted by the canteen menu and search endpoints.
+#[derive(Deserialize, Debug, Default, Clone, IntoParams)]
+pub struct MenuFilterQuery {
+    pub category_id: Option<i32>,
+    pub tag: Option<String>,
+    /// Drop items containing allergens the signed-in user has excluded instead of only
+    /// flagging them in `allergen_warnings`.
+    pub hide_allergens: Option<bool>,
+}
+
+impl MenuFilterQuery {
+    /// The tag filter normalized the same way stored tags are.
+    pub fn normalized_tag(&self) -> Result<Option<String>, String> {
+        self.tag.as_deref().map(sanitize_tag).transpose()
+    }
+}
+
+/// Text, filters and paging of the menu search.
+#[derive(Deserialize, Debug, Default, Clone, IntoParams)]
+pub struct MenuSearchQuery {
+    /// Words to look for in item names and descriptions, with typos in names tolerated.
+    /// Without it the filters alone pick the items, in alphabetical order.
+    pub q: Option<String>,
+    pub canteen_id: Option<i32>,
+    pub category_id: Option<i32>,
+    pub tag: Option<String>,
+    /// `true` for veg items only, `false` for non-veg items only.
+    pub veg: Option<bool>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    /// Leave out items that are sold out or switched off.
+    pub available_only: Option<bool>,
+    /// Leave out items of canteens that are closed right now.
+    pub open_only: Option<bool>,
+    /// Drop items containing allergens the signed-in user has excluded instead of only
+    /// flagging them in `allergen_warnings`.
+    pub hide_allergens: Option<bool>,
+    /// 1-based page number, 1 by default.
+    pub page: Option<i64>,
+    /// Items per page, 20 by default and at most 50.
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CanteenSearchFacet {
+    pub canteen_id: i32,
+    pub canteen_name: String,
+    pub item_count: i64,
+}
+
+/// Counts over every matching item, not only the returned page. Each facet ignores its own
+/// filter, so with `veg=true` the `non_veg` count still says what the other choice would give.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct MenuSearchFacets {
+    pub veg: i64,
+    pub non_veg: i64,
+    pub available: i64,
+    pub canteens: Vec<CanteenSearchFacet>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MenuSearchResults {
+    pub items: Vec<MenuItemWithPic>,
+    /// How many items matched across all pages.
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub facets: MenuSearchFacets,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MenuSearchResponse {
+    pub status: String,
+    pub data: Option<MenuSearchResults>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct SearchSuggestQuery {
+    /// What has been typed so far.
+    pub q: String,
+    /// Only suggest items of this canteen, and no canteens.
+    pub canteen_id: Option<i32>,
+    /// Suggestions of each kind, 8 by default and at most 20.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CanteenSuggestion {
+    pub canteen_id: i32,
+    pub canteen_name: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchSuggestions {
+    /// Item names that start with the typed text, or have a word that does.
+    pub items: Vec<String>,
+    pub canteens: Vec<CanteenSuggestion>,
+    /// Popular searches starting with the typed text.
+    pub queries: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchSuggestionsResponse {
+    pub status: String,
+    pub data: Option<SearchSuggestions>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct SearchInsightsQuery {
+    /// How many days back to look, 30 by default and at most 365.
+    pub days: Option<i64>,
+    /// Queries in each list, 20 by default and at most 100.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchQueryStat {
+    /// The search text, lowercased.
+    pub query: String,
+    pub search_count: i64,
+    /// How many of those searches found nothing.
+    pub no_result_count: i64,
+}
+
+/// Searches of the canteen's menu and searches across all canteens.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchInsights {
+    /// The most searched queries first.
+    pub popular: Vec<SearchQueryStat>,
+    /// Queries that found nothing, the most frequent first.
+    pub no_results: Vec<SearchQueryStat>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchInsightsResponse {
+    pub status: String,
+    pub data: Option<SearchInsights>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct AccountingExportQuery {
+    /// First business day to export, in the canteen time zone; 29 days before `to` by default.
+    #[param(value_type = Option<String>, format = "date")]
+    pub from: Option<NaiveDate>,
+    /// Last business day to export, included; today by default. Exports cover at most 366
+    /// days.
+    #[param(value_type = Option<String>, format = "date")]
+    pub to: Option<NaiveDate>,
+}
+
+/// What an accounting export lists, one CSV row each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountingExport {
+    Orders,
+    OrderItems,
+    Payments,
+}
+
+impl AccountingExport {
+    /// Header of the file. The columns are part of the export format: new ones are only ever
+    /// added at the end.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            AccountingExport::Orders => &[
+                "order_id",
+                "ordered_at",
+                "business_date",
+                "user_id",
+                "status",
+                "slot",
+                "total",
+                "merchant_order_id",
+                "payment_state",
+            ],
+            AccountingExport::OrderItems => &[
+                "order_id",
+                "ordered_at",
+                "business_date",
+                "status",
+                "item_id",
+                "item_name",
+                "quantity",
+                "unit_price",
+                "line_total",
+            ],
+            AccountingExport::Payments => &[
+                "payment_id",
+                "created_at",
+                "business_date",
+                "merchant_order_id",
+                "phonepe_order_id",
+                "hold_id",
+                "order_id",
+                "user_id",
+                "amount_paisa",
+                "payment_state",
+                "refunded_paisa",
+                "updated_at",
+            ],
+        }
+    }
+
+    pub fn file_stem(&self) -> &'static str {
+        match self {
+            AccountingExport::Orders => "orders",
+            AccountingExport::OrderItems => "order-items",
+            AccountingExport::Payments => "payments",
+        }
+    }
+}
+
+/// A finished order in the orders export. Fields are in column order.
+#[derive(Debug, Serialize)]
+pub struct OrderExportRow {
+    pub order_id: i32,
+    pub ordered_at: DateTime<Utc>,
+    pub business_date: NaiveDate,
+    pub user_id: i32,
+    /// `delivered` or `cancelled`.
+    pub status: String,
+    /// The delivery slot, or `Instant`.
+    pub slot: String,
+    /// Rupees.
+    pub total: i32,
+    /// The PhonePe payment for the order, if it was paid online.
+    pub merchant_order_id: Option<String>,
+    pub payment_state: Option<String>,
+}
+
+/// An item of a finished order in the line items export. Fields are in column order.
+#[derive(Debug, Serialize)]
+pub struct OrderItemExportRow {
+    pub order_id: i32,
+    pub ordered_at: DateTime<Utc>,
+    pub business_date: NaiveDate,
+    pub status: String,
+    pub item_id: i32,
+    /// Empty once the item has been removed from the menu.
+    pub item_name: Option<String>,
+    pub quantity: i16,
+    /// Rupees, as charged.
+    pub unit_price: i32,
+    pub line_total: i32,
+}
+
+/// An online payment in the payments export. Fields are in column order.
+#[derive(Debug, Serialize)]
+pub struct PaymentExportRow {
+    pub payment_id: i32,
+    pub created_at: DateTime<Utc>,
+    pub business_date: NaiveDate,
+    pub merchant_order_id: String,
+    pub phonepe_order_id: String,
+    pub hold_id: i32,
+    /// The order the payment confirmed, if any.
+    pub order_id: Option<i32>,
+    pub user_id: i32,
+    pub amount_paisa: i32,
+    pub payment_state: String,
+    /// Refunds PhonePe has completed, in paisa.
+    pub refunded_paisa: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Body of a failed export; successful ones are CSV files.
+#[derive(Serialize, ToSchema)]
+pub struct AccountingExportErrorResponse {
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct SettlementQuery {
+    /// First business day, in the canteen time zone; 29 days before `to` by default.
+    #[param(value_type = Option<String>, format = "date")]
+    pub from: Option<NaiveDate>,
+    /// Last business day, included; today by default. At most 366 days.
+    #[param(value_type = Option<String>, format = "date")]
+    pub to: Option<NaiveDate>,
+    /// Canteen to show, required for platform operators; canteen admins see their own.
+    pub canteen_id: Option<i32>,
+}
+
+/// Sums over the statements of a ledger, in paisa.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SettlementTotals {
+    pub gross: i64,
+    pub refunds: i64,
+    pub commission: i64,
+    pub net_payable: i64,
+    /// Net payable of the statements not paid out yet.
+    pub outstanding: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SettlementLedger {
+    #[schema(value_type = String, format = "date")]
+    pub from: NaiveDate,
+    #[schema(value_type = String, format = "date")]
+    pub to: NaiveDate,
+    /// Only days with payments or refunds have a statement; the latest first.
+    pub statements: Vec<Settlement>,
+    pub totals: SettlementTotals,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SettlementLedgerResponse {
+    pub status: String,
+    pub data: Option<SettlementLedger>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PayoutRequest {
+    /// Bank or UPI reference of the transfer, up to 100 characters.
+    pub reference: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SettlementResponse {
+    pub status: String,
+    pub data: Option<Settlement>,
+    pub error: Option<String>,
+}
+
+/// How a sales report is broken down over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyticsGranularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl AnalyticsGranularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnalyticsGranularity::Day => "day",
+            AnalyticsGranularity::Week => "week",
+            AnalyticsGranularity::Month => "month",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "day" => Some(AnalyticsGranularity::Day),
+            "week" => Some(AnalyticsGranularity::Week),
+            "month" => Some(AnalyticsGranularity::Month),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct SalesAnalyticsQuery {
+    /// First business day of the report, in the canteen time zone; 29 days before `to` by
+    /// default.
+    #[param(value_type = Option<String>, format = "date")]
+    pub from: Option<NaiveDate>,
+    /// Last business day of the report, included; today by default. Reports cover at most
+    /// 366 days.
+    #[param(value_type = Option<String>, format = "date")]
+    pub to: Option<NaiveDate>,
+    /// `day` (the default), `week` or `month`.
+    pub granularity: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SalesTotals {
+    /// Finished orders, delivered or cancelled.
+    pub orders: i64,
+    pub delivered: i64,
+    pub cancelled: i64,
+    /// Cancelled orders as a fraction of `orders`.
+    pub cancellation_rate: f64,
+    /// Rupees taken for delivered orders.
+    pub revenue: i64,
+    /// Rupees per delivered order.
+    pub average_basket: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SalesPeriod {
+    /// The day, the Monday of the week or the first of the month. The first and last periods
+    /// may be cut short by the range.
+    #[schema(value_type = String, format = "date")]
+    pub period_start: NaiveDate,
+    #[serde(flatten)]
+    pub totals: SalesTotals,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ItemSales {
+    pub item_id: i32,
+    /// Null once the item has been removed from the menu.
+    pub name: Option<String>,
+    pub quantity: i64,
+    /// Rupees, at the prices paid.
+    pub revenue: i64,
+    /// Fraction of the revenue of all items.
+    pub revenue_share: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SlotUtilisation {
+    /// The delivery slot, or `Instant` for orders wanted straight away.
+    pub slot: String,
+    pub orders: i64,
+    /// Fraction of all finished orders.
+    pub share: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HoldConversion {
+    pub holds: i64,
+    pub confirmed: i64,
+    /// Given up by the user.
+    pub released: i64,
+    pub expired: i64,
+    /// Still held.
+    pub pending: i64,
+    /// Confirmed holds as a fraction of those that have ended.
+    pub conversion_rate: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PaymentStats {
+    /// Online payments started.
+    pub payments: i64,
+    pub completed: i64,
+    pub failed: i64,
+    /// Not settled yet.
+    pub pending: i64,
+    /// Failed payments as a fraction of settled ones.
+    pub failure_rate: f64,
+}
+
+/// Sales of a canteen over a range of business days.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SalesAnalytics {
+    #[schema(value_type = String, format = "date")]
+    pub from: NaiveDate,
+    #[schema(value_type = String, format = "date")]
+    pub to: NaiveDate,
+    pub granularity: String,
+    pub totals: SalesTotals,
+    /// Only periods with orders, oldest first.
+    pub periods: Vec<SalesPeriod>,
+    /// Items of delivered orders, the highest revenue first.
+    pub items: Vec<ItemSales>,
+    pub slots: Vec<SlotUtilisation>,
+    /// Holds placed in the range.
+    pub holds: HoldConversion,
+    /// Online payments started in the range.
+    pub payments: PaymentStats,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SalesAnalyticsResponse {
+    pub status: String,
+    pub data: Option<SalesAnalytics>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateMenuCategoryRequest {
+    pub name: String,
+    #[serde(default)]
+    pub display_order: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateMenuCategoryRequest {
+    pub category_id: i32,
+    pub update: UpdateMenuCategory,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MenuCategoryResponse {
+    pub status: String,
+    pub data: Option<MenuCategory>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AllMenuCategoriesResponse {
+    pub status: String,
+    pub data: Vec<MenuCategory>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MenuTagCount {
+    pub tag: String,
+    pub item_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MenuTagsResponse {
+    pub status: String,
+    pub data: Vec<MenuTagCount>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RenameMenuTagRequest {
+    pub new_tag: String,
+}
+
+/// One menu item in an import or export file. Rows with an `item_id`, or whose name matches
+/// an existing item of the canteen, update that item; other rows create new items.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MenuTransferRow {
+    pub item_id: Option<i32>,
+    pub name: String,
+    pub is_veg: bool,
+    pub price: i32,
+    pub stock: i32,
+    pub is_available: bool,
+    pub description: Option<String>,
+    pub default_stock: Option<i32>,
+    /// Category name; categories that don't exist yet are created by the import.
+    pub category: Option<String>,
+    #[serde(default)]
+    pub display_order: i32,
+    #[serde(default)]
+    pub is_pinned: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub allergens: Vec<String>,
+    #[serde(default)]
+    pub diet_labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuTransferFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct MenuImportQuery {
+    /// Only report what would change, without writing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct MenuExportQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub format: MenuTransferFormat,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MenuImportChange {
+    /// 1-based position of the row in the file (header excluded).
+    pub row: usize,
+    pub item_id: Option<i32>,
+    pub name: String,
+    /// Fields that differ from the stored item; empty for new items.
+    pub changed_fields: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MenuImportRowError {
+    pub row: usize,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct MenuImportReport {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub created: Vec<MenuImportChange>,
+    pub updated: Vec<MenuImportChange>,
+    pub unchanged: usize,
+    pub created_categories: Vec<String>,
+    pub errors: Vec<MenuImportRowError>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MenuImportResponse {
+    pub status: String,
+    pub data: Option<MenuImportReport>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PricingRuleRequest {
+    pub name: String,
+    pub kind: PricingRuleKindEnum,
+    /// Required for `percent_off` rules.
+    pub percent_off: Option<i32>,
+    /// Required for `flat_off` rules.
+    pub amount_off: Option<i32>,
+    /// Required for `combo` rules.
+    pub combo_price: Option<i32>,
+    /// Items covered by a discount (empty for the whole menu), or the units making up a combo.
+    #[serde(default)]
+    pub item_ids: Vec<i32>,
+    /// Happy-hour windows; the rule applies at all times when empty.
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PricingRuleDetails {
+    pub rule_id: i32,
+    pub name: String,
+    pub kind: PricingRuleKindEnum,
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    pub combo_price: Option<i32>,
+    pub item_ids: Vec<i32>,
+    pub windows: Vec<ScheduleWindow>,
+    pub is_active: bool,
+}
+
+impl PricingRuleDetails {
+    pub fn new(rule: PricingRule, windows: Vec<ScheduleWindow>) -> Self {
+        Self {
+            rule_id: rule.rule_id,
+            name: rule.name,
+            kind: rule.kind,
+            percent_off: rule.percent_off,
+            amount_off: rule.amount_off,
+            combo_price: rule.combo_price,
+            item_ids: rule.item_ids,
+            windows,
+            is_active: rule.is_active,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PricingRuleResponse {
+    pub status: String,
+    pub data: Option<PricingRuleDetails>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AllPricingRulesResponse {
+    pub status: String,
+    pub data: Vec<PricingRuleDetails>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PromoCodeRequest {
+    /// Case-insensitive; stored uppercase.
+    pub code: String,
+    pub description: Option<String>,
+    /// Exactly one of `percent_off` and `amount_off` is required.
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    /// Caps the discount of a `percent_off` code.
+    pub max_discount: Option<i32>,
+    /// Minimum order total, after pricing rules, for the code to apply.
+    #[serde(default)]
+    pub min_order_total: i32,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses_total: Option<i32>,
+    pub max_uses_per_user: Option<i32>,
+    #[serde(default)]
+    pub first_order_only: bool,
+    /// Valid at every canteen. Only platform operators manage these, and every code they
+    /// create is platform-wide.
+    #[serde(default)]
+    pub platform_wide: bool,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PromoCodeDetails {
+    #[serde(flatten)]
+    pub promo: PromoCode,
+    /// Redemptions on confirmed orders and pending holds.
+    pub times_redeemed: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PromoCodeResponse {
+    pub status: String,
+    pub data: Option<PromoCodeDetails>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookRequest {
+    /// An http or https URL the server can reach.
+    pub url: String,
+    /// Shared secret the requests are signed with, at least 16 characters. Required when
+    /// registering; kept as is when omitted on update.
+    pub secret: Option<String>,
+    /// Any of `order.confirmed`, `order.cancelled`, `order.delivered` and `item.stock_out`;
+    /// all of them when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhookResponse {
+    pub status: String,
+    pub data: Option<CanteenWebhook>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AllWebhooksResponse {
+    pub status: String,
+    pub data: Vec<CanteenWebhook>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct WebhookDeliveriesQuery {
+    /// Only deliveries in this status: `pending`, `delivered` or `failed`.
+    pub status: Option<String>,
+    /// Most recent deliveries to return, at most 200; defaults to 50.
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhookDeliveriesResponse {
+    pub status: String,
+    pub data: Vec<WebhookDelivery>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AllPromoCodesResponse {
+    pub status: String,
+    pub data: Vec<PromoCodeDetails>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MenuItemScheduleRequest {
+    pub windows: Vec<ScheduleWindow>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MenuItemScheduleResponse {
+    pub status: String,
+    pub data: Vec<ScheduleWindow>,
+    pub error: Option<String>,
 }
 
 // ---------- CANTEEN ---------- //
@@ -1,8 +1,9 @@
+use crate::models::admin::PricingRuleKindEnum;
 use crate::models::common::{OrderItems, TimeBandEnum};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use with_pic_macro::{with_pic, WithPic};
 
 pub type TimedActiveItemCount = HashMap<String, Vec<ActiveItemCount>>;
@@ -31,6 +32,8 @@ pub struct ItemContainer {
     pub pic_link: Option<String>,
     pub pic_etag: Option<String>,
     pub description: Option<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[with_pic(OrderItems)]
@@ -49,6 +52,8 @@ pub struct OrderItemsWithPic {
     pub pic_link: Option<String>,
     pub pic_etag: Option<String>,
     pub description: Option<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[derive(Serialize, ToSchema, Debug)]
@@ -75,10 +80,19 @@ pub struct OrderItemsResponse {
     pub error: Option<String>,
 }
 
-#[derive(Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct OrderRequest {
     pub deliver_at: Option<String>,
-    pub item_ids: Vec<i32>,
+    /// Items of one canteen, repeated once per unit. Give either these or `saved_cart_id`.
+    pub item_ids: Option<Vec<i32>>,
+    /// One of the user's saved carts to hold instead of `item_ids`.
+    #[serde(default)]
+    pub saved_cart_id: Option<i32>,
+    #[serde(default)]
+    pub promo_code: Option<String>,
+    /// A note for the kitchen, printed on the order ticket; at most 200 characters.
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -87,11 +101,341 @@ pub struct OrderResponse {
     pub error: Option<String>,
 }
 
+/// A pricing rule that lowered the price of a hold, with the total amount it took off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AppliedPricingRule {
+    pub rule_id: i32,
+    pub name: String,
+    pub kind: PricingRuleKindEnum,
+    pub discount: i32,
+}
+
+/// A promo code redeemed on a hold, with the amount it took off after pricing rules.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AppliedPromoCode {
+    pub code: String,
+    pub discount: i32,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct HoldOrderResponse {
     pub status: String,
     pub hold_id: Option<i32>,
     pub expires_at: Option<i64>,
+    /// Amount to pay after pricing rules and the promo code.
+    pub total_price: Option<i32>,
+    pub applied_rules: Vec<AppliedPricingRule>,
+    pub applied_promo: Option<AppliedPromoCode>,
+    pub error: Option<String>,
+}
+
+/// Options for holds made from something other than a list of items, such as a past order
+/// or the user's cart.
+#[derive(Deserialize, ToSchema, Debug, Default)]
+pub struct HoldOptions {
+    #[serde(default)]
+    pub deliver_at: Option<String>,
+    #[serde(default)]
+    pub promo_code: Option<String>,
+    /// A note for the kitchen, printed on the order ticket; at most 200 characters.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct UnavailableItem {
+    pub item_id: i32,
+    /// `None` for items no longer on the menu.
+    pub name: Option<String>,
+    pub reason: String,
+}
+
+/// An item of a past order held in a smaller quantity because there is not enough stock left.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ReducedItem {
+    pub item_id: i32,
+    pub name: String,
+    pub ordered_quantity: i32,
+    pub held_quantity: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReorderResponse {
+    pub status: String,
+    pub hold_id: Option<i32>,
+    pub expires_at: Option<i64>,
+    /// Amount to pay after pricing rules and the promo code.
+    pub total_price: Option<i32>,
+    /// What the held items cost in the past order, in the held quantities. Missing for orders
+    /// finished before item prices were kept.
+    pub original_price: Option<i32>,
+    /// `total_price - original_price`, over the held items only; reduced and left-out items
+    /// are listed below rather than counted.
+    pub price_difference: Option<i32>,
+    pub applied_rules: Vec<AppliedPricingRule>,
+    pub applied_promo: Option<AppliedPromoCode>,
+    /// Items of the past order held in a smaller quantity, capped at the stock left.
+    pub reduced_items: Vec<ReducedItem>,
+    /// Items of the past order that cannot be ordered now and were left out of the hold.
+    pub unavailable_items: Vec<UnavailableItem>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewRequest {
+    /// The order as a whole, 1 to 5.
+    pub rating: i16,
+    /// At most 1000 characters.
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Ratings of items in the order; any subset of them.
+    #[serde(default)]
+    pub items: Vec<ItemRatingRequest>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ItemRatingRequest {
+    pub item_id: i32,
+    /// 1 to 5.
+    pub rating: i16,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ItemRating {
+    pub item_id: i32,
+    pub name: String,
+    pub rating: i16,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Review {
+    pub review_id: i32,
+    pub order_id: i32,
+    pub canteen_id: i32,
+    pub rating: i16,
+    pub comment: Option<String>,
+    pub items: Vec<ItemRating>,
+    /// Hidden by the canteen; only its admins see hidden reviews.
+    pub is_hidden: bool,
+    /// The canteen's reply.
+    pub reply: Option<String>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub replied_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReviewResponse {
+    pub status: String,
+    pub data: Option<Review>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReviewsResponse {
+    pub status: String,
+    pub data: Vec<Review>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewModerationRequest {
+    pub is_hidden: bool,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewReplyRequest {
+    /// At most 1000 characters; null or blank removes the reply.
+    pub reply: Option<String>,
+}
+
+/// What a support ticket is about.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SupportTicketCategory {
+    WrongItem,
+    MissingItem,
+    Quality,
+    Late,
+    Other,
+}
+
+impl SupportTicketCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupportTicketCategory::WrongItem => "wrong_item",
+            SupportTicketCategory::MissingItem => "missing_item",
+            SupportTicketCategory::Quality => "quality",
+            SupportTicketCategory::Late => "late",
+            SupportTicketCategory::Other => "other",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "wrong_item" => Some(SupportTicketCategory::WrongItem),
+            "missing_item" => Some(SupportTicketCategory::MissingItem),
+            "quality" => Some(SupportTicketCategory::Quality),
+            "late" => Some(SupportTicketCategory::Late),
+            "other" => Some(SupportTicketCategory::Other),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SupportTicketStatus {
+    /// Waiting for the canteen.
+    Open,
+    /// The canteen has replied.
+    InProgress,
+    /// The canteen has settled it; a new message from the user reopens it.
+    Resolved,
+    /// Closed for good; no more messages.
+    Closed,
+}
+
+impl SupportTicketStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupportTicketStatus::Open => "open",
+            SupportTicketStatus::InProgress => "in_progress",
+            SupportTicketStatus::Resolved => "resolved",
+            SupportTicketStatus::Closed => "closed",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "open" => Some(SupportTicketStatus::Open),
+            "in_progress" => Some(SupportTicketStatus::InProgress),
+            "resolved" => Some(SupportTicketStatus::Resolved),
+            "closed" => Some(SupportTicketStatus::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CreateSupportTicketRequest {
+    /// An active or past order of the user.
+    pub order_id: i32,
+    pub category: SupportTicketCategory,
+    /// What went wrong; at most 2000 characters.
+    pub message: String,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SupportMessageRequest {
+    /// At most 2000 characters.
+    pub message: String,
+    /// Keys from `POST /support/tickets/{ticket_id}/attachments` of files already uploaded;
+    /// at most 5.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SupportTicketStatusRequest {
+    pub status: SupportTicketStatus,
+    /// Paisa to refund of the order's online payment, only when resolving. Refunds of one
+    /// payment add up to at most what was paid.
+    #[serde(default)]
+    pub refund_amount: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct SupportTicketQuery {
+    /// Only tickets in this status: `open`, `in_progress`, `resolved` or `closed`.
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SupportTicket {
+    pub ticket_id: i32,
+    pub user_id: i32,
+    pub canteen_id: i32,
+    pub order_id: i32,
+    pub category: SupportTicketCategory,
+    pub status: SupportTicketStatus,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub updated_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SupportAttachment {
+    pub key: String,
+    /// Download link, valid for 12 hours.
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SupportMessage {
+    pub message_id: i32,
+    /// `user` or `canteen`.
+    pub author: String,
+    pub body: String,
+    pub attachments: Vec<SupportAttachment>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Refund {
+    pub refund_id: i32,
+    /// In paisa.
+    pub amount: i32,
+    /// `PENDING` until PhonePe settles it as `COMPLETED` or `FAILED`.
+    pub state: String,
+    pub failure_reason: Option<String>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SupportTicketDetail {
+    pub ticket: SupportTicket,
+    /// Oldest first.
+    pub messages: Vec<SupportMessage>,
+    pub refunds: Vec<Refund>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SupportTicketResponse {
+    pub status: String,
+    pub data: Option<SupportTicketDetail>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SupportTicketsResponse {
+    pub status: String,
+    pub data: Vec<SupportTicket>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SupportAttachmentUploadResponse {
+    pub status: String,
+    /// Presigned URL to `PUT` the file to, valid for 5 minutes.
+    pub upload_url: Option<String>,
+    /// Send this with the message the file belongs to.
+    pub attachment_key: Option<String>,
     pub error: Option<String>,
 }
 
@@ -107,6 +451,12 @@ pub struct ScanQrRequest {
     pub token: String,
 }
 
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct TicketQuery {
+    /// `escpos` (the default) for raw printer commands, `text` or `html`.
+    pub format: Option<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ScanQrResponse {
     pub status: String,
@@ -150,3 +500,94 @@ pub struct WebhookPaymentResponse {
     pub status: String,
     pub error: Option<String>,
 }
+
+/// Streams a realtime WebSocket can subscribe to, named like their SSE counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RealtimeChannel {
+    /// The user's own order updates; users only.
+    UserOrders,
+    /// The canteen's aggregated order updates; admins only.
+    CanteenOrders,
+    /// Inventory updates of any canteen.
+    CanteenInventory,
+}
+
+impl RealtimeChannel {
+    pub fn from_stream(stream: &str) -> Option<Self> {
+        match stream {
+            "user_orders" => Some(RealtimeChannel::UserOrders),
+            "canteen_orders" => Some(RealtimeChannel::CanteenOrders),
+            "canteen_inventory" => Some(RealtimeChannel::CanteenInventory),
+            _ => None,
+        }
+    }
+}
+
+/// A text frame sent by the client. `ref` is echoed on the reply so clients can match them.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RealtimeRequest {
+    #[serde(rename = "ref", default)]
+    pub request_ref: Option<String>,
+    #[serde(flatten)]
+    pub command: RealtimeCommand,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealtimeCommand {
+    /// Start receiving a stream. `id` defaults to the caller's own user or canteen and is
+    /// required for `canteen_inventory`. Events after `last_event_id`, or after the last
+    /// acknowledged event of this stream on this socket, are replayed first.
+    Subscribe {
+        channel: RealtimeChannel,
+        #[serde(default)]
+        id: Option<i32>,
+        #[serde(default)]
+        last_event_id: Option<u64>,
+    },
+    Unsubscribe {
+        channel: RealtimeChannel,
+        #[serde(default)]
+        id: Option<i32>,
+    },
+    /// Events of the stream up to `seq` have been handled.
+    Ack {
+        channel: RealtimeChannel,
+        #[serde(default)]
+        id: Option<i32>,
+        seq: u64,
+    },
+    /// Deliver or cancel one of the canteen's orders; admins only.
+    OrderAction { order_id: i32, action: String },
+}
+
+/// A text frame sent by the server.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RealtimeMessage {
+    Connected,
+    /// An event of a subscribed stream; `seq` is its SSE event id.
+    Event {
+        channel: RealtimeChannel,
+        id: i32,
+        seq: u64,
+        event: String,
+        data: serde_json::Value,
+    },
+    /// `resync`: some events of the stream could not be replayed; refetch its state.
+    Status {
+        channel: RealtimeChannel,
+        id: i32,
+        status: String,
+    },
+    Ok {
+        #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+        request_ref: Option<String>,
+    },
+    Error {
+        #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+        request_ref: Option<String>,
+        error: String,
+    },
+}
@@ -1,8 +1,9 @@
-use crate::enums::common::ItemContainer;
-use crate::models::user::PastOrderItem;
+use crate::enums::admin::{CanteenDetailsWithPic, MenuItemWithPic};
+use crate::enums::common::{ItemContainer, OrderRequest};
+use crate::models::user::{DeviceToken, PastOrderItem};
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use with_pic_macro::{with_pic, WithPic};
 
 #[with_pic(PastOrderItem)]
@@ -20,6 +21,8 @@ pub struct PastOrderItemWithPic {
     pub pic_link: Option<String>,
     pub pic_etag: Option<String>,
     pub description: Option<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[derive(Serialize, ToSchema, Debug)]
@@ -38,3 +41,215 @@ pub struct PastOrdersItemResponse {
     pub data: Vec<PastOrderItemContainer>,
     pub error: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UserPreferences {
+    /// Allergens to avoid; menu and search results flag or hide items containing them.
+    pub allergen_exclusions: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserPreferencesResponse {
+    pub status: String,
+    pub data: Option<UserPreferences>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterDeviceRequest {
+    /// FCM registration token of the device.
+    pub token: String,
+    /// `android`, `ios` or `web`.
+    pub platform: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeviceTokenResponse {
+    pub status: String,
+    pub data: Option<DeviceToken>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+pub struct RecommendationsQuery {
+    /// Only consider items and orders of this canteen.
+    pub canteen_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Recommendations {
+    /// The items the user orders most often.
+    pub most_ordered: Vec<MenuItemWithPic>,
+    /// Items other users often order together with the user's favourites.
+    pub also_ordered: Vec<MenuItemWithPic>,
+    /// What sells most at the canteen around this time of day; empty without a canteen.
+    pub popular_now: Vec<MenuItemWithPic>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecommendationsResponse {
+    pub status: String,
+    pub data: Option<Recommendations>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct UsualOrderLine {
+    pub item_id: i32,
+    pub name: String,
+    pub quantity: i16,
+    /// Current price of one item.
+    pub price: i32,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct UsualOrder {
+    /// Body for `POST /orders/hold` with the items that can be ordered now.
+    pub hold_request: OrderRequest,
+    pub lines: Vec<UsualOrderLine>,
+    /// Total at current prices, before pricing rules and promo codes.
+    pub total_price: i32,
+    /// Items of the usual order that are off the menu or out of stock.
+    pub unavailable_item_ids: Vec<i32>,
+    /// How many of the user's latest delivered orders were exactly this.
+    pub times_ordered: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsualOrderResponse {
+    pub status: String,
+    pub data: Option<UsualOrder>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Favourites {
+    pub items: Vec<MenuItemWithPic>,
+    pub canteens: Vec<CanteenDetailsWithPic>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FavouritesResponse {
+    pub status: String,
+    pub data: Option<Favourites>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FavouriteResponse {
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SavedCartRequest {
+    /// e.g. "Tuesday lunch"; unique per user, ignoring case.
+    pub name: String,
+    /// Items of one canteen, repeated once per unit like in `POST /orders/hold`.
+    pub item_ids: Vec<i32>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SavedCartLine {
+    pub item_id: i32,
+    pub name: String,
+    pub quantity: i16,
+    /// Current price of one item.
+    pub price: i32,
+    /// Whether the item is on the menu with enough stock for this line.
+    pub is_available: bool,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct SavedCart {
+    /// Pass as `saved_cart_id` to `POST /orders/hold` to hold this cart.
+    pub cart_id: i32,
+    pub canteen_id: i32,
+    pub name: String,
+    pub lines: Vec<SavedCartLine>,
+    /// Total at current prices, before pricing rules and promo codes.
+    pub total_price: i32,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SavedCartResponse {
+    pub status: String,
+    pub data: Option<SavedCart>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SavedCartsResponse {
+    pub status: String,
+    pub data: Vec<SavedCart>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CartItemRequest {
+    pub item_id: i32,
+    /// Units to add to what is already in the cart.
+    pub quantity: i16,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CartQuantityRequest {
+    /// The new number of units; zero removes the item.
+    pub quantity: i16,
+}
+
+/// How a cart line compares with the menu now.
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CartLineStatus {
+    Ok,
+    /// The price is not the one the item was added at; the cart is still orderable.
+    PriceChanged,
+    /// There is stock, but less than the quantity in the cart.
+    InsufficientStock,
+    /// The item is off the menu for now.
+    Unavailable,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct CartLine {
+    pub item_id: i32,
+    pub name: String,
+    pub quantity: i16,
+    /// Current price of one item.
+    pub unit_price: i32,
+    /// Price of one item when it was added or its quantity last changed.
+    pub added_price: i32,
+    /// `None` when the item's stock is not tracked.
+    pub available_stock: Option<i32>,
+    pub status: CartLineStatus,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Cart {
+    pub canteen_id: i32,
+    pub lines: Vec<CartLine>,
+    /// Total at current prices, before pricing rules and promo codes.
+    pub total_price: i32,
+    /// Whether every line can be ordered as it is; an empty cart is not orderable.
+    pub is_orderable: bool,
+    /// When the cart last changed; `None` for an empty cart.
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CartResponse {
+    pub status: String,
+    pub data: Option<Cart>,
+    pub error: Option<String>,
+}
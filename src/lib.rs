@@ -12,27 +12,49 @@ pub mod test_utils;
 pub mod traits;
 
 use crate::db::{
-    establish_connection_pool, run_db_migrations, AssetOperations, CanteenOperations,
-    HoldOperations, MenuOperations, OrderOperations, PaymentOperations, SearchOperations,
-    UserOperations,
+    establish_connection_pool, run_db_migrations, AnalyticsOperations, AssetOperations,
+    CanteenOperations, CartOperations, DeviceOperations, ExportOperations, FavouriteOperations,
+    HoldOperations, MenuOperations, OrderOperations, OutboxOperations, PaymentOperations,
+    PricingOperations, PromoOperations, RecommendationOperations, ReviewOperations,
+    SearchOperations, SettlementOperations, SupportOperations, UserOperations, WebhookOperations,
 };
 use crate::services::canteen_scheduler::CanteenSchedulerNotifier;
+use crate::services::email::MailSender;
+use crate::services::outbox::{OutboxConfig, OutboxDispatcher};
 use crate::services::phonepe::PhonePeClient;
+use crate::services::push::PushNotifier;
+use crate::services::refunds::RefundSender;
+use crate::services::webhooks::{WebhookConfig, WebhookDispatcher};
 use crate::sse::SseBroker;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_ops: UserOperations,
+    pub device_ops: DeviceOperations,
+    pub favourite_ops: FavouriteOperations,
+    pub cart_ops: CartOperations,
     pub menu_ops: MenuOperations,
     pub canteen_ops: CanteenOperations,
     pub order_ops: OrderOperations,
     pub hold_ops: HoldOperations,
     pub payment_ops: PaymentOperations,
     pub search_ops: SearchOperations,
+    pub recommendation_ops: RecommendationOperations,
+    pub review_ops: ReviewOperations,
+    pub analytics_ops: AnalyticsOperations,
+    pub export_ops: ExportOperations,
+    pub settlement_ops: SettlementOperations,
+    pub support_ops: SupportOperations,
+    pub pricing_ops: PricingOperations,
+    pub promo_ops: PromoOperations,
     pub asset_ops: AssetOperations,
+    pub webhook_ops: WebhookOperations,
     pub canteen_scheduler: CanteenSchedulerNotifier,
     pub sse_broker: SseBroker,
     pub phonepe_client: PhonePeClient,
+    pub push_notifier: PushNotifier,
+    pub webhook_dispatcher: WebhookDispatcher,
+    pub outbox_dispatcher: OutboxDispatcher,
 }
 
 impl AppState {
@@ -50,27 +72,68 @@ impl AppState {
             .expect("Unable to create asset_ops");
 
         let user_ops = UserOperations::new(db.clone(), asset_ops.clone()).await;
+        let device_ops = DeviceOperations::new(db.clone()).await;
+        let favourite_ops = FavouriteOperations::new(db.clone()).await;
+        let cart_ops = CartOperations::new(db.clone()).await;
         let menu_ops = MenuOperations::new(db.clone(), asset_ops.clone()).await;
         let canteen_ops = CanteenOperations::new(db.clone(), asset_ops.clone()).await;
         let order_ops = OrderOperations::new(db.clone()).await;
         let hold_ops = HoldOperations::new(db.clone(), hold_ttl_secs);
         let payment_ops = PaymentOperations::new(db.clone()).await;
         let search_ops = SearchOperations::new(db.clone()).await;
+        let recommendation_ops = RecommendationOperations::new(db.clone()).await;
+        let review_ops = ReviewOperations::new(db.clone()).await;
+        let analytics_ops = AnalyticsOperations::new(db.clone()).await;
+        let export_ops = ExportOperations::new(db.clone()).await;
+        let settlement_ops = SettlementOperations::new(db.clone()).await;
+        let support_ops = SupportOperations::new(db.clone(), asset_ops.clone()).await;
+        let pricing_ops = PricingOperations::new(db.clone()).await;
+        let promo_ops = PromoOperations::new(db.clone()).await;
+        let webhook_cfg = WebhookConfig::from_env();
+        let webhook_ops =
+            WebhookOperations::new(db.clone(), webhook_cfg.allow_private_targets).await;
         let canteen_scheduler = CanteenSchedulerNotifier::new();
-        let sse_broker = SseBroker::new();
+        let sse_broker = SseBroker::from_env(db.clone(), url);
         let phonepe_client = PhonePeClient::from_env().expect("Unable to create PhonePe client");
+        let push_notifier =
+            PushNotifier::from_env(device_ops.clone()).expect("Unable to create push notifier");
+        let webhook_dispatcher = WebhookDispatcher::start(webhook_ops.clone(), webhook_cfg);
+        let outbox_dispatcher = OutboxDispatcher::start(
+            OutboxOperations::new(db.clone()).await,
+            sse_broker.clone(),
+            push_notifier.clone(),
+            webhook_dispatcher.clone(),
+            RefundSender::new(phonepe_client.clone(), payment_ops.clone()),
+            MailSender::from_env().expect("Unable to create mail transport"),
+            OutboxConfig::from_env(),
+        );
         AppState {
             user_ops,
+            device_ops,
+            favourite_ops,
+            cart_ops,
             menu_ops,
             canteen_ops,
             order_ops,
             hold_ops,
             payment_ops,
             search_ops,
+            recommendation_ops,
+            review_ops,
+            analytics_ops,
+            export_ops,
+            settlement_ops,
+            support_ops,
+            pricing_ops,
+            promo_ops,
             asset_ops,
+            webhook_ops,
             canteen_scheduler,
             sse_broker,
             phonepe_client,
+            push_notifier,
+            webhook_dispatcher,
+            outbox_dispatcher,
         }
     }
 }
@@ -5,7 +5,9 @@ extern crate pretty_env_logger;
 use actix_web::{middleware, web, App, HttpServer};
 use dotenvy::dotenv;
 use proj_xs::api::default_error_handler;
-use proj_xs::auth::{AdminJwtConfig, AuthLayer, FirebaseAuthConfig, JwksCache};
+use proj_xs::auth::{
+    AdminJwtConfig, AuthLayer, FirebaseAuthConfig, JwksCache, PlatformOperatorConfig,
+};
 use proj_xs::{api, AppState};
 use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
 use utoipa::{Modify, OpenApi};
@@ -74,6 +76,7 @@ async fn main() -> std::io::Result<()> {
     // Auth config
     let fb_cfg = FirebaseAuthConfig::from_env();
     let admin_cfg = AdminJwtConfig::from_env();
+    let operator_cfg = PlatformOperatorConfig::from_env();
     let jwks_cache = JwksCache::new(fb_cfg.jwks_url.clone(), fb_cfg.cache_ttl_secs);
 
     // QR config
@@ -87,9 +90,9 @@ async fn main() -> std::io::Result<()> {
     // Spawn background task to clean up expired holds
     {
         let hold_ops = state.hold_ops.clone();
-        let sse_broker = state.sse_broker.clone();
+        let outbox_dispatcher = state.outbox_dispatcher.clone();
         tokio::spawn(async move {
-            proj_xs::services::hold_cleanup::run_hold_cleanup(hold_ops, sse_broker).await;
+            proj_xs::services::hold_cleanup::run_hold_cleanup(hold_ops, outbox_dispatcher).await;
         });
     }
 
@@ -104,6 +107,32 @@ async fn main() -> std::io::Result<()> {
         });
     }
 
+    // Spawn background task to reset daily stock and apply menu availability schedules
+    {
+        let menu_ops = state.menu_ops.clone();
+        let canteen_ops = state.canteen_ops.clone();
+        let sse_broker = state.sse_broker.clone();
+        let tz = proj_xs::services::canteen_hours::parse_tz_offset_from_env();
+        tokio::spawn(async move {
+            proj_xs::services::menu_scheduler::run_menu_scheduler(
+                menu_ops,
+                canteen_ops,
+                tz,
+                sse_broker,
+            )
+            .await;
+        });
+    }
+
+    // Spawn background task to generate the daily settlement statements of the canteens
+    {
+        let settlement_ops = state.settlement_ops.clone();
+        let cfg = proj_xs::services::settlement::SettlementConfig::from_env();
+        tokio::spawn(async move {
+            proj_xs::services::settlement::run_settlements(settlement_ops, cfg).await;
+        });
+    }
+
     // Server configuration
     const HOST: &str = if cfg!(debug_assertions) {
         "127.0.0.1"
@@ -140,6 +169,7 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(jwks_cache.clone()))
             .app_data(web::Data::new(state.user_ops.clone()))
             .app_data(web::Data::new(admin_cfg.clone()))
+            .app_data(web::Data::new(operator_cfg.clone()))
             .app_data(web::JsonConfig::default().error_handler(default_error_handler))
             .openapi_service(|api| {
                 let base_cfg = Config::default().persist_authorization(true);
@@ -1,6 +1,14 @@
-use chrono::{DateTime, NaiveTime, Utc};
-use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
-use serde::{Deserialize, Serialize};
+use crate::db::schema::sql_types::PricingRuleKind;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use diesel::deserialize::FromSql;
+use diesel::serialize::{IsNull, Output, ToSql};
+use diesel::{
+    serialize, AsChangeset, AsExpression, FromSqlRow, Identifiable, Insertable, Queryable,
+    Selectable,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::io::Write;
+use std::net::IpAddr;
 use utoipa::ToSchema;
 
 #[derive(Queryable, Debug, Identifiable, Serialize, Deserialize)]
@@ -18,6 +26,9 @@ pub struct Canteen {
     pub is_open: bool,
     pub last_opened_at: Option<DateTime<Utc>>,
     pub pic_key: Option<String>,
+    pub last_stock_reset_on: Option<NaiveDate>,
+    pub average_rating: Option<f64>,
+    pub rating_count: i32,
 }
 
 #[derive(Queryable, Debug, Identifiable, Selectable, Serialize, Deserialize)]
@@ -32,6 +43,8 @@ pub struct CanteenDetails {
     pub opening_time: Option<NaiveTime>,
     pub closing_time: Option<NaiveTime>,
     pub is_open: bool,
+    pub average_rating: Option<f64>,
+    pub rating_count: i32,
 }
 
 #[derive(Insertable, Debug, Serialize, Deserialize, ToSchema)]
@@ -71,6 +84,17 @@ pub struct MenuItem {
     pub description: Option<String>,
     pub pic_etag: Option<String>,
     pub pic_key: Option<String>,
+    pub default_stock: Option<i32>,
+    pub category_id: Option<i32>,
+    pub display_order: i32,
+    pub is_pinned: bool,
+    pub tags: Vec<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
+    pub average_rating: Option<f64>,
+    pub rating_count: i32,
+    /// Switched off by hand; schedules and the daily stock reset leave it off.
+    pub manually_disabled: bool,
 }
 
 #[derive(Insertable, Debug, Serialize, Deserialize, Selectable)]
@@ -83,6 +107,13 @@ pub struct NewMenuItem {
     pub stock: i32,
     pub is_available: bool,
     pub description: Option<String>,
+    pub default_stock: Option<i32>,
+    pub category_id: Option<i32>,
+    pub display_order: i32,
+    pub is_pinned: bool,
+    pub tags: Vec<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[derive(Debug, Selectable, Queryable)]
@@ -106,10 +137,365 @@ pub struct UpdateMenuItem {
     pub stock: Option<i32>,
     pub is_available: Option<bool>,
     pub description: Option<String>,
+    /// `null` stops the daily stock reset for the item; omit the field to leave it unchanged.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<i32>)]
+    pub default_stock: Option<Option<i32>>,
+    /// `null` moves the item out of its category; omit the field to leave it unchanged.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<i32>)]
+    pub category_id: Option<Option<i32>>,
+    pub display_order: Option<i32>,
+    pub is_pinned: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub allergens: Option<Vec<String>>,
+    pub diet_labels: Option<Vec<String>>,
 }
 
+/// Lets `Option<Option<T>>` fields tell an explicit `null` apart from a missing field.
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::menu_categories)]
+#[diesel(primary_key(category_id))]
+pub struct MenuCategory {
+    pub category_id: i32,
+    pub canteen_id: i32,
+    pub name: String,
+    pub display_order: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::menu_categories)]
+pub struct NewMenuCategory {
+    pub canteen_id: i32,
+    pub name: String,
+    pub display_order: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, AsChangeset, ToSchema)]
+#[serde(deny_unknown_fields)]
+#[diesel(table_name = crate::db::schema::menu_categories)]
+pub struct UpdateMenuCategory {
+    pub name: Option<String>,
+    pub display_order: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::menu_item_schedules)]
+#[diesel(primary_key(schedule_id))]
+pub struct MenuItemSchedule {
+    pub schedule_id: i32,
+    pub item_id: i32,
+    pub weekday: i16,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::menu_item_schedules)]
+pub struct NewMenuItemSchedule {
+    pub item_id: i32,
+    pub weekday: i16,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+/// A weekly availability window. `weekday` is 0 = Monday through 6 = Sunday.
+/// A window with `end_time` before `start_time` runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleWindow {
+    pub weekday: i16,
+    #[schema(value_type = String, format = "time")]
+    pub start_time: NaiveTime,
+    #[schema(value_type = String, format = "time")]
+    pub end_time: NaiveTime,
+}
+
+impl From<&MenuItemSchedule> for ScheduleWindow {
+    fn from(src: &MenuItemSchedule) -> Self {
+        Self {
+            weekday: src.weekday,
+            start_time: src.start_time,
+            end_time: src.end_time,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize, ToSchema,
+)]
+#[diesel(sql_type = PricingRuleKind)]
+#[serde(rename_all = "snake_case")]
+pub enum PricingRuleKindEnum {
+    /// Percentage off every unit of the covered items.
+    PercentOff,
+    /// Fixed amount off every unit of the covered items.
+    FlatOff,
+    /// One unit of each listed item sold together for `combo_price`.
+    Combo,
+}
+
+impl ToSql<PricingRuleKind, diesel::pg::Pg> for PricingRuleKindEnum {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, diesel::pg::Pg>) -> serialize::Result {
+        match *self {
+            PricingRuleKindEnum::PercentOff => out.write_all(b"percent_off")?,
+            PricingRuleKindEnum::FlatOff => out.write_all(b"flat_off")?,
+            PricingRuleKindEnum::Combo => out.write_all(b"combo")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<PricingRuleKind, diesel::pg::Pg> for PricingRuleKindEnum {
+    fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"percent_off" => Ok(PricingRuleKindEnum::PercentOff),
+            b"flat_off" => Ok(PricingRuleKindEnum::FlatOff),
+            b"combo" => Ok(PricingRuleKindEnum::Combo),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::pricing_rules)]
+#[diesel(primary_key(rule_id))]
+pub struct PricingRule {
+    pub rule_id: i32,
+    pub canteen_id: i32,
+    pub name: String,
+    pub kind: PricingRuleKindEnum,
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    pub combo_price: Option<i32>,
+    /// Items the rule covers; empty means the whole menu for discounts.
+    pub item_ids: Vec<i32>,
+    pub is_active: bool,
+}
+
+/// Used both to create a rule and to replace one wholesale.
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::pricing_rules)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewPricingRule {
+    pub canteen_id: i32,
+    pub name: String,
+    pub kind: PricingRuleKindEnum,
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    pub combo_price: Option<i32>,
+    pub item_ids: Vec<i32>,
+    pub is_active: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::pricing_rule_windows)]
+#[diesel(primary_key(window_id))]
+pub struct PricingRuleWindow {
+    pub window_id: i32,
+    pub rule_id: i32,
+    pub weekday: i16,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::pricing_rule_windows)]
+pub struct NewPricingRuleWindow {
+    pub rule_id: i32,
+    pub weekday: i16,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+impl From<&PricingRuleWindow> for ScheduleWindow {
+    fn from(src: &PricingRuleWindow) -> Self {
+        Self {
+            weekday: src.weekday,
+            start_time: src.start_time,
+            end_time: src.end_time,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::promo_codes)]
+#[diesel(primary_key(promo_id))]
+pub struct PromoCode {
+    pub promo_id: i32,
+    pub code: String,
+    /// `None` for platform-wide codes.
+    pub canteen_id: Option<i32>,
+    pub description: Option<String>,
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    pub max_discount: Option<i32>,
+    pub min_order_total: i32,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses_total: Option<i32>,
+    pub max_uses_per_user: Option<i32>,
+    pub first_order_only: bool,
+    pub is_active: bool,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl PromoCode {
+    /// Amount the code takes off an order of `order_total`, never more than the total.
+    pub fn discount_for(&self, order_total: i32) -> i32 {
+        let discount = match (self.percent_off, self.amount_off) {
+            (Some(percent), _) => {
+                let raw = (order_total as i64 * percent as i64 / 100) as i32;
+                self.max_discount.map_or(raw, |cap| raw.min(cap))
+            }
+            (None, Some(amount)) => amount,
+            (None, None) => 0,
+        };
+        discount.clamp(0, order_total.max(0))
+    }
+}
+
+/// Used both to create a code and to replace one wholesale.
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::promo_codes)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewPromoCode {
+    pub code: String,
+    pub canteen_id: Option<i32>,
+    pub description: Option<String>,
+    pub percent_off: Option<i32>,
+    pub amount_off: Option<i32>,
+    pub max_discount: Option<i32>,
+    pub min_order_total: i32,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses_total: Option<i32>,
+    pub max_uses_per_user: Option<i32>,
+    pub first_order_only: bool,
+    pub is_active: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::canteen_webhooks)]
+#[diesel(primary_key(webhook_id))]
+pub struct CanteenWebhook {
+    pub webhook_id: i32,
+    pub canteen_id: i32,
+    pub url: String,
+    /// Never sent back once registered.
+    #[serde(skip)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub is_active: bool,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::canteen_webhooks)]
+pub struct NewCanteenWebhook {
+    pub canteen_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub is_active: bool,
+}
+
+/// Replaces a webhook; the secret is kept when `None`.
+#[derive(AsChangeset, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::canteen_webhooks)]
+pub struct UpdateCanteenWebhook {
+    pub url: String,
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+    pub is_active: bool,
+}
+
+pub const WEBHOOK_DELIVERY_PENDING: &str = "pending";
+pub const WEBHOOK_DELIVERY_DELIVERED: &str = "delivered";
+pub const WEBHOOK_DELIVERY_FAILED: &str = "failed";
+pub const WEBHOOK_DELIVERY_STATUSES: [&str; 3] = [
+    WEBHOOK_DELIVERY_PENDING,
+    WEBHOOK_DELIVERY_DELIVERED,
+    WEBHOOK_DELIVERY_FAILED,
+];
+pub const WEBHOOK_DELIVERIES_DEFAULT_LIMIT: i64 = 50;
+pub const WEBHOOK_DELIVERIES_MAX_LIMIT: i64 = 200;
+
+/// One event sent, or to be sent, to one webhook.
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::webhook_deliveries)]
+#[diesel(primary_key(delivery_id))]
+pub struct WebhookDelivery {
+    pub delivery_id: i64,
+    pub webhook_id: i32,
+    pub event_id: String,
+    pub event_type: String,
+    /// The JSON body sent to the webhook.
+    pub payload: String,
+    /// `pending`, `delivered` or `failed` once retries are exhausted.
+    pub status: String,
+    pub attempts: i32,
+    #[schema(value_type = String, format = "date-time")]
+    pub next_attempt_at: DateTime<Utc>,
+    /// HTTP status of the last attempt, if the webhook answered.
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+pub const MENU_ITEM_SCHEDULE_MAX_WINDOWS: usize = 28;
+
 pub const MENU_ITEM_NAME_MAX_LEN: usize = 120;
 pub const MENU_ITEM_DESC_MAX_LEN: usize = 500;
+pub const MENU_CATEGORY_NAME_MAX_LEN: usize = 60;
+pub const MENU_ITEM_TAG_MAX_LEN: usize = 32;
+pub const MENU_ITEM_MAX_TAGS: usize = 10;
+pub const PRICING_RULE_NAME_MAX_LEN: usize = 60;
+pub const PRICING_RULE_MAX_ITEMS: usize = 50;
+pub const COMBO_MAX_UNITS: usize = 10;
+pub const PROMO_CODE_MAX_LEN: usize = 32;
+pub const WEBHOOK_URL_MAX_LEN: usize = 2048;
+pub const WEBHOOK_SECRET_MIN_LEN: usize = 16;
+pub const WEBHOOK_SECRET_MAX_LEN: usize = 256;
+/// Events canteens can subscribe their webhooks to.
+pub const WEBHOOK_EVENT_TYPES: [&str; 4] = [
+    "order.confirmed",
+    "order.cancelled",
+    "order.delivered",
+    "item.stock_out",
+];
+pub const ALLERGENS: [&str; 12] = [
+    "nuts",
+    "peanuts",
+    "dairy",
+    "gluten",
+    "egg",
+    "soy",
+    "fish",
+    "shellfish",
+    "sesame",
+    "mustard",
+    "celery",
+    "sulphites",
+];
+pub const DIET_LABELS: [&str; 5] = ["vegan", "jain", "eggetarian", "halal", "gluten_free"];
 
 fn sanitize_name(name: &str) -> Result<String, String> {
     let trimmed = name.trim();
@@ -156,12 +542,120 @@ fn validate_stock(stock: i32) -> Result<(), String> {
     Ok(())
 }
 
+pub fn sanitize_category_name(name: &str) -> Result<String, String> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return Err("category name must not be empty".to_string());
+    }
+    if trimmed.chars().count() > MENU_CATEGORY_NAME_MAX_LEN {
+        return Err(format!(
+            "category name must be at most {MENU_CATEGORY_NAME_MAX_LEN} characters"
+        ));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Tags are stored lowercase so "Spicy" and "spicy" filter the same way.
+pub fn sanitize_tag(tag: &str) -> Result<String, String> {
+    let normalized = tag.trim().to_lowercase();
+    if normalized.is_empty() {
+        return Err("tag must not be empty".to_string());
+    }
+    if normalized.chars().count() > MENU_ITEM_TAG_MAX_LEN {
+        return Err(format!(
+            "tag must be at most {MENU_ITEM_TAG_MAX_LEN} characters"
+        ));
+    }
+    Ok(normalized)
+}
+
+fn sanitize_tags(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut sanitized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = sanitize_tag(tag)?;
+        if !sanitized.contains(&tag) {
+            sanitized.push(tag);
+        }
+    }
+    if sanitized.len() > MENU_ITEM_MAX_TAGS {
+        return Err(format!(
+            "an item can have at most {MENU_ITEM_MAX_TAGS} tags"
+        ));
+    }
+    Ok(sanitized)
+}
+
+fn sanitize_vocabulary(
+    values: &[String],
+    allowed: &[&str],
+    kind: &str,
+) -> Result<Vec<String>, String> {
+    let mut sanitized: Vec<String> = Vec::with_capacity(values.len());
+    for value in values {
+        let normalized = value.trim().to_lowercase();
+        if !allowed.contains(&normalized.as_str()) {
+            return Err(format!(
+                "unknown {kind} '{}'; expected one of: {}",
+                value.trim(),
+                allowed.join(", ")
+            ));
+        }
+        if !sanitized.contains(&normalized) {
+            sanitized.push(normalized);
+        }
+    }
+    Ok(sanitized)
+}
+
+/// Normalizes allergen names and rejects anything outside [`ALLERGENS`].
+pub fn sanitize_allergens(allergens: &[String]) -> Result<Vec<String>, String> {
+    sanitize_vocabulary(allergens, &ALLERGENS, "allergen")
+}
+
+fn sanitize_diet_labels(labels: &[String]) -> Result<Vec<String>, String> {
+    sanitize_vocabulary(labels, &DIET_LABELS, "diet label")
+}
+
+/// Vegan and jain items are always vegetarian.
+fn validate_diet_labels_for_veg(labels: &[String], is_veg: bool) -> Result<(), String> {
+    match labels.iter().find(|l| *l == "vegan" || *l == "jain") {
+        Some(label) if !is_veg => Err(format!("a {label} item must also be veg")),
+        _ => Ok(()),
+    }
+}
+
+fn validate_schedule_window(window: &ScheduleWindow) -> Result<(), String> {
+    if !(0..=6).contains(&window.weekday) {
+        return Err("weekday must be between 0 (Monday) and 6 (Sunday)".to_string());
+    }
+    if window.start_time == window.end_time {
+        return Err("start_time and end_time cannot be the same".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_schedule_windows(windows: &[ScheduleWindow]) -> Result<(), String> {
+    if windows.len() > MENU_ITEM_SCHEDULE_MAX_WINDOWS {
+        return Err(format!(
+            "schedule must have at most {MENU_ITEM_SCHEDULE_MAX_WINDOWS} windows"
+        ));
+    }
+    windows.iter().try_for_each(validate_schedule_window)
+}
+
 impl NewMenuItem {
     pub fn sanitize_and_validate(mut self) -> Result<Self, String> {
         self.name = sanitize_name(&self.name)?;
         self.description = sanitize_description(&self.description)?;
         validate_price(self.price)?;
         validate_stock(self.stock)?;
+        if let Some(default_stock) = self.default_stock {
+            validate_stock(default_stock)?;
+        }
+        self.tags = sanitize_tags(&self.tags)?;
+        self.allergens = sanitize_allergens(&self.allergens)?;
+        self.diet_labels = sanitize_diet_labels(&self.diet_labels)?;
+        validate_diet_labels_for_veg(&self.diet_labels, self.is_veg)?;
         Ok(self)
     }
 }
@@ -177,7 +671,279 @@ impl UpdateMenuItem {
         if let Some(stock) = self.stock {
             validate_stock(stock)?;
         }
+        if let Some(Some(default_stock)) = self.default_stock {
+            validate_stock(default_stock)?;
+        }
+        if let Some(tags) = self.tags.as_ref() {
+            self.tags = Some(sanitize_tags(tags)?);
+        }
+        if let Some(allergens) = self.allergens.as_ref() {
+            self.allergens = Some(sanitize_allergens(allergens)?);
+        }
+        if let Some(diet_labels) = self.diet_labels.as_ref() {
+            let diet_labels = sanitize_diet_labels(diet_labels)?;
+            if let Some(is_veg) = self.is_veg {
+                validate_diet_labels_for_veg(&diet_labels, is_veg)?;
+            }
+            self.diet_labels = Some(diet_labels);
+        }
+        self.description = sanitize_description(&self.description)?;
+        Ok(self)
+    }
+}
+
+impl NewMenuCategory {
+    pub fn sanitize_and_validate(mut self) -> Result<Self, String> {
+        self.name = sanitize_category_name(&self.name)?;
+        Ok(self)
+    }
+}
+
+impl UpdateMenuCategory {
+    pub fn sanitize_and_validate(mut self) -> Result<Self, String> {
+        if let Some(name) = self.name.as_ref() {
+            self.name = Some(sanitize_category_name(name)?);
+        }
+        Ok(self)
+    }
+}
+
+impl NewPricingRule {
+    pub fn sanitize_and_validate(mut self) -> Result<Self, String> {
+        let trimmed = self.name.trim();
+        if trimmed.is_empty() {
+            return Err("rule name must not be empty".to_string());
+        }
+        if trimmed.chars().count() > PRICING_RULE_NAME_MAX_LEN {
+            return Err(format!(
+                "rule name must be at most {PRICING_RULE_NAME_MAX_LEN} characters"
+            ));
+        }
+        self.name = trimmed.to_string();
+
+        let (needs_percent, needs_amount, needs_combo) = match self.kind {
+            PricingRuleKindEnum::PercentOff => (true, false, false),
+            PricingRuleKindEnum::FlatOff => (false, true, false),
+            PricingRuleKindEnum::Combo => (false, false, true),
+        };
+        for (field, is_set, needed) in [
+            ("percent_off", self.percent_off.is_some(), needs_percent),
+            ("amount_off", self.amount_off.is_some(), needs_amount),
+            ("combo_price", self.combo_price.is_some(), needs_combo),
+        ] {
+            match (is_set, needed) {
+                (false, true) => return Err(format!("{field} is required for this rule kind")),
+                (true, false) => return Err(format!("{field} is not allowed for this rule kind")),
+                _ => {}
+            }
+        }
+        if self.percent_off.is_some_and(|p| !(1..=100).contains(&p)) {
+            return Err("percent_off must be between 1 and 100".to_string());
+        }
+        if self.amount_off.is_some_and(|a| a <= 0) {
+            return Err("amount_off must be greater than 0".to_string());
+        }
+        if self.combo_price.is_some_and(|c| c < 0) {
+            return Err("combo_price must not be negative".to_string());
+        }
+
+        if self.kind == PricingRuleKindEnum::Combo {
+            // Repeated ids stand for several units of the same item.
+            if !(2..=COMBO_MAX_UNITS).contains(&self.item_ids.len()) {
+                return Err(format!(
+                    "a combo must have between 2 and {COMBO_MAX_UNITS} items"
+                ));
+            }
+            self.item_ids.sort_unstable();
+        } else {
+            self.item_ids.sort_unstable();
+            self.item_ids.dedup();
+            if self.item_ids.len() > PRICING_RULE_MAX_ITEMS {
+                return Err(format!(
+                    "a rule can cover at most {PRICING_RULE_MAX_ITEMS} items"
+                ));
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Promo codes are matched case-insensitively and stored uppercase.
+pub fn sanitize_promo_code(code: &str) -> Result<String, String> {
+    let normalized = code.trim().to_uppercase();
+    if normalized.is_empty() {
+        return Err("promo code must not be empty".to_string());
+    }
+    if normalized.chars().count() > PROMO_CODE_MAX_LEN {
+        return Err(format!(
+            "promo code must be at most {PROMO_CODE_MAX_LEN} characters"
+        ));
+    }
+    if !normalized
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("promo code may only contain letters, digits, '-' and '_'".to_string());
+    }
+    Ok(normalized)
+}
+
+impl NewPromoCode {
+    pub fn sanitize_and_validate(mut self) -> Result<Self, String> {
+        self.code = sanitize_promo_code(&self.code)?;
         self.description = sanitize_description(&self.description)?;
+        match (self.percent_off, self.amount_off) {
+            (Some(percent), None) => {
+                if !(1..=100).contains(&percent) {
+                    return Err("percent_off must be between 1 and 100".to_string());
+                }
+            }
+            (None, Some(amount)) => {
+                if amount <= 0 {
+                    return Err("amount_off must be greater than 0".to_string());
+                }
+                if self.max_discount.is_some() {
+                    return Err("max_discount only applies to percent_off codes".to_string());
+                }
+            }
+            _ => return Err("exactly one of percent_off and amount_off must be set".to_string()),
+        }
+        if self.max_discount.is_some_and(|cap| cap <= 0) {
+            return Err("max_discount must be greater than 0".to_string());
+        }
+        if self.min_order_total < 0 {
+            return Err("min_order_total must not be negative".to_string());
+        }
+        if let (Some(from), Some(until)) = (self.valid_from, self.valid_until) {
+            if from >= until {
+                return Err("valid_from must be before valid_until".to_string());
+            }
+        }
+        if self.max_uses_total.is_some_and(|uses| uses <= 0)
+            || self.max_uses_per_user.is_some_and(|uses| uses <= 0)
+        {
+            return Err("usage limits must be greater than 0".to_string());
+        }
+        Ok(self)
+    }
+}
+
+/// Webhooks are called from the server, so they need an absolute http(s) URL, and one that
+/// does not point into the server's own network unless `allow_private_targets` is set. Names
+/// are checked again against what they resolve to on every delivery.
+pub fn sanitize_webhook_url(url: &str, allow_private_targets: bool) -> Result<String, String> {
+    let trimmed = url.trim();
+    if trimmed.len() > WEBHOOK_URL_MAX_LEN {
+        return Err(format!(
+            "url must be at most {WEBHOOK_URL_MAX_LEN} characters"
+        ));
+    }
+    let parsed = reqwest::Url::parse(trimmed).map_err(|_| "url is not a valid URL".to_string())?;
+    let Some(host) = parsed
+        .host_str()
+        .filter(|_| matches!(parsed.scheme(), "http" | "https"))
+    else {
+        return Err("url must be an http or https URL".to_string());
+    };
+    if !allow_private_targets && !is_public_host(host) {
+        return Err("url must not point at a private or local address".to_string());
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Whether a URL host may be public: not a local name such as `localhost`, and not an address
+/// that fails [`is_public_ip`]. Names still have to be resolved to know for sure.
+pub fn is_public_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return is_public_ip(ip);
+    }
+    let name = host.trim_end_matches('.').to_ascii_lowercase();
+    !(name == "localhost"
+        || name.ends_with(".localhost")
+        || name.ends_with(".local")
+        || name.ends_with(".internal"))
+}
+
+/// Whether the address is on the public internet rather than loopback, private, shared,
+/// link-local, unspecified, broadcast or multicast.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [first, second, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || first == 0
+                // 100.64.0.0/10, shared address space for carrier-grade NAT.
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    // fc00::/7 unique local and fe80::/10 link-local.
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+pub fn validate_webhook_secret(secret: &str) -> Result<(), String> {
+    let len = secret.chars().count();
+    if !(WEBHOOK_SECRET_MIN_LEN..=WEBHOOK_SECRET_MAX_LEN).contains(&len) {
+        return Err(format!(
+            "secret must be between {WEBHOOK_SECRET_MIN_LEN} and {WEBHOOK_SECRET_MAX_LEN} characters"
+        ));
+    }
+    Ok(())
+}
+
+/// Known event types, deduplicated and sorted; all of them when none are given.
+pub fn sanitize_webhook_events(events: &[String]) -> Result<Vec<String>, String> {
+    if events.is_empty() {
+        return Ok(WEBHOOK_EVENT_TYPES.iter().map(|e| e.to_string()).collect());
+    }
+    let mut sanitized = Vec::with_capacity(events.len());
+    for event in events {
+        let normalized = event.trim().to_lowercase();
+        if !WEBHOOK_EVENT_TYPES.contains(&normalized.as_str()) {
+            return Err(format!(
+                "unknown event {}, must be one of {}",
+                event.trim(),
+                WEBHOOK_EVENT_TYPES.join(", ")
+            ));
+        }
+        sanitized.push(normalized);
+    }
+    sanitized.sort_unstable();
+    sanitized.dedup();
+    Ok(sanitized)
+}
+
+impl NewCanteenWebhook {
+    pub fn sanitize_and_validate(mut self, allow_private_targets: bool) -> Result<Self, String> {
+        self.url = sanitize_webhook_url(&self.url, allow_private_targets)?;
+        validate_webhook_secret(&self.secret)?;
+        self.events = sanitize_webhook_events(&self.events)?;
+        Ok(self)
+    }
+}
+
+impl UpdateCanteenWebhook {
+    pub fn sanitize_and_validate(mut self, allow_private_targets: bool) -> Result<Self, String> {
+        self.url = sanitize_webhook_url(&self.url, allow_private_targets)?;
+        if let Some(secret) = self.secret.as_deref() {
+            validate_webhook_secret(secret)?;
+        }
+        self.events = sanitize_webhook_events(&self.events)?;
         Ok(self)
     }
 }
@@ -225,6 +991,75 @@ mod tests {
         assert!(validate_stock(-2).is_err());
     }
 
+    #[test]
+    fn sanitize_tags_normalizes_and_dedupes() {
+        let tags = vec![
+            " Spicy ".to_string(),
+            "spicy".to_string(),
+            "Bestseller".to_string(),
+        ];
+        assert_eq!(sanitize_tags(&tags).unwrap(), vec!["spicy", "bestseller"]);
+        assert!(sanitize_tags(&["  ".to_string()]).is_err());
+        assert!(sanitize_tags(&["a".repeat(MENU_ITEM_TAG_MAX_LEN + 1)]).is_err());
+        let too_many: Vec<String> = (0..=MENU_ITEM_MAX_TAGS).map(|i| format!("t{i}")).collect();
+        assert!(sanitize_tags(&too_many).is_err());
+    }
+
+    #[test]
+    fn sanitize_allergens_normalizes_and_rejects_unknown() {
+        let allergens = vec![
+            " Dairy".to_string(),
+            "NUTS".to_string(),
+            "dairy".to_string(),
+        ];
+        assert_eq!(
+            sanitize_allergens(&allergens).unwrap(),
+            vec!["dairy", "nuts"]
+        );
+        assert!(sanitize_allergens(&["chocolate".to_string()]).is_err());
+        assert!(validate_diet_labels_for_veg(&["vegan".to_string()], false).is_err());
+        assert!(validate_diet_labels_for_veg(&["halal".to_string()], false).is_ok());
+    }
+
+    #[test]
+    fn validate_schedule_windows_rejects_bad_weekday_and_empty_window() {
+        let window = |weekday: i16, start: (u32, u32), end: (u32, u32)| ScheduleWindow {
+            weekday,
+            start_time: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+        };
+
+        assert!(validate_schedule_windows(&[]).is_ok());
+        assert!(validate_schedule_windows(&[window(0, (8, 0), (11, 0))]).is_ok());
+        assert!(validate_schedule_windows(&[window(6, (22, 0), (2, 0))]).is_ok());
+        assert!(validate_schedule_windows(&[window(7, (8, 0), (11, 0))]).is_err());
+        assert!(validate_schedule_windows(&[window(-1, (8, 0), (11, 0))]).is_err());
+        assert!(validate_schedule_windows(&[window(2, (9, 30), (9, 30))]).is_err());
+
+        let too_many = vec![window(1, (8, 0), (9, 0)); MENU_ITEM_SCHEDULE_MAX_WINDOWS + 1];
+        assert!(validate_schedule_windows(&too_many).is_err());
+    }
+
+    #[test]
+    fn update_menu_item_sanitize_rejects_bad_default_stock() {
+        let update = UpdateMenuItem {
+            name: None,
+            is_veg: None,
+            price: None,
+            stock: None,
+            is_available: None,
+            description: None,
+            default_stock: Some(Some(-5)),
+            category_id: None,
+            display_order: None,
+            is_pinned: None,
+            tags: None,
+            allergens: None,
+            diet_labels: None,
+        };
+        assert!(update.sanitize_and_validate().is_err());
+    }
+
     #[test]
     fn update_menu_item_sanitize_all_none_passes() {
         let update = UpdateMenuItem {
@@ -234,6 +1069,13 @@ mod tests {
             stock: None,
             is_available: None,
             description: None,
+            default_stock: None,
+            category_id: None,
+            display_order: None,
+            is_pinned: None,
+            tags: None,
+            allergens: None,
+            diet_labels: None,
         };
         let result = update.sanitize_and_validate();
         assert!(result.is_ok());
@@ -252,6 +1094,13 @@ mod tests {
             stock: None,
             is_available: None,
             description: None,
+            default_stock: None,
+            category_id: None,
+            display_order: None,
+            is_pinned: None,
+            tags: None,
+            allergens: None,
+            diet_labels: None,
         };
         assert!(update.sanitize_and_validate().is_err());
     }
@@ -265,6 +1114,13 @@ mod tests {
             stock: Some(-2),
             is_available: None,
             description: None,
+            default_stock: None,
+            category_id: None,
+            display_order: None,
+            is_pinned: None,
+            tags: None,
+            allergens: None,
+            diet_labels: None,
         };
         assert!(update.sanitize_and_validate().is_err());
     }
@@ -278,6 +1134,13 @@ mod tests {
             stock: None,
             is_available: None,
             description: None,
+            default_stock: None,
+            category_id: None,
+            display_order: None,
+            is_pinned: None,
+            tags: None,
+            allergens: None,
+            diet_labels: None,
         };
         let result = update.sanitize_and_validate().unwrap();
         assert_eq!(result.name, Some("Burger".to_string()));
@@ -293,6 +1156,13 @@ mod tests {
             stock: 10,
             is_available: true,
             description: None,
+            default_stock: None,
+            category_id: None,
+            display_order: 0,
+            is_pinned: false,
+            tags: Vec::new(),
+            allergens: Vec::new(),
+            diet_labels: Vec::new(),
         };
         assert!(item.sanitize_and_validate().is_err());
     }
@@ -307,9 +1177,63 @@ mod tests {
             stock: 10,
             is_available: true,
             description: Some("a".repeat(MENU_ITEM_DESC_MAX_LEN + 1)),
+            default_stock: None,
+            category_id: None,
+            display_order: 0,
+            is_pinned: false,
+            tags: Vec::new(),
+            allergens: Vec::new(),
+            diet_labels: Vec::new(),
         };
         assert!(item.sanitize_and_validate().is_err());
     }
+
+    #[test]
+    fn webhook_sanitize_checks_url_secret_and_events() {
+        let webhook = NewCanteenWebhook {
+            canteen_id: 1,
+            url: " https://pos.example.com/hooks ".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events: vec![
+                "Order.Delivered".to_string(),
+                "order.confirmed".to_string(),
+                "order.delivered".to_string(),
+            ],
+            is_active: true,
+        }
+        .sanitize_and_validate(false)
+        .unwrap();
+        assert_eq!(webhook.url, "https://pos.example.com/hooks");
+        assert_eq!(webhook.events, vec!["order.confirmed", "order.delivered"]);
+
+        assert_eq!(sanitize_webhook_events(&[]).unwrap().len(), 4);
+        assert!(sanitize_webhook_events(&["order.paid".to_string()]).is_err());
+        assert!(sanitize_webhook_url("ftp://pos.example.com", false).is_err());
+        assert!(sanitize_webhook_url("/hooks", false).is_err());
+        assert!(validate_webhook_secret("too-short").is_err());
+    }
+
+    #[test]
+    fn webhook_url_must_not_point_into_the_local_network() {
+        for url in [
+            "http://127.0.0.1:8080/hooks",
+            "http://localhost/hooks",
+            "http://printer.local/hooks",
+            "http://10.0.0.7/hooks",
+            "http://192.168.1.20/hooks",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hooks",
+            "http://0.0.0.0/hooks",
+            "http://[::1]/hooks",
+            "http://[fd00::1]/hooks",
+            "http://[::ffff:10.0.0.7]/hooks",
+        ] {
+            assert!(sanitize_webhook_url(url, false).is_err(), "{url}");
+            assert!(sanitize_webhook_url(url, true).is_ok(), "{url}");
+        }
+        assert!(sanitize_webhook_url("https://8.8.8.8/hooks", false).is_ok());
+        assert!(sanitize_webhook_url("https://[2606:4700::1111]/hooks", false).is_ok());
+    }
 }
 
 #[derive(Debug, Selectable, Queryable, Serialize, ToSchema)]
@@ -318,3 +1242,45 @@ pub struct CanteenLoginSuccess {
     pub canteen_id: i32,
     pub canteen_name: String,
 }
+
+/// What a canteen is owed for a business day. Amounts are in paisa.
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::settlements)]
+#[diesel(primary_key(settlement_id))]
+pub struct Settlement {
+    pub settlement_id: i32,
+    pub canteen_id: i32,
+    #[schema(value_type = String, format = "date")]
+    pub business_date: NaiveDate,
+    /// Online payments completed for orders started that day.
+    pub payment_count: i32,
+    pub gross: i64,
+    /// Refunds PhonePe completed that day.
+    pub refund_count: i32,
+    pub refunds: i64,
+    /// The platform's cut of `gross` less `refunds`, in basis points.
+    pub commission_bps: i32,
+    pub commission: i64,
+    /// Negative when refunds outweigh the day's takings.
+    pub net_payable: i64,
+    #[schema(value_type = String, format = "date-time")]
+    pub generated_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub paid_at: Option<DateTime<Utc>>,
+    /// Bank or UPI reference of the payout.
+    pub payout_reference: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::settlements)]
+pub struct NewSettlement {
+    pub canteen_id: i32,
+    pub business_date: NaiveDate,
+    pub payment_count: i32,
+    pub gross: i64,
+    pub refund_count: i32,
+    pub refunds: i64,
+    pub commission_bps: i32,
+    pub commission: i64,
+    pub net_payable: i64,
+}
@@ -82,6 +82,7 @@ pub struct ActiveOrder {
     pub price: i32,
     pub deliver_at: Option<TimeBandEnum>,
     pub ordered_at: DateTime<Utc>,
+    pub note: Option<String>,
 }
 
 #[derive(Insertable, Debug, Serialize, Deserialize)]
@@ -110,6 +111,8 @@ pub struct OrderItems {
     pub pic_etag: Option<String>,
     pub pic_key: Option<String>,
     pub description: Option<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[derive(Queryable, Debug, Identifiable, Serialize, Deserialize)]
@@ -123,6 +126,22 @@ pub struct HeldOrder {
     pub deliver_at: Option<TimeBandEnum>,
     pub held_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    pub note: Option<String>,
+}
+
+pub const ORDER_NOTE_MAX_LEN: usize = 200;
+
+/// Trims a customer's note for the kitchen; blank notes are dropped.
+pub fn sanitize_order_note(note: Option<String>) -> Result<Option<String>, String> {
+    let Some(note) = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+    if note.chars().count() > ORDER_NOTE_MAX_LEN {
+        return Err(format!(
+            "note must be at most {ORDER_NOTE_MAX_LEN} characters"
+        ));
+    }
+    Ok(Some(note))
 }
 
 #[derive(Insertable, Debug)]
@@ -133,6 +152,7 @@ pub struct NewHeldOrder {
     pub total_price: i32,
     pub deliver_at: Option<TimeBandEnum>,
     pub expires_at: DateTime<Utc>,
+    pub note: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -161,6 +181,7 @@ pub struct PaymentOrder {
     pub app_order_id: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub canteen_id: Option<i32>,
 }
 
 #[derive(Insertable, Debug, Clone)]
@@ -175,4 +196,158 @@ pub struct NewPaymentOrder {
     pub payment_state: String,
     pub phonepe_expires_at: Option<DateTime<Utc>>,
     pub app_order_id: Option<i32>,
+    pub canteen_id: Option<i32>,
 }
+
+/// One event of an SSE stream's replay log.
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::sse_events)]
+pub struct StoredSseEvent {
+    pub stream_kind: String,
+    pub stream_id: i32,
+    pub seq: i64,
+    pub event_name: String,
+    pub data: String,
+}
+
+/// A side effect waiting in the outbox; `payload` is a JSON `OutboxMessage`.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::outbox)]
+pub struct OutboxEntry {
+    pub outbox_id: i64,
+    pub topic: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub failed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub const REVIEW_COMMENT_MAX_LEN: usize = 1000;
+pub const REVIEW_REPLY_MAX_LEN: usize = 1000;
+
+/// Trims the comment on a review or the canteen's reply to it; blank text is dropped.
+pub fn sanitize_review_text(
+    text: Option<String>,
+    max_len: usize,
+    field: &str,
+) -> Result<Option<String>, String> {
+    let Some(text) = text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()) else {
+        return Ok(None);
+    };
+    if text.chars().count() > max_len {
+        return Err(format!("{field} must be at most {max_len} characters"));
+    }
+    Ok(Some(text))
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::db::schema::order_reviews)]
+pub struct OrderReview {
+    pub review_id: i32,
+    pub order_id: i32,
+    pub user_id: i32,
+    pub canteen_id: i32,
+    pub rating: i16,
+    pub comment: Option<String>,
+    pub is_hidden: bool,
+    pub reply: Option<String>,
+    pub replied_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::order_reviews)]
+pub struct NewOrderReview {
+    pub order_id: i32,
+    pub user_id: i32,
+    pub canteen_id: i32,
+    pub rating: i16,
+    pub comment: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::item_ratings)]
+pub struct NewItemRating {
+    pub review_id: i32,
+    pub item_id: i32,
+    pub rating: i16,
+}
+
+pub const REFUND_STATE_PENDING: &str = "PENDING";
+pub const REFUND_STATE_COMPLETED: &str = "COMPLETED";
+pub const REFUND_STATE_FAILED: &str = "FAILED";
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::db::schema::support_tickets)]
+pub struct StoredSupportTicket {
+    pub ticket_id: i32,
+    pub user_id: i32,
+    pub canteen_id: i32,
+    pub order_id: i32,
+    pub category: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::support_tickets)]
+pub struct NewSupportTicket {
+    pub user_id: i32,
+    pub canteen_id: i32,
+    pub order_id: i32,
+    pub category: String,
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::db::schema::support_messages)]
+pub struct StoredSupportMessage {
+    pub message_id: i32,
+    pub ticket_id: i32,
+    pub author: String,
+    pub body: String,
+    pub attachment_keys: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::support_messages)]
+pub struct NewSupportMessage {
+    pub ticket_id: i32,
+    pub author: String,
+    pub body: String,
+    pub attachment_keys: Vec<String>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::refunds)]
+pub struct StoredRefund {
+    pub refund_id: i32,
+    pub payment_id: i32,
+    pub canteen_id: i32,
+    pub ticket_id: Option<i32>,
+    pub merchant_refund_id: String,
+    pub amount: i32,
+    pub state: String,
+    pub phonepe_refund_id: Option<String>,
+    pub failure_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::refunds)]
+pub struct NewRefund {
+    pub payment_id: i32,
+    pub canteen_id: i32,
+    pub ticket_id: Option<i32>,
+    pub merchant_refund_id: String,
+    pub amount: i32,
+}
+
+pub const HOLD_OUTCOME_CONFIRMED: &str = "confirmed";
+pub const HOLD_OUTCOME_RELEASED: &str = "released";
+pub const HOLD_OUTCOME_EXPIRED: &str = "expired";
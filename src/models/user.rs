@@ -1,3 +1,4 @@
+use crate::models::common::TimeBandEnum;
 use chrono::{DateTime, Utc};
 use diesel::{Identifiable, Insertable, Queryable, Selectable};
 use serde::{Deserialize, Serialize};
@@ -30,6 +31,8 @@ pub struct PastOrderItem {
     pub pic_etag: Option<String>,
     pub pic_key: Option<String>,
     pub description: Option<String>,
+    pub allergens: Vec<String>,
+    pub diet_labels: Vec<String>,
 }
 
 #[derive(Insertable, Debug, Serialize, Deserialize)]
@@ -41,6 +44,17 @@ pub struct NewPastOrder {
     pub order_status: bool,
     pub ordered_at: DateTime<Utc>,
     pub price: i32,
+    pub canteen_id: Option<i32>,
+    pub deliver_at: Option<TimeBandEnum>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::db::schema::past_order_items)]
+pub struct NewPastOrderItem {
+    pub order_id: i32,
+    pub item_id: i32,
+    pub quantity: i16,
+    pub price: i32,
 }
 
 #[derive(Queryable, Debug, Identifiable, Serialize, Deserialize)]
@@ -57,6 +71,7 @@ pub struct User {
     pub email_verified: bool,
     pub display_name: Option<String>,
     pub photo_url: Option<String>,
+    pub allergen_exclusions: Vec<String>,
 }
 
 #[derive(Insertable, Debug, Serialize, Deserialize, ToSchema)]
@@ -66,3 +81,18 @@ pub struct NewUser {
     pub name: String,
     pub email: String,
 }
+
+/// Platforms a device can register a push token from.
+pub const DEVICE_PLATFORMS: [&str; 3] = ["android", "ios", "web"];
+
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::db::schema::device_tokens)]
+pub struct DeviceToken {
+    pub token: String,
+    pub user_id: i32,
+    pub platform: String,
+    #[schema(value_type = String, format = "date-time")]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = "date-time")]
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,144 @@
+use crate::db::{ExportOperations, ReportRange};
+use crate::enums::admin::AccountingExport;
+use actix_web::web::Bytes;
+use futures::stream::{self, Stream};
+use log::{debug, error};
+use serde::Serialize;
+
+/// Rows read from the database per chunk of the file.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Where the next chunk of an export starts.
+enum ExportCursor {
+    Header,
+    /// After the row with this key: the order, or the payment, and the item for line items.
+    After(i32, i32),
+    Done,
+}
+
+fn write_csv_rows<T: Serialize>(rows: &[T]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+fn write_csv_header(export: AccountingExport) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(export.columns())
+        .map_err(|e| e.to_string())?;
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+/// A chunk of CSV read from one page of rows.
+struct ExportPage {
+    chunk: Vec<u8>,
+    rows: usize,
+    /// Key of the last row, where the next page starts.
+    last: (i32, i32),
+}
+
+/// The next chunk of CSV after `after`.
+fn next_page(
+    ops: &ExportOperations,
+    export: AccountingExport,
+    canteen_id: i32,
+    range: &ReportRange,
+    after: (i32, i32),
+) -> Result<ExportPage, String> {
+    match export {
+        AccountingExport::Orders => {
+            let rows = ops
+                .order_rows(canteen_id, range, after.0, EXPORT_PAGE_SIZE)
+                .map_err(|e| e.to_string())?;
+            let last = rows.last().map_or(after, |row| (row.order_id, 0));
+            Ok(ExportPage {
+                chunk: write_csv_rows(&rows)?,
+                rows: rows.len(),
+                last,
+            })
+        }
+        AccountingExport::OrderItems => {
+            let rows = ops
+                .order_item_rows(canteen_id, range, after, EXPORT_PAGE_SIZE)
+                .map_err(|e| e.to_string())?;
+            let last = rows.last().map_or(after, |row| (row.order_id, row.item_id));
+            Ok(ExportPage {
+                chunk: write_csv_rows(&rows)?,
+                rows: rows.len(),
+                last,
+            })
+        }
+        AccountingExport::Payments => {
+            let rows = ops
+                .payment_rows(canteen_id, range, after.0, EXPORT_PAGE_SIZE)
+                .map_err(|e| e.to_string())?;
+            let last = rows.last().map_or(after, |row| (row.payment_id, 0));
+            Ok(ExportPage {
+                chunk: write_csv_rows(&rows)?,
+                rows: rows.len(),
+                last,
+            })
+        }
+    }
+}
+
+/// The export as a stream of CSV chunks, read from the database a page at a time so that
+/// only one page is held in memory. A failure part way ends the stream with an error, which
+/// cuts the download short.
+pub fn csv_stream(
+    ops: ExportOperations,
+    export: AccountingExport,
+    canteen_id: i32,
+    range: ReportRange,
+) -> impl Stream<Item = Result<Bytes, String>> {
+    stream::unfold(ExportCursor::Header, move |cursor| {
+        let ops = ops.clone();
+        let range = range.clone();
+        async move {
+            match cursor {
+                ExportCursor::Done => None,
+                ExportCursor::Header => Some((
+                    write_csv_header(export).map(Bytes::from),
+                    ExportCursor::After(0, 0),
+                )),
+                ExportCursor::After(order, item) => {
+                    let page = tokio::task::spawn_blocking(move || {
+                        next_page(&ops, export, canteen_id, &range, (order, item))
+                    })
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|page| page);
+                    match page {
+                        Ok(page) => {
+                            let next = if (page.rows as i64) < EXPORT_PAGE_SIZE {
+                                debug!(
+                                    "accounting_export: finished {} export for canteen {}",
+                                    export.file_stem(),
+                                    canteen_id
+                                );
+                                ExportCursor::Done
+                            } else {
+                                ExportCursor::After(page.last.0, page.last.1)
+                            };
+                            Some((Ok(Bytes::from(page.chunk)), next))
+                        }
+                        Err(e) => {
+                            error!(
+                                "accounting_export: {} export for canteen {} failed: {}",
+                                export.file_stem(),
+                                canteen_id,
+                                e
+                            );
+                            Some((Err(e), ExportCursor::Done))
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
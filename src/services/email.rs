@@ -0,0 +1,257 @@
+use crate::services::push::RetryPolicy;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An email rendered when it was queued in the outbox.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Email {
+    pub recipient: String,
+    pub subject: String,
+    pub body_text: String,
+    pub body_html: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    /// Worth retrying later: connection failures, 4xx replies.
+    Transient(String),
+    /// Retrying will not help: a malformed address, 5xx replies.
+    Permanent(String),
+}
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transient(e) => write!(f, "transient: {e}"),
+            Self::Permanent(e) => write!(f, "permanent: {e}"),
+        }
+    }
+}
+
+/// Sends one outbox email. Blocking; the [`MailSender`] calls it off the async runtime.
+pub trait MailTransport: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+/// Local stand-in used when SMTP is not configured: logs emails instead of sending them.
+pub struct LogMailTransport;
+
+impl MailTransport for LogMailTransport {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        info!(
+            "email (not sent, SMTP disabled) to {}: {}",
+            email.recipient, email.subject
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub timeout_secs: u64,
+}
+
+impl SmtpConfig {
+    /// `None` unless `SMTP_ENABLED` is set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let enabled = std::env::var("SMTP_ENABLED")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let host = non_empty_env("SMTP_HOST")
+            .ok_or_else(|| "SMTP_HOST must be set when SMTP_ENABLED=true".to_string())?;
+        let from = non_empty_env("SMTP_FROM")
+            .ok_or_else(|| "SMTP_FROM must be set when SMTP_ENABLED=true".to_string())?;
+        let security = match non_empty_env("SMTP_SECURITY")
+            .unwrap_or_else(|| "starttls".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "none" => SmtpSecurity::None,
+            "starttls" => SmtpSecurity::StartTls,
+            "tls" => SmtpSecurity::Tls,
+            other => {
+                return Err(format!(
+                    "SMTP_SECURITY must be none, starttls or tls, got {other}"
+                ))
+            }
+        };
+        let port = match non_empty_env("SMTP_PORT") {
+            Some(port) => port
+                .parse::<u16>()
+                .map_err(|_| format!("SMTP_PORT is not a port: {port}"))?,
+            None => match security {
+                SmtpSecurity::None => 25,
+                SmtpSecurity::StartTls => 587,
+                SmtpSecurity::Tls => 465,
+            },
+        };
+        let timeout_secs = std::env::var("SMTP_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10);
+
+        Ok(Some(Self {
+            host,
+            port,
+            security,
+            username: non_empty_env("SMTP_USERNAME"),
+            password: non_empty_env("SMTP_PASSWORD"),
+            from,
+            timeout_secs,
+        }))
+    }
+}
+
+pub struct SmtpMailTransport {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailTransport {
+    pub fn new(cfg: SmtpConfig) -> Result<Self, String> {
+        let from = cfg
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("SMTP_FROM is not a mailbox: {}", e))?;
+        let mut builder = match cfg.security {
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&cfg.host),
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&cfg.host)
+                .map_err(|e| format!("failed to set up STARTTLS: {}", e))?,
+            SmtpSecurity::Tls => SmtpTransport::relay(&cfg.host)
+                .map_err(|e| format!("failed to set up TLS: {}", e))?,
+        }
+        .port(cfg.port)
+        .timeout(Some(std::time::Duration::from_secs(cfg.timeout_secs)));
+        if let (Some(username), Some(password)) = (cfg.username, cfg.password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl MailTransport for SmtpMailTransport {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        let to = email
+            .recipient
+            .parse::<Mailbox>()
+            .map_err(|e| MailError::Permanent(format!("invalid recipient: {}", e)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject.clone())
+            .multipart(MultiPart::alternative_plain_html(
+                email.body_text.clone(),
+                email.body_html.clone(),
+            ))
+            .map_err(|e| MailError::Permanent(format!("failed to build message: {}", e)))?;
+        self.transport.send(&message).map(|_| ()).map_err(|e| {
+            if e.is_permanent() {
+                MailError::Permanent(e.to_string())
+            } else {
+                MailError::Transient(e.to_string())
+            }
+        })
+    }
+}
+
+/// SMTP when `SMTP_ENABLED`, otherwise the logging stand-in.
+pub fn mail_transport_from_env() -> Result<Arc<dyn MailTransport>, String> {
+    Ok(match SmtpConfig::from_env()? {
+        Some(cfg) => Arc::new(SmtpMailTransport::new(cfg)?),
+        None => Arc::new(LogMailTransport),
+    })
+}
+
+/// What became of sending an email.
+#[derive(Debug)]
+pub enum MailOutcome {
+    /// The mail server accepted it.
+    Done,
+    /// Failed for now; worth trying again after the delay.
+    Retry(Duration, String),
+    /// Retrying will not help, or the retry budget is spent.
+    GiveUp(String),
+}
+
+/// Sends outbox emails, retrying transient failures with the backoff of the [`RetryPolicy`].
+#[derive(Clone)]
+pub struct MailSender {
+    transport: Arc<dyn MailTransport>,
+    retry: RetryPolicy,
+}
+
+impl MailSender {
+    pub fn new(transport: Arc<dyn MailTransport>, retry: RetryPolicy) -> Self {
+        Self { transport, retry }
+    }
+
+    /// The transport of [`mail_transport_from_env`], retried up to `EMAIL_MAX_ATTEMPTS`
+    /// times (default 8) from a first delay of `EMAIL_RETRY_BASE_SECS` (default 60).
+    pub fn from_env() -> Result<Self, String> {
+        fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<T>().ok())
+                .unwrap_or(default)
+        }
+        Ok(Self::new(
+            mail_transport_from_env()?,
+            RetryPolicy {
+                max_attempts: env_or("EMAIL_MAX_ATTEMPTS", 8u32).max(1),
+                base_delay: Duration::from_secs(env_or("EMAIL_RETRY_BASE_SECS", 60u64).max(1)),
+            },
+        ))
+    }
+
+    /// Send an email; `failed_attempts` is how many earlier attempts failed.
+    pub async fn send(&self, email: Email, failed_attempts: u32) -> MailOutcome {
+        let transport = self.transport.clone();
+        let recipient = email.recipient.clone();
+        let failure = match tokio::task::spawn_blocking(move || transport.send(&email)).await {
+            Ok(Ok(())) => return MailOutcome::Done,
+            Ok(Err(e)) => e,
+            Err(e) => MailError::Transient(e.to_string()),
+        };
+        let attempts = failed_attempts + 1;
+        if matches!(failure, MailError::Transient(_)) && attempts < self.retry.max_attempts {
+            return MailOutcome::Retry(self.retry.delay(failed_attempts), failure.to_string());
+        }
+        error!(
+            "email: giving up on email to {} after {} attempts: {}",
+            recipient, attempts, failure
+        );
+        MailOutcome::GiveUp(failure.to_string())
+    }
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -1,9 +1,9 @@
 use crate::db::HoldOperations;
-use crate::sse::{SseBroker, SseEvent};
+use crate::services::outbox::OutboxDispatcher;
 use actix_web::web;
 use tokio::time::{interval, Duration};
 
-pub async fn run_hold_cleanup(hold_ops: HoldOperations, broker: SseBroker) {
+pub async fn run_hold_cleanup(hold_ops: HoldOperations, outbox: OutboxDispatcher) {
     let mut tick = interval(Duration::from_secs(60));
     loop {
         tick.tick().await;
@@ -13,16 +13,9 @@ pub async fn run_hold_cleanup(hold_ops: HoldOperations, broker: SseBroker) {
         })
         .await
         {
-            Ok(Ok((count, restored_inventory_updates))) => {
-                for (canteen_id, inventory_updates) in restored_inventory_updates {
-                    broker.publish_canteen_subscription_event(
-                        canteen_id,
-                        &SseEvent::InventoryUpdate {
-                            items: inventory_updates,
-                        },
-                    );
-                }
+            Ok(Ok(count)) => {
                 if count > 0 {
+                    outbox.wake();
                     info!("Background cleanup: released {} expired order holds", count);
                 }
             }
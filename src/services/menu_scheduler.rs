@@ -0,0 +1,127 @@
+use crate::db::{CanteenOperations, MenuOperations, RepositoryError};
+use crate::models::admin::ScheduleWindow;
+use crate::sse::{InventoryUpdateItems, SseBroker, SseEvent};
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use std::collections::HashMap;
+use tokio::time::{interval, Duration};
+
+pub async fn run_menu_scheduler(
+    menu_ops: MenuOperations,
+    canteen_ops: CanteenOperations,
+    tz: FixedOffset,
+    broker: SseBroker,
+) {
+    let mut tick = interval(Duration::from_secs(60));
+    loop {
+        tick.tick().await;
+        match tokio::task::spawn_blocking({
+            let menu_ops = menu_ops.clone();
+            let canteen_ops = canteen_ops.clone();
+            move || run_menu_schedule_pass(&menu_ops, &canteen_ops, tz)
+        })
+        .await
+        {
+            Ok(Ok(updates)) => {
+                for (canteen_id, items) in updates {
+                    broker.publish_canteen_subscription_event(
+                        canteen_id,
+                        &SseEvent::InventoryUpdate { items },
+                    );
+                }
+            }
+            Ok(Err(e)) => {
+                error!("menu scheduler: error applying menu schedules: {}", e);
+            }
+            Err(e) => {
+                error!("menu scheduler: blocking task failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Run one pass of the daily stock reset and the availability schedule.
+/// Returns the inventory changes grouped by canteen, ready to be broadcast.
+pub fn run_menu_schedule_pass(
+    menu_ops: &MenuOperations,
+    canteen_ops: &CanteenOperations,
+    tz: FixedOffset,
+) -> Result<Vec<(i32, Vec<InventoryUpdateItems>)>, RepositoryError> {
+    let now_local = Utc::now().with_timezone(&tz).naive_local();
+    let mut updates: HashMap<i32, Vec<InventoryUpdateItems>> = HashMap::new();
+
+    for canteen in canteen_ops.list_stock_reset_states()? {
+        if !stock_reset_due(canteen.opening_time, canteen.last_stock_reset_on, now_local) {
+            continue;
+        }
+        let reset_items = menu_ops.reset_daily_stock(canteen.canteen_id, now_local.date())?;
+        if !reset_items.is_empty() {
+            info!(
+                "menu scheduler: reset daily stock for {} items in canteen {}",
+                reset_items.len(),
+                canteen.canteen_id
+            );
+            updates
+                .entry(canteen.canteen_id)
+                .or_default()
+                .extend(reset_items);
+        }
+    }
+
+    for item in menu_ops.list_scheduled_items()? {
+        let within = is_within_schedule(&item.windows, now_local);
+        let expected_available = within && item.stock != 0 && !item.manually_disabled;
+        if expected_available == item.is_available {
+            continue;
+        }
+        if let Some((canteen_id, update)) =
+            menu_ops.apply_scheduled_availability(item.item_id, within)?
+        {
+            debug!(
+                "menu scheduler: item {} in canteen {} is now {}",
+                update.item_id,
+                canteen_id,
+                if update.is_available {
+                    "available"
+                } else {
+                    "unavailable"
+                }
+            );
+            let canteen_updates = updates.entry(canteen_id).or_default();
+            // A reset earlier in this pass may already have reported the item.
+            canteen_updates.retain(|existing| existing.item_id != update.item_id);
+            canteen_updates.push(update);
+        }
+    }
+
+    Ok(updates.into_iter().collect())
+}
+
+/// Stock is reset once per local day, as soon as the canteen's opening time has passed.
+/// Canteens without an opening time reset at midnight.
+pub fn stock_reset_due(
+    opening_time: Option<NaiveTime>,
+    last_reset_on: Option<NaiveDate>,
+    now_local: NaiveDateTime,
+) -> bool {
+    if last_reset_on.is_some_and(|last| last >= now_local.date()) {
+        return false;
+    }
+    now_local.time() >= opening_time.unwrap_or(NaiveTime::MIN)
+}
+
+/// Whether `now_local` falls inside any of the weekly windows.
+/// Windows ending before they start run past midnight into the following weekday.
+pub fn is_within_schedule(windows: &[ScheduleWindow], now_local: NaiveDateTime) -> bool {
+    let today = now_local.weekday().num_days_from_monday() as i16;
+    let yesterday = (today + 6) % 7;
+    let time = now_local.time();
+
+    windows.iter().any(|window| {
+        if window.start_time < window.end_time {
+            window.weekday == today && time >= window.start_time && time < window.end_time
+        } else {
+            (window.weekday == today && time >= window.start_time)
+                || (window.weekday == yesterday && time < window.end_time)
+        }
+    })
+}
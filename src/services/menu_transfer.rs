@@ -0,0 +1,115 @@
+use crate::enums::admin::MenuTransferRow;
+use serde::{Deserialize, Serialize};
+
+/// Separator for list columns (tags, allergens, diet labels) in CSV files.
+pub const MENU_CSV_LIST_SEPARATOR: char = '|';
+
+/// Flat CSV shape of [`MenuTransferRow`]. Column order is part of the export format.
+#[derive(Debug, Serialize, Deserialize)]
+struct MenuCsvRecord {
+    item_id: Option<i32>,
+    name: String,
+    is_veg: bool,
+    price: i32,
+    stock: i32,
+    is_available: bool,
+    description: Option<String>,
+    default_stock: Option<i32>,
+    category: Option<String>,
+    display_order: Option<i32>,
+    is_pinned: Option<bool>,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    allergens: String,
+    #[serde(default)]
+    diet_labels: String,
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(MENU_CSV_LIST_SEPARATOR)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn join_list(values: &[String]) -> String {
+    values.join(&MENU_CSV_LIST_SEPARATOR.to_string())
+}
+
+impl From<MenuCsvRecord> for MenuTransferRow {
+    fn from(record: MenuCsvRecord) -> Self {
+        Self {
+            item_id: record.item_id,
+            name: record.name,
+            is_veg: record.is_veg,
+            price: record.price,
+            stock: record.stock,
+            is_available: record.is_available,
+            description: record.description.filter(|d| !d.trim().is_empty()),
+            default_stock: record.default_stock,
+            category: record.category.filter(|c| !c.trim().is_empty()),
+            display_order: record.display_order.unwrap_or(0),
+            is_pinned: record.is_pinned.unwrap_or(false),
+            tags: split_list(&record.tags),
+            allergens: split_list(&record.allergens),
+            diet_labels: split_list(&record.diet_labels),
+        }
+    }
+}
+
+impl From<&MenuTransferRow> for MenuCsvRecord {
+    fn from(row: &MenuTransferRow) -> Self {
+        Self {
+            item_id: row.item_id,
+            name: row.name.clone(),
+            is_veg: row.is_veg,
+            price: row.price,
+            stock: row.stock,
+            is_available: row.is_available,
+            description: row.description.clone(),
+            default_stock: row.default_stock,
+            category: row.category.clone(),
+            display_order: Some(row.display_order),
+            is_pinned: Some(row.is_pinned),
+            tags: join_list(&row.tags),
+            allergens: join_list(&row.allergens),
+            diet_labels: join_list(&row.diet_labels),
+        }
+    }
+}
+
+/// Parse a CSV menu file with a header row. List columns use `|` as separator.
+pub fn parse_menu_csv(data: &[u8]) -> Result<Vec<MenuTransferRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    reader
+        .deserialize::<MenuCsvRecord>()
+        .enumerate()
+        .map(|(index, record)| {
+            record
+                .map(MenuTransferRow::from)
+                .map_err(|e| format!("row {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+/// Parse a JSON menu file: an array of [`MenuTransferRow`].
+pub fn parse_menu_json(data: &[u8]) -> Result<Vec<MenuTransferRow>, String> {
+    serde_json::from_slice(data).map_err(|e| format!("invalid menu JSON: {e}"))
+}
+
+pub fn write_menu_csv(rows: &[MenuTransferRow]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer
+            .serialize(MenuCsvRecord::from(row))
+            .map_err(|e| format!("failed to write menu CSV: {e}"))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| format!("failed to write menu CSV: {e}"))
+}
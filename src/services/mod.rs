@@ -1,4 +1,16 @@
+pub mod accounting_export;
 pub mod canteen_hours;
 pub mod canteen_scheduler;
+pub mod email;
 pub mod hold_cleanup;
+pub mod menu_scheduler;
+pub mod menu_transfer;
+pub mod outbox;
 pub mod phonepe;
+pub mod pricing;
+pub mod push;
+pub mod receipts;
+pub mod refunds;
+pub mod settlement;
+pub mod tickets;
+pub mod webhooks;
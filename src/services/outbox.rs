@@ -0,0 +1,354 @@
+use crate::db::{OutboxOperations, RepositoryError};
+use crate::models::common::OutboxEntry;
+use crate::services::email::{Email, MailOutcome, MailSender};
+use crate::services::push::{PushMessage, PushNotifier, PushOutcome};
+use crate::services::refunds::{RefundOutcome, RefundSender};
+use crate::services::webhooks::{WebhookDispatcher, WebhookEvent};
+use crate::sse::{SseBroker, SseEvent};
+use actix_web::web;
+use chrono::Utc;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::{interval, Duration};
+
+/// Claimed entries are left alone by other dispatchers for this long; if delivery has not been
+/// recorded by then, they are delivered again.
+const CLAIM_LEASE_SECS: i64 = 60;
+
+/// A side effect of a state change, recorded in the outbox by the transaction making the change
+/// and delivered at least once afterwards.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "topic", rename_all = "snake_case")]
+pub enum OutboxMessage {
+    /// An event for a user's stream. Events that warrant a push also queue a
+    /// [`DevicePush`](Self::DevicePush) for each of the user's devices.
+    UserEvent { user_id: i32, event: SseEvent },
+    /// A push to one of a user's devices, delivered once the push service has accepted it.
+    DevicePush {
+        user_id: i32,
+        token: String,
+        message: PushMessage,
+    },
+    /// An event for a canteen's order stream.
+    CanteenEvent { canteen_id: i32, event: SseEvent },
+    /// An event for everyone subscribed to a canteen's inventory.
+    CanteenSubscriptionEvent { canteen_id: i32, event: SseEvent },
+    /// An event for the canteen's webhooks, queued for each subscribed one.
+    CanteenWebhook {
+        canteen_id: i32,
+        event: WebhookEvent,
+    },
+    /// A refund to send to PhonePe, delivered once PhonePe has accepted it.
+    Refund { refund_id: i32 },
+    /// An email to one of a user's addresses, such as an order receipt, delivered once the
+    /// mail server has accepted it.
+    Email { user_id: i32, email: Email },
+}
+
+impl OutboxMessage {
+    pub fn topic(&self) -> &'static str {
+        match self {
+            OutboxMessage::UserEvent { .. } => "user_event",
+            OutboxMessage::DevicePush { .. } => "device_push",
+            OutboxMessage::CanteenEvent { .. } => "canteen_event",
+            OutboxMessage::CanteenSubscriptionEvent { .. } => "canteen_subscription_event",
+            OutboxMessage::CanteenWebhook { .. } => "canteen_webhook",
+            OutboxMessage::Refund { .. } => "refund",
+            OutboxMessage::Email { .. } => "email",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OutboxConfig {
+    pub poll_secs: u64,
+    pub batch_size: i64,
+}
+
+impl OutboxConfig {
+    /// Reads `OUTBOX_POLL_SECS` (default 5) and `OUTBOX_BATCH_SIZE` (default 100).
+    pub fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<T>().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            poll_secs: env_or("OUTBOX_POLL_SECS", 5u64).max(1),
+            batch_size: env_or("OUTBOX_BATCH_SIZE", 100i64).max(1),
+        }
+    }
+}
+
+/// Where outbox entries are delivered to.
+struct Destinations {
+    broker: SseBroker,
+    notifier: PushNotifier,
+    webhooks: WebhookDispatcher,
+    refunds: RefundSender,
+    mail: MailSender,
+}
+
+/// Delivers outbox entries in the background. Handlers [`wake`](Self::wake) it after a
+/// state change commits; it also polls, picking up entries another instance left behind.
+#[derive(Clone)]
+pub struct OutboxDispatcher {
+    wake: Arc<Notify>,
+}
+
+impl OutboxDispatcher {
+    pub fn start(
+        outbox_ops: OutboxOperations,
+        broker: SseBroker,
+        notifier: PushNotifier,
+        webhooks: WebhookDispatcher,
+        refunds: RefundSender,
+        mail: MailSender,
+        cfg: OutboxConfig,
+    ) -> Self {
+        let wake = Arc::new(Notify::new());
+        let destinations = Destinations {
+            broker,
+            notifier,
+            webhooks,
+            refunds,
+            mail,
+        };
+        tokio::spawn(Self::run(outbox_ops, destinations, cfg, wake.clone()));
+        Self { wake }
+    }
+
+    /// Deliver what is due now rather than at the next poll.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    async fn run(
+        outbox_ops: OutboxOperations,
+        destinations: Destinations,
+        cfg: OutboxConfig,
+        wake: Arc<Notify>,
+    ) {
+        let mut tick = interval(Duration::from_secs(cfg.poll_secs));
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {},
+                _ = wake.notified() => {},
+            }
+            loop {
+                match dispatch_due(&outbox_ops, &destinations, &cfg, &wake).await {
+                    Ok(claimed) if claimed as i64 == cfg.batch_size => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!("Outbox dispatcher error: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Deliver one batch of due entries. Returns how many were claimed.
+async fn dispatch_due(
+    outbox_ops: &OutboxOperations,
+    destinations: &Destinations,
+    cfg: &OutboxConfig,
+    wake: &Arc<Notify>,
+) -> Result<usize, RepositoryError> {
+    let Destinations {
+        broker,
+        notifier,
+        webhooks,
+        refunds,
+        mail,
+    } = destinations;
+    let due = web::block({
+        let outbox_ops = outbox_ops.clone();
+        let limit = cfg.batch_size;
+        move || outbox_ops.claim_due(limit, CLAIM_LEASE_SECS)
+    })
+    .await
+    .map_err(|e| RepositoryError::InternalError(format!("blocking error: {}", e)))??;
+    let claimed = due.len();
+    if claimed == 0 {
+        return Ok(0);
+    }
+
+    let mut delivered = Vec::with_capacity(claimed);
+    let mut undeliverable = Vec::new();
+    let mut webhook_events = Vec::new();
+    let mut pushes = Vec::new();
+    let mut refund_sends = Vec::new();
+    let mut emails = Vec::new();
+    for entry in due {
+        match deliver(broker, &entry) {
+            Ok(Delivery::Done) => delivered.push(entry.outbox_id),
+            Ok(Delivery::Webhook(canteen_id, event)) => {
+                webhook_events.push((entry.outbox_id, entry.created_at, canteen_id, event))
+            }
+            Ok(Delivery::Push(token, message)) => {
+                pushes.push((entry.outbox_id, entry.attempts, token, message))
+            }
+            Ok(Delivery::Refund(refund_id)) => {
+                refund_sends.push((entry.outbox_id, entry.attempts, refund_id))
+            }
+            Ok(Delivery::Email(email)) => emails.push((entry.outbox_id, entry.attempts, email)),
+            Err(e) => {
+                error!(
+                    "dispatch_due: giving up on outbox entry {} ({}): {}",
+                    entry.outbox_id, entry.topic, e
+                );
+                undeliverable.push((entry.outbox_id, e));
+            }
+        }
+    }
+
+    let push_outcomes = join_all(pushes.iter().map(
+        |(outbox_id, attempts, token, message)| async move {
+            (
+                *outbox_id,
+                notifier
+                    .send(token, message, (*attempts).max(0) as u32)
+                    .await,
+            )
+        },
+    ))
+    .await;
+    let mut retries = Vec::new();
+    for (outbox_id, outcome) in push_outcomes {
+        match outcome {
+            PushOutcome::Done => delivered.push(outbox_id),
+            PushOutcome::Retry(delay, failure) => retries.push((outbox_id, failure, delay)),
+            PushOutcome::GiveUp(failure) => undeliverable.push((outbox_id, failure)),
+        }
+    }
+    let refund_outcomes = join_all(refund_sends.iter().map(
+        |(outbox_id, attempts, refund_id)| async move {
+            (
+                *outbox_id,
+                refunds.send(*refund_id, (*attempts).max(0) as u32).await,
+            )
+        },
+    ))
+    .await;
+    for (outbox_id, outcome) in refund_outcomes {
+        match outcome {
+            RefundOutcome::Done => delivered.push(outbox_id),
+            RefundOutcome::Retry(delay, failure) => retries.push((outbox_id, failure, delay)),
+            RefundOutcome::GiveUp(failure) => undeliverable.push((outbox_id, failure)),
+        }
+    }
+    let mail_outcomes = join_all(emails.into_iter().map(
+        |(outbox_id, attempts, email)| async move {
+            (outbox_id, mail.send(email, attempts.max(0) as u32).await)
+        },
+    ))
+    .await;
+    for (outbox_id, outcome) in mail_outcomes {
+        match outcome {
+            MailOutcome::Done => delivered.push(outbox_id),
+            MailOutcome::Retry(delay, failure) => retries.push((outbox_id, failure, delay)),
+            MailOutcome::GiveUp(failure) => undeliverable.push((outbox_id, failure)),
+        }
+    }
+    debug!("dispatch_due: delivered {} outbox entries", delivered.len());
+    let next_retry = retries.iter().map(|(_, _, delay)| *delay).min();
+
+    let queued_webhooks = web::block({
+        let outbox_ops = outbox_ops.clone();
+        let webhooks = webhooks.clone();
+        move || {
+            // Webhook events that cannot be queued now are retried once their lease runs out.
+            let mut queued = 0;
+            for (outbox_id, created_at, canteen_id, event) in webhook_events {
+                match webhooks.record_event(
+                    canteen_id,
+                    &format!("evt_{outbox_id}"),
+                    created_at,
+                    &event,
+                ) {
+                    Ok(count) => {
+                        queued += count;
+                        delivered.push(outbox_id);
+                    }
+                    Err(e) => error!(
+                        "dispatch_due: error queueing webhooks for outbox entry {}: {}",
+                        outbox_id, e
+                    ),
+                }
+            }
+            outbox_ops.mark_delivered(&delivered)?;
+            for (outbox_id, failure) in undeliverable {
+                outbox_ops.record_failure(outbox_id, &failure, None)?;
+            }
+            for (outbox_id, failure, delay) in retries {
+                let retry_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+                outbox_ops.record_failure(outbox_id, &failure, Some(retry_at))?;
+            }
+            Ok::<usize, RepositoryError>(queued)
+        }
+    })
+    .await
+    .map_err(|e| RepositoryError::InternalError(format!("blocking error: {}", e)))??;
+    if queued_webhooks > 0 {
+        webhooks.wake();
+    }
+    // Retries can be due well before the next poll.
+    if let Some(delay) = next_retry {
+        let wake = wake.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            wake.notify_one();
+        });
+    }
+    Ok(claimed)
+}
+
+/// What is left to do for an entry once it has been published.
+enum Delivery {
+    /// Nothing; the entry is delivered.
+    Done,
+    /// Queue the event for the canteen's webhooks, with the rest of the batch.
+    Webhook(i32, WebhookEvent),
+    /// Send the push; the entry is delivered once the push service accepts it.
+    Push(String, PushMessage),
+    /// Send the refund; the entry is delivered once PhonePe accepts it.
+    Refund(i32),
+    /// Send the email; the entry is delivered once the mail server accepts it.
+    Email(Email),
+}
+
+/// Publish an entry. Webhook events, pushes, refunds and emails are handed back to be sent with
+/// the rest of the batch.
+fn deliver(broker: &SseBroker, entry: &OutboxEntry) -> Result<Delivery, String> {
+    let message = serde_json::from_str::<OutboxMessage>(&entry.payload)
+        .map_err(|e| format!("unreadable payload: {}", e))?;
+    match message {
+        OutboxMessage::UserEvent { user_id, event } => {
+            broker.publish_user_event(user_id, &event);
+        }
+        OutboxMessage::DevicePush { token, message, .. } => {
+            return Ok(Delivery::Push(token, message));
+        }
+        OutboxMessage::CanteenEvent { canteen_id, event } => {
+            broker.publish_canteen_event(canteen_id, &event);
+        }
+        OutboxMessage::CanteenSubscriptionEvent { canteen_id, event } => {
+            broker.publish_canteen_subscription_event(canteen_id, &event);
+        }
+        OutboxMessage::CanteenWebhook { canteen_id, event } => {
+            return Ok(Delivery::Webhook(canteen_id, event));
+        }
+        OutboxMessage::Refund { refund_id } => {
+            return Ok(Delivery::Refund(refund_id));
+        }
+        OutboxMessage::Email { email, .. } => {
+            return Ok(Delivery::Email(email));
+        }
+    }
+    Ok(Delivery::Done)
+}
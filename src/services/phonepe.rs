@@ -64,6 +64,20 @@ struct CreateOrderRequest<'a> {
     // enabled_payment_modes: Vec<EnabledPaymentMode>,
 }
 
+#[derive(Debug, Clone)]
+pub struct PhonePeRefundResult {
+    pub refund_id: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateRefundRequest<'a> {
+    merchant_refund_id: &'a str,
+    original_merchant_order_id: &'a str,
+    amount: i32,
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct PaymentFlow {
     #[serde(rename = "type")]
@@ -574,6 +588,70 @@ impl PhonePeClient {
         })
     }
 
+    /// Refund `amount` paisa of a completed payment. PhonePe treats a repeated
+    /// `merchant_refund_id` as the same refund, so retrying is safe.
+    pub async fn create_refund(
+        &self,
+        merchant_refund_id: &str,
+        original_merchant_order_id: &str,
+        amount: i32,
+    ) -> Result<PhonePeRefundResult, String> {
+        self.ensure_enabled()?;
+
+        let token = self.get_oauth_token().await?;
+        let url = format!(
+            "{}/payments/v2/refund",
+            PhonePeConfig::trim_base(&self.cfg.pg_base_url)
+        );
+        let body = serde_json::to_string(&CreateRefundRequest {
+            merchant_refund_id,
+            original_merchant_order_id,
+            amount,
+        })
+        .map_err(|e| format!("failed to serialize refund payload: {}", e))?;
+        let resp = self
+            .http
+            .post(url)
+            .header(AUTHORIZATION, format!("O-Bearer {}", token))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("PhonePe refund request failed: {}", e))?;
+
+        let status = resp.status();
+        let resp_text = resp
+            .text()
+            .await
+            .map_err(|e| format!("failed to read refund response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("PhonePe refund returned {}: {}", status, resp_text));
+        }
+
+        let value: serde_json::Value = serde_json::from_str(&resp_text)
+            .map_err(|e| format!("invalid refund response JSON: {}", e))?;
+        let refund_id = extract_string_from_paths(
+            &value,
+            &[
+                &["refundId"],
+                &["data", "refundId"],
+                &["payload", "refundId"],
+            ],
+        )
+        .ok_or_else(|| "PhonePe refund response missing refundId".to_string())?;
+        let state = extract_string_from_paths(
+            &value,
+            &[&["state"], &["data", "state"], &["payload", "state"]],
+        )
+        .map(|v| v.to_uppercase())
+        .ok_or_else(|| "PhonePe refund response missing state".to_string())?;
+        debug!(
+            "create_refund: merchant_refund_id {} refund_id {} state {}",
+            merchant_refund_id, refund_id, state
+        );
+        Ok(PhonePeRefundResult { refund_id, state })
+    }
+
     pub async fn fetch_order_state(&self, merchant_order_id: &str) -> Result<String, String> {
         self.ensure_enabled()?;
 
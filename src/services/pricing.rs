@@ -0,0 +1,170 @@
+use crate::enums::admin::PricingRuleDetails;
+use crate::enums::common::AppliedPricingRule;
+use crate::models::admin::PricingRuleKindEnum;
+use crate::services::menu_scheduler::is_within_schedule;
+use chrono::NaiveDateTime;
+use std::collections::{BTreeMap, HashMap};
+
+/// One distinct item of an order at its menu price.
+#[derive(Debug, Clone)]
+pub struct PricingLine {
+    pub item_id: i32,
+    pub unit_price: i32,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct PricedOrder {
+    /// Effective unit price of each item, snapshotted on the hold.
+    pub unit_prices: HashMap<i32, i32>,
+    pub total_price: i32,
+    pub applied_rules: Vec<AppliedPricingRule>,
+}
+
+/// Whether the rule is switched on and `now_local` falls inside one of its windows.
+pub fn is_rule_active(rule: &PricingRuleDetails, now_local: NaiveDateTime) -> bool {
+    rule.is_active && (rule.windows.is_empty() || is_within_schedule(&rule.windows, now_local))
+}
+
+/// Price an order against a canteen's rules.
+///
+/// Combos are matched first, in rule id order, as many times as the order allows. Units
+/// left over get the single best percent or flat discount covering them; discounts never
+/// stack. A combo's price is spread over its items in proportion to their menu price, and
+/// each item's snapshot price is its average unit price rounded down, so the total charged
+/// is `sum(unit_price * quantity)` and rounding favours the customer. The applied rules'
+/// discounts add up to the list price minus the total; the last one takes what rounding
+/// leaves over.
+pub fn price_order(
+    lines: &[PricingLine],
+    rules: &[PricingRuleDetails],
+    now_local: NaiveDateTime,
+) -> PricedOrder {
+    let mut active = rules
+        .iter()
+        .filter(|rule| is_rule_active(rule, now_local))
+        .collect::<Vec<&PricingRuleDetails>>();
+    active.sort_by_key(|rule| rule.rule_id);
+
+    let menu_price: HashMap<i32, i64> = lines
+        .iter()
+        .map(|line| (line.item_id, line.unit_price as i64))
+        .collect();
+    let mut remaining: HashMap<i32, i32> = lines
+        .iter()
+        .map(|line| (line.item_id, line.quantity))
+        .collect();
+    let mut charged: HashMap<i32, i64> = HashMap::new();
+    let mut applied_rules: Vec<AppliedPricingRule> = Vec::new();
+
+    for rule in active
+        .iter()
+        .filter(|rule| rule.kind == PricingRuleKindEnum::Combo)
+    {
+        let combo_price = rule.combo_price.unwrap_or_default() as i64;
+        let mut needed: BTreeMap<i32, i32> = BTreeMap::new();
+        for item in &rule.item_ids {
+            *needed.entry(*item).or_default() += 1;
+        }
+        let times = needed
+            .iter()
+            .map(|(item, units)| remaining.get(item).copied().unwrap_or(0) / units)
+            .min()
+            .unwrap_or(0);
+        if times == 0 {
+            continue;
+        }
+        let list_total: i64 = needed
+            .iter()
+            .map(|(item, units)| menu_price[item] * *units as i64)
+            .sum();
+        if combo_price >= list_total {
+            continue;
+        }
+
+        // The last item takes whatever rounding leaves over.
+        let mut allotted = 0;
+        for (index, (item, units)) in needed.iter().enumerate() {
+            let share = if index + 1 == needed.len() {
+                combo_price - allotted
+            } else {
+                combo_price * menu_price[item] * *units as i64 / list_total
+            };
+            allotted += share;
+            *charged.entry(*item).or_default() += share * times as i64;
+            if let Some(left) = remaining.get_mut(item) {
+                *left -= units * times;
+            }
+        }
+        record_discount(
+            &mut applied_rules,
+            rule,
+            (list_total - combo_price) * times as i64,
+        );
+    }
+
+    for line in lines {
+        let units = remaining.get(&line.item_id).copied().unwrap_or(0) as i64;
+        if units == 0 {
+            continue;
+        }
+        let best = active
+            .iter()
+            .filter(|rule| rule.item_ids.is_empty() || rule.item_ids.contains(&line.item_id))
+            .map(|rule| (unit_discount(rule, line.unit_price), *rule))
+            .filter(|(discount, _)| *discount > 0)
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.rule_id.cmp(&a.1.rule_id)));
+        let discount = best.map(|(discount, _)| discount).unwrap_or(0);
+        *charged.entry(line.item_id).or_default() += (line.unit_price as i64 - discount) * units;
+        if let Some((discount, rule)) = best {
+            record_discount(&mut applied_rules, rule, discount * units);
+        }
+    }
+
+    let mut priced = PricedOrder {
+        applied_rules,
+        ..Default::default()
+    };
+    for line in lines {
+        let line_total = charged.get(&line.item_id).copied().unwrap_or(0);
+        let unit_price = (line_total / line.quantity.max(1) as i64) as i32;
+        priced.unit_prices.insert(line.item_id, unit_price);
+        priced.total_price += unit_price * line.quantity;
+    }
+    let list_total: i64 = lines
+        .iter()
+        .map(|line| line.unit_price as i64 * line.quantity as i64)
+        .sum();
+    let discounted: i64 = priced
+        .applied_rules
+        .iter()
+        .map(|rule| rule.discount as i64)
+        .sum();
+    if let Some(last) = priced.applied_rules.last_mut() {
+        last.discount += (list_total - priced.total_price as i64 - discounted) as i32;
+    }
+    priced
+}
+
+/// Discount a percent or flat rule gives on one unit; combos are handled separately.
+fn unit_discount(rule: &PricingRuleDetails, unit_price: i32) -> i64 {
+    match rule.kind {
+        PricingRuleKindEnum::PercentOff => {
+            unit_price as i64 * rule.percent_off.unwrap_or_default() as i64 / 100
+        }
+        PricingRuleKindEnum::FlatOff => rule.amount_off.unwrap_or_default().min(unit_price) as i64,
+        PricingRuleKindEnum::Combo => 0,
+    }
+}
+
+fn record_discount(applied: &mut Vec<AppliedPricingRule>, rule: &PricingRuleDetails, amount: i64) {
+    match applied.iter_mut().find(|a| a.rule_id == rule.rule_id) {
+        Some(existing) => existing.discount += amount as i32,
+        None => applied.push(AppliedPricingRule {
+            rule_id: rule.rule_id,
+            name: rule.name.clone(),
+            kind: rule.kind,
+            discount: amount as i32,
+        }),
+    }
+}
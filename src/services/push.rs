@@ -0,0 +1,469 @@
+use crate::db::DeviceOperations;
+use crate::sse::SseEvent;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use log::{debug, info, warn};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const TOKEN_EXPIRY_SAFETY_SECS: i64 = 60;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// A notification for one device.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushMessage {
+    pub title: String,
+    pub body: String,
+    /// Lets the app route the notification; FCM only carries string values.
+    pub data: BTreeMap<String, String>,
+}
+
+impl PushMessage {
+    /// The push for an event of a user's SSE stream, if it warrants one. Pending payments are
+    /// left to the foreground app.
+    pub fn for_event(event: &SseEvent) -> Option<Self> {
+        let (title, body) = match event {
+            SseEvent::UserOrderUpdate { order_id, status } => match status.as_str() {
+                "placed" => (
+                    "Order placed".to_string(),
+                    format!("Your order #{order_id} has been placed."),
+                ),
+                "delivered" => (
+                    "Order delivered".to_string(),
+                    format!("Your order #{order_id} has been delivered."),
+                ),
+                "cancelled" => (
+                    "Order cancelled".to_string(),
+                    format!("Your order #{order_id} was cancelled."),
+                ),
+                other => (
+                    "Order update".to_string(),
+                    format!("Your order #{order_id} is now {other}."),
+                ),
+            },
+            SseEvent::PaymentUpdate { payment_state, .. } => match payment_state.as_str() {
+                "COMPLETED" => (
+                    "Payment received".to_string(),
+                    "Your payment went through and your order is confirmed.".to_string(),
+                ),
+                "FAILED" => (
+                    "Payment failed".to_string(),
+                    "Your payment did not go through. Please try again.".to_string(),
+                ),
+                _ => return None,
+            },
+            SseEvent::InventoryUpdate { .. } | SseEvent::CanteenAggregatedOrderUpdate { .. } => {
+                return None
+            }
+        };
+
+        let mut data = BTreeMap::from([("event".to_string(), event.event_name().to_string())]);
+        if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(event) {
+            for (key, value) in fields {
+                let value = match value {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                data.insert(key, value);
+            }
+        }
+        Some(Self { title, body, data })
+    }
+}
+
+#[derive(Debug)]
+pub enum PushError {
+    /// The device is no longer registered; its token should be forgotten.
+    Unregistered,
+    /// Worth retrying later: network errors, rate limiting, server errors.
+    Transient(String),
+    Permanent(String),
+}
+
+/// Delivers a push to one device.
+pub trait PushSender: Send + Sync {
+    fn send<'a>(
+        &'a self,
+        token: &'a str,
+        message: &'a PushMessage,
+    ) -> BoxFuture<'a, Result<(), PushError>>;
+}
+
+/// Local stand-in used when FCM is not configured: logs pushes instead of sending them.
+pub struct LogPushSender;
+
+impl PushSender for LogPushSender {
+    fn send<'a>(
+        &'a self,
+        token: &'a str,
+        message: &'a PushMessage,
+    ) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(async move {
+            info!(
+                "push (not sent, FCM disabled) to {}: {} - {}",
+                token_suffix(token),
+                message.title,
+                message.body
+            );
+            Ok(())
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FcmConfig {
+    pub project_id: String,
+    pub base_url: String,
+    pub token_url: String,
+    pub http_timeout_secs: u64,
+    credentials: FcmCredentials,
+}
+
+#[derive(Clone, Debug)]
+enum FcmCredentials {
+    /// A fixed bearer token, for local FCM stand-ins.
+    AccessToken(String),
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+    },
+}
+
+impl FcmConfig {
+    /// `None` unless `FCM_ENABLED` is set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let enabled = std::env::var("FCM_ENABLED")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let project_id = std::env::var("FCM_PROJECT_ID")
+            .map_err(|_| "FCM_PROJECT_ID must be set when FCM_ENABLED=true".to_string())?;
+        let credentials = match non_empty_env("FCM_ACCESS_TOKEN") {
+            Some(token) => FcmCredentials::AccessToken(token),
+            None => FcmCredentials::ServiceAccount {
+                client_email: non_empty_env("FCM_CLIENT_EMAIL").ok_or_else(|| {
+                    "FCM_CLIENT_EMAIL must be set when FCM_ENABLED=true".to_string()
+                })?,
+                // Keys pasted into env files usually have their newlines escaped.
+                private_key: non_empty_env("FCM_PRIVATE_KEY")
+                    .ok_or_else(|| "FCM_PRIVATE_KEY must be set when FCM_ENABLED=true".to_string())?
+                    .replace("\\n", "\n"),
+            },
+        };
+        let base_url = non_empty_env("FCM_BASE_URL")
+            .unwrap_or_else(|| "https://fcm.googleapis.com".to_string());
+        let token_url = non_empty_env("FCM_TOKEN_URL")
+            .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string());
+        let http_timeout_secs = std::env::var("FCM_HTTP_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10);
+
+        Ok(Some(Self {
+            project_id,
+            base_url,
+            token_url,
+            http_timeout_secs,
+            credentials,
+        }))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CachedAccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Sends through the FCM HTTP v1 API.
+pub struct FcmSender {
+    cfg: FcmConfig,
+    http: reqwest::Client,
+    token_cache: RwLock<Option<CachedAccessToken>>,
+}
+
+#[derive(Serialize)]
+struct ServiceAccountClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+impl FcmSender {
+    pub fn new(cfg: FcmConfig) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(cfg.http_timeout_secs))
+            .build()
+            .map_err(|e| format!("failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            cfg,
+            http,
+            token_cache: RwLock::new(None),
+        })
+    }
+
+    async fn access_token(&self) -> Result<String, PushError> {
+        let (client_email, private_key) = match &self.cfg.credentials {
+            FcmCredentials::AccessToken(token) => return Ok(token.clone()),
+            FcmCredentials::ServiceAccount {
+                client_email,
+                private_key,
+            } => (client_email, private_key),
+        };
+        if let Some(cached) = self.token_cache.read().await.as_ref() {
+            if cached.expires_at - chrono::Duration::seconds(TOKEN_EXPIRY_SAFETY_SECS) > Utc::now()
+            {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        let claims = ServiceAccountClaims {
+            iss: client_email,
+            scope: FCM_SCOPE,
+            aud: &self.cfg.token_url,
+            iat: now,
+            exp: now + 3600,
+        };
+        let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+            .map_err(|e| PushError::Permanent(format!("invalid FCM_PRIVATE_KEY: {}", e)))?;
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &key)
+            .map_err(|e| PushError::Permanent(format!("failed to sign FCM assertion: {}", e)))?;
+
+        let resp = self
+            .http
+            .post(&self.cfg.token_url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(format!(
+                "grant_type={}&assertion={}",
+                urlencoding::encode("urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                urlencoding::encode(&assertion)
+            ))
+            .send()
+            .await
+            .map_err(|e| PushError::Transient(format!("FCM OAuth request failed: {}", e)))?;
+        let status = resp.status();
+        let resp_text = resp
+            .text()
+            .await
+            .map_err(|e| PushError::Transient(format!("failed to read OAuth response: {}", e)))?;
+        if !status.is_success() {
+            return Err(PushError::Transient(format!(
+                "FCM OAuth returned {}: {}",
+                status, resp_text
+            )));
+        }
+        let value: serde_json::Value = serde_json::from_str(&resp_text)
+            .map_err(|e| PushError::Transient(format!("invalid OAuth JSON: {}", e)))?;
+        let token = value["access_token"]
+            .as_str()
+            .ok_or_else(|| PushError::Transient("OAuth response missing access_token".into()))?
+            .to_string();
+        let expires_in = value["expires_in"].as_i64().unwrap_or(3600);
+
+        *self.token_cache.write().await = Some(CachedAccessToken {
+            token: token.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(expires_in),
+        });
+        Ok(token)
+    }
+
+    async fn send_message(&self, token: &str, message: &PushMessage) -> Result<(), PushError> {
+        let access_token = self.access_token().await?;
+        let url = format!(
+            "{}/v1/projects/{}/messages:send",
+            self.cfg.base_url.trim_end_matches('/'),
+            self.cfg.project_id
+        );
+        let body = serde_json::json!({
+            "message": {
+                "token": token,
+                "notification": { "title": message.title, "body": message.body },
+                "data": message.data,
+                "android": { "priority": "HIGH" },
+                "apns": { "headers": { "apns-priority": "10" } },
+            }
+        });
+
+        let resp = self
+            .http
+            .post(url)
+            .header(AUTHORIZATION, format!("Bearer {}", access_token))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| PushError::Transient(format!("FCM send request failed: {}", e)))?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let resp_text = resp.text().await.unwrap_or_default();
+        if status == StatusCode::NOT_FOUND || resp_text.contains("UNREGISTERED") {
+            return Err(PushError::Unregistered);
+        }
+        let failure = format!("FCM send returned {}: {}", status, resp_text);
+        if status == StatusCode::UNAUTHORIZED {
+            // The cached token may have been revoked; fetch a new one on the retry.
+            *self.token_cache.write().await = None;
+            return Err(PushError::Transient(failure));
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(PushError::Transient(failure));
+        }
+        Err(PushError::Permanent(failure))
+    }
+}
+
+impl PushSender for FcmSender {
+    fn send<'a>(
+        &'a self,
+        token: &'a str,
+        message: &'a PushMessage,
+    ) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(self.send_message(token, message))
+    }
+}
+
+/// How often, and how patiently, transient failures are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Reads `PUSH_MAX_ATTEMPTS` (default 5) and `PUSH_RETRY_BASE_MS` (default 1000).
+    pub fn from_env() -> Self {
+        Self {
+            max_attempts: std::env::var("PUSH_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(5)
+                .max(1),
+            base_delay: Duration::from_millis(
+                std::env::var("PUSH_RETRY_BASE_MS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(1000),
+            ),
+        }
+    }
+
+    /// Exponential backoff after the given (zero-based) failed attempt.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// What became of a push.
+#[derive(Debug)]
+pub enum PushOutcome {
+    /// The push service accepted it, or the device is gone and its token was forgotten.
+    Done,
+    /// Failed for now; worth trying again after the delay.
+    Retry(Duration, String),
+    GiveUp(String),
+}
+
+/// Sends pushes to users' devices for the outbox, which retries transient failures with the
+/// backoff of the [`RetryPolicy`]. Tokens that are no longer registered are forgotten.
+#[derive(Clone)]
+pub struct PushNotifier {
+    sender: Arc<dyn PushSender>,
+    devices: DeviceOperations,
+    retry: RetryPolicy,
+}
+
+impl PushNotifier {
+    pub fn new(sender: Arc<dyn PushSender>, devices: DeviceOperations, retry: RetryPolicy) -> Self {
+        Self {
+            sender,
+            devices,
+            retry,
+        }
+    }
+
+    /// FCM when `FCM_ENABLED`, otherwise the logging stand-in.
+    pub fn from_env(devices: DeviceOperations) -> Result<Self, String> {
+        let sender: Arc<dyn PushSender> = match FcmConfig::from_env()? {
+            Some(cfg) => Arc::new(FcmSender::new(cfg)?),
+            None => Arc::new(LogPushSender),
+        };
+        Ok(Self::new(sender, devices, RetryPolicy::from_env()))
+    }
+
+    /// Send a push to one device; `failed_attempts` is how many earlier attempts failed.
+    pub async fn send(
+        &self,
+        token: &str,
+        message: &PushMessage,
+        failed_attempts: u32,
+    ) -> PushOutcome {
+        match self.sender.send(token, message).await {
+            Ok(()) => {
+                debug!("push: delivered to {}", token_suffix(token));
+                PushOutcome::Done
+            }
+            Err(PushError::Unregistered) => {
+                debug!(
+                    "push: forgetting unregistered device {}",
+                    token_suffix(token)
+                );
+                let devices = self.devices.clone();
+                let stale_token = token.to_string();
+                let _ =
+                    tokio::task::spawn_blocking(move || devices.remove_token(&stale_token)).await;
+                PushOutcome::Done
+            }
+            Err(PushError::Transient(e)) if failed_attempts + 1 < self.retry.max_attempts => {
+                let delay = self.retry.delay(failed_attempts);
+                debug!(
+                    "push: attempt {} to {} failed, retrying in {:?}: {}",
+                    failed_attempts + 1,
+                    token_suffix(token),
+                    delay,
+                    e
+                );
+                PushOutcome::Retry(delay, e)
+            }
+            Err(PushError::Transient(e)) | Err(PushError::Permanent(e)) => {
+                warn!(
+                    "push: giving up on {} after {} attempts: {}",
+                    token_suffix(token),
+                    failed_attempts + 1,
+                    e
+                );
+                PushOutcome::GiveUp(e)
+            }
+        }
+    }
+}
+
+/// Enough of a token to tell devices apart in logs without logging the token.
+fn token_suffix(token: &str) -> String {
+    let start = token.len().saturating_sub(6);
+    format!("…{}", token.get(start..).unwrap_or_default())
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, FixedOffset, Utc};
+
+/// One item line of a receipt, at the unit price the order was placed at.
+#[derive(Debug, Clone)]
+pub struct ReceiptLine {
+    pub name: String,
+    pub quantity: i16,
+    pub unit_price: i32,
+}
+
+impl ReceiptLine {
+    fn line_total(&self) -> i32 {
+        self.unit_price * self.quantity as i32
+    }
+}
+
+/// Everything a student's order receipt shows. Prices are in rupees.
+#[derive(Debug, Clone)]
+pub struct OrderReceipt {
+    pub order_id: i32,
+    pub customer_name: String,
+    pub canteen_name: String,
+    pub canteen_location: String,
+    /// The delivery slot, or "Instant" for orders picked up right away.
+    pub slot: String,
+    pub ordered_at: DateTime<Utc>,
+    pub lines: Vec<ReceiptLine>,
+    pub total_price: i32,
+    /// The PhonePe merchant order id, when the order was paid through the app.
+    pub payment_reference: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl OrderReceipt {
+    /// Promo discounts apply to the order total, so they show up as the gap between the item
+    /// lines and the total.
+    fn discount(&self) -> i32 {
+        let subtotal: i32 = self.lines.iter().map(ReceiptLine::line_total).sum();
+        (subtotal - self.total_price).max(0)
+    }
+
+    fn payment_line(&self) -> String {
+        match &self.payment_reference {
+            Some(reference) => format!("Paid via PhonePe, reference {reference}"),
+            None => "Paid at the canteen".to_string(),
+        }
+    }
+
+    pub fn render(&self, tz: FixedOffset) -> RenderedEmail {
+        let ordered_at = self
+            .ordered_at
+            .with_timezone(&tz)
+            .format("%d %b %Y, %I:%M %p")
+            .to_string();
+        let subject = format!(
+            "Your receipt for order #{} at {}",
+            self.order_id, self.canteen_name
+        );
+        let discount = self.discount();
+
+        let mut text = format!(
+            "Hi {},\n\nThanks for your order! Here is your receipt.\n\n\
+             Order #{}\nCanteen: {}, {}\nSlot: {}\nOrdered at: {}\n\n",
+            self.customer_name,
+            self.order_id,
+            self.canteen_name,
+            self.canteen_location,
+            self.slot,
+            ordered_at
+        );
+        for line in &self.lines {
+            text.push_str(&format!(
+                "{} x {} @ Rs. {} = Rs. {}\n",
+                line.quantity,
+                line.name,
+                line.unit_price,
+                line.line_total()
+            ));
+        }
+        if discount > 0 {
+            text.push_str(&format!("Discount: -Rs. {discount}\n"));
+        }
+        text.push_str(&format!(
+            "Total: Rs. {}\n\n{}\n",
+            self.total_price,
+            self.payment_line()
+        ));
+
+        let rows = self
+            .lines
+            .iter()
+            .map(|line| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>&#8377;{}</td><td>&#8377;{}</td></tr>",
+                    escape_html(&line.name),
+                    line.quantity,
+                    line.unit_price,
+                    line.line_total()
+                )
+            })
+            .collect::<String>();
+        let discount_row = if discount > 0 {
+            format!("<tr><td colspan=\"3\">Discount</td><td>-&#8377;{discount}</td></tr>")
+        } else {
+            String::new()
+        };
+        let html = format!(
+            "<!DOCTYPE html><html><body>\
+             <p>Hi {name},</p><p>Thanks for your order! Here is your receipt.</p>\
+             <h2>Order #{order_id}</h2>\
+             <p>{canteen}, {location}<br>Slot: {slot}<br>Ordered at: {ordered_at}</p>\
+             <table><thead><tr><th>Item</th><th>Qty</th><th>Price</th><th>Amount</th></tr></thead>\
+             <tbody>{rows}{discount_row}\
+             <tr><th colspan=\"3\">Total</th><th>&#8377;{total}</th></tr></tbody></table>\
+             <p>{payment}</p></body></html>",
+            name = escape_html(&self.customer_name),
+            order_id = self.order_id,
+            canteen = escape_html(&self.canteen_name),
+            location = escape_html(&self.canteen_location),
+            slot = escape_html(&self.slot),
+            total = self.total_price,
+            payment = escape_html(&self.payment_line()),
+        );
+
+        RenderedEmail {
+            subject,
+            text,
+            html,
+        }
+    }
+}
+
+pub(crate) fn escape_html(raw: &str) -> String {
+    let mut escaped = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,114 @@
+use crate::db::PaymentOperations;
+use crate::services::phonepe::PhonePeClient;
+use crate::services::push::RetryPolicy;
+use log::{debug, warn};
+use std::time::Duration;
+
+/// Attempts at sending a refund before it is recorded as failed.
+const REFUND_MAX_ATTEMPTS: u32 = 8;
+/// Delay before the first retry; it doubles with each failed attempt.
+const REFUND_RETRY_BASE: Duration = Duration::from_secs(30);
+
+/// What became of sending a refund.
+#[derive(Debug)]
+pub enum RefundOutcome {
+    /// PhonePe accepted it, or there was nothing left to send.
+    Done,
+    /// Failed for now; worth trying again after the delay.
+    Retry(Duration, String),
+    /// Recorded as failed.
+    GiveUp(String),
+}
+
+/// Sends refunds to PhonePe for the outbox. Refunds that cannot be sent within the retry
+/// budget, or while PhonePe is disabled, are recorded as failed.
+#[derive(Clone)]
+pub struct RefundSender {
+    phonepe: PhonePeClient,
+    payment_ops: PaymentOperations,
+    retry: RetryPolicy,
+}
+
+impl RefundSender {
+    pub fn new(phonepe: PhonePeClient, payment_ops: PaymentOperations) -> Self {
+        Self {
+            phonepe,
+            payment_ops,
+            retry: RetryPolicy {
+                max_attempts: REFUND_MAX_ATTEMPTS,
+                base_delay: REFUND_RETRY_BASE,
+            },
+        }
+    }
+
+    /// Send a refund; `failed_attempts` is how many earlier attempts failed.
+    pub async fn send(&self, refund_id: i32, failed_attempts: u32) -> RefundOutcome {
+        let payment_ops = self.payment_ops.clone();
+        let pending = match tokio::task::spawn_blocking(move || {
+            payment_ops.refund_request(refund_id)
+        })
+        .await
+        {
+            Ok(Ok(pending)) => pending,
+            Ok(Err(e)) => {
+                return RefundOutcome::Retry(self.retry.delay(failed_attempts), e.to_string())
+            }
+            Err(e) => {
+                return RefundOutcome::Retry(self.retry.delay(failed_attempts), e.to_string())
+            }
+        };
+        let Some(pending) = pending else {
+            debug!("refund: {} has already been sent or settled", refund_id);
+            return RefundOutcome::Done;
+        };
+
+        match self
+            .phonepe
+            .create_refund(
+                &pending.merchant_refund_id,
+                &pending.original_merchant_order_id,
+                pending.amount,
+            )
+            .await
+        {
+            Ok(accepted) => {
+                let payment_ops = self.payment_ops.clone();
+                let recorded = tokio::task::spawn_blocking(move || {
+                    payment_ops.record_refund_submitted(
+                        refund_id,
+                        &accepted.refund_id,
+                        &accepted.state,
+                    )
+                })
+                .await;
+                match recorded {
+                    Ok(Ok(())) => RefundOutcome::Done,
+                    // PhonePe takes the same merchant refund id again, so a retry is harmless.
+                    Ok(Err(e)) => {
+                        RefundOutcome::Retry(self.retry.delay(failed_attempts), e.to_string())
+                    }
+                    Err(e) => {
+                        RefundOutcome::Retry(self.retry.delay(failed_attempts), e.to_string())
+                    }
+                }
+            }
+            Err(failure) => {
+                let attempts = failed_attempts + 1;
+                if self.phonepe.ensure_enabled().is_ok() && attempts < self.retry.max_attempts {
+                    return RefundOutcome::Retry(self.retry.delay(failed_attempts), failure);
+                }
+                warn!(
+                    "refund: giving up on refund {} after {} attempts: {}",
+                    refund_id, attempts, failure
+                );
+                let payment_ops = self.payment_ops.clone();
+                let reason = failure.clone();
+                let _ = tokio::task::spawn_blocking(move || {
+                    payment_ops.record_refund_failure(refund_id, &reason)
+                })
+                .await;
+                RefundOutcome::GiveUp(failure)
+            }
+        }
+    }
+}
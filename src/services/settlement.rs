@@ -0,0 +1,66 @@
+use crate::db::SettlementOperations;
+use tokio::time::{interval, Duration};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SettlementConfig {
+    /// The platform's cut of each day's takings less refunds, in basis points.
+    pub commission_bps: i32,
+    /// How long after a business day ends its statement is generated, so that payments
+    /// completed just before midnight have been recorded.
+    pub grace_secs: i64,
+    /// How many past business days are checked for missing statements.
+    pub lookback_days: i64,
+    pub poll_secs: u64,
+}
+
+impl SettlementConfig {
+    /// Reads `SETTLEMENT_COMMISSION_BPS` (default 0), `SETTLEMENT_GRACE_SECS` (default 3600),
+    /// `SETTLEMENT_LOOKBACK_DAYS` (default 7) and `SETTLEMENT_POLL_SECS` (default 900).
+    pub fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<T>().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            commission_bps: env_or("SETTLEMENT_COMMISSION_BPS", 0i32).clamp(0, 10_000),
+            grace_secs: env_or("SETTLEMENT_GRACE_SECS", 3600i64).max(0),
+            lookback_days: env_or("SETTLEMENT_LOOKBACK_DAYS", 7i64).max(1),
+            poll_secs: env_or("SETTLEMENT_POLL_SECS", 900u64).max(1),
+        }
+    }
+
+    /// The commission on `base` paisa, rounded to the nearest paisa; nothing on a loss.
+    pub fn commission_on(&self, base: i64) -> i64 {
+        if base <= 0 {
+            return 0;
+        }
+        (base * self.commission_bps as i64 + 5_000) / 10_000
+    }
+}
+
+pub async fn run_settlements(settlement_ops: SettlementOperations, cfg: SettlementConfig) {
+    let mut tick = interval(Duration::from_secs(cfg.poll_secs));
+    loop {
+        tick.tick().await;
+        match tokio::task::spawn_blocking({
+            let settlement_ops = settlement_ops.clone();
+            move || settlement_ops.generate_statements(&cfg, chrono::Utc::now())
+        })
+        .await
+        {
+            Ok(Ok(generated)) => {
+                if generated > 0 {
+                    info!("settlements: generated {} statements", generated);
+                }
+            }
+            Ok(Err(e)) => {
+                error!("settlements: error generating statements: {}", e);
+            }
+            Err(e) => {
+                error!("settlements: blocking task failed: {}", e);
+            }
+        }
+    }
+}
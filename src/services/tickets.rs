@@ -0,0 +1,272 @@
+use crate::services::receipts::escape_html;
+use chrono::{DateTime, FixedOffset, Utc};
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, QrCode};
+
+/// Characters per line; fits both 58mm and 80mm paper in the printer's default font.
+const TICKET_COLUMNS: usize = 32;
+/// Printer dots per QR module, and modules of blank margin around the code.
+const QR_MODULE_DOTS: usize = 6;
+const QR_QUIET_MODULES: usize = 4;
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// One item line of a kitchen ticket.
+#[derive(Debug, Clone)]
+pub struct TicketLine {
+    pub name: String,
+    pub quantity: i16,
+}
+
+/// What the kitchen needs to prepare and hand over an order.
+#[derive(Debug, Clone)]
+pub struct KitchenTicket {
+    pub order_id: i32,
+    pub canteen_name: String,
+    pub customer_name: String,
+    /// The delivery slot, or "Instant" for orders picked up right away.
+    pub slot: String,
+    pub ordered_at: DateTime<Utc>,
+    pub lines: Vec<TicketLine>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketFormat {
+    /// Raw ESC/POS commands for a thermal printer.
+    EscPos,
+    Text,
+    Html,
+}
+
+impl TicketFormat {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "escpos" => Some(TicketFormat::EscPos),
+            "text" => Some(TicketFormat::Text),
+            "html" => Some(TicketFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TicketFormat::EscPos => "application/octet-stream",
+            TicketFormat::Text => "text/plain; charset=utf-8",
+            TicketFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+impl KitchenTicket {
+    /// Render the ticket with a QR code of `qr_payload`, the token scanned at handover.
+    pub fn render(
+        &self,
+        format: TicketFormat,
+        qr_payload: &str,
+        tz: FixedOffset,
+    ) -> Result<Vec<u8>, String> {
+        let qr =
+            QrCode::new(qr_payload.as_bytes()).map_err(|e| format!("QR encoding error: {}", e))?;
+        Ok(match format {
+            TicketFormat::EscPos => self.render_escpos(&qr, tz),
+            TicketFormat::Text => self.render_text(&qr, tz).into_bytes(),
+            TicketFormat::Html => self.render_html(&qr, tz).into_bytes(),
+        })
+    }
+
+    fn ordered_at(&self, tz: FixedOffset) -> String {
+        self.ordered_at
+            .with_timezone(&tz)
+            .format("%d %b %Y, %I:%M %p")
+            .to_string()
+    }
+
+    fn render_escpos(&self, qr: &QrCode, tz: FixedOffset) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&[ESC, b'@']);
+        // Centered, double width and height.
+        out.extend_from_slice(&[ESC, b'a', 1, GS, b'!', 0x11]);
+        push_escpos_line(&mut out, &format!("ORDER #{}", self.order_id));
+        out.extend_from_slice(&[GS, b'!', 0x00]);
+        push_escpos_line(&mut out, &self.canteen_name);
+        out.extend_from_slice(&[ESC, b'a', 0]);
+        push_escpos_line(&mut out, &"=".repeat(TICKET_COLUMNS));
+        out.extend_from_slice(&[ESC, b'E', 1]);
+        push_escpos_line(&mut out, &format!("Slot: {}", self.slot));
+        out.extend_from_slice(&[ESC, b'E', 0]);
+        push_escpos_line(&mut out, &format!("For: {}", self.customer_name));
+        push_escpos_line(&mut out, &format!("Ordered: {}", self.ordered_at(tz)));
+        push_escpos_line(&mut out, &"-".repeat(TICKET_COLUMNS));
+        out.extend_from_slice(&[GS, b'!', 0x01]);
+        for line in &self.lines {
+            let prefix = format!("{:>2} x ", line.quantity);
+            for wrapped in wrap(&prefix, &line.name, "     ") {
+                push_escpos_line(&mut out, &wrapped);
+            }
+        }
+        out.extend_from_slice(&[GS, b'!', 0x00]);
+        if let Some(note) = &self.note {
+            push_escpos_line(&mut out, &"-".repeat(TICKET_COLUMNS));
+            out.extend_from_slice(&[ESC, b'E', 1]);
+            for wrapped in wrap("NOTE: ", note, "      ") {
+                push_escpos_line(&mut out, &wrapped);
+            }
+            out.extend_from_slice(&[ESC, b'E', 0]);
+        }
+        push_escpos_line(&mut out, &"=".repeat(TICKET_COLUMNS));
+        out.extend_from_slice(&[ESC, b'a', 1]);
+        push_escpos_qr(&mut out, qr);
+        out.extend_from_slice(&[ESC, b'a', 0]);
+        // Feed past the cutter, then cut.
+        out.extend_from_slice(&[ESC, b'd', 4, GS, b'V', 66, 0]);
+        out
+    }
+
+    fn render_text(&self, qr: &QrCode, tz: FixedOffset) -> String {
+        let rule = "-".repeat(TICKET_COLUMNS);
+        let mut text = format!(
+            "ORDER #{}\n{}\n{}\nSlot: {}\nFor: {}\nOrdered: {}\n{}\n",
+            self.order_id,
+            self.canteen_name,
+            rule,
+            self.slot,
+            self.customer_name,
+            self.ordered_at(tz),
+            rule
+        );
+        for line in &self.lines {
+            text.push_str(&format!("{:>2} x {}\n", line.quantity, line.name));
+        }
+        if let Some(note) = &self.note {
+            text.push_str(&format!("{rule}\nNOTE: {note}\n"));
+        }
+        text.push_str(&rule);
+        text.push('\n');
+        text.push_str(
+            &qr.render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Dark)
+                .light_color(unicode::Dense1x2::Light)
+                .quiet_zone(true)
+                .build(),
+        );
+        text.push('\n');
+        text
+    }
+
+    fn render_html(&self, qr: &QrCode, tz: FixedOffset) -> String {
+        let rows = self
+            .lines
+            .iter()
+            .map(|line| {
+                format!(
+                    "<tr><td class=\"qty\">{}</td><td>{}</td></tr>",
+                    line.quantity,
+                    escape_html(&line.name)
+                )
+            })
+            .collect::<String>();
+        let note = self
+            .note
+            .as_deref()
+            .map(|note| format!("<p class=\"note\"><b>Note:</b> {}</p>", escape_html(note)))
+            .unwrap_or_default();
+        let qr_svg = qr
+            .render::<svg::Color>()
+            .min_dimensions(160, 160)
+            .quiet_zone(true)
+            .build();
+        // The SVG is inlined, so its XML declaration is left out.
+        let qr_svg = qr_svg
+            .find("<svg")
+            .map_or(qr_svg.as_str(), |start| &qr_svg[start..]);
+        format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Order #{order_id}</title>\
+             <style>body{{font-family:monospace;width:72mm;margin:0 auto}}\
+             h1,.canteen,.qr{{text-align:center}}.qty{{text-align:right;padding-right:1em}}\
+             table{{font-size:1.2em}}.note{{border-top:1px dashed #000;padding-top:.5em}}</style>\
+             </head><body><h1>Order #{order_id}</h1><p class=\"canteen\">{canteen}</p>\
+             <p><b>Slot: {slot}</b><br>For: {customer}<br>Ordered: {ordered_at}</p>\
+             <table><tbody>{rows}</tbody></table>{note}<div class=\"qr\">{qr_svg}</div>\
+             </body></html>",
+            order_id = self.order_id,
+            canteen = escape_html(&self.canteen_name),
+            slot = escape_html(&self.slot),
+            customer = escape_html(&self.customer_name),
+            ordered_at = self.ordered_at(tz),
+        )
+    }
+}
+
+/// Thermal printers default to a single-byte code page, so anything outside ASCII is replaced.
+fn push_escpos_line(out: &mut Vec<u8>, line: &str) {
+    out.extend(line.chars().map(|c| {
+        if c.is_ascii() && !c.is_ascii_control() {
+            c as u8
+        } else {
+            b'?'
+        }
+    }));
+    out.push(b'\n');
+}
+
+/// The QR code as a raster bit image (`GS v 0`), one bit per dot, dark dots set.
+fn push_escpos_qr(out: &mut Vec<u8>, qr: &QrCode) {
+    let modules = qr.width();
+    let colors = qr.to_colors();
+    let dots = (modules + 2 * QR_QUIET_MODULES) * QR_MODULE_DOTS;
+    let row_bytes = dots.div_ceil(8);
+    let is_dark = |x: usize, y: usize| {
+        let (mx, my) = (x / QR_MODULE_DOTS, y / QR_MODULE_DOTS);
+        if mx < QR_QUIET_MODULES || my < QR_QUIET_MODULES {
+            return false;
+        }
+        let (mx, my) = (mx - QR_QUIET_MODULES, my - QR_QUIET_MODULES);
+        mx < modules && my < modules && colors[my * modules + mx] == Color::Dark
+    };
+
+    out.extend_from_slice(&[
+        GS,
+        b'v',
+        b'0',
+        0,
+        (row_bytes % 256) as u8,
+        (row_bytes / 256) as u8,
+        (dots % 256) as u8,
+        (dots / 256) as u8,
+    ]);
+    for y in 0..dots {
+        for byte in 0..row_bytes {
+            let mut bits = 0u8;
+            for bit in 0..8 {
+                let x = byte * 8 + bit;
+                if x < dots && is_dark(x, y) {
+                    bits |= 0x80 >> bit;
+                }
+            }
+            out.push(bits);
+        }
+    }
+    out.push(b'\n');
+}
+
+/// Word-wrap `text` after `prefix` to the ticket width, indenting continuation lines.
+fn wrap(prefix: &str, text: &str, indent: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = prefix.to_string();
+    let mut line_has_words = false;
+    for word in text.split_whitespace() {
+        if line_has_words && current.chars().count() + 1 + word.chars().count() > TICKET_COLUMNS {
+            lines.push(std::mem::replace(&mut current, indent.to_string()));
+            line_has_words = false;
+        }
+        if line_has_words {
+            current.push(' ');
+        }
+        current.push_str(word);
+        line_has_words = true;
+    }
+    lines.push(current);
+    lines
+}
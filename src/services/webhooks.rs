@@ -0,0 +1,358 @@
+use crate::auth::qr_token::sign_payload;
+use crate::db::{DueWebhookDelivery, RepositoryError, WebhookOperations};
+use crate::models::admin::{is_public_host, is_public_ip};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::{interval, Duration};
+
+/// Claimed deliveries are left alone by other dispatchers for this long; it has to outlast the
+/// request timeout.
+const CLAIM_LEASE_SECS: i64 = 120;
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 60 * 60;
+
+pub const WEBHOOK_ID_HEADER: &str = "X-Webhook-Id";
+pub const WEBHOOK_EVENT_HEADER: &str = "X-Webhook-Event";
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// `sha256=<hex HMAC-SHA256 of "{timestamp}.{body}" keyed with the webhook secret>`.
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebhookOrderItem {
+    pub item_id: i32,
+    pub name: String,
+    pub quantity: i32,
+    pub price: i32,
+}
+
+/// An event a canteen can subscribe its webhooks to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum WebhookEvent {
+    #[serde(rename = "order.confirmed")]
+    OrderConfirmed {
+        order_id: i32,
+        user_id: i32,
+        total_price: i32,
+        /// The time band the order is for, or "Instant".
+        deliver_at: String,
+        items: Vec<WebhookOrderItem>,
+    },
+    #[serde(rename = "order.cancelled")]
+    OrderCancelled { order_id: i32, user_id: i32 },
+    #[serde(rename = "order.delivered")]
+    OrderDelivered { order_id: i32, user_id: i32 },
+    /// An item ran out of stock, through an order or a change to the menu.
+    #[serde(rename = "item.stock_out")]
+    StockOut { item_id: i32, name: String },
+}
+
+impl WebhookEvent {
+    /// One of [`WEBHOOK_EVENT_TYPES`](crate::models::admin::WEBHOOK_EVENT_TYPES).
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            WebhookEvent::OrderConfirmed { .. } => "order.confirmed",
+            WebhookEvent::OrderCancelled { .. } => "order.cancelled",
+            WebhookEvent::OrderDelivered { .. } => "order.delivered",
+            WebhookEvent::StockOut { .. } => "item.stock_out",
+        }
+    }
+
+    /// The body posted to endpoints: `{id, type, canteen_id, created_at, data}`.
+    pub fn payload(&self, event_id: &str, canteen_id: i32, created_at: DateTime<Utc>) -> String {
+        let mut body = serde_json::json!({
+            "id": event_id,
+            "canteen_id": canteen_id,
+            "created_at": created_at.to_rfc3339(),
+        });
+        if let (serde_json::Value::Object(body), Ok(serde_json::Value::Object(event))) =
+            (&mut body, serde_json::to_value(self))
+        {
+            body.extend(event);
+        }
+        body.to_string()
+    }
+}
+
+/// The `X-Webhook-Signature` value for a body sent at `timestamp`.
+pub fn webhook_signature(secret: &str, timestamp: i64, body: &str) -> String {
+    format!(
+        "sha256={}",
+        sign_payload(&format!("{timestamp}.{body}"), secret)
+    )
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WebhookConfig {
+    pub poll_secs: u64,
+    pub batch_size: i64,
+    pub max_attempts: i32,
+    pub retry_base_secs: i64,
+    pub timeout_secs: u64,
+    /// Let webhooks call loopback, private and link-local addresses; for local development
+    /// and tests only.
+    pub allow_private_targets: bool,
+}
+
+impl WebhookConfig {
+    /// Reads `WEBHOOK_POLL_SECS` (default 5), `WEBHOOK_BATCH_SIZE` (default 20),
+    /// `WEBHOOK_MAX_ATTEMPTS` (default 8), `WEBHOOK_RETRY_BASE_SECS` (default 30),
+    /// `WEBHOOK_TIMEOUT_SECS` (default 10) and `WEBHOOK_ALLOW_PRIVATE_TARGETS` (default false).
+    pub fn from_env() -> Self {
+        fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<T>().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            poll_secs: env_or("WEBHOOK_POLL_SECS", 5u64).max(1),
+            batch_size: env_or("WEBHOOK_BATCH_SIZE", 20i64).max(1),
+            max_attempts: env_or("WEBHOOK_MAX_ATTEMPTS", 8i32).max(1),
+            retry_base_secs: env_or("WEBHOOK_RETRY_BASE_SECS", 30i64).max(1),
+            timeout_secs: env_or("WEBHOOK_TIMEOUT_SECS", 10u64).max(1),
+            allow_private_targets: env_or("WEBHOOK_ALLOW_PRIVATE_TARGETS", false),
+        }
+    }
+
+    /// Exponential backoff after the given number of failed attempts.
+    fn retry_delay_secs(&self, failed_attempts: i32) -> i64 {
+        self.retry_base_secs
+            .saturating_mul(1i64 << failed_attempts.clamp(0, 20))
+            .min(MAX_RETRY_DELAY_SECS)
+    }
+}
+
+/// Resolves webhook hosts to their public addresses only, so a name cannot be pointed into
+/// the server's own network after the webhook was saved. Connections go to the addresses
+/// checked here.
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let lookup_host = host.clone();
+            let addrs =
+                tokio::task::spawn_blocking(move || (lookup_host.as_str(), 0).to_socket_addrs())
+                    .await??
+                    .filter(|addr| is_public_ip(addr.ip()))
+                    .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// How one attempt went: the endpoint's status code, if it replied, and the failure, if any.
+struct AttemptOutcome {
+    delivery_id: i64,
+    attempts: i32,
+    status_code: Option<i32>,
+    failure: Option<String>,
+}
+
+/// Posts queued deliveries to canteens' webhooks in the background, retrying failures with
+/// backoff. [`wake`](Self::wake) it after queueing deliveries; it also polls.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    webhook_ops: WebhookOperations,
+    wake: Arc<Notify>,
+}
+
+impl WebhookDispatcher {
+    pub fn start(webhook_ops: WebhookOperations, cfg: WebhookConfig) -> Self {
+        let wake = Arc::new(Notify::new());
+        let mut http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(cfg.timeout_secs))
+            .redirect(reqwest::redirect::Policy::none());
+        if !cfg.allow_private_targets {
+            // A proxy would resolve the host itself, out of reach of the resolver.
+            http = http.no_proxy().dns_resolver(PublicAddressResolver);
+        }
+        let http = http.build().expect("Unable to create webhook HTTP client");
+        tokio::spawn(Self::run(webhook_ops.clone(), http, cfg, wake.clone()));
+        Self { webhook_ops, wake }
+    }
+
+    /// Send what is due now rather than at the next poll.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Queue an event for the canteen's subscribed webhooks. Blocking.
+    pub fn record_event(
+        &self,
+        canteen_id: i32,
+        event_id: &str,
+        created_at: DateTime<Utc>,
+        event: &WebhookEvent,
+    ) -> Result<usize, RepositoryError> {
+        self.webhook_ops.record_event(
+            canteen_id,
+            event_id,
+            event.event_type(),
+            &event.payload(event_id, canteen_id, created_at),
+        )
+    }
+
+    async fn run(
+        webhook_ops: WebhookOperations,
+        http: reqwest::Client,
+        cfg: WebhookConfig,
+        wake: Arc<Notify>,
+    ) {
+        let mut tick = interval(Duration::from_secs(cfg.poll_secs));
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {},
+                _ = wake.notified() => {},
+            }
+            loop {
+                match dispatch_due_webhooks(&webhook_ops, &http, &cfg).await {
+                    Ok(claimed) if claimed as i64 == cfg.batch_size => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!("Webhook dispatcher error: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Send one batch of due deliveries concurrently. Returns how many were claimed.
+async fn dispatch_due_webhooks(
+    webhook_ops: &WebhookOperations,
+    http: &reqwest::Client,
+    cfg: &WebhookConfig,
+) -> Result<usize, RepositoryError> {
+    let due = web::block({
+        let webhook_ops = webhook_ops.clone();
+        let limit = cfg.batch_size;
+        move || webhook_ops.claim_due_deliveries(limit, CLAIM_LEASE_SECS)
+    })
+    .await
+    .map_err(|e| RepositoryError::InternalError(format!("blocking error: {}", e)))??;
+    let claimed = due.len();
+    if claimed == 0 {
+        return Ok(0);
+    }
+
+    let outcomes = join_all(
+        due.iter()
+            .map(|due| send_delivery(http, due, cfg.allow_private_targets)),
+    )
+    .await;
+
+    let cfg = *cfg;
+    web::block({
+        let webhook_ops = webhook_ops.clone();
+        move || {
+            for outcome in outcomes {
+                let Some(failure) = outcome.failure else {
+                    webhook_ops.mark_delivery_delivered(
+                        outcome.delivery_id,
+                        outcome.status_code.unwrap_or_default(),
+                    )?;
+                    continue;
+                };
+                let failed_attempts = outcome.attempts + 1;
+                let retry_at = (failed_attempts < cfg.max_attempts).then(|| {
+                    Utc::now() + chrono::Duration::seconds(cfg.retry_delay_secs(outcome.attempts))
+                });
+                if retry_at.is_some() {
+                    warn!(
+                        "dispatch_due_webhooks: delivery {} failed (attempt {}), will retry: {}",
+                        outcome.delivery_id, failed_attempts, failure
+                    );
+                } else {
+                    error!(
+                        "dispatch_due_webhooks: giving up on delivery {} after {} attempts: {}",
+                        outcome.delivery_id, failed_attempts, failure
+                    );
+                }
+                webhook_ops.record_delivery_failure(
+                    outcome.delivery_id,
+                    outcome.status_code,
+                    &failure,
+                    retry_at,
+                )?;
+            }
+            Ok::<(), RepositoryError>(())
+        }
+    })
+    .await
+    .map_err(|e| RepositoryError::InternalError(format!("blocking error: {}", e)))??;
+    Ok(claimed)
+}
+
+async fn send_delivery(
+    http: &reqwest::Client,
+    (delivery, url, secret): &DueWebhookDelivery,
+    allow_private_targets: bool,
+) -> AttemptOutcome {
+    // Addresses in the URL itself are not resolved, so they are checked here.
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string));
+    if !allow_private_targets && !host.as_deref().is_some_and(is_public_host) {
+        return AttemptOutcome {
+            delivery_id: delivery.delivery_id,
+            attempts: delivery.attempts,
+            status_code: None,
+            failure: Some("url does not point at a public address".to_string()),
+        };
+    }
+    let timestamp = Utc::now().timestamp();
+    let result = http
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(WEBHOOK_ID_HEADER, &delivery.event_id)
+        .header(WEBHOOK_EVENT_HEADER, &delivery.event_type)
+        .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+        .header(
+            WEBHOOK_SIGNATURE_HEADER,
+            webhook_signature(secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    let (status_code, failure) = match result {
+        Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i32), None),
+        // Only the status is kept, so the delivery log cannot be used to read what a URL returns.
+        Ok(resp) => (
+            Some(resp.status().as_u16() as i32),
+            Some(format!("endpoint returned {}", resp.status())),
+        ),
+        Err(e) => (None, Some(format!("request failed: {}", e))),
+    };
+    AttemptOutcome {
+        delivery_id: delivery.delivery_id,
+        attempts: delivery.attempts,
+        status_code,
+        failure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PublicAddressResolver;
+    use reqwest::dns::Resolve;
+
+    #[tokio::test]
+    async fn resolver_leaves_out_local_addresses() {
+        let name = "localhost".parse().expect("valid name");
+        assert!(PublicAddressResolver.resolve(name).await.is_err());
+    }
+}
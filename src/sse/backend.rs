@@ -0,0 +1,171 @@
+use crate::db::SseNotifyOperations;
+use crate::models::common::StoredSseEvent;
+use crate::sse::broker::LocalStreams;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Postgres channel every instance listens on.
+const NOTIFY_CHANNEL: &str = "sse_events";
+/// Postgres rejects `NOTIFY` payloads of 8000 bytes or more.
+const NOTIFY_PAYLOAD_LIMIT: usize = 7999;
+/// Room for the `"seq"` field the notifier adds to a payload.
+const SEQ_FIELD_LEN: usize = r#","seq":18446744073709551615"#.len();
+/// How often the listener checks its connection for notifications, backing off from the
+/// shortest to the longest interval while the channel is quiet.
+const LISTEN_POLL_MIN: Duration = Duration::from_millis(25);
+const LISTEN_POLL_MAX: Duration = Duration::from_millis(500);
+const LISTEN_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// An event on its way to the subscribers of one stream.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct BrokerMessage {
+    pub stream: String,
+    pub stream_id: i32,
+    pub event: String,
+    pub data: String,
+    /// The stream sequence number, when the backend numbers events rather than each instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl BrokerMessage {
+    pub fn encoded_len(&self) -> usize {
+        serde_json::to_string(self).map_or(usize::MAX, |json| json.len())
+    }
+}
+
+/// Carries published events to the local subscribers of every instance.
+pub(crate) trait BrokerBackend: Send + Sync {
+    /// Hand the message to every instance, this one included. Must not block.
+    fn publish(&self, message: BrokerMessage);
+
+    /// Largest message the backend can carry, as encoded by [`BrokerMessage::encoded_len`].
+    fn max_message_size(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Single-node deployments: deliver straight to this instance's subscribers.
+pub(crate) struct InMemoryBackend {
+    local: LocalStreams,
+}
+
+impl InMemoryBackend {
+    pub fn new(local: LocalStreams) -> Self {
+        Self { local }
+    }
+}
+
+impl BrokerBackend for InMemoryBackend {
+    fn publish(&self, message: BrokerMessage) {
+        self.local.deliver(message);
+    }
+}
+
+/// Multi-node deployments: every event goes out through Postgres `NOTIFY` and every
+/// instance, the publishing one included, delivers what it hears on `LISTEN`. The database
+/// numbers each event before notifying it, so every instance sends the same ids, and
+/// instances that missed events see the gap. With `event_log_keep` set, the event is also
+/// written to the event log in that transaction.
+pub(crate) struct PostgresBackend {
+    tx: mpsc::Sender<BrokerMessage>,
+}
+
+impl PostgresBackend {
+    pub fn spawn(
+        ops: SseNotifyOperations,
+        local: LocalStreams,
+        event_log_keep: Option<usize>,
+    ) -> Self {
+        let listen_ops = ops.clone();
+        std::thread::Builder::new()
+            .name("sse-listen".to_string())
+            .spawn(move || Self::listen(listen_ops, local))
+            .expect("Unable to spawn SSE listener");
+
+        // NOTIFY is a database round trip; publishers hand it off instead of waiting on it.
+        let (tx, rx) = mpsc::channel::<BrokerMessage>();
+        std::thread::Builder::new()
+            .name("sse-notify".to_string())
+            .spawn(move || {
+                for message in rx {
+                    let event = StoredSseEvent {
+                        stream_kind: message.stream.clone(),
+                        stream_id: message.stream_id,
+                        seq: 0,
+                        event_name: message.event.clone(),
+                        data: message.data.clone(),
+                    };
+                    let numbered = |seq: i64| {
+                        let numbered = BrokerMessage {
+                            seq: Some(seq as u64),
+                            ..message.clone()
+                        };
+                        serde_json::to_string(&numbered).expect("broker messages serialize")
+                    };
+                    if let Err(e) =
+                        ops.notify_event(NOTIFY_CHANNEL, event, event_log_keep, numbered)
+                    {
+                        warn!(
+                            "sse_notify: dropped {} event for {} {}: {}",
+                            message.event, message.stream, message.stream_id, e
+                        );
+                    }
+                }
+            })
+            .expect("Unable to spawn SSE notifier");
+        Self { tx }
+    }
+
+    fn listen(ops: SseNotifyOperations, local: LocalStreams) {
+        loop {
+            let mut conn = match ops.listen(NOTIFY_CHANNEL) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("sse_listen: could not listen for SSE events: {}", e);
+                    std::thread::sleep(LISTEN_RETRY_INTERVAL);
+                    continue;
+                }
+            };
+            info!("sse_listen: listening on channel {}", NOTIFY_CHANNEL);
+            // Events notified while reconnecting are missed here; clients that reconnect with
+            // a Last-Event-ID before the gap are told to resync.
+            let mut poll_interval = LISTEN_POLL_MIN;
+            while let Ok(payloads) = SseNotifyOperations::received(&mut conn) {
+                if payloads.is_empty() {
+                    // Diesel cannot wait on the connection's socket, so poll it instead.
+                    std::thread::sleep(poll_interval);
+                    poll_interval = (poll_interval * 2).min(LISTEN_POLL_MAX);
+                    continue;
+                }
+                poll_interval = LISTEN_POLL_MIN;
+                for payload in payloads {
+                    match serde_json::from_str::<BrokerMessage>(&payload) {
+                        Ok(message) => local.deliver(message),
+                        Err(e) => warn!("sse_listen: skipping malformed notification: {}", e),
+                    }
+                }
+            }
+            warn!("sse_listen: lost the listening connection, reconnecting");
+            if let Err(e) = SseNotifyOperations::unlisten(&mut conn) {
+                debug!(
+                    "sse_listen: could not unlisten on the lost connection: {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl BrokerBackend for PostgresBackend {
+    fn publish(&self, message: BrokerMessage) {
+        if self.tx.send(message).is_err() {
+            warn!("sse_notify: notifier has stopped, event not published");
+        }
+    }
+
+    fn max_message_size(&self) -> Option<usize> {
+        Some(NOTIFY_PAYLOAD_LIMIT - SEQ_FIELD_LEN)
+    }
+}
@@ -1,197 +1,463 @@
+use crate::db::{SseEventLogOperations, SseNotifyOperations};
+use crate::sse::backend::{BrokerBackend, BrokerMessage, InMemoryBackend, PostgresBackend};
+use crate::sse::replay::{DurableEventLog, EventLog, LoggedEvent};
 use crate::sse::SseEvent;
 use actix_web_lab::sse;
 use dashmap::DashMap;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::PgConnection;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+/// Live events a connection may have queued before it is considered dead.
+const LIVE_EVENT_BACKLOG: usize = 64;
+const DEFAULT_REPLAY_BUFFER_SIZE: usize = 100;
+
+type Connections = HashMap<Uuid, Subscriber>;
+
+/// An event for a multiplexed subscriber, tagged with the stream it belongs to.
+#[derive(Clone, Debug)]
+pub struct ChannelEvent {
+    /// `user_orders`, `canteen_orders` or `canteen_inventory`.
+    pub channel: &'static str,
+    pub stream_id: i32,
+    /// The stream sequence number; `None` for `status` events.
+    pub seq: Option<u64>,
+    pub event: String,
+    pub data: String,
+}
+
+/// Where the broker sends a connection's events: an SSE response of its own, or a channel
+/// shared by every subscription of one WebSocket.
 #[derive(Clone)]
-pub struct SseBroker {
-    user_conns: Arc<DashMap<i32, HashMap<Uuid, mpsc::Sender<sse::Event>>>>, // send user order updates
-    canteen_conns: Arc<DashMap<i32, HashMap<Uuid, mpsc::Sender<sse::Event>>>>, // sends canteen aggregated order updates
-    canteen_subs: Arc<DashMap<i32, HashMap<Uuid, mpsc::Sender<sse::Event>>>>, // canteen id -> tx - sends both inventory updates
+pub enum Subscriber {
+    Sse(mpsc::Sender<sse::Event>),
+    Channel(mpsc::Sender<ChannelEvent>),
 }
-impl SseBroker {
-    pub fn new() -> Self {
-        Self {
-            user_conns: Arc::new(DashMap::new()),
-            canteen_conns: Arc::new(DashMap::new()),
-            canteen_subs: Arc::new(DashMap::new()),
-        }
+
+impl From<mpsc::Sender<sse::Event>> for Subscriber {
+    fn from(tx: mpsc::Sender<sse::Event>) -> Self {
+        Subscriber::Sse(tx)
     }
+}
 
-    pub fn register_user_connection(
-        &self,
-        user_id: i32,
-        conn_id: Uuid,
-        tx: mpsc::Sender<sse::Event>,
-    ) {
-        self.user_conns
-            .entry(user_id)
-            .or_default()
-            .insert(conn_id, tx);
-        debug!(
-            "user_order_events: user {} connected with id {}",
-            user_id, conn_id
-        );
+impl From<mpsc::Sender<ChannelEvent>> for Subscriber {
+    fn from(tx: mpsc::Sender<ChannelEvent>) -> Self {
+        Subscriber::Channel(tx)
     }
+}
 
-    pub fn register_canteen_connection(
-        &self,
-        canteen_id: i32,
-        conn_id: Uuid,
-        tx: mpsc::Sender<sse::Event>,
-    ) {
-        self.canteen_conns
-            .entry(canteen_id)
-            .or_default()
-            .insert(conn_id, tx);
-        debug!(
-            "canteen_order_events: canteen {} connected with id {}",
-            canteen_id, conn_id
-        );
+impl Subscriber {
+    /// `false` once the subscriber is gone or too far behind.
+    fn send(&self, channel: &'static str, stream_id: i32, event: &LoggedEvent) -> bool {
+        match self {
+            Subscriber::Sse(tx) => tx.try_send(event.to_sse_event()).is_ok(),
+            Subscriber::Channel(tx) => tx
+                .try_send(ChannelEvent {
+                    channel,
+                    stream_id,
+                    seq: Some(event.seq),
+                    event: event.event_name.clone(),
+                    data: event.data.clone(),
+                })
+                .is_ok(),
+        }
     }
 
-    pub fn register_canteen_subscription(
-        &self,
-        canteen_id: i32,
-        conn_id: Uuid,
-        tx: mpsc::Sender<sse::Event>,
-    ) {
-        self.canteen_subs
-            .entry(canteen_id)
-            .or_default()
-            .insert(conn_id, tx);
-        debug!(
-            "canteen_subscription_events: canteen {} connected with id {}",
-            canteen_id, conn_id
-        );
+    fn send_status(&self, channel: &'static str, stream_id: i32, status: &str) -> bool {
+        match self {
+            Subscriber::Sse(tx) => tx
+                .try_send(sse::Data::new(status.to_string()).event("status").into())
+                .is_ok(),
+            Subscriber::Channel(tx) => tx
+                .try_send(ChannelEvent {
+                    channel,
+                    stream_id,
+                    seq: None,
+                    event: "status".to_string(),
+                    data: status.to_string(),
+                })
+                .is_ok(),
+        }
     }
+}
 
-    pub fn unregister_user_connection(&self, user_id: i32, conn_id: Uuid) {
-        if let Some(mut conn_map) = self.user_conns.get_mut(&user_id) {
-            conn_map.remove(&conn_id);
-            if conn_map.is_empty() {
-                // drop whole hashmap if user has completely disconnected
-                drop(conn_map);
-                self.user_conns.remove(&user_id);
-            }
+/// One kind of stream (a user's orders, a canteen's orders, a canteen's inventory), keyed by
+/// user or canteen id. Every stream numbers its events from 1 and keeps the most recent
+/// ones for clients reconnecting with `Last-Event-ID`.
+#[derive(Clone)]
+struct EventStreams {
+    kind: &'static str,
+    label: &'static str,
+    conns: Arc<DashMap<i32, Connections>>,
+    logs: Arc<DashMap<i32, EventLog>>,
+    replay_capacity: usize,
+    durable: Option<DurableEventLog>,
+}
+
+impl EventStreams {
+    fn new(
+        kind: &'static str,
+        label: &'static str,
+        replay_capacity: usize,
+        durable: Option<DurableEventLog>,
+    ) -> Self {
+        Self {
+            kind,
+            label,
+            conns: Arc::new(DashMap::new()),
+            logs: Arc::new(DashMap::new()),
+            replay_capacity,
+            durable,
         }
-        debug!(
-            "user_order_events: user {} disconnected with id {}",
-            user_id, conn_id
-        );
     }
 
-    pub fn unregister_canteen_connection(&self, canteen_id: i32, conn_id: Uuid) {
-        if let Some(mut conn_map) = self.canteen_conns.get_mut(&canteen_id) {
-            conn_map.remove(&conn_id);
-            if conn_map.is_empty() {
-                drop(conn_map);
-                self.canteen_conns.remove(&canteen_id);
+    /// Replay what the client missed, then start delivering live events. The stream's log
+    /// stays locked throughout so no event is lost or sent twice in between.
+    fn register(&self, stream_id: i32, conn_id: Uuid, tx: Subscriber, last_event_id: Option<u64>) {
+        let log = self
+            .logs
+            .entry(stream_id)
+            .or_insert_with(|| EventLog::new(self.replay_capacity));
+        if let Some(last_event_id) = last_event_id {
+            let replay = log.since(last_event_id);
+            if replay.incomplete {
+                tx.send_status(self.kind, stream_id, "resync");
+            }
+            debug!(
+                "{}: replaying {} events to {} {} after id {} (incomplete: {})",
+                self.label,
+                replay.events.len(),
+                stream_id,
+                conn_id,
+                last_event_id,
+                replay.incomplete
+            );
+            for event in &replay.events {
+                tx.send(self.kind, stream_id, event);
             }
         }
+        self.conns.entry(stream_id).or_default().insert(conn_id, tx);
+        drop(log);
         debug!(
-            "canteen_order_events: canteen {} disconnected with id {}",
-            canteen_id, conn_id
+            "{}: {} connected with id {}",
+            self.label, stream_id, conn_id
         );
     }
 
-    pub fn unregister_canteen_subscription(&self, canteen_id: i32, conn_id: Uuid) {
-        if let Some(mut conn_map) = self.canteen_subs.get_mut(&canteen_id) {
+    fn unregister(&self, stream_id: i32, conn_id: Uuid) {
+        if let Some(mut conn_map) = self.conns.get_mut(&stream_id) {
             conn_map.remove(&conn_id);
             if conn_map.is_empty() {
+                // drop whole hashmap once every device of the stream has disconnected
                 drop(conn_map);
-                self.canteen_subs.remove(&canteen_id);
+                self.conns.remove(&stream_id);
             }
         }
         debug!(
-            "canteen_subscription_events: canteen {} disconnected with id {}",
-            canteen_id, conn_id
+            "{}: {} disconnected with id {}",
+            self.label, stream_id, conn_id
         );
     }
 
-    pub fn publish_user_event(&self, user_id: i32, event: &SseEvent) {
-        debug!("user_order_events: publishing event to user {}", user_id);
-        let sse_event = event.to_sse_event();
+    /// Numbers the event on this instance unless the backend already did.
+    fn publish(&self, stream_id: i32, event_name: &str, data: String, seq: Option<u64>) {
+        debug!("{}: publishing event to {}", self.label, stream_id);
+        let mut log = self
+            .logs
+            .entry(stream_id)
+            .or_insert_with(|| EventLog::new(self.replay_capacity));
+        let logged = match seq {
+            Some(seq) => log.record(LoggedEvent {
+                seq,
+                event_name: event_name.to_string(),
+                data,
+            }),
+            None => log.append(event_name, data),
+        };
+        if let Some(durable) = &self.durable {
+            durable.write(self.kind, stream_id, &logged);
+        }
+
         let mut dead_devices: Vec<Uuid> = Vec::new();
         let mut successful_count: i32 = 0;
-        if let Some(conn_map) = self.user_conns.get(&user_id) {
+        if let Some(conn_map) = self.conns.get(&stream_id) {
             for (conn_id, tx) in conn_map.iter() {
-                if tx.try_send(sse_event.clone()).is_err() {
+                if !tx.send(self.kind, stream_id, &logged) {
                     dead_devices.push(*conn_id);
                 } else {
                     successful_count += 1;
                 }
             }
         }
+        drop(log);
         for dead_device in dead_devices {
-            self.unregister_user_connection(user_id, dead_device);
+            self.unregister(stream_id, dead_device);
         }
         debug!(
-            "user_order_events: finished publishing event to user {}: {} devices",
-            user_id, successful_count
+            "{}: finished publishing event {} to {}: {} devices",
+            self.label, logged.seq, stream_id, successful_count
         );
     }
 
-    pub fn publish_canteen_event(&self, canteen_id: i32, event: &SseEvent) {
-        debug!(
-            "canteen_order_events: publishing event to canteen {}",
-            canteen_id
-        );
-        let sse_event = event.to_sse_event();
-        let mut dead_devices: Vec<Uuid> = Vec::new();
-        let mut successful_count: i32 = 0;
-        if let Some(conn_map) = self.canteen_conns.get(&canteen_id) {
-            debug!(
-                "canteen_order_events: initial: found {} devices",
-                conn_map.len()
+    fn restore(&self, stream_id: i32, event: LoggedEvent) {
+        self.logs
+            .entry(stream_id)
+            .or_insert_with(|| EventLog::new(self.replay_capacity))
+            .record(event);
+    }
+}
+
+/// This instance's subscribers, for every kind of stream.
+#[derive(Clone)]
+pub(crate) struct LocalStreams {
+    user_streams: EventStreams,    // send user order updates
+    canteen_streams: EventStreams, // sends canteen aggregated order updates
+    canteen_subs: EventStreams,    // canteen id -> tx - sends inventory updates
+}
+
+impl LocalStreams {
+    fn new(replay_capacity: usize, durable: Option<DurableEventLog>) -> Self {
+        Self {
+            user_streams: EventStreams::new(
+                "user_orders",
+                "user_order_events",
+                replay_capacity,
+                durable.clone(),
+            ),
+            canteen_streams: EventStreams::new(
+                "canteen_orders",
+                "canteen_order_events",
+                replay_capacity,
+                durable.clone(),
+            ),
+            canteen_subs: EventStreams::new(
+                "canteen_inventory",
+                "canteen_subscription_events",
+                replay_capacity,
+                durable,
+            ),
+        }
+    }
+
+    /// Reload the replay buffers from the event log in Postgres, so clients can resume
+    /// across restarts.
+    fn restore_from(&self, ops: &SseEventLogOperations, replay_capacity: usize) {
+        let stored = ops.load_recent(replay_capacity).unwrap_or_else(|e| {
+            warn!("SseBroker: could not load the SSE event log: {}", e);
+            Vec::new()
+        });
+        let restored = stored.len();
+        for event in stored {
+            let Some(streams) = self.streams(&event.stream_kind) else {
+                warn!(
+                    "SseBroker: skipping stored event of unknown stream '{}'",
+                    event.stream_kind
+                );
+                continue;
+            };
+            streams.restore(
+                event.stream_id,
+                LoggedEvent {
+                    seq: event.seq as u64,
+                    event_name: event.event_name,
+                    data: event.data,
+                },
             );
-            for (conn_id, tx) in conn_map.iter() {
-                if tx.try_send(sse_event.clone()).is_err() {
-                    dead_devices.push(*conn_id);
-                } else {
-                    successful_count += 1;
+        }
+        info!(
+            "SseBroker: restored {} events from the SSE event log",
+            restored
+        );
+    }
+
+    fn streams(&self, kind: &str) -> Option<&EventStreams> {
+        match kind {
+            "user_orders" => Some(&self.user_streams),
+            "canteen_orders" => Some(&self.canteen_streams),
+            "canteen_inventory" => Some(&self.canteen_subs),
+            _ => None,
+        }
+    }
+
+    /// Number the event on its stream, unless the backend did, and send it to this
+    /// instance's subscribers.
+    pub(crate) fn deliver(&self, message: BrokerMessage) {
+        match self.streams(&message.stream) {
+            Some(streams) => {
+                streams.publish(message.stream_id, &message.event, message.data, message.seq)
+            }
+            None => warn!(
+                "SseBroker: dropping event for unknown stream '{}'",
+                message.stream
+            ),
+        }
+    }
+}
+
+/// Fans events out to SSE subscribers. Subscribers are always held by the instance they
+/// connected to; the backend decides how a published event reaches every instance.
+#[derive(Clone)]
+pub struct SseBroker {
+    local: LocalStreams,
+    backend: Arc<dyn BrokerBackend>,
+    replay_capacity: usize,
+}
+
+impl SseBroker {
+    /// Single-node broker with an in-memory replay buffer of `replay_capacity` events per
+    /// stream.
+    pub fn new(replay_capacity: usize) -> Self {
+        Self::in_memory(LocalStreams::new(replay_capacity, None), replay_capacity)
+    }
+
+    /// Single-node broker whose replay buffer is also kept in Postgres.
+    pub fn with_event_log(replay_capacity: usize, ops: SseEventLogOperations) -> Self {
+        let local = LocalStreams::new(
+            replay_capacity,
+            Some(DurableEventLog::spawn(ops.clone(), replay_capacity)),
+        );
+        local.restore_from(&ops, replay_capacity);
+        Self::in_memory(local, replay_capacity)
+    }
+
+    /// Reads `SSE_REPLAY_BUFFER_SIZE`, `SSE_EVENT_LOG_ENABLED` and `SSE_BROKER_BACKEND`
+    /// (`memory`, the default, or `postgres` to fan events out to every instance).
+    pub fn from_env(pool: Pool<ConnectionManager<PgConnection>>, database_url: &str) -> Self {
+        let replay_capacity = std::env::var("SSE_REPLAY_BUFFER_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_REPLAY_BUFFER_SIZE);
+        let durable = std::env::var("SSE_EVENT_LOG_ENABLED")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let event_log = durable.then(|| SseEventLogOperations::new(pool.clone()));
+
+        let backend = std::env::var("SSE_BROKER_BACKEND").unwrap_or_default();
+        match backend.trim().to_ascii_lowercase().as_str() {
+            "" | "memory" => match event_log {
+                Some(ops) => Self::with_event_log(replay_capacity, ops),
+                None => Self::new(replay_capacity),
+            },
+            "postgres" => {
+                info!("SseBroker: fanning events out through Postgres NOTIFY");
+                // The notifier writes the event log in the transaction that numbers the event.
+                let local = LocalStreams::new(replay_capacity, None);
+                if let Some(ops) = &event_log {
+                    local.restore_from(ops, replay_capacity);
+                }
+                let backend = PostgresBackend::spawn(
+                    SseNotifyOperations::new(pool, database_url),
+                    local.clone(),
+                    event_log.map(|_| replay_capacity),
+                );
+                Self {
+                    local,
+                    backend: Arc::new(backend),
+                    replay_capacity,
                 }
             }
+            other => panic!("Unknown SSE_BROKER_BACKEND '{other}', expected memory or postgres"),
         }
-        for dead_device in dead_devices {
-            self.unregister_canteen_connection(canteen_id, dead_device);
+    }
+
+    fn in_memory(local: LocalStreams, replay_capacity: usize) -> Self {
+        Self {
+            backend: Arc::new(InMemoryBackend::new(local.clone())),
+            local,
+            replay_capacity,
         }
-        debug!(
-            "canteen_order_events: finished publishing event to canteen {}: {} devices",
-            canteen_id, successful_count
-        );
+    }
+
+    /// Channel size for a new connection: room for a full replay plus live events.
+    pub fn channel_capacity(&self) -> usize {
+        // One extra slot for the `resync` status event.
+        LIVE_EVENT_BACKLOG + self.replay_capacity + 1
+    }
+
+    pub fn register_user_connection(
+        &self,
+        user_id: i32,
+        conn_id: Uuid,
+        tx: impl Into<Subscriber>,
+        last_event_id: Option<u64>,
+    ) {
+        self.local
+            .user_streams
+            .register(user_id, conn_id, tx.into(), last_event_id);
+    }
+
+    pub fn register_canteen_connection(
+        &self,
+        canteen_id: i32,
+        conn_id: Uuid,
+        tx: impl Into<Subscriber>,
+        last_event_id: Option<u64>,
+    ) {
+        self.local
+            .canteen_streams
+            .register(canteen_id, conn_id, tx.into(), last_event_id);
+    }
+
+    pub fn register_canteen_subscription(
+        &self,
+        canteen_id: i32,
+        conn_id: Uuid,
+        tx: impl Into<Subscriber>,
+        last_event_id: Option<u64>,
+    ) {
+        self.local
+            .canteen_subs
+            .register(canteen_id, conn_id, tx.into(), last_event_id);
+    }
+
+    pub fn unregister_user_connection(&self, user_id: i32, conn_id: Uuid) {
+        self.local.user_streams.unregister(user_id, conn_id);
+    }
+
+    pub fn unregister_canteen_connection(&self, canteen_id: i32, conn_id: Uuid) {
+        self.local.canteen_streams.unregister(canteen_id, conn_id);
+    }
+
+    pub fn unregister_canteen_subscription(&self, canteen_id: i32, conn_id: Uuid) {
+        self.local.canteen_subs.unregister(canteen_id, conn_id);
+    }
+
+    pub fn publish_user_event(&self, user_id: i32, event: &SseEvent) {
+        self.publish(self.local.user_streams.kind, user_id, event);
+    }
+
+    pub fn publish_canteen_event(&self, canteen_id: i32, event: &SseEvent) {
+        self.publish(self.local.canteen_streams.kind, canteen_id, event);
     }
 
     pub fn publish_canteen_subscription_event(&self, canteen_id: i32, event: &SseEvent) {
-        debug!(
-            "canteen_subscription_events: publishing event for canteen {}",
-            canteen_id
-        );
-        let sse_event = event.to_sse_event();
-        let mut dead_devices: Vec<Uuid> = Vec::new();
-        let mut successful_count: i32 = 0;
-        if let Some(conn_map) = self.canteen_subs.get(&canteen_id) {
-            debug!(
-                "canteen_subscription_events: initial: found {} devices",
-                conn_map.len()
-            );
-            for (conn_id, tx) in conn_map.iter() {
-                if tx.try_send(sse_event.clone()).is_err() {
-                    dead_devices.push(*conn_id);
-                } else {
-                    successful_count += 1;
+        self.publish(self.local.canteen_subs.kind, canteen_id, event);
+    }
+
+    fn publish(&self, stream: &str, stream_id: i32, event: &SseEvent) {
+        let message = BrokerMessage {
+            stream: stream.to_string(),
+            stream_id,
+            event: event.event_name().to_string(),
+            data: serde_json::to_string(event).expect("SSE events serialize to JSON"),
+            seq: None,
+        };
+        if let Some(max_size) = self.backend.max_message_size() {
+            if message.encoded_len() > max_size {
+                if let Some((first, second)) = event.split() {
+                    self.publish(stream, stream_id, &first);
+                    self.publish(stream, stream_id, &second);
+                    return;
                 }
+                warn!(
+                    "SseBroker: {} event for {} {} exceeds the backend limit of {} bytes",
+                    message.event, stream, stream_id, max_size
+                );
             }
         }
-        for dead_device in dead_devices {
-            self.unregister_canteen_subscription(canteen_id, dead_device);
-        }
-        debug!(
-            "canteen_subscription_events: finished publishing event for canteen {}: {} devices",
-            canteen_id, successful_count
-        );
+        self.backend.publish(message);
     }
 }
@@ -1,11 +1,12 @@
+mod backend;
 mod broker;
+mod replay;
 
-use actix_web_lab::sse;
-pub use broker::SseBroker;
-use serde::Serialize;
-use std::time::SystemTime;
+use actix_web::HttpRequest;
+pub use broker::{ChannelEvent, SseBroker};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InventoryUpdateItems {
     pub item_id: i32,
     pub stock: i32,
@@ -13,14 +14,16 @@ pub struct InventoryUpdateItems {
     pub price: i32,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CanteenAggregatedOrderUpdateItem {
     pub item_id: i32,
     pub num_ordered: i32,
 }
 
-#[derive(Clone, Debug, Serialize)]
-#[serde(untagged)]
+/// Serialized untagged, as the data of the SSE event named by [`SseEvent::event_name`]. Unknown
+/// fields are rejected so each variant reads back as itself.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
 pub enum SseEvent {
     InventoryUpdate {
         // to both user and canteen
@@ -45,27 +48,80 @@ pub enum SseEvent {
 }
 
 impl SseEvent {
-    pub fn to_sse_event(&self) -> sse::Event {
-        let (event_name, json_str) = match self {
-            SseEvent::InventoryUpdate { .. } => {
-                ("inventory_update", serde_json::to_string(self).unwrap())
-            }
-            SseEvent::UserOrderUpdate { .. } => {
-                ("user_order_update", serde_json::to_string(self).unwrap())
-            }
-            SseEvent::PaymentUpdate { .. } => {
-                ("payment_update", serde_json::to_string(self).unwrap())
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            SseEvent::InventoryUpdate { .. } => "inventory_update",
+            SseEvent::UserOrderUpdate { .. } => "user_order_update",
+            SseEvent::PaymentUpdate { .. } => "payment_update",
+            SseEvent::CanteenAggregatedOrderUpdate { .. } => "canteen_aggregated_order_update",
+        }
+    }
+
+    /// Halve an inventory update into two, for backends that cannot carry it whole. Clients
+    /// apply inventory items one by one, so each half is a valid update on its own.
+    pub fn split(&self) -> Option<(SseEvent, SseEvent)> {
+        match self {
+            SseEvent::InventoryUpdate { items } if items.len() > 1 => {
+                let (first, second) = items.split_at(items.len() / 2);
+                Some((
+                    SseEvent::InventoryUpdate {
+                        items: first.to_vec(),
+                    },
+                    SseEvent::InventoryUpdate {
+                        items: second.to_vec(),
+                    },
+                ))
             }
-            SseEvent::CanteenAggregatedOrderUpdate { .. } => (
-                "canteen_aggregated_order_update",
-                serde_json::to_string(self).unwrap(),
-            ),
-        };
-        let now = SystemTime::now();
-        let epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
-        sse::Data::new(json_str)
-            .event(event_name)
-            .id(epoch.as_millis().to_string())
-            .into()
+            _ => None,
+        }
+    }
+}
+
+/// The `Last-Event-ID` a reconnecting client sent, if it is a sequence number we issued.
+pub fn last_event_id(req: &HttpRequest) -> Option<u64> {
+    req.headers()
+        .get("Last-Event-ID")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_read_back_as_the_same_variant() {
+        let events = [
+            SseEvent::InventoryUpdate {
+                items: vec![InventoryUpdateItems {
+                    item_id: 1,
+                    stock: 4,
+                    is_available: true,
+                    price: 120,
+                }],
+            },
+            SseEvent::UserOrderUpdate {
+                order_id: 7,
+                status: "placed".to_string(),
+            },
+            SseEvent::PaymentUpdate {
+                hold_id: 3,
+                merchant_order_id: "MO-1".to_string(),
+                payment_state: "COMPLETED".to_string(),
+            },
+            SseEvent::CanteenAggregatedOrderUpdate {
+                time_band: "Instant".to_string(),
+                items: Vec::new(),
+            },
+        ];
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            let read_back: SseEvent = serde_json::from_str(&json).unwrap();
+            assert_eq!(read_back.event_name(), event.event_name());
+            assert_eq!(serde_json::to_string(&read_back).unwrap(), json);
+        }
     }
 }
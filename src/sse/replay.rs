@@ -0,0 +1,215 @@
+use crate::db::SseEventLogOperations;
+use crate::models::common::StoredSseEvent;
+use actix_web_lab::sse;
+use std::collections::VecDeque;
+use std::sync::mpsc;
+
+/// An event as sent on one stream, with that stream's sequence number as its SSE id.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LoggedEvent {
+    pub seq: u64,
+    pub event_name: String,
+    pub data: String,
+}
+
+impl LoggedEvent {
+    pub fn to_sse_event(&self) -> sse::Event {
+        sse::Data::new(self.data.clone())
+            .event(self.event_name.clone())
+            .id(self.seq.to_string())
+            .into()
+    }
+}
+
+/// What a reconnecting client missed since its `Last-Event-ID`.
+#[derive(Debug, Default)]
+pub(crate) struct Replay {
+    pub events: Vec<LoggedEvent>,
+    /// Some events are no longer buffered, or the id is from before a restart; the client
+    /// should refetch its state instead of relying on the replay alone.
+    pub incomplete: bool,
+}
+
+/// Bounded per-stream log of the most recent events.
+#[derive(Debug)]
+pub(crate) struct EventLog {
+    last_seq: u64,
+    capacity: usize,
+    events: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            last_seq: 0,
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Assign the next sequence number and buffer the event.
+    pub fn append(&mut self, event_name: &str, data: String) -> LoggedEvent {
+        self.last_seq += 1;
+        let event = LoggedEvent {
+            seq: self.last_seq,
+            event_name: event_name.to_string(),
+            data,
+        };
+        self.push(event.clone());
+        event
+    }
+
+    /// Buffer an event numbered elsewhere, by the durable log or the database, in sequence
+    /// order. A gap in the sequence drops what is buffered, so replays across it are
+    /// incomplete.
+    pub fn record(&mut self, event: LoggedEvent) -> LoggedEvent {
+        if event.seq > self.last_seq + 1 {
+            self.events.clear();
+        }
+        self.last_seq = self.last_seq.max(event.seq);
+        self.push(event.clone());
+        event
+    }
+
+    pub fn since(&self, last_event_id: u64) -> Replay {
+        if last_event_id > self.last_seq {
+            return Replay {
+                events: Vec::new(),
+                incomplete: true,
+            };
+        }
+        let oldest = self
+            .events
+            .front()
+            .map_or(self.last_seq + 1, |event| event.seq);
+        Replay {
+            events: self
+                .events
+                .iter()
+                .filter(|event| event.seq > last_event_id)
+                .cloned()
+                .collect(),
+            incomplete: last_event_id + 1 < oldest,
+        }
+    }
+
+    fn push(&mut self, event: LoggedEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+}
+
+/// Writes events to the `sse_events` table on a background thread, so publishing never
+/// waits on the database.
+#[derive(Clone)]
+pub(crate) struct DurableEventLog {
+    tx: mpsc::Sender<StoredSseEvent>,
+}
+
+impl DurableEventLog {
+    pub fn spawn(ops: SseEventLogOperations, keep: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<StoredSseEvent>();
+        std::thread::Builder::new()
+            .name("sse-event-log".to_string())
+            .spawn(move || {
+                for event in rx {
+                    if let Err(e) = ops.append(&event, keep) {
+                        warn!(
+                            "sse_event_log: dropped event {} of {} {}: {}",
+                            event.seq, event.stream_kind, event.stream_id, e
+                        );
+                    }
+                }
+            })
+            .expect("Unable to spawn SSE event log writer");
+        Self { tx }
+    }
+
+    pub fn write(&self, stream_kind: &str, stream_id: i32, event: &LoggedEvent) {
+        let stored = StoredSseEvent {
+            stream_kind: stream_kind.to_string(),
+            stream_id,
+            seq: event.seq as i64,
+            event_name: event.event_name.clone(),
+            data: event.data.clone(),
+        };
+        if self.tx.send(stored).is_err() {
+            warn!("sse_event_log: writer has stopped, event not persisted");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_with(capacity: usize, count: usize) -> EventLog {
+        let mut log = EventLog::new(capacity);
+        for n in 0..count {
+            log.append("user_order_update", format!("{{\"n\":{n}}}"));
+        }
+        log
+    }
+
+    #[test]
+    fn sequence_is_monotonic_per_log() {
+        let mut log = EventLog::new(4);
+        assert_eq!(log.append("a", String::new()).seq, 1);
+        assert_eq!(log.append("a", String::new()).seq, 2);
+    }
+
+    #[test]
+    fn since_returns_only_missed_events() {
+        let log = log_with(10, 5);
+        let replay = log.since(3);
+        assert_eq!(
+            replay.events.iter().map(|e| e.seq).collect::<Vec<u64>>(),
+            vec![4, 5]
+        );
+        assert!(!replay.incomplete);
+        assert!(log.since(5).events.is_empty());
+    }
+
+    #[test]
+    fn evicted_or_unknown_ids_are_incomplete() {
+        let log = log_with(3, 6);
+        let replay = log.since(1);
+        assert_eq!(replay.events.first().map(|e| e.seq), Some(4));
+        assert!(replay.incomplete);
+        assert!(!log.since(3).incomplete);
+        assert!(log.since(99).incomplete);
+    }
+
+    #[test]
+    fn record_continues_the_sequence() {
+        let mut log = EventLog::new(3);
+        log.record(LoggedEvent {
+            seq: 41,
+            event_name: "inventory_update".to_string(),
+            data: "{}".to_string(),
+        });
+        assert_eq!(log.append("inventory_update", "{}".to_string()).seq, 42);
+        assert_eq!(log.since(40).events.len(), 2);
+    }
+
+    #[test]
+    fn gaps_in_recorded_events_make_replays_incomplete() {
+        let mut log = EventLog::new(5);
+        for seq in [1, 2, 5] {
+            log.record(LoggedEvent {
+                seq,
+                event_name: "user_order_update".to_string(),
+                data: "{}".to_string(),
+            });
+        }
+        assert!(log.since(2).incomplete);
+        let replay = log.since(4);
+        assert_eq!(replay.events.len(), 1);
+        assert!(!replay.incomplete);
+    }
+}
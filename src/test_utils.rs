@@ -87,8 +87,16 @@ pub fn build_test_pool(database_url: &str) -> Pool<ConnectionManager<PgConnectio
 pub fn reset_db(pool: &Pool<ConnectionManager<PgConnection>>) -> Result<(), RepositoryError> {
     let mut conn = DbConnection::new(pool)?;
     diesel::sql_query(
-        "TRUNCATE TABLE active_order_items, active_orders, held_order_items, held_orders, \
-         payment_orders, menu_items, past_orders, users, canteens RESTART IDENTITY CASCADE",
+        "TRUNCATE TABLE settlements, hold_history, refunds, support_messages, support_tickets, promo_redemptions, promo_codes, active_order_items, active_orders, \
+         held_order_items, held_orders, payment_orders, pricing_rule_windows, pricing_rules, \
+         item_ratings, order_reviews, menu_item_schedules, menu_items, menu_categories, \
+         past_order_items, past_orders, \
+         sse_events, sse_stream_seqs, \
+         device_tokens, outbox, webhook_deliveries, canteen_webhooks, \
+         search_query_stats, user_item_stats, item_pair_stats, item_hourly_sales, \
+         favourite_items, favourite_canteens, saved_cart_items, saved_carts, cart_items, \
+         users, canteens \
+         RESTART IDENTITY CASCADE",
     )
     .execute(conn.connection())
     .map_err(RepositoryError::DatabaseError)?;
@@ -206,6 +214,13 @@ pub fn seed_menu_item(
         stock: stock_val,
         is_available: is_available_val,
         description: description_val.map(|val| val.to_string()),
+        default_stock: None,
+        category_id: None,
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
     };
 
     diesel::insert_into(menu_items)
@@ -6,28 +6,9 @@ use actix_web::test;
 use common::auth_header;
 use diesel::prelude::*;
 use proj_xs::db::DbConnection;
-use proj_xs::test_utils::{build_test_pool, insert_canteen, seed_menu_item};
+use proj_xs::test_utils::build_test_pool;
 use serde_json::Value;
 
-fn seed_other_canteen_item(db_url: &str) -> (i32, i32) {
-    let pool = build_test_pool(db_url);
-    let mut conn = DbConnection::new(&pool).expect("db connection");
-    let other_canteen_id =
-        insert_canteen(conn.connection(), "Other Canteen", "Block Z").expect("insert canteen");
-    let other_item_id = seed_menu_item(
-        conn.connection(),
-        other_canteen_id,
-        "Other Canteen Item",
-        199,
-        8,
-        true,
-        true,
-        Some("owned by another canteen"),
-    )
-    .expect("insert menu item");
-    (other_canteen_id, other_item_id)
-}
-
 #[actix_rt::test]
 async fn create_menu_item_success() {
     let (app, fixtures, _db_url) = common::setup_api_app().await;
@@ -140,7 +121,7 @@ async fn update_menu_item_not_found() {
 #[actix_rt::test]
 async fn update_menu_item_forbidden_for_item_owned_by_another_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     let req = test::TestRequest::put()
         .uri(&format!("/menu/update?as=admin-{}", fixtures.canteen_id))
@@ -195,7 +176,7 @@ async fn delete_menu_item_success_and_not_found() {
 #[actix_rt::test]
 async fn delete_menu_item_forbidden_for_item_owned_by_another_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     let req = test::TestRequest::delete()
         .uri(&format!(
@@ -749,7 +730,7 @@ async fn upload_menu_item_pic_nonexistent_item() {
 #[actix_rt::test]
 async fn upload_menu_item_pic_forbidden_for_item_owned_by_another_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     let req = test::TestRequest::put()
         .uri(&format!(
@@ -804,7 +785,7 @@ async fn set_menu_item_pic_nonexistent_item() {
 #[actix_rt::test]
 async fn set_menu_item_pic_forbidden_for_item_owned_by_another_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     let req = test::TestRequest::put()
         .uri(&format!(
@@ -982,3 +963,429 @@ async fn menu_set_pic_success_when_object_exists() {
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["status"], "ok");
 }
+
+#[actix_rt::test]
+async fn create_menu_item_with_default_stock() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/create?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "name": "Idli",
+            "is_veg": true,
+            "price": 40,
+            "stock": 30,
+            "is_available": true,
+            "description": null,
+            "default_stock": 30
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let item_id = body["item_id"].as_i64().expect("item_id");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/menu/items/{}", item_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["default_stock"], 30);
+
+    // Leaving the field out keeps the default stock; an explicit null clears it.
+    for (update, expected) in [
+        (serde_json::json!({ "stock": 12 }), serde_json::json!(30)),
+        (serde_json::json!({ "default_stock": null }), Value::Null),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/menu/update?as=admin-{}", fixtures.canteen_id))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(serde_json::json!({ "item_id": item_id, "update": update }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/menu/items/{}", item_id))
+            .insert_header(auth_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"]["default_stock"], expected);
+    }
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_set_and_get() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let item_id = fixtures.menu_item_ids[0];
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/menu/schedule/{}?as=admin-{}",
+            item_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "windows": [
+                { "weekday": 4, "start_time": "16:00:00", "end_time": "18:30:00" },
+                { "weekday": 0, "start_time": "08:00:00", "end_time": "11:00:00" }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["data"].as_array().expect("windows").len(), 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/menu/schedule/{}?as=admin-{}",
+            item_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"][0]["weekday"], 0);
+    assert_eq!(body["data"][0]["start_time"], "08:00:00");
+    assert_eq!(body["data"][1]["weekday"], 4);
+    assert_eq!(body["data"][1]["end_time"], "18:30:00");
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_rejects_invalid_weekday() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/menu/schedule/{}?as=admin-{}",
+            fixtures.menu_item_ids[0], fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "windows": [{ "weekday": 7, "start_time": "08:00:00", "end_time": "11:00:00" }]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "error");
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_forbidden_for_item_owned_by_another_canteen() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/menu/schedule/{}?as=admin-{}",
+            other_item_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "windows": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "item not found");
+}
+
+#[actix_rt::test]
+async fn menu_categories_and_tags_filter_canteen_menu() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/menu/categories?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "name": "Breakfast", "display_order": 1 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let category_id = body["data"]["category_id"].as_i64().expect("category_id");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/create?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "name": "Masala Dosa",
+            "is_veg": true,
+            "price": 70,
+            "stock": 20,
+            "is_available": true,
+            "description": null,
+            "default_stock": null,
+            "category_id": category_id,
+            "is_pinned": true,
+            "tags": ["Bestseller", "south-indian"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let item_id = body["item_id"].as_i64().expect("item_id");
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/canteen/{}/categories?as=user-{}",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"][0]["name"], "Breakfast");
+
+    // Pinned items lead the unfiltered menu.
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/canteen/{}/items?as=user-{}",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"][0]["item_id"], item_id);
+    assert_eq!(body["data"].as_array().expect("items").len(), 3);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/canteen/{}/items?as=user-{}&tag=bestseller",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    let items = body["data"].as_array().expect("items");
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0]["tags"],
+        serde_json::json!(["bestseller", "south-indian"])
+    );
+
+    // An explicit null moves the item out of its category.
+    let req = test::TestRequest::put()
+        .uri(&format!("/menu/update?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "item_id": item_id,
+            "update": { "category_id": null }
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/canteen/{}/items?as=user-{}&category_id={}",
+            fixtures.canteen_id, fixtures.user_id, category_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["data"].as_array().expect("items").is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/menu/tags?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().expect("tags").len(), 2);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/menu/categories/{}?as=admin-{}",
+            category_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn create_menu_item_rejects_category_of_another_canteen() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/menu/categories?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "name": "Snacks" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    let category_id = body["data"]["category_id"].as_i64().expect("category_id");
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/menu/create?as=admin-{}",
+            fixtures.canteen_id + 1
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "name": "Samosa",
+            "is_veg": true,
+            "price": 20,
+            "stock": 10,
+            "is_available": true,
+            "description": null,
+            "default_stock": null,
+            "category_id": category_id
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn menu_csv_import_dry_run_apply_and_export() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let csv = "item_id,name,is_veg,price,stock,is_available,description,default_stock,category,display_order,is_pinned,tags,allergens,diet_labels\n\
+               ,Idli,true,40,30,true,Steamed rice cakes,30,Breakfast,1,true,south-indian|light,,vegan\n";
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/menu/import?dry_run=true&as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["applied"], false);
+    assert_eq!(body["data"]["created"][0]["name"], "Idli");
+    assert_eq!(body["data"]["created_categories"][0], "Breakfast");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/import?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["applied"], true);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/menu/export?format=csv&as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/csv"
+    );
+    assert!(resp
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("menu.csv"));
+    let exported = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let mut lines = exported.lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("item_id,name,is_veg,price"));
+    assert_eq!(lines.clone().count(), 3);
+    assert!(exported.contains(
+        ",Idli,true,40,30,true,Steamed rice cakes,30,Breakfast,1,true,south-indian|light,,vegan"
+    ));
+
+    // The JSON export imports back as a no-op.
+    let req = test::TestRequest::get()
+        .uri(&format!("/menu/export?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let exported: Value = test::read_body_json(resp).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/import?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(&exported)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["unchanged"], 3);
+}
+
+#[actix_rt::test]
+async fn menu_import_rejects_invalid_rows_and_unknown_content_type() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/import?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!([
+            { "name": "Poha", "is_veg": true, "price": 30, "stock": 10, "is_available": true,
+              "description": null, "default_stock": null, "category": null },
+            { "name": "", "is_veg": true, "price": 30, "stock": 10, "is_available": true,
+              "description": null, "default_stock": null, "category": null }
+        ]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["applied"], false);
+    assert_eq!(body["data"]["errors"][0]["row"], 2);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/menu/import?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .set_payload("name\nPoha\n")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/menu/items?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+}
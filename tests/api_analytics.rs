@@ -0,0 +1,182 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::schema::payment_orders;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::json;
+
+#[actix_rt::test]
+async fn sales_report_covers_orders_items_slots_holds_and_payments() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+
+    common::complete_order(&app, user_id, canteen_id, &[veg_id, wrap_id], "delivered").await;
+    common::complete_order(&app, user_id, canteen_id, &[wrap_id], "cancelled").await;
+    let slotted = common::place_order(
+        &app,
+        user_id,
+        canteen_id,
+        json!({ "deliver_at": "11:00am - 12:00pm", "item_ids": [veg_id] }),
+    )
+    .await;
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{slotted}/delivered?as=admin-{canteen_id}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let released = common::hold_order(&app, user_id, json!({ "item_ids": [veg_id] })).await;
+    let req = test::TestRequest::delete()
+        .uri(&format!("/orders/hold/{released}?as=user-{user_id}"))
+        .insert_header(auth_header())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    common::hold_order(&app, user_id, json!({ "item_ids": [wrap_id] })).await;
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    for (hold_id, state, payment_canteen_id) in [
+        (9001, "COMPLETED", Some(canteen_id)),
+        (9002, "FAILED", Some(canteen_id)),
+        (9003, "PENDING", Some(canteen_id)),
+        (9004, "FAILED", None),
+    ] {
+        diesel::insert_into(payment_orders::table)
+            .values((
+                payment_orders::hold_id.eq(hold_id),
+                payment_orders::user_id.eq(user_id),
+                payment_orders::merchant_order_id.eq(format!("MO-{hold_id}")),
+                payment_orders::phonepe_order_id.eq(format!("OMO-{hold_id}")),
+                payment_orders::sdk_token.eq("token"),
+                payment_orders::amount.eq(12000),
+                payment_orders::payment_state.eq(state),
+                payment_orders::canteen_id.eq(payment_canteen_id),
+            ))
+            .execute(conn.connection())
+            .expect("insert payment");
+    }
+
+    let (status, body) =
+        common::get_json(&app, &format!("/analytics/sales?as=admin-{canteen_id}")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let report = &body["data"];
+    assert_eq!(report["granularity"], "day");
+    assert_eq!(
+        report["totals"],
+        json!({
+            "orders": 3,
+            "delivered": 2,
+            "cancelled": 1,
+            "cancellation_rate": 0.3333,
+            "revenue": 420,
+            "average_basket": 210.0
+        })
+    );
+    let periods = report["periods"].as_array().unwrap();
+    assert_eq!(periods.len(), 1);
+    assert_eq!(periods[0]["orders"], 3);
+    assert_eq!(periods[0]["revenue"], 420);
+    assert_eq!(periods[0]["period_start"], report["to"]);
+
+    let items = report["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["item_id"], veg_id);
+    assert_eq!(items[0]["name"], "Veg Sandwich");
+    assert_eq!(items[0]["quantity"], 2);
+    assert_eq!(items[0]["revenue"], 240);
+    assert_eq!(items[0]["revenue_share"], 0.5714);
+    assert_eq!(items[1]["item_id"], wrap_id);
+    assert_eq!(items[1]["revenue"], 180);
+
+    assert_eq!(
+        report["slots"],
+        json!([
+            { "slot": "Instant", "orders": 2, "share": 0.6667 },
+            { "slot": "11:00am - 12:00pm", "orders": 1, "share": 0.3333 }
+        ])
+    );
+    assert_eq!(
+        report["holds"],
+        json!({
+            "holds": 5,
+            "confirmed": 3,
+            "released": 1,
+            "expired": 0,
+            "pending": 1,
+            "conversion_rate": 0.75
+        })
+    );
+    assert_eq!(
+        report["payments"],
+        json!({
+            "payments": 3,
+            "completed": 1,
+            "failed": 1,
+            "pending": 1,
+            "failure_rate": 0.5
+        })
+    );
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/analytics/sales?granularity=month&as=admin-{canteen_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let periods = body["data"]["periods"].as_array().unwrap();
+    assert_eq!(periods.len(), 1);
+    assert!(periods[0]["period_start"]
+        .as_str()
+        .unwrap()
+        .ends_with("-01"));
+
+    // Another canteen sees none of it.
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/analytics/sales?as=admin-{}", canteen_id + 1000),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["totals"]["orders"], 0);
+    assert_eq!(body["data"]["items"], json!([]));
+    assert_eq!(body["data"]["holds"]["holds"], 0);
+    assert_eq!(body["data"]["payments"]["payments"], 0);
+}
+
+#[actix_rt::test]
+async fn sales_report_rejects_bad_ranges_and_granularities() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let canteen_id = fixtures.canteen_id;
+
+    for query in [
+        "from=2026-08-10&to=2026-08-01",
+        "from=2025-01-01&to=2026-01-02",
+        "granularity=hour",
+    ] {
+        let (status, body) = common::get_json(
+            &app,
+            &format!("/analytics/sales?{query}&as=admin-{canteen_id}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+        assert_eq!(body["status"], "error");
+    }
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!(
+            "/analytics/sales?from=2025-01-01&to=2026-01-01&granularity=week&as=admin-{canteen_id}"
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["from"], "2025-01-01");
+    assert_eq!(body["data"]["periods"], json!([]));
+}
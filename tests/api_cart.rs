@@ -0,0 +1,253 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, Error};
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::schema::menu_items;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+
+async fn call<S>(app: &S, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let mut req = test::TestRequest::default()
+        .method(method)
+        .uri(uri)
+        .insert_header(auth_header());
+    if let Some(body) = body {
+        req = req
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(body);
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+fn line_statuses(cart: &Value) -> Vec<(i64, String)> {
+    cart["lines"]
+        .as_array()
+        .expect("lines")
+        .iter()
+        .map(|line| {
+            (
+                line["item_id"].as_i64().unwrap(),
+                line["status"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn cart_reads_revalidate_prices_and_stock() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let cart_uri = format!("/users/cart/{canteen_id}");
+    let as_user = format!("as=user-{user_id}");
+
+    for _ in 0..2 {
+        let (status, body) = call(
+            &app,
+            Method::POST,
+            &format!("{cart_uri}/items?{as_user}"),
+            Some(json!({ "item_id": veg_id, "quantity": 2 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+    }
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        &format!("{cart_uri}/items?{as_user}"),
+        Some(json!({ "item_id": wrap_id, "quantity": 1 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["lines"][0]["quantity"], 4);
+    assert_eq!(body["data"]["total_price"], 4 * 120 + 180);
+    assert_eq!(body["data"]["is_orderable"], true);
+
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &format!("{cart_uri}/items?{as_user}"),
+        Some(json!({ "item_id": wrap_id, "quantity": 5 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &format!("{cart_uri}/items?{as_user}"),
+        Some(json!({ "item_id": 99999, "quantity": 1 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(
+        &app,
+        Method::PUT,
+        &format!("{cart_uri}/items/{veg_id}?{as_user}"),
+        Some(json!({ "quantity": 21 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    diesel::update(menu_items::table.find(veg_id))
+        .set(menu_items::price.eq(130))
+        .execute(conn.connection())
+        .expect("raise price");
+    diesel::update(menu_items::table.find(wrap_id))
+        .set(menu_items::stock.eq(0))
+        .execute(conn.connection())
+        .expect("sell out wrap");
+
+    let (status, body) = call(&app, Method::GET, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        line_statuses(&body["data"]),
+        vec![
+            (veg_id as i64, "price_changed".to_string()),
+            (wrap_id as i64, "insufficient_stock".to_string()),
+        ]
+    );
+    assert_eq!(body["data"]["lines"][0]["added_price"], 120);
+    assert_eq!(body["data"]["lines"][0]["unit_price"], 130);
+    assert_eq!(body["data"]["lines"][1]["available_stock"], 0);
+    assert_eq!(body["data"]["is_orderable"], false);
+
+    diesel::update(menu_items::table.find(wrap_id))
+        .set((menu_items::stock.eq(5), menu_items::is_available.eq(false)))
+        .execute(conn.connection())
+        .expect("disable wrap");
+    let (_, body) = call(&app, Method::GET, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(body["data"]["lines"][1]["status"], "unavailable");
+
+    let (status, body) = call(
+        &app,
+        Method::DELETE,
+        &format!("{cart_uri}/items/{wrap_id}?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        line_statuses(&body["data"]),
+        vec![(veg_id as i64, "price_changed".to_string())]
+    );
+    assert_eq!(body["data"]["is_orderable"], true);
+
+    let (status, body) = call(
+        &app,
+        Method::PUT,
+        &format!("{cart_uri}/items/{veg_id}?{as_user}"),
+        Some(json!({ "quantity": 1 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        line_statuses(&body["data"]),
+        vec![(veg_id as i64, "ok".to_string())]
+    );
+    assert_eq!(body["data"]["total_price"], 130);
+
+    let (status, _) = call(&app, Method::DELETE, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(&app, Method::GET, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(body["data"]["lines"], json!([]));
+    assert_eq!(body["data"]["is_orderable"], false);
+}
+
+#[actix_rt::test]
+async fn holding_a_cart_is_all_or_nothing() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let cart_uri = format!("/users/cart/{canteen_id}");
+    let hold_uri = format!("/orders/hold/cart/{canteen_id}?as=user-{user_id}");
+    let as_user = format!("as=user-{user_id}");
+
+    let (status, body) = call(&app, Method::POST, &hold_uri, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["status"], "error");
+
+    for (item_id, quantity) in [(veg_id, 3), (wrap_id, 2)] {
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            &format!("{cart_uri}/items?{as_user}"),
+            Some(json!({ "item_id": item_id, "quantity": quantity })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    diesel::update(menu_items::table.find(wrap_id))
+        .set(menu_items::stock.eq(1))
+        .execute(conn.connection())
+        .expect("lower wrap stock");
+    let (status, _) = call(&app, Method::POST, &hold_uri, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(common::menu_item_state(conn.connection(), veg_id).0, 10);
+    let (_, body) = call(&app, Method::GET, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(body["data"]["lines"].as_array().unwrap().len(), 2);
+
+    diesel::update(menu_items::table.find(wrap_id))
+        .set(menu_items::stock.eq(5))
+        .execute(conn.connection())
+        .expect("restock wrap");
+
+    let sse_req = test::TestRequest::get()
+        .uri(&format!("/users/events/cart/{canteen_id}?{as_user}"))
+        .insert_header(auth_header())
+        .to_request();
+    let sse_resp = test::call_service(&app, sse_req).await;
+    assert_eq!(sse_resp.status(), StatusCode::OK);
+    let mut cart_stream = sse_resp.into_body();
+    let _retry = common::read_sse_frame(&mut cart_stream).await;
+    let _connected = common::wait_for_connected_event(&mut cart_stream).await;
+
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        &hold_uri,
+        Some(json!({ "deliver_at": null, "note": "no onions" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["hold_id"].is_number());
+    assert_eq!(body["total_price"], 3 * 120 + 2 * 180);
+    assert_eq!(common::menu_item_state(conn.connection(), veg_id).0, 7);
+    assert_eq!(common::menu_item_state(conn.connection(), wrap_id).0, 3);
+
+    let (_, body) = call(&app, Method::GET, &format!("{cart_uri}?{as_user}"), None).await;
+    assert_eq!(body["data"]["lines"], json!([]));
+
+    let event = common::wait_for_sse_event(&mut cart_stream, "inventory_update").await;
+    let payload = common::sse_frame_data_json(&event);
+    let mut stocks = payload["items"]
+        .as_array()
+        .expect("items")
+        .iter()
+        .map(|item| {
+            (
+                item["item_id"].as_i64().unwrap(),
+                item["stock"].as_i64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    stocks.sort();
+    assert_eq!(stocks, vec![(veg_id as i64, 7), (wrap_id as i64, 3)]);
+}
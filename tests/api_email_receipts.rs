@@ -0,0 +1,276 @@
+mod common;
+
+use diesel::prelude::*;
+use proj_xs::db::schema::{outbox, payment_orders, users};
+use proj_xs::db::{DbConnection, HoldOperations};
+use proj_xs::models::common::NewPaymentOrder;
+use proj_xs::services::email::{
+    Email, MailOutcome, MailSender, SmtpConfig, SmtpMailTransport, SmtpSecurity,
+};
+use proj_xs::services::outbox::OutboxMessage;
+use proj_xs::services::push::RetryPolicy;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A local SMTP sink: accepts one message per connection and keeps its DATA, or rejects the
+/// recipient with `rcpt_reply` when given.
+fn start_smtp_sink(rcpt_reply: Option<&'static str>) -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind SMTP sink");
+    let port = listener.local_addr().unwrap().port();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let _ = stream.write_all(b"220 sink ESMTP\r\n");
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                let command = line.trim_end().to_uppercase();
+                let reply = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    "250 sink\r\n"
+                } else if command.starts_with("RCPT") {
+                    rcpt_reply.unwrap_or("250 OK\r\n")
+                } else if command == "DATA" {
+                    let _ = stream.write_all(b"354 go ahead\r\n");
+                    let mut data = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&line);
+                    }
+                    sink.lock().unwrap().push(data);
+                    "250 queued\r\n"
+                } else if command == "QUIT" {
+                    let _ = stream.write_all(b"221 bye\r\n");
+                    break;
+                } else {
+                    "250 OK\r\n"
+                };
+                let _ = stream.write_all(reply.as_bytes());
+            }
+        }
+    });
+    (port, received)
+}
+
+fn sink_transport(port: u16) -> SmtpMailTransport {
+    SmtpMailTransport::new(SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        security: SmtpSecurity::None,
+        username: None,
+        password: None,
+        from: "Canteen Receipts <receipts@example.com>".to_string(),
+        timeout_secs: 5,
+    })
+    .expect("SMTP transport")
+}
+
+/// Hold and confirm an order of one Veg Sandwich, optionally paid through PhonePe.
+fn confirm_order(
+    hold_ops: &HoldOperations,
+    fixtures: &proj_xs::test_utils::TestFixtures,
+    conn: &mut PgConnection,
+    payment_reference: Option<&str>,
+) -> i32 {
+    let (hold_id, _, _) = hold_ops
+        .hold_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
+        .expect("hold order");
+
+    if let Some(reference) = payment_reference {
+        diesel::insert_into(payment_orders::table)
+            .values(NewPaymentOrder {
+                hold_id,
+                user_id: fixtures.user_id,
+                merchant_order_id: reference.to_string(),
+                phonepe_order_id: "OMO123".to_string(),
+                sdk_token: "token".to_string(),
+                amount: 12000,
+                payment_state: "PENDING".to_string(),
+                phonepe_expires_at: None,
+                app_order_id: None,
+                canteen_id: Some(fixtures.canteen_id),
+            })
+            .execute(conn)
+            .expect("insert payment mapping");
+    }
+
+    let (order_id, _, _, _) = hold_ops
+        .confirm_held_order_internal(hold_id)
+        .expect("confirm hold");
+    order_id
+}
+
+fn verify_user_email(conn: &mut PgConnection, user_id: i32) {
+    diesel::update(users::table.find(user_id))
+        .set(users::email_verified.eq(true))
+        .execute(conn)
+        .expect("verify email");
+}
+
+/// Emails waiting in the outbox, oldest first.
+fn queued_emails(conn: &mut PgConnection) -> Vec<(i32, Email)> {
+    outbox::table
+        .filter(outbox::topic.eq("email"))
+        .order(outbox::outbox_id.asc())
+        .select(outbox::payload)
+        .load::<String>(conn)
+        .expect("outbox entries")
+        .into_iter()
+        .map(
+            |payload| match serde_json::from_str(&payload).expect("payload") {
+                OutboxMessage::Email { user_id, email } => (user_id, email),
+                other => panic!("unexpected outbox message {other:?}"),
+            },
+        )
+        .collect()
+}
+
+fn receipt(recipient: &str) -> Email {
+    Email {
+        recipient: recipient.to_string(),
+        subject: "Your receipt".to_string(),
+        body_text: "Total: Rs. 120".to_string(),
+        body_html: "<p>Total: Rs. 120</p>".to_string(),
+    }
+}
+
+#[test]
+fn confirmed_orders_queue_a_receipt_in_the_outbox() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let hold_ops = HoldOperations::new(pool.clone(), 300);
+
+    // Users without a verified email get no receipt.
+    confirm_order(&hold_ops, &fixtures, conn.connection(), None);
+    assert!(queued_emails(conn.connection()).is_empty());
+
+    verify_user_email(conn.connection(), fixtures.user_id);
+    confirm_order(&hold_ops, &fixtures, conn.connection(), None);
+    let order_id = confirm_order(
+        &hold_ops,
+        &fixtures,
+        conn.connection(),
+        Some("MO-RECEIPT-1"),
+    );
+
+    let queued = queued_emails(conn.connection());
+    assert_eq!(queued.len(), 2);
+    assert!(queued[0].1.body_text.contains("Paid at the canteen"));
+    let (user_id, email) = &queued[1];
+    assert_eq!(*user_id, fixtures.user_id);
+    assert_eq!(email.recipient, "user1@example.com");
+    assert_eq!(
+        email.subject,
+        format!("Your receipt for order #{order_id} at Test Canteen")
+    );
+    for expected in [
+        "Hi User One",
+        "Canteen: Test Canteen, Block A",
+        "Slot: Instant",
+        "1 x Veg Sandwich @ Rs. 120 = Rs. 120",
+        "Total: Rs. 120",
+        "Paid via PhonePe, reference MO-RECEIPT-1",
+    ] {
+        assert!(email.body_text.contains(expected), "missing {expected:?}");
+    }
+}
+
+#[actix_rt::test]
+async fn receipts_are_sent_over_smtp_by_the_outbox_dispatcher() {
+    let (port, received) = start_smtp_sink(None);
+    std::env::set_var("SMTP_ENABLED", "true");
+    std::env::set_var("SMTP_HOST", "127.0.0.1");
+    std::env::set_var("SMTP_PORT", port.to_string());
+    std::env::set_var("SMTP_SECURITY", "none");
+    std::env::set_var("SMTP_FROM", "Canteen Receipts <receipts@example.com>");
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    for key in [
+        "SMTP_ENABLED",
+        "SMTP_HOST",
+        "SMTP_PORT",
+        "SMTP_SECURITY",
+        "SMTP_FROM",
+    ] {
+        std::env::remove_var(key);
+    }
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    verify_user_email(conn.connection(), fixtures.user_id);
+
+    let order_id = common::place_order(
+        &app,
+        fixtures.user_id,
+        fixtures.canteen_id,
+        json!({ "deliver_at": null, "item_ids": [fixtures.menu_item_ids[0]] }),
+    )
+    .await;
+
+    for _ in 0..50 {
+        if !received.lock().unwrap().is_empty() && queued_emails(conn.connection()).is_empty() {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let messages = received.lock().unwrap().clone();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("To: user1@example.com"));
+    assert!(messages[0].contains(&format!("Your receipt for order #{order_id}")));
+    assert!(messages[0].contains("text/html"));
+    // Delivered emails leave the outbox.
+    assert!(queued_emails(conn.connection()).is_empty());
+}
+
+#[actix_rt::test]
+async fn mail_failures_are_retried_until_the_budget_is_spent() {
+    let retry = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_secs(60),
+    };
+
+    // Nothing listens on the port: a transient failure, retried later.
+    let closed_port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let unreachable = MailSender::new(Arc::new(sink_transport(closed_port)), retry);
+    let email = receipt("user1@example.com");
+    match unreachable.send(email.clone(), 0).await {
+        MailOutcome::Retry(delay, _) => assert_eq!(delay, Duration::from_secs(60)),
+        other => panic!("expected a retry, got {other:?}"),
+    }
+    match unreachable.send(email.clone(), 1).await {
+        MailOutcome::Retry(delay, _) => assert_eq!(delay, Duration::from_secs(120)),
+        other => panic!("expected a retry, got {other:?}"),
+    }
+    assert!(matches!(
+        unreachable.send(email.clone(), 2).await,
+        MailOutcome::GiveUp(_)
+    ));
+
+    // A rejected recipient is permanent: the email is given up on at once.
+    let (port, received) = start_smtp_sink(Some("550 no such mailbox\r\n"));
+    let rejecting = MailSender::new(Arc::new(sink_transport(port)), retry);
+    assert!(matches!(
+        rejecting.send(email.clone(), 0).await,
+        MailOutcome::GiveUp(_)
+    ));
+    assert!(received.lock().unwrap().is_empty());
+
+    let (port, received) = start_smtp_sink(None);
+    let accepting = MailSender::new(Arc::new(sink_transport(port)), retry);
+    assert!(matches!(accepting.send(email, 0).await, MailOutcome::Done));
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, Error};
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+
+/// Status, headers and the CSV lines of an export.
+async fn export<S>(app: &S, uri: &str) -> (StatusCode, header::HeaderMap, Vec<String>)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = test::read_body(resp).await;
+    let lines = String::from_utf8(body.to_vec())
+        .expect("utf-8 body")
+        .lines()
+        .map(str::to_string)
+        .collect();
+    (status, headers, lines)
+}
+
+#[actix_rt::test]
+async fn exports_orders_items_and_payments_as_csv() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let delivered =
+        common::complete_order(&app, user_id, canteen_id, &[veg_id, wrap_id], "delivered").await;
+    let cancelled =
+        common::complete_order(&app, user_id, canteen_id, &[wrap_id], "cancelled").await;
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    diesel::sql_query(
+        "WITH payment AS ( \
+             INSERT INTO payment_orders (hold_id, user_id, merchant_order_id, phonepe_order_id, \
+                                         sdk_token, amount, payment_state, app_order_id, canteen_id) \
+             VALUES (9001, $1, 'MO-9001', 'OMO-9001', 'token', 30000, 'COMPLETED', $2, $3) \
+             RETURNING payment_id) \
+         INSERT INTO refunds (payment_id, canteen_id, merchant_refund_id, amount, state) \
+         SELECT payment_id, $3, refund.id, refund.amount, refund.state FROM payment, \
+             (VALUES ('MR-1', 2000, 'COMPLETED'), ('MR-2', 500, 'FAILED')) AS refund (id, amount, state)",
+    )
+    .bind::<diesel::sql_types::Int4, _>(user_id)
+    .bind::<diesel::sql_types::Int4, _>(delivered as i32)
+    .bind::<diesel::sql_types::Int4, _>(canteen_id)
+    .execute(conn.connection())
+    .expect("insert payment");
+
+    let (status, headers, lines) =
+        export(&app, &format!("/exports/orders?as=admin-{canteen_id}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "text/csv");
+    let disposition = headers
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(disposition.contains("attachment"));
+    assert!(disposition.contains("orders-"));
+    assert_eq!(
+        lines[0],
+        "order_id,ordered_at,business_date,user_id,status,slot,total,merchant_order_id,payment_state"
+    );
+    assert_eq!(lines.len(), 3);
+    let row: Vec<&str> = lines[1].split(',').collect();
+    assert_eq!(row[0], delivered.to_string());
+    assert_eq!(row[3], user_id.to_string());
+    assert_eq!(
+        &row[4..],
+        ["delivered", "Instant", "300", "MO-9001", "COMPLETED"]
+    );
+    let row: Vec<&str> = lines[2].split(',').collect();
+    assert_eq!(row[0], cancelled.to_string());
+    assert_eq!(&row[4..], ["cancelled", "Instant", "180", "", ""]);
+
+    let (status, _, lines) =
+        export(&app, &format!("/exports/order-items?as=admin-{canteen_id}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines[0],
+        "order_id,ordered_at,business_date,status,item_id,item_name,quantity,unit_price,line_total"
+    );
+    assert_eq!(lines.len(), 4);
+    let items: Vec<Vec<&str>> = lines[1..]
+        .iter()
+        .map(|line| line.split(',').collect())
+        .collect();
+    assert_eq!(items[0][0], delivered.to_string());
+    assert_eq!(items[0][5..], ["Veg Sandwich", "1", "120", "120"]);
+    assert_eq!(items[1][5..], ["Chicken Wrap", "1", "180", "180"]);
+    assert_eq!(items[2][0], cancelled.to_string());
+    assert_eq!(items[2][3], "cancelled");
+
+    let (status, _, lines) =
+        export(&app, &format!("/exports/payments?as=admin-{canteen_id}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines[0],
+        "payment_id,created_at,business_date,merchant_order_id,phonepe_order_id,hold_id,order_id,user_id,amount_paisa,payment_state,refunded_paisa,updated_at"
+    );
+    assert_eq!(lines.len(), 2);
+    let row: Vec<&str> = lines[1].split(',').collect();
+    assert_eq!(
+        row[3..11],
+        [
+            "MO-9001",
+            "OMO-9001",
+            "9001",
+            &delivered.to_string(),
+            &user_id.to_string(),
+            "30000",
+            "COMPLETED",
+            "2000"
+        ]
+    );
+
+    // Another canteen's export has just the header.
+    let (status, _, lines) = export(
+        &app,
+        &format!("/exports/orders?as=admin-{}", canteen_id + 1000),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(lines.len(), 1);
+}
+
+#[actix_rt::test]
+async fn large_exports_are_streamed_in_pages() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let canteen_id = fixtures.canteen_id;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    diesel::sql_query(
+        "INSERT INTO past_orders (order_id, user_id, items, price, order_status, ordered_at, canteen_id) \
+         SELECT 100000 + n, $1, ARRAY[$3], 120, n % 10 <> 0, NOW() - n * INTERVAL '1 second', $2 \
+         FROM generate_series(1, 1201) AS n",
+    )
+    .bind::<diesel::sql_types::Int4, _>(fixtures.user_id)
+    .bind::<diesel::sql_types::Int4, _>(canteen_id)
+    .bind::<diesel::sql_types::Int4, _>(fixtures.menu_item_ids[0])
+    .execute(conn.connection())
+    .expect("insert past orders");
+
+    let (status, _, lines) = export(&app, &format!("/exports/orders?as=admin-{canteen_id}")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(lines.len(), 1202);
+    let order_ids: Vec<i32> = lines[1..]
+        .iter()
+        .map(|line| line.split(',').next().unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(order_ids, (100001..=101201).collect::<Vec<_>>());
+    assert_eq!(
+        lines[1..]
+            .iter()
+            .filter(|line| line.contains(",cancelled,"))
+            .count(),
+        120
+    );
+}
+
+#[actix_rt::test]
+async fn exports_reject_bad_ranges() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let canteen_id = fixtures.canteen_id;
+
+    for query in [
+        "from=2026-08-10&to=2026-08-01",
+        "from=2025-01-01&to=2026-01-02",
+    ] {
+        let (status, _, lines) = export(
+            &app,
+            &format!("/exports/payments?{query}&as=admin-{canteen_id}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+        assert!(lines[0].contains("\"status\":\"error\""));
+    }
+
+    let (status, headers, lines) = export(
+        &app,
+        &format!("/exports/order-items?from=2026-01-01&to=2026-01-31&as=admin-{canteen_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("order-items-2026-01-01-2026-01-31.csv"));
+    assert_eq!(lines.len(), 1);
+}
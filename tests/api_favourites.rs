@@ -0,0 +1,274 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, Error};
+use common::auth_header;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::{build_test_pool, insert_canteen, insert_user, seed_menu_item};
+use serde_json::{json, Value};
+
+async fn call<S>(app: &S, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let mut req = test::TestRequest::default()
+        .method(method)
+        .uri(uri)
+        .insert_header(auth_header());
+    if let Some(body) = body {
+        req = req
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(body);
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+fn favourite_flags(items: &Value) -> Vec<(i64, bool)> {
+    items
+        .as_array()
+        .expect("items")
+        .iter()
+        .map(|item| {
+            (
+                item["item_id"].as_i64().unwrap(),
+                item["is_favourite"].as_bool().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[actix_rt::test]
+async fn favourites_are_marked_in_menus_and_search() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let as_user = format!("as=user-{user_id}");
+
+    for _ in 0..2 {
+        let (status, body) = call(
+            &app,
+            Method::PUT,
+            &format!("/users/favourites/items/{veg_id}?{as_user}"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+    }
+    let (status, _) = call(
+        &app,
+        Method::PUT,
+        &format!("/users/favourites/items/99999?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(
+        &app,
+        Method::PUT,
+        &format!("/users/favourites/canteens/{canteen_id}?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = call(
+        &app,
+        Method::GET,
+        &format!("/canteen/{canteen_id}/items?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut flags = favourite_flags(&body["data"]);
+    flags.sort();
+    assert_eq!(flags, vec![(veg_id as i64, true), (wrap_id as i64, false)]);
+
+    let (status, body) = call(&app, Method::GET, &format!("/search?q=veg&{as_user}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        favourite_flags(&body["data"]["items"]),
+        vec![(veg_id as i64, true)]
+    );
+
+    let (status, body) = call(
+        &app,
+        Method::GET,
+        &format!("/users/favourites?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["items"][0]["item_id"], veg_id);
+    assert_eq!(body["data"]["canteens"][0]["canteen_id"], canteen_id);
+
+    let (status, _) = call(
+        &app,
+        Method::DELETE,
+        &format!("/users/favourites/items/{veg_id}?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(
+        &app,
+        Method::DELETE,
+        &format!("/users/favourites/items/{veg_id}?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = call(
+        &app,
+        Method::GET,
+        &format!("/canteen/{canteen_id}/items?{as_user}"),
+        None,
+    )
+    .await;
+    assert!(favourite_flags(&body["data"]).iter().all(|(_, fav)| !fav));
+}
+
+#[actix_rt::test]
+async fn saved_carts_can_be_held() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let as_user = format!("as=user-{user_id}");
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let other_canteen =
+        insert_canteen(conn.connection(), "Other Canteen", "Block B").expect("second canteen");
+    let tea_id = seed_menu_item(
+        conn.connection(),
+        other_canteen,
+        "Tea",
+        15,
+        -1,
+        true,
+        true,
+        None,
+    )
+    .expect("seed tea");
+    let other_user = insert_user(
+        conn.connection(),
+        "test-user-2",
+        "user2@example.com",
+        "User Two",
+        None,
+    )
+    .expect("second user");
+
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        &format!("/users/carts?{as_user}"),
+        Some(json!({ "name": "  Tuesday   lunch ", "item_ids": [veg_id, wrap_id, veg_id] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let cart = &body["data"];
+    let cart_id = cart["cart_id"].as_i64().expect("cart_id");
+    assert_eq!(cart["name"], "Tuesday lunch");
+    assert_eq!(cart["canteen_id"], canteen_id);
+    assert_eq!(cart["total_price"], 2 * 120 + 180);
+    assert_eq!(cart["lines"][1]["item_id"], veg_id);
+    assert_eq!(cart["lines"][1]["quantity"], 2);
+
+    for invalid in [
+        json!({ "name": "tuesday LUNCH", "item_ids": [veg_id] }),
+        json!({ "name": " ", "item_ids": [veg_id] }),
+        json!({ "name": "Empty", "item_ids": [] }),
+        json!({ "name": "Mixed", "item_ids": [veg_id, tea_id] }),
+        json!({ "name": "Missing", "item_ids": [99999] }),
+    ] {
+        let (status, body) = call(
+            &app,
+            Method::POST,
+            &format!("/users/carts?{as_user}"),
+            Some(invalid),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    let (status, body) = call(&app, Method::GET, &format!("/users/carts?{as_user}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    let (_, body) = call(
+        &app,
+        Method::GET,
+        &format!("/users/carts?as=user-{other_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(body["data"], json!([]));
+
+    // The cart is the input of a hold.
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        &format!("/orders/hold?{as_user}"),
+        Some(json!({ "saved_cart_id": cart_id })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["total_price"], 2 * 120 + 180);
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &format!("/orders/hold?as=user-{other_user}"),
+        Some(json!({ "saved_cart_id": cart_id })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &format!("/orders/hold?{as_user}"),
+        Some(json!({ "saved_cart_id": cart_id, "item_ids": [veg_id] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(
+        &app,
+        Method::PUT,
+        &format!("/users/carts/{cart_id}?{as_user}"),
+        Some(json!({ "name": "Tea break", "item_ids": [tea_id] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["canteen_id"], other_canteen);
+    assert_eq!(body["data"]["total_price"], 15);
+    let (status, _) = call(
+        &app,
+        Method::PUT,
+        &format!("/users/carts/{cart_id}?as=user-{other_user}"),
+        Some(json!({ "name": "Mine now", "item_ids": [tea_id] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call(
+        &app,
+        Method::DELETE,
+        &format!("/users/carts/{cart_id}?{as_user}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &format!("/orders/hold?{as_user}"),
+        Some(json!({ "saved_cart_id": cart_id })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
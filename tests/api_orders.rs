@@ -6,28 +6,9 @@ use actix_web::test;
 use common::auth_header;
 use diesel::prelude::*;
 use proj_xs::db::{DbConnection, OrderOperations};
-use proj_xs::test_utils::{build_test_pool, insert_canteen, seed_menu_item};
+use proj_xs::test_utils::build_test_pool;
 use serde_json::Value;
 
-fn seed_other_canteen_item(db_url: &str) -> (i32, i32) {
-    let pool = build_test_pool(db_url);
-    let mut conn = DbConnection::new(&pool).expect("db connection");
-    let other_canteen_id =
-        insert_canteen(conn.connection(), "Other Canteen", "Block Z").expect("insert canteen");
-    let other_item_id = seed_menu_item(
-        conn.connection(),
-        other_canteen_id,
-        "Other Canteen Item",
-        199,
-        8,
-        true,
-        true,
-        Some("owned by another canteen"),
-    )
-    .expect("insert menu item");
-    (other_canteen_id, other_item_id)
-}
-
 #[actix_rt::test]
 async fn put_order_actions_and_invalid_action() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
@@ -176,7 +157,7 @@ async fn get_order_by_id_other_canteen_returns_null() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
     let pool = build_test_pool(&db_url);
     let order_ops = OrderOperations::new(pool.clone()).await;
-    let (other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     order_ops
         .create_order(fixtures.user_id, vec![other_item_id], None)
@@ -308,7 +289,7 @@ async fn order_actions_other_canteen_order_conflict_and_order_remains() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
     let pool = build_test_pool(&db_url);
     let order_ops = OrderOperations::new(pool.clone()).await;
-    let (other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     order_ops
         .create_order(fixtures.user_id, vec![other_item_id], None)
@@ -590,7 +571,7 @@ async fn get_orders_by_user_admin_scoped_to_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
     let pool = build_test_pool(&db_url);
     let order_ops = OrderOperations::new(pool.clone()).await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     order_ops
         .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
@@ -619,7 +600,7 @@ async fn get_orders_by_rfid_admin_scoped_to_canteen() {
     let (app, fixtures, db_url) = common::setup_api_app().await;
     let pool = build_test_pool(&db_url);
     let order_ops = OrderOperations::new(pool.clone()).await;
-    let (_other_canteen_id, other_item_id) = seed_other_canteen_item(&db_url);
+    let (_other_canteen_id, other_item_id) = common::seed_other_canteen_item(&db_url);
 
     order_ops
         .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
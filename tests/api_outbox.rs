@@ -0,0 +1,155 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::schema::outbox;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::json;
+use std::time::Duration;
+
+fn outbox_count(conn: &mut PgConnection) -> i64 {
+    outbox::table
+        .filter(outbox::failed_at.is_null())
+        .count()
+        .get_result(conn)
+        .expect("count outbox")
+}
+
+#[actix_rt::test]
+async fn side_effects_commit_with_the_state_change_and_are_delivered() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+
+    // A hold that fails writes nothing to the outbox.
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "deliver_at": null, "item_ids": [i32::MAX] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert_eq!(outbox_count(conn.connection()), 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/users/events/orders?as=user-{}",
+            fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut user_stream = resp.into_body();
+    let _connected = common::wait_for_connected_event(&mut user_stream).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "deliver_at": null, "item_ids": [fixtures.menu_item_ids[0]] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let hold_id = body["hold_id"].as_i64().expect("hold_id");
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/orders/hold/{hold_id}/confirm?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let order_id = body["order_id"].as_i64().expect("order_id");
+
+    let event = common::wait_for_sse_event(&mut user_stream, "user_order_update").await;
+    let payload = common::sse_frame_data_json(&event);
+    assert_eq!(payload["order_id"], order_id);
+    assert_eq!(payload["status"], "placed");
+
+    // Delivered entries are removed.
+    let mut remaining = outbox_count(conn.connection());
+    for _ in 0..40 {
+        if remaining == 0 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        remaining = outbox_count(conn.connection());
+    }
+    assert_eq!(remaining, 0);
+}
+
+#[actix_rt::test]
+async fn entries_left_behind_are_delivered_by_the_next_dispatcher() {
+    std::env::set_var("OUTBOX_POLL_SECS", "1");
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    std::env::remove_var("OUTBOX_POLL_SECS");
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/users/events/orders?as=user-{}",
+            fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut user_stream = resp.into_body();
+    let _connected = common::wait_for_connected_event(&mut user_stream).await;
+
+    // As if an instance committed these and went down before delivering them.
+    diesel::insert_into(outbox::table)
+        .values(vec![
+            (
+                outbox::topic.eq("user_event"),
+                outbox::payload.eq(json!({
+                    "topic": "user_event",
+                    "user_id": fixtures.user_id,
+                    "event": { "order_id": 42, "status": "delivered" }
+                })
+                .to_string()),
+            ),
+            (
+                outbox::topic.eq("user_event"),
+                outbox::payload.eq("{\"topic\":\"user_event\"".to_string()),
+            ),
+        ])
+        .execute(conn.connection())
+        .expect("insert outbox entries");
+
+    let event = common::wait_for_sse_event(&mut user_stream, "user_order_update").await;
+    let payload = common::sse_frame_data_json(&event);
+    assert_eq!(payload["order_id"], 42);
+    assert_eq!(payload["status"], "delivered");
+
+    // The unreadable entry is given up on and kept for inspection.
+    let mut failed: Vec<(String, Option<String>)> = Vec::new();
+    for _ in 0..40 {
+        failed = outbox::table
+            .filter(outbox::failed_at.is_not_null())
+            .select((outbox::payload, outbox::last_error))
+            .load(conn.connection())
+            .expect("failed entries");
+        if !failed.is_empty() {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0]
+        .1
+        .as_deref()
+        .is_some_and(|e| e.starts_with("unreadable payload")));
+    assert_eq!(outbox_count(conn.connection()), 0);
+}
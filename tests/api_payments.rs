@@ -11,19 +11,6 @@ use sha2::{Digest, Sha256};
 use wiremock::matchers::{method, path, path_regex};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
-fn configure_phonepe_mock_env(base_url: &str) {
-    std::env::set_var("PHONEPE_AUTH_BASE_URL", base_url);
-    std::env::set_var("PHONEPE_PG_BASE_URL", base_url);
-    std::env::set_var(
-        "PHONEPE_WEB_REDIRECT_URL",
-        "https://pwa.example/phonepe-return",
-    );
-    std::env::set_var(
-        "PHONEPE_WEB_PAYMENT_URL_TEMPLATE",
-        "https://pwa.example/pay?merchantOrderId={merchant_order_id_urlencoded}&orderId={order_id_urlencoded}&token={token_urlencoded}",
-    );
-}
-
 fn webhook_hash_header_value() -> String {
     let mut hasher = Sha256::new();
     hasher.update(b"test-phonepe-webhook-user:test-phonepe-webhook-password");
@@ -68,7 +55,7 @@ async fn create_hold(
 #[actix_rt::test]
 async fn payments_initiate_success_and_reuse_existing_mapping() {
     let mock_server = MockServer::start().await;
-    configure_phonepe_mock_env(&mock_server.uri());
+    common::configure_phonepe_mock_env(&mock_server.uri());
     mock_phonepe_oauth(&mock_server).await;
 
     Mock::given(method("POST"))
@@ -139,7 +126,7 @@ async fn payments_initiate_success_and_reuse_existing_mapping() {
 #[actix_rt::test]
 async fn payments_initiate_web_route_uses_checkout_pay() {
     let mock_server = MockServer::start().await;
-    configure_phonepe_mock_env(&mock_server.uri());
+    common::configure_phonepe_mock_env(&mock_server.uri());
     mock_phonepe_oauth(&mock_server).await;
 
     Mock::given(method("POST"))
@@ -183,7 +170,7 @@ async fn payments_initiate_web_route_uses_checkout_pay() {
 #[actix_rt::test]
 async fn payments_verify_completed_confirms_hold_and_creates_order() {
     let mock_server = MockServer::start().await;
-    configure_phonepe_mock_env(&mock_server.uri());
+    common::configure_phonepe_mock_env(&mock_server.uri());
     mock_phonepe_oauth(&mock_server).await;
 
     Mock::given(method("POST"))
@@ -253,7 +240,7 @@ async fn payments_verify_completed_confirms_hold_and_creates_order() {
 #[actix_rt::test]
 async fn payments_verify_pending_and_failed_paths() {
     let mock_server = MockServer::start().await;
-    configure_phonepe_mock_env(&mock_server.uri());
+    common::configure_phonepe_mock_env(&mock_server.uri());
     mock_phonepe_oauth(&mock_server).await;
 
     Mock::given(method("POST"))
@@ -343,10 +330,115 @@ async fn payments_verify_pending_and_failed_paths() {
     assert_eq!(common::held_orders_count(conn.connection()), 0);
 }
 
+#[actix_rt::test]
+async fn payments_verify_failed_after_completion_sends_no_payment_update() {
+    let mock_server = MockServer::start().await;
+    common::configure_phonepe_mock_env(&mock_server.uri());
+    mock_phonepe_oauth(&mock_server).await;
+
+    Mock::given(method("POST"))
+        .and(path("/checkout/v2/sdk/order"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "orderId": "OLATEFAIL1",
+            "token": "sdk_token_late_fail",
+            "merchantId": "MERCHANT_ID_TEST"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // First verify => completed, second verify => failed
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/checkout/v2/order/.+/status$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "state": "COMPLETED"
+        })))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/checkout/v2/order/.+/status$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "state": "FAILED"
+        })))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let mut stream = common::open_user_stream(&app, fixtures.user_id, None).await;
+    let hold_id = create_hold(&app, fixtures.user_id, fixtures.menu_item_ids[0]).await;
+
+    let initiate_req = test::TestRequest::post()
+        .uri(&format!(
+            "/payments/initiate/app?as=user-{}",
+            fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "hold_id": hold_id,
+            "amount": 120
+        }))
+        .to_request();
+    let initiate_resp = test::call_service(&app, initiate_req).await;
+    assert_eq!(initiate_resp.status(), StatusCode::OK);
+    let initiate_body: Value = test::read_body_json(initiate_resp).await;
+    let merchant_order_id = initiate_body["merchant_order_id"]
+        .as_str()
+        .expect("merchant_order_id")
+        .to_string();
+
+    let verify = || {
+        test::TestRequest::post()
+            .uri(&format!(
+                "/payments/verify/{}?as=user-{}",
+                hold_id, fixtures.user_id
+            ))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(serde_json::json!({
+                "merchant_order_id": merchant_order_id
+            }))
+            .to_request()
+    };
+    let verify_resp = test::call_service(&app, verify()).await;
+    assert_eq!(verify_resp.status(), StatusCode::OK);
+    let update = common::wait_for_sse_event(&mut stream, "payment_update").await;
+    assert_eq!(
+        common::sse_frame_data_json(&update)["payment_state"],
+        "COMPLETED"
+    );
+
+    let verify_failed_resp = test::call_service(&app, verify()).await;
+    assert_eq!(verify_failed_resp.status(), StatusCode::CONFLICT);
+    let failed_body: Value = test::read_body_json(verify_failed_resp).await;
+    assert_eq!(failed_body["payment_state"], "FAILED");
+
+    // The completed payment is not marked failed, so no update goes out for it.
+    let late_update = tokio::time::timeout(
+        std::time::Duration::from_secs(1),
+        common::wait_for_sse_event(&mut stream, "payment_update"),
+    )
+    .await;
+    assert!(late_update.is_err());
+
+    use diesel::prelude::*;
+    use proj_xs::db::schema::payment_orders::dsl as po;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db conn");
+    let state: String = po::payment_orders
+        .filter(po::merchant_order_id.eq(&merchant_order_id))
+        .select(po::payment_state)
+        .first(conn.connection())
+        .expect("payment mapping");
+    assert_eq!(state, "COMPLETED");
+    assert_eq!(common::active_orders_count(conn.connection()), 1);
+}
+
 #[actix_rt::test]
 async fn payments_webhook_auth_and_idempotency() {
     let mock_server = MockServer::start().await;
-    configure_phonepe_mock_env(&mock_server.uri());
+    common::configure_phonepe_mock_env(&mock_server.uri());
     mock_phonepe_oauth(&mock_server).await;
 
     Mock::given(method("POST"))
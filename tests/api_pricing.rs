@@ -0,0 +1,154 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use serde_json::Value;
+
+#[actix_rt::test]
+async fn pricing_rules_crud_and_hold_response() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let veg_item = fixtures.menu_item_ids[0];
+    let non_veg_item = fixtures.menu_item_ids[1];
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/pricing/rules?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "name": "Lunch combo",
+            "kind": "combo",
+            "combo_price": 250,
+            "item_ids": [veg_item, non_veg_item]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let rule_id = body["data"]["rule_id"].as_i64().expect("rule_id");
+    assert_eq!(body["data"]["is_active"], true);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "deliver_at": null,
+            "item_ids": [veg_item, non_veg_item]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["total_price"], 250);
+    assert_eq!(body["applied_rules"][0]["rule_id"], rule_id);
+    assert_eq!(body["applied_rules"][0]["kind"], "combo");
+    assert_eq!(body["applied_rules"][0]["discount"], 50);
+
+    // Switch the rule off; the next hold pays menu price.
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/pricing/rules/{}?as=admin-{}",
+            rule_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "name": "Lunch combo",
+            "kind": "combo",
+            "combo_price": 250,
+            "item_ids": [veg_item, non_veg_item],
+            "is_active": false
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "deliver_at": null,
+            "item_ids": [non_veg_item]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["total_price"], 180);
+    assert_eq!(body["applied_rules"].as_array().unwrap().len(), 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/pricing/rules?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["is_active"], false);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/pricing/rules/{}?as=admin-{}",
+            rule_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/pricing/rules/{}?as=admin-{}",
+            rule_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn pricing_rule_validation_errors() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    for (payload, expected) in [
+        (
+            serde_json::json!({ "name": "Half off", "kind": "percent_off", "percent_off": 150 }),
+            "percent_off must be between 1 and 100",
+        ),
+        (
+            serde_json::json!({ "name": "Flat", "kind": "flat_off", "percent_off": 10 }),
+            "percent_off is not allowed for this rule kind",
+        ),
+        (
+            serde_json::json!({ "name": "Solo combo", "kind": "combo", "combo_price": 10,
+                                "item_ids": [fixtures.menu_item_ids[0]] }),
+            "a combo must have between 2 and 10 items",
+        ),
+        (
+            serde_json::json!({ "name": "Elsewhere", "kind": "flat_off", "amount_off": 10,
+                                "item_ids": [999999] }),
+            "item 999999 does not belong to this canteen",
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/pricing/rules?as=admin-{}", fixtures.canteen_id))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(payload)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], expected);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/pricing/rules?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use serde_json::Value;
+
+#[actix_rt::test]
+async fn promo_codes_crud_and_hold_redemption() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let non_veg_item = fixtures.menu_item_ids[1];
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/promo/codes?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "code": "wrap20",
+            "percent_off": 20,
+            "max_discount": 30,
+            "min_order_total": 100,
+            "max_uses_total": 1
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let promo_id = body["data"]["promo_id"].as_i64().expect("promo_id");
+    assert_eq!(body["data"]["code"], "WRAP20");
+    assert_eq!(body["data"]["canteen_id"], fixtures.canteen_id);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "deliver_at": null,
+            "item_ids": [non_veg_item],
+            "promo_code": "Wrap20"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["total_price"], 180 - 30);
+    assert_eq!(body["applied_promo"]["code"], "WRAP20");
+    assert_eq!(body["applied_promo"]["discount"], 30);
+
+    // The only allowed use is taken by the pending hold.
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "deliver_at": null,
+            "item_ids": [non_veg_item],
+            "promo_code": "WRAP20"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("has reached its usage limit"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/promo/codes?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["times_redeemed"], 1);
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/promo/codes/{}?as=admin-{}",
+            promo_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "code": "WRAP20",
+            "percent_off": 20,
+            "is_active": false
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["is_active"], false);
+    assert_eq!(body["data"]["max_uses_total"], Value::Null);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/promo/codes/{}?as=admin-{}",
+            promo_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/promo/codes/{}?as=admin-{}",
+            promo_id, fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn platform_wide_codes_need_a_platform_operator() {
+    let (_app, fixtures, db_url) = common::setup_api_app().await;
+    // Operators are read once, when the app is built.
+    std::env::set_var("PLATFORM_OPERATOR_USER_IDS", fixtures.user_id.to_string());
+    let app = common::init_api_app(&db_url).await;
+    std::env::remove_var("PLATFORM_OPERATOR_USER_IDS");
+    let payload = serde_json::json!({
+        "code": "WELCOME",
+        "amount_off": 25,
+        "first_order_only": true,
+        "platform_wide": true
+    });
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/promo/codes?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/promo/codes?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["canteen_id"], Value::Null);
+
+    // Duplicate codes are rejected regardless of case.
+    let req = test::TestRequest::post()
+        .uri(&format!("/promo/codes?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "code": "welcome", "percent_off": 5 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "promo code WELCOME already exists");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "deliver_at": null,
+            "item_ids": [fixtures.menu_item_ids[0]],
+            "promo_code": "WELCOME"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["total_price"], 120 - 25);
+}
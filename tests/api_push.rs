@@ -0,0 +1,206 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::{DbConnection, OrderOperations};
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::matchers::{body_partial_json, header as header_eq, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const SEND_PATH: &str = "/v1/projects/test-project/messages:send";
+
+fn configure_fcm_mock_env(base_url: &str) {
+    std::env::set_var("FCM_ENABLED", "true");
+    std::env::set_var("FCM_PROJECT_ID", "test-project");
+    std::env::set_var("FCM_ACCESS_TOKEN", "fcm_token_test");
+    std::env::set_var("FCM_BASE_URL", base_url);
+    std::env::set_var("PUSH_RETRY_BASE_MS", "10");
+}
+
+fn clear_fcm_mock_env() {
+    for key in [
+        "FCM_ENABLED",
+        "FCM_PROJECT_ID",
+        "FCM_ACCESS_TOKEN",
+        "FCM_BASE_URL",
+        "PUSH_RETRY_BASE_MS",
+    ] {
+        std::env::remove_var(key);
+    }
+}
+
+fn sends_to(requests: &[Request], token: &str) -> Vec<Value> {
+    requests
+        .iter()
+        .filter(|r| r.url.path() == SEND_PATH)
+        .map(|r| serde_json::from_slice::<Value>(&r.body).expect("JSON push"))
+        .filter(|body| body["message"]["token"] == token)
+        .collect()
+}
+
+#[actix_rt::test]
+async fn order_updates_are_pushed_to_registered_devices() {
+    let server = MockServer::start().await;
+    configure_fcm_mock_env(&server.uri());
+    // The first push to the phone hits a transient failure and is retried.
+    Mock::given(method("POST"))
+        .and(path(SEND_PATH))
+        .and(body_partial_json(
+            json!({ "message": { "token": "phone-token" } }),
+        ))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(SEND_PATH))
+        .and(header_eq("authorization", "Bearer fcm_token_test"))
+        .and(body_partial_json(
+            json!({ "message": { "token": "phone-token" } }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "m/1" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(SEND_PATH))
+        .and(body_partial_json(
+            json!({ "message": { "token": "stale-token" } }),
+        ))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "error": { "status": "NOT_FOUND", "details": [{ "errorCode": "UNREGISTERED" }] }
+        })))
+        .mount(&server)
+        .await;
+
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    clear_fcm_mock_env();
+
+    for (token, platform) in [("phone-token", "android"), ("stale-token", "ios")] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/users/devices?as=user-{}", fixtures.user_id))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(json!({ "token": token, "platform": platform }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"]["token"], token);
+        assert_eq!(body["data"]["user_id"], fixtures.user_id);
+    }
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    OrderOperations::new(pool.clone())
+        .await
+        .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
+        .expect("create order");
+    use proj_xs::db::schema::active_orders::dsl as ao;
+    let order_id: i32 = ao::active_orders
+        .select(ao::order_id)
+        .order(ao::order_id.desc())
+        .first(conn.connection())
+        .expect("latest order id");
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/delivered?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    use proj_xs::db::schema::device_tokens::dsl as dt;
+    use proj_xs::db::schema::outbox::dsl as ob;
+    let mut remaining: Vec<String> = Vec::new();
+    let mut pending_pushes = -1;
+    for _ in 0..100 {
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        let requests = server.received_requests().await.unwrap_or_default();
+        remaining = dt::device_tokens
+            .select(dt::token)
+            .load(conn.connection())
+            .expect("device tokens");
+        pending_pushes = ob::outbox
+            .filter(ob::topic.eq("device_push"))
+            .count()
+            .get_result::<i64>(conn.connection())
+            .expect("pending pushes");
+        if sends_to(&requests, "phone-token").len() >= 2
+            && remaining.len() == 1
+            && pending_pushes == 0
+        {
+            break;
+        }
+    }
+
+    let requests = server.received_requests().await.unwrap_or_default();
+    let phone_pushes = sends_to(&requests, "phone-token");
+    assert_eq!(phone_pushes.len(), 2, "one failed attempt and one retry");
+    let message = &phone_pushes[1]["message"];
+    assert_eq!(message["notification"]["title"], "Order delivered");
+    assert_eq!(message["data"]["event"], "user_order_update");
+    assert_eq!(message["data"]["order_id"], order_id.to_string());
+    assert_eq!(message["data"]["status"], "delivered");
+    assert_eq!(sends_to(&requests, "stale-token").len(), 1);
+    assert_eq!(remaining, vec!["phone-token".to_string()]);
+    assert_eq!(
+        pending_pushes, 0,
+        "pushes leave the outbox once FCM accepts them"
+    );
+}
+
+#[actix_rt::test]
+async fn devices_can_be_registered_and_unregistered() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/devices?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "token": "tablet-token", "platform": "windows" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "platform must be one of android, ios, web");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/devices?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "token": "tablet-token", "platform": "Web" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["platform"], "web");
+
+    // Only the owner can unregister the device.
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/users/devices/tablet-token?as=user-{}",
+            fixtures.user_id + 1
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/users/devices/tablet-token?as=user-{}",
+            fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::error::PayloadError;
+use actix_http::ws::{Codec, Frame, Message};
+use actix_http::{BoxedPayloadStream, Payload, Request};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::Error;
+use common::auth_header;
+use futures::channel::mpsc;
+use proj_xs::db::{DbConnection, OrderOperations};
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Client end of a realtime socket opened on the test service.
+struct TestSocket {
+    tx: mpsc::UnboundedSender<Result<Bytes, PayloadError>>,
+    body: BoxBody,
+    codec: Codec,
+    buf: BytesMut,
+}
+
+impl TestSocket {
+    async fn open(
+        app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+        principal: &str,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded::<Result<Bytes, PayloadError>>();
+        let req = test::TestRequest::get()
+            .uri(&format!("/realtime?as={principal}"))
+            .insert_header(auth_header())
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_request();
+        let payload: BoxedPayloadStream = Box::pin(rx);
+        let (req, _) = req.replace_payload(Payload::from(payload));
+        let resp = test::call_service(app, req).await;
+        assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        let mut socket = Self {
+            tx,
+            body: resp.into_body(),
+            codec: Codec::new().client_mode(),
+            buf: BytesMut::new(),
+        };
+        assert_eq!(socket.recv().await["type"], "connected");
+        socket
+    }
+
+    fn send(&mut self, command: Value) {
+        let mut frame = BytesMut::new();
+        self.codec
+            .encode(Message::Text(command.to_string().into()), &mut frame)
+            .expect("encode frame");
+        self.tx
+            .unbounded_send(Ok(frame.freeze()))
+            .expect("socket open");
+    }
+
+    /// The next JSON message, skipping pings.
+    async fn recv(&mut self) -> Value {
+        loop {
+            if let Some(frame) = self.codec.decode(&mut self.buf).expect("valid frame") {
+                match frame {
+                    Frame::Text(text) => {
+                        return serde_json::from_slice(&text).expect("JSON message");
+                    }
+                    Frame::Ping(_) => continue,
+                    other => panic!("unexpected frame {other:?}"),
+                }
+            }
+            let body = &mut self.body;
+            let chunk = actix_rt::time::timeout(
+                Duration::from_secs(3),
+                poll_fn(|cx| Pin::new(&mut *body).poll_next(cx)),
+            )
+            .await
+            .expect("timed out waiting for a frame")
+            .expect("socket closed")
+            .expect("body chunk");
+            self.buf.extend_from_slice(&chunk);
+        }
+    }
+
+    async fn request(&mut self, command: Value) -> Value {
+        self.send(command);
+        self.recv().await
+    }
+}
+
+#[actix_rt::test]
+async fn socket_multiplexes_streams_and_runs_order_actions() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let mut admin = TestSocket::open(&app, &format!("admin-{}", fixtures.canteen_id)).await;
+
+    let reply = admin
+        .request(json!({ "type": "subscribe", "channel": "canteen_orders", "ref": "a" }))
+        .await;
+    assert_eq!(reply, json!({ "type": "ok", "ref": "a" }));
+    let reply = admin
+        .request(json!({
+            "type": "subscribe",
+            "channel": "canteen_inventory",
+            "id": fixtures.canteen_id
+        }))
+        .await;
+    assert_eq!(reply["type"], "ok");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "deliver_at": null, "item_ids": [fixtures.menu_item_ids[0]] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let event = admin.recv().await;
+    assert_eq!(event["type"], "event");
+    assert_eq!(event["channel"], "canteen_inventory");
+    assert_eq!(event["id"], fixtures.canteen_id);
+    assert_eq!(event["seq"], 1);
+    assert_eq!(event["event"], "inventory_update");
+    assert_eq!(
+        event["data"]["items"][0]["item_id"],
+        fixtures.menu_item_ids[0]
+    );
+    let reply = admin
+        .request(json!({
+            "type": "ack",
+            "channel": "canteen_inventory",
+            "id": fixtures.canteen_id,
+            "seq": 1
+        }))
+        .await;
+    assert_eq!(reply["type"], "ok");
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    OrderOperations::new(pool.clone())
+        .await
+        .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[1]], None)
+        .expect("create order");
+    use diesel::prelude::*;
+    use proj_xs::db::schema::active_orders::dsl as ao;
+    let order_id: i32 = ao::active_orders
+        .select(ao::order_id)
+        .order(ao::order_id.desc())
+        .first(conn.connection())
+        .expect("latest order id");
+
+    let mut user = TestSocket::open(&app, &format!("user-{}", fixtures.user_id)).await;
+    let reply = user
+        .request(json!({ "type": "subscribe", "channel": "user_orders" }))
+        .await;
+    assert_eq!(reply["type"], "ok");
+
+    let reply = admin
+        .request(json!({
+            "type": "order_action",
+            "order_id": order_id,
+            "action": "delivered",
+            "ref": "deliver"
+        }))
+        .await;
+    assert_eq!(reply, json!({ "type": "ok", "ref": "deliver" }));
+    let update = user.recv().await;
+    assert_eq!(update["channel"], "user_orders");
+    assert_eq!(update["event"], "user_order_update");
+    assert_eq!(update["data"]["order_id"], order_id);
+    assert_eq!(update["data"]["status"], "delivered");
+}
+
+#[actix_rt::test]
+async fn socket_commands_are_checked_against_the_principal() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let mut user = TestSocket::open(&app, &format!("user-{}", fixtures.user_id)).await;
+
+    let reply = user
+        .request(json!({ "type": "subscribe", "channel": "canteen_orders", "ref": "x" }))
+        .await;
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["ref"], "x");
+    assert_eq!(
+        reply["error"],
+        "canteen_orders is only available to canteen admins"
+    );
+
+    let reply = user
+        .request(json!({
+            "type": "subscribe",
+            "channel": "user_orders",
+            "id": fixtures.user_id + 1
+        }))
+        .await;
+    assert_eq!(
+        reply["error"],
+        "cannot subscribe to another account's stream"
+    );
+
+    let reply = user
+        .request(json!({ "type": "order_action", "order_id": 1, "action": "delivered" }))
+        .await;
+    assert_eq!(
+        reply["error"],
+        "order actions are only available to canteen admins"
+    );
+
+    let reply = user.request(json!({ "type": "shout" })).await;
+    assert_eq!(reply["type"], "error");
+    assert!(reply["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid command"));
+
+    // A plain GET is not upgraded.
+    let req = test::TestRequest::get()
+        .uri(&format!("/realtime?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
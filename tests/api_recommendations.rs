@@ -0,0 +1,173 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::{build_test_pool, insert_user, seed_menu_item};
+use serde_json::{json, Value};
+
+fn item_ids(items: &Value) -> Vec<i64> {
+    items
+        .as_array()
+        .expect("items")
+        .iter()
+        .filter_map(|item| item["item_id"].as_i64())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn recommendations_follow_delivered_orders() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let chai_id = seed_menu_item(
+        conn.connection(),
+        canteen_id,
+        "Masala Chai",
+        20,
+        50,
+        true,
+        true,
+        None,
+    )
+    .expect("seed chai");
+    let other_user = insert_user(
+        conn.connection(),
+        "test-user-2",
+        "user2@example.com",
+        "User Two",
+        None,
+    )
+    .expect("second user");
+
+    common::complete_order(&app, user_id, canteen_id, &[veg_id, veg_id], "delivered").await;
+    common::complete_order(&app, user_id, canteen_id, &[wrap_id], "delivered").await;
+    common::complete_order(&app, user_id, canteen_id, &[veg_id, veg_id], "delivered").await;
+    // Cancelled orders count for nothing.
+    common::complete_order(&app, user_id, canteen_id, &[wrap_id, wrap_id], "cancelled").await;
+    common::complete_order(
+        &app,
+        other_user,
+        canteen_id,
+        &[veg_id, chai_id],
+        "delivered",
+    )
+    .await;
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/users/recommendations?as=user-{user_id}&canteen_id={canteen_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let data = &body["data"];
+    assert_eq!(
+        item_ids(&data["most_ordered"]),
+        vec![veg_id as i64, wrap_id as i64]
+    );
+    assert_eq!(item_ids(&data["also_ordered"]), vec![chai_id as i64]);
+    assert_eq!(item_ids(&data["popular_now"])[0], veg_id as i64);
+
+    // Without a canteen there is nothing to be popular at.
+    let (_, body) =
+        common::get_json(&app, &format!("/users/recommendations?as=user-{user_id}")).await;
+    assert_eq!(body["data"]["popular_now"], json!([]));
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/users/recommendations/popular/{canteen_id}?as=user-{other_user}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(item_ids(&body["data"]).len(), 3);
+    assert_eq!(item_ids(&body["data"])[0], veg_id as i64);
+
+    // Unavailable items are not recommended.
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(chai_id)))
+            .set(is_available.eq(false))
+            .execute(conn.connection())
+            .expect("switch off chai");
+    }
+    let (_, body) =
+        common::get_json(&app, &format!("/users/recommendations?as=user-{user_id}")).await;
+    assert_eq!(body["data"]["also_ordered"], json!([]));
+}
+
+#[actix_rt::test]
+async fn usual_order_is_a_ready_hold_request() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/users/recommendations/usual?as=user-{user_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["status"], "error");
+
+    common::complete_order(&app, user_id, canteen_id, &[veg_id, wrap_id], "delivered").await;
+    common::complete_order(&app, user_id, canteen_id, &[veg_id], "delivered").await;
+    common::complete_order(&app, user_id, canteen_id, &[wrap_id, veg_id], "delivered").await;
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/users/recommendations/usual?as=user-{user_id}&canteen_id={canteen_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let usual = &body["data"];
+    assert_eq!(usual["times_ordered"], 2);
+    assert_eq!(usual["total_price"], 120 + 180);
+    assert_eq!(usual["unavailable_item_ids"], json!([]));
+    let mut hold_items = usual["hold_request"]["item_ids"]
+        .as_array()
+        .expect("item_ids")
+        .clone();
+    hold_items.sort_by_key(|id| id.as_i64());
+    assert_eq!(hold_items, vec![json!(veg_id), json!(wrap_id)]);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{user_id}"))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(&usual["hold_request"])
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(wrap_id)))
+            .set(is_available.eq(false))
+            .execute(conn.connection())
+            .expect("switch off wrap");
+    }
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/users/recommendations/usual?as=user-{user_id}"),
+    )
+    .await;
+    let usual = &body["data"];
+    assert_eq!(usual["hold_request"]["item_ids"], json!([veg_id]));
+    assert_eq!(usual["unavailable_item_ids"], json!([wrap_id]));
+    assert_eq!(usual["total_price"], 120);
+
+    // Orders from another canteen are not considered.
+    let (status, _) = common::get_json(
+        &app,
+        &format!("/users/recommendations/usual?as=user-{user_id}&canteen_id=99999"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{test, Error};
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+
+async fn reorder<S>(
+    app: &S,
+    user_id: i32,
+    order_id: i64,
+    body: Option<Value>,
+) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let mut req = test::TestRequest::post()
+        .uri(&format!("/orders/reorder/{order_id}?as=user-{user_id}"))
+        .insert_header(auth_header());
+    if let Some(body) = body {
+        req = req
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(body);
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+#[actix_rt::test]
+async fn reorder_holds_past_items_at_current_prices() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        canteen_id,
+        &[veg_id, veg_id, wrap_id],
+        "delivered",
+    )
+    .await;
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(veg_id)))
+            .set(price.eq(150))
+            .execute(conn.connection())
+            .expect("raise price");
+    }
+
+    let (status, body) = reorder(&app, user_id, order_id, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert!(body["hold_id"].is_number());
+    assert_eq!(body["original_price"], 2 * 120 + 180);
+    assert_eq!(body["total_price"], 2 * 150 + 180);
+    assert_eq!(body["price_difference"], 60);
+    assert_eq!(body["unavailable_items"], json!([]));
+
+    // The hold reserved the same items again.
+    let held = {
+        use proj_xs::db::schema::held_order_items::dsl::*;
+        held_order_items
+            .filter(hold_id.eq(body["hold_id"].as_i64().unwrap() as i32))
+            .select((item_id, quantity))
+            .order_by(item_id)
+            .load::<(i32, i16)>(conn.connection())
+            .expect("held items")
+    };
+    assert_eq!(held, vec![(veg_id, 2), (wrap_id, 1)]);
+
+    // Orders finished before item prices were kept have nothing to compare with.
+    {
+        use proj_xs::db::schema::past_order_items;
+        diesel::delete(
+            past_order_items::table.filter(past_order_items::order_id.eq(order_id as i32)),
+        )
+        .execute(conn.connection())
+        .expect("forget item prices");
+    }
+    let (status, body) = reorder(&app, user_id, order_id, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["original_price"].is_null());
+    assert!(body["price_difference"].is_null());
+}
+
+#[actix_rt::test]
+async fn reorder_leaves_out_unavailable_items() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let order_id =
+        common::complete_order(&app, user_id, canteen_id, &[veg_id, wrap_id], "delivered").await;
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(wrap_id)))
+            .set(is_available.eq(false))
+            .execute(conn.connection())
+            .expect("switch off wrap");
+    }
+
+    let (status, body) = reorder(
+        &app,
+        user_id,
+        order_id,
+        Some(json!({ "deliver_at": "11:00am - 12:00pm", "note": "less spicy" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total_price"], 120);
+    // Only the veg sandwich is compared; the wrap is listed instead.
+    assert_eq!(body["original_price"], 120);
+    assert_eq!(body["price_difference"], 0);
+    assert_eq!(body["unavailable_items"][0]["item_id"], wrap_id);
+    assert_eq!(body["unavailable_items"][0]["name"], "Chicken Wrap");
+    assert_eq!(body["unavailable_items"][0]["reason"], "Not available");
+
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(veg_id)))
+            .set(stock.eq(0))
+            .execute(conn.connection())
+            .expect("sell out veg");
+    }
+    let (status, body) = reorder(&app, user_id, order_id, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["status"], "error");
+    assert!(body["hold_id"].is_null());
+    assert_eq!(body["unavailable_items"].as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn reorder_holds_what_is_left_of_short_items() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        canteen_id,
+        &[veg_id, veg_id, veg_id, wrap_id],
+        "delivered",
+    )
+    .await;
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(veg_id)))
+            .set(stock.eq(2))
+            .execute(conn.connection())
+            .expect("lower veg stock");
+    }
+
+    let (status, body) = reorder(&app, user_id, order_id, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total_price"], 2 * 120 + 180);
+    assert_eq!(body["original_price"], 2 * 120 + 180);
+    assert_eq!(body["price_difference"], 0);
+    assert_eq!(
+        body["reduced_items"],
+        json!([{
+            "item_id": veg_id,
+            "name": "Veg Sandwich",
+            "ordered_quantity": 3,
+            "held_quantity": 2
+        }])
+    );
+    assert_eq!(body["unavailable_items"], json!([]));
+    assert_eq!(common::menu_item_state(conn.connection(), veg_id).0, 0);
+
+    // The first reorder took the last two, so the veg sandwich is left out instead.
+    let (status, body) = reorder(&app, user_id, order_id, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total_price"], 180);
+    assert_eq!(body["reduced_items"], json!([]));
+    assert_eq!(body["unavailable_items"][0]["item_id"], veg_id);
+}
+
+#[actix_rt::test]
+async fn reorder_only_own_orders_and_validates_input() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        canteen_id,
+        &[fixtures.menu_item_ids[0]],
+        "delivered",
+    )
+    .await;
+
+    let (status, _) = reorder(&app, user_id + 1000, order_id, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = reorder(&app, user_id, order_id + 1000, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = reorder(
+        &app,
+        user_id,
+        order_id,
+        Some(json!({ "deliver_at": "midnight" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Invalid time band: midnight");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/reorder/{order_id}?as=admin-{canteen_id}"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
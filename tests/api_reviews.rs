@@ -0,0 +1,282 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{test, Error};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+
+async fn review<S>(app: &S, user_id: i32, order_id: i64, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    common::send_json(
+        app,
+        test::TestRequest::post().uri(&format!(
+            "/users/orders/{order_id}/review?as=user-{user_id}"
+        )),
+        body,
+    )
+    .await
+}
+
+/// The menu item with `id` as listed to users.
+async fn listed_item<S>(app: &S, user_id: i32, canteen_id: i32, id: i32) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let (status, body) = common::get_json(
+        app,
+        &format!("/canteen/{canteen_id}/items?as=user-{user_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["item_id"] == id)
+        .cloned()
+        .expect("item on the menu")
+}
+
+/// The canteen with `id` as listed by `GET /canteen`.
+async fn listed_canteen<S>(app: &S, id: i32) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let (status, body) = common::get_json(app, "/canteen").await;
+    assert_eq!(status, StatusCode::OK);
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|canteen| canteen["canteen_id"] == id)
+        .cloned()
+        .expect("canteen listed")
+}
+
+#[actix_rt::test]
+async fn reviewing_a_delivered_order_updates_average_ratings() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let first =
+        common::complete_order(&app, user_id, canteen_id, &[veg_id, wrap_id], "delivered").await;
+    let second = common::complete_order(&app, user_id, canteen_id, &[veg_id], "delivered").await;
+
+    let (status, body) = review(
+        &app,
+        user_id,
+        first,
+        json!({
+            "rating": 4,
+            "comment": "  Hot and quick  ",
+            "items": [{ "item_id": veg_id, "rating": 5 }, { "item_id": wrap_id, "rating": 2 }]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["order_id"], first);
+    assert_eq!(body["data"]["canteen_id"], canteen_id);
+    assert_eq!(body["data"]["comment"], "Hot and quick");
+    assert_eq!(body["data"]["items"][0]["item_id"], veg_id);
+    assert_eq!(body["data"]["items"][0]["rating"], 5);
+    assert!(body["data"]["items"][0]["name"].is_string());
+
+    let (status, _) = review(
+        &app,
+        user_id,
+        second,
+        json!({ "rating": 1, "items": [{ "item_id": veg_id, "rating": 4 }] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let veg = listed_item(&app, user_id, canteen_id, veg_id).await;
+    assert_eq!(veg["average_rating"], 4.5);
+    assert_eq!(veg["rating_count"], 2);
+    let wrap = listed_item(&app, user_id, canteen_id, wrap_id).await;
+    assert_eq!(wrap["average_rating"], 2.0);
+    assert_eq!(wrap["rating_count"], 1);
+    let canteen = listed_canteen(&app, canteen_id).await;
+    assert_eq!(canteen["average_rating"], 2.5);
+    assert_eq!(canteen["rating_count"], 2);
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/users/orders/{first}/review?as=user-{user_id}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["rating"], 4);
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn each_order_can_be_reviewed_once() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        canteen_id,
+        &[fixtures.menu_item_ids[0]],
+        "delivered",
+    )
+    .await;
+
+    let (status, _) = review(&app, user_id, order_id, json!({ "rating": 5 })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = review(&app, user_id, order_id, json!({ "rating": 1 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("already been reviewed"));
+
+    let canteen = listed_canteen(&app, canteen_id).await;
+    assert_eq!(canteen["average_rating"], 5.0);
+    assert_eq!(canteen["rating_count"], 1);
+}
+
+#[actix_rt::test]
+async fn only_the_users_recent_delivered_orders_can_be_reviewed() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let cancelled = common::complete_order(&app, user_id, canteen_id, &[veg_id], "cancelled").await;
+    let delivered = common::complete_order(&app, user_id, canteen_id, &[veg_id], "delivered").await;
+
+    let (status, body) = review(&app, user_id, cancelled, json!({ "rating": 3 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "only delivered orders can be reviewed");
+
+    // Another user's order is not found.
+    let (status, _) = review(&app, user_id + 1000, delivered, json!({ "rating": 3 })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    for invalid in [
+        json!({ "rating": 0 }),
+        json!({ "rating": 6 }),
+        json!({ "rating": 3, "items": [{ "item_id": wrap_id, "rating": 3 }] }),
+        json!({ "rating": 3, "comment": "x".repeat(1001) }),
+    ] {
+        let (status, _) = review(&app, user_id, delivered, invalid).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::past_orders::dsl::*;
+        diesel::update(past_orders.filter(order_id.eq(delivered as i32)))
+            .set(ordered_at.eq(Utc::now() - Duration::days(8)))
+            .execute(conn.connection())
+            .expect("age the order");
+    }
+    let (status, body) = review(&app, user_id, delivered, json!({ "rating": 3 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "orders can only be reviewed within 7 days");
+}
+
+#[actix_rt::test]
+async fn admins_hide_and_reply_to_reviews_of_their_canteen() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let veg_id = fixtures.menu_item_ids[0];
+    let mut review_ids = Vec::new();
+    for rating in [5, 1] {
+        let order_id =
+            common::complete_order(&app, user_id, canteen_id, &[veg_id], "delivered").await;
+        let (status, body) = review(
+            &app,
+            user_id,
+            order_id,
+            json!({ "rating": rating, "items": [{ "item_id": veg_id, "rating": rating }] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        review_ids.push(body["data"]["review_id"].as_i64().unwrap());
+    }
+    let abusive = review_ids[1];
+
+    // Another canteen's admin cannot moderate it.
+    let (status, _) = common::send_json(
+        &app,
+        test::TestRequest::put().uri(&format!(
+            "/canteen/reviews/{abusive}/moderation?as=admin-{}",
+            canteen_id + 1000
+        )),
+        json!({ "is_hidden": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = common::send_json(
+        &app,
+        test::TestRequest::put().uri(&format!(
+            "/canteen/reviews/{abusive}/moderation?as=admin-{canteen_id}"
+        )),
+        json!({ "is_hidden": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["is_hidden"], true);
+
+    // Hidden reviews no longer count and are only listed for the admin.
+    let veg = listed_item(&app, user_id, canteen_id, veg_id).await;
+    assert_eq!(veg["average_rating"], 5.0);
+    assert_eq!(veg["rating_count"], 1);
+    let canteen = listed_canteen(&app, canteen_id).await;
+    assert_eq!(canteen["average_rating"], 5.0);
+    assert_eq!(canteen["rating_count"], 1);
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/canteen/{canteen_id}/reviews?as=user-{user_id}"),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/canteen/{canteen_id}/reviews?as=admin-{canteen_id}"),
+    )
+    .await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    assert_eq!(body["data"][0]["review_id"], abusive);
+
+    let reply_uri = format!(
+        "/canteen/reviews/{}/reply?as=admin-{canteen_id}",
+        review_ids[0]
+    );
+    let (status, body) = common::send_json(
+        &app,
+        test::TestRequest::put().uri(&reply_uri),
+        json!({ "reply": "Thank you!" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["reply"], "Thank you!");
+    assert!(body["data"]["replied_at"].is_string());
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/canteen/{canteen_id}/reviews?as=user-{user_id}"),
+    )
+    .await;
+    assert_eq!(body["data"][0]["reply"], "Thank you!");
+
+    let (status, body) = common::send_json(
+        &app,
+        test::TestRequest::put().uri(&reply_uri),
+        json!({ "reply": "  " }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"]["reply"].is_null());
+    assert!(body["data"]["replied_at"].is_null());
+}
@@ -161,3 +161,120 @@ async fn search_by_canteen_unauthenticated() {
         .to_request();
     common::assert_unauthenticated(&app, req).await;
 }
+
+#[actix_rt::test]
+async fn search_menu_filters_pages_and_counts_facets() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::get()
+        .uri("/search?q=wrap&veg=false&available_only=true&per_page=5")
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "ok");
+    let data = &body["data"];
+    assert_eq!(data["total"], 1);
+    assert_eq!(
+        (data["page"].as_i64(), data["per_page"].as_i64()),
+        (Some(1), Some(5))
+    );
+    assert_eq!(data["items"][0]["item_id"], fixtures.menu_item_ids[1]);
+    assert_eq!(data["facets"]["non_veg"], 1);
+    assert_eq!(
+        data["facets"]["canteens"][0]["canteen_name"],
+        "Test Canteen"
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/search?min_price=300&max_price=100")
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "error");
+    assert!(body["data"].is_null());
+
+    let req = test::TestRequest::get().uri("/search?q=wrap").to_request();
+    common::assert_unauthenticated(&app, req).await;
+}
+
+#[actix_rt::test]
+async fn suggest_completes_names_and_popular_searches() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let (status, body) = common::get_json(&app, "/search/suggest?q=SAND").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["items"], serde_json::json!(["Veg Sandwich"]));
+    assert_eq!(body["data"]["canteens"], serde_json::json!([]));
+
+    let (_, body) = common::get_json(&app, "/search/suggest?q=test").await;
+    assert_eq!(
+        body["data"]["canteens"][0]["canteen_id"],
+        fixtures.canteen_id
+    );
+
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/search/suggest?q=test&canteen_id={}", fixtures.canteen_id),
+    )
+    .await;
+    assert_eq!(body["data"]["canteens"], serde_json::json!([]));
+
+    // A search is only suggested once enough people have made it and it found something.
+    for _ in 0..3 {
+        common::get_json(&app, "/search?q=Veg%20%20SANDWICH").await;
+        common::get_json(&app, "/search/vegan%20pizza").await;
+    }
+    let (_, body) = common::get_json(&app, "/search/suggest?q=veg").await;
+    assert_eq!(body["data"]["queries"], serde_json::json!(["veg sandwich"]));
+    // Wildcards in the typed text are matched literally.
+    let (_, body) = common::get_json(&app, "/search/suggest?q=v_g").await;
+    assert_eq!(body["data"]["queries"], serde_json::json!([]));
+
+    let (status, body) = common::get_json(&app, "/search/suggest?q=%20").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["status"], "error");
+}
+
+#[actix_rt::test]
+async fn search_insights_show_popular_and_unanswered_searches() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    for uri in [
+        "/search?q=wrap".to_string(),
+        "/search?q=Wrap&page=2".to_string(),
+        "/search/wrap".to_string(),
+        format!("/search/{}/biryani", fixtures.canteen_id),
+        "/search?q=biryani&canteen_id=99999".to_string(),
+    ] {
+        let (status, _) = common::get_json(&app, &uri).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+    }
+
+    let (status, body) = common::get_json(
+        &app,
+        &format!("/search/insights?as=admin-{}", fixtures.canteen_id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let insights = &body["data"];
+    assert_eq!(insights["popular"][0]["query"], "wrap");
+    assert_eq!(insights["popular"][0]["search_count"], 2);
+    // The search in a canteen that does not exist still worked but was not logged.
+    assert_eq!(insights["no_results"].as_array().map(Vec::len), Some(1));
+    assert_eq!(insights["no_results"][0]["query"], "biryani");
+    assert_eq!(insights["no_results"][0]["no_result_count"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/search/insights?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .to_request();
+    let status = match test::try_call_service(&app, req).await {
+        Ok(resp) => resp.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
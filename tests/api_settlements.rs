@@ -0,0 +1,376 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, Error};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use common::auth_header;
+use diesel::prelude::*;
+use diesel::sql_types::{Int4, Timestamptz, Varchar};
+use proj_xs::db::{DbConnection, PaymentOperations, SettlementOperations};
+use proj_xs::services::canteen_hours::parse_tz_offset_from_env;
+use proj_xs::services::settlement::SettlementConfig;
+use proj_xs::test_utils::{build_test_pool, insert_canteen};
+use serde_json::{json, Value};
+
+async fn call_json<S>(app: &S, req: test::TestRequest) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let resp = test::call_service(app, req.insert_header(auth_header()).to_request()).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+fn payout(settlement_id: i64, principal: &str, reference: &str) -> test::TestRequest {
+    test::TestRequest::put()
+        .uri(&format!(
+            "/settlements/{settlement_id}/payout?as={principal}"
+        ))
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "reference": reference }))
+}
+
+/// Noon of the business day `days_ago` days before today, in the canteen time zone.
+fn noon(days_ago: i64) -> (NaiveDate, DateTime<Utc>) {
+    let tz = parse_tz_offset_from_env();
+    let day = Utc::now().with_timezone(&tz).date_naive() - Duration::days(days_ago);
+    let at = (day.and_hms_opt(12, 0, 0).unwrap() - Duration::seconds(tz.local_minus_utc() as i64))
+        .and_utc();
+    (day, at)
+}
+
+fn insert_payment(
+    conn: &mut PgConnection,
+    hold_id: i32,
+    user_id: i32,
+    canteen_id: i32,
+    amount: i32,
+    state: &str,
+    updated_at: DateTime<Utc>,
+) -> i32 {
+    diesel::sql_query(
+        "INSERT INTO payment_orders (hold_id, user_id, merchant_order_id, phonepe_order_id, \
+                                     sdk_token, amount, payment_state, canteen_id, created_at, \
+                                     updated_at) \
+         VALUES ($1, $2, 'MO-' || $1, 'OMO-' || $1, 'token', $3, $4, $5, $6, $6) \
+         RETURNING payment_id",
+    )
+    .bind::<Int4, _>(hold_id)
+    .bind::<Int4, _>(user_id)
+    .bind::<Int4, _>(amount)
+    .bind::<Varchar, _>(state)
+    .bind::<Int4, _>(canteen_id)
+    .bind::<Timestamptz, _>(updated_at)
+    .get_result::<PaymentId>(conn)
+    .expect("insert payment")
+    .payment_id
+}
+
+#[derive(QueryableByName)]
+struct PaymentId {
+    #[diesel(sql_type = Int4)]
+    payment_id: i32,
+}
+
+fn config(commission_bps: i32) -> SettlementConfig {
+    SettlementConfig {
+        commission_bps,
+        grace_secs: 3600,
+        lookback_days: 7,
+        poll_secs: 60,
+    }
+}
+
+#[actix_rt::test]
+async fn daily_statements_are_generated_once_and_paid_out_once() {
+    let (_app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    // Operators are read once, when the app is built.
+    std::env::set_var("PLATFORM_OPERATOR_USER_IDS", user_id.to_string());
+    let app = common::init_api_app(&db_url).await;
+    std::env::remove_var("PLATFORM_OPERATOR_USER_IDS");
+    let (admin, operator) = (format!("admin-{canteen_id}"), format!("user-{user_id}"));
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let other_canteen_id =
+        insert_canteen(conn.connection(), "Other Canteen", "Block B").expect("insert canteen");
+    let (day, at) = noon(2);
+
+    let paid = insert_payment(
+        conn.connection(),
+        9001,
+        user_id,
+        canteen_id,
+        30000,
+        "COMPLETED",
+        at,
+    );
+    insert_payment(
+        conn.connection(),
+        9002,
+        user_id,
+        canteen_id,
+        12000,
+        "COMPLETED",
+        at,
+    );
+    insert_payment(
+        conn.connection(),
+        9003,
+        user_id,
+        canteen_id,
+        5000,
+        "FAILED",
+        at,
+    );
+    insert_payment(
+        conn.connection(),
+        9004,
+        user_id,
+        other_canteen_id,
+        10000,
+        "COMPLETED",
+        at,
+    );
+    // Today's payments wait for today's statement.
+    insert_payment(
+        conn.connection(),
+        9005,
+        user_id,
+        canteen_id,
+        7000,
+        "COMPLETED",
+        Utc::now(),
+    );
+    diesel::sql_query(
+        "INSERT INTO refunds (payment_id, canteen_id, merchant_refund_id, amount, state, updated_at) \
+         VALUES ($1, $2, 'MR-1', 2000, 'COMPLETED', $3), ($1, $2, 'MR-2', 500, 'FAILED', $3)",
+    )
+    .bind::<Int4, _>(paid)
+    .bind::<Int4, _>(canteen_id)
+    .bind::<Timestamptz, _>(at)
+    .execute(conn.connection())
+    .expect("insert refunds");
+
+    let settlement_ops = SettlementOperations::new(pool.clone()).await;
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(250), Utc::now())
+            .unwrap(),
+        2
+    );
+
+    let (status, body) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!("/settlements?as={admin}")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let statements = body["data"]["statements"].as_array().unwrap();
+    assert_eq!(statements.len(), 1);
+    let statement = &statements[0];
+    assert_eq!(statement["canteen_id"], canteen_id);
+    assert_eq!(statement["business_date"], day.to_string());
+    assert_eq!(statement["payment_count"], 2);
+    assert_eq!(statement["gross"], 42000);
+    assert_eq!(statement["refund_count"], 1);
+    assert_eq!(statement["refunds"], 2000);
+    assert_eq!(statement["commission_bps"], 250);
+    assert_eq!(statement["commission"], 1000);
+    assert_eq!(statement["net_payable"], 39000);
+    assert_eq!(statement["paid_at"], Value::Null);
+    assert_eq!(body["data"]["totals"]["outstanding"], 39000);
+    let settlement_id = statement["settlement_id"].as_i64().unwrap();
+
+    // Statements are frozen: late payments and a new commission do not change them.
+    insert_payment(
+        conn.connection(),
+        9006,
+        user_id,
+        canteen_id,
+        9000,
+        "COMPLETED",
+        at,
+    );
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(500), Utc::now())
+            .unwrap(),
+        0
+    );
+    assert!(diesel::sql_query("UPDATE settlements SET gross = 1")
+        .execute(conn.connection())
+        .is_err());
+    let (_, body) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!("/settlements?as={admin}")),
+    )
+    .await;
+    assert_eq!(body["data"]["statements"][0]["gross"], 42000);
+    assert_eq!(body["data"]["statements"][0]["commission"], 1000);
+
+    // Only platform operators record payouts and see other canteens.
+    let (status, _) = call_json(&app, payout(settlement_id, &admin, "UTR123")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!(
+            "/settlements?canteen_id={other_canteen_id}&as={admin}"
+        )),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!("/settlements?as={operator}")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call_json(&app, payout(settlement_id, &operator, "  ")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call_json(&app, payout(settlement_id + 1000, &operator, "UTR123")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = call_json(&app, payout(settlement_id, &operator, " UTR123 ")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["payout_reference"], "UTR123");
+    assert!(body["data"]["paid_at"].is_string());
+    let (status, body) = call_json(&app, payout(settlement_id, &operator, "UTR456")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["error"],
+        format!("settlement {settlement_id} has already been paid out")
+    );
+    assert!(
+        diesel::sql_query("UPDATE settlements SET payout_reference = 'UTR789'")
+            .execute(conn.connection())
+            .is_err()
+    );
+
+    let (status, body) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!(
+            "/settlements?canteen_id={canteen_id}&as={operator}"
+        )),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["statements"][0]["payout_reference"], "UTR123");
+    assert_eq!(body["data"]["totals"]["net_payable"], 39000);
+    assert_eq!(body["data"]["totals"]["outstanding"], 0);
+}
+
+#[actix_rt::test]
+async fn statements_wait_for_the_day_and_grace_period_to_end() {
+    let (_app, fixtures, db_url) = common::setup_api_app().await;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let (_, yesterday) = noon(1);
+    insert_payment(
+        conn.connection(),
+        9001,
+        fixtures.user_id,
+        fixtures.canteen_id,
+        12000,
+        "COMPLETED",
+        yesterday,
+    );
+
+    let settlement_ops = SettlementOperations::new(pool.clone()).await;
+    // Half an hour after midnight the grace period of an hour has not passed yet.
+    let tz = parse_tz_offset_from_env();
+    let just_after_midnight = (yesterday.with_timezone(&tz).date_naive() + Duration::days(1))
+        .and_hms_opt(0, 30, 0)
+        .unwrap()
+        - Duration::seconds(tz.local_minus_utc() as i64);
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(0), just_after_midnight.and_utc())
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        settlement_ops
+            .generate_statements(
+                &config(0),
+                just_after_midnight.and_utc() + Duration::hours(1)
+            )
+            .unwrap(),
+        1
+    );
+}
+
+#[actix_rt::test]
+async fn payments_completed_after_their_days_statement_are_paid_out_with_the_next() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let (yesterday, at) = noon(1);
+    insert_payment(
+        conn.connection(),
+        9001,
+        user_id,
+        canteen_id,
+        12000,
+        "COMPLETED",
+        at,
+    );
+    insert_payment(
+        conn.connection(),
+        9002,
+        user_id,
+        canteen_id,
+        5000,
+        "PENDING",
+        at,
+    );
+
+    let settlement_ops = SettlementOperations::new(pool.clone()).await;
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(0), Utc::now())
+            .unwrap(),
+        1
+    );
+
+    // PhonePe completes the payment started yesterday only after yesterday's statement.
+    PaymentOperations::new(pool.clone())
+        .await
+        .update_mapping_state("MO-9002", "COMPLETED", None)
+        .expect("complete payment");
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(0), Utc::now())
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        settlement_ops
+            .generate_statements(&config(0), Utc::now() + Duration::days(1))
+            .unwrap(),
+        1
+    );
+
+    let (status, body) = call_json(
+        &app,
+        test::TestRequest::get().uri(&format!("/settlements?as=admin-{canteen_id}")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let statements = body["data"]["statements"].as_array().unwrap();
+    assert_eq!(statements.len(), 2);
+    assert_eq!(
+        statements[0]["business_date"],
+        (yesterday + Duration::days(1)).to_string()
+    );
+    assert_eq!(statements[0]["payment_count"], 1);
+    assert_eq!(statements[0]["gross"], 5000);
+    assert_eq!(statements[1]["business_date"], yesterday.to_string());
+    assert_eq!(statements[1]["payment_count"], 1);
+    assert_eq!(statements[1]["gross"], 12000);
+    assert_eq!(body["data"]["totals"]["gross"], 17000);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use actix_web::Error;
+use common::auth_header;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use proj_xs::db::{DbConnection, OrderOperations};
+use proj_xs::test_utils::{build_test_pool, TestFixtures};
+use std::time::Duration;
+
+#[actix_rt::test]
+async fn postgres_backend_delivers_events_to_every_instance() {
+    std::env::set_var("SSE_BROKER_BACKEND", "postgres");
+    let (app_a, fixtures, db_url) = common::setup_api_app().await;
+    let app_b = common::init_api_app(&db_url).await;
+    std::env::remove_var("SSE_BROKER_BACKEND");
+    // Both listeners connect in the background.
+    actix_rt::time::sleep(Duration::from_millis(500)).await;
+
+    let mut streams = [
+        common::open_user_stream(&app_a, fixtures.user_id, None).await,
+        common::open_user_stream(&app_b, fixtures.user_id, None).await,
+    ];
+
+    let pool = build_test_pool(&db_url);
+    let order_id = deliver_new_order(&app_a, &pool, &fixtures).await;
+    for stream in streams.iter_mut() {
+        let update = common::wait_for_sse_event(stream, "user_order_update").await;
+        assert_eq!(update.id.as_deref(), Some("1"));
+        let payload = common::sse_frame_data_json(&update);
+        assert_eq!(payload["order_id"], order_id);
+        assert_eq!(payload["status"], "delivered");
+    }
+
+    // An instance started after the first event still numbers the next one like the others.
+    std::env::set_var("SSE_BROKER_BACKEND", "postgres");
+    let app_c = common::init_api_app(&db_url).await;
+    std::env::remove_var("SSE_BROKER_BACKEND");
+    actix_rt::time::sleep(Duration::from_millis(500)).await;
+    let mut late_stream = common::open_user_stream(&app_c, fixtures.user_id, None).await;
+
+    let order_id = deliver_new_order(&app_b, &pool, &fixtures).await;
+    for stream in streams.iter_mut().chain(std::iter::once(&mut late_stream)) {
+        let update = common::wait_for_sse_event(stream, "user_order_update").await;
+        assert_eq!(update.id.as_deref(), Some("2"));
+        assert_eq!(common::sse_frame_data_json(&update)["order_id"], order_id);
+    }
+}
+
+/// Places an order and marks it delivered through `app`, returning its id.
+async fn deliver_new_order(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    fixtures: &TestFixtures,
+) -> i32 {
+    let mut conn = DbConnection::new(pool).expect("db connection");
+    OrderOperations::new(pool.clone())
+        .await
+        .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
+        .expect("create order");
+    use proj_xs::db::schema::active_orders::dsl as ao;
+    let order_id: i32 = ao::active_orders
+        .select(ao::order_id)
+        .order(ao::order_id.desc())
+        .first(conn.connection())
+        .expect("latest order id");
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/delivered?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    order_id
+}
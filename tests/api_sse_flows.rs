@@ -12,8 +12,8 @@ use serde_json::Value;
 fn assert_numeric_sse_id(frame: &common::SseFrame) {
     let id = frame.id.as_deref().expect("SSE event id should be present");
     assert!(
-        id.parse::<u128>().is_ok(),
-        "SSE id should be numeric millis"
+        id.parse::<u64>().is_ok(),
+        "SSE id should be the numeric stream sequence"
     );
 }
 
@@ -0,0 +1,137 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use actix_web::Error;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::{DbConnection, OrderOperations};
+use proj_xs::test_utils::build_test_pool;
+use std::time::Duration;
+
+async fn mark_latest_order(
+    app: &impl Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+    conn: &mut PgConnection,
+    canteen_id: i32,
+    action: &str,
+) -> i32 {
+    use proj_xs::db::schema::active_orders::dsl as ao;
+    let order_id: i32 = ao::active_orders
+        .select(ao::order_id)
+        .order(ao::order_id.desc())
+        .first(conn)
+        .expect("latest order id");
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/{action}?as=admin-{canteen_id}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    order_id
+}
+
+#[actix_rt::test]
+async fn user_stream_replays_events_missed_while_disconnected() {
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let pool = build_test_pool(&db_url);
+    let order_ops = OrderOperations::new(pool.clone()).await;
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+
+    let mut stream = common::open_user_stream(&app, fixtures.user_id, None).await;
+    order_ops
+        .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
+        .expect("create order");
+    mark_latest_order(&app, conn.connection(), fixtures.canteen_id, "delivered").await;
+    let delivered = common::wait_for_sse_event(&mut stream, "user_order_update").await;
+    assert_eq!(delivered.id.as_deref(), Some("1"));
+    drop(stream);
+
+    // The phone is offline while the second order is cancelled.
+    order_ops
+        .create_order(fixtures.user_id, vec![fixtures.menu_item_ids[1]], None)
+        .expect("create order");
+    let cancelled_order_id =
+        mark_latest_order(&app, conn.connection(), fixtures.canteen_id, "cancelled").await;
+
+    let mut stream = common::open_user_stream(&app, fixtures.user_id, Some("1")).await;
+    let replayed = common::wait_for_sse_event(&mut stream, "user_order_update").await;
+    assert_eq!(replayed.id.as_deref(), Some("2"));
+    let payload = common::sse_frame_data_json(&replayed);
+    assert_eq!(payload["order_id"], cancelled_order_id);
+    assert_eq!(payload["status"], "cancelled");
+}
+
+#[actix_rt::test]
+async fn unknown_last_event_id_asks_the_client_to_resync() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/menu/events/inventory/{}?as=user-{}",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .insert_header(("Last-Event-ID", "999"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut stream = resp.into_body();
+    let _retry = common::read_sse_frame(&mut stream).await;
+    let _connected = common::wait_for_connected_event(&mut stream).await;
+    let resync = common::read_sse_frame(&mut stream).await;
+    assert_eq!(resync.event.as_deref(), Some("status"));
+    assert_eq!(resync.data.as_deref(), Some("resync"));
+}
+
+#[actix_rt::test]
+async fn event_log_persists_the_most_recent_events_per_stream() {
+    std::env::set_var("SSE_EVENT_LOG_ENABLED", "true");
+    std::env::set_var("SSE_REPLAY_BUFFER_SIZE", "2");
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    std::env::remove_var("SSE_EVENT_LOG_ENABLED");
+    std::env::remove_var("SSE_REPLAY_BUFFER_SIZE");
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+
+    for _ in 0..3 {
+        let req = test::TestRequest::post()
+            .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(serde_json::json!({
+                "deliver_at": null,
+                "item_ids": [fixtures.menu_item_ids[0]]
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    // The log is written in the background.
+    use proj_xs::db::schema::sse_events::dsl as se;
+    let mut stored: Vec<(String, i32, i64)> = Vec::new();
+    for _ in 0..50 {
+        stored = se::sse_events
+            .select((se::stream_kind, se::stream_id, se::seq))
+            .order(se::seq.asc())
+            .load(conn.connection())
+            .expect("load sse events");
+        if stored.last().map(|row| row.2) == Some(3) && stored.len() == 2 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        stored,
+        vec![
+            ("canteen_inventory".to_string(), fixtures.canteen_id, 2),
+            ("canteen_inventory".to_string(), fixtures.canteen_id, 3),
+        ]
+    );
+}
@@ -0,0 +1,372 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{test, Error};
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::DbConnection;
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Open a ticket on `order_id` as the user; returns its ticket ID.
+async fn open_ticket<S>(app: &S, user_id: i32, order_id: i64) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let (status, body) = common::send_json(
+        app,
+        test::TestRequest::post().uri(&format!("/support/tickets?as=user-{user_id}")),
+        json!({ "order_id": order_id, "category": "missing_item", "message": "No raita" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["data"]["ticket"]["ticket_id"].as_i64().unwrap()
+}
+
+async fn post_message<S>(app: &S, who: &str, ticket_id: i64, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    common::send_json(
+        app,
+        test::TestRequest::post().uri(&format!("/support/tickets/{ticket_id}/messages?as={who}")),
+        body,
+    )
+    .await
+}
+
+async fn set_status<S>(app: &S, canteen_id: i32, ticket_id: i64, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    common::send_json(
+        app,
+        test::TestRequest::put().uri(&format!(
+            "/support/tickets/{ticket_id}/status?as=admin-{canteen_id}"
+        )),
+        body,
+    )
+    .await
+}
+
+#[actix_rt::test]
+async fn users_and_canteens_work_a_ticket_through_to_closed() {
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let (user_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let user = format!("user-{user_id}");
+    let admin = format!("admin-{canteen_id}");
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        canteen_id,
+        &[fixtures.menu_item_ids[0]],
+        "delivered",
+    )
+    .await;
+
+    let ticket_id = open_ticket(&app, user_id, order_id).await;
+    let (status, body) = common::send_json(
+        &app,
+        test::TestRequest::post().uri(&format!("/support/tickets?as={user}")),
+        json!({ "order_id": order_id, "category": "quality", "message": "Cold" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("already has an open ticket"));
+
+    // Other users and other canteens do not see it.
+    let (status, _) = common::get_json(
+        &app,
+        &format!("/support/tickets/{ticket_id}?as=user-{}", user_id + 1000),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/support/tickets?as=admin-{}", canteen_id + 1000),
+    )
+    .await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+
+    let (_, body) =
+        common::get_json(&app, &format!("/support/tickets?status=open&as={admin}")).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["category"], "missing_item");
+    let (status, _) =
+        common::get_json(&app, &format!("/support/tickets?status=lost&as={admin}")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = post_message(
+        &app,
+        &admin,
+        ticket_id,
+        json!({ "message": "Sorry! We will sort this out." }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["ticket"]["status"], "in_progress");
+
+    let (status, body) =
+        set_status(&app, canteen_id, ticket_id, json!({ "status": "resolved" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["ticket"]["status"], "resolved");
+    assert!(body["data"]["ticket"]["resolved_at"].is_string());
+
+    // The user is not satisfied, which reopens it.
+    let (status, body) = post_message(
+        &app,
+        &user,
+        ticket_id,
+        json!({ "message": "Still no raita" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["ticket"]["status"], "open");
+    assert!(body["data"]["ticket"]["resolved_at"].is_null());
+    let messages = body["data"]["messages"].as_array().unwrap();
+    let authors: Vec<&str> = messages
+        .iter()
+        .map(|m| m["author"].as_str().unwrap())
+        .collect();
+    assert_eq!(authors, ["user", "canteen", "user"]);
+
+    let (status, _) = set_status(&app, canteen_id, ticket_id, json!({ "status": "closed" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = post_message(&app, &user, ticket_id, json!({ "message": "Hello?" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "ticket is closed");
+
+    // With that one closed, the order can get a new ticket.
+    open_ticket(&app, user_id, order_id).await;
+}
+
+#[actix_rt::test]
+async fn attachments_must_be_uploaded_for_the_ticket() {
+    let mock_s3 = common::start_mock_s3().await;
+    let (app, fixtures, _) = common::setup_api_app().await;
+    let user_id = fixtures.user_id;
+    let user = format!("user-{user_id}");
+    let order_id = common::complete_order(
+        &app,
+        user_id,
+        fixtures.canteen_id,
+        &[fixtures.menu_item_ids[0]],
+        "delivered",
+    )
+    .await;
+    let ticket_id = open_ticket(&app, user_id, order_id).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/support/tickets/{ticket_id}/attachments?as={user}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let key = body["attachment_key"].as_str().unwrap().to_string();
+    assert!(key.starts_with(&format!("support/{ticket_id}/")));
+    assert!(body["upload_url"].as_str().unwrap().contains(&key));
+
+    // Not uploaded yet.
+    mock_s3.mock_object_not_found(&key).await;
+    let (status, body) = post_message(
+        &app,
+        &user,
+        ticket_id,
+        json!({ "message": "Photo", "attachments": [key] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("has not been uploaded"));
+
+    let foreign = format!("support/{}/photo", ticket_id + 1);
+    let (status, _) = post_message(
+        &app,
+        &user,
+        ticket_id,
+        json!({ "message": "Photo", "attachments": [foreign] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    mock_s3.server.reset().await;
+    mock_s3.mock_object_exists(&key).await;
+    let (status, body) = post_message(
+        &app,
+        &user,
+        ticket_id,
+        json!({ "message": "Photo", "attachments": [key] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let attachment = &body["data"]["messages"][1]["attachments"][0];
+    assert_eq!(attachment["key"], key);
+    assert!(attachment["url"].as_str().unwrap().contains(&key));
+}
+
+fn webhook_auth_header() -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"test-phonepe-webhook-user:test-phonepe-webhook-password");
+    format!("SHA256({})", hex::encode(hasher.finalize()))
+}
+
+#[actix_rt::test]
+async fn resolving_a_ticket_refunds_the_online_payment() {
+    let phonepe = MockServer::start().await;
+    common::configure_phonepe_mock_env(&phonepe.uri());
+    Mock::given(method("POST"))
+        .and(path("/v1/oauth/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "oauth_token_test",
+            "expires_at": 4_102_444_800i64
+        })))
+        .mount(&phonepe)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/payments/v2/refund"))
+        .and(body_partial_json(json!({
+            "originalMerchantOrderId": "MO_SUPPORT_1",
+            "amount": 5000
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "refundId": "OMR_1",
+            "amount": 5000,
+            "state": "PENDING"
+        })))
+        .expect(1)
+        .mount(&phonepe)
+        .await;
+
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+    let (buyer_id, canteen_id) = (fixtures.user_id, fixtures.canteen_id);
+    let order_id = common::complete_order(
+        &app,
+        buyer_id,
+        canteen_id,
+        &[fixtures.menu_item_ids[0]],
+        "delivered",
+    )
+    .await;
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::payment_orders::dsl::*;
+        diesel::insert_into(payment_orders)
+            .values((
+                hold_id.eq(order_id as i32),
+                user_id.eq(buyer_id),
+                merchant_order_id.eq("MO_SUPPORT_1"),
+                phonepe_order_id.eq("OMO_SUPPORT_1"),
+                sdk_token.eq("token"),
+                amount.eq(12000),
+                payment_state.eq("COMPLETED"),
+                app_order_id.eq(Some(order_id as i32)),
+            ))
+            .execute(conn.connection())
+            .expect("payment row");
+    }
+    let ticket_id = open_ticket(&app, buyer_id, order_id).await;
+
+    for (invalid, error) in [
+        (
+            json!({ "status": "in_progress", "refund_amount": 5000 }),
+            "refunds can only be issued when resolving a ticket",
+        ),
+        (
+            json!({ "status": "resolved", "refund_amount": 12001 }),
+            "at most 12000 paisa of the payment can still be refunded",
+        ),
+    ] {
+        let (status, body) = set_status(&app, canteen_id, ticket_id, invalid).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], error);
+    }
+
+    let (status, body) = set_status(
+        &app,
+        canteen_id,
+        ticket_id,
+        json!({ "status": "resolved", "refund_amount": 5000 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["data"]["refunds"][0]["amount"], 5000);
+    assert_eq!(body["data"]["refunds"][0]["state"], "PENDING");
+
+    use proj_xs::db::schema::refunds::dsl as rf;
+    let mut sent = None;
+    for _ in 0..100 {
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        sent = rf::refunds
+            .select((rf::merchant_refund_id, rf::phonepe_refund_id))
+            .first::<(String, Option<String>)>(conn.connection())
+            .ok()
+            .filter(|(_, phonepe_refund_id)| phonepe_refund_id.is_some());
+        if sent.is_some() {
+            break;
+        }
+    }
+    let (merchant_refund_id, phonepe_refund_id) = sent.expect("refund sent to PhonePe");
+    assert_eq!(phonepe_refund_id.as_deref(), Some("OMR_1"));
+
+    let req = test::TestRequest::post()
+        .uri("/payments/webhook")
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .insert_header((header::AUTHORIZATION, webhook_auth_header()))
+        .set_json(json!({
+            "event": "pg.refund.completed",
+            "payload": {
+                "merchantRefundId": merchant_refund_id,
+                "originalMerchantOrderId": "MO_SUPPORT_1",
+                "amount": 5000,
+                "state": "COMPLETED"
+            }
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (_, body) = common::get_json(
+        &app,
+        &format!("/support/tickets/{ticket_id}?as=user-{buyer_id}"),
+    )
+    .await;
+    assert_eq!(body["data"]["refunds"][0]["state"], "COMPLETED");
+
+    // Reopened, only what is left of the payment can be refunded.
+    let (status, _) = post_message(
+        &app,
+        &format!("user-{buyer_id}"),
+        ticket_id,
+        json!({ "message": "Half the order was missing" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = set_status(
+        &app,
+        canteen_id,
+        ticket_id,
+        json!({ "status": "resolved", "refund_amount": 7001 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["error"],
+        "at most 7000 paisa of the payment can still be refunded"
+    );
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use serde_json::json;
+
+fn content_type(resp: &ServiceResponse) -> String {
+    resp.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[actix_rt::test]
+async fn tickets_render_items_slot_and_note_in_every_format() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+    let order_id = common::place_order(
+        &app,
+        fixtures.user_id,
+        fixtures.canteen_id,
+        json!({
+            "deliver_at": "11:00am - 12:00pm",
+            "item_ids": [veg_id, wrap_id, veg_id],
+            "note": "  No onions, <extra> chutney  "
+        }),
+    )
+    .await;
+    let ticket_uri = format!("/orders/{order_id}/ticket?as=admin-{}", fixtures.canteen_id);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{ticket_uri}&format=text"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(content_type(&resp).starts_with("text/plain"));
+    let text = String::from_utf8(test::read_body(resp).await.to_vec()).expect("UTF-8 ticket");
+    for expected in [
+        format!("ORDER #{order_id}\nTest Canteen\n"),
+        "Slot: 11:00am - 12:00pm\nFor: User One\n".to_string(),
+        " 1 x Chicken Wrap\n 2 x Veg Sandwich\n".to_string(),
+        "NOTE: No onions, <extra> chutney\n".to_string(),
+    ] {
+        assert!(text.contains(&expected), "{expected:?} missing from {text}");
+    }
+    assert!(text.contains('\u{2588}'), "QR code missing from {text}");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{ticket_uri}&format=html"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(content_type(&resp).starts_with("text/html"));
+    let html = String::from_utf8(test::read_body(resp).await.to_vec()).expect("UTF-8 ticket");
+    assert!(html.contains(&format!("<h1>Order #{order_id}</h1>")));
+    assert!(html.contains("No onions, &lt;extra&gt; chutney"));
+    assert!(html.contains("<svg") && !html.contains("<?xml"));
+
+    // ESC/POS is the default.
+    let req = test::TestRequest::get()
+        .uri(&ticket_uri)
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(content_type(&resp), "application/octet-stream");
+    let escpos = test::read_body(resp).await.to_vec();
+    assert!(escpos.starts_with(&[0x1b, b'@']));
+    assert!(escpos.ends_with(&[0x1d, b'V', 66, 0]));
+    let contains = |needle: &[u8]| escpos.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(format!("ORDER #{order_id}\n").as_bytes()));
+    assert!(contains(b" 2 x Veg Sandwich\n"));
+    assert!(contains(b"NOTE: No onions, <extra> chutney\n"));
+    // The QR code, as a raster image.
+    assert!(contains(&[0x1d, b'v', b'0', 0]));
+}
+
+#[actix_rt::test]
+async fn tickets_are_only_for_the_canteens_active_orders() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({
+            "deliver_at": null,
+            "item_ids": [fixtures.menu_item_ids[0]],
+            "note": "x".repeat(201)
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let order_id = common::place_order(
+        &app,
+        fixtures.user_id,
+        fixtures.canteen_id,
+        json!({ "deliver_at": null, "item_ids": [fixtures.menu_item_ids[0]] }),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/orders/{order_id}/ticket?format=text&as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let text = String::from_utf8(test::read_body(resp).await.to_vec()).expect("UTF-8 ticket");
+    assert!(text.contains("Slot: Instant\n"));
+    assert!(!text.contains("NOTE"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/orders/{order_id}/ticket?format=pdf&as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    for uri in [
+        format!(
+            "/orders/{order_id}/ticket?as=admin-{}",
+            fixtures.canteen_id + 1000
+        ),
+        format!(
+            "/orders/{}/ticket?as=admin-{}",
+            order_id + 1000,
+            fixtures.canteen_id
+        ),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(auth_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+    }
+
+    // Delivered orders are no longer printable.
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/delivered?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/orders/{order_id}/ticket?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -91,3 +91,92 @@ async fn get_past_orders_unauthenticated() {
         .to_request();
     common::assert_unauthenticated(&app, req).await;
 }
+
+#[actix_rt::test]
+async fn allergen_preferences_flag_menu_and_search() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/menu/update?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({
+            "item_id": fixtures.menu_item_ids[0],
+            "update": { "allergens": ["Gluten", "dairy"], "diet_labels": ["jain"] }
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/users/preferences?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "allergen_exclusions": ["DAIRY"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["data"]["allergen_exclusions"],
+        serde_json::json!(["dairy"])
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/users/preferences?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["data"]["allergen_exclusions"],
+        serde_json::json!(["dairy"])
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/canteen/{}/items?as=user-{}",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    let items = body["data"].as_array().expect("items");
+    let sandwich = items
+        .iter()
+        .find(|i| i["item_id"] == fixtures.menu_item_ids[0])
+        .expect("sandwich");
+    assert_eq!(
+        sandwich["allergens"],
+        serde_json::json!(["gluten", "dairy"])
+    );
+    assert_eq!(sandwich["diet_labels"], serde_json::json!(["jain"]));
+    assert_eq!(sandwich["allergen_warnings"], serde_json::json!(["dairy"]));
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/search/{}/Veg%20Sandwich?as=user-{}&hide_allergens=true",
+            fixtures.canteen_id, fixtures.user_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["data"].as_array().expect("items").is_empty());
+}
+
+#[actix_rt::test]
+async fn allergen_preferences_reject_unknown_allergen() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/users/preferences?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
+        .set_json(serde_json::json!({ "allergen_exclusions": ["chocolate"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
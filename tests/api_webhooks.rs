@@ -0,0 +1,546 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use common::auth_header;
+use diesel::prelude::*;
+use proj_xs::db::schema::canteen_webhooks;
+use proj_xs::db::DbConnection;
+use proj_xs::models::admin::NewCanteenWebhook;
+use proj_xs::services::webhooks::{
+    webhook_signature, WEBHOOK_EVENT_HEADER, WEBHOOK_ID_HEADER, WEBHOOK_SIGNATURE_HEADER,
+    WEBHOOK_TIMESTAMP_HEADER,
+};
+use proj_xs::test_utils::build_test_pool;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const SECRET: &str = "pos-shared-secret-0001";
+
+fn header_value<'a>(request: &'a Request, name: &str) -> &'a str {
+    request
+        .headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+async fn wait_for_requests(server: &MockServer, hook_path: &str, count: usize) -> Vec<Request> {
+    for _ in 0..100 {
+        let requests = server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.url.path() == hook_path)
+            .collect::<Vec<_>>();
+        if requests.len() >= count {
+            return requests;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("expected {count} webhook requests to {hook_path}");
+}
+
+#[actix_rt::test]
+async fn order_and_stock_events_are_posted_signed_and_logged() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/pos"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    // The mock server listens on loopback.
+    std::env::set_var("WEBHOOK_ALLOW_PRIVATE_TARGETS", "true");
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    std::env::remove_var("WEBHOOK_ALLOW_PRIVATE_TARGETS");
+
+    // Subscribed to everything.
+    let req = test::TestRequest::post()
+        .uri(&format!("/webhooks?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "url": format!("{}/pos", server.uri()), "secret": SECRET }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let webhook_id = body["data"]["webhook_id"].as_i64().expect("webhook_id");
+    assert_eq!(body["data"]["events"].as_array().map(Vec::len), Some(4));
+    assert!(body["data"].get("secret").is_none());
+
+    // Takes the last five wraps.
+    let wrap_id = fixtures.menu_item_ids[1];
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "deliver_at": null, "item_ids": vec![wrap_id; 5] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let hold_id = body["hold_id"].as_i64().expect("hold_id");
+
+    let stock_out = wait_for_requests(&server, "/pos", 1).await.remove(0);
+    assert_eq!(
+        header_value(&stock_out, WEBHOOK_EVENT_HEADER),
+        "item.stock_out"
+    );
+    let payload: Value = serde_json::from_slice(&stock_out.body).expect("JSON payload");
+    assert_eq!(payload["type"], "item.stock_out");
+    assert_eq!(payload["canteen_id"], fixtures.canteen_id);
+    assert_eq!(
+        payload["data"],
+        json!({ "item_id": wrap_id, "name": "Chicken Wrap" })
+    );
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/orders/hold/{hold_id}/confirm?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let order_id = body["order_id"].as_i64().expect("order_id");
+
+    let confirmed = wait_for_requests(&server, "/pos", 2).await.remove(1);
+    let timestamp = header_value(&confirmed, WEBHOOK_TIMESTAMP_HEADER);
+    let body = std::str::from_utf8(&confirmed.body).expect("UTF-8 body");
+    assert_eq!(
+        header_value(&confirmed, WEBHOOK_SIGNATURE_HEADER),
+        webhook_signature(SECRET, timestamp.parse().expect("timestamp"), body)
+    );
+    assert_eq!(
+        header_value(&confirmed, WEBHOOK_EVENT_HEADER),
+        "order.confirmed"
+    );
+    let payload: Value = serde_json::from_str(body).expect("JSON payload");
+    assert_eq!(header_value(&confirmed, WEBHOOK_ID_HEADER), payload["id"]);
+    assert_eq!(payload["type"], "order.confirmed");
+    assert_eq!(payload["data"]["order_id"], order_id);
+    assert_eq!(payload["data"]["user_id"], fixtures.user_id);
+    assert_eq!(payload["data"]["total_price"], 900);
+    assert_eq!(payload["data"]["deliver_at"], "Instant");
+    assert_eq!(
+        payload["data"]["items"],
+        json!([{ "item_id": wrap_id, "name": "Chicken Wrap", "quantity": 5, "price": 180 }])
+    );
+
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/delivered?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let delivered = wait_for_requests(&server, "/pos", 3).await.remove(2);
+    let payload: Value = serde_json::from_slice(&delivered.body).expect("JSON payload");
+    assert_eq!(payload["type"], "order.delivered");
+    assert_eq!(
+        payload["data"],
+        json!({ "order_id": order_id, "user_id": fixtures.user_id })
+    );
+
+    // The log has every delivery, newest first.
+    let mut deliveries = Vec::new();
+    for _ in 0..50 {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/webhooks/{webhook_id}/deliveries?status=delivered&as=admin-{}",
+                fixtures.canteen_id
+            ))
+            .insert_header(auth_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        deliveries = body["data"].as_array().cloned().unwrap_or_default();
+        if deliveries.len() == 3 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let event_types = deliveries
+        .iter()
+        .map(|d| d["event_type"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        event_types,
+        ["order.delivered", "order.confirmed", "item.stock_out"]
+    );
+    assert!(deliveries
+        .iter()
+        .all(|d| d["last_status_code"] == 204 && d["attempts"] == 1));
+}
+
+#[actix_rt::test]
+async fn selling_out_from_the_menu_editor_posts_stock_out() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/pos"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    std::env::set_var("WEBHOOK_ALLOW_PRIVATE_TARGETS", "true");
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    std::env::remove_var("WEBHOOK_ALLOW_PRIVATE_TARGETS");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/webhooks?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({
+            "url": format!("{}/pos", server.uri()),
+            "secret": SECRET,
+            "events": ["item.stock_out"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let veg_id = fixtures.menu_item_ids[0];
+    let req = test::TestRequest::put()
+        .uri(&format!("/menu/update?as=admin-{}", fixtures.canteen_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "item_id": veg_id, "update": { "stock": 0 } }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let stock_out = wait_for_requests(&server, "/pos", 1).await.remove(0);
+    let payload: Value = serde_json::from_slice(&stock_out.body).expect("JSON payload");
+    assert_eq!(payload["type"], "item.stock_out");
+    assert_eq!(
+        payload["data"],
+        json!({ "item_id": veg_id, "name": "Veg Sandwich" })
+    );
+}
+
+#[actix_rt::test]
+async fn failed_deliveries_are_retried_then_given_up_on() {
+    let server = MockServer::start().await;
+    // Recovers after one failure.
+    Mock::given(method("POST"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("printer offline"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/flaky"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/down"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("internal details"))
+        .mount(&server)
+        .await;
+
+    std::env::set_var("WEBHOOK_POLL_SECS", "1");
+    std::env::set_var("WEBHOOK_RETRY_BASE_SECS", "1");
+    std::env::set_var("WEBHOOK_MAX_ATTEMPTS", "2");
+    std::env::set_var("WEBHOOK_ALLOW_PRIVATE_TARGETS", "true");
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    for key in [
+        "WEBHOOK_POLL_SECS",
+        "WEBHOOK_RETRY_BASE_SECS",
+        "WEBHOOK_MAX_ATTEMPTS",
+        "WEBHOOK_ALLOW_PRIVATE_TARGETS",
+    ] {
+        std::env::remove_var(key);
+    }
+
+    let mut webhook_ids = Vec::new();
+    for hook_path in ["/flaky", "/down"] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/webhooks?as=admin-{}", fixtures.canteen_id))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(json!({
+                "url": format!("{}{hook_path}", server.uri()),
+                "secret": SECRET,
+                "events": ["order.confirmed"]
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        webhook_ids.push(body["data"]["webhook_id"].as_i64().expect("webhook_id"));
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "deliver_at": null, "item_ids": [fixtures.menu_item_ids[0]] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let hold_id = body["hold_id"].as_i64().expect("hold_id");
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/orders/hold/{hold_id}/confirm?as=admin-{}",
+            fixtures.canteen_id
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Both attempts carry the same event.
+    let flaky = wait_for_requests(&server, "/flaky", 2).await;
+    assert_eq!(
+        header_value(&flaky[0], WEBHOOK_ID_HEADER),
+        header_value(&flaky[1], WEBHOOK_ID_HEADER)
+    );
+    wait_for_requests(&server, "/down", 2).await;
+
+    let mut logs: Vec<Value> = Vec::new();
+    for _ in 0..50 {
+        logs.clear();
+        for webhook_id in &webhook_ids {
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/webhooks/{webhook_id}/deliveries?as=admin-{}",
+                    fixtures.canteen_id
+                ))
+                .insert_header(auth_header())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+            logs.push(body["data"][0].clone());
+        }
+        if logs.iter().all(|d| d["status"] != "pending") {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(logs[0]["status"], "delivered");
+    assert_eq!(logs[0]["attempts"], 2);
+    assert_eq!(logs[0]["last_status_code"], 200);
+    assert!(logs[0]["last_error"].is_null());
+
+    assert_eq!(logs[1]["status"], "failed");
+    assert_eq!(logs[1]["attempts"], 2);
+    assert_eq!(logs[1]["last_status_code"], 503);
+    // The reply itself is not logged.
+    assert_eq!(
+        logs[1]["last_error"],
+        "endpoint returned 503 Service Unavailable"
+    );
+    // Given up on, so not retried again.
+    actix_rt::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(wait_for_requests(&server, "/down", 2).await.len(), 2);
+}
+
+#[actix_rt::test]
+async fn webhooks_are_validated_and_scoped_to_their_canteen() {
+    let (app, fixtures, _db_url) = common::setup_api_app().await;
+    let admin = format!("as=admin-{}", fixtures.canteen_id);
+
+    for (payload, expected_error) in [
+        (
+            json!({ "url": "https://pos.example.com/hook" }),
+            "secret is required",
+        ),
+        (
+            json!({ "url": "ftp://pos.example.com/hook", "secret": SECRET }),
+            "url",
+        ),
+        (
+            json!({ "url": "https://pos.example.com/hook", "secret": "short" }),
+            "secret",
+        ),
+        (
+            json!({ "url": "http://169.254.169.254/latest/meta-data", "secret": SECRET }),
+            "private or local address",
+        ),
+        (
+            json!({ "url": "http://localhost:8080/hook", "secret": SECRET }),
+            "private or local address",
+        ),
+        (
+            json!({
+                "url": "https://pos.example.com/hook",
+                "secret": SECRET,
+                "events": ["order.refunded"]
+            }),
+            "unknown event order.refunded",
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/webhooks?{admin}"))
+            .insert_header(auth_header())
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_json(payload)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert!(
+            body["error"]
+                .as_str()
+                .is_some_and(|e| e.contains(expected_error)),
+            "{body}"
+        );
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/webhooks?{admin}"))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({
+            "url": "https://pos.example.com/hook",
+            "secret": SECRET,
+            "events": ["Order.Delivered", "order.confirmed", "order.delivered"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let webhook_id = body["data"]["webhook_id"].as_i64().expect("webhook_id");
+    assert_eq!(
+        body["data"]["events"],
+        json!(["order.confirmed", "order.delivered"])
+    );
+
+    // Another canteen's admin can neither see nor change it.
+    let other_admin = format!("as=admin-{}", fixtures.canteen_id + 1000);
+    let req = test::TestRequest::put()
+        .uri(&format!("/webhooks/{webhook_id}?{other_admin}"))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "url": "https://evil.example.com/hook", "secret": SECRET }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::get()
+        .uri(&format!("/webhooks/{webhook_id}/deliveries?{other_admin}"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::get()
+        .uri(&format!("/webhooks?{other_admin}"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"], json!([]));
+
+    // Updating without a secret keeps the old one.
+    let req = test::TestRequest::put()
+        .uri(&format!("/webhooks/{webhook_id}?{admin}"))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "url": "https://pos.example.com/v2/hook", "is_active": false }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"]["url"], "https://pos.example.com/v2/hook");
+    assert_eq!(body["data"]["is_active"], false);
+    assert_eq!(body["data"]["events"].as_array().map(Vec::len), Some(4));
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/webhooks/{webhook_id}/deliveries?status=bogus&{admin}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/webhooks/{webhook_id}?{admin}"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/webhooks?{admin}"))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["data"], json!([]));
+}
+
+#[actix_rt::test]
+async fn deliveries_to_private_addresses_are_refused() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+    let (app, fixtures, db_url) = common::setup_api_app().await;
+
+    // Saved before the address checks existed, or pointed there since.
+    let pool = build_test_pool(&db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let webhook_id = diesel::insert_into(canteen_webhooks::table)
+        .values(&NewCanteenWebhook {
+            canteen_id: fixtures.canteen_id,
+            url: format!("{}/pos", server.uri()),
+            secret: SECRET.to_string(),
+            events: vec!["item.stock_out".to_string()],
+            is_active: true,
+        })
+        .returning(canteen_webhooks::webhook_id)
+        .get_result::<i32>(conn.connection())
+        .expect("insert webhook");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{}", fixtures.user_id))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(json!({ "item_ids": vec![fixtures.menu_item_ids[1]; 5] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let mut delivery = Value::Null;
+    for _ in 0..100 {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/webhooks/{webhook_id}/deliveries?as=admin-{}",
+                fixtures.canteen_id
+            ))
+            .insert_header(auth_header())
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        delivery = body["data"][0].clone();
+        if delivery["attempts"].as_i64().unwrap_or(0) > 0 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(delivery["attempts"], 1);
+    assert!(delivery["last_status_code"].is_null());
+    assert_eq!(
+        delivery["last_error"],
+        "url does not point at a public address"
+    );
+    assert!(server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .is_empty());
+}
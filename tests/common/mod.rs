@@ -19,12 +19,16 @@ use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::PgConnection;
 use futures::future::poll_fn;
-use proj_xs::auth::{AdminJwtConfig, AuthLayer, FirebaseAuthConfig, JwksCache};
+use proj_xs::auth::{
+    AdminJwtConfig, AuthLayer, FirebaseAuthConfig, JwksCache, PlatformOperatorConfig,
+};
+use proj_xs::db::DbConnection;
 use proj_xs::test_utils::{
-    build_test_pool, init_test_env, reset_db, seed_basic_fixtures, TestFixtures,
+    build_test_pool, init_test_env, insert_canteen, reset_db, seed_basic_fixtures, seed_menu_item,
+    TestFixtures,
 };
 use proj_xs::{api, AppState};
-use serde_json::Value;
+use serde_json::{json, Value};
 use testcontainers::core::IntoContainerPort;
 use testcontainers::runners::AsyncRunner;
 use testcontainers::{ContainerAsync, GenericImage, ImageExt};
@@ -133,6 +137,26 @@ pub async fn wait_for_connected_event(body: &mut BoxBody) -> SseFrame {
     panic!("did not receive initial connected status SSE event");
 }
 
+/// Opens a user's order event stream, resuming after `last_event_id` if given, and skips
+/// past the opening frames.
+pub async fn open_user_stream<S>(app: &S, user_id: i32, last_event_id: Option<&str>) -> BoxBody
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let mut req = test::TestRequest::get()
+        .uri(&format!("/users/events/orders?as=user-{user_id}"))
+        .insert_header(auth_header());
+    if let Some(last_event_id) = last_event_id {
+        req = req.insert_header(("Last-Event-ID", last_event_id));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut stream = resp.into_body();
+    let _retry = read_sse_frame(&mut stream).await;
+    let _connected = wait_for_connected_event(&mut stream).await;
+    stream
+}
+
 pub fn sse_frame_data_json(frame: &SseFrame) -> Value {
     let data = frame.data.as_deref().expect("SSE frame missing data");
     serde_json::from_str(data).expect("SSE data should be valid JSON")
@@ -279,10 +303,19 @@ pub async fn setup_api_app() -> (
     reset_db(&pool).expect("reset db");
     let fixtures = seed_basic_fixtures(&pool).expect("seed fixtures");
 
-    let state = AppState::new(&db.database_url).await;
+    let app = init_api_app(&db.database_url).await;
+    (app, fixtures, db.database_url.clone())
+}
+
+/// Another app instance on an already set up database, like a second server process.
+pub async fn init_api_app(
+    database_url: &str,
+) -> impl Service<Request, Response = ServiceResponse<BoxBody>, Error = Error> {
+    let state = AppState::new(database_url).await;
 
     let fb_cfg = FirebaseAuthConfig::from_env();
     let admin_cfg = AdminJwtConfig::from_env();
+    let operator_cfg = PlatformOperatorConfig::from_env();
     let jwks_cache = JwksCache::new(fb_cfg.jwks_url.clone(), fb_cfg.cache_ttl_secs);
 
     let qr_secret =
@@ -311,13 +344,143 @@ pub async fn setup_api_app() -> (
             .app_data(web::Data::new(jwks_cache))
             .app_data(web::Data::new(state.user_ops.clone()))
             .app_data(web::Data::new(admin_cfg))
+            .app_data(web::Data::new(operator_cfg))
             .app_data(web::JsonConfig::default().error_handler(api::default_error_handler))
         })
         .into_app();
 
-    let app = test::init_service(app).await;
+    test::init_service(app).await
+}
 
-    (app, fixtures, db.database_url.clone())
+/// Hold an order through the API with the given `/orders/hold` body; returns its hold ID.
+pub async fn hold_order<S>(app: &S, user_id: i32, payload: Value) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/hold?as=user-{user_id}"))
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(payload)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    body["hold_id"].as_i64().expect("hold_id")
+}
+
+/// Confirm a hold as the canteen's admin; returns the order ID.
+pub async fn confirm_hold<S>(app: &S, canteen_id: i32, hold_id: i64) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/orders/hold/{hold_id}/confirm?as=admin-{canteen_id}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    body["order_id"].as_i64().expect("order_id")
+}
+
+/// Hold and confirm an order through the API; returns its order ID.
+pub async fn place_order<S>(app: &S, user_id: i32, canteen_id: i32, payload: Value) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let hold_id = hold_order(app, user_id, payload).await;
+    confirm_hold(app, canteen_id, hold_id).await
+}
+
+/// Hold, confirm and then deliver or cancel (`action` is `delivered` or `cancelled`) an
+/// order of `item_ids`; returns its order ID.
+pub async fn complete_order<S>(
+    app: &S,
+    user_id: i32,
+    canteen_id: i32,
+    item_ids: &[i32],
+    action: &str,
+) -> i64
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let order_id = place_order(app, user_id, canteen_id, json!({ "item_ids": item_ids })).await;
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/orders/{order_id}/{action}?as=admin-{canteen_id}"
+        ))
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    order_id
+}
+
+/// GET `uri` with the test auth header; returns the status and the JSON body.
+pub async fn get_json<S>(app: &S, uri: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header(auth_header())
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+/// Send `req` with the test auth header and `body` as JSON; returns the status and the
+/// JSON body.
+pub async fn send_json<S>(app: &S, req: test::TestRequest, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+{
+    let req = req
+        .insert_header(auth_header())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    (status, test::read_body_json(resp).await)
+}
+
+/// Seed a second canteen with one menu item; returns `(canteen_id, item_id)`.
+pub fn seed_other_canteen_item(db_url: &str) -> (i32, i32) {
+    let pool = build_test_pool(db_url);
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let other_canteen_id =
+        insert_canteen(conn.connection(), "Other Canteen", "Block Z").expect("insert canteen");
+    let other_item_id = seed_menu_item(
+        conn.connection(),
+        other_canteen_id,
+        "Other Canteen Item",
+        199,
+        8,
+        true,
+        true,
+        Some("owned by another canteen"),
+    )
+    .expect("insert menu item");
+    (other_canteen_id, other_item_id)
+}
+
+/// Point the PhonePe client at a wiremock server at `base_url`.
+pub fn configure_phonepe_mock_env(base_url: &str) {
+    env::set_var("PHONEPE_AUTH_BASE_URL", base_url);
+    env::set_var("PHONEPE_PG_BASE_URL", base_url);
+    env::set_var(
+        "PHONEPE_WEB_REDIRECT_URL",
+        "https://pwa.example/phonepe-return",
+    );
+    env::set_var(
+        "PHONEPE_WEB_PAYMENT_URL_TEMPLATE",
+        "https://pwa.example/pay?merchantOrderId={merchant_order_id_urlencoded}&orderId={order_id_urlencoded}&token={token_urlencoded}",
+    );
 }
 
 // ---------------------------------------------------------------------------
@@ -1,7 +1,9 @@
 mod common;
 
 use diesel::prelude::*;
-use proj_xs::db::{AssetOperations, CanteenOperations, DbConnection};
+use proj_xs::db::{AssetOperations, CanteenOperations, DbConnection, MenuOperations};
+use proj_xs::enums::admin::{MenuFilterQuery, MenuItemWithPic};
+use proj_xs::models::admin::NewMenuCategory;
 use proj_xs::test_utils::{insert_canteen, seed_menu_item};
 
 #[test]
@@ -195,3 +197,153 @@ fn login_canteen_db_unknown_user() {
         "login should return None for unknown username"
     );
 }
+
+#[actix_rt::test]
+async fn get_canteen_items_orders_pinned_then_category_and_filters() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let canteen_ops = CanteenOperations::new(pool.clone(), asset_ops.clone()).await;
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+    let (sandwich_id, wrap_id) = (fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]);
+
+    let mains = menu_ops
+        .create_category(NewMenuCategory {
+            canteen_id: fixtures.canteen_id,
+            name: "Mains".to_string(),
+            display_order: 2,
+        })
+        .expect("create mains");
+    let snacks = menu_ops
+        .create_category(NewMenuCategory {
+            canteen_id: fixtures.canteen_id,
+            name: "Snacks".to_string(),
+            display_order: 1,
+        })
+        .expect("create snacks");
+
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let cola_id = seed_menu_item(
+        conn.connection(),
+        fixtures.canteen_id,
+        "Cola",
+        40,
+        10,
+        true,
+        true,
+        None,
+    )
+    .expect("insert item");
+
+    use proj_xs::db::schema::menu_items::dsl::*;
+    diesel::update(menu_items.filter(item_id.eq(sandwich_id)))
+        .set(category_id.eq(mains.category_id))
+        .execute(conn.connection())
+        .expect("categorise sandwich");
+    diesel::update(menu_items.filter(item_id.eq(wrap_id)))
+        .set((
+            category_id.eq(snacks.category_id),
+            tags.eq(vec!["spicy".to_string()]),
+        ))
+        .execute(conn.connection())
+        .expect("categorise wrap");
+    diesel::update(menu_items.filter(item_id.eq(cola_id)))
+        .set(is_pinned.eq(true))
+        .execute(conn.connection())
+        .expect("pin cola");
+
+    let ids = |items: Vec<MenuItemWithPic>| items.iter().map(|i| i.item_id).collect::<Vec<_>>();
+    let all = canteen_ops
+        .get_canteen_items(fixtures.canteen_id, &MenuFilterQuery::default(), None)
+        .await
+        .expect("get items");
+    assert_eq!(ids(all), vec![cola_id, wrap_id, sandwich_id]);
+
+    let by_category = canteen_ops
+        .get_canteen_items(
+            fixtures.canteen_id,
+            &MenuFilterQuery {
+                category_id: Some(mains.category_id),
+                tag: None,
+                hide_allergens: None,
+            },
+            None,
+        )
+        .await
+        .expect("filter by category");
+    assert_eq!(ids(by_category), vec![sandwich_id]);
+
+    let by_tag = canteen_ops
+        .get_canteen_items(
+            fixtures.canteen_id,
+            &MenuFilterQuery {
+                category_id: None,
+                tag: Some("Spicy".to_string()),
+                hide_allergens: None,
+            },
+            None,
+        )
+        .await
+        .expect("filter by tag");
+    assert_eq!(ids(by_tag), vec![wrap_id]);
+}
+
+#[actix_rt::test]
+async fn get_canteen_items_flags_or_hides_excluded_allergens() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let canteen_ops = CanteenOperations::new(pool.clone(), asset_ops).await;
+    let (sandwich_id, wrap_id) = (fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]);
+
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    {
+        use proj_xs::db::schema::menu_items::dsl::*;
+        diesel::update(menu_items.filter(item_id.eq(sandwich_id)))
+            .set(allergens.eq(vec!["gluten".to_string(), "dairy".to_string()]))
+            .execute(conn.connection())
+            .expect("set allergens");
+    }
+    {
+        use proj_xs::db::schema::users::dsl::*;
+        diesel::update(users.filter(user_id.eq(fixtures.user_id)))
+            .set(allergen_exclusions.eq(vec!["dairy".to_string()]))
+            .execute(conn.connection())
+            .expect("set exclusions");
+    }
+
+    let items = canteen_ops
+        .get_canteen_items(
+            fixtures.canteen_id,
+            &MenuFilterQuery::default(),
+            Some(fixtures.user_id),
+        )
+        .await
+        .expect("get items");
+    assert_eq!(items.len(), 2);
+    let sandwich = items.iter().find(|i| i.item_id == sandwich_id).unwrap();
+    assert_eq!(sandwich.allergen_warnings, vec!["dairy"]);
+    let wrap = items.iter().find(|i| i.item_id == wrap_id).unwrap();
+    assert!(wrap.allergen_warnings.is_empty());
+
+    // Without a viewer nothing is flagged.
+    let items = canteen_ops
+        .get_canteen_items(fixtures.canteen_id, &MenuFilterQuery::default(), None)
+        .await
+        .expect("get items");
+    assert!(items.iter().all(|i| i.allergen_warnings.is_empty()));
+
+    let items = canteen_ops
+        .get_canteen_items(
+            fixtures.canteen_id,
+            &MenuFilterQuery {
+                hide_allergens: Some(true),
+                ..Default::default()
+            },
+            Some(fixtures.user_id),
+        )
+        .await
+        .expect("get items");
+    assert_eq!(
+        items.iter().map(|i| i.item_id).collect::<Vec<_>>(),
+        vec![wrap_id]
+    );
+}
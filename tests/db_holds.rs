@@ -5,7 +5,10 @@ use common::{
     menu_item_state,
 };
 use diesel::prelude::*;
-use proj_xs::db::{DbConnection, HoldOperations, RepositoryError};
+use proj_xs::db::{
+    DbConnection, HoldOperations, PricingOperations, PromoOperations, RepositoryError,
+};
+use proj_xs::models::admin::{NewPricingRule, NewPromoCode, PricingRuleKindEnum};
 use proj_xs::models::common::TimeBandEnum;
 use proj_xs::test_utils::{insert_canteen, insert_user, seed_menu_item};
 
@@ -360,14 +363,26 @@ fn cleanup_expired_holds_restores_stock_and_deletes() {
         .hold_order(fixtures.user_id, vec![item_b], None)
         .expect("active hold");
 
-    let (cleaned, updates) = hold_ops_active.cleanup_expired_holds().expect("cleanup");
+    let cleaned = hold_ops_active.cleanup_expired_holds().expect("cleanup");
     assert_eq!(cleaned, 1);
-    assert_eq!(updates.len(), 1);
-    assert_eq!(updates[0].0, fixtures.canteen_id);
-    assert_eq!(updates[0].1.len(), 1);
-    assert_eq!(updates[0].1[0].item_id, item_a);
-    assert_eq!(updates[0].1[0].stock, 2);
-    assert!(updates[0].1[0].is_available);
+
+    // The stock given back is queued for the canteen's subscribers.
+    use proj_xs::db::schema::outbox::dsl as ob;
+    let payload: String = ob::outbox
+        .filter(ob::topic.eq("canteen_subscription_event"))
+        .order(ob::outbox_id.desc())
+        .select(ob::payload)
+        .first(conn.connection())
+        .expect("queued inventory update");
+    let payload: serde_json::Value = serde_json::from_str(&payload).expect("outbox payload");
+    assert_eq!(payload["canteen_id"], fixtures.canteen_id);
+    let items = payload["event"]["items"]
+        .as_array()
+        .expect("inventory items");
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["item_id"], item_a);
+    assert_eq!(items[0]["stock"], 2);
+    assert_eq!(items[0]["is_available"], true);
 
     assert_eq!(held_orders_count(conn.connection()), 1);
     let (stock_a, _) = menu_item_state(conn.connection(), item_a);
@@ -444,9 +459,8 @@ fn cleanup_expired_holds_returns_zero_when_none_expired() {
         .hold_order(fixtures.user_id, vec![fixtures.menu_item_ids[0]], None)
         .expect("hold order");
 
-    let (cleaned, updates) = hold_ops.cleanup_expired_holds().expect("cleanup");
+    let cleaned = hold_ops.cleanup_expired_holds().expect("cleanup");
     assert_eq!(cleaned, 0);
-    assert!(updates.is_empty());
 }
 
 #[test]
@@ -553,3 +567,217 @@ fn concurrent_confirm_and_cancel_same_hold_one_succeeds() {
         "at most 1 active order should exist"
     );
 }
+
+#[actix_rt::test]
+async fn hold_order_snapshots_prices_after_pricing_rules() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let veg_item = fixtures.menu_item_ids[0];
+    let non_veg_item = fixtures.menu_item_ids[1];
+
+    let pricing_ops = PricingOperations::new(pool.clone()).await;
+    pricing_ops
+        .create_rule(
+            NewPricingRule {
+                canteen_id: fixtures.canteen_id,
+                name: "Wrap deal".to_string(),
+                kind: PricingRuleKindEnum::FlatOff,
+                percent_off: None,
+                amount_off: Some(30),
+                combo_price: None,
+                item_ids: vec![non_veg_item],
+                is_active: true,
+            },
+            Vec::new(),
+        )
+        .expect("create rule");
+    pricing_ops
+        .create_rule(
+            NewPricingRule {
+                canteen_id: fixtures.canteen_id,
+                name: "Paused".to_string(),
+                kind: PricingRuleKindEnum::PercentOff,
+                percent_off: Some(90),
+                amount_off: None,
+                combo_price: None,
+                item_ids: Vec::new(),
+                is_active: false,
+            },
+            Vec::new(),
+        )
+        .expect("create paused rule");
+
+    let hold_ops = HoldOperations::new(pool.clone(), 300);
+    let (hold_id_val, _, (_, _, (total_price, applied_rules, _))) = hold_ops
+        .hold_order(fixtures.user_id, vec![veg_item, non_veg_item], None)
+        .expect("hold order");
+    assert_eq!(total_price, 120 + 150);
+    assert_eq!(applied_rules.len(), 1);
+    assert_eq!(applied_rules[0].name, "Wrap deal");
+    assert_eq!(applied_rules[0].discount, 30);
+
+    use proj_xs::db::schema::held_order_items::dsl as held_order_items_dsl;
+    let non_veg_price = held_order_items_dsl::held_order_items
+        .filter(held_order_items_dsl::hold_id.eq(hold_id_val))
+        .filter(held_order_items_dsl::item_id.eq(non_veg_item))
+        .select(held_order_items_dsl::price)
+        .first::<i32>(conn.connection())
+        .expect("held item");
+    assert_eq!(non_veg_price, 150);
+
+    use proj_xs::db::schema::held_orders::dsl as held_orders_dsl;
+    let stored_total = held_orders_dsl::held_orders
+        .filter(held_orders_dsl::hold_id.eq(hold_id_val))
+        .select(held_orders_dsl::total_price)
+        .first::<i32>(conn.connection())
+        .expect("held order");
+    assert_eq!(stored_total, 270);
+}
+
+fn promo_code(code: &str, canteen_id: Option<i32>) -> NewPromoCode {
+    NewPromoCode {
+        code: code.to_string(),
+        canteen_id,
+        description: None,
+        percent_off: None,
+        amount_off: Some(50),
+        max_discount: None,
+        min_order_total: 0,
+        valid_from: None,
+        valid_until: None,
+        max_uses_total: None,
+        max_uses_per_user: None,
+        first_order_only: false,
+        is_active: true,
+    }
+}
+
+fn promo_redemptions_count(conn: &mut PgConnection) -> i64 {
+    use proj_xs::db::schema::promo_redemptions::dsl::*;
+    promo_redemptions
+        .count()
+        .get_result(conn)
+        .expect("count redemptions")
+}
+
+#[actix_rt::test]
+async fn hold_order_with_promo_records_redemption_and_release_returns_it() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let veg_item = fixtures.menu_item_ids[0];
+
+    let promo_ops = PromoOperations::new(pool.clone()).await;
+    promo_ops
+        .create_code(NewPromoCode {
+            max_uses_per_user: Some(1),
+            ..promo_code("save50", Some(fixtures.canteen_id))
+        })
+        .expect("create code");
+
+    let hold_ops = HoldOperations::new(pool.clone(), 300);
+    let (hold_id_val, _, (_, _, (total_price, _, applied_promo))) = hold_ops
+        .hold_order_with_promo(
+            fixtures.user_id,
+            vec![veg_item, veg_item],
+            None,
+            Some("Save50".to_string()),
+            None,
+        )
+        .expect("hold with promo");
+    assert_eq!(total_price, 240 - 50);
+    let applied_promo = applied_promo.expect("applied promo");
+    assert_eq!(applied_promo.code, "SAVE50");
+    assert_eq!(applied_promo.discount, 50);
+    assert_eq!(promo_redemptions_count(conn.connection()), 1);
+
+    // The pending hold already uses the user's only redemption.
+    let err = hold_ops
+        .hold_order_with_promo(
+            fixtures.user_id,
+            vec![veg_item],
+            None,
+            Some("SAVE50".to_string()),
+            None,
+        )
+        .expect_err("per-user limit");
+    assert!(
+        matches!(err, RepositoryError::ValidationError(ref msg) if msg.contains("maximum number of times"))
+    );
+
+    hold_ops
+        .release_held_order(hold_id_val, fixtures.user_id)
+        .expect("release hold");
+    assert_eq!(promo_redemptions_count(conn.connection()), 0);
+
+    let (hold_id_val, _, _) = hold_ops
+        .hold_order_with_promo(
+            fixtures.user_id,
+            vec![veg_item],
+            None,
+            Some("SAVE50".to_string()),
+            None,
+        )
+        .expect("hold after release");
+    let (order_id_val, _, _, _) = hold_ops
+        .confirm_held_order(hold_id_val, fixtures.user_id)
+        .expect("confirm hold");
+
+    use proj_xs::db::schema::promo_redemptions::dsl as redemptions_dsl;
+    let (hold_ref, order_ref) = redemptions_dsl::promo_redemptions
+        .select((redemptions_dsl::hold_id, redemptions_dsl::order_id))
+        .first::<(Option<i32>, Option<i32>)>(conn.connection())
+        .expect("redemption");
+    assert_eq!(hold_ref, None);
+    assert_eq!(order_ref, Some(order_id_val));
+}
+
+#[actix_rt::test]
+async fn hold_order_with_promo_rejects_invalid_codes_without_holding() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let veg_item = fixtures.menu_item_ids[0];
+    let other_canteen =
+        insert_canteen(conn.connection(), "Promo Other Canteen", "Block C").expect("other canteen");
+
+    let promo_ops = PromoOperations::new(pool.clone()).await;
+    promo_ops
+        .create_code(promo_code("ELSEWHERE", Some(other_canteen)))
+        .expect("create foreign code");
+    promo_ops
+        .create_code(NewPromoCode {
+            min_order_total: 500,
+            ..promo_code("BIGORDER", None)
+        })
+        .expect("create platform code");
+    promo_ops
+        .create_code(NewPromoCode {
+            valid_until: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+            ..promo_code("OLD", None)
+        })
+        .expect("create expired code");
+
+    let hold_ops = HoldOperations::new(pool.clone(), 300);
+    for (code, reason) in [
+        ("NOPE", "is not valid"),
+        ("ELSEWHERE", "not valid at this canteen"),
+        ("BIGORDER", "minimum order of 500"),
+        ("OLD", "has expired"),
+    ] {
+        let err = hold_ops
+            .hold_order_with_promo(
+                fixtures.user_id,
+                vec![veg_item],
+                None,
+                Some(code.into()),
+                None,
+            )
+            .expect_err(code);
+        assert!(
+            matches!(err, RepositoryError::ValidationError(ref msg) if msg.contains(reason)),
+            "{code}: {err}"
+        );
+    }
+    assert_eq!(held_orders_count(conn.connection()), 0);
+    let (stock, _) = menu_item_state(conn.connection(), veg_item);
+    assert_eq!(stock, 10);
+}
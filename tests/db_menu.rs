@@ -1,7 +1,12 @@
 mod common;
 
 use proj_xs::db::{AssetOperations, DbConnection, MenuOperations, RepositoryError};
-use proj_xs::models::admin::{NewMenuItem, UpdateMenuItem};
+use proj_xs::enums::admin::MenuTransferRow;
+use proj_xs::models::admin::{
+    NewMenuCategory, NewMenuItem, ScheduleWindow, UpdateMenuCategory, UpdateMenuItem,
+};
+use proj_xs::services::outbox::OutboxMessage;
+use proj_xs::services::webhooks::WebhookEvent;
 
 #[actix_rt::test]
 async fn add_menu_item_success() {
@@ -17,6 +22,13 @@ async fn add_menu_item_success() {
         stock: 20,
         is_available: true,
         description: Some("Grilled paneer".to_string()),
+        default_stock: None,
+        category_id: None,
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
     };
 
     let result = menu_ops.add_menu_item(new_item);
@@ -42,6 +54,13 @@ async fn add_menu_item_rejects_zero_price() {
         stock: 10,
         is_available: true,
         description: None,
+        default_stock: None,
+        category_id: None,
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
     };
 
     let result = menu_ops.add_menu_item(new_item);
@@ -66,6 +85,13 @@ async fn add_menu_item_rejects_empty_name() {
         stock: 10,
         is_available: true,
         description: None,
+        default_stock: None,
+        category_id: None,
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
     };
 
     let result = menu_ops.add_menu_item(new_item);
@@ -90,6 +116,13 @@ async fn update_menu_item_partial_name_only() {
         stock: None,
         is_available: None,
         description: None,
+        default_stock: None,
+        category_id: None,
+        display_order: None,
+        is_pinned: None,
+        tags: None,
+        allergens: None,
+        diet_labels: None,
     };
 
     let result = menu_ops.update_menu_item(item_id, fixtures.canteen_id, update);
@@ -114,6 +147,13 @@ async fn update_menu_item_not_found() {
         stock: None,
         is_available: None,
         description: None,
+        default_stock: None,
+        category_id: None,
+        display_order: None,
+        is_pinned: None,
+        tags: None,
+        allergens: None,
+        diet_labels: None,
     };
 
     let result = menu_ops.update_menu_item(99999, fixtures.canteen_id, update);
@@ -173,3 +213,650 @@ async fn get_menu_item_by_id_and_not_found() {
     assert!(result.is_err());
     assert!(matches!(result.unwrap_err(), RepositoryError::NotFound(_)));
 }
+
+#[actix_rt::test]
+async fn reset_daily_stock_restores_default_stock_once_per_day() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let reset_id = fixtures.menu_item_ids[0];
+    let untouched_id = fixtures.menu_item_ids[1];
+    let update = UpdateMenuItem {
+        name: None,
+        is_veg: None,
+        price: None,
+        stock: Some(0),
+        is_available: Some(false),
+        description: None,
+        default_stock: Some(Some(25)),
+        category_id: None,
+        display_order: None,
+        is_pinned: None,
+        tags: None,
+        allergens: None,
+        diet_labels: None,
+    };
+    menu_ops
+        .update_menu_item(reset_id, fixtures.canteen_id, update)
+        .expect("set default stock");
+
+    let today = chrono::NaiveDate::from_ymd_opt(2026, 4, 6).unwrap();
+    let updates = menu_ops
+        .reset_daily_stock(fixtures.canteen_id, today)
+        .expect("reset daily stock");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].item_id, reset_id);
+    assert_eq!(updates[0].stock, 25);
+    assert!(updates[0].is_available);
+
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    assert_eq!(
+        common::menu_item_state(conn.connection(), reset_id),
+        (25, true)
+    );
+    assert_eq!(
+        common::menu_item_state(conn.connection(), untouched_id),
+        (5, true)
+    );
+
+    // A second pass on the same day must not clobber stock sold since the reset.
+    menu_ops
+        .update_menu_item(
+            reset_id,
+            fixtures.canteen_id,
+            UpdateMenuItem {
+                name: None,
+                is_veg: None,
+                price: None,
+                stock: Some(3),
+                is_available: None,
+                description: None,
+                default_stock: None,
+                category_id: None,
+                display_order: None,
+                is_pinned: None,
+                tags: None,
+                allergens: None,
+                diet_labels: None,
+            },
+        )
+        .expect("sell some stock");
+    let updates = menu_ops
+        .reset_daily_stock(fixtures.canteen_id, today)
+        .expect("second reset");
+    assert!(updates.is_empty());
+    assert_eq!(
+        common::menu_item_state(conn.connection(), reset_id),
+        (3, true)
+    );
+
+    let updates = menu_ops
+        .reset_daily_stock(fixtures.canteen_id, today.succ_opt().unwrap())
+        .expect("next day reset");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(
+        common::menu_item_state(conn.connection(), reset_id),
+        (25, true)
+    );
+}
+
+#[actix_rt::test]
+async fn reset_daily_stock_keeps_items_switched_off_by_hand() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let disabled_id = fixtures.menu_item_ids[1];
+    menu_ops
+        .update_menu_item(
+            disabled_id,
+            fixtures.canteen_id,
+            UpdateMenuItem {
+                name: None,
+                is_veg: None,
+                price: None,
+                stock: Some(2),
+                is_available: Some(false),
+                description: None,
+                default_stock: Some(Some(25)),
+                category_id: None,
+                display_order: None,
+                is_pinned: None,
+                tags: None,
+                allergens: None,
+                diet_labels: None,
+            },
+        )
+        .expect("switch item off");
+
+    let today = chrono::NaiveDate::from_ymd_opt(2026, 4, 6).unwrap();
+    let updates = menu_ops
+        .reset_daily_stock(fixtures.canteen_id, today)
+        .expect("reset daily stock");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].stock, 25);
+    assert!(!updates[0].is_available);
+
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    assert_eq!(
+        common::menu_item_state(conn.connection(), disabled_id),
+        (25, false)
+    );
+}
+
+/// Item ids of the `item.stock_out` webhooks in the outbox, oldest first.
+fn queued_stock_outs(conn: &mut diesel::PgConnection) -> Vec<i32> {
+    use diesel::prelude::*;
+    use proj_xs::db::schema::outbox;
+    outbox::table
+        .order_by(outbox::outbox_id.asc())
+        .select(outbox::payload)
+        .load::<String>(conn)
+        .expect("outbox entries")
+        .iter()
+        .filter_map(|payload| {
+            match serde_json::from_str::<OutboxMessage>(payload).expect("outbox payload") {
+                OutboxMessage::CanteenWebhook {
+                    event: WebhookEvent::StockOut { item_id, .. },
+                    ..
+                } => Some(item_id),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn stock_update(new_stock: Option<i32>, new_default_stock: Option<Option<i32>>) -> UpdateMenuItem {
+    UpdateMenuItem {
+        name: None,
+        is_veg: None,
+        price: None,
+        stock: new_stock,
+        is_available: None,
+        description: None,
+        default_stock: new_default_stock,
+        category_id: None,
+        display_order: None,
+        is_pinned: None,
+        tags: None,
+        allergens: None,
+        diet_labels: None,
+    }
+}
+
+#[actix_rt::test]
+async fn menu_changes_that_sell_an_item_out_queue_stock_out_webhooks() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    let [veg_id, wrap_id] = [fixtures.menu_item_ids[0], fixtures.menu_item_ids[1]];
+
+    menu_ops
+        .update_menu_item(wrap_id, fixtures.canteen_id, stock_update(Some(0), None))
+        .expect("sell out wrap");
+    // Already sold out.
+    menu_ops
+        .update_menu_item(wrap_id, fixtures.canteen_id, stock_update(Some(0), None))
+        .expect("update sold-out wrap");
+    assert_eq!(queued_stock_outs(conn.connection()), vec![wrap_id]);
+
+    let mut rows = menu_ops.export_menu(fixtures.canteen_id).expect("export");
+    rows[0].stock = 0;
+    let (report, _) = menu_ops
+        .import_menu(fixtures.canteen_id, rows, false)
+        .expect("import");
+    assert!(report.applied);
+    assert_eq!(queued_stock_outs(conn.connection()), vec![wrap_id, veg_id]);
+
+    menu_ops
+        .update_menu_item(
+            veg_id,
+            fixtures.canteen_id,
+            stock_update(Some(4), Some(Some(0))),
+        )
+        .expect("restock veg");
+    let today = chrono::NaiveDate::from_ymd_opt(2026, 4, 6).unwrap();
+    menu_ops
+        .reset_daily_stock(fixtures.canteen_id, today)
+        .expect("reset daily stock");
+    assert_eq!(
+        queued_stock_outs(conn.connection()),
+        vec![wrap_id, veg_id, veg_id]
+    );
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_replace_and_apply() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let item_id = fixtures.menu_item_ids[0];
+    let window = |weekday: i16, start: u32, end: u32| ScheduleWindow {
+        weekday,
+        start_time: chrono::NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+        end_time: chrono::NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+    };
+
+    let stored = menu_ops
+        .set_menu_item_schedule(
+            item_id,
+            fixtures.canteen_id,
+            vec![window(2, 8, 11), window(0, 8, 11)],
+        )
+        .expect("set schedule");
+    assert_eq!(stored, vec![window(0, 8, 11), window(2, 8, 11)]);
+
+    let stored = menu_ops
+        .set_menu_item_schedule(item_id, fixtures.canteen_id, vec![window(4, 16, 18)])
+        .expect("replace schedule");
+    assert_eq!(stored, vec![window(4, 16, 18)]);
+
+    let scheduled = menu_ops.list_scheduled_items().expect("list scheduled");
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].item_id, item_id);
+    assert_eq!(scheduled[0].windows, vec![window(4, 16, 18)]);
+
+    let (canteen_id, update) = menu_ops
+        .apply_scheduled_availability(item_id, false)
+        .expect("apply")
+        .expect("availability should change");
+    assert_eq!(canteen_id, fixtures.canteen_id);
+    assert!(!update.is_available);
+    assert!(menu_ops
+        .apply_scheduled_availability(item_id, false)
+        .expect("apply again")
+        .is_none());
+
+    let (_, update) = menu_ops
+        .apply_scheduled_availability(item_id, true)
+        .expect("apply")
+        .expect("availability should change");
+    assert!(update.is_available);
+
+    let cleared = menu_ops
+        .set_menu_item_schedule(item_id, fixtures.canteen_id, Vec::new())
+        .expect("clear schedule");
+    assert!(cleared.is_empty());
+    assert!(menu_ops.list_scheduled_items().expect("list").is_empty());
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_keeps_items_switched_off_by_hand() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let item_id = fixtures.menu_item_ids[0];
+    let window = ScheduleWindow {
+        weekday: 0,
+        start_time: chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        end_time: chrono::NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+    };
+    menu_ops
+        .set_menu_item_schedule(item_id, fixtures.canteen_id, vec![window])
+        .expect("set schedule");
+    let switch = |available: bool| UpdateMenuItem {
+        is_available: Some(available),
+        ..stock_update(None, None)
+    };
+
+    // Switched off by hand inside the window: the schedule leaves it off.
+    let item = menu_ops
+        .update_menu_item(item_id, fixtures.canteen_id, switch(false))
+        .expect("switch off");
+    assert!(item.manually_disabled);
+    let scheduled = menu_ops.list_scheduled_items().expect("list scheduled");
+    assert!(scheduled[0].manually_disabled);
+    assert!(menu_ops
+        .apply_scheduled_availability(item_id, true)
+        .expect("apply")
+        .is_none());
+    let mut conn = DbConnection::new(&pool).expect("db connection");
+    assert_eq!(
+        common::menu_item_state(conn.connection(), item_id),
+        (10, false)
+    );
+
+    // Switching it back on by hand hands it back to the schedule.
+    let item = menu_ops
+        .update_menu_item(item_id, fixtures.canteen_id, switch(true))
+        .expect("switch on");
+    assert!(!item.manually_disabled);
+    let (_, update) = menu_ops
+        .apply_scheduled_availability(item_id, false)
+        .expect("apply")
+        .expect("closed outside the window");
+    assert!(!update.is_available);
+    let (_, update) = menu_ops
+        .apply_scheduled_availability(item_id, true)
+        .expect("apply")
+        .expect("open inside the window");
+    assert!(update.is_available);
+}
+
+#[actix_rt::test]
+async fn menu_item_schedule_rejects_invalid_and_foreign_items() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let bad_window = ScheduleWindow {
+        weekday: 9,
+        start_time: chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        end_time: chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+    };
+    let result = menu_ops.set_menu_item_schedule(
+        fixtures.menu_item_ids[0],
+        fixtures.canteen_id,
+        vec![bad_window],
+    );
+    assert!(matches!(
+        result.unwrap_err(),
+        RepositoryError::ValidationError(_)
+    ));
+
+    let result = menu_ops.get_menu_item_schedule(fixtures.menu_item_ids[0], 99999);
+    assert!(matches!(result.unwrap_err(), RepositoryError::NotFound(_)));
+}
+
+#[actix_rt::test]
+async fn menu_category_crud_and_item_assignment() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let drinks = menu_ops
+        .create_category(NewMenuCategory {
+            canteen_id: fixtures.canteen_id,
+            name: "  Drinks ".to_string(),
+            display_order: 5,
+        })
+        .expect("create category");
+    assert_eq!(drinks.name, "Drinks");
+
+    let duplicate = menu_ops.create_category(NewMenuCategory {
+        canteen_id: fixtures.canteen_id,
+        name: "Drinks".to_string(),
+        display_order: 0,
+    });
+    assert!(matches!(
+        duplicate.unwrap_err(),
+        RepositoryError::ValidationError(_)
+    ));
+
+    let breakfast = menu_ops
+        .create_category(NewMenuCategory {
+            canteen_id: fixtures.canteen_id,
+            name: "Breakfast".to_string(),
+            display_order: 1,
+        })
+        .expect("create category");
+    let listed = menu_ops
+        .list_categories(fixtures.canteen_id)
+        .expect("list categories");
+    assert_eq!(
+        listed.iter().map(|c| c.category_id).collect::<Vec<_>>(),
+        vec![breakfast.category_id, drinks.category_id]
+    );
+
+    let renamed = menu_ops
+        .update_category(
+            drinks.category_id,
+            fixtures.canteen_id,
+            UpdateMenuCategory {
+                name: Some("Beverages".to_string()),
+                display_order: Some(0),
+            },
+        )
+        .expect("update category");
+    assert_eq!(renamed.name, "Beverages");
+    assert_eq!(renamed.display_order, 0);
+
+    let item_id = fixtures.menu_item_ids[0];
+    let assign = |category: Option<i32>| UpdateMenuItem {
+        name: None,
+        is_veg: None,
+        price: None,
+        stock: None,
+        is_available: None,
+        description: None,
+        default_stock: None,
+        category_id: Some(category),
+        display_order: None,
+        is_pinned: None,
+        tags: None,
+        allergens: None,
+        diet_labels: None,
+    };
+    let updated = menu_ops
+        .update_menu_item(
+            item_id,
+            fixtures.canteen_id,
+            assign(Some(drinks.category_id)),
+        )
+        .expect("assign category");
+    assert_eq!(updated.category_id, Some(drinks.category_id));
+
+    let result = menu_ops.update_menu_item(item_id, fixtures.canteen_id, assign(Some(99999)));
+    assert!(matches!(
+        result.unwrap_err(),
+        RepositoryError::ValidationError(_)
+    ));
+
+    menu_ops
+        .delete_category(drinks.category_id, fixtures.canteen_id)
+        .expect("delete category");
+    let item = menu_ops.get_menu_item(item_id).await.expect("get item");
+    assert_eq!(item.category_id, None);
+
+    let result = menu_ops.delete_category(breakfast.category_id, 99999);
+    assert!(matches!(result.unwrap_err(), RepositoryError::NotFound(_)));
+}
+
+#[actix_rt::test]
+async fn menu_tags_list_rename_and_delete() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let tag_update = |new_tags: Vec<&str>| UpdateMenuItem {
+        name: None,
+        is_veg: None,
+        price: None,
+        stock: None,
+        is_available: None,
+        description: None,
+        default_stock: None,
+        category_id: None,
+        display_order: None,
+        is_pinned: None,
+        tags: Some(new_tags.into_iter().map(String::from).collect()),
+        allergens: None,
+        diet_labels: None,
+    };
+    let updated = menu_ops
+        .update_menu_item(
+            fixtures.menu_item_ids[0],
+            fixtures.canteen_id,
+            tag_update(vec!["Spicy", "hot"]),
+        )
+        .expect("tag sandwich");
+    assert_eq!(updated.tags, vec!["spicy", "hot"]);
+    menu_ops
+        .update_menu_item(
+            fixtures.menu_item_ids[1],
+            fixtures.canteen_id,
+            tag_update(vec!["spicy"]),
+        )
+        .expect("tag wrap");
+
+    let tags = menu_ops.list_tags(fixtures.canteen_id).expect("list tags");
+    assert_eq!(tags.len(), 2);
+    assert_eq!((tags[0].tag.as_str(), tags[0].item_count), ("spicy", 2));
+    assert_eq!((tags[1].tag.as_str(), tags[1].item_count), ("hot", 1));
+
+    // Renaming onto a tag the item already has must not duplicate it.
+    let renamed = menu_ops
+        .rename_tag(fixtures.canteen_id, "hot", "Spicy")
+        .expect("rename tag");
+    assert_eq!(renamed, 1);
+    let item = menu_ops
+        .get_menu_item(fixtures.menu_item_ids[0])
+        .await
+        .expect("get item");
+    assert_eq!(item.tags, vec!["spicy"]);
+
+    let removed = menu_ops
+        .delete_tag(fixtures.canteen_id, "spicy")
+        .expect("delete tag");
+    assert_eq!(removed, 2);
+    assert!(menu_ops
+        .list_tags(fixtures.canteen_id)
+        .expect("list tags")
+        .is_empty());
+
+    let result = menu_ops.delete_tag(fixtures.canteen_id, "spicy");
+    assert!(matches!(result.unwrap_err(), RepositoryError::NotFound(_)));
+}
+
+fn transfer_row(name: &str, price: i32, category: Option<&str>) -> MenuTransferRow {
+    MenuTransferRow {
+        item_id: None,
+        name: name.to_string(),
+        is_veg: true,
+        price,
+        stock: 10,
+        is_available: true,
+        description: None,
+        default_stock: None,
+        category: category.map(str::to_string),
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
+    }
+}
+
+#[actix_rt::test]
+async fn import_menu_dry_run_reports_without_writing() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let mut sandwich = transfer_row("veg sandwich", 130, Some("Snacks"));
+    sandwich.description = Some("Simple veg sandwich".to_string());
+    let rows = vec![sandwich, transfer_row("Idli", 40, Some("snacks"))];
+
+    let (report, updates) = menu_ops
+        .import_menu(fixtures.canteen_id, rows, true)
+        .expect("dry run");
+    assert!(report.dry_run);
+    assert!(!report.applied);
+    assert!(updates.is_empty());
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(report.created_categories, vec!["Snacks".to_string()]);
+    assert_eq!(report.created.len(), 1);
+    assert_eq!(report.created[0].name, "Idli");
+    assert_eq!(report.updated.len(), 1);
+    assert_eq!(report.updated[0].item_id, Some(fixtures.menu_item_ids[0]));
+    assert_eq!(
+        report.updated[0].changed_fields,
+        vec![
+            "name".to_string(),
+            "price".to_string(),
+            "category".to_string()
+        ]
+    );
+
+    let exported = menu_ops.export_menu(fixtures.canteen_id).expect("export");
+    assert_eq!(exported.len(), 2);
+    assert_eq!(exported[0].price, 120);
+    assert!(menu_ops
+        .list_categories(fixtures.canteen_id)
+        .expect("categories")
+        .is_empty());
+}
+
+#[actix_rt::test]
+async fn import_menu_applies_and_export_roundtrips() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let mut wrap = transfer_row("Chicken Wrap", 200, Some("Mains"));
+    wrap.item_id = Some(fixtures.menu_item_ids[1]);
+    wrap.is_veg = false;
+    wrap.stock = 5;
+    wrap.description = Some("Spicy chicken wrap".to_string());
+    let mut idli = transfer_row("Idli", 40, Some("Breakfast"));
+    idli.tags = vec!["South-Indian".to_string()];
+    let rows = vec![wrap, idli];
+
+    let (report, updates) = menu_ops
+        .import_menu(fixtures.canteen_id, rows, false)
+        .expect("import");
+    assert!(report.applied);
+    assert_eq!(report.updated.len(), 1);
+    assert_eq!(report.updated[0].changed_fields, vec!["price", "category"]);
+    assert_eq!(report.created.len(), 1);
+    let idli_id = report.created[0].item_id.expect("created id");
+    assert_eq!(updates.len(), 2);
+
+    let exported = menu_ops.export_menu(fixtures.canteen_id).expect("export");
+    assert_eq!(exported.len(), 3);
+    assert_eq!(exported[1].price, 200);
+    assert_eq!(exported[1].category.as_deref(), Some("Mains"));
+    assert_eq!(exported[2].item_id, Some(idli_id));
+    assert_eq!(exported[2].tags, vec!["south-indian".to_string()]);
+    // Items not in the file are left alone.
+    assert_eq!(exported[0].name, "Veg Sandwich");
+    assert_eq!(exported[0].category, None);
+
+    // Re-importing the export changes nothing.
+    let (report, updates) = menu_ops
+        .import_menu(fixtures.canteen_id, exported, false)
+        .expect("re-import");
+    assert_eq!(report.unchanged, 3);
+    assert!(report.created.is_empty() && report.updated.is_empty());
+    assert!(report.created_categories.is_empty());
+    assert!(updates.is_empty());
+}
+
+#[actix_rt::test]
+async fn import_menu_with_invalid_rows_writes_nothing() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let menu_ops = MenuOperations::new(pool.clone(), asset_ops).await;
+
+    let mut vegan_chicken = transfer_row("Vegan Chicken", 90, None);
+    vegan_chicken.is_veg = false;
+    vegan_chicken.diet_labels = vec!["vegan".to_string()];
+    let mut foreign = transfer_row("Foreign", 50, None);
+    foreign.item_id = Some(i32::MAX);
+    let rows = vec![
+        transfer_row("Idli", 40, Some("Breakfast")),
+        transfer_row("Poha", -5, None),
+        vegan_chicken,
+        foreign,
+        transfer_row("idli", 45, None),
+    ];
+
+    let (report, updates) = menu_ops
+        .import_menu(fixtures.canteen_id, rows, false)
+        .expect("import");
+    assert!(!report.applied);
+    assert!(updates.is_empty());
+    let error_rows = report.errors.iter().map(|e| e.row).collect::<Vec<usize>>();
+    assert_eq!(error_rows, vec![2, 3, 4, 5]);
+
+    let exported = menu_ops.export_menu(fixtures.canteen_id).expect("export");
+    assert_eq!(exported.len(), 2);
+    assert!(menu_ops
+        .list_categories(fixtures.canteen_id)
+        .expect("categories")
+        .is_empty());
+}
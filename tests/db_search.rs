@@ -1,7 +1,8 @@
 mod common;
 
-use diesel::RunQueryDsl;
-use proj_xs::db::SearchOperations;
+use diesel::prelude::*;
+use proj_xs::db::{RepositoryError, SearchOperations};
+use proj_xs::enums::admin::{MenuFilterQuery, MenuSearchQuery};
 use proj_xs::test_utils::{insert_canteen, seed_menu_item};
 
 #[actix_rt::test]
@@ -11,7 +12,7 @@ async fn search_returns_matching_items() {
 
     // "Veg Sandwich" is seeded; use full name to ensure pg_trgm similarity match
     let results = search_ops
-        .search_menu_items("Veg Sandwich")
+        .search_menu_items("Veg Sandwich", &MenuFilterQuery::default(), None)
         .await
         .expect("search should succeed");
 
@@ -32,7 +33,7 @@ async fn search_no_match_returns_empty() {
     let search_ops = SearchOperations::new(pool.clone()).await;
 
     let results = search_ops
-        .search_menu_items("zzzznonexistent")
+        .search_menu_items("zzzznonexistent", &MenuFilterQuery::default(), None)
         .await
         .expect("search should succeed even with no results");
 
@@ -66,7 +67,12 @@ async fn search_by_canteen_filters_correctly() {
 
     // Search scoped to the original canteen
     let results = search_ops
-        .search_menu_items_by_canteen(&fixtures.canteen_id, "Veg Sandwich")
+        .search_menu_items_by_canteen(
+            &fixtures.canteen_id,
+            "Veg Sandwich",
+            &MenuFilterQuery::default(),
+            None,
+        )
         .await
         .expect("search should succeed");
 
@@ -83,6 +89,182 @@ async fn search_by_canteen_filters_correctly() {
     }
 }
 
+#[actix_rt::test]
+async fn search_filters_by_tag() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = proj_xs::db::DbConnection::new(&pool).expect("db connection");
+
+    use proj_xs::db::schema::menu_items::dsl::*;
+    diesel::update(menu_items.filter(item_id.eq(fixtures.menu_item_ids[0])))
+        .set(tags.eq(vec!["bestseller".to_string()]))
+        .execute(conn.connection())
+        .expect("tag item");
+
+    let search_ops = SearchOperations::new(pool.clone()).await;
+    let filter = MenuFilterQuery {
+        category_id: None,
+        tag: Some("BestSeller".to_string()),
+        hide_allergens: None,
+    };
+    let results = search_ops
+        .search_menu_items_by_canteen(&fixtures.canteen_id, "Veg Sandwich", &filter, None)
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].tags, vec!["bestseller"]);
+
+    let filter = MenuFilterQuery {
+        category_id: None,
+        tag: Some("spicy".to_string()),
+        hide_allergens: None,
+    };
+    let results = search_ops
+        .search_menu_items("Veg Sandwich", &filter, None)
+        .await
+        .expect("search should succeed");
+    assert!(results.is_empty());
+}
+
+#[actix_rt::test]
+async fn search_menu_matches_descriptions_and_counts_facets() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let mut conn = proj_xs::db::DbConnection::new(&pool).expect("db connection");
+    let east_canteen =
+        insert_canteen(conn.connection(), "East Canteen", "Block E").expect("insert canteen");
+    let paneer_wrap = seed_menu_item(
+        conn.connection(),
+        east_canteen,
+        "Paneer Wrap",
+        150,
+        10,
+        true,
+        true,
+        Some("Grilled paneer in a spicy wrap"),
+    )
+    .expect("seed paneer wrap");
+    {
+        use proj_xs::db::schema::canteens::dsl::*;
+        diesel::update(canteens.filter(canteen_id.eq(east_canteen)))
+            .set(is_open.eq(false))
+            .execute(conn.connection())
+            .expect("close canteen");
+    }
+
+    let search_ops = SearchOperations::new(pool.clone()).await;
+    let results = search_ops
+        .search_menu(
+            &MenuSearchQuery {
+                q: Some("spicy".to_string()),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.total, 2);
+    let mut ids: Vec<i32> = results.items.iter().map(|item| item.item_id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![fixtures.menu_item_ids[1], paneer_wrap]);
+    assert_eq!((results.facets.veg, results.facets.non_veg), (1, 1));
+    assert_eq!(results.facets.canteens.len(), 2);
+    assert_eq!(
+        (results.facets.min_price, results.facets.max_price),
+        (Some(150), Some(180))
+    );
+
+    // A facet ignores its own filter but honours the others.
+    let results = search_ops
+        .search_menu(
+            &MenuSearchQuery {
+                q: Some("spicy".to_string()),
+                veg: Some(true),
+                min_price: Some(100),
+                max_price: Some(160),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.total, 1);
+    assert_eq!(results.items[0].item_id, paneer_wrap);
+    assert_eq!((results.facets.veg, results.facets.non_veg), (1, 0));
+    assert_eq!(
+        (results.facets.min_price, results.facets.max_price),
+        (Some(150), Some(150))
+    );
+
+    let results = search_ops
+        .search_menu(
+            &MenuSearchQuery {
+                q: Some("wraps".to_string()),
+                open_only: Some(true),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.total, 1);
+    assert_eq!(results.items[0].item_id, fixtures.menu_item_ids[1]);
+    assert_eq!(results.facets.canteens.len(), 1);
+    assert_eq!(results.facets.canteens[0].canteen_id, fixtures.canteen_id);
+}
+
+#[actix_rt::test]
+async fn search_menu_pages_tolerates_typos_and_validates() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let search_ops = SearchOperations::new(pool.clone()).await;
+
+    let results = search_ops
+        .search_menu(
+            &MenuSearchQuery {
+                q: Some("Chiken Wrap".to_string()),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.items[0].item_id, fixtures.menu_item_ids[1]);
+
+    // Without a query text the filters alone pick the items, alphabetically.
+    let results = search_ops
+        .search_menu(
+            &MenuSearchQuery {
+                canteen_id: Some(fixtures.canteen_id),
+                page: Some(2),
+                per_page: Some(1),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .expect("search should succeed");
+    assert_eq!(results.total, 2);
+    assert_eq!((results.page, results.per_page), (2, 1));
+    assert_eq!(results.items.len(), 1);
+    assert_eq!(results.items[0].name, "Veg Sandwich");
+
+    for query in [
+        MenuSearchQuery {
+            page: Some(0),
+            ..Default::default()
+        },
+        MenuSearchQuery {
+            min_price: Some(200),
+            max_price: Some(100),
+            ..Default::default()
+        },
+    ] {
+        let err = search_ops
+            .search_menu(&query, None)
+            .await
+            .expect_err("invalid search");
+        assert!(matches!(err, RepositoryError::ValidationError(_)), "{err}");
+    }
+}
+
 #[test]
 fn pg_trgm_similarity_threshold_matches_migration() {
     // Migration installs pg_trgm with no custom threshold, so PostgreSQL default (0.3) applies.
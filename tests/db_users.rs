@@ -217,3 +217,39 @@ async fn get_past_orders_by_userid_populated() {
     assert_eq!(orders.len(), 1, "should have exactly one past order");
     assert!(orders[0].order_status, "order should be marked delivered");
 }
+
+#[actix_rt::test]
+async fn allergen_exclusions_set_and_get() {
+    let (pool, fixtures) = common::setup_pool_with_fixtures();
+    let asset_ops = AssetOperations::new().await.expect("AssetOperations::new");
+    let user_ops = UserOperations::new(pool.clone(), asset_ops).await;
+
+    assert!(user_ops
+        .get_allergen_exclusions(fixtures.user_id)
+        .expect("get exclusions")
+        .is_empty());
+
+    let saved = user_ops
+        .set_allergen_exclusions(
+            fixtures.user_id,
+            vec![
+                "Nuts".to_string(),
+                " dairy ".to_string(),
+                "nuts".to_string(),
+            ],
+        )
+        .expect("set exclusions");
+    assert_eq!(saved, vec!["nuts", "dairy"]);
+    assert_eq!(
+        user_ops
+            .get_allergen_exclusions(fixtures.user_id)
+            .expect("get exclusions"),
+        vec!["nuts", "dairy"]
+    );
+
+    let result = user_ops.set_allergen_exclusions(fixtures.user_id, vec!["chocolate".to_string()]);
+    assert!(matches!(result, Err(RepositoryError::ValidationError(_))));
+
+    let result = user_ops.set_allergen_exclusions(99999, vec!["egg".to_string()]);
+    assert!(matches!(result, Err(RepositoryError::NotFound(_))));
+}
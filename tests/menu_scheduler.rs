@@ -0,0 +1,65 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use proj_xs::models::admin::ScheduleWindow;
+use proj_xs::services::menu_scheduler::{is_within_schedule, stock_reset_due};
+
+fn at(date: (i32, u32, u32), hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn window(weekday: i16, start: (u32, u32), end: (u32, u32)) -> ScheduleWindow {
+    ScheduleWindow {
+        weekday,
+        start_time: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+        end_time: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+    }
+}
+
+// 2026-04-06 is a Monday.
+const MONDAY: (i32, u32, u32) = (2026, 4, 6);
+const TUESDAY: (i32, u32, u32) = (2026, 4, 7);
+
+#[test]
+fn schedule_window_matches_weekday_and_time() {
+    let breakfast = [window(0, (8, 0), (11, 0))];
+    assert!(is_within_schedule(&breakfast, at(MONDAY, 8, 0)));
+    assert!(is_within_schedule(&breakfast, at(MONDAY, 10, 59)));
+    assert!(!is_within_schedule(&breakfast, at(MONDAY, 11, 0)));
+    assert!(!is_within_schedule(&breakfast, at(MONDAY, 7, 59)));
+    assert!(!is_within_schedule(&breakfast, at(TUESDAY, 9, 0)));
+    assert!(!is_within_schedule(&[], at(MONDAY, 9, 0)));
+}
+
+#[test]
+fn schedule_window_past_midnight_spills_into_next_day() {
+    let late_night = [window(0, (22, 0), (2, 0))];
+    assert!(is_within_schedule(&late_night, at(MONDAY, 23, 30)));
+    assert!(is_within_schedule(&late_night, at(TUESDAY, 1, 59)));
+    assert!(!is_within_schedule(&late_night, at(TUESDAY, 2, 0)));
+    assert!(!is_within_schedule(&late_night, at(MONDAY, 1, 0)));
+
+    // Sunday night wraps around to Monday morning.
+    let sunday = [window(6, (23, 0), (1, 0))];
+    assert!(is_within_schedule(&sunday, at(MONDAY, 0, 30)));
+}
+
+#[test]
+fn stock_reset_due_after_opening_time_once_per_day() {
+    let opening = NaiveTime::from_hms_opt(8, 30, 0);
+    let monday = NaiveDate::from_ymd_opt(MONDAY.0, MONDAY.1, MONDAY.2).unwrap();
+    let sunday = monday.pred_opt().unwrap();
+
+    assert!(!stock_reset_due(opening, Some(sunday), at(MONDAY, 8, 29)));
+    assert!(stock_reset_due(opening, Some(sunday), at(MONDAY, 8, 30)));
+    assert!(stock_reset_due(opening, None, at(MONDAY, 12, 0)));
+    assert!(!stock_reset_due(opening, Some(monday), at(MONDAY, 12, 0)));
+}
+
+#[test]
+fn stock_reset_due_at_midnight_without_opening_time() {
+    let monday = NaiveDate::from_ymd_opt(MONDAY.0, MONDAY.1, MONDAY.2).unwrap();
+    assert!(stock_reset_due(None, monday.pred_opt(), at(MONDAY, 0, 0)));
+    assert!(!stock_reset_due(None, Some(monday), at(MONDAY, 0, 1)));
+}
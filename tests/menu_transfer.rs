@@ -0,0 +1,41 @@
+use proj_xs::services::menu_transfer::{parse_menu_csv, parse_menu_json, write_menu_csv};
+
+const HEADER: &str = "item_id,name,is_veg,price,stock,is_available,description,default_stock,category,display_order,is_pinned,tags,allergens,diet_labels";
+
+#[test]
+fn csv_roundtrip_preserves_rows() {
+    let csv = format!(
+        "{HEADER}\n7,Masala Dosa,true,70,20,true,\"Crispy, with chutney\",20,Breakfast,2,true,bestseller|south-indian,nuts,vegan\n,Tea,true,15,100,true,,,,,,,,\n"
+    );
+    let rows = parse_menu_csv(csv.as_bytes()).expect("parse");
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].item_id, Some(7));
+    assert_eq!(rows[0].description.as_deref(), Some("Crispy, with chutney"));
+    assert_eq!(rows[0].tags, vec!["bestseller", "south-indian"]);
+    assert_eq!(rows[1].item_id, None);
+    assert_eq!(rows[1].description, None);
+    assert_eq!(rows[1].category, None);
+    assert!(!rows[1].is_pinned);
+    assert!(rows[1].tags.is_empty());
+
+    let written = write_menu_csv(&rows).expect("write");
+    assert!(String::from_utf8(written.clone())
+        .unwrap()
+        .starts_with(HEADER));
+    assert_eq!(parse_menu_csv(&written).expect("reparse"), rows);
+}
+
+#[test]
+fn csv_parse_errors_name_the_row() {
+    let csv =
+        format!("{HEADER}\n,Tea,true,15,100,true,,,,,,,,\n,Coffee,maybe,20,100,true,,,,,,,,\n");
+    let err = parse_menu_csv(csv.as_bytes()).unwrap_err();
+    assert!(err.starts_with("row 2:"), "{err}");
+}
+
+#[test]
+fn json_rejects_unknown_fields() {
+    let json = br#"[{"name":"Tea","is_veg":true,"price":15,"stock":1,"is_available":true,
+        "description":null,"default_stock":null,"category":null,"colour":"red"}]"#;
+    assert!(parse_menu_json(json).is_err());
+}
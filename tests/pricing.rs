@@ -0,0 +1,146 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use proj_xs::enums::admin::PricingRuleDetails;
+use proj_xs::models::admin::{PricingRuleKindEnum, ScheduleWindow};
+use proj_xs::services::pricing::{price_order, PricingLine};
+
+// 2026-04-06 is a Monday.
+fn monday_at(hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 4, 6)
+        .unwrap()
+        .and_hms_opt(hour, 0, 0)
+        .unwrap()
+}
+
+fn rule(rule_id: i32, kind: PricingRuleKindEnum, item_ids: Vec<i32>) -> PricingRuleDetails {
+    PricingRuleDetails {
+        rule_id,
+        name: format!("rule {rule_id}"),
+        kind,
+        percent_off: None,
+        amount_off: None,
+        combo_price: None,
+        item_ids,
+        windows: Vec::new(),
+        is_active: true,
+    }
+}
+
+fn line(item_id: i32, unit_price: i32, quantity: i32) -> PricingLine {
+    PricingLine {
+        item_id,
+        unit_price,
+        quantity,
+    }
+}
+
+#[test]
+fn no_rules_charges_menu_price() {
+    let priced = price_order(&[line(1, 120, 2), line(2, 180, 1)], &[], monday_at(12));
+    assert_eq!(priced.total_price, 420);
+    assert_eq!(priced.unit_prices[&1], 120);
+    assert!(priced.applied_rules.is_empty());
+}
+
+#[test]
+fn best_single_discount_wins_per_item() {
+    let mut ten_percent = rule(1, PricingRuleKindEnum::PercentOff, Vec::new());
+    ten_percent.percent_off = Some(10);
+    let mut flat_thirty = rule(2, PricingRuleKindEnum::FlatOff, vec![2]);
+    flat_thirty.amount_off = Some(30);
+
+    let priced = price_order(
+        &[line(1, 120, 2), line(2, 180, 1)],
+        &[ten_percent, flat_thirty],
+        monday_at(12),
+    );
+    assert_eq!(priced.unit_prices[&1], 108);
+    assert_eq!(priced.unit_prices[&2], 150);
+    assert_eq!(priced.total_price, 2 * 108 + 150);
+    assert_eq!(priced.applied_rules.len(), 2);
+    assert_eq!(priced.applied_rules[0].discount, 24);
+    assert_eq!(priced.applied_rules[1].discount, 30);
+}
+
+#[test]
+fn happy_hour_only_applies_inside_its_window() {
+    let mut happy_hour = rule(1, PricingRuleKindEnum::PercentOff, Vec::new());
+    happy_hour.percent_off = Some(50);
+    happy_hour.windows = vec![ScheduleWindow {
+        weekday: 0,
+        start_time: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        end_time: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+    }];
+    let rules = [happy_hour];
+
+    assert_eq!(
+        price_order(&[line(1, 100, 1)], &rules, monday_at(17)).total_price,
+        50
+    );
+    assert_eq!(
+        price_order(&[line(1, 100, 1)], &rules, monday_at(12)).total_price,
+        100
+    );
+
+    let mut inactive = rules[0].clone();
+    inactive.is_active = false;
+    assert_eq!(
+        price_order(&[line(1, 100, 1)], &[inactive], monday_at(17)).total_price,
+        100
+    );
+}
+
+#[test]
+fn combo_takes_precedence_and_leftover_units_pay_menu_price() {
+    // Samosa (15) + chai (20) for 30.
+    let mut combo = rule(1, PricingRuleKindEnum::Combo, vec![1, 2]);
+    combo.combo_price = Some(30);
+    let mut percent = rule(2, PricingRuleKindEnum::PercentOff, vec![1, 2]);
+    percent.percent_off = Some(10);
+
+    let priced = price_order(
+        &[line(1, 15, 2), line(2, 20, 1)],
+        &[combo, percent],
+        monday_at(12),
+    );
+    // One combo (samosa 12 + chai 18), then one samosa at 10% off (14): 44 over 3 units.
+    assert_eq!(priced.unit_prices[&2], 18);
+    assert_eq!(priced.unit_prices[&1], 13);
+    assert_eq!(priced.total_price, 2 * 13 + 18);
+    assert_eq!(priced.applied_rules[0].rule_id, 1);
+    assert_eq!(priced.applied_rules[0].discount, 5);
+    assert_eq!(priced.applied_rules[1].discount, 1);
+}
+
+#[test]
+fn rule_discounts_add_up_to_the_saving_when_prices_round_down() {
+    let mut combo = rule(1, PricingRuleKindEnum::Combo, vec![1, 2]);
+    combo.combo_price = Some(30);
+
+    // Samosas come to 12 + 15 = 27, which averages to 13 each: rounding saves another 1.
+    let priced = price_order(&[line(1, 15, 2), line(2, 20, 1)], &[combo], monday_at(12));
+    assert_eq!(priced.total_price, 2 * 13 + 18);
+    assert_eq!(priced.applied_rules.len(), 1);
+    assert_eq!(priced.applied_rules[0].discount, 2 * 15 + 20 - 44);
+}
+
+#[test]
+fn combo_with_repeated_items_needs_every_unit() {
+    let mut two_samosas_and_chai = rule(1, PricingRuleKindEnum::Combo, vec![1, 1, 2]);
+    two_samosas_and_chai.combo_price = Some(40);
+
+    let partial = price_order(
+        &[line(1, 15, 1), line(2, 20, 1)],
+        std::slice::from_ref(&two_samosas_and_chai),
+        monday_at(12),
+    );
+    assert_eq!(partial.total_price, 35);
+    assert!(partial.applied_rules.is_empty());
+
+    let full = price_order(
+        &[line(1, 15, 4), line(2, 20, 2)],
+        &[two_samosas_and_chai],
+        monday_at(12),
+    );
+    assert_eq!(full.total_price, 80);
+    assert_eq!(full.applied_rules[0].discount, 20);
+}
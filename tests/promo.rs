@@ -0,0 +1,110 @@
+use chrono::{Duration, Utc};
+use proj_xs::auth::PlatformOperatorConfig;
+use proj_xs::models::admin::{sanitize_promo_code, NewPromoCode, PromoCode};
+
+fn promo(
+    percent_off: Option<i32>,
+    amount_off: Option<i32>,
+    max_discount: Option<i32>,
+) -> PromoCode {
+    PromoCode {
+        promo_id: 1,
+        code: "TEST".to_string(),
+        canteen_id: None,
+        description: None,
+        percent_off,
+        amount_off,
+        max_discount,
+        min_order_total: 0,
+        valid_from: None,
+        valid_until: None,
+        max_uses_total: None,
+        max_uses_per_user: None,
+        first_order_only: false,
+        is_active: true,
+        created_at: Utc::now(),
+    }
+}
+
+fn new_code(code: &str) -> NewPromoCode {
+    NewPromoCode {
+        code: code.to_string(),
+        canteen_id: Some(1),
+        description: None,
+        percent_off: Some(10),
+        amount_off: None,
+        max_discount: None,
+        min_order_total: 0,
+        valid_from: None,
+        valid_until: None,
+        max_uses_total: None,
+        max_uses_per_user: None,
+        first_order_only: false,
+        is_active: true,
+    }
+}
+
+#[test]
+fn percent_discount_rounds_down_and_respects_cap() {
+    assert_eq!(promo(Some(15), None, None).discount_for(250), 37);
+    assert_eq!(promo(Some(50), None, Some(60)).discount_for(300), 60);
+}
+
+#[test]
+fn flat_discount_never_exceeds_order_total() {
+    assert_eq!(promo(None, Some(50), None).discount_for(120), 50);
+    assert_eq!(promo(None, Some(500), None).discount_for(120), 120);
+}
+
+#[test]
+fn codes_are_normalized_and_checked() {
+    assert_eq!(sanitize_promo_code("  lunch-10 ").unwrap(), "LUNCH-10");
+    assert!(sanitize_promo_code("   ").is_err());
+    assert!(sanitize_promo_code("HALF OFF").is_err());
+    assert!(sanitize_promo_code(&"A".repeat(33)).is_err());
+}
+
+#[test]
+fn validation_rejects_inconsistent_codes() {
+    let both = NewPromoCode {
+        amount_off: Some(20),
+        ..new_code("BOTH")
+    };
+    assert!(both.sanitize_and_validate().is_err());
+
+    let capped_flat = NewPromoCode {
+        percent_off: None,
+        amount_off: Some(20),
+        max_discount: Some(10),
+        ..new_code("FLAT")
+    };
+    assert!(capped_flat.sanitize_and_validate().is_err());
+
+    let now = Utc::now();
+    let backwards = NewPromoCode {
+        valid_from: Some(now),
+        valid_until: Some(now - Duration::days(1)),
+        ..new_code("LATE")
+    };
+    assert!(backwards.sanitize_and_validate().is_err());
+
+    let zero_uses = NewPromoCode {
+        max_uses_per_user: Some(0),
+        ..new_code("ZERO")
+    };
+    assert!(zero_uses.sanitize_and_validate().is_err());
+
+    assert_eq!(
+        new_code("ok10").sanitize_and_validate().unwrap().code,
+        "OK10"
+    );
+}
+
+#[test]
+fn platform_operator_ids_skip_blank_and_invalid_entries() {
+    assert_eq!(
+        PlatformOperatorConfig::parse("1, 7,,x,12").user_ids,
+        vec![1, 7, 12]
+    );
+    assert!(PlatformOperatorConfig::parse("").user_ids.is_empty());
+}
@@ -16,6 +16,16 @@ fn pic_key_menu_item_format() {
         description: None,
         pic_etag: None,
         pic_key: Some("abc-uuid".to_string()),
+        default_stock: None,
+        category_id: None,
+        display_order: 0,
+        is_pinned: false,
+        tags: Vec::new(),
+        allergens: Vec::new(),
+        diet_labels: Vec::new(),
+        average_rating: None,
+        rating_count: 0,
+        manually_disabled: false,
     };
     assert_eq!(item.pic_key(), Some("items/abc-uuid".to_string()));
 }
@@ -31,6 +41,8 @@ fn pic_key_canteen_details_format() {
         opening_time: None,
         closing_time: None,
         is_open: true,
+        average_rating: None,
+        rating_count: 0,
     };
     assert_eq!(canteen.pic_key(), Some("canteens/canteen-uuid".to_string()));
 }
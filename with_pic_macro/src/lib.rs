@@ -23,17 +23,23 @@ pub fn derive_with_pic(input: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn with_pic(attr: TokenStream, item: TokenStream) -> TokenStream {
     let from_path = parse_macro_input!(attr as Path);
-    let item_struct = parse_macro_input!(item as ItemStruct);
-    let target_ident = &item_struct.ident;
+    let mut item_struct = parse_macro_input!(item as ItemStruct);
+    let target_ident = item_struct.ident.clone();
 
-    // Build field initializers by name; special-case `pic_link` to None
+    // Build field initializers by name; special-case `pic_link` to None and fields marked
+    // `#[with_pic(default)]` (which have no counterpart on the source) to their default
     let mut inits = Vec::new();
-    match &item_struct.fields {
+    match &mut item_struct.fields {
         Fields::Named(named) => {
-            for f in &named.named {
+            for f in named.named.iter_mut() {
+                let attrs_before = f.attrs.len();
+                f.attrs.retain(|a| !a.path().is_ident("with_pic"));
+                let use_default = f.attrs.len() != attrs_before;
                 let fname = f.ident.as_ref().expect("expected named field");
                 if fname == "pic_link" {
                     inits.push(quote! { #fname: None });
+                } else if use_default {
+                    inits.push(quote! { #fname: Default::default() });
                 } else {
                     inits.push(quote! { #fname: src.#fname.clone() });
                 }